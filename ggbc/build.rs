@@ -0,0 +1,59 @@
+//! Identifies the build of the compiler for the build cache.
+//!
+//! The sources of the compiler crates are hashed into the `GGBC_BUILD`
+//! environment variable, so that any change to the compiler invalidates the
+//! entries cached by previous builds of it, even if its version is the same.
+use std::{env, fs, io, path::Path};
+
+fn main() -> io::Result<()> {
+    let manifest = env::var("CARGO_MANIFEST_DIR").expect("Cargo sets CARGO_MANIFEST_DIR");
+    let manifest = Path::new(&manifest);
+    let mut hash = Fnv::default();
+    // the sources of the other crates are only available within the workspace
+    for dir in &["src", "../parser/src", "../ir/src"] {
+        let dir = manifest.join(dir);
+        if dir.is_dir() {
+            println!("cargo:rerun-if-changed={}", dir.display());
+            hash_dir(&dir, &dir, &mut hash)?;
+        }
+    }
+    println!("cargo:rustc-env=GGBC_BUILD={:016x}", hash.0);
+    Ok(())
+}
+
+// hashes the paths (relative to `root`) and contents of the files of a
+// directory, in a stable order.
+fn hash_dir(root: &Path, dir: &Path, hash: &mut Fnv) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            hash_dir(root, &path, hash)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            hash.write(relative.to_string_lossy().as_bytes());
+            hash.write(&fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+// FNV-1a, like the keys of the cache.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! On-disk incremental build cache.
+//!
+//! Compiled programs are stored in a cache directory, keyed by a hash of the
+//! source code, the build of the compiler and the compilation options, so that
+//! compiling an unchanged file skips parsing and IR compilation entirely. The
+//! build of the compiler is identified by the IR format version and a hash of
+//! the sources of the compiler, so entries written by other builds are never
+//! reused, even during development.
+//!
+//! Syntax trees are cached too, keyed by the source code alone, so compiling
+//! an unchanged file with other options skips parsing and checking. Only the
+//! programs that parse and check without errors are cached.
+//!
+//! Optimized routines are also cached individually (along with the spans of
+//! their instructions), keyed by their unoptimized instructions and spans.
+//! When a file changes, only the routines whose code changed need to go
+//! through the optimizer again.
+//!
+//! The cache is best-effort: a missing, corrupt or read-only cache directory
+//! is treated as a cache miss and never causes compilation to fail.
+//!
+//! # Example
+//! ```no_run
+//! use ggbc::{byteorder::LittleEndian, cache::Cache};
+//!
+//! let cache = Cache::new("target/ggbc").unwrap();
+//! let ir = cache.ir::<LittleEndian>("static FOO:u8", &Default::default()).unwrap();
+//! ```
+use crate::{
+    byteorder::ByteOrder,
+    ir::{binary, CompileError, Ir},
+    parser::{self, Ast},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// hash of the sources of the compiler (see build.rs)
+const BUILD: &str = env!("GGBC_BUILD");
+
+/// Compilation options that affect the cached output.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Options {
+    /// Run the IR optimizer.
+    pub optimize: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { optimize: true }
+    }
+}
+
+/// Errors compiling a program through the cache.
+///
+/// Programs that fail to compile are never cached.
+#[derive(Error, Debug)]
+pub enum Error<'a> {
+    #[error("Parsing error")]
+    Parser(parser::Error<'a>),
+
+    #[error("{0}")]
+    Compile(CompileError),
+}
+
+impl<'a> From<parser::Error<'a>> for Error<'a> {
+    fn from(error: parser::Error<'a>) -> Self {
+        Self::Parser(error)
+    }
+}
+
+impl From<CompileError> for Error<'_> {
+    fn from(error: CompileError) -> Self {
+        Self::Compile(error)
+    }
+}
+
+/// On-disk build cache.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Open (or create) a cache in the given directory.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        for sub in SUBDIRS {
+            fs::create_dir_all(dir.join(sub))?;
+        }
+        Ok(Self { dir })
+    }
+
+    /// Cache directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compile a program into IR, reusing cached results when possible.
+    pub fn ir<'a, B: ByteOrder>(
+        &self,
+        input: &'a str,
+        options: &Options,
+    ) -> Result<Ir<B>, Error<'a>> {
+        let key = Key::new::<B>(options).write(input.as_bytes()).finish();
+        let path = self.dir.join("ir").join(&key);
+        if let Some(ir) = load(&path) {
+            return Ok(ir);
+        }
+
+        // the cached tree borrows its text from the bytes of the entry
+        let ast_path = self
+            .dir
+            .join("ast")
+            .join(Key::build().write(input.as_bytes()).finish());
+        let bytes = fs::read(&ast_path).ok();
+        let cached: Option<Ast<'_>> = bytes
+            .as_deref()
+            .and_then(|bytes| bincode::deserialize(bytes).ok());
+        let ast = match cached {
+            Some(ast) => ast,
+            None => {
+                let ast = parser::parse(input)?;
+                if let Some(error) = parser::check::check(&ast).into_iter().next() {
+                    return Err(Error::Parser(error));
+                }
+                store(&ast_path, &ast);
+                ast
+            }
+        };
+        let mut ir = Ir::try_new(&ast)?;
+        if options.optimize {
+            for routine in ir.routines.iter_mut() {
+                let bytes = match bincode::serialize(&(&routine.statements, &routine.spans)) {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        routine.optimize();
+                        continue;
+                    }
+                };
+                let key = Key::new::<B>(options).write(&bytes).finish();
+                let path = self.dir.join("routine").join(&key);
+                match load(&path) {
+                    Some((statements, spans)) => {
+                        routine.statements = statements;
+                        routine.spans = spans;
+                    }
+                    None => {
+                        routine.optimize();
+                        store(&path, &(&routine.statements, &routine.spans));
+                    }
+                }
+            }
+        }
+        store(&path, &ir);
+        Ok(ir)
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) -> io::Result<()> {
+        for sub in SUBDIRS {
+            let dir = self.dir.join(sub);
+            fs::remove_dir_all(&dir)?;
+            fs::create_dir_all(&dir)?;
+        }
+        Ok(())
+    }
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let bytes = fs::read(path).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+fn store<T: Serialize>(path: &Path, value: &T) {
+    // write to a temporary file first so concurrent builds never observe a
+    // partially written entry.
+    if let Ok(bytes) = bincode::serialize(value) {
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        if fs::write(&tmp, bytes).is_ok() && fs::rename(&tmp, path).is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+}
+
+// directories of the cached syntax trees, programs and routines.
+const SUBDIRS: &[&str] = &["ast", "ir", "routine"];
+
+// FNV-1a hash of the cache key.
+// The std hasher is not used because its output is not stable across builds.
+struct Key(u64);
+
+impl Key {
+    // key of the entries of the current build of the compiler.
+    fn build() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+            .write(VERSION.as_bytes())
+            .write(&binary::VERSION.to_le_bytes())
+            .write(BUILD.as_bytes())
+    }
+
+    fn new<B: ByteOrder>(options: &Options) -> Self {
+        Self::build()
+            .write(std::any::type_name::<B>().as_bytes())
+            .write(&[options.optimize as u8])
+    }
+
+    fn write(mut self, bytes: &[u8]) -> Self {
+        // length prefix, so that adjacent fields can't be confused
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+        self
+    }
+
+    fn finish(self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cache, Error, Options};
+    use crate::{byteorder::NativeEndian, ir::Ir};
+    use std::fs;
+
+    fn cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("ggbc-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Cache::new(dir).unwrap()
+    }
+
+    fn entries(cache: &Cache, sub: &str) -> usize {
+        fs::read_dir(cache.dir().join(sub)).unwrap().count()
+    }
+
+    #[test]
+    fn hit() {
+        let cache = cache("hit");
+        let input = "static X:u8 fn foo { (= X 1) } (foo)";
+        let ir = cache
+            .ir::<NativeEndian>(input, &Options::default())
+            .unwrap();
+        assert_eq!(1, entries(&cache, "ir"));
+        assert_eq!(2, entries(&cache, "routine"));
+
+        let cached = cache
+            .ir::<NativeEndian>(input, &Options::default())
+            .unwrap();
+        assert_eq!(ir, cached);
+        assert_eq!(1, entries(&cache, "ir"));
+
+        let mut gt: Ir<NativeEndian> = Ir::new(&crate::parser::parse(input).unwrap());
+        gt.optimize();
+        assert_eq!(gt, cached);
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn ast() {
+        let cache = cache("ast");
+        let input = "static X:u8 fn foo { (= X 1) } (foo)";
+        cache
+            .ir::<NativeEndian>(input, &Options::default())
+            .unwrap();
+        assert_eq!(1, entries(&cache, "ast"));
+
+        // the program isn't cached with these options, but its syntax tree is
+        let options = Options { optimize: false };
+        let ir = cache.ir::<NativeEndian>(input, &options).unwrap();
+        assert_eq!(Ir::new(&crate::parser::parse(input).unwrap()), ir);
+        assert_eq!(1, entries(&cache, "ast"));
+        assert_eq!(2, entries(&cache, "ir"));
+
+        // programs with errors aren't
+        assert!(cache
+            .ir::<NativeEndian>("static X:u8 (= X", &options)
+            .is_err());
+        assert_eq!(1, entries(&cache, "ast"));
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn compile_error() {
+        let cache = cache("compile_error");
+        let options = Options::default();
+        for input in &["(= X 1)", "static X:u8 static X:u8"] {
+            match cache.ir::<NativeEndian>(input, &options) {
+                Err(Error::Compile(_)) => {}
+                other => panic!("expected a compile error, got {:?}", other),
+            }
+            // the failing program is never cached
+            assert!(cache.ir::<NativeEndian>(input, &options).is_err());
+        }
+        assert_eq!(0, entries(&cache, "ir"));
+        assert_eq!(0, entries(&cache, "routine"));
+
+        // the cache can still be used after the failures
+        let input = "static X:u8 (= X 1)";
+        let ir = cache.ir::<NativeEndian>(input, &options).unwrap();
+        assert_eq!(ir, cache.ir::<NativeEndian>(input, &options).unwrap());
+        assert_eq!(1, entries(&cache, "ir"));
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn options() {
+        let cache = cache("options");
+        let options = Options { optimize: false };
+        let input = "static X:u8 (= X 1)";
+        let ir = cache.ir::<NativeEndian>(input, &options).unwrap();
+        assert_eq!(Ir::new(&crate::parser::parse(input).unwrap()), ir);
+        assert_eq!(0, entries(&cache, "routine"));
+
+        cache
+            .ir::<NativeEndian>(input, &Options::default())
+            .unwrap();
+        assert_eq!(2, entries(&cache, "ir"));
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn corrupt() {
+        let cache = cache("corrupt");
+        let input = "static X:u8 (= X 1)";
+        let ir = cache
+            .ir::<NativeEndian>(input, &Options::default())
+            .unwrap();
+        for entry in fs::read_dir(cache.dir().join("ir")).unwrap() {
+            fs::write(entry.unwrap().path(), b"garbage").unwrap();
+        }
+        assert_eq!(
+            ir,
+            cache
+                .ir::<NativeEndian>(input, &Options::default())
+                .unwrap()
+        );
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+}
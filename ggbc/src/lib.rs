@@ -1,9 +1,38 @@
 //! Compiler for the `GGB` (Great Game Boy) programming language.
 //!
-//! Definition of the **intermediate representation** (IR) and compilation
-//! **targets**.
+//! Facade over the components of the `GGBC` (Great Game Boy Compiler)
+//! toolchain. Each component is re-exported behind a cargo feature, so tools
+//! can depend on just the pieces they need:
 //!
-//! This is part of the `GGBC` (Great Game Boy Compiler) toolchain.
+//! | Feature       | Components                                          |
+//! |---------------|-----------------------------------------------------|
+//! | (always)      | [`parser`]                                          |
+//! | `parser-only` | Nothing else (use with `default-features = false`)  |
+//! | `ir`          | `ir` (intermediate representation)                  |
+//! | `vm`          | `ir`, `vm` (IR virtual machine)                     |
+//! | `codegen`     | `ir`, `target` and `compile`                        |
+//! | `serde`       | AST and IR serialization (default)                  |
+//! | `cache`       | `ir`, `cache` (on-disk build cache, default)        |
+//! | `lsp`         | `lsp` (editor tooling APIs)                         |
+//!
+//! Tools that only need the parser can disable the default features. The
+//! `serde` feature only serializes the IR if the `ir` feature is enabled too,
+//! so it can be combined with `parser-only`:
+//!
+//! ```toml
+//! [dependencies]
+//! ggbc = { version = "0.1", default-features = false, features = ["parser-only"] }
+//! ```
+//!
+//! # Stability
+//!
+//! - `parser` and `ir`, as well as the `target`, `compile` and `cache` items,
+//!   follow semantic versioning.
+//! - `vm` is a testing tool for the IR. Its API may change along with the IR
+//!   instruction set.
+//! - Everything under `lsp` is unstable, and may change in minor releases.
+//! - Enabling a feature never changes the behavior of the items that are
+//!   available without it.
 
 #![warn(
     clippy::all,
@@ -24,26 +53,42 @@
     nonstandard_style
 )]
 
-pub use byteorder;
+#[cfg(feature = "ir")]
+pub use ir::{self, byteorder, Bytes};
 pub use parser;
+#[cfg(feature = "vm")]
+pub use vm;
+
+#[cfg(feature = "codegen")]
 use target::Target;
+#[cfg(feature = "codegen")]
 use thiserror::Error;
 
-pub mod ir;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(feature = "codegen")]
 pub mod target;
 
-pub type Bytes = Box<[u8]>;
-
 /// Compilation errors.
+#[cfg(feature = "codegen")]
 #[derive(Error, Debug)]
 pub enum Error<'a, T: Target> {
     #[error("Parsing error")]
     Parser(parser::Error<'a>),
 
+    #[error("{0}")]
+    Compile(ir::CompileError),
+
+    #[error("{0}")]
+    Recursion(ir::stack::Recursion),
+
     #[error("Codegen error")]
     Codegen(T::Error),
 }
 
+#[cfg(feature = "codegen")]
 impl<'a, T: Target> From<parser::Error<'a>> for Error<'a, T> {
     fn from(error: parser::Error<'a>) -> Self {
         Self::Parser(error)
@@ -59,9 +104,15 @@ impl<'a, T: Target> From<parser::Error<'a>> for Error<'a, T> {
 /// # #[cfg(well_actually_no)]
 /// let program = ggbc::compile::<LR35902>(include_str!("program.ggb")).unwrap();
 /// ```
+#[cfg(feature = "codegen")]
 pub fn compile<T: Target>(input: &str) -> Result<T::Output, Error<'_, T>> {
     let ast = parser::parse(input)?;
-    let mut ir = ir::Ir::new(&ast);
+    if let Some(error) = parser::check::check(&ast).into_iter().next() {
+        return Err(Error::Parser(error));
+    }
+    let mut ir = ir::Ir::try_new(&ast).map_err(Error::Compile)?;
+    // stack frames are allocated statically
+    ir.stack_report().map_err(Error::Recursion)?;
     ir.optimize();
     T::codegen(&ir).map_err(Error::Codegen)
 }
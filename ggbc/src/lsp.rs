@@ -0,0 +1,15 @@
+//! Parser APIs for editor tooling (language servers, syntax highlighters,
+//! linters).
+//!
+//! Unstable: the contents of this module may change in minor releases.
+pub use parser::{
+    ast::{ErrorNode, Statement},
+    error::{render, Diagnostic, Theme},
+    html,
+    incremental::Edit,
+    lex::{
+        span::{LineColumn, LineIndex, Span, Spanned},
+        SpannedTokens,
+    },
+    parse_recovering, reparse_with_context, Ast, ContextBuilder, Error, Tokens,
+};
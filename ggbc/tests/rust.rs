@@ -1,3 +1,5 @@
+#![cfg(feature = "codegen")]
+
 use ggbc::target::Rust;
 use std::process::{Command, Stdio};
 
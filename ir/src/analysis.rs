@@ -0,0 +1,267 @@
+//! Data-flow analyses of routines.
+//!
+//! The analyses run over the statements of a single routine, following its
+//! control-flow graph ([`Cfg`]). They track the values held by [`Slot`]s:
+//! registers, and bytes of the stack frame of the routine at a fixed offset.
+//!
+//! - [`Liveness`]: the slots whose value may be read before it is written
+//!   again, before and after each statement.
+//! - [`ReachingDefinitions`]: the statements whose writes may reach each
+//!   statement without being overwritten.
+//!
+//! ```
+//! use ir::{analysis::{Liveness, ReachingDefinitions, Slot}, byteorder::NativeEndian, Ir};
+//!
+//! let text = "routine 0 - stack 1 args 0 return 0
+//!     ld 1 -> r0
+//!     ld r0 -> [stack 0]
+//!     ld [stack 0] -> [static 0]
+//!     stop success
+//! end";
+//! let ir: Ir<NativeEndian> = ir::parse_text(text).unwrap();
+//! let statements = &ir.main().statements;
+//!
+//! let liveness = Liveness::new(statements);
+//! assert!(liveness.live_out(0).contains(&Slot::Register(0)));
+//! assert!(liveness.live_out(1).contains(&Slot::Stack(0)));
+//! assert!(liveness.live_out(2).is_empty());
+//!
+//! let definitions = ReachingDefinitions::new(statements);
+//! assert_eq!(vec![1], definitions.definitions(2, Slot::Stack(0)).collect::<Vec<_>>());
+//! ```
+//!
+//! Reads and writes whose address isn't known at compile time (through
+//! pointers, with a dynamic offset, and by calls and memory copies) may access
+//! any byte of the stack frame: they read all of them, and write all of them
+//! without overwriting the values they held. Tail jumps to other routines read
+//! all of them. The stack frame is made of the bytes accessed at a fixed offset
+//! by any statement of the routine.
+use crate::{
+    opcodes::{Location, Register, Statement},
+    opt,
+};
+use std::collections::BTreeSet;
+
+/// Register or byte of the stack frame.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Slot {
+    /// 8bit register.
+    Register(Register),
+
+    /// 16bit register.
+    RegisterW(Register),
+
+    /// Byte of the stack frame of the routine, at the given offset.
+    Stack(u16),
+}
+
+/// Slots read and written by a statement.
+#[derive(Debug, Default)]
+pub(crate) struct Access {
+    pub(crate) reads: Vec<Slot>,
+    pub(crate) writes: Vec<Slot>,
+    // the statement may read any byte of the stack frame
+    pub(crate) reads_frame: bool,
+    // the statement may write any byte of the stack frame
+    pub(crate) writes_frame: bool,
+}
+
+/// Control-flow graph of a routine, from statement to statement.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cfg {
+    successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    /// Build the graph of the statements of a routine.
+    pub fn new(statements: &[Statement]) -> Self {
+        use Statement::{Jmp, Ret, Stop};
+        let successors: Vec<Vec<usize>> = statements
+            .iter()
+            .enumerate()
+            .map(|(index, statement)| {
+                let mut successors = match statement {
+                    // conditional jumps and jump tables may not jump
+                    Jmp {
+                        location: Location::Table { .. },
+                    } => vec![index + 1],
+                    // other jumps only reach their targets (none for tail jumps,
+                    // which leave the routine like returns)
+                    Jmp { .. } | Ret | Stop(_) => vec![],
+                    _ => vec![index + 1],
+                };
+                successors.extend(opt::targets(index, statement));
+                successors.sort_unstable();
+                successors.dedup();
+                successors
+                    .into_iter()
+                    .filter(|s| *s < statements.len())
+                    .collect()
+            })
+            .collect();
+        let mut predecessors = vec![Vec::new(); statements.len()];
+        for (index, successors) in successors.iter().enumerate() {
+            for successor in successors {
+                predecessors[*successor].push(index);
+            }
+        }
+        Self {
+            successors,
+            predecessors,
+        }
+    }
+
+    /// Number of statements of the graph.
+    pub fn len(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Returns true if the routine has no statements.
+    pub fn is_empty(&self) -> bool {
+        self.successors.is_empty()
+    }
+
+    /// Indices of the statements that may run after the given one.
+    pub fn successors(&self, index: usize) -> &[usize] {
+        &self.successors[index]
+    }
+
+    /// Indices of the statements that may run before the given one.
+    pub fn predecessors(&self, index: usize) -> &[usize] {
+        &self.predecessors[index]
+    }
+}
+
+/// Slots alive before and after each statement of a routine: their value may
+/// be read by a statement that runs afterwards, before it is overwritten.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Liveness {
+    live_in: Vec<BTreeSet<Slot>>,
+    live_out: Vec<BTreeSet<Slot>>,
+}
+
+impl Liveness {
+    /// Compute the live slots of the statements of a routine.
+    pub fn new(statements: &[Statement]) -> Self {
+        let cfg = Cfg::new(statements);
+        let (accesses, frame) = accesses(statements);
+        let mut live_in = vec![BTreeSet::new(); statements.len()];
+        let mut live_out = vec![BTreeSet::new(); statements.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, access) in accesses.iter().enumerate().rev() {
+                let out: BTreeSet<Slot> = cfg
+                    .successors(index)
+                    .iter()
+                    .flat_map(|s| live_in[*s].iter().copied())
+                    .collect();
+                let mut in_: BTreeSet<Slot> = access.reads.iter().copied().collect();
+                if access.reads_frame {
+                    in_.extend(frame.iter().copied());
+                }
+                in_.extend(out.iter().filter(|slot| !access.writes.contains(slot)));
+                if in_ != live_in[index] {
+                    live_in[index] = in_;
+                    changed = true;
+                }
+                live_out[index] = out;
+            }
+        }
+        Self { live_in, live_out }
+    }
+
+    /// Slots alive before the statement at the given index.
+    pub fn live_in(&self, index: usize) -> &BTreeSet<Slot> {
+        &self.live_in[index]
+    }
+
+    /// Slots alive after the statement at the given index.
+    pub fn live_out(&self, index: usize) -> &BTreeSet<Slot> {
+        &self.live_out[index]
+    }
+}
+
+/// Write of a slot by a statement.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Definition {
+    /// Index of the statement.
+    pub statement: usize,
+
+    /// Slot written by the statement.
+    pub slot: Slot,
+}
+
+/// Definitions that reach each statement of a routine: there is a path from
+/// the statement of the definition to it along which the slot isn't
+/// overwritten.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReachingDefinitions {
+    reaching: Vec<BTreeSet<Definition>>,
+}
+
+impl ReachingDefinitions {
+    /// Compute the reaching definitions of the statements of a routine.
+    pub fn new(statements: &[Statement]) -> Self {
+        let cfg = Cfg::new(statements);
+        let (accesses, frame) = accesses(statements);
+        let mut reaching = vec![BTreeSet::new(); statements.len()];
+        let mut out = vec![BTreeSet::new(); statements.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, access) in accesses.iter().enumerate() {
+                let in_: BTreeSet<Definition> = cfg
+                    .predecessors(index)
+                    .iter()
+                    .flat_map(|p| out[*p].iter().copied())
+                    .collect();
+                let mut out_: BTreeSet<Definition> = in_
+                    .iter()
+                    .filter(|definition| !access.writes.contains(&definition.slot))
+                    .copied()
+                    .collect();
+                let written = access.writes.iter().copied();
+                let clobbered = frame.iter().copied().filter(|_| access.writes_frame);
+                out_.extend(written.chain(clobbered).map(|slot| Definition {
+                    statement: index,
+                    slot,
+                }));
+                reaching[index] = in_;
+                if out_ != out[index] {
+                    out[index] = out_;
+                    changed = true;
+                }
+            }
+        }
+        Self { reaching }
+    }
+
+    /// Definitions that reach the statement at the given index (before it
+    /// runs).
+    pub fn reaching(&self, index: usize) -> &BTreeSet<Definition> {
+        &self.reaching[index]
+    }
+
+    /// Indices of the statements whose write of the slot reaches the statement
+    /// at the given index.
+    pub fn definitions(&self, index: usize, slot: Slot) -> impl Iterator<Item = usize> + '_ {
+        self.reaching[index]
+            .iter()
+            .filter(move |definition| definition.slot == slot)
+            .map(|definition| definition.statement)
+    }
+}
+
+// slots accessed by each statement, and the bytes of the stack frame.
+fn accesses(statements: &[Statement]) -> (Vec<Access>, Vec<Slot>) {
+    let accesses: Vec<_> = statements.iter().map(opt::access).collect();
+    let frame: BTreeSet<Slot> = accesses
+        .iter()
+        .flat_map(|access| access.reads.iter().chain(&access.writes))
+        .filter(|slot| matches!(slot, Slot::Stack(_)))
+        .copied()
+        .collect();
+    (accesses, frame.into_iter().collect())
+}
@@ -0,0 +1,782 @@
+//! Binary format of the IR.
+//!
+//! [`Ir::to_bytes`] encodes a program so it can be cached, or loaded by a
+//! backend without compiling its source again ([`Ir::from_bytes`]).
+//!
+//! The data starts with a header: the magic bytes `GGIR`, the byte order mark
+//! `0xfeff`, and the [`VERSION`] of the format. Integers are encoded with the
+//! byte ordering `B` of the program, so loading a program with the other
+//! ordering fails with [`DecodeError::ByteOrder`], instead of reading swapped
+//! values.
+//!
+//! Lists and strings are prefixed by their length (32bit), optional values by
+//! a byte (`0` for `None`), and enums by a byte with the index of their
+//! variant.
+use crate::{
+    opcodes::{Destination, Location, Pointer, Source, Statement, StopStatus},
+    parser::lex::span::{SourceId, Span},
+    Handlers, Inline, Ir, Overflow, Region, Routine, Space,
+};
+use byteorder::{ByteOrder, WriteBytesExt};
+use std::{convert::TryFrom, fmt, marker::PhantomData, ops::Range};
+
+/// Magic bytes at the start of the data.
+pub const MAGIC: &[u8; 4] = b"GGIR";
+
+/// Version of the binary format.
+///
+/// Data encoded with other versions can't be decoded.
+pub const VERSION: u16 = 3;
+
+// byte order mark, read swapped if the data was encoded with the other byte
+// order.
+const BYTE_ORDER_MARK: u16 = 0xfeff;
+
+/// Error decoding a program in the binary format.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The data doesn't start with the [`MAGIC`] bytes.
+    Magic,
+
+    /// The data was encoded with the other byte order.
+    ByteOrder,
+
+    /// The data was encoded with another [`VERSION`] of the format.
+    Version(u16),
+
+    /// The data ends before the end of the program.
+    Eof,
+
+    /// Invalid variant of an enum.
+    Tag {
+        /// Name of the enum.
+        what: &'static str,
+
+        /// Index of the variant.
+        tag: u8,
+    },
+
+    /// String that isn't valid UTF-8.
+    Utf8,
+
+    /// Handler of the program that refers to a routine that doesn't exist.
+    Handler(usize),
+
+    /// The data continues past the end of the program.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Magic => write!(f, "Not an IR binary"),
+            Self::ByteOrder => write!(f, "IR binary encoded with the other byte order"),
+            Self::Version(version) => write!(
+                f,
+                "Unsupported IR binary version {} (expected {})",
+                version, VERSION
+            ),
+            Self::Eof => write!(f, "Unexpected end of IR binary"),
+            Self::Tag { what, tag } => write!(f, "Invalid {} tag {}", what, tag),
+            Self::Utf8 => write!(f, "Invalid UTF-8 string"),
+            Self::Handler(routine) => write!(f, "Undefined handler routine {}", routine),
+            Self::TrailingBytes => write!(f, "Unexpected data past the end of IR binary"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl<B: ByteOrder> Ir<B> {
+    /// Encode the program in the binary format.
+    ///
+    /// ```
+    /// use ir::{byteorder::NativeEndian, Ir};
+    ///
+    /// let ast = ir::parser::parse("static A:u8\n(= A 42)").unwrap();
+    /// let ir: Ir<NativeEndian> = Ir::new(&ast);
+    ///
+    /// let bytes = ir.to_bytes();
+    /// assert_eq!(b"GGIR", &bytes[..4]);
+    /// assert_eq!(ir, Ir::from_bytes(&bytes).unwrap());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::<B> {
+            bytes: MAGIC.to_vec(),
+            _phantom: PhantomData,
+        };
+        writer.put(&BYTE_ORDER_MARK);
+        writer.put(&VERSION);
+        writer.list(&self.const_);
+        writer.list(&self.static_);
+        writer.put(&self.static_alloc);
+        writer.put(&self.banks.len());
+        for (bank, size) in &self.banks {
+            writer.put(bank);
+            writer.put(size);
+        }
+        writer.list(&self.regions);
+        writer.list(&self.volatile);
+        writer.list(&self.routines);
+        writer.put(&self.handlers);
+        writer.put(&self.overflow);
+        writer.bytes
+    }
+
+    /// Decode a program encoded with [`to_bytes`](Self::to_bytes).
+    ///
+    /// Fails if the data is malformed, was encoded with another version of the
+    /// format or byte order, or if a handler refers to a routine that doesn't
+    /// exist.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(DecodeError::Magic);
+        }
+        let mut reader = Reader::<B> {
+            bytes: &bytes[MAGIC.len()..],
+            _phantom: PhantomData,
+        };
+        match reader.get::<u16>()? {
+            BYTE_ORDER_MARK => {}
+            mark if mark == BYTE_ORDER_MARK.swap_bytes() => return Err(DecodeError::ByteOrder),
+            _ => return Err(DecodeError::Magic),
+        }
+        match reader.get()? {
+            VERSION => {}
+            version => return Err(DecodeError::Version(version)),
+        }
+
+        let ir = Self {
+            const_: reader.get::<Vec<u8>>()?.into_boxed_slice(),
+            static_: reader.get::<Vec<u8>>()?.into_boxed_slice(),
+            static_alloc: reader.get()?,
+            banks: reader.get::<Vec<_>>()?.into_iter().collect(),
+            regions: reader.get::<Vec<_>>()?.into_boxed_slice(),
+            volatile: reader.get::<Vec<_>>()?.into_boxed_slice(),
+            routines: reader.get::<Vec<_>>()?.into_boxed_slice(),
+            handlers: reader.get()?,
+            overflow: reader.get()?,
+            _phantom: PhantomData,
+        };
+        if !reader.bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let handlers = &ir.handlers;
+        let handled = Some(handlers.main)
+            .into_iter()
+            .chain(handlers.vblank)
+            .chain(handlers.lcd_stat)
+            .chain(handlers.timer)
+            .chain(handlers.serial)
+            .chain(handlers.joypad)
+            .find(|routine| *routine >= ir.routines.len());
+        match handled {
+            Some(routine) => Err(DecodeError::Handler(routine)),
+            None => Ok(ir),
+        }
+    }
+}
+
+struct Writer<B> {
+    bytes: Vec<u8>,
+    _phantom: PhantomData<B>,
+}
+
+impl<B: ByteOrder> Writer<B> {
+    fn put<T: Encode>(&mut self, value: &T) {
+        value.encode(self)
+    }
+
+    // values prefixed by their length.
+    fn list<T: Encode>(&mut self, values: &[T]) {
+        self.put(&values.len());
+        for value in values {
+            self.put(value);
+        }
+    }
+
+    // index of the variant of an enum.
+    fn tag(&mut self, tag: u8) {
+        self.bytes.push(tag);
+    }
+}
+
+struct Reader<'a, B> {
+    bytes: &'a [u8],
+    _phantom: PhantomData<B>,
+}
+
+impl<'a, B: ByteOrder> Reader<'a, B> {
+    fn get<T: Encode>(&mut self) -> Result<T, DecodeError> {
+        T::decode(self)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::Eof);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    // index of the variant of an enum.
+    fn tag(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+// values that can be encoded in the binary format.
+trait Encode: Sized {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>);
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError>;
+}
+
+impl Encode for u8 {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.bytes.push(*self);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(reader.take(1)?[0])
+    }
+}
+
+impl Encode for i8 {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.bytes.write_i8(*self).unwrap();
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(reader.take(1)?[0] as Self)
+    }
+}
+
+impl Encode for u16 {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.bytes.write_u16::<B>(*self).unwrap();
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(B::read_u16(reader.take(2)?))
+    }
+}
+
+impl Encode for u32 {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.bytes.write_u32::<B>(*self).unwrap();
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(B::read_u32(reader.take(4)?))
+    }
+}
+
+// indices and lengths are encoded as 32bit integers.
+impl Encode for usize {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        let value = u32::try_from(*self).expect("Value doesn't fit in 32 bits");
+        writer.put(&value);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(reader.get::<u32>()? as Self)
+    }
+}
+
+impl Encode for String {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.len());
+        writer.bytes.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        let len = reader.get()?;
+        let bytes = reader.take(len)?;
+        Self::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Utf8)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        match self {
+            None => writer.tag(0),
+            Some(value) => {
+                writer.tag(1);
+                writer.put(value);
+            }
+        }
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        match reader.tag()? {
+            0 => Ok(None),
+            1 => Ok(Some(reader.get()?)),
+            tag => Err(DecodeError::Tag {
+                what: "option",
+                tag,
+            }),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Box<T> {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&**self);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(Self::new(reader.get()?))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.list(self);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        let len: usize = reader.get()?;
+        // the length isn't trusted to preallocate
+        let mut values = Self::with_capacity(len.min(reader.bytes.len()));
+        for _ in 0..len {
+            values.push(reader.get()?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Encode, U: Encode> Encode for (T, U) {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.0);
+        writer.put(&self.1);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok((reader.get()?, reader.get()?))
+    }
+}
+
+impl<T: Encode> Encode for Range<T> {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.start);
+        writer.put(&self.end);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(reader.get()?..reader.get()?)
+    }
+}
+
+impl Encode for Region {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.name);
+        writer.put(&self.range);
+        writer.put(&self.space);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            name: reader.get()?,
+            range: reader.get()?,
+            space: reader.get()?,
+        })
+    }
+}
+
+impl Encode for Routine {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.debug_name);
+        writer.put(&self.stack_size);
+        writer.put(&self.args_size);
+        writer.put(&self.return_size);
+        writer.put(&self.bank);
+        writer.put(&self.inline);
+        writer.put(&self.statements);
+        writer.put(&self.spans);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            debug_name: reader.get()?,
+            stack_size: reader.get()?,
+            args_size: reader.get()?,
+            return_size: reader.get()?,
+            bank: reader.get()?,
+            inline: reader.get()?,
+            statements: reader.get()?,
+            spans: reader.get()?,
+        })
+    }
+}
+
+impl Encode for Span {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&(self.min[0], self.min[1]));
+        writer.put(&(self.max[0], self.max[1]));
+        writer.put(&self.source.index());
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        let (min, max): ((usize, usize), (usize, usize)) = (reader.get()?, reader.get()?);
+        Ok(Self {
+            min: [min.0, min.1],
+            max: [max.0, max.1],
+            source: SourceId::new(reader.get()?),
+        })
+    }
+}
+
+impl Encode for Handlers {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        writer.put(&self.main);
+        writer.put(&self.vblank);
+        writer.put(&self.lcd_stat);
+        writer.put(&self.timer);
+        writer.put(&self.serial);
+        writer.put(&self.joypad);
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            main: reader.get()?,
+            vblank: reader.get()?,
+            lcd_stat: reader.get()?,
+            timer: reader.get()?,
+            serial: reader.get()?,
+            joypad: reader.get()?,
+        })
+    }
+}
+
+// Enums without fields, and the tags of their variants.
+macro_rules! tags {
+    ($($enum:ident $what:literal { $($variant:ident $tag:literal,)* })*) => {$(
+        impl Encode for $enum {
+            fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+                match self {
+                    $(Self::$variant => writer.tag($tag),)*
+                }
+            }
+
+            fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+                match reader.tag()? {
+                    $($tag => Ok(Self::$variant),)*
+                    tag => Err(DecodeError::Tag { what: $what, tag }),
+                }
+            }
+        }
+    )*};
+}
+
+tags! {
+    Overflow "overflow" {
+        Wrapping 0,
+        Saturating 1,
+        Trapping 2,
+    }
+    Inline "inline" {
+        Auto 0,
+        Always 1,
+        Never 2,
+    }
+    Space "space" {
+        Static 0,
+        Const 1,
+        Absolute 2,
+    }
+    StopStatus "stop status" {
+        Error 0,
+        Success 1,
+    }
+}
+
+impl Encode for Pointer {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        match self {
+            Self::Absolute(address) => {
+                writer.tag(0);
+                writer.put(address);
+            }
+            Self::Static(address) => {
+                writer.tag(1);
+                writer.put(address);
+            }
+            Self::Const(address) => {
+                writer.tag(2);
+                writer.put(address);
+            }
+            Self::Stack(address) => {
+                writer.tag(3);
+                writer.put(address);
+            }
+            Self::Return(address) => {
+                writer.tag(4);
+                writer.put(address);
+            }
+            Self::Banked(bank, address) => {
+                writer.tag(5);
+                writer.put(bank);
+                writer.put(address);
+            }
+        }
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        match reader.tag()? {
+            0 => Ok(Self::Absolute(reader.get()?)),
+            1 => Ok(Self::Static(reader.get()?)),
+            2 => Ok(Self::Const(reader.get()?)),
+            3 => Ok(Self::Stack(reader.get()?)),
+            4 => Ok(Self::Return(reader.get()?)),
+            5 => Ok(Self::Banked(reader.get()?, reader.get()?)),
+            tag => Err(DecodeError::Tag {
+                what: "pointer",
+                tag,
+            }),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Source<T> {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        match self {
+            Self::Pointer { base, offset } => {
+                writer.tag(0);
+                writer.put(base);
+                writer.put(offset);
+            }
+            Self::Indirect(source) => {
+                writer.tag(1);
+                writer.put(source);
+            }
+            Self::Register(register) => {
+                writer.tag(2);
+                writer.put(register);
+            }
+            Self::Literal(literal) => {
+                writer.tag(3);
+                writer.put(literal);
+            }
+        }
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        match reader.tag()? {
+            0 => Ok(Self::Pointer {
+                base: reader.get()?,
+                offset: reader.get()?,
+            }),
+            1 => Ok(Self::Indirect(reader.get()?)),
+            2 => Ok(Self::Register(reader.get()?)),
+            3 => Ok(Self::Literal(reader.get()?)),
+            tag => Err(DecodeError::Tag {
+                what: "source",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Destination {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        match self {
+            Self::Pointer { base, offset } => {
+                writer.tag(0);
+                writer.put(base);
+                writer.put(offset);
+            }
+            Self::Indirect(source) => {
+                writer.tag(1);
+                writer.put(source);
+            }
+            Self::Register(register) => {
+                writer.tag(2);
+                writer.put(register);
+            }
+        }
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        match reader.tag()? {
+            0 => Ok(Self::Pointer {
+                base: reader.get()?,
+                offset: reader.get()?,
+            }),
+            1 => Ok(Self::Indirect(reader.get()?)),
+            2 => Ok(Self::Register(reader.get()?)),
+            tag => Err(DecodeError::Tag {
+                what: "destination",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Location {
+    fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+        match self {
+            Self::Relative(offset) => {
+                writer.tag(0);
+                writer.put(offset);
+            }
+            Self::Table { index, targets } => {
+                writer.tag(1);
+                writer.put(index);
+                writer.put(targets);
+            }
+            Self::Absolute(index) => {
+                writer.tag(2);
+                writer.put(index);
+            }
+            Self::Routine(routine) => {
+                writer.tag(3);
+                writer.put(routine);
+            }
+        }
+    }
+
+    fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+        match reader.tag()? {
+            0 => Ok(Self::Relative(reader.get()?)),
+            1 => Ok(Self::Table {
+                index: reader.get()?,
+                targets: reader.get()?,
+            }),
+            2 => Ok(Self::Absolute(reader.get()?)),
+            3 => Ok(Self::Routine(reader.get()?)),
+            tag => Err(DecodeError::Tag {
+                what: "location",
+                tag,
+            }),
+        }
+    }
+}
+
+// Statements, and the tags of their variants. Fields are encoded in the
+// given order.
+macro_rules! statements {
+    ($($variant:ident $tag:literal { $($field:ident)* })*) => {
+        impl Encode for Statement {
+            fn encode<B: ByteOrder>(&self, writer: &mut Writer<B>) {
+                match self {
+                    Self::Nop(n) => {
+                        writer.tag(0);
+                        writer.put(n);
+                    }
+                    Self::Stop(status) => {
+                        writer.tag(1);
+                        writer.put(status);
+                    }
+                    Self::Call { routine, range } => {
+                        writer.tag(41);
+                        writer.put(routine);
+                        writer.put(&range.start);
+                    }
+                    Self::CallIndirect { routine, range } => {
+                        writer.tag(42);
+                        writer.put(routine);
+                        writer.put(&range.start);
+                    }
+                    $(Self::$variant { $($field),* } => {
+                        writer.tag($tag);
+                        $(writer.put($field);)*
+                    })*
+                }
+            }
+
+            fn decode<B: ByteOrder>(reader: &mut Reader<'_, B>) -> Result<Self, DecodeError> {
+                match reader.tag()? {
+                    0 => Ok(Self::Nop(reader.get()?)),
+                    1 => Ok(Self::Stop(reader.get()?)),
+                    41 => Ok(Self::Call {
+                        routine: reader.get()?,
+                        range: reader.get::<u16>()?..,
+                    }),
+                    42 => Ok(Self::CallIndirect {
+                        routine: reader.get()?,
+                        range: reader.get::<u16>()?..,
+                    }),
+                    $($tag => Ok(Self::$variant { $($field: reader.get()?),* }),)*
+                    tag => Err(DecodeError::Tag { what: "statement", tag }),
+                }
+            }
+        }
+    };
+}
+
+statements! {
+    Ld 2 { source destination }
+    LdW 3 { source destination }
+    LdAddr 4 { source destination }
+    Ext 5 { source destination }
+    SignExt 6 { source destination }
+    Trunc 7 { source destination }
+    Inc 8 { source destination }
+    Dec 9 { source destination }
+    IncW 10 { source destination }
+    DecW 11 { source destination }
+    Add 12 { left right destination }
+    Sub 13 { left right destination }
+    And 14 { left right destination }
+    Xor 15 { left right destination }
+    Or 16 { left right destination }
+    LeftShift 17 { left right destination }
+    RightShift 18 { left right destination }
+    Mul 19 { left right destination }
+    Div 20 { left right destination }
+    Rem 21 { left right destination }
+    AddW 22 { left right destination }
+    SubW 23 { left right destination }
+    AndW 24 { left right destination }
+    XorW 25 { left right destination }
+    OrW 26 { left right destination }
+    LeftShiftW 27 { left right destination }
+    RightShiftW 28 { left right destination }
+    MulW 29 { left right destination }
+    DivW 30 { left right destination }
+    RemW 31 { left right destination }
+    Eq 32 { left right destination }
+    NotEq 33 { left right destination }
+    Greater 34 { left right destination }
+    GreaterEq 35 { left right destination }
+    Less 36 { left right destination }
+    LessEq 37 { left right destination }
+    Jmp 38 { location }
+    JmpCmp 39 { location source }
+    JmpCmpNot 40 { location source }
+    Ret 43 {}
+    PushBank 44 { bank }
+    PopBank 45 {}
+    Memcpy 46 { source destination len }
+    Memset 47 { value destination len }
+    SwapNibbles 48 { source destination }
+    BcdAdjust 49 { source destination }
+    Halt 50 {}
+    EnableInterrupts 51 {}
+    RightShiftS 52 { left right destination }
+    DivS 53 { left right destination }
+    RemS 54 { left right destination }
+    RightShiftSW 55 { left right destination }
+    DivSW 56 { left right destination }
+    RemSW 57 { left right destination }
+    GreaterS 58 { left right destination }
+    GreaterEqS 59 { left right destination }
+    LessS 60 { left right destination }
+    LessEqS 61 { left right destination }
+    EqW 62 { left right destination }
+    NotEqW 63 { left right destination }
+    GreaterW 64 { left right destination }
+    GreaterEqW 65 { left right destination }
+    LessW 66 { left right destination }
+    LessEqW 67 { left right destination }
+    GreaterSW 68 { left right destination }
+    GreaterEqSW 69 { left right destination }
+    LessSW 70 { left right destination }
+    LessEqSW 71 { left right destination }
+    AddC 72 { left right carry destination }
+    SubC 73 { left right carry destination }
+    AddCarry 74 { left right carry destination }
+    SubBorrow 75 { left right carry destination }
+}
@@ -0,0 +1,1755 @@
+use crate::{
+    byteorder::ByteOrder,
+    opcodes::{
+        Bank, Destination, Location, Pointer, Source, Statement,
+        Statement::{Inc, Jmp, JmpCmp, JmpCmpNot, Ld, Nop, Ret, Stop, Sub},
+        StopStatus,
+    },
+    parser::{
+        ast,
+        ast::{visit, Visitor},
+        lex::{
+            span::{Span, Spanned},
+            Label, Lit,
+        },
+    },
+    Handlers, Inline, Region, Routine, Space,
+};
+use alloc::{FnAlloc, Instance, Modules, RegisterAlloc, SymbolAlloc, Visibility};
+pub(crate) use block::Block;
+pub use error::CompileError;
+use layout::Layout;
+use std::collections::{HashMap, HashSet};
+
+mod alloc;
+mod block;
+mod error;
+pub(crate) mod expression;
+mod layout;
+pub(crate) mod optimize;
+
+// placeholder NOPs
+pub(crate) const NOP_PERSIST: usize = 0;
+pub(crate) const NOP_CONTINUE: usize = 1;
+pub(crate) const NOP_BREAK: usize = 2;
+pub(crate) const NOP_UNREACHABLE: usize = 3;
+// break (and continue, +1) out of the nth enclosing loop, counting from 0:
+// NOP_OUTER + 2 * n
+pub(crate) const NOP_OUTER: usize = 4;
+
+type Result<T = ()> = std::result::Result<T, CompileError>;
+
+fn compile_scope<B, F>(context: &mut Context<B>, fun: F) -> Result
+where
+    B: ByteOrder,
+    F: FnOnce(&mut Context<B>) -> Result,
+{
+    let child = context.symbol_alloc.clone();
+    compile_with_symbols(context, child, fun)
+}
+
+// compile in a child scope with the given symbols.
+fn compile_with_symbols<B, F>(context: &mut Context<B>, child: SymbolAlloc<B>, fun: F) -> Result
+where
+    B: ByteOrder,
+    F: FnOnce(&mut Context<B>) -> Result,
+{
+    // push static symbols from the parent scope (to be restored later)
+    // all symbols defined within the child scope will be freed by the end.
+    let parent: SymbolAlloc<B> = std::mem::replace(&mut context.symbol_alloc, child);
+    //let parent_stack_usage = context.symbol_alloc.stack_usage();
+
+    fun(context)?;
+
+    // restore symbols
+    let child_static_usage = context.symbol_alloc.static_usage();
+    let child_stack_usage = context.symbol_alloc.stack_usage();
+    let child = std::mem::replace(&mut context.symbol_alloc, parent);
+    context.stack_size = context.stack_size.max(child_stack_usage);
+    context.symbol_alloc.set_static_usage(child_static_usage);
+    context
+        .symbol_alloc
+        .set_bank_usage(child.bank_usage().clone());
+    let _ = context
+        .symbol_alloc
+        .set_static(child.static_data().to_vec());
+    let _ = context.symbol_alloc.set_const(child.into_const_data());
+    Ok(())
+}
+
+// allocate the string literals of an expression in const memory, so that
+// the expression can take their address.
+fn alloc_strings<B: ByteOrder>(
+    expression: &ast::Expression<'_>,
+    symbol_alloc: &mut SymbolAlloc<B>,
+) {
+    struct Strings<'s, B: ByteOrder>(&'s mut SymbolAlloc<B>);
+
+    impl<'a, B: ByteOrder> Visitor<'a> for Strings<'_, B> {
+        fn visit_lit(&mut self, node: &Lit<'a>) {
+            self.0.alloc_const_str(node);
+            visit::walk_lit(self, node);
+        }
+    }
+
+    Strings(symbol_alloc).visit_expression(expression);
+}
+
+/// Ir compilation context.
+#[derive(Default)]
+pub struct Context<B: ByteOrder> {
+    pub(super) routines: Vec<Routine>,
+    pub(super) symbol_alloc: SymbolAlloc<B>,
+    pub(super) stack_size: u16,
+    // routines of the interrupt handlers (the main routine is compiled last)
+    pub(super) handlers: Handlers,
+    return_: Option<Layout>,
+    // labels of the loops being compiled, innermost last, along with the
+    // number of deferred blocks when they began
+    loops: Vec<(Option<String>, usize)>,
+    // compiled deferred blocks of the routine being compiled, innermost last
+    defers: Vec<Block>,
+    fn_alloc: FnAlloc,
+    register_alloc: RegisterAlloc,
+    // symbols visible from the definitions of the generic functions
+    generics: HashMap<String, SymbolAlloc<B>>,
+}
+
+impl<B: ByteOrder> Context<B> {
+    // store the routine of the given index.
+    // Routines aren't compiled in index order (generic functions are compiled
+    // once the rest of the program is).
+    fn set_routine(&mut self, index: usize, routine: Routine) {
+        if self.routines.len() <= index {
+            self.routines.resize_with(index + 1, Routine::default);
+        }
+        self.routines[index] = routine;
+    }
+}
+
+pub trait Compile {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result;
+}
+
+impl Compile for [ast::Statement<'_>] {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        let defers = context.defers.len();
+        // deferred blocks run when falling through the end of the block.
+        // Other exits (break, continue, return) run them on their own.
+        if !compile_statements(self, context, out)? {
+            compile_defers(context, defers, out);
+        }
+        context.defers.truncate(defers);
+        Ok(())
+    }
+}
+
+// compile the statements of a block.
+// Returns whether control flow never reaches the end of the block.
+fn compile_statements<B: ByteOrder>(
+    statements: &[ast::Statement<'_>],
+    context: &mut Context<B>,
+    out: &mut Block,
+) -> Result<bool> {
+    for statement in statements {
+        let start = out.len();
+        let diverges = compile_statement(statement, context, out)?;
+        out.set_span(start, statement.span());
+        if diverges {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// compile a single statement.
+// Returns whether control flow never reaches the statement after it.
+fn compile_statement<B: ByteOrder>(
+    statement: &ast::Statement<'_>,
+    context: &mut Context<B>,
+    out: &mut Block,
+) -> Result<bool> {
+    match statement {
+        ast::Statement::If(if_) => if_.compile(context, out)?,
+        ast::Statement::IfElse(if_else) => if_else.compile(context, out)?,
+        // the declarations of the taken branch belong to the enclosing scope
+        ast::Statement::IfConst(if_const) => {
+            return compile_statements(if_const.statements(), context, out)
+        }
+        ast::Statement::Scope(scope) => scope.compile(context, out)?,
+        ast::Statement::Mod(mod_) => {
+            return compile_module(mod_.ident.name(), &mod_.inner, context, out)
+        }
+        ast::Statement::Import(import) => {
+            return compile_module(&import.name(), &import.inner, context, out)
+        }
+        // macros are expanded by the parser
+        ast::Statement::Macro(_) => {}
+        ast::Statement::MacroCall(call) => call.compile(context, out)?,
+        ast::Statement::Memory(memory) => memory.compile(context, out)?,
+        ast::Statement::Static(static_) => static_.compile(context, out)?,
+        ast::Statement::Const(const_) => const_.compile(context, out)?,
+        ast::Statement::StaticAssert(assert) => assert.compile(context, out)?,
+        ast::Statement::Enum(enum_) => enum_.compile(context, out)?,
+        ast::Statement::TypeAlias(alias) => alias.compile(context, out)?,
+        ast::Statement::Let(let_) => let_.compile(context, out)?,
+        ast::Statement::LetTuple(let_) => let_.compile(context, out)?,
+        ast::Statement::For(for_) => for_.compile(context, out)?,
+        ast::Statement::Loop(loop_) => loop_.compile(context, out)?,
+        ast::Statement::While(while_) => while_.compile(context, out)?,
+        ast::Statement::Match(match_) => match_.compile(context, out)?,
+        ast::Statement::Inline(inline) => inline.compile(context, out)?,
+        ast::Statement::Fn(fn_) => fn_.compile(context, out)?,
+        ast::Statement::Defer(defer) => defer.compile(context, out)?,
+        ast::Statement::Panic(panic) => {
+            panic.compile(context, out)?;
+            return Ok(true);
+        }
+        ast::Statement::Continue(continue_) => {
+            continue_.compile(context, out)?;
+            return Ok(true);
+        }
+        ast::Statement::Break(break_) => {
+            break_.compile(context, out)?;
+            return Ok(true);
+        }
+        ast::Statement::Return(return_) => {
+            return_.compile(context, out)?;
+            return Ok(true);
+        }
+        ast::Statement::Error(_) => {
+            let span = statement.span();
+            return Err(CompileError::Erroneous { span });
+        }
+    }
+    Ok(false)
+}
+
+// compile the statements of a module in place. Its declarations are named
+// after it, and remain visible through its path once it ends.
+// Returns whether control flow never reaches the statement after it.
+fn compile_module<B: ByteOrder>(
+    name: &str,
+    statements: &[ast::Statement<'_>],
+    context: &mut Context<B>,
+    out: &mut Block,
+) -> Result<bool> {
+    context.symbol_alloc.modules_mut().push(name);
+    context.fn_alloc.modules_mut().push(name);
+    let diverges = compile_statements(statements, context, out);
+    context.symbol_alloc.modules_mut().pop();
+    context.fn_alloc.modules_mut().pop();
+    diverges
+}
+
+// emit the deferred blocks registered after the first `from` ones, innermost
+// first. Every exit path gets a copy of the blocks it leaves.
+fn compile_defers<B: ByteOrder>(context: &Context<B>, from: usize, out: &mut Block) {
+    for block in context.defers[from..].iter().rev() {
+        out.extend(block.clone());
+    }
+}
+
+impl Compile for ast::Ast<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        out.push(Nop(NOP_PERSIST));
+        self.inner.compile(context, out)?;
+        out.push(Stop(StopStatus::Success));
+        let stack_size = context.symbol_alloc.stack_usage();
+        context.stack_size = context.stack_size.max(stack_size);
+        Ok(())
+    }
+}
+
+impl Compile for ast::Panic<'_> {
+    fn compile<B: ByteOrder>(&self, _: &mut Context<B>, out: &mut Block) -> Result {
+        out.push(Stop(StopStatus::Error));
+        Ok(())
+    }
+}
+
+impl Compile for ast::Scope<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        compile_scope(context, |ctx| self.inner.compile(ctx, out))
+    }
+}
+
+impl Compile for ast::Defer<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        // compiled in place, so the block sees the symbols defined before it,
+        // then copied into the exit paths of the enclosing scope
+        let mut block = Block::default();
+        compile_scope(context, |ctx| self.inner.compile(ctx, &mut block))?;
+        context.defers.push(block);
+        Ok(())
+    }
+}
+
+impl Compile for ast::MacroCall<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        compile_scope(context, |ctx| self.inner.compile(ctx, out))
+    }
+}
+
+impl Compile for ast::Memory<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        let regions = self
+            .regions
+            .iter()
+            .map(|region| {
+                let (start, len) =
+                    const_range(&region.range, &context.symbol_alloc, "memory region");
+                let start = u32::from(start);
+                assert!(
+                    start + len <= 0x10000,
+                    "Memory region out of the address space!"
+                );
+                Region {
+                    name: region.ident.name().to_string(),
+                    range: start..start + len,
+                    space: match &region.space {
+                        Some(ast::RegionSpace::Static(_)) => Space::Static,
+                        Some(ast::RegionSpace::Const(_)) => Space::Const,
+                        None => Space::Absolute,
+                    },
+                }
+            })
+            .collect();
+        context.symbol_alloc.set_regions(regions);
+        Ok(())
+    }
+}
+
+// statics at a fixed location (absolute or banked) can't be initialized.
+fn initialized_static(field: &ast::Field<'_>, init: Option<&ast::Expression<'_>>) -> Result {
+    match init {
+        Some(_) => Err(CompileError::InitializedStatic {
+            name: field.ident.name().to_string(),
+            span: field.ident.span(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// bank number of a banked static or function.
+fn const_bank<B: ByteOrder>(
+    bank: &ast::Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+) -> Result<u8> {
+    let value =
+        expression::const_expr(bank, Some(symbol_alloc)).expect("Not a constant expression bank!");
+    if value > 0xff {
+        return Err(CompileError::BankOutOfRange { span: bank.span() });
+    }
+    Ok(value as u8)
+}
+
+impl Compile for ast::Static<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        let init = self.init.as_ref().map(|init| &init.expression);
+        let visibility = Visibility::new(&self.pub_);
+        if let Some(offset) = &self.offset {
+            // static memory with explicit offset means the memory is located at the
+            // absolute location in memory.
+            initialized_static(&self.field, init)?;
+            let volatile = offset.volatile.is_some();
+            let symbol_alloc = &context.symbol_alloc;
+            let offset = expression::const_expr(&offset.expression, Some(symbol_alloc))
+                .expect("Not a constant expression offset!");
+            context
+                .symbol_alloc
+                .alloc_absolute(&self.field, offset, volatile, visibility)
+        } else if let Some(bank) = &self.bank {
+            // banked statics are allocated in the memory space of their RAM bank.
+            initialized_static(&self.field, init)?;
+            let bank = const_bank(&bank.expression, &context.symbol_alloc)?;
+            context
+                .symbol_alloc
+                .alloc_banked(&self.field, bank, visibility)
+        } else {
+            // otw the memory is allocated by the compiler in the static virtual memory
+            // space.
+            context
+                .symbol_alloc
+                .alloc_static(&self.field, init, visibility)
+        }
+    }
+}
+
+impl Compile for ast::Const<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        context
+            .symbol_alloc
+            .alloc_const(&self.field, &self.expression, Visibility::new(&self.pub_))
+    }
+}
+
+impl Compile for ast::StaticAssert<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        let value = expression::const_expr(&self.expression, Some(&context.symbol_alloc))
+            .expect("Static assertion is not a constant expression");
+        assert_ne!(0, value, "Static assertion failed: {}", self.message());
+        Ok(())
+    }
+}
+
+impl Compile for ast::Enum<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        context.symbol_alloc.alloc_enum(self)
+    }
+}
+
+impl Compile for ast::TypeAlias<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        context.symbol_alloc.alloc_type_alias(self)
+    }
+}
+
+impl Compile for ast::Let<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // allocate memory on the stack for this field
+        // the compiled expression should store the result on the stack
+        alloc_strings(&self.expression, &mut context.symbol_alloc);
+        context.symbol_alloc.alloc_stack_field(&self.field)?;
+        expression::compile_into_symbol(
+            &self.expression,
+            &self.field.ident.name().to_string(),
+            self.field.ident.span(),
+            &context.symbol_alloc,
+            &context.fn_alloc,
+            &mut context.register_alloc,
+            out,
+        )
+    }
+}
+
+impl Compile for ast::LetTuple<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // the fields are allocated contiguously on the stack, so the tuple can be
+        // compiled into the memory of the first one, as if it was a struct.
+        alloc_strings(&self.expression, &mut context.symbol_alloc);
+        let layouts = self
+            .fields
+            .iter()
+            .map(|field| Layout::with_symbols(&field.type_, Some(&context.symbol_alloc)))
+            .collect();
+        let offset = context.symbol_alloc.stack_address();
+        for field in &self.fields {
+            context.symbol_alloc.alloc_stack_field(field)?;
+        }
+        expression::compile_expression_into_pointer(
+            &self.expression,
+            &Layout::Struct(layouts),
+            &context.symbol_alloc,
+            &context.fn_alloc,
+            Pointer::Stack(offset),
+            &mut context.register_alloc,
+            out,
+        )
+    }
+}
+
+impl Compile for ast::Inline<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // compile expression and drop the results.
+        // the expression will be evaluated by the result is not stored anywhere.
+        alloc_strings(&self.inner, &mut context.symbol_alloc);
+        expression::compile_expr_void(
+            &self.inner,
+            &context.symbol_alloc,
+            &context.fn_alloc,
+            &mut context.register_alloc,
+            out,
+        )
+    }
+}
+
+struct IfStatements<'a, 'b> {
+    expression: &'a ast::Expression<'b>,
+    inner: &'a Vec<ast::Statement<'b>>,
+    has_else: bool,
+}
+
+impl Compile for ast::If<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        let const_expr = expression::const_expr(&self.expression, Some(&context.symbol_alloc));
+
+        match const_expr {
+            Some(0) => Ok(()),
+            Some(_) => compile_scope(context, |ctx| self.inner.compile(ctx, out)),
+            None => compile_scope(context, |ctx| {
+                IfStatements {
+                    expression: &self.expression,
+                    inner: &self.inner,
+                    has_else: false,
+                }
+                .compile(ctx, out)
+            }),
+        }
+    }
+}
+
+impl Compile for ast::IfElse<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        let const_expr = expression::const_expr(&self.if_.expression, Some(&context.symbol_alloc));
+
+        match const_expr {
+            Some(0) => compile_scope(context, |ctx| self.else_.inner.compile(ctx, out)),
+            Some(_) => compile_scope(context, |ctx| self.if_.inner.compile(ctx, out)),
+            None => {
+                // compiled else_ block
+                let mut else_ = Block::default();
+
+                compile_scope(context, |ctx| self.else_.inner.compile(ctx, &mut else_))?;
+                compile_scope(context, |ctx| {
+                    IfStatements {
+                        expression: &self.if_.expression,
+                        inner: &self.if_.inner,
+                        has_else: true,
+                    }
+                    .compile(ctx, out)
+                })?;
+
+                out.push(Jmp {
+                    location: Location::Relative(else_.len() as _),
+                });
+                out.extend(else_);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Compile for IfStatements<'_, '_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // compile expression into an 8bit register
+        let source = expression::compile_expr_u8(
+            &self.expression,
+            &context.symbol_alloc,
+            &context.fn_alloc,
+            &mut context.register_alloc,
+            out,
+        )?;
+        expression::free_source_registers(&source, &mut context.register_alloc);
+
+        // compile the block of statements inside the if block.
+        // clone the symbol_alloc to free any symbols defined within the block.
+        let mut inner = Block::default();
+        self.inner.compile(context, &mut inner)?;
+
+        let jmp = inner.len() + if self.has_else { 1 } else { 0 };
+        out.push(JmpCmpNot {
+            location: Location::Relative(jmp as _),
+            source,
+        });
+        out.extend(inner);
+        Ok(())
+    }
+}
+
+// Generalized loop statement.
+// Equivalent to the following loop statement:
+// ```no_rust
+// loop {
+//     <prefix>
+//     <inner>
+//     <suffix>
+// }
+// ```
+struct LoopInner<'a, 'b> {
+    label: &'a Option<ast::LoopLabel<'b>>,
+    prefix: Vec<Statement>,
+    inner: &'a Vec<ast::Statement<'b>>,
+    suffix: Vec<Statement>,
+}
+
+impl Compile for LoopInner<'_, '_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // compile statements inside the loop block
+        // at the end, jump back to the first statement
+        let mut inner = Block::default();
+
+        inner.extend_from_slice(&self.prefix);
+        let label = self.label.as_ref().map(|l| l.label.to_string());
+        context.loops.push((label, context.defers.len()));
+        self.inner.compile(context, &mut inner)?;
+        context.loops.pop();
+        inner.extend_from_slice(&self.suffix);
+
+        let suffix_start = inner.len() - self.suffix.len();
+        let loop_statements_signed = inner.len() as isize;
+        inner.push(Jmp {
+            location: Location::Relative(-(loop_statements_signed + 1) as i8),
+        });
+
+        // replace Nop statements (placeholders for break and continue) with the
+        // appropriate Jmp statements. Don't worry about optimizing jumps for now. That
+        // will be dealt with later...
+        let statements_len = inner.len();
+        for (i, statement) in inner.iter_mut().enumerate() {
+            match statement {
+                // break
+                Nop(NOP_BREAK) => {
+                    let relative = statements_len - i - 1;
+                    *statement = Jmp {
+                        location: Location::Relative(relative as _),
+                    };
+                }
+                // continue
+                // jumps to the suffix (the increment of a for loop), if any
+                Nop(NOP_CONTINUE) if !self.suffix.is_empty() => {
+                    let relative = suffix_start - i - 1;
+                    *statement = Jmp {
+                        location: Location::Relative(relative as _),
+                    };
+                }
+                Nop(NOP_CONTINUE) => {
+                    let relative = i as isize + 1;
+                    *statement = Jmp {
+                        location: Location::Relative(-relative as _),
+                    };
+                }
+                // labeled break or continue out of an enclosing loop
+                Nop(n) if *n >= NOP_OUTER => {
+                    *statement = match *n - NOP_OUTER {
+                        0 => Nop(NOP_BREAK),
+                        1 => Nop(NOP_CONTINUE),
+                        n => Nop(NOP_OUTER + n - 2),
+                    };
+                }
+                _ => {}
+            }
+        }
+        out.extend(inner);
+        Ok(())
+    }
+}
+
+impl Compile for ast::Loop<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        compile_scope(context, |context| {
+            LoopInner {
+                label: &self.label,
+                prefix: Vec::new(),
+                inner: &self.inner,
+                suffix: Vec::new(),
+            }
+            .compile(context, out)
+        })
+    }
+}
+
+impl Compile for ast::While<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        let const_expr = expression::const_expr(&self.expression, Some(&context.symbol_alloc));
+
+        let mut prefix = Vec::new();
+        match const_expr {
+            Some(0) => return Ok(()),
+            // infinite loop, same as the loop statement
+            Some(_) => {}
+            None => {
+                // evaluate the condition at the beginning of every iteration.
+                // the jump out of the loop is computed once the loop is compiled.
+                let source = expression::compile_expr_u8(
+                    &self.expression,
+                    &context.symbol_alloc,
+                    &context.fn_alloc,
+                    &mut context.register_alloc,
+                    &mut prefix,
+                )?;
+                expression::free_source_registers(&source, &mut context.register_alloc);
+                prefix.push(JmpCmpNot {
+                    location: Location::Relative(0),
+                    source,
+                });
+            }
+        }
+
+        let jmp = prefix.len().checked_sub(1);
+        let mut while_statements = Block::default();
+        compile_scope(context, |context| {
+            LoopInner {
+                label: &self.label,
+                prefix,
+                inner: &self.inner,
+                suffix: Vec::new(),
+            }
+            .compile(context, &mut while_statements)
+        })?;
+        if let Some(jmp) = jmp {
+            let relative = while_statements.len() - jmp - 1;
+            match &mut while_statements[jmp] {
+                JmpCmpNot { location, .. } => *location = Location::Relative(relative as _),
+                _ => unreachable!(),
+            }
+        }
+        out.extend(while_statements);
+        Ok(())
+    }
+}
+
+// compile the statements of a for loop that performs a single iteration.
+// Breaking out of (or continuing) the loop still needs a loop to jump out of,
+// so it is compiled as a loop that breaks at the end of the first iteration.
+fn compile_for_once<B: ByteOrder>(
+    for_: &ast::For<'_>,
+    context: &mut Context<B>,
+    out: &mut Block,
+) -> Result {
+    if !breaks_out(&for_.label, &for_.inner) {
+        return for_.inner.compile(context, out);
+    }
+    LoopInner {
+        label: &for_.label,
+        prefix: Vec::new(),
+        inner: &for_.inner,
+        suffix: vec![Nop(NOP_BREAK)],
+    }
+    .compile(context, out)
+}
+
+// whether any of the statements breaks out of (or continues) the loop with the
+// given label that contains them.
+fn breaks_out(label: &Option<ast::LoopLabel<'_>>, inner: &[ast::Statement<'_>]) -> bool {
+    struct Breaks {
+        label: Option<String>,
+        // loops between the visited statements and the outer loop
+        depth: usize,
+        breaks: bool,
+    }
+
+    impl Breaks {
+        fn targets(&self, label: &Option<Label<'_>>) -> bool {
+            match label {
+                None => self.depth == 0,
+                Some(label) => self.label.as_deref() == Some(&label.to_string()),
+            }
+        }
+    }
+
+    impl<'a> Visitor<'a> for Breaks {
+        fn visit_break(&mut self, node: &ast::Break<'a>) {
+            self.breaks |= self.targets(&node.label);
+        }
+
+        fn visit_continue(&mut self, node: &ast::Continue<'a>) {
+            self.breaks |= self.targets(&node.label);
+        }
+
+        fn visit_loop(&mut self, node: &ast::Loop<'a>) {
+            self.depth += 1;
+            visit::walk_loop(self, node);
+            self.depth -= 1;
+        }
+
+        fn visit_while(&mut self, node: &ast::While<'a>) {
+            self.depth += 1;
+            visit::walk_while(self, node);
+            self.depth -= 1;
+        }
+
+        fn visit_for(&mut self, node: &ast::For<'a>) {
+            self.depth += 1;
+            visit::walk_for(self, node);
+            self.depth -= 1;
+        }
+
+        // loops can't be broken out of from inside a function definition
+        fn visit_fn(&mut self, _: &ast::Fn<'a>) {}
+    }
+
+    let mut breaks = Breaks {
+        label: label.as_ref().map(|l| l.label.to_string()),
+        depth: 0,
+        breaks: false,
+    };
+    for statement in inner {
+        breaks.visit_statement(statement);
+    }
+    breaks.breaks
+}
+
+impl Compile for ast::For<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        compile_scope(context, |context| {
+            let mut for_statements = Block::default();
+            let stack_address = context.symbol_alloc.alloc_stack_field(&self.field)?;
+
+            // init for variable with the lhs side of the range
+            // TODO non-U8 variables
+            let init = expression::compile_expr_u8(
+                &self.range.left,
+                &context.symbol_alloc,
+                &context.fn_alloc,
+                &mut context.register_alloc,
+                out,
+            )?;
+            expression::free_source_registers(&init, &mut context.register_alloc);
+            out.push(Ld {
+                source: init,
+                destination: Destination::Pointer {
+                    base: Pointer::Stack(stack_address),
+                    offset: None,
+                },
+            });
+
+            // if the for loop only performs a single iteration (an this can be determined
+            // statically), compile as a regular block statement.
+            // if it performs no iterations at all, only the variable is initialized.
+            let l = expression::const_expr(&self.range.left, Some(&context.symbol_alloc));
+            let r = expression::const_expr(&self.range.right, Some(&context.symbol_alloc));
+            match (l, r, &self.range.eq, &self.range.plus) {
+                // for _ in n..m (m <= n)
+                (Some(l), Some(r), None, None) if r <= l => return Ok(()),
+                // for _ in n..=m (m < n)
+                (Some(l), Some(r), Some(_), None) if r < l => return Ok(()),
+                // for _ in n..+0
+                (Some(_), Some(0), None, Some(_)) => return Ok(()),
+                // for _ in n..+1
+                (Some(_), Some(1), None, Some(_)) => return compile_for_once(self, context, out),
+                // for _ in n..=n
+                (Some(l), Some(r), Some(_), None) if l == r => {
+                    return compile_for_once(self, context, out)
+                }
+                // for _ in n..(n+1)
+                (Some(l), Some(r), None, None) if l + 1 == r => {
+                    return compile_for_once(self, context, out)
+                }
+                // for _ in n..=+0
+                (Some(_), Some(0), Some(_), Some(_)) => {
+                    return compile_for_once(self, context, out)
+                }
+                _ => {}
+            }
+
+            // compute end index of the for loop with the rhs of the range
+            // (added to the lhs if it's a length), increment if it's an inclusive range
+            let end = expression::compile_expr_u8(
+                &self.range.right,
+                &context.symbol_alloc,
+                &context.fn_alloc,
+                &mut context.register_alloc,
+                out,
+            )?;
+            let end_register = context.register_alloc.alloc();
+            if self.range.plus.is_some() {
+                out.push(Statement::Add {
+                    left: Source::Pointer {
+                        base: Pointer::Stack(stack_address),
+                        offset: None,
+                    },
+                    right: end.clone(),
+                    destination: Destination::Register(end_register),
+                });
+            } else {
+                out.push(Statement::Ld {
+                    source: end.clone(),
+                    destination: Destination::Register(end_register),
+                });
+            }
+            expression::free_source_registers(&end, &mut context.register_alloc);
+            if self.range.eq.is_some() {
+                out.push(Inc {
+                    source: Source::Register(end_register),
+                    destination: Destination::Register(end_register),
+                });
+            }
+
+            // begin compiling the inner for loop statements.
+            // check if for loop variable has reached the limit.
+            let cmp_register = context.register_alloc.alloc();
+            let prefix = vec![
+                Sub {
+                    left: Source::Register(end_register),
+                    right: Source::Pointer {
+                        base: Pointer::Stack(stack_address),
+                        offset: None,
+                    },
+                    destination: Destination::Register(cmp_register),
+                },
+                // TODO optimize away, as this is equivalent to: if foo { break }
+                JmpCmp {
+                    location: Location::Relative(1),
+                    source: Source::Register(cmp_register),
+                },
+                Nop(NOP_BREAK),
+            ];
+            context.register_alloc.free(cmp_register);
+            // increment the for loop variable
+            let suffix = vec![Inc {
+                source: Source::Pointer {
+                    base: Pointer::Stack(stack_address),
+                    offset: None,
+                },
+                destination: Destination::Pointer {
+                    base: Pointer::Stack(stack_address),
+                    offset: None,
+                },
+            }];
+
+            // parse inner loop statements
+            LoopInner {
+                label: &self.label,
+                prefix,
+                inner: &self.inner,
+                suffix,
+            }
+            .compile(context, &mut for_statements)?;
+
+            out.extend(for_statements);
+
+            // free register holding the last index of the for loop
+            context.register_alloc.free(end_register);
+            Ok(())
+        })
+    }
+}
+
+// values matched by a match arm pattern, as a (first value, number of values)
+// pair. Patterns must be constant expressions.
+fn pattern_range<B: ByteOrder>(
+    pattern: &ast::Pattern<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+) -> (u16, u32) {
+    match pattern {
+        ast::Pattern::Expression(expression) => (
+            expression::const_expr(expression, Some(symbol_alloc))
+                .expect("Not a constant expression match pattern!"),
+            1,
+        ),
+        ast::Pattern::Range(range) => const_range(range, symbol_alloc, "match range"),
+    }
+}
+
+// start and length of a constant range: n..m, n..=m, n..+len, n..=+len
+fn const_range<B: ByteOrder>(
+    range: &ast::Range<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    what: &str,
+) -> (u16, u32) {
+    let const_expr = |expression| {
+        expression::const_expr(expression, Some(symbol_alloc))
+            .unwrap_or_else(|| panic!("Not a constant expression {}!", what))
+    };
+    let l = u32::from(const_expr(&range.left));
+    let r = u32::from(const_expr(&range.right));
+    let len = if range.plus.is_some() {
+        r
+    } else {
+        r.wrapping_sub(l)
+    };
+    let len = len.wrapping_add(range.eq.is_some() as u32);
+    assert!(len as i32 > 0, "Empty {}!", what);
+    (l as u16, len)
+}
+
+// Statement of the comparison chain of a match statement. Jumps to the body of
+// the nth arm are resolved once the length of the chain is known.
+enum MatchTest {
+    Statement(Statement),
+    JmpCmp(Source<u8>, usize),
+    Jmp(usize),
+    // jump to the arm of each value of the index
+    Table(Source<u8>, Vec<usize>),
+}
+
+// smallest number of arms of a match statement dispatched by a jump table.
+const JUMP_TABLE_ARMS: usize = 4;
+
+// first value, and arm of each value from it, of the jump table of a match
+// statement over a u8 scrutinee. Only for dense arms: at least
+// `JUMP_TABLE_ARMS` of them, matching at least half of the values of the
+// table. Values no arm matches jump past the last arm.
+fn jump_table(patterns: &[(u16, u32)]) -> Option<(u8, Vec<usize>)> {
+    if patterns.len() < JUMP_TABLE_ARMS || patterns.iter().any(|&(_, len)| len > 0xff) {
+        return None;
+    }
+    let first = patterns.iter().map(|&(first, _)| u32::from(first)).min()?;
+    let end = patterns
+        .iter()
+        .map(|&(first, len)| u32::from(first) + len)
+        .max()?;
+    if end > 0x100 {
+        return None;
+    }
+    let none = patterns.len();
+    let mut arms = vec![none; (end - first) as usize];
+    // arms are matched in order
+    for (arm, &(start, len)) in patterns.iter().enumerate().rev() {
+        let start = (u32::from(start) - first) as usize;
+        arms[start..start + len as usize]
+            .iter_mut()
+            .for_each(|entry| *entry = arm);
+    }
+    let matched = arms.iter().filter(|arm| **arm != none).count();
+    if matched * 2 < arms.len() {
+        return None;
+    }
+    Some((first as u8, arms))
+}
+
+// jump table of a match statement over a u8 scrutinee, indexed by
+// (scrutinee - first). `arms` is the number of arms of the match statement.
+fn match_table_u8<B: ByteOrder>(
+    scrutinee: &ast::Expression<'_>,
+    first: u8,
+    table: Vec<usize>,
+    arms: usize,
+    context: &mut Context<B>,
+    tests: &mut Vec<MatchTest>,
+    out: &mut Vec<Statement>,
+) -> Result {
+    let scrutinee = expression::compile_expr_u8(
+        scrutinee,
+        &context.symbol_alloc,
+        &context.fn_alloc,
+        &mut context.register_alloc,
+        out,
+    )?;
+    let register = context.register_alloc.alloc();
+    let index = if first == 0 {
+        scrutinee.clone()
+    } else {
+        // values below the first one match no arm (checked before the
+        // subtraction, which may not wrap around)
+        tests.push(MatchTest::Statement(Statement::Less {
+            left: scrutinee.clone(),
+            right: Source::Literal(first),
+            destination: Destination::Register(register),
+        }));
+        tests.push(MatchTest::JmpCmp(Source::Register(register), arms));
+        tests.push(MatchTest::Statement(Sub {
+            left: scrutinee.clone(),
+            right: Source::Literal(first),
+            destination: Destination::Register(register),
+        }));
+        Source::Register(register)
+    };
+    tests.push(MatchTest::Table(index, table));
+    context.register_alloc.free(register);
+    expression::free_source_registers(&scrutinee, &mut context.register_alloc);
+    Ok(())
+}
+
+impl Compile for ast::Match<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        let patterns: Vec<_> = self
+            .arms
+            .iter()
+            .map(|arm| pattern_range(&arm.pattern, &context.symbol_alloc))
+            .collect();
+
+        // scrutinee known at compile time, only compile the matching arm.
+        let const_expr = expression::const_expr(&self.expression, Some(&context.symbol_alloc));
+        if let Some(n) = const_expr {
+            let n = u32::from(n);
+            let arm = patterns
+                .iter()
+                .position(|&(first, len)| n.wrapping_sub(u32::from(first)) < len);
+            return match (arm, &self.else_) {
+                (Some(arm), _) => {
+                    let inner = &self.arms[arm].inner;
+                    compile_scope(context, |ctx| inner.compile(ctx, out))
+                }
+                (None, Some(else_)) => compile_scope(context, |ctx| else_.inner.compile(ctx, out)),
+                (None, None) => Ok(()),
+            };
+        }
+
+        compile_scope(context, |context| {
+            let mut tests = Vec::new();
+            if expression::is_word(&self.expression, &context.symbol_alloc) {
+                match_tests_u16(&self.expression, &patterns, context, &mut tests, out)?;
+            } else if let Some((first, table)) = jump_table(&patterns) {
+                let arms = self.arms.len();
+                match_table_u8(
+                    &self.expression,
+                    first,
+                    table,
+                    arms,
+                    context,
+                    &mut tests,
+                    out,
+                )?;
+            } else {
+                match_tests_u8(&self.expression, &patterns, context, &mut tests, out)?;
+            }
+            // none of the arms matched
+            tests.push(MatchTest::Jmp(self.arms.len()));
+
+            // compile arm bodies. each one jumps to the end of the match statement.
+            let mut bodies = Vec::new();
+            for arm in &self.arms {
+                let mut body = Block::default();
+                compile_scope(context, |ctx| arm.inner.compile(ctx, &mut body))?;
+                bodies.push(body);
+            }
+            let mut else_ = Block::default();
+            if let Some(e) = &self.else_ {
+                compile_scope(context, |ctx| e.inner.compile(ctx, &mut else_))?;
+            }
+            let mut offsets = Vec::with_capacity(bodies.len() + 1);
+            let mut offset = tests.len();
+            for body in &bodies {
+                offsets.push(offset);
+                offset += body.len() + 1;
+            }
+            offsets.push(offset);
+
+            for (i, test) in tests.into_iter().enumerate() {
+                let relative = |arm: usize| (offsets[arm] - i - 1) as i8;
+                out.push(match test {
+                    MatchTest::Statement(statement) => statement,
+                    MatchTest::JmpCmp(source, arm) => JmpCmp {
+                        location: Location::Relative(relative(arm)),
+                        source,
+                    },
+                    MatchTest::Jmp(arm) => Jmp {
+                        location: Location::Relative(relative(arm)),
+                    },
+                    MatchTest::Table(index, arms) => Jmp {
+                        location: Location::Table {
+                            index,
+                            targets: arms.into_iter().map(relative).collect(),
+                        },
+                    },
+                });
+            }
+            let end = offset + else_.len();
+            for (body, offset) in bodies.into_iter().zip(offsets) {
+                let len = body.len();
+                out.extend(body);
+                out.push(Jmp {
+                    location: Location::Relative((end - offset - len - 1) as _),
+                });
+            }
+            out.extend(else_);
+            Ok(())
+        })
+    }
+}
+
+// comparison chain of a match statement over a u8 scrutinee.
+fn match_tests_u8<B: ByteOrder>(
+    scrutinee: &ast::Expression<'_>,
+    patterns: &[(u16, u32)],
+    context: &mut Context<B>,
+    tests: &mut Vec<MatchTest>,
+    out: &mut Vec<Statement>,
+) -> Result {
+    let scrutinee = expression::compile_expr_u8(
+        scrutinee,
+        &context.symbol_alloc,
+        &context.fn_alloc,
+        &mut context.register_alloc,
+        out,
+    )?;
+    let register = context.register_alloc.alloc();
+    for (arm, &(first, len)) in patterns.iter().enumerate() {
+        let first = first as u8;
+        let test = Source::Register(register);
+        let destination = || Destination::Register(register);
+        match len {
+            1 => tests.push(MatchTest::Statement(Statement::Eq {
+                left: scrutinee.clone(),
+                right: Source::Literal(first),
+                destination: destination(),
+            })),
+            // the arm matches every value
+            len if len > 0xff => {
+                tests.push(MatchTest::Jmp(arm));
+                continue;
+            }
+            // first <= scrutinee < first + len
+            // computed as (scrutinee - first) < len
+            len => {
+                let mut left = scrutinee.clone();
+                if first != 0 {
+                    tests.push(MatchTest::Statement(Sub {
+                        left,
+                        right: Source::Literal(first),
+                        destination: destination(),
+                    }));
+                    left = test.clone();
+                }
+                tests.push(MatchTest::Statement(Statement::Less {
+                    left,
+                    right: Source::Literal(len as u8),
+                    destination: destination(),
+                }));
+            }
+        }
+        tests.push(MatchTest::JmpCmp(test, arm));
+    }
+    context.register_alloc.free(register);
+    expression::free_source_registers(&scrutinee, &mut context.register_alloc);
+    Ok(())
+}
+
+// comparison chain of a match statement over a u16 scrutinee.
+fn match_tests_u16<B: ByteOrder>(
+    scrutinee: &ast::Expression<'_>,
+    patterns: &[(u16, u32)],
+    context: &mut Context<B>,
+    tests: &mut Vec<MatchTest>,
+    out: &mut Vec<Statement>,
+) -> Result {
+    // words are compared byte by byte, so they are stored on the stack:
+    // the scrutinee, followed by the (scrutinee - first) of range patterns.
+    let stack_address = context.symbol_alloc.alloc_stack(4);
+    let scrutinee = expression::compile_expr_u16(
+        scrutinee,
+        &context.symbol_alloc,
+        &context.fn_alloc,
+        &mut context.register_alloc,
+        out,
+    )?;
+    expression::free_source_registers(&scrutinee, &mut context.register_alloc);
+    out.push(Statement::LdW {
+        source: scrutinee,
+        destination: Destination::Pointer {
+            base: Pointer::Stack(stack_address),
+            offset: None,
+        },
+    });
+
+    // bytes of a word: (low, high)
+    let bytes = |word: u16| (word as u8, (word >> 8) as u8);
+    let byte_source = |address: u16, high: bool| {
+        let mut probe = [0; 2];
+        B::write_u16(&mut probe, 0xff00);
+        let offset = (probe[1] == 0xff) as u16 ^ (!high) as u16;
+        Source::Pointer {
+            base: Pointer::Stack(address + offset),
+            offset: None,
+        }
+    };
+
+    let register = context.register_alloc.alloc();
+    let test = Source::Register(register);
+    let destination = || Destination::Register(register);
+    for (arm, &(first, len)) in patterns.iter().enumerate() {
+        let mut address = stack_address;
+        let (lo, hi) = match len {
+            1 => bytes(first),
+            // the arm matches every value
+            len if len > 0xffff => {
+                tests.push(MatchTest::Jmp(arm));
+                continue;
+            }
+            len => {
+                if first != 0 {
+                    address = stack_address + 2;
+                    tests.push(MatchTest::Statement(Statement::SubW {
+                        left: Source::Pointer {
+                            base: Pointer::Stack(stack_address),
+                            offset: None,
+                        },
+                        right: Source::Literal(first),
+                        destination: Destination::Pointer {
+                            base: Pointer::Stack(address),
+                            offset: None,
+                        },
+                    }));
+                }
+                bytes(len as u16)
+            }
+        };
+        if len == 1 {
+            // skip the comparison of the high byte if the low bytes differ
+            tests.push(MatchTest::Statement(Statement::Eq {
+                left: byte_source(address, false),
+                right: Source::Literal(lo),
+                destination: destination(),
+            }));
+            tests.push(MatchTest::Statement(JmpCmpNot {
+                location: Location::Relative(2),
+                source: test.clone(),
+            }));
+            tests.push(MatchTest::Statement(Statement::Eq {
+                left: byte_source(address, true),
+                right: Source::Literal(hi),
+                destination: destination(),
+            }));
+        } else {
+            // (scrutinee - first) < len, comparing the high bytes first
+            tests.push(MatchTest::Statement(Statement::Less {
+                left: byte_source(address, true),
+                right: Source::Literal(hi),
+                destination: destination(),
+            }));
+            tests.push(MatchTest::JmpCmp(test.clone(), arm));
+            tests.push(MatchTest::Statement(Statement::NotEq {
+                left: byte_source(address, true),
+                right: Source::Literal(hi),
+                destination: destination(),
+            }));
+            tests.push(MatchTest::Statement(JmpCmp {
+                location: Location::Relative(2),
+                source: test.clone(),
+            }));
+            tests.push(MatchTest::Statement(Statement::Less {
+                left: byte_source(address, false),
+                right: Source::Literal(lo),
+                destination: destination(),
+            }));
+        }
+        tests.push(MatchTest::JmpCmp(test.clone(), arm));
+    }
+    context.register_alloc.free(register);
+    Ok(())
+}
+
+// number of loops between a break/continue statement and the loop it refers to.
+// Fails if no enclosing loop has the label (or there are no loops at all).
+fn loop_depth<B: ByteOrder>(
+    context: &Context<B>,
+    label: &Option<Label<'_>>,
+    span: Span,
+) -> Result<usize> {
+    let depth = match label {
+        None if context.loops.is_empty() => None,
+        None => Some(0),
+        Some(label) => {
+            let label = label.to_string();
+            context
+                .loops
+                .iter()
+                .rev()
+                .position(|(l, _)| l.as_ref() == Some(&label))
+        }
+    };
+    depth.ok_or(CompileError::OutsideLoop { span })
+}
+
+// run the deferred blocks of the scopes left by a break/continue statement.
+fn compile_loop_defers<B: ByteOrder>(
+    context: &Context<B>,
+    depth: usize,
+    span: Span,
+    out: &mut Block,
+) -> Result {
+    let (_, defers) = context
+        .loops
+        .len()
+        .checked_sub(depth + 1)
+        .and_then(|index| context.loops.get(index))
+        .ok_or(CompileError::OutsideLoop { span })?;
+    compile_defers(context, *defers, out);
+    Ok(())
+}
+
+impl Compile for ast::Break<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // in order to compile the Break statement, the compiler needs to know how many
+        // instructions there are ahead of it. add placeholder Nop statement, which
+        // should be replaced inside the compile_loop compile_for functions.
+        let depth = loop_depth(context, &self.label, self.span())?;
+        compile_loop_defers(context, depth, self.span(), out)?;
+        match depth {
+            0 => out.push(Nop(NOP_BREAK)),
+            n => out.push(Nop(NOP_OUTER + 2 * (n - 1))),
+        }
+        Ok(())
+    }
+}
+
+impl Compile for ast::Continue<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        // same deal as with the break statement.
+        // use a different Nop to differentiate it.
+        let depth = loop_depth(context, &self.label, self.span())?;
+        compile_loop_defers(context, depth, self.span(), out)?;
+        match depth {
+            0 => out.push(Nop(NOP_CONTINUE)),
+            n => out.push(Nop(NOP_OUTER + 2 * (n - 1) + 1)),
+        }
+        Ok(())
+    }
+}
+
+#[rustfmt::skip]
+impl Compile for ast::Fn<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, _: &mut Block) -> Result {
+        // calls to const functions are evaluated by the parser
+        if self.const_.is_some() {
+            return Ok(());
+        }
+
+        // generic functions are compiled once for each instance, after the rest
+        // of the program (see `compile_instances`).
+        if self.generics.is_some() {
+            assert!(self.interrupt.is_none(), "Interrupt handlers can't be generic");
+            assert!(
+                self.attributes.iter().all(|a| a.bank().is_none()),
+                "Generic functions can't be banked"
+            );
+            context.fn_alloc.alloc_generic(self, &context.symbol_alloc)?;
+            let name = context.fn_alloc.modules().qualified(self.ident.name());
+            context.generics.insert(name, context.symbol_alloc.clone());
+            return Ok(());
+        }
+
+        compile_scope(context, |context| {
+            // this is a function so only const and static symbols are visible
+            context.symbol_alloc.clear_stack();
+
+            // allocate a new routine index/handle (used by the Call statement).
+            // this is the index where the routine must be stored in Ir::routines.
+            let bank = match self.attributes.iter().find_map(|a| a.bank()) {
+                Some(bank) => Some(const_bank(bank, &context.symbol_alloc)?),
+                None => None,
+            };
+            let handle = context.fn_alloc.alloc(self, bank)?;
+            // banked functions are entered through their thunk.
+            let entry = if bank.is_some() { handle + 1 } else { handle };
+
+            // register the routine as an interrupt handler.
+            if let Some(interrupt) = &self.interrupt {
+                assert!(
+                    self.fn_arg.is_none() && self.fn_return.is_none(),
+                    "Interrupt handlers can't take arguments or return values"
+                );
+                let handler = match interrupt.interrupt() {
+                    ast::Interrupt::VBlank => &mut context.handlers.vblank,
+                    ast::Interrupt::LcdStat => &mut context.handlers.lcd_stat,
+                    ast::Interrupt::Timer => &mut context.handlers.timer,
+                    ast::Interrupt::Serial => &mut context.handlers.serial,
+                    ast::Interrupt::Joypad => &mut context.handlers.joypad,
+                };
+                if handler.is_some() {
+                    return Err(CompileError::DuplicateHandler {
+                        name: interrupt.ident.name().to_string(),
+                        span: interrupt.ident.span(),
+                    });
+                }
+                *handler = Some(entry);
+            }
+
+            let name = context.fn_alloc.modules().qualified(self.ident.name());
+            let (args_size, return_size) =
+                compile_routine(self, context, handle, name.clone(), bank)?;
+
+            // the thunk maps the ROM bank of the function for the duration of the call.
+            // the arguments and return value are passed through, since the stack frame
+            // of the thunk is the one of the function.
+            if let Some(bank) = bank {
+                context.set_routine(handle + 1, Routine {
+                    debug_name: Some(format!("{}::thunk", name)),
+                    stack_size: args_size,
+                    args_size,
+                    return_size,
+                    bank: None,
+                    inline: Inline::Auto,
+                    statements: vec![
+                        Nop(NOP_PERSIST),
+                        Statement::PushBank { bank },
+                        Statement::Call { routine: handle, range: 0.. },
+                        Statement::PopBank,
+                        Ret,
+                    ],
+                    spans: Vec::new(),
+                });
+            }
+            Ok(())
+        })
+    }
+}
+
+// compile the body of a function into the routine of the given index, in a
+// scope where only the const and static symbols are visible.
+// Returns the size of its arguments, and of its return value.
+fn compile_routine<B: ByteOrder>(
+    fn_: &ast::Fn<'_>,
+    context: &mut Context<B>,
+    handle: usize,
+    name: String,
+    bank: Option<Bank>,
+) -> Result<(u16, u16)> {
+    // allocate function parameters in the new stack frame.
+    if let Some(args) = &fn_.fn_arg {
+        for field in &args.inner {
+            context.symbol_alloc.alloc_stack_field(field)?;
+        }
+    }
+
+    let args_size = context.symbol_alloc.stack_usage();
+    // the routine has its own stack frame, separate from the enclosing one
+    let stack_size = std::mem::replace(&mut context.stack_size, args_size);
+
+    // like with main, start the routine with a Nop instruction
+    let mut out = Block::from(vec![Nop(NOP_PERSIST)]);
+    let return_layout = fn_
+        .fn_return
+        .as_ref()
+        .map(|r| Layout::with_symbols(&r.type_, Some(&context.symbol_alloc)));
+
+    let return_size = return_layout.as_ref().map(|l| l.size()).unwrap_or(0);
+
+    // loops enclosing the function definition can't be broken out of, and
+    // the deferred blocks of the enclosing scopes don't run on its return
+    let loops = std::mem::take(&mut context.loops);
+    let defers = std::mem::take(&mut context.defers);
+    context.return_ = return_layout;
+    fn_.inner.compile(context, &mut out)?;
+    context.return_ = None;
+    context.loops = loops;
+    context.defers = defers;
+
+    // the symbols of the frame are freed, so they don't count towards the stack
+    // of the enclosing routine when the scope of the function ends
+    let routine_stack_size = context.stack_size.max(context.symbol_alloc.stack_usage());
+    context.stack_size = stack_size;
+    context.symbol_alloc.clear_stack();
+
+    out.push(Ret);
+
+    let inline = match fn_.attributes.iter().find_map(|a| a.inline()) {
+        Some(true) => Inline::Always,
+        Some(false) => Inline::Never,
+        None => Inline::Auto,
+    };
+    let (statements, spans) = out.into_parts();
+    let routine = Routine {
+        debug_name: Some(name),
+        stack_size: routine_stack_size,
+        args_size,
+        return_size,
+        bank,
+        inline,
+        statements,
+        spans,
+    };
+    context.set_routine(handle, routine);
+    Ok((args_size, return_size))
+}
+
+/// Compile the statements of the program into `main`, followed by the instances
+/// of its generic functions.
+pub(crate) fn compile_program<B: ByteOrder>(
+    ast: &ast::Ast<'_>,
+    context: &mut Context<B>,
+    main: &mut Block,
+) -> Result {
+    let result = ast
+        .compile(context, main)
+        .and_then(|_| compile_instances(ast, context));
+    // compilation stops at the first error, before the statements being
+    // compiled free the registers they hold, so these don't leak.
+    if result.is_err() {
+        context.register_alloc.clear();
+    }
+    result
+}
+
+/// Compile the instances of the generic functions called by the program,
+/// including the ones called from other instances.
+pub(crate) fn compile_instances<B: ByteOrder>(
+    ast: &ast::Ast<'_>,
+    context: &mut Context<B>,
+) -> Result {
+    // compiles the pending instances of the generic functions it visits
+    struct Instances<'c, B: ByteOrder> {
+        context: &'c mut Context<B>,
+        // routines of the compiled instances
+        compiled: HashSet<usize>,
+        // module of the visited functions
+        modules: Modules,
+        // first error compiling an instance (no more are compiled after it)
+        result: Result,
+    }
+
+    impl<'a, B: ByteOrder> Visitor<'a> for Instances<'_, B> {
+        fn visit_fn(&mut self, node: &ast::Fn<'a>) {
+            if node.generics.is_some() {
+                let name = self.modules.qualified(node.ident.name());
+                let mut n = 0;
+                while let Some(instance) = self.context.fn_alloc.nth_instance(n) {
+                    if self.result.is_err() {
+                        return;
+                    }
+                    if instance.name == name && self.compiled.insert(instance.routine) {
+                        self.result = compile_instance(node, &instance, self.context);
+                    }
+                    n += 1;
+                }
+            }
+            visit::walk_fn(self, node);
+        }
+
+        fn visit_mod(&mut self, node: &ast::Mod<'a>) {
+            self.modules.push(node.ident.name());
+            visit::walk_mod(self, node);
+            self.modules.pop();
+        }
+
+        fn visit_import(&mut self, node: &ast::Import<'a>) {
+            self.modules.push(&node.name());
+            visit::walk_import(self, node);
+            self.modules.pop();
+        }
+    }
+
+    let mut instances = Instances {
+        context,
+        compiled: HashSet::new(),
+        modules: Modules::default(),
+        result: Ok(()),
+    };
+    // instances may call generic functions visited before them
+    while instances
+        .context
+        .fn_alloc
+        .nth_instance(instances.compiled.len())
+        .is_some()
+    {
+        instances.visit_ast(ast);
+        instances.result.clone()?;
+    }
+    Ok(())
+}
+
+// compile an instance of a generic function, with the symbols visible from its
+// definition, and its generic parameters defined as consts.
+fn compile_instance<B: ByteOrder>(
+    fn_: &ast::Fn<'_>,
+    instance: &Instance,
+    context: &mut Context<B>,
+) -> Result {
+    let mut symbols = context.generics[&instance.name].clone();
+    // calls resolve from the module of the definition
+    let modules = symbols.modules().clone();
+    let modules = std::mem::replace(context.fn_alloc.modules_mut(), modules);
+    symbols.set_static_usage(context.symbol_alloc.static_usage());
+    symbols.set_bank_usage(context.symbol_alloc.bank_usage().clone());
+    symbols.set_static(context.symbol_alloc.static_data().to_vec());
+    symbols.set_const(context.symbol_alloc.const_data().to_vec());
+    compile_with_symbols(context, symbols, |context| {
+        context.symbol_alloc.clear_stack();
+        let params = fn_.generics.iter().flat_map(|g| &g.params);
+        for (param, value) in params.zip(&instance.values) {
+            context
+                .symbol_alloc
+                .alloc_const_value(&param.field, *value)?;
+        }
+        let values: Vec<_> = instance.values.iter().map(|v| v.to_string()).collect();
+        let name = format!("{}<{}>", instance.name, values.join(", "));
+        compile_routine(fn_, context, instance.routine, name, None)?;
+        Ok(())
+    })?;
+    *context.fn_alloc.modules_mut() = modules;
+    Ok(())
+}
+
+impl Compile for ast::Return<'_> {
+    fn compile<B: ByteOrder>(&self, context: &mut Context<B>, out: &mut Block) -> Result {
+        if let Some(return_layout) = &context.return_ {
+            expression::compile_expression_into_pointer::<B>(
+                self.expression.as_ref().unwrap(),
+                return_layout,
+                &context.symbol_alloc,
+                &context.fn_alloc,
+                Pointer::Return(0),
+                &mut context.register_alloc,
+                out,
+            )?;
+        }
+        // the return value is computed before leaving the scopes
+        compile_defers(context, 0, out);
+        out.push(Statement::Ret);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        compile::{alloc::Visibility, Block, Compile, Context},
+        opcodes::Statement,
+    };
+
+    #[test]
+    fn test_visibility() {
+        let ast = crate::parser::parse(
+            r#"
+        pub static FOO:struct { x:u8 y:u8 }
+        static BAR:u8
+        pub const BAZ:u8 = 0
+        pub enum Dir { Up Down }
+        type Tile = [u8 16]
+        pub fn exported { }
+        fn internal { }
+        "#,
+        )
+        .unwrap();
+        let mut context = Context::<crate::byteorder::NativeEndian>::default();
+        ast.inner
+            .compile(&mut context, &mut Block::default())
+            .unwrap();
+        let visibility = |name| context.symbol_alloc.declaration(name).map(|(_, v)| v);
+        assert_eq!(Some(Visibility::Public), visibility("FOO"));
+        assert_eq!(Some(Visibility::Public), visibility("FOO::y"));
+        assert_eq!(Some(Visibility::Private), visibility("BAR"));
+        assert_eq!(Some(Visibility::Public), visibility("BAZ"));
+        assert_eq!(Some(Visibility::Public), visibility("Dir::Down"));
+        assert_eq!(Some(Visibility::Private), visibility("Tile"));
+        assert_eq!(
+            Visibility::Public,
+            context.fn_alloc.find("exported").unwrap().0.visibility
+        );
+        assert_eq!(
+            Visibility::Private,
+            context.fn_alloc.find("internal").unwrap().0.visibility
+        );
+    }
+
+    #[test]
+    fn test_if_const_expr() {
+        let ast = crate::parser::parse(
+            r#"
+        const F:u8 = 0
+        static RESULT1:[u8 13]
+        static RESULT2:[u8 13]
+        if F {
+            // this code will be removed!
+            (= ([0]RESULT1) 1)
+            (= ([1]RESULT1) 1)
+            (= ([0]RESULT2) 1)
+            (= ([1]RESULT2) 1)
+            for offset:u8 in 2..13 {
+                (= ([offset]RESULT1)
+                   (+ ([(-offset 1)]RESULT1) ([(-offset 2)]RESULT1)))
+                (+= ([offset]RESULT2)
+                    ([(-offset 1)]RESULT2))
+                (+= ([offset]RESULT2)
+                    ([(-offset 2)]RESULT2))
+            }
+        }
+        if 1 {
+        } else {
+            // this code will be removed!
+            (= ([0]RESULT1) 1)
+            (= ([1]RESULT1) 1)
+            (= ([0]RESULT2) 1)
+            (= ([1]RESULT2) 1)
+            for offset:u8 in 2..13 {
+                (= ([offset]RESULT1)
+                    (+ ([(-offset 1)]RESULT1)
+                       ([(-offset 2)]RESULT1)))
+                (+= ([offset]RESULT2)
+                    ([(-offset 1)]RESULT2))
+                (+= ([offset]RESULT2)
+                    ([(-offset 2)]RESULT2))
+            }
+        }
+        "#,
+        )
+        .unwrap();
+        let mut context = Context::<crate::byteorder::NativeEndian>::default();
+        let mut statements = Block::default();
+        ast.inner.compile(&mut context, &mut statements).unwrap();
+        let gt: Vec<Statement> = vec![];
+        assert_eq!(gt, *statements); // no code must be generated
+    }
+}
@@ -0,0 +1,1461 @@
+use crate::{
+    byteorder::ByteOrder,
+    compile::{
+        error::CompileError,
+        expression::const_expr,
+        layout::{align_to, bits_mask, is_packed, member_align, members_align, Layout, Template},
+    },
+    opcodes::{Bank, Pointer},
+    parser::{
+        ast,
+        ast::{
+            types::{Attribute, Member},
+            Expression, Field, Path, Type,
+        },
+        lex,
+        lex::{
+            span::{Span, Spanned},
+            Ident, Lit,
+        },
+    },
+    Charset, Region, Space,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+    ops::Range,
+};
+
+pub struct Fn {
+    pub arg_layout: Vec<Layout>,
+    pub ret_layout: Option<Layout>,
+    pub visibility: Visibility,
+}
+
+/// Visibility of a declaration.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Visibility {
+    /// Internal to the program (or module) declaring it.
+    Private,
+
+    /// Exported (declared with `pub`).
+    Public,
+}
+
+impl Visibility {
+    pub fn new(pub_: &Option<lex::Pub<'_>>) -> Self {
+        match pub_ {
+            Some(_) => Self::Public,
+            None => Self::Private,
+        }
+    }
+}
+
+/// Path of the module being compiled, outermost first (empty at the top level
+/// of the program).
+///
+/// Declarations of a module are named after it (`sprites::TILES`). Names used
+/// within a module refer to the declarations of the innermost module that
+/// declares them, like in the parser.
+#[derive(Debug, Default, Clone)]
+pub struct Modules(Vec<String>);
+
+impl Modules {
+    /// Enter a (nested) module.
+    pub fn push(&mut self, name: &str) {
+        self.0.push(name.to_string());
+    }
+
+    /// Leave the innermost module.
+    pub fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    /// Name of the module (`outer::inner`).
+    pub fn path(&self) -> String {
+        self.0.join("::")
+    }
+
+    /// Name of a declaration of the module.
+    pub fn qualified(&self, name: &str) -> String {
+        self.0
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    // fails if the declaration with the given name is private to a module
+    // other than this one (or one enclosing it).
+    fn check_visible(
+        &self,
+        declaration: &str,
+        visibility: Visibility,
+        span: Span,
+    ) -> Result<(), CompileError> {
+        let mut module: Vec<_> = declaration.split("::").collect();
+        module.pop();
+        let within =
+            module.len() <= self.0.len() && self.0.iter().zip(&module).all(|(a, b)| a == b);
+        if visibility == Visibility::Public || within {
+            Ok(())
+        } else {
+            Err(CompileError::PrivateSymbol {
+                name: declaration.to_string(),
+                span,
+            })
+        }
+    }
+
+    // first declaration a name used within the module refers to, from the
+    // innermost module to the outermost one.
+    fn resolve<T>(&self, name: &str, mut find: impl FnMut(&str) -> Option<T>) -> Option<T> {
+        (0..=self.0.len()).rev().find_map(|i| {
+            let mut path = self.0[..i].to_vec();
+            path.push(name.to_string());
+            find(&path.join("::"))
+        })
+    }
+}
+
+/// Signature of a generic function.
+pub struct Generic {
+    pub params: Vec<String>,
+    pub visibility: Visibility,
+    pub arg_template: Vec<Template>,
+    pub ret_template: Option<Template>,
+}
+
+/// Generic function instantiated with the given values of its generic
+/// parameters.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Instance {
+    pub name: String,
+    pub values: Vec<u16>,
+    pub routine: usize,
+}
+
+/// Function allocator.
+///
+/// Declarations of functions already allocated fail with a
+/// [`CompileError`]. Other panics mean a bug somewhere in the compiler (likely
+/// in the frontend).
+#[derive(Default)]
+pub struct FnAlloc {
+    // functions and the routine they are called through
+    fns: HashMap<String, (Fn, usize)>,
+    generics: HashMap<String, Generic>,
+    // instances are allocated as the calls to generic functions are compiled,
+    // which only have a shared reference to the allocator.
+    instances: RefCell<Vec<Instance>>,
+    // next free routine index
+    next: Cell<usize>,
+    // module of the functions being allocated
+    modules: Modules,
+}
+
+impl FnAlloc {
+    /// Allocated a function from it's statement.
+    /// Fails if a function of the same name is already allocated.
+    ///
+    /// Returns the index of the routine of the function. Functions in a switchable
+    /// ROM bank are also allocated a thunk routine right after it, which maps the
+    /// bank before calling the function. Calls and function pointers refer to the
+    /// thunk.
+    pub fn alloc(&mut self, fn_: &ast::Fn<'_>, bank: Option<Bank>) -> Result<usize, CompileError> {
+        let name = self.check_undefined(&fn_.ident)?;
+        let id = self.next.get();
+        self.next.set(id + if bank.is_some() { 2 } else { 1 });
+        let entry = if bank.is_some() { id + 1 } else { id };
+        let fn_ = Fn {
+            arg_layout: fn_
+                .fn_arg
+                .iter()
+                .flat_map(|a| &a.inner)
+                .map(|field| Layout::new(&field.type_))
+                .collect(),
+            ret_layout: fn_.fn_return.as_ref().map(|r| Layout::new(&r.type_)),
+            visibility: Visibility::new(&fn_.pub_),
+        };
+        self.fns.insert(name, (fn_, entry));
+        Ok(id)
+    }
+
+    /// Returns the function with the given name, if it's defined.
+    pub fn find(&self, name: &str) -> Option<(&Fn, usize)> {
+        self.modules
+            .resolve(name, |name| self.fns.get(name))
+            .map(|(fn_, id)| (fn_, *id))
+    }
+
+    /// Fails if the path names a function (generic or not) private to another
+    /// module.
+    pub fn check_visible(&self, path: &Path<'_>) -> Result<(), CompileError> {
+        let name: Vec<_> = path.iter().map(|ident| ident.name().to_string()).collect();
+        let declaration = self.modules.resolve(&name.join("::"), |name| {
+            let fn_ = self
+                .fns
+                .get_key_value(name)
+                .map(|(k, (f, _))| (k, f.visibility));
+            fn_.or_else(|| {
+                self.generics
+                    .get_key_value(name)
+                    .map(|(k, g)| (k, g.visibility))
+            })
+        });
+        match declaration {
+            Some((name, visibility)) => self.modules.check_visible(name, visibility, path.span()),
+            None => Ok(()),
+        }
+    }
+
+    /// Module of the functions being allocated.
+    pub fn modules(&self) -> &Modules {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut Modules {
+        &mut self.modules
+    }
+
+    /// Allocate a generic function from it's statement.
+    /// Fails if a function of the same name is already allocated.
+    ///
+    /// No routine is allocated until the function is instantiated.
+    pub fn alloc_generic<B: ByteOrder>(
+        &mut self,
+        fn_: &ast::Fn<'_>,
+        symbol_alloc: &SymbolAlloc<B>,
+    ) -> Result<(), CompileError> {
+        let name = self.check_undefined(&fn_.ident)?;
+        let params: Vec<_> = fn_
+            .generics
+            .iter()
+            .flat_map(|g| &g.params)
+            .map(|param| param.field.ident.name().to_string())
+            .collect();
+        let generic = Generic {
+            arg_template: fn_
+                .fn_arg
+                .iter()
+                .flat_map(|a| &a.inner)
+                .map(|field| Template::new(&field.type_, &params, symbol_alloc))
+                .collect(),
+            ret_template: fn_
+                .fn_return
+                .as_ref()
+                .map(|r| Template::new(&r.type_, &params, symbol_alloc)),
+            params,
+            visibility: Visibility::new(&fn_.pub_),
+        };
+        self.generics.insert(name, generic);
+        Ok(())
+    }
+
+    // fails if a function (generic or not) with the name of the identifier is
+    // already allocated in the module. Returns the name of the function.
+    fn check_undefined(&self, ident: &Ident<'_>) -> Result<String, CompileError> {
+        let name = self.modules.qualified(ident.name());
+        if self.fns.contains_key(&name) || self.generics.contains_key(&name) {
+            return Err(CompileError::DuplicateSymbol {
+                name,
+                span: ident.span(),
+            });
+        }
+        Ok(name)
+    }
+
+    /// Returns the instance of the generic function with the given name that
+    /// takes arguments of the given layouts (unknown layouts are `None`), if
+    /// the function is generic.
+    ///
+    /// The values of the generic parameters are inferred from the lengths of
+    /// the array arguments. The routine of the instance is allocated the first
+    /// time it is requested.
+    /// Panics if a parameter can't be inferred.
+    pub fn instance(&self, name: &str, args: &[Option<Layout>]) -> Option<(Fn, usize)> {
+        let (name, generic) = self
+            .modules
+            .resolve(name, |name| self.generics.get_key_value(name))?;
+        let mut values = vec![None; generic.params.len()];
+        for (template, layout) in generic.arg_template.iter().zip(args) {
+            if let Some(layout) = layout {
+                template.infer(layout, &mut values);
+            }
+        }
+        let values: Vec<_> = values
+            .into_iter()
+            .zip(&generic.params)
+            .map(|(value, param)| {
+                value.unwrap_or_else(|| panic!("Can't infer generic parameter `{}`", param))
+            })
+            .collect();
+        let mut instances = self.instances.borrow_mut();
+        let routine = match instances
+            .iter()
+            .find(|i| &i.name == name && i.values == values)
+        {
+            Some(instance) => instance.routine,
+            None => {
+                let routine = self.next.get();
+                self.next.set(routine + 1);
+                instances.push(Instance {
+                    name: name.clone(),
+                    values: values.clone(),
+                    routine,
+                });
+                routine
+            }
+        };
+        let fn_ = Fn {
+            arg_layout: generic
+                .arg_template
+                .iter()
+                .map(|t| t.layout(&values))
+                .collect(),
+            ret_layout: generic.ret_template.as_ref().map(|t| t.layout(&values)),
+            visibility: generic.visibility,
+        };
+        Some((fn_, routine))
+    }
+
+    /// Returns the nth instance of a generic function, in allocation order.
+    pub fn nth_instance(&self, n: usize) -> Option<Instance> {
+        self.instances.borrow().get(n).cloned()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SymbolMemorySpace {
+    /// Static memory.
+    Static,
+
+    /// Const memory (ROM).
+    Const,
+
+    /// Stack memory.
+    Stack,
+
+    /// Absolute memory space.
+    Absolute,
+
+    /// Static memory of a switchable RAM bank.
+    Banked(Bank),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Symbolic name.
+    pub name: String,
+
+    /// Offset in virtual memory.
+    pub offset: u16,
+
+    /// Size of the symbol itself.
+    pub size: u16,
+
+    /// The type of the symbol.
+    pub layout: Layout,
+
+    /// Virtual memory space.
+    pub memory_space: SymbolMemorySpace,
+}
+
+impl Symbol {
+    pub(crate) fn pointer(&self) -> Pointer {
+        match self.memory_space {
+            SymbolMemorySpace::Static => Pointer::Static(self.offset),
+            SymbolMemorySpace::Const => Pointer::Const(self.offset),
+            SymbolMemorySpace::Stack => Pointer::Stack(self.offset),
+            SymbolMemorySpace::Absolute => Pointer::Absolute(self.offset),
+            SymbolMemorySpace::Banked(bank) => Pointer::Banked(bank, self.offset),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SymbolAlloc<B: ByteOrder> {
+    const_: Vec<u8>,
+    static_: Vec<u8>,
+    absolute_symbols: Vec<Symbol>,
+    const_symbols: Vec<Symbol>,
+    static_symbols: Vec<Symbol>,
+    stack_symbols: Vec<Symbol>,
+    banked_symbols: Vec<Symbol>,
+    static_symbols_alloc: u16,
+    stack_symbols_alloc: u16,
+    // static memory allocated in each switchable RAM bank
+    banks_alloc: BTreeMap<Bank, u16>,
+    charset: Option<Charset>,
+    enums: HashSet<String>,
+    aliases: HashMap<String, Vec<Symbol>>,
+    // alignment of the type aliases
+    aligns: HashMap<String, u16>,
+    // visibility of the declarations (statics, consts, enums, and aliases)
+    visibility: HashMap<String, Visibility>,
+    // absolute memory of the volatile statics
+    volatile: Vec<Range<u16>>,
+    // memory map
+    regions: Vec<Region>,
+    // module of the symbols being allocated
+    modules: Modules,
+    _phantom: PhantomData<B>,
+}
+
+impl<B: ByteOrder> SymbolAlloc<B> {
+    pub fn into_const_data(self) -> Vec<u8> {
+        self.const_
+    }
+
+    /// const data so far.
+    pub fn const_data(&self) -> &[u8] {
+        &self.const_
+    }
+
+    /// Initial static memory data so far (up to the last initialized static).
+    pub fn static_data(&self) -> &[u8] {
+        &self.static_
+    }
+
+    /// Absolute memory regions of the volatile statics.
+    pub fn volatile(&self) -> &[Range<u16>] {
+        &self.volatile
+    }
+
+    pub fn static_usage(&self) -> u16 {
+        self.static_symbols_alloc
+    }
+
+    /// Static memory used in each switchable RAM bank.
+    pub fn bank_usage(&self) -> &BTreeMap<Bank, u16> {
+        &self.banks_alloc
+    }
+
+    /// override the memory usage of the RAM banks (see `set_static_usage`).
+    pub fn set_bank_usage(&mut self, usage: BTreeMap<Bank, u16>) {
+        self.banks_alloc = usage;
+    }
+
+    /// Memory map declared so far.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Declare the memory map.
+    ///
+    /// Panics if a map is already declared, if the regions overlap, if more than
+    /// one region holds the same memory space, or if the memory allocated so far
+    /// doesn't fit in it.
+    pub fn set_regions(&mut self, regions: Vec<Region>) {
+        assert!(self.regions.is_empty(), "Memory map already declared!");
+        for (i, region) in regions.iter().enumerate() {
+            for other in &regions[..i] {
+                assert!(
+                    region.range.end <= other.range.start || other.range.end <= region.range.start,
+                    "Memory regions `{}` and `{}` overlap!",
+                    other.name,
+                    region.name
+                );
+                assert!(
+                    region.space == Space::Absolute || region.space != other.space,
+                    "Memory regions `{}` and `{}` hold the same memory space!",
+                    other.name,
+                    region.name
+                );
+            }
+        }
+        self.regions = regions;
+
+        self.check_region(Space::Const, self.const_.len() as u32, "const memory");
+        self.check_region(
+            Space::Static,
+            self.static_symbols_alloc.into(),
+            "static memory",
+        );
+        for symbol in self
+            .absolute_symbols
+            .iter()
+            .filter(|s| !s.name.contains("::"))
+        {
+            self.check_absolute(symbol.offset, symbol.size, &symbol.name);
+        }
+    }
+
+    // panics if the memory used by the given space overflows its region.
+    fn check_region(&self, space: Space, usage: u32, name: &str) {
+        if let Some(region) = self.regions.iter().find(|r| r.space == space) {
+            let size = region.range.end - region.range.start;
+            assert!(
+                usage <= size,
+                "Memory region `{}` overflowed by {} bytes allocating {}!",
+                region.name,
+                usage - size,
+                name
+            );
+        }
+    }
+
+    // panics if the absolute memory isn't within a region of the memory map.
+    fn check_absolute(&self, offset: u16, size: u16, name: &str) {
+        let range = u32::from(offset)..u32::from(offset) + u32::from(size);
+        assert!(
+            self.regions.is_empty()
+                || self
+                    .regions
+                    .iter()
+                    .any(|r| r.range.start <= range.start && range.end <= r.range.end),
+            "Absolute static `{}` is outside of the memory map!",
+            name
+        );
+    }
+
+    pub fn stack_usage(&self) -> u16 {
+        self.stack_symbols_alloc
+    }
+
+    /// override static memory usage.
+    ///
+    /// Used for then the symbol allocator is cloned when compiling a child
+    /// scope, but you still want to keep the static memory allocated within it.
+    pub fn set_static_usage(&mut self, usage: u16) {
+        assert!(usage >= self.static_symbols_alloc);
+        self.static_symbols_alloc = usage;
+    }
+
+    pub fn set_charset(&mut self, charset: Charset) {
+        self.charset = Some(charset);
+    }
+
+    /// Encode the value of a string literal with the charset.
+    /// Returns `None` if the literal is not a string literal.
+    /// Panics if a character is not part of the charset.
+    pub fn encode_str(&self, lit: &Lit<'_>) -> Option<Vec<u8>> {
+        let charset = self.charset.unwrap_or(crate::ascii);
+        let value = lit.str_value()?;
+        Some(
+            value
+                .chars()
+                .map(|c| charset(c).expect(&format!("Character not in charset: {:?}", c)))
+                .collect(),
+        )
+    }
+
+    /// Allocate the data of a string literal in const memory, unless the same
+    /// bytes are already there. Returns the offset of the data.
+    pub fn alloc_const_str(&mut self, lit: &Lit<'_>) -> Option<u16> {
+        if let Some(offset) = self.const_str(lit) {
+            return Some(offset);
+        }
+        let offset = self.const_.len() as u16;
+        self.const_.extend(self.encode_str(lit)?);
+        self.check_region(Space::Const, self.const_.len() as u32, "a string literal");
+        Some(offset)
+    }
+
+    /// Returns the offset of the data of a string literal in const memory, if
+    /// it's been allocated.
+    pub fn const_str(&self, lit: &Lit<'_>) -> Option<u16> {
+        let data = self.encode_str(lit)?;
+        if data.is_empty() {
+            return Some(self.const_.len() as u16);
+        }
+        self.const_
+            .windows(data.len())
+            .position(|window| window == &data[..])
+            .map(|offset| offset as u16)
+    }
+
+    pub fn set_const(&mut self, const_: Vec<u8>) -> Vec<u8> {
+        std::mem::replace(&mut self.const_, const_)
+    }
+
+    pub fn set_static(&mut self, static_: Vec<u8>) -> Vec<u8> {
+        std::mem::replace(&mut self.static_, static_)
+    }
+
+    /// Clear stack symbols
+    pub fn clear_stack(&mut self) {
+        self.stack_symbols.clear();
+        self.stack_symbols_alloc = 0;
+    }
+
+    /// Module of the symbols being allocated.
+    pub fn modules(&self) -> &Modules {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut Modules {
+        &mut self.modules
+    }
+
+    /// Name and visibility of the declaration a symbol or type belongs to, if
+    /// any (locals don't belong to one).
+    ///
+    /// Fields (`FOO::bar`) and enum variants (`Enum::Variant`) belong to the
+    /// declaration named by the shortest declared prefix of their name.
+    pub(crate) fn declaration(&self, name: &str) -> Option<(String, Visibility)> {
+        let segments: Vec<_> = name.split("::").collect();
+        (1..=segments.len()).find_map(|i| {
+            let declaration = segments[..i].join("::");
+            let visibility = *self.visibility.get(&declaration)?;
+            Some((declaration, visibility))
+        })
+    }
+
+    /// Allocate const address.
+    pub fn alloc_const(
+        &mut self,
+        field: &Field<'_>,
+        expression: &Expression<'_>,
+        visibility: Visibility,
+    ) -> Result<(), CompileError> {
+        let (offset, size) = self.alloc_const_symbols(field, visibility)?;
+
+        // compute constant expression value
+        let mut data = vec![0; size as usize];
+        let name = self.modules.qualified(field.ident.name());
+        self.init_data(&name, field.ident.span(), expression, offset, &mut data)?;
+        self.const_.extend(data);
+        let name = format!("`{}`", field.ident.name());
+        self.check_region(Space::Const, self.const_.len() as u32, &name);
+        Ok(())
+    }
+
+    /// Allocate the const of a generic parameter, with the value of the
+    /// instance being compiled.
+    pub fn alloc_const_value(&mut self, field: &Field<'_>, value: u16) -> Result<(), CompileError> {
+        let (_, size) = self.alloc_const_symbols(field, Visibility::Private)?;
+        let mut data = vec![0; size as usize];
+        match size {
+            1 => data[0] = value as u8,
+            2 => B::write_u16(&mut data, value),
+            _ => panic!("Generic parameter is not an integer"),
+        }
+        self.const_.extend(data);
+        let name = format!("`{}`", field.ident.name());
+        self.check_region(Space::Const, self.const_.len() as u32, &name);
+        Ok(())
+    }
+
+    // allocate the symbols of a const, at the end of the const memory.
+    // Returns the offset and size of the allocated const.
+    fn alloc_const_symbols(
+        &mut self,
+        field: &Field<'_>,
+        visibility: Visibility,
+    ) -> Result<(u16, u16), CompileError> {
+        let name = self.check_undeclared(&field.ident)?;
+        self.visibility.insert(name, visibility);
+
+        let offset = align_to(self.const_.len() as u16, self.align(field));
+        self.const_.resize(offset as usize, 0);
+        let mut symbols = Vec::new();
+        let size = self.compute_all_symbols(
+            &self.modules.path(),
+            offset,
+            field,
+            SymbolMemorySpace::Const,
+            &mut symbols,
+        )?;
+        self.const_symbols.extend(symbols);
+        Ok((offset, size))
+    }
+
+    // compute the bytes of the constant initializer of the symbol with the given
+    // name (declared by the identifier at `span`), into the data of the symbol
+    // allocated at the `base` offset.
+    // Struct literals are computed field by field (missing fields are zeroed).
+    fn init_data(
+        &self,
+        name: &str,
+        span: Span,
+        expression: &Expression<'_>,
+        base: u16,
+        out: &mut [u8],
+    ) -> Result<(), CompileError> {
+        let symbol = match self.find(name) {
+            Some(symbol) => symbol,
+            None => {
+                let name = name.to_string();
+                return Err(CompileError::UndefinedSymbol { name, span });
+            }
+        };
+        match expression {
+            // integers are initialized bitfield by bitfield
+            Expression::StructLit(struct_lit) => {
+                assert!(
+                    matches!(
+                        symbol.layout,
+                        Layout::Struct(_) | Layout::Union(_) | Layout::U8 | Layout::U16
+                    ),
+                    "Struct literal assigned to a non-struct value"
+                );
+                for field in &struct_lit.fields {
+                    let name = format!("{}::{}", name, field.ident.name());
+                    let span = field.ident.span();
+                    self.init_data(&name, span, &field.expression, base, out)?;
+                }
+            }
+            expression if matches!(symbol.layout, Layout::Bits { .. }) => {
+                let (inner, shift, width) = match &symbol.layout {
+                    Layout::Bits {
+                        inner,
+                        shift,
+                        width,
+                    } => (inner, *shift, *width),
+                    _ => unreachable!(),
+                };
+                let value = const_expr(expression, Some(self)).expect("Not a constant expression");
+                let mask = bits_mask(width);
+                assert!(value <= mask, "Value doesn't fit in the bitfield");
+                let offset = (symbol.offset - base) as usize;
+                let data = &mut out[offset..offset + inner.size() as usize];
+                if let Layout::U16 = **inner {
+                    let word = B::read_u16(data) | value << shift;
+                    B::write_u16(data, word);
+                } else {
+                    data[0] |= (value << shift) as u8;
+                }
+            }
+            expression => {
+                let mut data = Vec::new();
+                compute_const_expr_into_vec::<B>(&symbol.layout, expression, self, &mut data);
+                let offset = (symbol.offset - base) as usize;
+                out[offset..offset + data.len()].copy_from_slice(&data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocate the variants of an enum as `u8` const symbols, named after the
+    /// enum (`Enum::Variant`).
+    ///
+    /// Variants without an explicit discriminant take the value of the previous
+    /// variant plus one (the first one defaults to 0).
+    pub fn alloc_enum(&mut self, enum_: &ast::Enum<'_>) -> Result<(), CompileError> {
+        let name = self.check_undeclared_type(&enum_.ident)?;
+
+        let mut value = 0;
+        for variant in &enum_.variants {
+            if let Some(discriminant) = &variant.discriminant {
+                value = const_expr(&discriminant.expression, Some(self))
+                    .expect("Not a constant expression discriminant!");
+            }
+            assert!(value <= 0xff);
+            let name = format!("{}::{}", name, variant.ident.name());
+            if self.find(&name).is_some() {
+                let span = variant.ident.span();
+                return Err(CompileError::DuplicateSymbol { name, span });
+            }
+            self.const_symbols.push(Symbol {
+                name,
+                offset: self.const_.len() as _,
+                size: 1,
+                layout: Layout::U8,
+                memory_space: SymbolMemorySpace::Const,
+            });
+            self.const_.push(value as u8);
+            value += 1;
+        }
+        let usage = self.const_.len() as u32;
+        self.check_region(Space::Const, usage, &format!("`{}`", name));
+        self.visibility
+            .insert(name.clone(), Visibility::new(&enum_.pub_));
+        self.enums.insert(name);
+        Ok(())
+    }
+
+    /// Returns true if the path names an enum.
+    pub fn is_enum(&self, path: &Path<'_>) -> bool {
+        let name: Vec<_> = path.iter().map(|ident| ident.name().to_string()).collect();
+        self.modules
+            .resolve(&name.join("::"), |name| self.enums.get(name))
+            .is_some()
+    }
+
+    /// Declare a type alias.
+    ///
+    /// The symbols of a value of the aliased type (the value itself and its
+    /// fields) are computed once, relative to the value, and are reused by
+    /// every value declared with the alias.
+    pub fn alloc_type_alias(&mut self, alias: &ast::TypeAlias<'_>) -> Result<(), CompileError> {
+        let name = self.check_undeclared_type(&alias.ident)?;
+
+        let mut symbols = Vec::new();
+        self.compute_type_symbols(
+            String::new(),
+            0,
+            &alias.inner,
+            SymbolMemorySpace::Static,
+            &mut symbols,
+        )?;
+        self.visibility
+            .insert(name.clone(), Visibility::new(&alias.pub_));
+        self.aligns
+            .insert(name.clone(), Layout::align(&alias.inner, Some(self)));
+        self.aliases.insert(name, symbols);
+        Ok(())
+    }
+
+    // fails if an enum or a type alias with the name of the identifier is
+    // already declared in the module. Returns the name of the type.
+    fn check_undeclared_type(&self, ident: &Ident<'_>) -> Result<String, CompileError> {
+        let name = self.modules.qualified(ident.name());
+        if self.enums.contains(&name) || self.aliases.contains_key(&name) {
+            return Err(CompileError::DuplicateSymbol {
+                name,
+                span: ident.span(),
+            });
+        }
+        Ok(name)
+    }
+
+    // symbols of a value of the type named by the path, if it is a type alias.
+    fn alias(&self, path: &Path<'_>) -> Option<&[Symbol]> {
+        let name: Vec<_> = path.iter().map(|ident| ident.name().to_string()).collect();
+        self.modules
+            .resolve(&name.join("::"), |name| self.aliases.get(name))
+            .map(|s| &s[..])
+    }
+
+    /// Alignment of the type named by the path, if it is a type alias.
+    pub fn alias_align(&self, path: &Path<'_>) -> Option<u16> {
+        let name: Vec<_> = path.iter().map(|ident| ident.name().to_string()).collect();
+        self.modules
+            .resolve(&name.join("::"), |name| self.aligns.get(name))
+            .copied()
+    }
+
+    /// Layout of the type named by the path, if it is a type alias.
+    pub fn alias_layout(&self, path: &Path<'_>) -> Option<Layout> {
+        self.alias(path).map(|symbols| symbols[0].layout.clone())
+    }
+
+    // alignment of the type of a field.
+    fn align(&self, field: &Field<'_>) -> u16 {
+        Layout::align(&field.type_, Some(self))
+    }
+
+    /// Allocate static address.
+    ///
+    /// The address is aligned to the alignment of the type of the static,
+    /// relative to the beginning of the static memory.
+    ///
+    /// The value of the initializer expression, if any, is computed at compile
+    /// time and stored in the initial static memory data. Struct literals can be
+    /// nested to initialize nested fields (`{ pos:{ x:8 y:16 } tile:0 }`).
+    pub fn alloc_static(
+        &mut self,
+        field: &Field<'_>,
+        init: Option<&Expression<'_>>,
+        visibility: Visibility,
+    ) -> Result<(), CompileError> {
+        let name = self.check_undeclared(&field.ident)?;
+        self.visibility.insert(name.clone(), visibility);
+
+        let offset = align_to(self.static_symbols_alloc, self.align(field));
+        let mut symbols = Vec::new();
+        let size = self.compute_all_symbols(
+            &self.modules.path(),
+            offset,
+            field,
+            SymbolMemorySpace::Static,
+            &mut symbols,
+        )?;
+        self.static_symbols.extend(symbols);
+        self.static_symbols_alloc = offset + size;
+        let usage = u32::from(self.static_symbols_alloc);
+        self.check_region(Space::Static, usage, &format!("`{}`", field.ident.name()));
+
+        if let Some(expression) = init {
+            let mut data = vec![0; size as usize];
+            self.init_data(&name, field.ident.span(), expression, offset, &mut data)?;
+            let offset = offset as usize;
+            if self.static_.len() < offset + data.len() {
+                self.static_.resize(offset + data.len(), 0);
+            }
+            self.static_[offset..offset + data.len()].copy_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// Declares a symbol located at the given offset.
+    /// Note that it is possible to overlap two symbols, as long as the language
+    /// frontend allows it... (the IR doesn't really care about memory aliasing)
+    ///
+    /// Accesses to the memory of volatile symbols must not be optimized away.
+    pub fn alloc_absolute(
+        &mut self,
+        field: &Field<'_>,
+        offset: u16,
+        volatile: bool,
+        visibility: Visibility,
+    ) -> Result<(), CompileError> {
+        let name = self.check_undeclared(&field.ident)?;
+        assert_eq!(0, offset % self.align(field), "Misaligned absolute static");
+        self.visibility.insert(name, visibility);
+
+        let mut symbols = Vec::new();
+        let size = self.compute_all_symbols(
+            &self.modules.path(),
+            offset,
+            field,
+            SymbolMemorySpace::Absolute,
+            &mut symbols,
+        )?;
+        self.absolute_symbols.extend(symbols);
+        self.check_absolute(offset, size, &field.ident.name());
+        if volatile {
+            self.volatile.push(offset..offset + size);
+        }
+        Ok(())
+    }
+
+    /// Allocate static address in the given switchable RAM bank.
+    ///
+    /// Each bank is a virtual memory space of its own, so the address is aligned
+    /// relative to the beginning of the bank. Banked statics can't be
+    /// initialized.
+    pub fn alloc_banked(
+        &mut self,
+        field: &Field<'_>,
+        bank: Bank,
+        visibility: Visibility,
+    ) -> Result<(), CompileError> {
+        let name = self.check_undeclared(&field.ident)?;
+        self.visibility.insert(name, visibility);
+
+        let alloc = self.banks_alloc.get(&bank).copied().unwrap_or(0);
+        let offset = align_to(alloc, self.align(field));
+        let mut symbols = Vec::new();
+        let size = self.compute_all_symbols(
+            &self.modules.path(),
+            offset,
+            field,
+            SymbolMemorySpace::Banked(bank),
+            &mut symbols,
+        )?;
+        self.banked_symbols.extend(symbols);
+        self.banks_alloc.insert(bank, offset + size);
+        Ok(())
+    }
+
+    pub fn stack_address(&self) -> u16 {
+        self.stack_symbols_alloc
+    }
+
+    /// Allocate stack address, associated to the given field.
+    /// Returns the first allocated address.
+    pub fn alloc_stack_field(&mut self, field: &Field<'_>) -> Result<u16, CompileError> {
+        self.check_undefined(&field.ident)?;
+
+        let mut symbols = Vec::new();
+        let size = self.compute_all_symbols(
+            &String::new(),
+            self.stack_symbols_alloc,
+            field,
+            SymbolMemorySpace::Stack,
+            &mut symbols,
+        )?;
+        self.stack_symbols.extend(symbols);
+
+        let alloc = self.stack_symbols_alloc;
+        self.stack_symbols_alloc += size;
+        Ok(alloc)
+    }
+
+    /// Allocate anonymous stack memory (compiler temporaries).
+    /// Returns the first allocated address.
+    pub fn alloc_stack(&mut self, size: u16) -> u16 {
+        let alloc = self.stack_symbols_alloc;
+        self.stack_symbols_alloc += size;
+        alloc
+    }
+
+    /// Locates a symbol by name, if it's defined.
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.modules.resolve(name, |name| {
+            self.stack_symbols
+                .iter()
+                .chain(self.static_symbols.iter())
+                .chain(self.const_symbols.iter())
+                .chain(self.absolute_symbols.iter())
+                .chain(self.banked_symbols.iter())
+                .find(|s| s.name == name)
+        })
+    }
+
+    /// Locates the symbol named by a path (`FOO`, `FOO::bar`, `Enum::Variant`).
+    /// Fails if the symbol is not defined, or if it is private to another
+    /// module.
+    pub fn symbol(&self, path: &Path<'_>) -> Result<&Symbol, CompileError> {
+        let name: Vec<_> = path.iter().map(|ident| ident.name().to_string()).collect();
+        let name = name.join("::");
+        match self.find(&name) {
+            Some(symbol) => {
+                if let Some((declaration, visibility)) = self.declaration(&symbol.name) {
+                    self.modules
+                        .check_visible(&declaration, visibility, path.span())?;
+                }
+                Ok(symbol)
+            }
+            None => Err(CompileError::UndefinedSymbol {
+                name,
+                span: path.span(),
+            }),
+        }
+    }
+
+    // fails if a symbol with the name of the identifier is already defined.
+    fn check_undefined(&self, ident: &Ident<'_>) -> Result<(), CompileError> {
+        let name = ident.name().to_string();
+        if self.is_undefined(&name) {
+            Ok(())
+        } else {
+            Err(CompileError::DuplicateSymbol {
+                name,
+                span: ident.span(),
+            })
+        }
+    }
+
+    // fails if a symbol with the name of the identifier is already declared
+    // in the module. Returns the name of the symbol.
+    fn check_undeclared(&self, ident: &Ident<'_>) -> Result<String, CompileError> {
+        let name = self.modules.qualified(ident.name());
+        if self.is_undefined(&name) {
+            Ok(name)
+        } else {
+            Err(CompileError::DuplicateSymbol {
+                name,
+                span: ident.span(),
+            })
+        }
+    }
+
+    fn is_undefined(&self, name: &str) -> bool {
+        !(Self::_is_undefined(name, &self.absolute_symbols)
+            || Self::_is_undefined(name, &self.static_symbols)
+            || Self::_is_undefined(name, &self.const_symbols)
+            || Self::_is_undefined(name, &self.stack_symbols)
+            || Self::_is_undefined(name, &self.banked_symbols))
+    }
+
+    fn _is_undefined(name: &str, symbols: &[Symbol]) -> bool {
+        symbols.iter().any(|s| s.name == name)
+    }
+
+    // TODO optimize because I'm far too sleepy to do this now.
+    //  No need to be calling size_of all over the place here.
+    fn compute_all_symbols(
+        &self,
+        prefix: &str,
+        offset: u16,
+        field: &Field<'_>,
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<u16, CompileError> {
+        // append field identifier to the queried field.
+        let name = if prefix.is_empty() {
+            field.ident.name().to_string()
+        } else {
+            let mut prefix = prefix.to_string();
+            prefix.push_str(&format!("::{}", field.ident.name()));
+            prefix
+        };
+        let size =
+            self.compute_type_symbols(name.clone(), offset, &field.type_, memory_space, symbols)?;
+        if let Some(bits) = &field.bits {
+            self.compute_bits_symbols(&name, offset, &field.type_, bits, memory_space, symbols)?;
+        }
+        Ok(size)
+    }
+
+    // bitfields are packed LSB-first. Each one is a symbol at the offset of the
+    // integer holding it (`name::bitfield`).
+    fn compute_bits_symbols(
+        &self,
+        name: &str,
+        offset: u16,
+        type_: &Type<'_>,
+        bits: &ast::Bits<'_>,
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<(), CompileError> {
+        let inner = Layout::with_symbols(type_, Some(self));
+        let size = inner.size();
+        let mut shift = 0;
+        for field in &bits.fields {
+            let name = format!("{}::{}", name, field.ident.name());
+            let width =
+                const_expr(&field.width, Some(self)).expect("Not a constant bitfield width");
+            // only unsigned integers hold bitfields
+            let integer = matches!(inner, Layout::U8 | Layout::U16);
+            if !integer || width == 0 || shift + width > size * 8 {
+                let span = field.width.span();
+                return Err(CompileError::InvalidBitfield { name, span });
+            }
+            symbols.push(Symbol {
+                name,
+                offset,
+                size,
+                layout: Layout::Bits {
+                    inner: Box::new(inner.clone()),
+                    shift: shift as u8,
+                    width: width as u8,
+                },
+                memory_space,
+            });
+            shift += width;
+        }
+        Ok(())
+    }
+
+    fn compute_type_symbols(
+        &self,
+        name: String,
+        offset: u16,
+        type_: &Type<'_>,
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<u16, CompileError> {
+        let layout = Layout::with_symbols(type_, Some(self));
+        let size = layout.size();
+        match type_ {
+            // type aliases reuse the (relative) symbols of the aliased type
+            Type::Path(path) if self.alias(path).is_some() => {
+                for symbol in self.alias(path).unwrap() {
+                    let name = match &symbol.name[..] {
+                        "" => name.clone(),
+                        field => format!("{}::{}", name, field),
+                    };
+                    symbols.push(Symbol {
+                        name,
+                        offset: offset + symbol.offset,
+                        memory_space,
+                        ..symbol.clone()
+                    });
+                }
+            }
+            Type::U8(_)
+            | Type::I8(_)
+            | Type::U16(_)
+            | Type::I16(_)
+            | Type::Bool(_)
+            | Type::Fixed(_)
+            | Type::Array(_)
+            | Type::Pointer(_)
+            | Type::Fn(_)
+            | Type::Tuple(_)
+            | Type::Path(_) => {
+                symbols.push(Symbol {
+                    name,
+                    offset,
+                    size,
+                    layout,
+                    memory_space,
+                });
+            }
+            Type::Struct(struct_) => {
+                // the struct itself is a symbol too (for whole-struct copies)
+                symbols.push(Symbol {
+                    name: name.clone(),
+                    offset,
+                    size,
+                    layout,
+                    memory_space,
+                });
+                self.compute_struct_symbols(
+                    &name,
+                    offset,
+                    &struct_.attributes,
+                    &struct_.fields,
+                    memory_space,
+                    symbols,
+                )?;
+            }
+            Type::Union(union) => {
+                symbols.push(Symbol {
+                    name: name.clone(),
+                    offset,
+                    size,
+                    layout,
+                    memory_space,
+                });
+                self.compute_union_symbols(
+                    &name,
+                    offset,
+                    &union.attributes,
+                    &union.fields,
+                    memory_space,
+                    symbols,
+                )?;
+            }
+        }
+        Ok(size)
+    }
+
+    // members of a struct are laid out one after the other, each one aligned
+    // to its own alignment (unless the struct is packed). The fields of
+    // anonymous structs and unions are hoisted into the enclosing namespace.
+    fn compute_struct_symbols(
+        &self,
+        prefix: &str,
+        offset: u16,
+        attributes: &[Attribute<'_>],
+        members: &[Member<'_>],
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<u16, CompileError> {
+        let packed = is_packed(attributes);
+        let mut size = 0;
+        for member in members {
+            if !packed {
+                size = align_to(size, member_align(member, Some(self)));
+            }
+            #[rustfmt::skip] let member_size = self.compute_member_symbols(prefix, offset + size, member, memory_space, symbols)?;
+            size += member_size;
+        }
+        Ok(align_to(
+            size,
+            members_align(attributes, members, Some(self)),
+        ))
+    }
+
+    // members of an union all begin at the same offset.
+    fn compute_union_symbols(
+        &self,
+        prefix: &str,
+        offset: u16,
+        attributes: &[Attribute<'_>],
+        members: &[Member<'_>],
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<u16, CompileError> {
+        let mut size = 0;
+        for member in members {
+            #[rustfmt::skip] let member_size = self.compute_member_symbols(prefix, offset, member, memory_space, symbols)?;
+            size = size.max(member_size);
+        }
+        Ok(align_to(
+            size,
+            members_align(attributes, members, Some(self)),
+        ))
+    }
+
+    fn compute_member_symbols(
+        &self,
+        prefix: &str,
+        offset: u16,
+        member: &Member<'_>,
+        memory_space: SymbolMemorySpace,
+        symbols: &mut Vec<Symbol>,
+    ) -> Result<u16, CompileError> {
+        match member {
+            Member::Field(field) => {
+                self.compute_all_symbols(prefix, offset, field, memory_space, symbols)
+            }
+            Member::Struct(struct_) => self.compute_struct_symbols(
+                prefix,
+                offset,
+                &struct_.attributes,
+                &struct_.fields,
+                memory_space,
+                symbols,
+            ),
+            Member::Union(union) => self.compute_union_symbols(
+                prefix,
+                offset,
+                &union.attributes,
+                &union.fields,
+                memory_space,
+                symbols,
+            ),
+        }
+    }
+}
+
+#[deprecated]
+pub fn compute_const_expr_into_vec<B: ByteOrder>(
+    layout: &Layout,
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    out: &mut Vec<u8>,
+) {
+    match (layout, expression) {
+        // either an unsigned or a sign extended 8bit value
+        (Layout::U8 | Layout::I8, expression) => {
+            let lit = const_expr(expression, Some(symbol_alloc)).unwrap();
+            assert!(lit <= 0xff || lit >= 0xff80);
+            out.push(lit as u8);
+        }
+        // fixed-point values are never folded, so only literals are constant
+        (Layout::Fixed, Expression::Lit(lit)) => {
+            let lit = lit.fixed_value().expect("Expected a fixed-point literal");
+            let offset = out.len();
+            out.push(0);
+            out.push(0);
+            B::write_u16(&mut out[offset..], lit);
+        }
+        (Layout::U16 | Layout::I16, expression) => {
+            let lit = const_expr(expression, Some(symbol_alloc)).unwrap();
+            let offset = out.len();
+            out.push(0);
+            out.push(0);
+            B::write_u16(&mut out[offset..], lit);
+        }
+        (Layout::Pointer(_), expr @ Expression::Lit(_)) => {
+            let lit = const_expr(expr, Some(symbol_alloc)).unwrap();
+            let offset = out.len();
+            // append value with the correct endianness
+            out.push(0);
+            out.push(0);
+            B::write_u16(&mut out[offset..], lit);
+        }
+        (Layout::Array { inner, len }, Expression::Lit(lit)) => {
+            assert_eq!(&Layout::U8, inner.as_ref());
+            let data = symbol_alloc.encode_str(lit).unwrap();
+            assert_eq!(*len as usize, data.len());
+            out.extend(data);
+        }
+        (Layout::Array { inner, len }, Expression::Array(array)) => {
+            assert_eq!(*len as usize, array.inner.len());
+            for item in &array.inner {
+                compute_const_expr_into_vec::<B>(inner, item, symbol_alloc, out);
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+/// Virtual register allocator.
+///
+/// Every allocation returns a new virtual register, so each intermediate value
+/// has its own register. The registers of the compiled routines are mapped to
+/// as few registers as possible afterwards, from the liveness of their values
+/// (see [`opt::allocate_registers`](crate::opt::allocate_registers)).
+#[derive(Default)]
+pub struct RegisterAlloc {
+    // 64 registers per word
+    bitset: Vec<u64>,
+    // next virtual register
+    next: usize,
+}
+
+impl Drop for RegisterAlloc {
+    fn drop(&mut self) {
+        assert!(self.bitset.iter().all(|word| *word == 0), "register leak");
+    }
+}
+
+impl RegisterAlloc {
+    /// Returns number of allocated registers.
+    #[cfg(test)]
+    pub fn len(&self) -> u32 {
+        self.bitset.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Allocate register.
+    pub fn alloc(&mut self) -> usize {
+        let register = self.next;
+        self.next += 1;
+        self.set(register, true);
+        register
+    }
+
+    /// Free register being used.
+    pub fn free(&mut self, index: usize) {
+        assert!(self.get(index));
+        self.set(index, false);
+    }
+
+    /// Free all the registers being used.
+    pub fn clear(&mut self) {
+        self.bitset.clear();
+    }
+
+    fn get(&self, index: usize) -> bool {
+        let bit = 1 << (index % 64);
+        self.bitset
+            .get(index / 64)
+            .is_some_and(|word| (word & bit) != 0)
+    }
+
+    fn set(&mut self, index: usize, value: bool) -> bool {
+        let old = self.get(index);
+        if index / 64 >= self.bitset.len() {
+            self.bitset.resize(index / 64 + 1, 0);
+        }
+        let bit = 1 << (index % 64);
+        let word = &mut self.bitset[index / 64];
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+        old
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RegisterAlloc;
+
+    #[test]
+    fn alloc() {
+        let mut alloc = RegisterAlloc::default();
+
+        alloc.alloc();
+        alloc.alloc();
+        alloc.alloc();
+        alloc.alloc();
+        assert_eq!(vec![0b1111], alloc.bitset);
+        alloc.set(1, false);
+        assert_eq!(vec![0b1101], alloc.bitset);
+        alloc.alloc();
+        assert_eq!(vec![0b11101], alloc.bitset);
+        alloc.bitset.clear();
+    }
+
+    #[test]
+    fn set() {
+        let mut alloc = RegisterAlloc::default();
+        alloc.set(0, true);
+        alloc.set(2, true);
+        alloc.set(4, true);
+        alloc.set(6, true);
+        assert!(alloc.get(0));
+        assert!(!alloc.get(1));
+        assert!(alloc.get(2));
+        assert!(!alloc.get(3));
+        assert!(alloc.get(4));
+        assert!(!alloc.get(5));
+        assert!(alloc.get(6));
+        assert_eq!(vec![0b1010101], alloc.bitset);
+        alloc.set(2, false);
+        alloc.set(4, false);
+        alloc.set(6, false);
+        assert_eq!(vec![0b1], alloc.bitset);
+        alloc.bitset.clear();
+    }
+
+    #[test]
+    fn alloc_fresh() {
+        let mut alloc = RegisterAlloc::default();
+        for register in 0..100 {
+            assert_eq!(register, alloc.alloc());
+        }
+        assert_eq!(100, alloc.len());
+        // freed registers aren't reused
+        alloc.free(70);
+        alloc.free(3);
+        assert_eq!(100, alloc.alloc());
+        assert_eq!(99, alloc.len());
+        for register in (0..101).filter(|r| *r != 3 && *r != 70) {
+            alloc.free(register);
+        }
+        assert_eq!(0, alloc.len());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{opcodes::Statement, parser::lex::span::Span};
+use std::ops::{Deref, DerefMut};
+
+/// Block of compiled statements, along with the spans of the source code they
+/// were compiled from.
+///
+/// Dereferences to the statements, so expressions are compiled into it like
+/// into any other `Vec<Statement>`. Statements pushed that way have no span
+/// until [`set_span`](Self::set_span) is called.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Block {
+    statements: Vec<Statement>,
+    // may be shorter than `statements` (trailing statements have no span)
+    spans: Vec<Option<Span>>,
+}
+
+impl Block {
+    /// Set the span of the statements from the given index that don't have one
+    /// already (the ones compiled from nested AST statements keep theirs).
+    pub(crate) fn set_span(&mut self, from: usize, span: Span) {
+        self.spans.resize(self.statements.len(), None);
+        for s in &mut self.spans[from..] {
+            s.get_or_insert(span);
+        }
+    }
+
+    /// Append the statements of another block, and their spans.
+    pub(crate) fn extend(&mut self, block: Self) {
+        self.spans.resize(self.statements.len(), None);
+        self.statements.extend(block.statements);
+        self.spans.extend(block.spans);
+    }
+
+    /// Statements and their spans. The spans are empty if none of the
+    /// statements has one.
+    pub(crate) fn into_parts(mut self) -> (Vec<Statement>, Vec<Option<Span>>) {
+        if self.spans.iter().all(Option::is_none) {
+            self.spans.clear();
+        } else {
+            self.spans.resize(self.statements.len(), None);
+        }
+        (self.statements, self.spans)
+    }
+}
+
+impl From<Vec<Statement>> for Block {
+    fn from(statements: Vec<Statement>) -> Self {
+        Self {
+            statements,
+            spans: Vec::new(),
+        }
+    }
+}
+
+impl Deref for Block {
+    type Target = Vec<Statement>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.statements
+    }
+}
+
+impl DerefMut for Block {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.statements
+    }
+}
@@ -0,0 +1,187 @@
+use crate::parser::lex::span::Span;
+use std::fmt;
+
+/// Error compiling a program into IR.
+///
+/// The parser rejects most of these programs on its own. The ones it can't
+/// (uses of [extern symbols](crate::parser::ContextBuilder::extern_symbol)
+/// that aren't declared, erroneous ASTs, ...) fail to compile with the name and
+/// the location of the offending symbol (or statement).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CompileError {
+    /// Declaration of a symbol (static, const, local, enum, type alias or
+    /// function) already declared in the same scope.
+    DuplicateSymbol {
+        /// Name of the symbol.
+        name: String,
+
+        /// Span of the identifier of the second declaration.
+        span: Span,
+    },
+
+    /// Use of a symbol that isn't declared.
+    UndefinedSymbol {
+        /// Name of the symbol (`FOO::bar` for fields and enum variants).
+        name: String,
+
+        /// Span of the path to the symbol.
+        span: Span,
+    },
+
+    /// Bitfield of zero width, that doesn't fit in the bits left in its field,
+    /// or of a field that isn't a `u8` or a `u16`.
+    InvalidBitfield {
+        /// Name of the bitfield (`FOO::bar`).
+        name: String,
+
+        /// Span of the width of the bitfield.
+        span: Span,
+    },
+
+    /// Use of a symbol in an expression of another width (a word where a
+    /// byte is expected, or the other way around).
+    MismatchedWidth {
+        /// Name of the symbol.
+        name: String,
+
+        /// Span of the path to the symbol.
+        span: Span,
+    },
+
+    /// Statement that failed to parse (see
+    /// [`ContextBuilder::error_tolerant`](crate::parser::ContextBuilder::error_tolerant)).
+    Erroneous {
+        /// Span of the statement.
+        span: Span,
+    },
+
+    /// Use of a private declaration of a module from outside of it.
+    PrivateSymbol {
+        /// Name of the declaration (`module::FOO`).
+        name: String,
+
+        /// Span of the path to the symbol.
+        span: Span,
+    },
+
+    /// Bank number of a banked static or function that doesn't fit in a byte.
+    BankOutOfRange {
+        /// Span of the bank number.
+        span: Span,
+    },
+
+    /// Initializer of a static at a fixed location (absolute or banked).
+    InitializedStatic {
+        /// Name of the static.
+        name: String,
+
+        /// Span of the identifier of the static.
+        span: Span,
+    },
+
+    /// Second handler of the same interrupt.
+    DuplicateHandler {
+        /// Name of the interrupt (`vblank`, `timer`, ...).
+        name: String,
+
+        /// Span of the interrupt of the second handler.
+        span: Span,
+    },
+
+    /// `break` or `continue` statement outside of a loop, or with a label
+    /// that no enclosing loop has.
+    OutsideLoop {
+        /// Span of the statement.
+        span: Span,
+    },
+
+    /// Constant expression used as a byte whose value doesn't fit in one.
+    OutOfRange {
+        /// Span of the constant expression.
+        span: Span,
+    },
+
+    /// Address of a local variable or function argument (`@x`). Pointers can
+    /// only address static memory.
+    StackAddress {
+        /// Span of the address-of expression.
+        span: Span,
+    },
+
+    /// Expression the compiler doesn't support in its context (such as a
+    /// struct assignment to a value that isn't a path).
+    Unsupported {
+        /// Span of the expression.
+        span: Span,
+    },
+}
+
+impl CompileError {
+    /// Name of the offending symbol, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::DuplicateSymbol { name, .. }
+            | Self::UndefinedSymbol { name, .. }
+            | Self::InvalidBitfield { name, .. }
+            | Self::MismatchedWidth { name, .. }
+            | Self::PrivateSymbol { name, .. }
+            | Self::InitializedStatic { name, .. }
+            | Self::DuplicateHandler { name, .. } => Some(name),
+            Self::Erroneous { .. }
+            | Self::BankOutOfRange { .. }
+            | Self::OutsideLoop { .. }
+            | Self::OutOfRange { .. }
+            | Self::StackAddress { .. }
+            | Self::Unsupported { .. } => None,
+        }
+    }
+
+    /// Location of the offending symbol (or statement) in the source code.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::DuplicateSymbol { span, .. }
+            | Self::UndefinedSymbol { span, .. }
+            | Self::InvalidBitfield { span, .. }
+            | Self::MismatchedWidth { span, .. }
+            | Self::Erroneous { span }
+            | Self::PrivateSymbol { span, .. }
+            | Self::BankOutOfRange { span }
+            | Self::InitializedStatic { span, .. }
+            | Self::DuplicateHandler { span, .. }
+            | Self::OutsideLoop { span }
+            | Self::OutOfRange { span }
+            | Self::StackAddress { span }
+            | Self::Unsupported { span } => *span,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateSymbol { name, .. } => write!(f, "Duplicate symbol `{}`", name),
+            Self::UndefinedSymbol { name, .. } => write!(f, "Undefined symbol `{}`", name),
+            Self::InvalidBitfield { name, .. } => write!(f, "Invalid bitfield `{}`", name),
+            Self::MismatchedWidth { name, .. } => write!(f, "Mismatched width of `{}`", name),
+            Self::Erroneous { .. } => write!(f, "Erroneous statement"),
+            Self::PrivateSymbol { name, .. } => write!(f, "Private symbol `{}`", name),
+            Self::BankOutOfRange { .. } => write!(f, "Bank number out of range"),
+            Self::InitializedStatic { name, .. } => {
+                write!(
+                    f,
+                    "Static `{}` at a fixed location can't be initialized",
+                    name
+                )
+            }
+            Self::DuplicateHandler { name, .. } => {
+                write!(f, "Interrupt handler `{}` already defined", name)
+            }
+            Self::OutsideLoop { .. } => write!(f, "Break or continue outside of a loop"),
+            Self::OutOfRange { .. } => write!(f, "Constant out of range for a byte"),
+            Self::StackAddress { .. } => write!(f, "Address of stack memory can't be taken"),
+            Self::Unsupported { .. } => write!(f, "Unsupported expression"),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -0,0 +1,2398 @@
+use crate::{
+    byteorder::ByteOrder,
+    compile::{
+        alloc::{Fn, FnAlloc, RegisterAlloc, Symbol, SymbolAlloc, SymbolMemorySpace},
+        error::CompileError,
+        layout::{bits_mask, Layout},
+    },
+    opcodes::{Destination, Location, Pointer, Source, Statement},
+    parser::ast::{
+        expression::{eval, Call, Conditional, LispNode, SizeOfOperand},
+        Builtin, Expression, Path, Type,
+    },
+    parser::lex::span::{Span, Spanned},
+};
+
+// match to a particular `Expression` enum variant.
+// panics if not possible.
+// match_expr!(expr, Expression::Index);
+macro_rules! match_expr {
+    ($expr:expr, $enum:ident :: $var:ident) => {
+        match $expr {
+            $enum::$var(inner) => inner,
+            _ => panic!(),
+        }
+    };
+
+    ($expr:expr, $enum:ident :: $var:ident, $field:ident) => {
+        match $expr {
+            $enum::$var { $field, .. } => $field,
+            _ => panic!(),
+        }
+    };
+}
+
+/// Utility function to free all registers referenced inside a Source.
+pub fn free_source_registers<T>(source: &Source<T>, register_alloc: &mut RegisterAlloc) {
+    match source {
+        Source::Register(r) => register_alloc.free(*r),
+        Source::Pointer {
+            offset: Some(offset),
+            ..
+        } => free_source_registers(offset, register_alloc),
+        Source::Indirect(pointer) => free_source_registers(pointer, register_alloc),
+        _ => {}
+    }
+}
+
+/// Utility function to free any registers referenced within a given
+/// `Destination`.
+pub fn free_destination_registers(destination: &Destination, register_alloc: &mut RegisterAlloc) {
+    match destination {
+        Destination::Register(r) => register_alloc.free(*r),
+        Destination::Pointer {
+            offset: Some(offset),
+            ..
+        } => free_source_registers(offset, register_alloc),
+        Destination::Indirect(pointer) => free_source_registers(pointer, register_alloc),
+        _ => {}
+    }
+}
+
+fn destination_to_source<T>(destination: &Destination) -> Source<T> {
+    use Destination::*;
+    match destination {
+        Pointer { base, offset } => Source::Pointer {
+            base: base.clone(),
+            offset: offset.clone(),
+        },
+        Indirect(pointer) => Source::Indirect(pointer.clone()),
+        Register(register) => Source::Register(*register),
+    }
+}
+
+/// Evaluate and return the result of a constant expression.
+/// If the passed expression is not a constant expression, returns `None`.
+///
+/// Operators are evaluated by [`eval`](parser::ast::expression::eval). Paths
+/// to consts, casts and `sizeof` are resolved with the symbols, and calls to
+/// const functions have the value the parser evaluated.
+pub fn const_expr<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: Option<&SymbolAlloc<B>>,
+) -> Option<u16> {
+    use Expression as E;
+    eval::eval(
+        expression,
+        &mut |expression| match (symbol_alloc, expression) {
+            (Some(symbol_alloc), E::Path(path)) => {
+                let name = path_to_symbol_name(path);
+                let symbol = symbol_alloc.find(&name)?;
+                match symbol.memory_space {
+                    SymbolMemorySpace::Const => {
+                        let data = &symbol_alloc.const_data()[symbol.offset as usize..];
+                        match symbol.layout {
+                            // the raw values of fixed-point consts aren't
+                            // integers, so they are never folded
+                            Layout::Fixed => None,
+                            Layout::U16 | Layout::I16 => Some(B::read_u16(data)),
+                            _ => Some(data[0] as _),
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            // casts to a 8bit type keep the low byte. 8bit signed values are sign extended
+            (_, E::Cast(e)) => {
+                let n = const_expr(&e.inner.inner, symbol_alloc)?;
+                let signed =
+                    matches!(&e.inner.inner, E::Cast(c) if matches!(c.inner.type_, Type::I8(_)));
+                Some(match Layout::with_symbols(&e.inner.type_, symbol_alloc) {
+                    Layout::Fixed => return None,
+                    Layout::U8 | Layout::I8 => n & 0xff,
+                    _ if signed => n as u8 as i8 as u16,
+                    _ => n,
+                })
+            }
+            (_, E::SizeOf(e)) => match &e.inner.inner {
+                SizeOfOperand::Type(type_) => {
+                    Some(Layout::with_symbols(type_, symbol_alloc).size())
+                }
+                // enums are represented as u8
+                SizeOfOperand::Expression(E::Path(path)) if matches!(symbol_alloc, Some(s) if s.is_enum(path)) => {
+                    Some(Layout::U8.size())
+                }
+                // type aliases
+                SizeOfOperand::Expression(E::Path(path)) if matches!(symbol_alloc, Some(s) if s.alias_layout(path).is_some()) => {
+                    Some(symbol_alloc?.alias_layout(path)?.size())
+                }
+                SizeOfOperand::Expression(expression) => {
+                    Some(value_layout(expression, symbol_alloc?)?.size())
+                }
+            },
+            // calls to const functions, evaluated by the parser
+            (_, E::Call(e)) => e.inner.value,
+            _ => None,
+        },
+    )
+}
+
+/// Compile assignment statement/expression (including `++` and `--`).
+/// These expressions evaluate to no value in particular.
+pub fn compile_assign<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    macro_rules! arithmetic_branch {
+        ($var:ident, $var_w:ident, $node:expr) => {{
+            let destination = assign_destination(
+                &$node.inner.left,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            // free left and destination only (right is a copy of the former)
+            if is_word(&$node.inner.left, symbol_alloc) {
+                let left = destination_to_source(&destination);
+                #[rustfmt::skip] let right = compile_expr_u16(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&right, register_alloc);
+                free_destination_registers(&destination, register_alloc);
+                statements.push(Statement::$var_w {
+                    left,
+                    right,
+                    destination,
+                });
+            } else {
+                let left = destination_to_source(&destination);
+                #[rustfmt::skip] let right = compile_expr_u8(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&right, register_alloc);
+                free_destination_registers(&destination, register_alloc);
+                statements.push(Statement::$var {
+                    left,
+                    right,
+                    destination,
+                });
+            }
+        }};
+    }
+
+    // pointers are offset by multiples of the size of the data they point to
+    macro_rules! pointer_branch {
+        ($var_w:ident, $node:expr) => {{
+            let size = pointee(&$node.inner.left, symbol_alloc).unwrap().size();
+            #[rustfmt::skip] let destination = assign_destination(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let right = compile_offset(&$node.inner.right, size, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&right, register_alloc);
+            free_destination_registers(&destination, register_alloc);
+            statements.push(Statement::$var_w {
+                left: destination_to_source(&destination),
+                right,
+                destination,
+            });
+        }};
+    }
+
+    // increments & decrements are applied in place
+    macro_rules! unary_branch {
+        ($var:ident, $var_w:ident, $ptr:ident, $node:expr) => {{
+            let destination = assign_destination(
+                &$node.inner.inner,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            free_destination_registers(&destination, register_alloc);
+            let pointee = pointee(&$node.inner.inner, symbol_alloc);
+            if let Some(size) = pointee.map(|l| l.size()).filter(|s| *s != 1) {
+                statements.push(Statement::$ptr {
+                    left: destination_to_source(&destination),
+                    right: Source::Literal(size),
+                    destination,
+                });
+            } else if is_word(&$node.inner.inner, symbol_alloc) {
+                statements.push(Statement::$var_w {
+                    source: destination_to_source(&destination),
+                    destination,
+                });
+            } else {
+                statements.push(Statement::$var {
+                    source: destination_to_source(&destination),
+                    destination,
+                });
+            }
+        }};
+    }
+
+    use Expression as E;
+    match expression {
+        E::Assign(node) if bits_symbol(&node.inner.left, symbol_alloc).is_some() => {
+            let symbol = bits_symbol(&node.inner.left, symbol_alloc).unwrap();
+            #[rustfmt::skip] compile_bits_assign(symbol, &node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+        }
+        // structs (and struct literals) are copied field by field, and arrays
+        // from other arrays
+        E::Assign(node)
+            if matches!(node.inner.right, E::StructLit(_))
+                || matches!(
+                    value_layout(&node.inner.left, symbol_alloc),
+                    Some(Layout::Struct(_)) | Some(Layout::Union(_))
+                )
+                || matches!(node.inner.right, E::Path(_))
+                    && matches!(
+                        value_layout(&node.inner.left, symbol_alloc),
+                        Some(Layout::Array { .. })
+                    ) =>
+        {
+            let (name, span) = match &node.inner.left {
+                E::Path(path) => (symbol_alloc.symbol(path)?.name.clone(), path.span()),
+                left => return Err(CompileError::Unsupported { span: left.span() }),
+            };
+            #[rustfmt::skip] compile_into_symbol(&node.inner.right, &name, span, symbol_alloc, fn_alloc, register_alloc, statements)?;
+        }
+        // FIXME assuming array inner type is u8 :/
+        // TODO generalize to any type composition!!
+        E::Assign(node) if matches!(node.inner.right, E::Array(_)) => {
+            let array = match_expr!(&node.inner.right, E::Array);
+            #[rustfmt::skip] let destination = assign_destination(&node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let mut offset = 0;
+            for expression in &array.inner {
+                #[rustfmt::skip] let source = compile_expr_u8(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                let mut destination = destination.clone();
+                let base = match_expr!(destination, Destination::Pointer, base).offset(offset);
+                *match_expr!(&mut destination, Destination::Pointer, base) = base;
+                free_source_registers(&source, register_alloc);
+                free_destination_registers(&destination, register_alloc);
+                statements.push(Statement::Ld {
+                    source,
+                    destination,
+                });
+                offset += 1;
+            }
+        }
+        E::Assign(node) if is_word(&node.inner.left, symbol_alloc) => {
+            #[rustfmt::skip] let destination = assign_destination(&node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let source = compile_expr_u16(&node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            free_destination_registers(&destination, register_alloc);
+            statements.push(Statement::LdW {
+                source,
+                destination,
+            });
+        }
+        E::Assign(node) => {
+            #[rustfmt::skip] let destination = assign_destination(&node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let source = compile_expr_u8(&node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            free_destination_registers(&destination, register_alloc);
+            statements.push(Statement::Ld {
+                source,
+                destination,
+            });
+        }
+        E::PlusAssign(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            pointer_branch!(AddW, node)
+        }
+        E::MinusAssign(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            pointer_branch!(SubW, node)
+        }
+        E::PlusAssign(node) => arithmetic_branch!(Add, AddW, node),
+        E::MinusAssign(node) => arithmetic_branch!(Sub, SubW, node),
+        E::MulAssign(node)
+            if is_fixed(&node.inner.left, symbol_alloc)
+                && is_fixed(&node.inner.right, symbol_alloc) =>
+        {
+            #[rustfmt::skip] let destination = assign_destination(&node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let right = compile_expr_u16(&node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let left = destination_to_source(&destination);
+            let product = compile_fixed_mul(&left, &right, register_alloc, statements);
+            free_source_registers(&product, register_alloc);
+            free_source_registers(&right, register_alloc);
+            free_destination_registers(&destination, register_alloc);
+            statements.push(Statement::LdW {
+                source: product,
+                destination,
+            });
+        }
+        E::MulAssign(node) => arithmetic_branch!(Mul, MulW, node),
+        E::DivAssign(node) if is_signed(&node.inner.left, symbol_alloc) => {
+            arithmetic_branch!(DivS, DivSW, node)
+        }
+        E::DivAssign(node) => arithmetic_branch!(Div, DivW, node),
+        E::AndAssign(node) => arithmetic_branch!(And, AndW, node),
+        E::OrAssign(node) => arithmetic_branch!(Or, OrW, node),
+        E::XorAssign(node) => arithmetic_branch!(Xor, XorW, node),
+        E::Increment(node) => unary_branch!(Inc, IncW, AddW, node),
+        E::Decrement(node) => unary_branch!(Dec, DecW, SubW, node),
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+// whether an expression (destination of an assignment, match scrutinee, ...)
+// is a 16bit word (or a pointer).
+pub fn is_word<B: ByteOrder>(expression: &Expression<'_>, symbol_alloc: &SymbolAlloc<B>) -> bool {
+    is_wide(&value_layout(expression, symbol_alloc))
+}
+
+// layout of the data a pointer expression points to.
+fn pointee<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+) -> Option<Layout> {
+    match value_layout(expression, symbol_alloc)? {
+        Layout::Pointer(inner) => Some(*inner),
+        _ => None,
+    }
+}
+
+// layout of the value of an expression, if known (paths, casts & pointers).
+fn value_layout<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+) -> Option<Layout> {
+    match expression {
+        Expression::Path(path) => match &symbol_alloc.find(&path_to_symbol_name(path))?.layout {
+            // bitfields are read into an integer of the same type as the one holding them
+            Layout::Bits { inner, .. } => Some(*inner.clone()),
+            layout => Some(layout.clone()),
+        },
+        Expression::Cast(node) => Some(Layout::with_symbols(&node.inner.type_, Some(symbol_alloc))),
+        Expression::AddressOf(node) => Some(Layout::Pointer(Box::new(value_layout(
+            &node.inner,
+            symbol_alloc,
+        )?))),
+        Expression::Deref(node) => pointee(&node.inner, symbol_alloc),
+        // elements of an array
+        Expression::Index(node) => match value_layout(&node.inner.right, symbol_alloc)? {
+            Layout::Array { inner, .. } => Some(*inner),
+            _ => None,
+        },
+        // pointer arithmetic
+        Expression::Add(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            value_layout(&node.inner.left, symbol_alloc)
+        }
+        Expression::Sub(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            value_layout(&node.inner.left, symbol_alloc)
+        }
+        _ => None,
+    }
+}
+
+// whether a value layout is 16bit wide (words and pointers).
+fn is_wide(layout: &Option<Layout>) -> bool {
+    matches!(
+        layout,
+        Some(Layout::U16)
+            | Some(Layout::I16)
+            | Some(Layout::Fixed)
+            | Some(Layout::Pointer(_))
+            | Some(Layout::Fn { .. })
+    )
+}
+
+// whether an expression is a fixed-point (`fx8.8`) value.
+fn is_fixed<B: ByteOrder>(expression: &Expression<'_>, symbol_alloc: &SymbolAlloc<B>) -> bool {
+    match expression {
+        Expression::Lit(lit) => lit.is_fixed(),
+        // arithmetic is of the type of the left operand
+        Expression::Add(node) => is_fixed(&node.inner.left, symbol_alloc),
+        Expression::Sub(node) => is_fixed(&node.inner.left, symbol_alloc),
+        Expression::Mul(node) => is_fixed(&node.inner.left, symbol_alloc),
+        Expression::Div(node) => is_fixed(&node.inner.left, symbol_alloc),
+        Expression::Conditional(node) => is_fixed(&node.inner.then, symbol_alloc),
+        expression => value_layout(expression, symbol_alloc) == Some(Layout::Fixed),
+    }
+}
+
+// whether an expression is a signed (`i8` or `i16`) integer, so it is
+// divided, shifted and compared as such.
+fn is_signed<B: ByteOrder>(expression: &Expression<'_>, symbol_alloc: &SymbolAlloc<B>) -> bool {
+    match expression {
+        // arithmetic is of the type of the left operand
+        Expression::Add(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Sub(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Mul(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Div(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::And(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Or(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Xor(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::LeftShift(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::RightShift(node) => is_signed(&node.inner.left, symbol_alloc),
+        Expression::Minus(node) => is_signed(&node.inner, symbol_alloc),
+        Expression::Conditional(node) => is_signed(&node.inner.then, symbol_alloc),
+        expression => matches!(
+            value_layout(expression, symbol_alloc),
+            Some(Layout::I8) | Some(Layout::I16)
+        ),
+    }
+}
+
+// comparisons are signed if either of their operands is.
+macro_rules! signed_operands {
+    ($node:expr, $symbol_alloc:expr) => {
+        is_signed(&$node.inner.left, $symbol_alloc) || is_signed(&$node.inner.right, $symbol_alloc)
+    };
+}
+
+// whether an operand of a comparison is a 16bit value. Literals that don't fit
+// in a byte (other than negative ones) are words too.
+fn is_word_operand<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+) -> bool {
+    if let Some(n) = const_expr(expression, Some(symbol_alloc)) {
+        return n > 0xff && n < 0xff80;
+    }
+    match expression {
+        // arithmetic is of the type of the left operand
+        Expression::Add(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Sub(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Mul(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Div(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::And(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Or(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Xor(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::LeftShift(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::RightShift(node) => is_word_operand(&node.inner.left, symbol_alloc),
+        Expression::Conditional(node) => is_word_operand(&node.inner.then, symbol_alloc),
+        expression => is_word(expression, symbol_alloc),
+    }
+}
+
+// compile an operand of a comparison of words, extending it if it is a byte.
+fn compile_word_operand<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u16>, CompileError> {
+    if let Some(n) = const_expr(expression, Some(symbol_alloc)) {
+        return Ok(Source::Literal(n));
+    }
+    if is_word_operand(expression, symbol_alloc) {
+        return compile_expr_u16(
+            expression,
+            symbol_alloc,
+            fn_alloc,
+            register_alloc,
+            statements,
+        );
+    }
+    #[rustfmt::skip] let source = compile_expr_u8(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+    let signed = is_signed(expression, symbol_alloc);
+    Ok(extend(source, signed, register_alloc, statements))
+}
+
+// comparisons are of words if either of their operands is a word.
+macro_rules! word_operands {
+    ($node:expr, $symbol_alloc:expr) => {
+        is_word_operand(&$node.inner.left, $symbol_alloc)
+            || is_word_operand(&$node.inner.right, $symbol_alloc)
+    };
+}
+
+// shift a 16bit value 8 bits to the left (or to the right), which turns an
+// integer into a fixed-point value (or the other way around).
+fn compile_fixed_shift(
+    source: Source<u16>,
+    left: bool,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Source<u16> {
+    free_source_registers(&source, register_alloc);
+    let store_register = register_alloc.alloc();
+    let right = Source::Literal(8);
+    let destination = Destination::Register(store_register);
+    statements.push(if left {
+        Statement::LeftShiftW {
+            left: source,
+            right,
+            destination,
+        }
+    } else {
+        Statement::RightShiftW {
+            left: source,
+            right,
+            destination,
+        }
+    });
+    Source::Register(store_register)
+}
+
+// compile the product of two fixed-point values, `(a * b) >> 8`, without the
+// 32bit intermediate result. Splitting the values into their integer and
+// fractional bytes (`a = ah.al`), it is
+// `((ah * bh) << 8) + ah * bl + al * bh + ((al * bl) >> 8)`.
+//
+// # Note
+// The registers referenced by `left` and `right` are not freed.
+fn compile_fixed_mul(
+    left: &Source<u16>,
+    right: &Source<u16>,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Source<u16> {
+    macro_rules! op {
+        ($var:ident, $left:expr, $right:expr) => {{
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::$var {
+                left: $left,
+                right: $right,
+                destination: Destination::Register(store_register),
+            });
+            Source::Register(store_register)
+        }};
+    }
+    let ah = op!(RightShiftW, left.clone(), Source::Literal(8));
+    let al = op!(AndW, left.clone(), Source::Literal(0xff));
+    let bh = op!(RightShiftW, right.clone(), Source::Literal(8));
+    let bl = op!(AndW, right.clone(), Source::Literal(0xff));
+
+    let high = op!(MulW, ah.clone(), bh.clone());
+    let ah_bl = op!(MulW, ah.clone(), bl.clone());
+    let al_bh = op!(MulW, al.clone(), bh.clone());
+    let low = op!(MulW, al.clone(), bl.clone());
+    for source in &[ah, al, bh, bl] {
+        free_source_registers(source, register_alloc);
+    }
+    let high = compile_fixed_shift(high, true, register_alloc, statements);
+    let low = compile_fixed_shift(low, false, register_alloc, statements);
+
+    let mut sum = high;
+    for source in vec![ah_bl, al_bh, low] {
+        free_source_registers(&sum, register_alloc);
+        free_source_registers(&source, register_alloc);
+        sum = op!(AddW, sum, source);
+    }
+    sum
+}
+
+// function (and its routine index) named by a path expression, if any.
+fn fn_path<'f>(expression: &Expression<'_>, fn_alloc: &'f FnAlloc) -> Option<(&'f Fn, usize)> {
+    match expression {
+        Expression::Path(path) => fn_alloc.find(&path_to_symbol_name(path)),
+        _ => None,
+    }
+}
+
+// instance of the generic function called by a call expression, if any.
+fn fn_instance<B: ByteOrder>(
+    call: &LispNode<'_, Call<'_>>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+) -> Option<(Fn, usize)> {
+    match &call.inner.left {
+        Expression::Path(path) => {
+            let args: Vec<_> = call
+                .inner
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    // only the length of array literals is known (which is all
+                    // that is needed to infer the generic parameters)
+                    Expression::Array(array) => Some(Layout::Array {
+                        inner: Box::new(Layout::U8),
+                        len: array.inner.len() as u16,
+                    }),
+                    arg => value_layout(arg, symbol_alloc),
+                })
+                .collect();
+            fn_alloc.instance(&path_to_symbol_name(path), &args)
+        }
+        _ => None,
+    }
+}
+
+// routine index of the function named by a path expression, if any.
+// Fails if the function is private to another module.
+fn fn_routine(
+    expression: &Expression<'_>,
+    fn_alloc: &FnAlloc,
+) -> Result<Option<usize>, CompileError> {
+    if let Expression::Path(path) = expression {
+        fn_alloc.check_visible(path)?;
+    }
+    Ok(fn_path(expression, fn_alloc).map(|(_, routine)| routine))
+}
+
+// builtin function called by a call expression, if any.
+fn builtin(call: &LispNode<'_, Call<'_>>) -> Option<Builtin> {
+    match &call.inner.left {
+        Expression::Path(path) => Builtin::from_path(path),
+        _ => None,
+    }
+}
+
+// compile a call to a builtin function into its dedicated statement. Returns
+// the `Source` holding the result of the builtins that return a byte.
+//
+// # Note
+// As with `compile_expr`, the callee is responsible for freeing any register
+// referenced by the returned `Source`.
+fn compile_builtin<B: ByteOrder>(
+    call: &LispNode<'_, Call<'_>>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Option<Source<u8>>, CompileError> {
+    let builtin = builtin(call).expect("Not a builtin!");
+    let args: Vec<_> = call.inner.args.iter().collect();
+    assert_eq!(builtin.arity(), args.len());
+    match builtin {
+        Builtin::Memcpy => {
+            #[rustfmt::skip] let destination = compile_expr_u16(args[0], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let source = compile_expr_u16(args[1], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let len = compile_expr_u16(args[2], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&destination, register_alloc);
+            free_source_registers(&source, register_alloc);
+            free_source_registers(&len, register_alloc);
+            statements.push(Statement::Memcpy {
+                source,
+                destination,
+                len,
+            });
+            Ok(None)
+        }
+        Builtin::Memset => {
+            #[rustfmt::skip] let destination = compile_expr_u16(args[0], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let value = compile_expr_u8(args[1], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let len = compile_expr_u16(args[2], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&destination, register_alloc);
+            free_source_registers(&value, register_alloc);
+            free_source_registers(&len, register_alloc);
+            statements.push(Statement::Memset {
+                value,
+                destination,
+                len,
+            });
+            Ok(None)
+        }
+        Builtin::SwapNibbles | Builtin::BcdAdjust => {
+            #[rustfmt::skip] let source = compile_expr_u8(args[0], symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            let store_register = register_alloc.alloc();
+            let destination = Destination::Register(store_register);
+            statements.push(match builtin {
+                Builtin::SwapNibbles => Statement::SwapNibbles {
+                    source,
+                    destination,
+                },
+                _ => Statement::BcdAdjust {
+                    source,
+                    destination,
+                },
+            });
+            Ok(Some(Source::Register(store_register)))
+        }
+        Builtin::Halt => {
+            statements.push(Statement::Halt);
+            Ok(None)
+        }
+        Builtin::EnableInterrupts => {
+            statements.push(Statement::EnableInterrupts);
+            Ok(None)
+        }
+    }
+}
+
+// compile an expression evaluating to a pointer.
+// panics if the expression is not a pointer.
+fn compile_pointer<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u16>, CompileError> {
+    assert!(
+        pointee(expression, symbol_alloc).is_some(),
+        "Dereference of a non-pointer expression"
+    );
+    compile_expr_u16(
+        expression,
+        symbol_alloc,
+        fn_alloc,
+        register_alloc,
+        statements,
+    )
+}
+
+// compile the offset `n` of a pointer arithmetic expression (`p + n`), scaled by
+// the `size` of the data the pointer points to.
+fn compile_offset<B: ByteOrder>(
+    expression: &Expression<'_>,
+    size: u16,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u16>, CompileError> {
+    if let Some(n) = const_expr(expression, Some(symbol_alloc)) {
+        return Ok(Source::Literal(n.wrapping_mul(size)));
+    }
+    let offset = match value_layout(expression, symbol_alloc) {
+        layout if is_wide(&layout) => compile_expr_u16(
+            expression,
+            symbol_alloc,
+            fn_alloc,
+            register_alloc,
+            statements,
+        )?,
+        layout => {
+            #[rustfmt::skip] let source = compile_expr_u8(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let signed = matches!(layout, Some(Layout::I8));
+            extend(source, signed, register_alloc, statements)
+        }
+    };
+    if size == 1 {
+        return Ok(offset);
+    }
+    free_source_registers(&offset, register_alloc);
+    let store_register = register_alloc.alloc();
+    statements.push(Statement::MulW {
+        left: offset,
+        right: Source::Literal(size),
+        destination: Destination::Register(store_register),
+    });
+    Ok(Source::Register(store_register))
+}
+
+// byte offset of an element of an array, from its index. Indices are scaled
+// by the size of the elements of the array.
+fn compile_index<B: ByteOrder>(
+    index: &Expression<'_>,
+    array: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u8>, CompileError> {
+    let size = match value_layout(array, symbol_alloc) {
+        Some(Layout::Array { inner, .. }) => inner.size() as u8,
+        _ => 1,
+    };
+    #[rustfmt::skip] let offset = compile_expr_u8(index, symbol_alloc, fn_alloc, register_alloc, statements)?;
+    match offset {
+        _ if size == 1 => Ok(offset),
+        Source::Literal(n) => Ok(Source::Literal(n.wrapping_mul(size))),
+        offset => {
+            free_source_registers(&offset, register_alloc);
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::Mul {
+                left: offset,
+                right: Source::Literal(size),
+                destination: Destination::Register(store_register),
+            });
+            Ok(Source::Register(store_register))
+        }
+    }
+}
+
+// symbol of a bitfield path expression.
+fn bits_symbol<'s, B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &'s SymbolAlloc<B>,
+) -> Option<&'s Symbol> {
+    match expression {
+        Expression::Path(path) => symbol_alloc
+            .find(&path_to_symbol_name(path))
+            .filter(|s| matches!(s.layout, Layout::Bits { .. })),
+        _ => None,
+    }
+}
+
+// shift and width of a bitfield symbol.
+fn bits(symbol: &Symbol) -> (u8, u8) {
+    match &symbol.layout {
+        Layout::Bits { shift, width, .. } => (*shift, *width),
+        _ => unreachable!(),
+    }
+}
+
+// bitfields are read by masking the integer holding them, and shifting the
+// masked bits down.
+macro_rules! bits_read {
+    ($ty:ty, $and:ident, $shift:ident, $symbol:expr, $register_alloc:expr, $statements:expr) => {{
+        let (shift, width) = bits($symbol);
+        let register = $register_alloc.alloc();
+        $statements.push(Statement::$and {
+            left: Source::Pointer {
+                base: $symbol.pointer(),
+                offset: None,
+            },
+            right: Source::Literal((bits_mask(width) << shift) as $ty),
+            destination: Destination::Register(register),
+        });
+        if shift != 0 {
+            $statements.push(Statement::$shift {
+                left: Source::Register(register),
+                right: Source::Literal(shift),
+                destination: Destination::Register(register),
+            });
+        }
+        Source::Register(register)
+    }};
+}
+
+fn compile_bits_u8(
+    symbol: &Symbol,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Source<u8> {
+    bits_read!(u8, And, RightShift, symbol, register_alloc, statements)
+}
+
+fn compile_bits_u16(
+    symbol: &Symbol,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Source<u16> {
+    bits_read!(u16, AndW, RightShiftW, symbol, register_alloc, statements)
+}
+
+// bitfields are written by clearing their bits from the integer holding them,
+// and or'ing the (masked and shifted) value in.
+fn compile_bits_assign<B: ByteOrder>(
+    symbol: &Symbol,
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    macro_rules! bits_branch {
+        ($ty:ty, $compile:ident, $and:ident, $or:ident, $shift:ident) => {{
+            let (shift, width) = bits(symbol);
+            let mask = bits_mask(width);
+            let value = match const_expr(expression, Some(symbol_alloc)) {
+                Some(n) => Source::Literal(((n & mask) << shift) as $ty),
+                None => {
+                    #[rustfmt::skip] let source = $compile(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                    free_source_registers(&source, register_alloc);
+                    let register = register_alloc.alloc();
+                    statements.push(Statement::$and {
+                        left: source,
+                        right: Source::Literal(mask as $ty),
+                        destination: Destination::Register(register),
+                    });
+                    if shift != 0 {
+                        statements.push(Statement::$shift {
+                            left: Source::Register(register),
+                            right: Source::Literal(shift),
+                            destination: Destination::Register(register),
+                        });
+                    }
+                    Source::Register(register)
+                }
+            };
+            let destination = Destination::Pointer {
+                base: symbol.pointer(),
+                offset: None,
+            };
+            let register = register_alloc.alloc();
+            statements.push(Statement::$and {
+                left: destination_to_source(&destination),
+                right: Source::Literal(!(mask << shift) as $ty),
+                destination: Destination::Register(register),
+            });
+            statements.push(Statement::$or {
+                left: Source::Register(register),
+                right: value.clone(),
+                destination,
+            });
+            register_alloc.free(register);
+            free_source_registers(&value, register_alloc);
+        }};
+    }
+
+    match &symbol.layout {
+        Layout::Bits { inner, .. } if **inner == Layout::U16 => {
+            bits_branch!(u16, compile_expr_u16, AndW, OrW, LeftShiftW)
+        }
+        _ => bits_branch!(u8, compile_expr_u8, And, Or, LeftShift),
+    }
+    Ok(())
+}
+
+// extend an 8bit source into a 16bit register.
+fn extend(
+    source: Source<u8>,
+    signed: bool,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Source<u16> {
+    free_source_registers(&source, register_alloc);
+    let store_register = register_alloc.alloc();
+    let destination = Destination::Register(store_register);
+    statements.push(if signed {
+        Statement::SignExt {
+            source,
+            destination,
+        }
+    } else {
+        Statement::Ext {
+            source,
+            destination,
+        }
+    });
+    Source::Register(store_register)
+}
+
+// compute the destination of an assignment expression
+fn assign_destination<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Destination, CompileError> {
+    use Expression as E;
+    Ok(match expression {
+        E::Path(path) => {
+            let symbol = symbol_alloc.symbol(path)?;
+            assert!(
+                !matches!(symbol.layout, Layout::Bits { .. }),
+                "Only `=` assignments to bitfields are supported"
+            );
+            Destination::Pointer {
+                base: symbol.pointer(),
+                offset: None,
+            }
+        }
+        E::Index(index) => {
+            #[rustfmt::skip] let offset = compile_index(&index.inner.left, &index.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let mut destination = assign_destination(&index.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            match_expr!(&mut destination, Destination::Pointer, offset).replace(Box::new(offset));
+            destination
+        }
+        E::Deref(node) => {
+            #[rustfmt::skip] let pointer = compile_pointer(&node.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            Destination::Indirect(Box::new(pointer))
+        }
+        _ => unreachable!(),
+    })
+}
+
+pub fn compile_expr_u8<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u8>, CompileError> {
+    #[rustfmt::skip] let source = compile_expr(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+    assert_eq!(1, source.len());
+    Ok(source[0].clone())
+}
+
+/// compile a `Layout::U16` (or `Layout::I16`) expression, and return the
+/// `Source<u16>` holding the result.
+///
+/// # Note
+/// As with `compile_expr`, the callee is responsible for freeing any register
+/// referenced by the returned `Source`.
+pub fn compile_expr_u16<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Source<u16>, CompileError> {
+    macro_rules! arithmetic_branch {
+        ($var:ident, $node:expr, $right:ident) => {{
+            #[rustfmt::skip] let left = compile_expr_u16(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let right = $right(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&left, register_alloc);
+            free_source_registers(&right, register_alloc);
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::$var {
+                left,
+                right,
+                destination: Destination::Register(store_register),
+            });
+            Source::Register(store_register)
+        }};
+    }
+
+    // pointers are offset by multiples of the size of the data they point to
+    macro_rules! pointer_branch {
+        ($var:ident, $node:expr) => {{
+            let size = pointee(&$node.inner.left, symbol_alloc).unwrap().size();
+            #[rustfmt::skip] let left = compile_expr_u16(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let right = compile_offset(&$node.inner.right, size, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&left, register_alloc);
+            free_source_registers(&right, register_alloc);
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::$var {
+                left,
+                right,
+                destination: Destination::Register(store_register),
+            });
+            Source::Register(store_register)
+        }};
+    }
+
+    use Expression as E;
+
+    if let Some(n) = const_expr(expression, Some(symbol_alloc)) {
+        return Ok(Source::Literal(n));
+    }
+
+    Ok(match expression {
+        E::Lit(lit) if lit.is_fixed() => {
+            Source::Literal(lit.fixed_value().expect("Fixed-point literal out of range"))
+        }
+        E::Path(_) if bits_symbol(expression, symbol_alloc).is_some() => {
+            let symbol = bits_symbol(expression, symbol_alloc).unwrap();
+            compile_bits_u16(symbol, register_alloc, statements)
+        }
+        E::Path(path) => {
+            let symbol = symbol_alloc.symbol(path)?;
+            if !matches!(
+                &symbol.layout,
+                Layout::U16 | Layout::I16 | Layout::Fixed | Layout::Pointer(_) | Layout::Fn { .. }
+            ) {
+                let (name, span) = (symbol.name.clone(), path.span());
+                return Err(CompileError::MismatchedWidth { name, span });
+            }
+            Source::Pointer {
+                base: symbol.pointer(),
+                offset: None,
+            }
+        }
+
+        // pointers
+        E::AddressOf(node) if fn_path(&node.inner, fn_alloc).is_some() => {
+            Source::Literal(fn_routine(&node.inner, fn_alloc)?.unwrap() as u16)
+        }
+        E::AddressOf(node) => match &node.inner {
+            // `@*p` is `p`
+            E::Deref(deref) => compile_pointer(
+                &deref.inner,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?,
+            inner => {
+                #[rustfmt::skip] let destination = assign_destination(inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_destination_registers(&destination, register_alloc);
+                // pointers can only address static memory
+                if let Destination::Pointer {
+                    base: Pointer::Stack(_),
+                    ..
+                } = destination
+                {
+                    let span = expression.span();
+                    return Err(CompileError::StackAddress { span });
+                }
+                let store_register = register_alloc.alloc();
+                statements.push(Statement::LdAddr {
+                    source: destination_to_source(&destination),
+                    destination: Destination::Register(store_register),
+                });
+                Source::Register(store_register)
+            }
+        },
+        E::Deref(node) => {
+            #[rustfmt::skip] let pointer = compile_pointer(&node.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            Source::Indirect(Box::new(pointer))
+        }
+        E::Add(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            pointer_branch!(AddW, node)
+        }
+        E::Sub(node) if pointee(&node.inner.left, symbol_alloc).is_some() => {
+            pointer_branch!(SubW, node)
+        }
+
+        // fixed-point arithmetic (other than products, it is 16bit arithmetic)
+        E::Mul(node)
+            if is_fixed(&node.inner.left, symbol_alloc)
+                && is_fixed(&node.inner.right, symbol_alloc) =>
+        {
+            #[rustfmt::skip] let left = compile_expr_u16(&node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            #[rustfmt::skip] let right = compile_expr_u16(&node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let product = compile_fixed_mul(&left, &right, register_alloc, statements);
+            free_source_registers(&left, register_alloc);
+            free_source_registers(&right, register_alloc);
+            product
+        }
+
+        // 16bit arithmetic
+        E::Add(node) => arithmetic_branch!(AddW, node, compile_expr_u16),
+        E::Sub(node) => arithmetic_branch!(SubW, node, compile_expr_u16),
+        E::Mul(node) => arithmetic_branch!(MulW, node, compile_expr_u16),
+        E::Div(node) if is_signed(expression, symbol_alloc) => {
+            arithmetic_branch!(DivSW, node, compile_expr_u16)
+        }
+        E::Div(node) => arithmetic_branch!(DivW, node, compile_expr_u16),
+        E::And(node) => arithmetic_branch!(AndW, node, compile_expr_u16),
+        E::Or(node) => arithmetic_branch!(OrW, node, compile_expr_u16),
+        E::Xor(node) => arithmetic_branch!(XorW, node, compile_expr_u16),
+        E::LeftShift(node) => arithmetic_branch!(LeftShiftW, node, compile_expr_u8),
+        E::RightShift(node) if is_signed(expression, symbol_alloc) => {
+            arithmetic_branch!(RightShiftSW, node, compile_expr_u8)
+        }
+        E::RightShift(node) => arithmetic_branch!(RightShiftW, node, compile_expr_u8),
+
+        // integers are converted into fixed-point values (and back) by shifting
+        // them 8 bits to the left (or to the right)
+        E::Cast(node)
+            if is_fixed(expression, symbol_alloc) && !is_fixed(&node.inner.inner, symbol_alloc) =>
+        {
+            let inner = &node.inner.inner;
+            let layout = value_layout(inner, symbol_alloc);
+            let source = if is_wide(&layout) {
+                compile_expr_u16(inner, symbol_alloc, fn_alloc, register_alloc, statements)?
+            } else {
+                #[rustfmt::skip] let source = compile_expr_u8(inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                let signed = matches!(layout, Some(Layout::I8));
+                extend(source, signed, register_alloc, statements)
+            };
+            compile_fixed_shift(source, true, register_alloc, statements)
+        }
+        E::Cast(node)
+            if is_fixed(&node.inner.inner, symbol_alloc)
+                && !is_fixed(expression, symbol_alloc)
+                && is_wide(&value_layout(expression, symbol_alloc)) =>
+        {
+            #[rustfmt::skip] let source = compile_expr_u16(&node.inner.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            compile_fixed_shift(source, false, register_alloc, statements)
+        }
+
+        // 8bit values are extended according to the signedness of their type
+        E::Cast(node) => {
+            let layout = value_layout(&node.inner.inner, symbol_alloc);
+            if !is_wide(&value_layout(expression, symbol_alloc)) {
+                #[rustfmt::skip] let source = compile_expr_u8(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                let signed = matches!(node.inner.type_, Type::I8(_));
+                extend(source, signed, register_alloc, statements)
+            } else if is_wide(&layout) {
+                compile_expr_u16(
+                    &node.inner.inner,
+                    symbol_alloc,
+                    fn_alloc,
+                    register_alloc,
+                    statements,
+                )?
+            } else {
+                #[rustfmt::skip] let source = compile_expr_u8(&node.inner.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                let signed = matches!(layout, Some(Layout::I8));
+                extend(source, signed, register_alloc, statements)
+            }
+        }
+
+        // conditional
+        E::Conditional(node) => {
+            let store_register = register_alloc.alloc();
+            compile_conditional(
+                &node.inner,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+                |expression, register_alloc, statements| {
+                    let source = compile_expr_u16(
+                        expression,
+                        symbol_alloc,
+                        fn_alloc,
+                        register_alloc,
+                        statements,
+                    )?;
+                    free_source_registers(&source, register_alloc);
+                    statements.push(Statement::LdW {
+                        source,
+                        destination: Destination::Register(store_register),
+                    });
+                    Ok(())
+                },
+            )?;
+            Source::Register(store_register)
+        }
+
+        // array indexing
+        E::Index(node) => {
+            let symbol = match &node.inner.right {
+                E::Path(path) => symbol_alloc.symbol(path)?,
+                right => return Err(CompileError::Unsupported { span: right.span() }),
+            };
+            let offset = compile_index(
+                &node.inner.left,
+                &node.inner.right,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            Source::Pointer {
+                base: symbol.pointer(),
+                offset: Some(Box::new(offset)),
+            }
+        }
+        expression => {
+            return Err(CompileError::Unsupported {
+                span: expression.span(),
+            })
+        }
+    })
+}
+
+/// compile a `Layout::U8` expression, and store the result in a `Source<u8>`
+/// return this source.
+///
+/// # Note
+/// It is possible that the `Source` variant will be a `Source::Register`, or a
+/// `Source::Pointer` with the value of a register set as offset. In
+/// those situations, the callee of the function is responsible for freeing this
+/// register.
+pub fn compile_expr<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<Vec<Source<u8>>, CompileError> {
+    macro_rules! arithmetic_branch {
+        ($var:ident, $node:expr) => {{
+            let left = compile_expr_u8(
+                &$node.inner.left,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            let right = compile_expr_u8(
+                &$node.inner.right,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            free_source_registers(&left, register_alloc);
+            free_source_registers(&right, register_alloc);
+            // TODO for now, put it in a register, but it shpuld be possible to instruct the
+            //  function to put it somewhere in memory instead.
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::$var {
+                left,
+                right,
+                destination: Destination::Register(store_register),
+            });
+            vec![Source::Register(store_register)]
+        }};
+    }
+
+    // short-circuit evaluation of logical operators.
+    // the right operand is only evaluated if the left one doesn't determine the result.
+    macro_rules! logical_branch {
+        ($jmp:ident, $node:expr) => {{
+            let store_register = register_alloc.alloc();
+            let left = compile_expr_u8(
+                &$node.inner.left,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            free_source_registers(&left, register_alloc);
+            statements.push(Statement::NotEq {
+                left,
+                right: Source::Literal(0),
+                destination: Destination::Register(store_register),
+            });
+            let mut right_statements = Vec::new();
+            let right = compile_expr_u8(
+                &$node.inner.right,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                &mut right_statements,
+            )?;
+            free_source_registers(&right, register_alloc);
+            right_statements.push(Statement::NotEq {
+                left: right,
+                right: Source::Literal(0),
+                destination: Destination::Register(store_register),
+            });
+            statements.push(Statement::$jmp {
+                location: Location::Relative(right_statements.len() as _),
+                source: Source::Register(store_register),
+            });
+            statements.extend(right_statements);
+            vec![Source::Register(store_register)]
+        }};
+    }
+
+    // 16bit operands are compared as words (both of them)
+    macro_rules! comparison_branch {
+        ($var:ident, $var_w:ident, $node:expr) => {{
+            if word_operands!($node, symbol_alloc) {
+                #[rustfmt::skip] let left = compile_word_operand(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                #[rustfmt::skip] let right = compile_word_operand(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&left, register_alloc);
+                free_source_registers(&right, register_alloc);
+                let store_register = register_alloc.alloc();
+                statements.push(Statement::$var_w {
+                    left,
+                    right,
+                    destination: Destination::Register(store_register),
+                });
+                vec![Source::Register(store_register)]
+            } else {
+                arithmetic_branch!($var, $node)
+            }
+        }};
+    }
+
+    use Expression as E;
+
+    // if the expression is a constant expression, return it as a literal.
+    if let Some(n) = const_expr(expression, Some(symbol_alloc)) {
+        // negative bytes are sign extended by the evaluation
+        if n > 0xff && n < 0xff80 {
+            let span = expression.span();
+            return Err(CompileError::OutOfRange { span });
+        }
+        return Ok(vec![Source::Literal(n as u8)]);
+    }
+
+    Ok(match expression {
+        // numeric const expressions are handled by the above statement, string literals are not
+        // u8 expressions.
+        E::Lit(_) => panic!("String literal in a u8 expression"),
+
+        // bitfields
+        E::Path(_) if bits_symbol(expression, symbol_alloc).is_some() => {
+            let symbol = bits_symbol(expression, symbol_alloc).unwrap();
+            vec![compile_bits_u8(symbol, register_alloc, statements)]
+        }
+
+        // symbol name
+        E::Path(path) => {
+            let symbol = symbol_alloc.symbol(path)?;
+            if !matches!(&symbol.layout, Layout::U8 | Layout::I8) {
+                let (name, span) = (symbol.name.clone(), path.span());
+                return Err(CompileError::MismatchedWidth { name, span });
+            }
+            vec![Source::Pointer {
+                base: symbol.pointer(),
+                offset: None,
+            }]
+        }
+
+        // 8bit arithmetic
+        E::Add(node) => arithmetic_branch!(Add, node),
+        E::Sub(node) => arithmetic_branch!(Sub, node),
+        E::Mul(node) => arithmetic_branch!(Mul, node),
+        E::Div(node) if is_signed(expression, symbol_alloc) => arithmetic_branch!(DivS, node),
+        E::Div(node) => arithmetic_branch!(Div, node),
+        // TODO modulo
+        E::And(node) => arithmetic_branch!(And, node),
+        E::Or(node) => arithmetic_branch!(Or, node),
+        E::Xor(node) => arithmetic_branch!(Xor, node),
+        E::LeftShift(node) => arithmetic_branch!(LeftShift, node),
+        E::RightShift(node) if is_signed(expression, symbol_alloc) => {
+            arithmetic_branch!(RightShiftS, node)
+        }
+        E::RightShift(node) => arithmetic_branch!(RightShift, node),
+
+        // boolean
+        E::Eq(node) => comparison_branch!(Eq, EqW, node),
+        E::NotEq(node) => comparison_branch!(NotEq, NotEqW, node),
+        E::Greater(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_branch!(GreaterS, GreaterSW, node)
+        }
+        E::Greater(node) => comparison_branch!(Greater, GreaterW, node),
+        E::GreaterEq(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_branch!(GreaterEqS, GreaterEqSW, node)
+        }
+        E::GreaterEq(node) => comparison_branch!(GreaterEq, GreaterEqW, node),
+        E::Less(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_branch!(LessS, LessSW, node)
+        }
+        E::Less(node) => comparison_branch!(Less, LessW, node),
+        E::LessEq(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_branch!(LessEqS, LessEqSW, node)
+        }
+        E::LessEq(node) => comparison_branch!(LessEq, LessEqW, node),
+
+        // logical
+        E::LogicalAnd(node) => logical_branch!(JmpCmpNot, node),
+        E::LogicalOr(node) => logical_branch!(JmpCmp, node),
+
+        // pointer dereference
+        E::Deref(node) => {
+            #[rustfmt::skip] let pointer = compile_pointer(&node.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            vec![Source::Indirect(Box::new(pointer))]
+        }
+
+        // constant sizes are handled above
+        E::SizeOf(_) => panic!("Size of expression is not known at compile time"),
+
+        // the integer part of a fixed-point value is its high byte
+        E::Cast(node) if is_fixed(&node.inner.inner, symbol_alloc) => {
+            #[rustfmt::skip] let source = compile_expr_u16(&node.inner.inner, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let source = compile_fixed_shift(source, false, register_alloc, statements);
+            free_source_registers(&source, register_alloc);
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::Trunc {
+                source,
+                destination: Destination::Register(store_register),
+            });
+            vec![Source::Register(store_register)]
+        }
+
+        // 16bit values are truncated to their low byte
+        E::Cast(node) => {
+            let word = if is_wide(&value_layout(expression, symbol_alloc)) {
+                expression
+            } else if is_wide(&value_layout(&node.inner.inner, symbol_alloc)) {
+                &node.inner.inner
+            } else {
+                #[rustfmt::skip] return compile_expr(&node.inner.inner, symbol_alloc, fn_alloc, register_alloc, statements);
+            };
+            #[rustfmt::skip] let source = compile_expr_u16(word, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            let store_register = register_alloc.alloc();
+            statements.push(Statement::Trunc {
+                source,
+                destination: Destination::Register(store_register),
+            });
+            vec![Source::Register(store_register)]
+        }
+
+        // conditional
+        E::Conditional(node) => {
+            let store_register = register_alloc.alloc();
+            compile_conditional(
+                &node.inner,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+                |expression, register_alloc, statements| {
+                    let source = compile_expr_u8(
+                        expression,
+                        symbol_alloc,
+                        fn_alloc,
+                        register_alloc,
+                        statements,
+                    )?;
+                    free_source_registers(&source, register_alloc);
+                    statements.push(Statement::Ld {
+                        source,
+                        destination: Destination::Register(store_register),
+                    });
+                    Ok(())
+                },
+            )?;
+            vec![Source::Register(store_register)]
+        }
+
+        // array indexing
+        E::Index(node) => {
+            let symbol = match &node.inner.right {
+                E::Path(path) => symbol_alloc.symbol(path)?,
+                right => return Err(CompileError::Unsupported { span: right.span() }),
+            };
+            let offset = compile_index(
+                &node.inner.left,
+                &node.inner.right,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            vec![Source::Pointer {
+                base: symbol.pointer(),
+                offset: Some(Box::new(offset)),
+            }]
+        }
+
+        // functions
+        E::Call(node) if builtin(node).is_some() => {
+            #[rustfmt::skip] let source = compile_builtin(node, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            match source {
+                Some(source) => vec![source],
+                None => return Err(CompileError::Unsupported { span: node.span() }),
+            }
+        }
+
+        // arrays and function calls are only compiled into memory
+        expression @ E::Array(_) | expression @ E::Call(_) => {
+            return Err(CompileError::Unsupported {
+                span: expression.span(),
+            })
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// compiles the evaluation of an expression, but the result is not stored
+/// anywhere.
+#[warn(unused)]
+pub fn compile_expr_void<B: ByteOrder>(
+    expression: &Expression<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    use Expression as E;
+    match expression {
+        // superfluous expressions
+        E::Lit(_) | E::Path(_) => { /* Nop */ }
+        E::Call(node) if node.inner.value.is_some() => { /* Nop */ }
+
+        // assignments
+        expression @ E::PlusAssign(_)
+        | expression @ E::MinusAssign(_)
+        | expression @ E::MulAssign(_)
+        | expression @ E::DivAssign(_)
+        | expression @ E::AndAssign(_)
+        | expression @ E::OrAssign(_)
+        | expression @ E::XorAssign(_)
+        | expression @ E::Assign(_)
+        | expression @ E::Increment(_)
+        | expression @ E::Decrement(_) => compile_assign(
+            expression,
+            symbol_alloc,
+            fn_alloc,
+            register_alloc,
+            statements,
+        )?,
+
+        // builtin call
+        E::Call(node) if builtin(node).is_some() => {
+            #[rustfmt::skip] let source = compile_builtin(node, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            if let Some(source) = source {
+                free_source_registers(&source, register_alloc);
+            }
+        }
+
+        // function call
+        // FIXME placeholder implementation
+        expression @ E::Call(_) => {
+            // the arguments are passed at the top of the stack, where the
+            // frame of the callee begins
+            let dst_base = Pointer::Stack(symbol_alloc.stack_address());
+            let layout = Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 0,
+            };
+            // FIXME remove the above function as it's creating implicit dependencies
+            //  between these two functions...
+            compile_expression_into_pointer(
+                expression,
+                &layout,
+                symbol_alloc,
+                fn_alloc,
+                dst_base,
+                register_alloc,
+                statements,
+            )?;
+        }
+
+        // conditional
+        E::Conditional(node) => {
+            compile_conditional(
+                &node.inner,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+                |expression, register_alloc, statements| {
+                    compile_expr_void(
+                        expression,
+                        symbol_alloc,
+                        fn_alloc,
+                        register_alloc,
+                        statements,
+                    )
+                },
+            )?;
+        }
+        expression => {
+            return Err(CompileError::Unsupported {
+                span: expression.span(),
+            })
+        }
+    }
+    Ok(())
+}
+
+// compile the branching of a conditional expression. The statements of the
+// evaluated branch are compiled by `branch`. If the condition is constant, only
+// one of the branches is compiled.
+fn compile_conditional<B, F>(
+    conditional: &Conditional<'_>,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+    mut branch: F,
+) -> Result<(), CompileError>
+where
+    B: ByteOrder,
+    F: FnMut(&Expression<'_>, &mut RegisterAlloc, &mut Vec<Statement>) -> Result<(), CompileError>,
+{
+    match const_expr(&conditional.condition, Some(symbol_alloc)) {
+        Some(0) => branch(&conditional.else_, register_alloc, statements),
+        Some(_) => branch(&conditional.then, register_alloc, statements),
+        None => {
+            #[rustfmt::skip] let source = compile_expr_u8(&conditional.condition, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            let mut then = Vec::new();
+            let mut else_ = Vec::new();
+            branch(&conditional.then, register_alloc, &mut then)?;
+            branch(&conditional.else_, register_alloc, &mut else_)?;
+            // skip the `then` branch and the jump that follows it
+            statements.push(Statement::JmpCmpNot {
+                location: Location::Relative((then.len() + 1) as _),
+                source,
+            });
+            statements.extend(then);
+            statements.push(Statement::Jmp {
+                location: Location::Relative(else_.len() as _),
+            });
+            statements.extend(else_);
+            Ok(())
+        }
+    }
+}
+
+// TODO remove/replace code below
+
+#[deprecated]
+fn path_to_symbol_name(path: &Path<'_>) -> String {
+    let mut items = path.iter();
+    let name = items.next().unwrap().name().to_string();
+    items.fold(name, |mut o, ident| {
+        o.push_str("::");
+        o.push_str(ident.name());
+        o
+    })
+}
+
+/// compile the given expression and store the result in the memory of the
+/// symbol with the given name (named in the source at `span`).
+///
+/// Struct literals are compiled field by field, into the symbols of each of
+/// the fields (`name::field`). Fields missing from the literal are left
+/// unchanged.
+pub fn compile_into_symbol<B: ByteOrder>(
+    expression: &Expression<'_>,
+    name: &str,
+    span: Span,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    let symbol = match symbol_alloc.find(name) {
+        Some(symbol) => symbol,
+        None => {
+            let name = name.to_string();
+            return Err(CompileError::UndefinedSymbol { name, span });
+        }
+    };
+    match expression {
+        Expression::StructLit(struct_lit) => {
+            assert!(
+                matches!(symbol.layout, Layout::Struct(_) | Layout::Union(_)),
+                "Struct literal assigned to a non-struct value"
+            );
+            for field in &struct_lit.fields {
+                let name = format!("{}::{}", name, field.ident.name());
+                let span = field.ident.span();
+                #[rustfmt::skip] compile_into_symbol(&field.expression, &name, span, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            }
+            Ok(())
+        }
+        expression => compile_expression_into_pointer(
+            expression,
+            &symbol.layout,
+            symbol_alloc,
+            fn_alloc,
+            symbol.pointer(),
+            register_alloc,
+            statements,
+        ),
+    }
+}
+
+// compile computation of the given expression and store the result in the given
+// stack address (it is assume that the expression fits).
+#[deprecated]
+pub fn compile_expression_into_pointer<B: ByteOrder>(
+    expression: &Expression<'_>,
+    layout: &Layout,
+    symbol_alloc: &SymbolAlloc<B>,
+    fn_alloc: &FnAlloc,
+    dst_base: Pointer,
+    register_alloc: &mut RegisterAlloc,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    macro_rules! arithmetic_match_branch {
+        ($node:expr, $var:ident, $var_w:ident, $right_w:ident) => {{
+            if let Layout::U16 | Layout::I16 = layout {
+                #[rustfmt::skip] let left = compile_expr_u16(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                #[rustfmt::skip] let right = $right_w(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&left, register_alloc);
+                free_source_registers(&right, register_alloc);
+                statements.push($var_w {
+                    left,
+                    right,
+                    destination: Destination::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                });
+            } else {
+                arithmetic_match_branch!($node, $var)
+            }
+        }};
+        ($node:expr, $var:ident) => {{
+            let left = compile_expr_u8(
+                &$node.inner.left,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            let right = compile_expr_u8(
+                &$node.inner.right,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            free_source_registers(&left, register_alloc);
+            free_source_registers(&right, register_alloc);
+            statements.push($var {
+                left,
+                right,
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }};
+    }
+
+    // 16bit operands are compared as words (both of them)
+    macro_rules! comparison_match_branch {
+        ($node:expr, $var:ident, $var_w:ident) => {{
+            if word_operands!($node, symbol_alloc) {
+                #[rustfmt::skip] let left = compile_word_operand(&$node.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                #[rustfmt::skip] let right = compile_word_operand(&$node.inner.right, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&left, register_alloc);
+                free_source_registers(&right, register_alloc);
+                statements.push($var_w {
+                    left,
+                    right,
+                    destination: Destination::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                });
+            } else {
+                arithmetic_match_branch!($node, $var)
+            }
+        }};
+    }
+
+    use super::Statement::{
+        Add, AddW, And, AndW, Div, DivS, DivSW, DivW, Eq, EqW, Greater, GreaterEq, GreaterEqS,
+        GreaterEqSW, GreaterEqW, GreaterS, GreaterSW, GreaterW, Ld, LdAddr, LdW, LeftShift,
+        LeftShiftW, Less, LessEq, LessEqS, LessEqSW, LessEqW, LessS, LessSW, LessW, Memcpy, Mul,
+        MulW, NotEq, NotEqW, Or, OrW, RightShift, RightShiftS, RightShiftSW, RightShiftW, Sub,
+        SubW, Xor, XorW,
+    };
+
+    match expression {
+        // fixed-point values are computed as 16bit words
+        _ if *layout == Layout::Fixed && !matches!(expression, Expression::Call(_)) => {
+            #[rustfmt::skip] let source = compile_expr_u16(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            statements.push(LdW {
+                source,
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }
+        // compile literal expression by simply move a literal value unto the stack address.
+        // the size must be either a u8 or a u16 at this point. Any other value is wrong and the
+        // compiler frontend should've caught it by now, hence the panic.
+        // string literals are either copied, or referenced from const memory.
+        Expression::Lit(lit) if lit.str_value().is_some() => match layout {
+            Layout::Array { .. } => {
+                let data = symbol_alloc.encode_str(lit).unwrap();
+                assert_eq!(layout.size() as usize, data.len());
+                for (offset, byte) in data.into_iter().enumerate() {
+                    statements.push(Ld {
+                        source: Source::Literal(byte),
+                        destination: Destination::Pointer {
+                            base: dst_base.offset(offset as u16),
+                            offset: None,
+                        },
+                    });
+                }
+            }
+            Layout::Pointer(_) => statements.push(LdAddr {
+                source: Source::Pointer {
+                    base: Pointer::Const(symbol_alloc.const_str(lit).unwrap()),
+                    offset: None,
+                },
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            }),
+            _ => panic!(),
+        },
+        expr @ Expression::Lit(_) | expr @ Expression::SizeOf(_) => {
+            let lit = const_expr(expr, Some(symbol_alloc)).unwrap();
+            compile_literal_into_pointer(lit, expr.span(), layout, dst_base, statements)?;
+        }
+        // calls to const functions, evaluated by the parser
+        Expression::Call(call) if call.inner.value.is_some() => {
+            let lit = call.inner.value.unwrap();
+            compile_literal_into_pointer(lit, call.span(), layout, dst_base, statements)?;
+        }
+        Expression::Path(path) => {
+            let symbol = symbol_alloc.symbol(path)?;
+            if layout != &symbol.layout {
+                return Err(CompileError::MismatchedWidth {
+                    name: symbol.name.clone(),
+                    span: path.span(),
+                });
+            }
+
+            let src_base = match symbol.memory_space {
+                SymbolMemorySpace::Static => Pointer::Static(symbol.offset),
+                SymbolMemorySpace::Const => Pointer::Const(symbol.offset),
+                SymbolMemorySpace::Stack => Pointer::Stack(symbol.offset),
+                SymbolMemorySpace::Absolute => Pointer::Absolute(symbol.offset),
+                SymbolMemorySpace::Banked(bank) => Pointer::Banked(bank, symbol.offset),
+            };
+            // aggregates between addressable memory are copied as a block.
+            // Anything else is copied byte by byte.
+            let addressable =
+                |base: &Pointer| matches!(base, Pointer::Static(_) | Pointer::Absolute(_));
+            let aggregate = matches!(
+                layout,
+                Layout::Array { .. } | Layout::Struct(_) | Layout::Union(_)
+            );
+            if aggregate && addressable(&src_base) && addressable(&dst_base) {
+                let source = register_alloc.alloc();
+                let destination = register_alloc.alloc();
+                statements.push(LdAddr {
+                    source: Source::Pointer {
+                        base: src_base,
+                        offset: None,
+                    },
+                    destination: Destination::Register(source),
+                });
+                statements.push(LdAddr {
+                    source: Source::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                    destination: Destination::Register(destination),
+                });
+                statements.push(Memcpy {
+                    source: Source::Register(source),
+                    destination: Source::Register(destination),
+                    len: Source::Literal(layout.size()),
+                });
+                register_alloc.free(source);
+                register_alloc.free(destination);
+                return Ok(());
+            }
+            for offset in 0..layout.size() {
+                let source = Source::Pointer {
+                    base: src_base.offset(offset),
+                    offset: None,
+                };
+                let destination = Destination::Pointer {
+                    base: dst_base.offset(offset),
+                    offset: None,
+                };
+                statements.push(Ld {
+                    source,
+                    destination,
+                });
+            }
+        }
+        Expression::Array(value) => match layout {
+            Layout::Array { inner, len } => {
+                assert_eq!(*len as usize, value.inner.len());
+
+                let array_type_size = inner.size();
+
+                for (i, expr) in value.inner.iter().enumerate() {
+                    let offset = array_type_size * (i as u16);
+                    compile_expression_into_pointer(
+                        expr,
+                        inner,
+                        symbol_alloc,
+                        fn_alloc,
+                        dst_base.offset(offset),
+                        register_alloc,
+                        statements,
+                    )?;
+                }
+            }
+            // tuple values
+            Layout::Struct(inner) => {
+                assert_eq!(inner.len(), value.inner.len());
+
+                let mut offset = 0;
+                for (expr, layout) in value.inner.iter().zip(inner) {
+                    compile_expression_into_pointer(
+                        expr,
+                        layout,
+                        symbol_alloc,
+                        fn_alloc,
+                        dst_base.offset(offset),
+                        register_alloc,
+                        statements,
+                    )?;
+                    offset += layout.size();
+                }
+            }
+            _ => panic!(),
+        },
+        // negative literals
+        Expression::Minus(_) if const_expr(expression, Some(symbol_alloc)).is_some() => {
+            let lit = const_expr(expression, Some(symbol_alloc)).unwrap();
+            let span = expression.span();
+            compile_literal_into_pointer(lit, span, layout, dst_base, statements)?;
+        }
+        Expression::Minus(_) => {}
+        Expression::AddressOf(address_of) => match layout {
+            Layout::Pointer(ptr) => {
+                match &address_of.inner {
+                    Expression::Path(path) => {
+                        let symbol = symbol_alloc.symbol(path)?;
+
+                        // check layouts
+                        assert_eq!(ptr.as_ref(), &symbol.layout);
+
+                        let source_ptr = match symbol.memory_space {
+                            SymbolMemorySpace::Stack => {
+                                return Err(CompileError::StackAddress {
+                                    span: expression.span(),
+                                })
+                            }
+                            SymbolMemorySpace::Static => Pointer::Static(symbol.offset),
+                            SymbolMemorySpace::Const => Pointer::Const(symbol.offset),
+                            SymbolMemorySpace::Absolute => Pointer::Absolute(symbol.offset),
+                            SymbolMemorySpace::Banked(bank) => Pointer::Banked(bank, symbol.offset),
+                        };
+                        statements.push(LdAddr {
+                            source: Source::Pointer {
+                                base: source_ptr,
+                                offset: None,
+                            },
+                            destination: Destination::Pointer {
+                                base: dst_base,
+                                offset: None,
+                            },
+                        });
+                    }
+                    Expression::Index(index) => {
+                        match &index.inner.right {
+                            Expression::Path(path) => {
+                                let symbol = symbol_alloc.symbol(path)?;
+
+                                // TODO fix lint
+                                #[allow(unused)]
+                                if let Layout::Array { inner, len } = &symbol.layout {
+                                    assert_eq!(ptr, inner);
+
+                                    // TODO extend to non-constant expression indices
+                                    let type_size = inner.size();
+                                    let offset_const_expr =
+                                        const_expr(&index.inner.left, Some(symbol_alloc)).unwrap();
+                                    let offset = symbol.offset + type_size * offset_const_expr;
+
+                                    let source_ptr = match symbol.memory_space {
+                                        SymbolMemorySpace::Static => Pointer::Static(offset),
+                                        SymbolMemorySpace::Const => Pointer::Const(offset),
+                                        SymbolMemorySpace::Stack => {
+                                            return Err(CompileError::StackAddress {
+                                                span: expression.span(),
+                                            })
+                                        }
+                                        SymbolMemorySpace::Absolute => Pointer::Absolute(offset),
+                                        SymbolMemorySpace::Banked(bank) => {
+                                            Pointer::Banked(bank, offset)
+                                        }
+                                    };
+                                    statements.push(LdAddr {
+                                        source: Source::Pointer {
+                                            base: source_ptr,
+                                            offset: None,
+                                        },
+                                        destination: Destination::Pointer {
+                                            base: dst_base,
+                                            offset: None,
+                                        },
+                                    });
+                                } else {
+                                    panic!()
+                                }
+                            }
+                            right => return Err(CompileError::Unsupported { span: right.span() }),
+                        }
+                    }
+                    _ => {
+                        #[rustfmt::skip] let source = compile_expr_u16(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                        free_source_registers(&source, register_alloc);
+                        statements.push(LdW {
+                            source,
+                            destination: Destination::Pointer {
+                                base: dst_base,
+                                offset: None,
+                            },
+                        });
+                    }
+                }
+            }
+            // address of a function (function pointer)
+            Layout::Fn { .. } => {
+                let routine = fn_routine(&address_of.inner, fn_alloc)?.expect("Not a function");
+                statements.push(LdW {
+                    source: Source::Literal(routine as u16),
+                    destination: Destination::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                });
+            }
+            _ => panic!(),
+        },
+        Expression::Not(_) => {}
+
+        // pointer arithmetic
+        Expression::Add(_) | Expression::Sub(_) if matches!(layout, Layout::Pointer(_)) => {
+            #[rustfmt::skip] let source = compile_expr_u16(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            free_source_registers(&source, register_alloc);
+            statements.push(LdW {
+                source,
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }
+
+        // binary expressions
+        Expression::Add(node) => arithmetic_match_branch!(node, Add, AddW, compile_expr_u16),
+        Expression::Sub(node) => arithmetic_match_branch!(node, Sub, SubW, compile_expr_u16),
+        Expression::Mul(node) => arithmetic_match_branch!(node, Mul, MulW, compile_expr_u16),
+        Expression::Div(node) if is_signed(expression, symbol_alloc) => {
+            arithmetic_match_branch!(node, DivS, DivSW, compile_expr_u16)
+        }
+        Expression::Div(node) => arithmetic_match_branch!(node, Div, DivW, compile_expr_u16),
+        Expression::And(node) => arithmetic_match_branch!(node, And, AndW, compile_expr_u16),
+        Expression::Or(node) => arithmetic_match_branch!(node, Or, OrW, compile_expr_u16),
+        Expression::Xor(node) => arithmetic_match_branch!(node, Xor, XorW, compile_expr_u16),
+        Expression::LeftShift(node) => {
+            arithmetic_match_branch!(node, LeftShift, LeftShiftW, compile_expr_u8)
+        }
+        Expression::RightShift(node) if is_signed(expression, symbol_alloc) => {
+            arithmetic_match_branch!(node, RightShiftS, RightShiftSW, compile_expr_u8)
+        }
+        Expression::RightShift(node) => {
+            arithmetic_match_branch!(node, RightShift, RightShiftW, compile_expr_u8)
+        }
+
+        // boolean
+        Expression::Eq(node) => comparison_match_branch!(node, Eq, EqW),
+        Expression::NotEq(node) => comparison_match_branch!(node, NotEq, NotEqW),
+        Expression::Greater(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_match_branch!(node, GreaterS, GreaterSW)
+        }
+        Expression::Greater(node) => comparison_match_branch!(node, Greater, GreaterW),
+        Expression::GreaterEq(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_match_branch!(node, GreaterEqS, GreaterEqSW)
+        }
+        Expression::GreaterEq(node) => comparison_match_branch!(node, GreaterEq, GreaterEqW),
+        Expression::Less(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_match_branch!(node, LessS, LessSW)
+        }
+        Expression::Less(node) => comparison_match_branch!(node, Less, LessW),
+        Expression::LessEq(node) if signed_operands!(node, symbol_alloc) => {
+            comparison_match_branch!(node, LessEqS, LessEqSW)
+        }
+        Expression::LessEq(node) => comparison_match_branch!(node, LessEq, LessEqW),
+
+        // logical
+        Expression::LogicalAnd(_) | Expression::LogicalOr(_) => {
+            let source = compile_expr_u8(
+                expression,
+                symbol_alloc,
+                fn_alloc,
+                register_alloc,
+                statements,
+            )?;
+            free_source_registers(&source, register_alloc);
+            statements.push(Ld {
+                source,
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }
+
+        // assignment (these return void, so panic)
+        Expression::Assign(_)
+        | Expression::PlusAssign(_)
+        | Expression::MinusAssign(_)
+        | Expression::MulAssign(_)
+        | Expression::DivAssign(_)
+        | Expression::AndAssign(_)
+        | Expression::OrAssign(_)
+        | Expression::XorAssign(_)
+        | Expression::Increment(_)
+        | Expression::Decrement(_) => panic!(),
+
+        Expression::Conditional(_) | Expression::Cast(_) | Expression::Deref(_) => match layout {
+            Layout::U16 | Layout::I16 | Layout::Pointer(_) => {
+                #[rustfmt::skip] let source = compile_expr_u16(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&source, register_alloc);
+                statements.push(LdW {
+                    source,
+                    destination: Destination::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                });
+            }
+            _ => {
+                #[rustfmt::skip] let source = compile_expr_u8(expression, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                free_source_registers(&source, register_alloc);
+                statements.push(Ld {
+                    source,
+                    destination: Destination::Pointer {
+                        base: dst_base,
+                        offset: None,
+                    },
+                });
+            }
+        },
+
+        // TODO reimplement (only works for [u8 N] atm)
+        Expression::Index(index) => {
+            match &index.inner.right {
+                Expression::Path(path) => {
+                    let symbol = symbol_alloc.symbol(path)?;
+
+                    //assert_eq!(&Layout::Array {}, &symbol.layout);
+
+                    // compute offset
+                    let offset = compile_expr_u8(
+                        &index.inner.left,
+                        symbol_alloc,
+                        fn_alloc,
+                        register_alloc,
+                        statements,
+                    )?;
+                    free_source_registers(&offset, register_alloc);
+                    let base = match symbol.memory_space {
+                        SymbolMemorySpace::Static => Pointer::Static(symbol.offset),
+                        SymbolMemorySpace::Const => Pointer::Const(symbol.offset),
+                        SymbolMemorySpace::Stack => Pointer::Stack(symbol.offset),
+                        SymbolMemorySpace::Absolute => Pointer::Absolute(symbol.offset),
+                        SymbolMemorySpace::Banked(bank) => Pointer::Banked(bank, symbol.offset),
+                    };
+                    statements.push(Ld {
+                        source: Source::Pointer {
+                            base,
+                            offset: Some(Box::new(offset)),
+                        },
+                        destination: Destination::Pointer {
+                            base: dst_base,
+                            offset: None,
+                        },
+                    });
+                }
+                right => return Err(CompileError::Unsupported { span: right.span() }),
+            }
+        }
+        Expression::Call(call) if builtin(call).is_some() => {
+            #[rustfmt::skip] let source = compile_builtin(call, symbol_alloc, fn_alloc, register_alloc, statements)?;
+            let source = match source {
+                Some(source) => source,
+                None => return Err(CompileError::Unsupported { span: call.span() }),
+            };
+            free_source_registers(&source, register_alloc);
+            statements.push(Statement::Ld {
+                source,
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }
+        Expression::Call(call) => {
+            if let Expression::Path(path) = &call.inner.left {
+                fn_alloc.check_visible(path)?;
+            }
+            // calls to a function, or through a function pointer
+            let (args_layout, ret_layout, routine) = match fn_path(&call.inner.left, fn_alloc) {
+                Some((fn_, routine)) => (
+                    fn_.arg_layout.clone(),
+                    fn_.ret_layout.clone(),
+                    Some(routine),
+                ),
+                None => match fn_instance(call, symbol_alloc, fn_alloc) {
+                    Some((fn_, routine)) => (fn_.arg_layout, fn_.ret_layout, Some(routine)),
+                    None => match value_layout(&call.inner.left, symbol_alloc) {
+                        Some(Layout::Fn { args, ret }) => (args, ret.map(|r| *r), None),
+                        _ => {
+                            // the callee may be an undefined symbol
+                            if let Expression::Path(path) = &call.inner.left {
+                                symbol_alloc.symbol(path)?;
+                            }
+                            panic!("Not a function!")
+                        }
+                    },
+                },
+            };
+
+            // check that the function returns the type we're trying to compile!
+            //assert_eq!(ret_layout.as_ref(), Some(layout));
+
+            let args_call = &call.inner.args;
+
+            // TODO implement functions
+            #[warn(unused)]
+            let _destination = Some(Destination::Pointer {
+                base: dst_base,
+                offset: None,
+            });
+
+            assert_eq!(args_call.len(), args_layout.len());
+
+            let mut offset = 0;
+            let start =
+                symbol_alloc.stack_address() - ret_layout.as_ref().map(|l| l.size()).unwrap_or(0);
+
+            for (call_arg, arg_layout) in args_call.iter().zip(&args_layout) {
+                compile_expression_into_pointer(
+                    call_arg,
+                    arg_layout,
+                    symbol_alloc,
+                    fn_alloc,
+                    dst_base.offset(offset),
+                    register_alloc,
+                    statements,
+                )?;
+                offset += arg_layout.size();
+            }
+
+            // call the function and place the results in the stack
+            match routine {
+                Some(routine) => statements.push(Statement::Call {
+                    routine,
+                    range: start..,
+                }),
+                None => {
+                    #[rustfmt::skip] let routine = compile_expr_u16(&call.inner.left, symbol_alloc, fn_alloc, register_alloc, statements)?;
+                    free_source_registers(&routine, register_alloc);
+                    statements.push(Statement::CallIndirect {
+                        routine,
+                        range: start..,
+                    });
+                }
+            }
+            for i in 0..layout.size() {
+                let source = Source::Pointer {
+                    base: Pointer::Return(i),
+                    offset: None,
+                };
+                let destination = Destination::Pointer {
+                    base: Pointer::Stack(start + i),
+                    offset: None,
+                };
+                statements.push(Statement::Ld {
+                    source,
+                    destination,
+                });
+            }
+        }
+        Expression::StructLit(_) => {
+            panic!("Struct literals can only be assigned to a named value")
+        }
+    }
+    Ok(())
+}
+
+// store a literal value at the given address.
+fn compile_literal_into_pointer(
+    lit: u16,
+    span: Span,
+    layout: &Layout,
+    dst_base: Pointer,
+    statements: &mut Vec<Statement>,
+) -> Result<(), CompileError> {
+    use super::Statement::{Ld, LdW};
+    match layout {
+        // negative values are sign extended by the evaluation
+        Layout::U8 | Layout::I8 => {
+            if lit > 0xff && lit < 0xff80 {
+                return Err(CompileError::OutOfRange { span });
+            }
+            statements.push(Ld {
+                source: Source::Literal(lit as u8),
+                destination: Destination::Pointer {
+                    base: dst_base,
+                    offset: None,
+                },
+            });
+        }
+        Layout::U16 | Layout::I16 | Layout::Pointer(_) => statements.push(LdW {
+            source: Source::Literal(lit),
+            destination: Destination::Pointer {
+                base: dst_base,
+                offset: None,
+            },
+        }),
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{byteorder::NativeEndian, parser::ast};
+
+    #[test]
+    fn const_expr() {
+        fn ast(input: &str) -> ast::Expression<'_> {
+            let mut ctx = crate::parser::ContextBuilder::default().build();
+            let mut tokens = crate::parser::lex::Tokens::new(input).peekable();
+            crate::parser::ast::Grammar::parse(&mut ctx, &mut tokens)
+                .expect("Error parsing constant expression")
+        }
+
+        assert_eq!(
+            Some(0x42),
+            super::const_expr::<NativeEndian>(&ast("(+ 0x40 2)"), None)
+        );
+    }
+}
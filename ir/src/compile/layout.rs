@@ -0,0 +1,583 @@
+use crate::{
+    compile::{alloc::SymbolAlloc, expression::const_expr},
+    parser::{
+        ast,
+        ast::{
+            types::{Attribute, Member},
+            Type,
+        },
+    },
+};
+use byteorder::{ByteOrder, NativeEndian};
+
+const BYTE_SIZE: u16 = 1;
+const WORD_SIZE: u16 = 2;
+
+/// Memory layout.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Layout {
+    /// Unsigned 8bit byte layout.
+    U8,
+
+    /// Signed 8bit byte layout.
+    I8,
+
+    /// Unsigned 16bit word layout.
+    U16,
+
+    /// Signed 16bit word layout.
+    I16,
+
+    /// Fixed-point 16bit word layout, with 8 fractional bits (`fx8.8`).
+    Fixed,
+
+    /// Array layout.
+    Array {
+        /// Array inner type layout.
+        inner: Box<Self>,
+
+        /// Array length.
+        len: u16,
+    },
+
+    /// Pointer layout (16bits).
+    Pointer(Box<Self>),
+
+    /// Function pointer layout (16bits index of the routine).
+    Fn {
+        /// Layouts of the function arguments.
+        args: Vec<Self>,
+
+        /// Layout of the returned value.
+        ret: Option<Box<Self>>,
+    },
+
+    /// Struct memory layout.
+    Struct(Vec<Self>),
+
+    /// Enum memory layout.
+    Union(Vec<Self>),
+
+    /// Bitfield of an integer layout.
+    Bits {
+        /// Layout of the integer holding the bitfield.
+        inner: Box<Self>,
+
+        /// Position of the least significant bit of the bitfield.
+        shift: u8,
+
+        /// Bitfield width, in bits.
+        width: u8,
+    },
+}
+
+impl Layout {
+    /// Create type layout from a type from the AST.
+    pub fn new(ty: &ast::Type<'_>) -> Self {
+        Self::with_symbols::<NativeEndian>(ty, None)
+    }
+
+    /// Create type layout from a type from the AST, resolving the symbols it
+    /// references (array lengths and enum types).
+    pub fn with_symbols<B: ByteOrder>(
+        ty: &ast::Type<'_>,
+        symbol_alloc: Option<&SymbolAlloc<B>>,
+    ) -> Self {
+        match ty {
+            Type::U8(_) | Type::Bool(_) => Self::U8,
+            Type::I8(_) => Self::I8,
+            Type::U16(_) => Self::U16,
+            Type::I16(_) => Self::I16,
+            Type::Fixed(_) => Self::Fixed,
+            Type::Array(array) => {
+                let inner = Box::new(Self::with_symbols(&array.type_, symbol_alloc));
+                let len = const_expr(&array.len, symbol_alloc).unwrap();
+                Self::Array { inner, len }
+            }
+            Type::Pointer(ptr) => {
+                let ptr = Box::new(Self::with_symbols(&ptr.type_, symbol_alloc));
+                Self::Pointer(ptr)
+            }
+            Type::Fn(fn_) => Self::Fn {
+                args: fn_
+                    .args
+                    .iter()
+                    .flat_map(|a| &a.inner)
+                    .map(|type_| Self::with_symbols(type_, symbol_alloc))
+                    .collect(),
+                ret: fn_
+                    .fn_return
+                    .as_ref()
+                    .map(|r| Box::new(Self::with_symbols(&r.type_, symbol_alloc))),
+            },
+            // tuples are laid out like structs
+            Type::Tuple(tuple) => Self::Struct(
+                tuple
+                    .inner
+                    .iter()
+                    .map(|type_| Self::with_symbols(type_, symbol_alloc))
+                    .collect(),
+            ),
+            Type::Struct(struct_) => Self::Struct(Self::struct_members(
+                &struct_.attributes,
+                &struct_.fields,
+                symbol_alloc,
+            )),
+            Type::Union(union) => Self::Union(Self::union_members(
+                &union.attributes,
+                &union.fields,
+                symbol_alloc,
+            )),
+            // enums are represented as u8
+            Type::Path(path) if matches!(symbol_alloc, Some(s) if s.is_enum(path)) => Self::U8,
+            // type aliases are expanded
+            Type::Path(path) => symbol_alloc
+                .and_then(|s| s.alias_layout(path))
+                .expect("Type noy yet supported!"),
+        }
+    }
+
+    /// Alignment of a type from the AST, in bytes.
+    ///
+    /// See [`Attribute`](ast::types::Attribute) for the layout rules.
+    pub fn align<B: ByteOrder>(ty: &ast::Type<'_>, symbol_alloc: Option<&SymbolAlloc<B>>) -> u16 {
+        match ty {
+            Type::Array(array) => Self::align(&array.type_, symbol_alloc),
+            Type::Struct(struct_) => {
+                members_align(&struct_.attributes, &struct_.fields, symbol_alloc)
+            }
+            Type::Union(union) => members_align(&union.attributes, &union.fields, symbol_alloc),
+            Type::Path(path) => symbol_alloc.and_then(|s| s.alias_align(path)).unwrap_or(1),
+            _ => 1,
+        }
+    }
+
+    // layout of a member of a struct or union type.
+    fn member<B: ByteOrder>(member: &Member<'_>, symbol_alloc: Option<&SymbolAlloc<B>>) -> Self {
+        match member {
+            Member::Field(field) => Self::with_symbols(&field.type_, symbol_alloc),
+            Member::Struct(struct_) => Self::Struct(Self::struct_members(
+                &struct_.attributes,
+                &struct_.fields,
+                symbol_alloc,
+            )),
+            Member::Union(union) => Self::Union(Self::union_members(
+                &union.attributes,
+                &union.fields,
+                symbol_alloc,
+            )),
+        }
+    }
+
+    // layouts of the members of a struct type. Padding is laid out as byte
+    // arrays between the members, and after the last one.
+    fn struct_members<B: ByteOrder>(
+        attributes: &[Attribute<'_>],
+        members: &[Member<'_>],
+        symbol_alloc: Option<&SymbolAlloc<B>>,
+    ) -> Vec<Self> {
+        let packed = is_packed(attributes);
+        let mut layouts = Vec::new();
+        let mut size = 0;
+        for member in members {
+            if !packed {
+                let offset = align_to(size, member_align(member, symbol_alloc));
+                layouts.extend(Self::padding(offset - size));
+                size = offset;
+            }
+            let layout = Self::member(member, symbol_alloc);
+            size += layout.size();
+            layouts.push(layout);
+        }
+        let align = members_align(attributes, members, symbol_alloc);
+        layouts.extend(Self::padding(align_to(size, align) - size));
+        layouts
+    }
+
+    // layouts of the members of a union type. The padding is laid out as a byte
+    // array as large as the union.
+    fn union_members<B: ByteOrder>(
+        attributes: &[Attribute<'_>],
+        members: &[Member<'_>],
+        symbol_alloc: Option<&SymbolAlloc<B>>,
+    ) -> Vec<Self> {
+        let mut layouts: Vec<_> = members
+            .iter()
+            .map(|member| Self::member(member, symbol_alloc))
+            .collect();
+        let size = layouts.iter().fold(0, |size, l| l.size().max(size));
+        let align = members_align(attributes, members, symbol_alloc);
+        if align_to(size, align) > size {
+            layouts.extend(Self::padding(align_to(size, align)));
+        }
+        layouts
+    }
+
+    // layout of `len` bytes of padding, if any.
+    fn padding(len: u16) -> Option<Self> {
+        if len > 0 {
+            Some(Self::Array {
+                inner: Box::new(Self::U8),
+                len,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Compute size of the type layout.
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::U8 | Self::I8 => BYTE_SIZE,
+            Self::U16 | Self::I16 | Self::Fixed | Self::Pointer(_) | Self::Fn { .. } => WORD_SIZE,
+            Self::Array { inner, len } => len * inner.size(),
+            Self::Struct(inner) => inner.iter().fold(0, |o, l| o + l.size()),
+            Self::Union(inner) => inner.iter().fold(0, |o, l| l.size().max(o)),
+            Self::Bits { inner, .. } => inner.size(),
+        }
+    }
+}
+
+/// Layout of an argument (or the return value) of a generic function, which
+/// depends on the values of its generic parameters.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Template {
+    /// Layout that doesn't depend on the generic parameters.
+    Layout(Layout),
+
+    /// Array whose length is a generic parameter.
+    Array {
+        /// Array inner type template.
+        inner: Box<Self>,
+
+        /// Index of the generic parameter.
+        param: usize,
+    },
+
+    /// Pointer to a template.
+    Pointer(Box<Self>),
+}
+
+impl Template {
+    /// Create a template from a type from the AST, given the names of the
+    /// generic parameters.
+    pub fn new<B: ByteOrder>(
+        ty: &ast::Type<'_>,
+        params: &[String],
+        symbol_alloc: &SymbolAlloc<B>,
+    ) -> Self {
+        match ty {
+            Type::Array(array) => match &array.len {
+                ast::Expression::Path(path) => {
+                    let name: Vec<_> = path.iter().map(|i| i.name().to_string()).collect();
+                    match params.iter().position(|p| *p == name.join("::")) {
+                        Some(param) => Self::Array {
+                            inner: Box::new(Self::new(&array.type_, params, symbol_alloc)),
+                            param,
+                        },
+                        None => Self::Layout(Layout::with_symbols(ty, Some(symbol_alloc))),
+                    }
+                }
+                _ => Self::Layout(Layout::with_symbols(ty, Some(symbol_alloc))),
+            },
+            Type::Pointer(ptr) => match Self::new(&ptr.type_, params, symbol_alloc) {
+                Self::Layout(inner) => Self::Layout(Layout::Pointer(Box::new(inner))),
+                inner => Self::Pointer(Box::new(inner)),
+            },
+            ty => Self::Layout(Layout::with_symbols(ty, Some(symbol_alloc))),
+        }
+    }
+
+    /// Layout given the values of the generic parameters.
+    pub fn layout(&self, values: &[u16]) -> Layout {
+        match self {
+            Self::Layout(layout) => layout.clone(),
+            Self::Array { inner, param } => Layout::Array {
+                inner: Box::new(inner.layout(values)),
+                len: values[*param],
+            },
+            Self::Pointer(inner) => Layout::Pointer(Box::new(inner.layout(values))),
+        }
+    }
+
+    /// Infer the values of the generic parameters from the layout of a value
+    /// matching the template.
+    /// Panics if the values are inconsistent with the ones inferred so far.
+    pub fn infer(&self, layout: &Layout, values: &mut [Option<u16>]) {
+        match (self, layout) {
+            (Self::Array { inner, param }, Layout::Array { inner: layout, len }) => {
+                match values[*param] {
+                    Some(value) => assert_eq!(value, *len, "Mismatched generic array lengths"),
+                    None => values[*param] = Some(*len),
+                }
+                inner.infer(layout, values);
+            }
+            (Self::Pointer(inner), Layout::Pointer(layout)) => inner.infer(layout, values),
+            _ => {}
+        }
+    }
+}
+
+// first offset from `offset` that is a multiple of `align`.
+pub(crate) fn align_to(offset: u16, align: u16) -> u16 {
+    offset.div_ceil(align) * align
+}
+
+// returns true if the attributes include `#[repr(packed)]`.
+pub(crate) fn is_packed(attributes: &[Attribute<'_>]) -> bool {
+    attributes.iter().any(|attribute| attribute.is_packed())
+}
+
+// alignment of a struct or union type, given its attributes and members.
+pub(crate) fn members_align<B: ByteOrder>(
+    attributes: &[Attribute<'_>],
+    members: &[Member<'_>],
+    symbol_alloc: Option<&SymbolAlloc<B>>,
+) -> u16 {
+    let align = attributes
+        .iter()
+        .filter_map(|attribute| attribute.align())
+        .map(|expression| {
+            let align = const_expr(expression, symbol_alloc).expect("Not a constant alignment");
+            assert!(align.is_power_of_two(), "Alignment is not a power of two");
+            align
+        })
+        .max()
+        .unwrap_or(1);
+    if is_packed(attributes) {
+        align
+    } else {
+        members
+            .iter()
+            .map(|member| member_align(member, symbol_alloc))
+            .fold(align, u16::max)
+    }
+}
+
+// alignment of a member of a struct or union type.
+pub(crate) fn member_align<B: ByteOrder>(
+    member: &Member<'_>,
+    symbol_alloc: Option<&SymbolAlloc<B>>,
+) -> u16 {
+    match member {
+        Member::Field(field) => Layout::align(&field.type_, symbol_alloc),
+        Member::Struct(struct_) => {
+            members_align(&struct_.attributes, &struct_.fields, symbol_alloc)
+        }
+        Member::Union(union) => members_align(&union.attributes, &union.fields, symbol_alloc),
+    }
+}
+
+// mask of the low `width` bits of a bitfield.
+pub(crate) fn bits_mask(width: u8) -> u16 {
+    ((1u32 << width) - 1) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::Layout;
+    use crate::parser::{ast::Grammar, lex::Tokens, ContextBuilder};
+    use byteorder::NativeEndian;
+
+    #[test]
+    fn test_new() {
+        let mut ctx = ContextBuilder::default().build();
+        let mut tokens = Tokens::new("[&u8 (+ 2 2)]").peekable();
+        let type_ = Grammar::parse(&mut ctx, &mut tokens).unwrap();
+
+        assert_eq!(
+            Layout::Array {
+                inner: Box::new(Layout::Pointer(Box::new(Layout::U8))),
+                len: 4
+            },
+            Layout::new(&type_)
+        );
+    }
+
+    #[test]
+    fn test_align() {
+        let mut ctx = ContextBuilder::default().build();
+        let mut tokens = Tokens::new("#[align(4)] struct { a:u8, b:u16 }").peekable();
+        let type_ = Grammar::parse(&mut ctx, &mut tokens).unwrap();
+        let layout = Layout::new(&type_);
+
+        assert_eq!(4, Layout::align::<NativeEndian>(&type_, None));
+        assert_eq!(4, layout.size());
+        assert_eq!(
+            Layout::Struct(vec![
+                Layout::U8,
+                Layout::U16,
+                Layout::Array {
+                    inner: Box::new(Layout::U8),
+                    len: 1
+                }
+            ]),
+            layout
+        );
+    }
+
+    #[test]
+    fn zero_size_types() {
+        assert_eq!(
+            0,
+            Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 0
+            }
+            .size()
+        );
+        assert_eq!(0, Layout::Struct(vec![]).size());
+        assert_eq!(0, Layout::Union(vec![]).size());
+    }
+
+    #[test]
+    fn size_u8() {
+        assert_eq!(1, Layout::U8.size());
+    }
+
+    #[test]
+    fn size_i8() {
+        assert_eq!(1, Layout::I8.size());
+    }
+
+    #[test]
+    fn size_word() {
+        assert_eq!(2, Layout::U16.size());
+        assert_eq!(2, Layout::I16.size());
+    }
+
+    #[test]
+    fn test_pointer() {
+        assert_eq!(2, Layout::Pointer(Box::new(Layout::U8)).size());
+        assert_eq!(
+            2,
+            Layout::Pointer(Box::new(Layout::Struct(vec![Layout::U8, Layout::I8]))).size()
+        );
+        assert_eq!(
+            2,
+            Layout::Pointer(Box::new(Layout::Struct(vec![Layout::Struct(vec![])]))).size()
+        );
+        assert_eq!(
+            2,
+            Layout::Pointer(Box::new(Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 16
+            }))
+            .size()
+        );
+    }
+
+    #[test]
+    fn test_array() {
+        assert_eq!(
+            0,
+            Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 0
+            }
+            .size()
+        );
+        assert_eq!(
+            1,
+            Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 1
+            }
+            .size()
+        );
+        assert_eq!(
+            2,
+            Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 2
+            }
+            .size()
+        );
+        assert_eq!(
+            3,
+            Layout::Array {
+                inner: Box::new(Layout::U8),
+                len: 3
+            }
+            .size()
+        );
+    }
+
+    #[test]
+    fn test_array_composite() {
+        let inner = Layout::Array {
+            inner: Box::new(Layout::U8),
+            len: 4,
+        };
+        assert_eq!(
+            0,
+            Layout::Array {
+                inner: Box::new(inner.clone()),
+                len: 0
+            }
+            .size()
+        );
+        assert_eq!(
+            4,
+            Layout::Array {
+                inner: Box::new(inner.clone()),
+                len: 1
+            }
+            .size()
+        );
+        assert_eq!(
+            8,
+            Layout::Array {
+                inner: Box::new(inner.clone()),
+                len: 2
+            }
+            .size()
+        );
+        assert_eq!(
+            12,
+            Layout::Array {
+                inner: Box::new(inner.clone()),
+                len: 3
+            }
+            .size()
+        );
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_eq!(0, Layout::Struct(vec![]).size());
+        assert_eq!(1, Layout::Struct(vec![Layout::U8]).size());
+        assert_eq!(2, Layout::Struct(vec![Layout::U8, Layout::I8]).size());
+        assert_eq!(
+            6,
+            Layout::Struct(vec![
+                Layout::U8,
+                Layout::I8,
+                Layout::Array {
+                    inner: Box::new(Layout::U8),
+                    len: 4,
+                }
+            ])
+            .size()
+        );
+    }
+
+    #[test]
+    fn test_union() {
+        assert_eq!(0, Layout::Union(vec![]).size());
+        assert_eq!(1, Layout::Union(vec![Layout::U8]).size());
+        assert_eq!(1, Layout::Union(vec![Layout::U8, Layout::I8]).size());
+        assert_eq!(
+            4,
+            Layout::Union(vec![
+                Layout::U8,
+                Layout::I8,
+                Layout::Array {
+                    inner: Box::new(Layout::U8),
+                    len: 4,
+                }
+            ])
+            .size()
+        );
+    }
+}
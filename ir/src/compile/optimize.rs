@@ -1,11 +1,14 @@
-use crate::ir::{
+use crate::{
     compile::NOP_UNREACHABLE,
     opcodes::{Location, Source, Statement},
+    opt::{location, location_mut},
+    parser::lex::span::Span,
 };
 
 /// Delete unreachable statements, previously marked as Nop(NOP_UNREACHABLE) by
-/// the other functions. TODO confusing code: document or rewrite
-pub(crate) fn delete_nops(statements: &mut Vec<Statement>) -> bool {
+/// the other functions, along with their spans (if any).
+/// TODO confusing code: document or rewrite
+pub(crate) fn delete_nops(statements: &mut Vec<Statement>, spans: &mut Vec<Option<Span>>) -> bool {
     use Statement::{Jmp, JmpCmp, JmpCmpNot, Nop};
 
     // update jump instructions by counting the number of NOPs within a jump, and
@@ -13,46 +16,68 @@ pub(crate) fn delete_nops(statements: &mut Vec<Statement>) -> bool {
     // been updated and Nops can safely be removed from the ir.
     for i in 0..statements.len() {
         #[rustfmt::skip]
-            let r0 = match &statements[i] {
-            Jmp       { location: Location::Relative(r0)     } => *r0,
-            JmpCmp    { location: Location::Relative(r0), .. } => *r0,
-            JmpCmpNot { location: Location::Relative(r0), .. } => *r0,
+            let offsets = match &statements[i] {
+            Jmp       { location     } => location.offsets().to_vec(),
+            JmpCmp    { location, .. } => location.offsets().to_vec(),
+            JmpCmpNot { location, .. } => location.offsets().to_vec(),
             _ => continue,
         };
-        // range to compute the # of NOPs inside of
-        let mut range = i..(i + r0.abs() as usize + 1);
-        if r0 < 0 {
-            range.start -= r0.abs() as usize;
-            range.end -= r0.abs() as usize;
-        }
-        let nops = statements[range.clone()]
-            .iter()
-            .filter(|s| matches!(s, Nop(NOP_UNREACHABLE)))
-            .count();
-
-        // update how much the statement jumps by, by subtracting the # of Nops found
-        // within the jump.
-        let r1 = if r0 < 0 {
-            let mut t = r0 + nops as i8;
-            if &statements[(i as isize + r0 as isize) as usize] == &Statement::Nop(NOP_UNREACHABLE)
-            {
-                t -= 1
+        // every entry of a jump table is updated the same way
+        let offsets: Vec<_> = offsets
+            .into_iter()
+            .map(|r0| {
+                // range to compute the # of NOPs inside of (from the target of
+                // backward jumps, which may be the first statement)
+                let range = if r0 < 0 {
+                    (i as isize + r0 as isize + 1) as usize..i
+                } else {
+                    i..(i + r0 as usize + 1)
+                };
+                let nops = statements[range]
+                    .iter()
+                    .filter(|s| matches!(s, Nop(NOP_UNREACHABLE)))
+                    .count();
+
+                // update how much the statement jumps by, by subtracting the # of
+                // Nops found within the jump.
+                if r0 < 0 {
+                    r0 + nops as i8
+                } else {
+                    r0 - nops as i8
+                }
+            })
+            .collect();
+
+        // absolute locations move back by the # of Nops before their target
+        let absolute = match location(&statements[i]) {
+            Some(Location::Absolute(target)) => {
+                let nops = statements[..*target]
+                    .iter()
+                    .filter(|s| matches!(s, Nop(NOP_UNREACHABLE)))
+                    .count();
+                Some(Location::Absolute(target - nops))
             }
-            t
-        } else {
-            r0 - nops as i8
+            _ => None,
         };
+        if let Some(absolute) = absolute {
+            *location_mut(&mut statements[i]).unwrap() = absolute;
+        }
 
         #[rustfmt::skip]
         match &mut statements[i] {
-            Jmp       { location: Location::Relative(r0)     } => *r0 = r1,
-            JmpCmp    { location: Location::Relative(r0), .. } => *r0 = r1,
-            JmpCmpNot { location: Location::Relative(r0), .. } => *r0 = r1,
+            Jmp       { location     } => location.offsets_mut().copy_from_slice(&offsets),
+            JmpCmp    { location, .. } => location.offsets_mut().copy_from_slice(&offsets),
+            JmpCmpNot { location, .. } => location.offsets_mut().copy_from_slice(&offsets),
             _ => unreachable!(),
         }; // rustfmt::skip woks on expressions but not statements (adding ;
            // turns match into the former)
     }
 
+    if !spans.is_empty() {
+        let mut deleted = statements.iter().map(|s| matches!(s, Nop(NOP_UNREACHABLE)));
+        spans.retain(|_| !deleted.next().unwrap());
+    }
+
     // previous # of statements
     let len = statements.len();
     // once all Jmps and conditional Jmps have been updated, it is safe to delete
@@ -103,6 +128,17 @@ pub(crate) fn jump_threading(statements: &mut Vec<Statement>) -> bool {
                     statements_opt[i] = Jmp { location: Location::Relative(*r0 + *r1 + 1) };
                 }
             }
+            // entries of jump tables
+            Jmp { location: location @ Location::Table { .. } } => {
+                let mut location = location.clone();
+                for r0 in location.offsets_mut() {
+                    let next = ((i as isize) + (*r0 as isize) + 1) as usize;
+                    if let Jmp { location: Location::Relative(r1), } = &statements[next] {
+                        *r0 += *r1 + 1;
+                    }
+                }
+                statements_opt[i] = Jmp { location };
+            }
             _ => {}
         };
     }
@@ -123,16 +159,38 @@ pub(crate) fn mark_unreachable(statements: &mut Vec<Statement>) -> bool {
 
     while let Some(n) = stack.pop() {
         if !matches!(statements[n], Stop(_) | Ret) {
+            // jump tables may jump to any of their entries (or fall through),
+            // and absolute jumps to the statement at their index
+            let targets = match &statements[n] {
+                Jmp { location } | JmpCmp { location, .. } | JmpCmpNot { location, .. } => {
+                    match location {
+                        Location::Table { targets, .. } => targets
+                            .iter()
+                            .map(|r| (n as isize + *r as isize + 1) as usize)
+                            .collect(),
+                        Location::Absolute(target) => vec![*target],
+                        _ => vec![],
+                    }
+                }
+                _ => vec![],
+            };
+            for target in targets {
+                if let Some(false) = visited.get(target) {
+                    stack.push(target);
+                    visited[target] = true;
+                }
+            }
             let mut next = n + 1; // next statement
             let mut next_branch = n + 1; // next (branched) statement
             #[rustfmt::skip]
             match statements[n] {
-                JmpCmpNot { location: Location::Relative(r), source: Source::Literal(n) } if n != 0 => {
-                    next_branch = (n as isize + r as isize + 1) as usize;
-                    next = next_branch;
-                }
-                Jmp    { location: Location::Relative(r), .. } |
-                JmpCmp { location: Location::Relative(r), source: Source::Literal(0) } => {
+                // conditions that never jump
+                JmpCmp    { source: Source::Literal(0), .. } => {}
+                JmpCmpNot { source: Source::Literal(l), .. } if l != 0 => {}
+                // conditions that always jump
+                Jmp       { location: Location::Relative(r), .. } |
+                JmpCmp    { location: Location::Relative(r), source: Source::Literal(_) } |
+                JmpCmpNot { location: Location::Relative(r), source: Source::Literal(0) } => {
                     next_branch = (n as isize + r as isize + 1) as usize;
                     next = next_branch;
                 }
@@ -140,6 +198,10 @@ pub(crate) fn mark_unreachable(statements: &mut Vec<Statement>) -> bool {
                     next_branch = (n as isize + r as isize + 1) as usize,
                 JmpCmpNot { location: Location::Relative(r), .. } =>
                     next_branch = (n as isize + r as isize + 1) as usize,
+                // absolute jumps (whose target is already visited) and tail
+                // jumps to other routines don't fall through
+                Jmp       { location: Location::Absolute(_) } |
+                Jmp       { location: Location::Routine(_) } => continue,
                 _ => {}
             };
             if let Some(false) = visited.get(next) {
@@ -167,7 +229,7 @@ pub(crate) fn mark_unreachable(statements: &mut Vec<Statement>) -> bool {
 
 #[cfg(test)]
 mod test {
-    use crate::ir::{
+    use crate::{
         compile::NOP_UNREACHABLE,
         opcodes::{Location, Location::Relative, Source, Statement},
     };
@@ -178,7 +240,7 @@ mod test {
             Statement::Nop(0),
             Statement::JmpCmpNot {
                 location: Location::Relative(2),
-                source: Source::Literal(1),
+                source: Source::Literal(0),
             },
             Statement::Nop(0),
             Statement::Nop(0),
@@ -191,7 +253,7 @@ mod test {
             Statement::Nop(0),
             Statement::JmpCmpNot {
                 location: Location::Relative(2),
-                source: Source::Literal(1),
+                source: Source::Literal(0),
             },
             Statement::Nop(NOP_UNREACHABLE),
             Statement::Nop(NOP_UNREACHABLE),
@@ -207,7 +269,7 @@ mod test {
             Statement::Nop(0),
             Statement::JmpCmp {
                 location: Location::Relative(2),
-                source: Source::Literal(0),
+                source: Source::Literal(1),
             },
             Statement::Nop(0),
             Statement::Nop(0),
@@ -220,7 +282,7 @@ mod test {
             Statement::Nop(0),
             Statement::JmpCmp {
                 location: Location::Relative(2),
-                source: Source::Literal(0),
+                source: Source::Literal(1),
             },
             Statement::Nop(NOP_UNREACHABLE),
             Statement::Nop(NOP_UNREACHABLE),
@@ -361,7 +423,7 @@ mod test {
             },
         ];
 
-        super::delete_nops(&mut statements);
+        super::delete_nops(&mut statements, &mut Vec::new());
 
         let gt = vec![
             Statement::Nop(0),
@@ -406,7 +468,7 @@ mod test {
             },
         ];
 
-        super::delete_nops(&mut statements);
+        super::delete_nops(&mut statements, &mut Vec::new());
 
         let gt = vec![
             Statement::Nop(0),
@@ -468,7 +530,7 @@ mod test {
             },
         ];
 
-        super::delete_nops(&mut statements);
+        super::delete_nops(&mut statements, &mut Vec::new());
 
         let gt = vec![
             Statement::Nop(0),
@@ -654,7 +716,7 @@ mod test {
         for _ in 0..16 {
             super::jump_threading(&mut statements);
             super::mark_unreachable(&mut statements);
-            super::delete_nops(&mut statements);
+            super::delete_nops(&mut statements, &mut Vec::new());
         }
 
         let gt = vec![
@@ -0,0 +1,996 @@
+//! Text format of the IR.
+//!
+//! [`Ir`] implements [`Display`](fmt::Display), which prints the program in a
+//! stable, line oriented text format that [`parse_text`] reads back. The text
+//! can be used to inspect the output of the compiler, to diff it across
+//! versions, or to write IR by hand for tests.
+//!
+//! ```text
+//! overflow wrapping
+//! static_alloc 1
+//! volatile 0xff40..0xff41
+//! const 01 02 03
+//! handler main 1
+//!
+//! routine 0 "f" stack 1 args 1 return 1
+//!     ld [stack 0] -> [return 0]
+//!     ret
+//! end
+//!
+//! routine 1 "main" stack 1 args 0 return 0
+//!     ld 42 -> [stack 0]
+//!     call 0, 0..
+//!     ld [return 0] -> [absolute 0xff40]
+//!     stop success
+//! end
+//! ```
+//!
+//! Each line holds one item of the program (`;` starts a comment):
+//!
+//! - `overflow wrapping|saturating|trapping`, `static_alloc N`, and
+//!   `bank BANK N` (static memory of a RAM bank).
+//! - `region "NAME" START..END static|const|absolute`, and
+//!   `volatile START..END`.
+//! - `const` and `static` followed by bytes in hex, appended to the initial
+//!   const and static memory.
+//! - `handler main|vblank|lcd_stat|timer|serial|joypad ROUTINE`.
+//! - `routine INDEX "NAME"|- stack N args N return N [bank BANK]
+//!   [inline always|never]`, followed by the statements of the routine, one
+//!   per line, and `end`.
+//!
+//! Statements are written as their name in `snake_case`, followed by their
+//! sources and, if they have one, `->` and their destination:
+//!
+//! - Literals are numbers (`42`, `0xff`), and registers are `r` followed by
+//!   their index (`r3`).
+//! - Pointers are written in brackets, with an optional offset
+//!   (`[static 4]`, `[stack 2 + r0]`, `[banked 1 16]`).
+//! - Dereferences of 16bit sources are prefixed with `*` (`*[stack 0]`).
+//! - Jump locations are relative (`jmp -3`), and the ranges of calls are
+//!   written as the start of the frame of the called routine (`call 2, 4..`).
+//!   Jump tables are written as their index and targets
+//!   (`jmp table r0 [1 4 -2]`), jumps to the index of a statement as
+//!   `jmp absolute 12`, and tail jumps to a routine as `jmp routine 2`.
+//!
+//! Statements with a [span](Routine::spans) are followed by `@` and the span
+//! (`LINE:COLUMN..LINE:COLUMN`, and `source N` if it doesn't belong to the
+//! default source): `ld 2 -> [stack 0] @ 1:0..1:12`.
+//!
+//! ```
+//! use ir::{byteorder::NativeEndian, Ir};
+//!
+//! let ast = ir::parser::parse("static A:u8\nlet a:u8 = 2\n(= A (+ a 1))").unwrap();
+//! let ir: Ir<NativeEndian> = Ir::new(&ast);
+//!
+//! let text = ir.to_string();
+//! assert!(text.contains("add [stack 0], 1 -> "));
+//! assert_eq!(ir, ir::parse_text(&text).unwrap());
+//! ```
+use crate::{
+    opcodes::{Destination, Location, Pointer, Source, Statement, StopStatus},
+    parser::lex::span::{SourceId, Span},
+    ByteOrder, Handlers, Inline, Ir, Overflow, Region, Routine, Space,
+};
+use std::{collections::BTreeMap, convert::TryFrom, fmt, ops::Range};
+
+// bytes of const and static memory printed in each line.
+const BYTES_PER_LINE: usize = 16;
+
+// dynamic offset of a pointer.
+type Offset = Option<Box<Source<u8>>>;
+
+/// Error reading the text format of the IR.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    /// Line of the error, starting at 1.
+    pub line: usize,
+
+    /// Description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Read a program written in the text format of the IR.
+///
+/// Fails if the text is malformed, or if a handler refers to a routine that
+/// doesn't exist. Other inconsistencies (such as calls to routines that don't
+/// exist) are not checked.
+pub fn parse_text<B: ByteOrder>(input: &str) -> Result<Ir<B>, ParseError> {
+    let mut overflow = Overflow::default();
+    let mut static_alloc = 0;
+    let mut banks = BTreeMap::new();
+    let mut regions = Vec::new();
+    let mut volatile = Vec::new();
+    let mut const_ = Vec::new();
+    let mut static_ = Vec::new();
+    let mut handlers = Handlers::default();
+    let mut routines = Vec::new();
+
+    // routine whose statements are being read
+    let mut routine: Option<Routine> = None;
+    let mut number = 0;
+
+    for (index, text) in input.lines().enumerate() {
+        number = index + 1;
+        let mut line = Line::new(text, number)?;
+        if line.is_done() {
+            continue;
+        }
+        if let Some(current) = &mut routine {
+            if line.peek() == Some(&Token::Word("end")) {
+                line.next()?;
+                // routines without debug info have no spans
+                if current.spans.iter().all(Option::is_none) {
+                    current.spans.clear();
+                }
+                routines.extend(routine.take());
+            } else {
+                current.statements.push(line.statement()?);
+                current.spans.push(line.span()?);
+            }
+            line.end()?;
+            continue;
+        }
+        match line.word()? {
+            "overflow" => {
+                overflow = match line.word()? {
+                    "wrapping" => Overflow::Wrapping,
+                    "saturating" => Overflow::Saturating,
+                    "trapping" => Overflow::Trapping,
+                    word => return Err(line.error(format!("unknown overflow `{}`", word))),
+                }
+            }
+            "static_alloc" => static_alloc = line.number()?,
+            "bank" => {
+                let bank = line.number()?;
+                banks.insert(bank, line.number()?);
+            }
+            "region" => {
+                let name = line.string()?;
+                let range = line.range()?;
+                let space = match line.word()? {
+                    "static" => Space::Static,
+                    "const" => Space::Const,
+                    "absolute" => Space::Absolute,
+                    word => return Err(line.error(format!("unknown memory space `{}`", word))),
+                };
+                regions.push(Region { name, range, space });
+            }
+            "volatile" => volatile.push(line.range()?),
+            "const" => line.bytes(&mut const_)?,
+            "static" => line.bytes(&mut static_)?,
+            "handler" => {
+                let handler = line.word()?;
+                let index = line.number()?;
+                match handler {
+                    "main" => handlers.main = index,
+                    "vblank" => handlers.vblank = Some(index),
+                    "lcd_stat" => handlers.lcd_stat = Some(index),
+                    "timer" => handlers.timer = Some(index),
+                    "serial" => handlers.serial = Some(index),
+                    "joypad" => handlers.joypad = Some(index),
+                    word => return Err(line.error(format!("unknown handler `{}`", word))),
+                }
+            }
+            "routine" => routine = Some(line.routine(routines.len())?),
+            word => return Err(line.error(format!("unexpected `{}`", word))),
+        }
+        line.end()?;
+    }
+
+    if routine.is_some() {
+        return Err(ParseError {
+            line: number,
+            message: "expected `end` of the routine".to_string(),
+        });
+    }
+    let handled = [
+        Some(handlers.main),
+        handlers.vblank,
+        handlers.lcd_stat,
+        handlers.timer,
+        handlers.serial,
+        handlers.joypad,
+    ];
+    if let Some(index) = handled.iter().flatten().find(|i| **i >= routines.len()) {
+        return Err(ParseError {
+            line: number,
+            message: format!("undefined handler routine {}", index),
+        });
+    }
+
+    Ok(Ir {
+        const_: const_.into_boxed_slice(),
+        static_: static_.into_boxed_slice(),
+        static_alloc,
+        banks,
+        regions: regions.into_boxed_slice(),
+        volatile: volatile.into_boxed_slice(),
+        routines: routines.into_boxed_slice(),
+        handlers,
+        overflow,
+        _phantom: std::marker::PhantomData,
+    })
+}
+
+impl<B: ByteOrder> fmt::Display for Ir<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "overflow {}", self.overflow)?;
+        writeln!(f, "static_alloc {}", self.static_alloc)?;
+        for (bank, size) in &self.banks {
+            writeln!(f, "bank {} {}", bank, size)?;
+        }
+        for region in self.regions.iter() {
+            write!(f, "region ")?;
+            string(f, &region.name)?;
+            writeln!(
+                f,
+                " {:#06x}..{:#06x} {}",
+                region.range.start, region.range.end, region.space
+            )?;
+        }
+        for range in self.volatile.iter() {
+            writeln!(f, "volatile {:#06x}..{:#06x}", range.start, range.end)?;
+        }
+        bytes(f, "const", &self.const_)?;
+        bytes(f, "static", &self.static_)?;
+
+        let handlers = [
+            ("main", Some(self.handlers.main)),
+            ("vblank", self.handlers.vblank),
+            ("lcd_stat", self.handlers.lcd_stat),
+            ("timer", self.handlers.timer),
+            ("serial", self.handlers.serial),
+            ("joypad", self.handlers.joypad),
+        ];
+        for (name, index) in handlers.iter() {
+            if let Some(index) = index {
+                writeln!(f, "handler {} {}", name, index)?;
+            }
+        }
+
+        for (index, routine) in self.routines.iter().enumerate() {
+            write!(f, "\nroutine {} ", index)?;
+            match &routine.debug_name {
+                Some(name) => string(f, name)?,
+                None => write!(f, "-")?,
+            }
+            write!(
+                f,
+                " stack {} args {} return {}",
+                routine.stack_size, routine.args_size, routine.return_size
+            )?;
+            if let Some(bank) = routine.bank {
+                write!(f, " bank {}", bank)?;
+            }
+            match routine.inline {
+                Inline::Auto => {}
+                Inline::Always => write!(f, " inline always")?,
+                Inline::Never => write!(f, " inline never")?,
+            }
+            writeln!(f)?;
+            for (index, statement) in routine.statements.iter().enumerate() {
+                write!(f, "    {}", statement)?;
+                if let Some(span) = routine.span(index) {
+                    write!(
+                        f,
+                        " @ {}:{}..{}:{}",
+                        span.min[0], span.min[1], span.max[0], span.max[1]
+                    )?;
+                    if span.source != SourceId::default() {
+                        write!(f, " source {}", span.source.index())?;
+                    }
+                }
+                writeln!(f)?;
+            }
+            writeln!(f, "end")?;
+        }
+        Ok(())
+    }
+}
+
+// print a quoted string.
+fn string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        if c == '"' || c == '\\' {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    write!(f, "\"")
+}
+
+// print memory data, in lines of up to `BYTES_PER_LINE` bytes.
+fn bytes(f: &mut fmt::Formatter<'_>, directive: &str, bytes: &[u8]) -> fmt::Result {
+    for chunk in bytes.chunks(BYTES_PER_LINE) {
+        write!(f, "{}", directive)?;
+        for byte in chunk {
+            write!(f, " {:02x}", byte)?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wrapping => write!(f, "wrapping"),
+            Self::Saturating => write!(f, "saturating"),
+            Self::Trapping => write!(f, "trapping"),
+        }
+    }
+}
+
+impl fmt::Display for Space {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Static => write!(f, "static"),
+            Self::Const => write!(f, "const"),
+            Self::Absolute => write!(f, "absolute"),
+        }
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Absolute(address) => write!(f, "absolute {:#06x}", address),
+            Self::Static(address) => write!(f, "static {}", address),
+            Self::Const(address) => write!(f, "const {}", address),
+            Self::Stack(address) => write!(f, "stack {}", address),
+            Self::Return(address) => write!(f, "return {}", address),
+            Self::Banked(bank, address) => write!(f, "banked {} {}", bank, address),
+        }
+    }
+}
+
+// print a pointer, with its optional offset.
+fn pointer(f: &mut fmt::Formatter<'_>, base: &Pointer, offset: &Offset) -> fmt::Result {
+    write!(f, "[{}", base)?;
+    if let Some(offset) = offset {
+        write!(f, " + {}", offset)?;
+    }
+    write!(f, "]")
+}
+
+impl<T: fmt::Display> fmt::Display for Source<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pointer { base, offset } => pointer(f, base, offset),
+            Self::Indirect(source) => write!(f, "*{}", source),
+            Self::Register(register) => write!(f, "r{}", register),
+            Self::Literal(literal) => write!(f, "{}", literal),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pointer { base, offset } => pointer(f, base, offset),
+            Self::Indirect(source) => write!(f, "*{}", source),
+            Self::Register(register) => write!(f, "r{}", register),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Relative(offset) => write!(f, "{}", offset),
+            Self::Table { index, targets } => {
+                write!(f, "table {} [", index)?;
+                for (i, target) in targets.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", target)?;
+                }
+                write!(f, "]")
+            }
+            Self::Absolute(index) => write!(f, "absolute {}", index),
+            Self::Routine(routine) => write!(f, "routine {}", routine),
+        }
+    }
+}
+
+impl fmt::Display for StopStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Success => write!(f, "success"),
+        }
+    }
+}
+
+// Statements with a source and a destination (unary), two sources and a
+// destination (binary), or two sources, a carry and a destination (carry), and
+// their names in the text format.
+macro_rules! operators {
+    (
+        unary { $($unary:ident $unary_name:literal,)* }
+        binary { $($binary:ident $binary_name:literal,)* }
+        carry { $($carry:ident $carry_name:literal,)* }
+    ) => {
+        // print an operator statement.
+        fn fmt_operator(statement: &Statement, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match statement {
+                $(Statement::$unary { source, destination } => {
+                    write!(f, "{} {} -> {}", $unary_name, source, destination)
+                })*
+                $(Statement::$binary { left, right, destination } => {
+                    write!(f, "{} {}, {} -> {}", $binary_name, left, right, destination)
+                })*
+                $(Statement::$carry { left, right, carry, destination } => {
+                    write!(f, "{} {}, {}, {} -> {}", $carry_name, left, right, carry, destination)
+                })*
+                _ => unreachable!("not an operator statement"),
+            }
+        }
+
+        // read the operands of an operator statement, if the name is one.
+        fn parse_operator(name: &str, line: &mut Line<'_>) -> Result<Option<Statement>, ParseError> {
+            let statement = match name {
+                $($unary_name => {
+                    let source = line.source()?;
+                    let destination = line.destination()?;
+                    Statement::$unary { source, destination }
+                })*
+                $($binary_name => {
+                    let left = line.source()?;
+                    line.expect(",")?;
+                    let right = line.source()?;
+                    let destination = line.destination()?;
+                    Statement::$binary { left, right, destination }
+                })*
+                $($carry_name => {
+                    let left = line.source()?;
+                    line.expect(",")?;
+                    let right = line.source()?;
+                    line.expect(",")?;
+                    let carry = line.source()?;
+                    let destination = line.destination()?;
+                    Statement::$carry { left, right, carry, destination }
+                })*
+                _ => return Ok(None),
+            };
+            Ok(Some(statement))
+        }
+    };
+}
+
+operators! {
+    unary {
+        Ld "ld",
+        LdW "ld_w",
+        LdAddr "ld_addr",
+        Ext "ext",
+        SignExt "sign_ext",
+        Trunc "trunc",
+        Inc "inc",
+        Dec "dec",
+        IncW "inc_w",
+        DecW "dec_w",
+        SwapNibbles "swap_nibbles",
+        BcdAdjust "bcd_adjust",
+    }
+    binary {
+        Add "add",
+        Sub "sub",
+        And "and",
+        Xor "xor",
+        Or "or",
+        LeftShift "left_shift",
+        RightShift "right_shift",
+        Mul "mul",
+        Div "div",
+        Rem "rem",
+        RightShiftS "right_shift_s",
+        DivS "div_s",
+        RemS "rem_s",
+        AddW "add_w",
+        SubW "sub_w",
+        AndW "and_w",
+        XorW "xor_w",
+        OrW "or_w",
+        LeftShiftW "left_shift_w",
+        RightShiftW "right_shift_w",
+        MulW "mul_w",
+        DivW "div_w",
+        RemW "rem_w",
+        RightShiftSW "right_shift_sw",
+        DivSW "div_sw",
+        RemSW "rem_sw",
+        Eq "eq",
+        NotEq "not_eq",
+        Greater "greater",
+        GreaterEq "greater_eq",
+        Less "less",
+        LessEq "less_eq",
+        GreaterS "greater_s",
+        GreaterEqS "greater_eq_s",
+        LessS "less_s",
+        LessEqS "less_eq_s",
+        EqW "eq_w",
+        NotEqW "not_eq_w",
+        GreaterW "greater_w",
+        GreaterEqW "greater_eq_w",
+        LessW "less_w",
+        LessEqW "less_eq_w",
+        GreaterSW "greater_sw",
+        GreaterEqSW "greater_eq_sw",
+        LessSW "less_sw",
+        LessEqSW "less_eq_sw",
+    }
+    carry {
+        AddC "add_c",
+        SubC "sub_c",
+        AddCarry "add_carry",
+        SubBorrow "sub_borrow",
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nop(n) => write!(f, "nop {}", n),
+            Self::Stop(status) => write!(f, "stop {}", status),
+            Self::Jmp { location } => write!(f, "jmp {}", location),
+            Self::JmpCmp { location, source } => write!(f, "jmp_cmp {}, {}", location, source),
+            Self::JmpCmpNot { location, source } => {
+                write!(f, "jmp_cmp_not {}, {}", location, source)
+            }
+            Self::Call { routine, range } => write!(f, "call {}, {}..", routine, range.start),
+            Self::CallIndirect { routine, range } => {
+                write!(f, "call_indirect {}, {}..", routine, range.start)
+            }
+            Self::Ret => write!(f, "ret"),
+            Self::PushBank { bank } => write!(f, "push_bank {}", bank),
+            Self::PopBank => write!(f, "pop_bank"),
+            Self::Memcpy {
+                source,
+                destination,
+                len,
+            } => write!(f, "memcpy {}, {}, {}", source, destination, len),
+            Self::Memset {
+                value,
+                destination,
+                len,
+            } => write!(f, "memset {}, {}, {}", value, destination, len),
+            Self::Halt => write!(f, "halt"),
+            Self::EnableInterrupts => write!(f, "enable_interrupts"),
+            statement => fmt_operator(statement, f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token<'a> {
+    // number, register, or keyword
+    Word(&'a str),
+    // quoted string, unescaped
+    Str(String),
+    Punct(&'static str),
+}
+
+// punctuation, longest first.
+const PUNCT: &[&str] = &["->", "..", "[", "]", ",", "*", "+", "@", ":"];
+
+// tokens of a line of text.
+struct Line<'a> {
+    number: usize,
+    tokens: Vec<Token<'a>>,
+    next: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str, number: usize) -> Result<Self, ParseError> {
+        let mut line = Self {
+            number,
+            tokens: Vec::new(),
+            next: 0,
+        };
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            let c = match rest.chars().next() {
+                None | Some(';') => break,
+                Some(c) => c,
+            };
+            if c == '"' {
+                let mut string = String::new();
+                let mut chars = rest.char_indices().skip(1);
+                let end = loop {
+                    match chars.next() {
+                        Some((i, '"')) => break i + 1,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) if c == '"' || c == '\\' => string.push(c),
+                            _ => return Err(line.error("invalid escape in string")),
+                        },
+                        Some((_, c)) => string.push(c),
+                        None => return Err(line.error("unterminated string")),
+                    }
+                };
+                line.tokens.push(Token::Str(string));
+                rest = &rest[end..];
+            } else if let Some(punct) = PUNCT.iter().find(|p| rest.starts_with(*p)) {
+                line.tokens.push(Token::Punct(punct));
+                rest = &rest[punct.len()..];
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(line.error(format!("unexpected `{}`", c)));
+                }
+                line.tokens.push(Token::Word(&rest[..end]));
+                rest = &rest[end..];
+            }
+        }
+        Ok(line)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.number,
+            message: message.into(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.next == self.tokens.len()
+    }
+
+    // fails if there are tokens left.
+    fn end(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(self.unexpected(token)),
+        }
+    }
+
+    fn unexpected(&self, token: &Token<'_>) -> ParseError {
+        match token {
+            Token::Word(word) => self.error(format!("unexpected `{}`", word)),
+            Token::Str(string) => self.error(format!("unexpected string {:?}", string)),
+            Token::Punct(punct) => self.error(format!("unexpected `{}`", punct)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next)
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, ParseError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end of line"))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    // consume the given punctuation, if it comes next.
+    fn eat(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(p)) if *p == punct => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => Err(self.unexpected(token)),
+                None => Err(self.error(format!("expected `{}`", punct))),
+            }
+        }
+    }
+
+    fn word(&mut self) -> Result<&'a str, ParseError> {
+        match self.next()? {
+            Token::Word(word) => Ok(word),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.word()? {
+            word if word == keyword => Ok(()),
+            word => Err(self.error(format!("expected `{}`, found `{}`", keyword, word))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Token::Str(string) => Ok(string),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    fn number<T: TryFrom<i64>>(&mut self) -> Result<T, ParseError> {
+        let word = self.word()?;
+        self.int(word)
+    }
+
+    // decimal or `0x` prefixed hex number, in the range of `T`.
+    fn int<T: TryFrom<i64>>(&self, word: &str) -> Result<T, ParseError> {
+        let (sign, digits) = match word.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, word),
+        };
+        let value = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => digits.parse(),
+        };
+        value
+            .ok()
+            .and_then(|value| T::try_from(sign * value).ok())
+            .ok_or_else(|| self.error(format!("invalid number `{}`", word)))
+    }
+
+    fn range<T: TryFrom<i64>>(&mut self) -> Result<Range<T>, ParseError> {
+        let start = self.number()?;
+        self.expect("..")?;
+        Ok(start..self.number()?)
+    }
+
+    // bytes in hex, until the end of the line.
+    fn bytes(&mut self, bytes: &mut Vec<u8>) -> Result<(), ParseError> {
+        while !self.is_done() {
+            let word = self.word()?;
+            match u8::from_str_radix(word, 16) {
+                Ok(byte) if word.len() == 2 => bytes.push(byte),
+                _ => return Err(self.error(format!("invalid byte `{}`", word))),
+            }
+        }
+        Ok(())
+    }
+
+    // header of the routine with the given index (after `routine`).
+    fn routine(&mut self, index: usize) -> Result<Routine, ParseError> {
+        let number: usize = self.number()?;
+        if number != index {
+            return Err(self.error(format!("expected routine {}, found {}", index, number)));
+        }
+        let debug_name = match self.next()? {
+            Token::Str(name) => Some(name),
+            Token::Word("-") => None,
+            token => return Err(self.unexpected(&token)),
+        };
+        self.keyword("stack")?;
+        let stack_size = self.number()?;
+        self.keyword("args")?;
+        let args_size = self.number()?;
+        self.keyword("return")?;
+        let return_size = self.number()?;
+        let mut bank = None;
+        let mut inline = Inline::Auto;
+        while !self.is_done() {
+            match self.word()? {
+                "bank" => bank = Some(self.number()?),
+                "inline" => {
+                    inline = match self.word()? {
+                        "always" => Inline::Always,
+                        "never" => Inline::Never,
+                        word => return Err(self.error(format!("unknown inline `{}`", word))),
+                    }
+                }
+                word => {
+                    return Err(self.error(format!("expected `bank` or `inline`, found `{}`", word)))
+                }
+            }
+        }
+        Ok(Routine {
+            debug_name,
+            stack_size,
+            args_size,
+            return_size,
+            bank,
+            inline,
+            statements: Vec::new(),
+            spans: Vec::new(),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        let name = self.word()?;
+        if let Some(statement) = parse_operator(name, self)? {
+            return Ok(statement);
+        }
+        let statement = match name {
+            "nop" => Statement::Nop(self.number()?),
+            "stop" => Statement::Stop(match self.word()? {
+                "error" => StopStatus::Error,
+                "success" => StopStatus::Success,
+                word => return Err(self.error(format!("unknown stop status `{}`", word))),
+            }),
+            "jmp" => Statement::Jmp {
+                location: self.location()?,
+            },
+            "jmp_cmp" | "jmp_cmp_not" => {
+                let location = self.location()?;
+                self.expect(",")?;
+                let source = self.source()?;
+                if name == "jmp_cmp" {
+                    Statement::JmpCmp { location, source }
+                } else {
+                    Statement::JmpCmpNot { location, source }
+                }
+            }
+            "call" => {
+                let routine = self.number()?;
+                let range = self.range_from()?;
+                Statement::Call { routine, range }
+            }
+            "call_indirect" => {
+                let routine = self.source()?;
+                let range = self.range_from()?;
+                Statement::CallIndirect { routine, range }
+            }
+            "ret" => Statement::Ret,
+            "push_bank" => Statement::PushBank {
+                bank: self.number()?,
+            },
+            "pop_bank" => Statement::PopBank,
+            "memcpy" => {
+                let source = self.source()?;
+                self.expect(",")?;
+                let destination = self.source()?;
+                self.expect(",")?;
+                let len = self.source()?;
+                Statement::Memcpy {
+                    source,
+                    destination,
+                    len,
+                }
+            }
+            "memset" => {
+                let value = self.source()?;
+                self.expect(",")?;
+                let destination = self.source()?;
+                self.expect(",")?;
+                let len = self.source()?;
+                Statement::Memset {
+                    value,
+                    destination,
+                    len,
+                }
+            }
+            "halt" => Statement::Halt,
+            "enable_interrupts" => Statement::EnableInterrupts,
+            name => return Err(self.error(format!("unknown statement `{}`", name))),
+        };
+        Ok(statement)
+    }
+
+    // span of a statement, if any (`@ LINE:COLUMN..LINE:COLUMN [source N]`).
+    fn span(&mut self) -> Result<Option<Span>, ParseError> {
+        if !self.eat("@") {
+            return Ok(None);
+        }
+        let min = self.position()?;
+        self.expect("..")?;
+        let max = self.position()?;
+        let source = if self.is_done() {
+            SourceId::default()
+        } else {
+            self.keyword("source")?;
+            SourceId::new(self.number()?)
+        };
+        Ok(Some(Span { min, max, source }))
+    }
+
+    // `LINE:COLUMN` position of a span.
+    fn position(&mut self) -> Result<[usize; 2], ParseError> {
+        let line = self.number()?;
+        self.expect(":")?;
+        Ok([line, self.number()?])
+    }
+
+    fn location(&mut self) -> Result<Location, ParseError> {
+        match self.peek() {
+            Some(Token::Word("table")) => {
+                self.next += 1;
+                let index = self.source()?;
+                self.expect("[")?;
+                let mut targets = Vec::new();
+                while !self.eat("]") {
+                    targets.push(self.number()?);
+                }
+                Ok(Location::Table { index, targets })
+            }
+            Some(Token::Word("absolute")) => {
+                self.next += 1;
+                Ok(Location::Absolute(self.number()?))
+            }
+            Some(Token::Word("routine")) => {
+                self.next += 1;
+                Ok(Location::Routine(self.number()?))
+            }
+            _ => Ok(Location::Relative(self.number()?)),
+        }
+    }
+
+    // start of the frame of a call (`, START..`).
+    fn range_from(&mut self) -> Result<std::ops::RangeFrom<u16>, ParseError> {
+        self.expect(",")?;
+        let start = self.number()?;
+        self.expect("..")?;
+        Ok(start..)
+    }
+
+    fn register(word: &str) -> Option<usize> {
+        word.strip_prefix('r')?.parse().ok()
+    }
+
+    fn source<T: TryFrom<i64>>(&mut self) -> Result<Source<T>, ParseError> {
+        match self.next()? {
+            Token::Punct("[") => {
+                let (base, offset) = self.pointer()?;
+                Ok(Source::Pointer { base, offset })
+            }
+            Token::Punct("*") => Ok(Source::Indirect(Box::new(self.source()?))),
+            Token::Word(word) => match Self::register(word) {
+                Some(register) => Ok(Source::Register(register)),
+                None => Ok(Source::Literal(self.int(word)?)),
+            },
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    // destination of a statement (`-> DESTINATION`).
+    fn destination(&mut self) -> Result<Destination, ParseError> {
+        self.expect("->")?;
+        match self.next()? {
+            Token::Punct("[") => {
+                let (base, offset) = self.pointer()?;
+                Ok(Destination::Pointer { base, offset })
+            }
+            Token::Punct("*") => Ok(Destination::Indirect(Box::new(self.source()?))),
+            Token::Word(word) => match Self::register(word) {
+                Some(register) => Ok(Destination::Register(register)),
+                None => Err(self.error(format!("invalid destination `{}`", word))),
+            },
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    // pointer and its offset, after the opening bracket.
+    fn pointer(&mut self) -> Result<(Pointer, Offset), ParseError> {
+        let base = match self.word()? {
+            "absolute" => Pointer::Absolute(self.number()?),
+            "static" => Pointer::Static(self.number()?),
+            "const" => Pointer::Const(self.number()?),
+            "stack" => Pointer::Stack(self.number()?),
+            "return" => Pointer::Return(self.number()?),
+            "banked" => {
+                let bank = self.number()?;
+                Pointer::Banked(bank, self.number()?)
+            }
+            word => return Err(self.error(format!("unknown pointer `{}`", word))),
+        };
+        let offset = if self.eat("+") {
+            Some(Box::new(self.source()?))
+        } else {
+            None
+        };
+        self.expect("]")?;
+        Ok((base, offset))
+    }
+}
@@ -0,0 +1,429 @@
+//! Intermediate representation language.
+//!
+//! Definition of the **intermediate representation** (IR) of `GGB` programs,
+//! compilation of the AST into IR, optimization passes ([`opt`]), and its text
+//! ([`fmt`]) and binary ([`binary`]) formats.
+//!
+//! This is part of the `GGBC` (Great Game Boy Compiler) toolchain.
+
+#![warn(
+    clippy::all,
+    clippy::doc_markdown,
+    clippy::dbg_macro,
+    clippy::todo,
+    clippy::empty_enum,
+    clippy::enum_glob_use,
+    clippy::pub_enum_variant_names,
+    clippy::mem_forget,
+    clippy::use_self,
+    clippy::filter_map_next,
+    clippy::needless_continue,
+    clippy::needless_borrow,
+    unused,
+    rust_2018_idioms,
+    future_incompatible,
+    nonstandard_style
+)]
+
+pub use byteorder;
+pub use compile::CompileError;
+pub use fmt::parse_text;
+pub use parser;
+
+use byteorder::ByteOrder;
+use compile::{Block, Context};
+use opcodes::{Address, Bank, Pointer, Statement};
+use parser::{ast, lex::span::Span};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ops::Range};
+
+pub mod analysis;
+pub mod binary;
+mod compile;
+pub mod fmt;
+pub mod opcodes;
+pub mod opt;
+pub mod stack;
+
+pub type Bytes = Box<[u8]>;
+
+/// Mapping from the characters of string literals to bytes.
+///
+/// Game Boy programs usually render text with tiles, so a custom mapping can
+/// be used to lay out strings in the order of the tiles of the font.
+/// Characters mapped to `None` can't be used in string literals.
+pub type Charset = fn(char) -> Option<u8>;
+
+/// Default [`Charset`], which maps ASCII characters (and `\xHH` escapes) to
+/// their byte value.
+pub fn ascii(c: char) -> Option<u8> {
+    let c = c as u32;
+    if c <= 0xff {
+        Some(c as u8)
+    } else {
+        None
+    }
+}
+
+/// Behavior of the 8bit and 16bit arithmetic operations (`Add`, `Sub`, `Mul`,
+/// `Inc`, `Dec`, and their 16bit variants) whose result doesn't fit in the
+/// destination.
+///
+/// Constant expressions are always evaluated with wrapping arithmetic.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Overflow {
+    /// The result wraps around (`0xff + 1 == 0`).
+    Wrapping,
+
+    /// The result is clamped to the range of the type (`0xff + 1 == 0xff`).
+    Saturating,
+
+    /// The program stops with an error, as if it panicked.
+    Trapping,
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Self::Wrapping
+    }
+}
+
+/// Inlining hint of a routine, from the `#[inline]` and `#[inline(never)]`
+/// attributes of the function it was compiled from.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Inline {
+    /// Inlined if the routine is small enough.
+    Auto,
+
+    /// Always inlined, regardless of its size (`#[inline]`).
+    Always,
+
+    /// Never inlined (`#[inline(never)]`).
+    Never,
+}
+
+impl Default for Inline {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// Options of the compilation of an AST into IR.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Encoding of the string literals.
+    pub charset: Charset,
+
+    /// Behavior of the arithmetic operations that overflow.
+    pub overflow: Overflow,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            charset: ascii,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+/// Intermediate representation of a program.
+///
+/// Generic over the byte ordering `B` of the bytes in `const_`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Ir<B: ByteOrder> {
+    /// Constant memory data.
+    pub const_: Bytes,
+
+    /// Initial static memory data.
+    /// Static memory past the end of this data is zero-initialized.
+    pub static_: Bytes,
+
+    /// Total static memory used by the program.
+    /// This amount must be allocated in order to run the program.
+    pub static_alloc: u16,
+
+    /// Static memory used by the program in each switchable RAM bank.
+    pub banks: BTreeMap<Bank, u16>,
+
+    /// Memory map of the program (`memory { ... }`).
+    /// Empty if the program doesn't declare one.
+    pub regions: Box<[Region]>,
+
+    /// Absolute memory regions of the `volatile` statics (hardware registers).
+    ///
+    /// Reads and writes to these regions have side effects, so they must not be
+    /// cached nor eliminated, and may be routed through I/O hooks when run.
+    pub volatile: Box<[Range<Address>]>,
+
+    /// Compiled routines.
+    pub routines: Box<[Routine]>,
+
+    /// Indices for entry point and interrupt routines handlers.
+    pub handlers: Handlers,
+
+    /// Behavior of the arithmetic operations that overflow, which backends must
+    /// implement.
+    pub overflow: Overflow,
+
+    _phantom: std::marker::PhantomData<B>,
+}
+
+impl<B: ByteOrder> Ir<B> {
+    /// Convert AST into IR intermediate code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program fails to compile (see [`try_new`](Self::try_new)).
+    pub fn new(ast: &ast::Ast<'_>) -> Self {
+        Self::with_options(ast, Options::default())
+    }
+
+    /// Convert AST into IR intermediate code.
+    ///
+    /// Fails with a [`CompileError`] (the name and the span of the offending
+    /// symbol or expression) if the program declares a symbol twice, uses a
+    /// symbol that isn't declared, or uses an expression the compiler doesn't
+    /// support. See [`CompileError`] for the full list.
+    ///
+    /// # Panics
+    ///
+    /// Programs the parser should have rejected (non-constant expressions
+    /// where a constant is required, for example) may still panic.
+    pub fn try_new(ast: &ast::Ast<'_>) -> Result<Self, CompileError> {
+        Self::try_with_options(ast, Options::default())
+    }
+
+    /// Compile the AST, encoding string literals with the given [`Charset`].
+    pub fn with_charset(ast: &ast::Ast<'_>, charset: Charset) -> Self {
+        let options = Options {
+            charset,
+            ..Options::default()
+        };
+        Self::with_options(ast, options)
+    }
+
+    /// Compile the AST with the given [`Options`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program fails to compile (see
+    /// [`try_with_options`](Self::try_with_options)).
+    pub fn with_options(ast: &ast::Ast<'_>, options: Options) -> Self {
+        Self::try_with_options(ast, options).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Compile the AST with the given [`Options`].
+    ///
+    /// Fails with a [`CompileError`] like [`try_new`](Self::try_new).
+    pub fn try_with_options(ast: &ast::Ast<'_>, options: Options) -> Result<Self, CompileError> {
+        let mut context: Context<B> = Context::default();
+        context.symbol_alloc.set_charset(options.charset);
+        let mut main = Block::default();
+
+        compile::compile_program(ast, &mut context, &mut main)?;
+
+        // inner ast statements define the entry point (a.k.a. main) routine
+        let main_handle = context.routines.len();
+        let (statements, spans) = main.into_parts();
+        context.routines.push(Routine {
+            debug_name: Some("main".to_string()),
+            stack_size: context.stack_size,
+            args_size: 0,
+            return_size: 0,
+            bank: None,
+            inline: Inline::Auto,
+            statements,
+            spans,
+        });
+
+        // every value has its own virtual register until now
+        for routine in context.routines.iter_mut() {
+            opt::allocate_routine(routine, usize::MAX);
+        }
+
+        Ok(Self {
+            static_alloc: context.symbol_alloc.static_usage(),
+            static_: context
+                .symbol_alloc
+                .static_data()
+                .to_vec()
+                .into_boxed_slice(),
+            banks: context.symbol_alloc.bank_usage().clone(),
+            regions: context.symbol_alloc.regions().to_vec().into_boxed_slice(),
+            volatile: context.symbol_alloc.volatile().to_vec().into_boxed_slice(),
+            const_: context.symbol_alloc.into_const_data().into_boxed_slice(),
+            routines: context.routines.into_boxed_slice(),
+            handlers: Handlers {
+                main: main_handle,
+                ..context.handlers
+            },
+            overflow: options.overflow,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Optimize IR instructions of all routines.
+    pub fn optimize(&mut self) {
+        for routine in self.routines.iter_mut() {
+            routine.optimize();
+        }
+    }
+
+    /// Returns true if the pointer refers to volatile memory.
+    pub fn is_volatile(&self, pointer: Pointer) -> bool {
+        match pointer {
+            Pointer::Absolute(address) => self.volatile.iter().any(|r| r.contains(&address)),
+            _ => false,
+        }
+    }
+
+    /// MAIN handler routine.
+    pub fn main(&self) -> &Routine {
+        &self.routines[self.handlers.main]
+    }
+
+    /// VBLANK handler routine.
+    pub fn vblank(&self) -> Option<&Routine> {
+        self.handlers.vblank.map(|i| &self.routines[i])
+    }
+
+    /// LCD STAT handler routine.
+    pub fn lcd_stat(&self) -> Option<&Routine> {
+        self.handlers.lcd_stat.map(|i| &self.routines[i])
+    }
+
+    /// TIMER handler routine.
+    pub fn timer(&self) -> Option<&Routine> {
+        self.handlers.timer.map(|i| &self.routines[i])
+    }
+
+    /// SERIAL handler routine.
+    pub fn serial(&self) -> Option<&Routine> {
+        self.handlers.serial.map(|i| &self.routines[i])
+    }
+
+    /// JOYPAD handler routine.
+    pub fn joypad(&self) -> Option<&Routine> {
+        self.handlers.joypad.map(|i| &self.routines[i])
+    }
+}
+
+/// Region of the memory map of a program.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Region {
+    /// Name of the region.
+    pub name: String,
+
+    /// Address range of the region.
+    /// The end is past the last address, so it may be `0x10000`.
+    pub range: Range<u32>,
+
+    /// Memory space held by the region.
+    pub space: Space,
+}
+
+/// Memory space held by a [`Region`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Space {
+    /// Static memory. Offset 0 of `Pointer::Static` is the start of the region.
+    Static,
+
+    /// Const memory. Offset 0 of `Pointer::Const` is the start of the region.
+    Const,
+
+    /// Statics at absolute addresses.
+    Absolute,
+}
+
+/// Handlers for the main routine and interrupt handlers.
+///
+/// Interrupt handlers are functions declared with the interrupt they handle
+/// (`fn@vblank on_vblank { ... }`).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct Handlers {
+    /// Index of the entry point routine.
+    pub main: usize,
+
+    /// VBLANK interrupt handler.
+    pub vblank: Option<usize>,
+
+    /// LCD-STAT interrupt handler.
+    pub lcd_stat: Option<usize>,
+
+    /// TIMER interrupt handler.
+    pub timer: Option<usize>,
+
+    /// SERIAL interrupt handler.
+    pub serial: Option<usize>,
+
+    /// JOYPAD interrupt handler.
+    pub joypad: Option<usize>,
+}
+
+/// Data associated with a compiled IR routine.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct Routine {
+    /// Optional routine name (for debugging purposes).
+    pub debug_name: Option<String>,
+
+    /// Size of the stack to fit all the variables used by the routine, function
+    /// arguments included.
+    pub stack_size: u16,
+
+    /// Size of the total function arguments.
+    pub args_size: u16,
+
+    /// Size of the returned data.
+    ///
+    /// If the target architecture only supports return values up to a given
+    /// size, this field can be used to detect invalid routines.
+    pub return_size: u16,
+
+    /// Switchable ROM bank the routine is located in.
+    ///
+    /// Banked routines are called through a thunk routine that maps the bank
+    /// before the call (`PushBank`), and restores the previous one after it
+    /// (`PopBank`).
+    pub bank: Option<Bank>,
+
+    /// Whether calls to the routine should be replaced by its body (see
+    /// [`opt::inline`]).
+    pub inline: Inline,
+
+    /// Instructions of the routine.
+    pub statements: Vec<Statement>,
+
+    /// Spans of the source code the instructions were compiled from, for
+    /// debugging purposes (stepping through the source code, locating runtime
+    /// errors, attributing profiles...).
+    ///
+    /// One span per instruction, or empty if the routine has no debug info
+    /// (such as the routines generated by the compiler). Instructions that
+    /// don't belong to any statement of the source code have no span.
+    pub spans: Vec<Option<Span>>,
+}
+
+impl Routine {
+    /// Optimize IR instructions of the routine.
+    pub fn optimize(&mut self) {
+        while compile::optimize::mark_unreachable(&mut self.statements)
+            || compile::optimize::jump_threading(&mut self.statements)
+            || compile::optimize::delete_nops(&mut self.statements, &mut self.spans)
+        {}
+    }
+
+    /// Span of the source code the instruction at the given index was
+    /// compiled from, if known.
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied().flatten()
+    }
+}
@@ -0,0 +1,664 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::RangeFrom;
+
+/// Virtual memory address type.
+pub type Address = u16;
+
+/// Switchable memory bank number.
+pub type Bank = u8;
+
+/// Virtual register index.
+pub type Register = usize;
+
+/// Virtual memory pointers.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Pointer {
+    /// Absolute pointer.
+    Absolute(Address),
+
+    /// Pointer in virtual static memory.
+    Static(Address),
+
+    /// Pointer in virtual const (ROM) memory.
+    Const(Address),
+
+    /// Pointer in virtual stack memory.
+    Stack(Address),
+
+    /// Function return space.
+    Return(Address),
+
+    /// Pointer in the virtual memory of a switchable RAM bank.
+    Banked(Bank, Address),
+}
+
+impl Pointer {
+    pub(crate) fn offset(self, offset: Address) -> Self {
+        use Pointer::{Absolute, Banked, Const, Return, Stack, Static};
+        match self {
+            Absolute(a) => Absolute(a + offset),
+            Static(a) => Static(a + offset),
+            Const(a) => Const(a + offset),
+            Stack(a) => Stack(a + offset),
+            Return(a) => Return(a + offset),
+            Banked(b, a) => Banked(b, a + offset),
+        }
+    }
+}
+
+/// Source from where to pull a value.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Source<T> {
+    /// Data at the given address.
+    Pointer {
+        /// The base pointer itself.
+        base: Pointer,
+
+        /// Dynamic applied to the address of the pointer.
+        offset: Option<Box<Source<u8>>>,
+    },
+
+    /// Data at the address held by a 16bit source (pointer dereference).
+    Indirect(Box<Source<Address>>),
+
+    /// Data at the given register.
+    Register(Register),
+
+    /// Literal data.
+    Literal(T),
+}
+
+/// Destination where to store a value.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Destination {
+    /// Store at the given address
+    Pointer {
+        /// The base pointer itself.
+        base: Pointer,
+
+        /// Dynamic applied to the address of the pointer.
+        offset: Option<Box<Source<u8>>>,
+    },
+
+    /// Store at the address held by a 16bit source (pointer dereference).
+    Indirect(Box<Source<Address>>),
+
+    /// Store at the given register.
+    Register(Register),
+}
+
+/// Jump location of `Jmp` and `Cmp` statements.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Location {
+    /// Jump relative to the current program pointer.
+    Relative(i8),
+
+    /// Jump table (dispatch of a `match` statement).
+    /// Jumps relative to the current program pointer by the nth entry of
+    /// `targets`, where n is the value of `index`. Doesn't jump if `index` is
+    /// out of the bounds of the table.
+    Table { index: Source<u8>, targets: Vec<i8> },
+
+    /// Jump to the statement at the given index of the current routine.
+    Absolute(usize),
+
+    /// Tail jump to the routine at the given index, which takes the place of
+    /// the current one: it runs in the stack frame of the current routine (with
+    /// its arguments at the start of the frame), and returns to its caller.
+    Routine(usize),
+}
+
+impl Location {
+    // relative offsets of the statements the location jumps to (none for
+    // absolute locations).
+    pub(crate) fn offsets(&self) -> &[i8] {
+        match self {
+            Self::Relative(relative) => std::slice::from_ref(relative),
+            Self::Table { targets, .. } => targets,
+            Self::Absolute(_) | Self::Routine(_) => &[],
+        }
+    }
+
+    pub(crate) fn offsets_mut(&mut self) -> &mut [i8] {
+        match self {
+            Self::Relative(relative) => std::slice::from_mut(relative),
+            Self::Table { targets, .. } => targets,
+            Self::Absolute(_) | Self::Routine(_) => &mut [],
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum StopStatus {
+    Error,
+    Success,
+}
+
+/// The statements, or instruction set of the IR.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Statement {
+    /// Do nothing, used as placeholder.
+    Nop(usize),
+
+    /// Statement to stop execution (end program).
+    Stop(StopStatus),
+
+    /// 8bit load.
+    Ld {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit load.
+    LdW {
+        source: Source<u16>,
+        destination: Destination,
+    },
+
+    /// Load address.
+    LdAddr {
+        source: Source<Address>,
+        destination: Destination,
+    },
+
+    /// 8bit to 16bit zero extension.
+    Ext {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit to 16bit sign extension.
+    SignExt {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit to 8bit truncation (keeps the low byte).
+    Trunc {
+        source: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 8bit increment.
+    Inc {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit decrement.
+    Dec {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit increment.
+    IncW {
+        source: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit decrement.
+    DecW {
+        source: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 8bit add.
+    Add {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit subtract.
+    Sub {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit bitwise AND.
+    And {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit bitwise XOR.
+    Xor {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit bitwise OR.
+    Or {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit left shift.
+    LeftShift {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit right shift.
+    RightShift {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit multiply.
+    Mul {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit divide.
+    Div {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit remainder.
+    Rem {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit arithmetic right shift (of a signed value, keeping its sign).
+    RightShiftS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed divide (rounds towards zero).
+    DivS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed remainder (of the sign of the dividend).
+    RemS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit add with carry (`left + right + carry`, wrapping around).
+    ///
+    /// Only the lowest bit of `carry` is added. Additions (and subtractions) wider
+    /// than 16 bits are chains of bytes, each one taking the carry out of the
+    /// previous one.
+    AddC {
+        left: Source<u8>,
+        right: Source<u8>,
+        carry: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit subtract with borrow (`left - right - carry`, wrapping around).
+    SubC {
+        left: Source<u8>,
+        right: Source<u8>,
+        carry: Source<u8>,
+        destination: Destination,
+    },
+
+    /// Carry out (0 or 1) of an 8bit add with carry (`AddC`).
+    AddCarry {
+        left: Source<u8>,
+        right: Source<u8>,
+        carry: Source<u8>,
+        destination: Destination,
+    },
+
+    /// Borrow out (0 or 1) of an 8bit subtract with borrow (`SubC`).
+    SubBorrow {
+        left: Source<u8>,
+        right: Source<u8>,
+        carry: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit add.
+    AddW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit subtract.
+    SubW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit bitwise AND.
+    AndW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit bitwise XOR.
+    XorW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit bitwise OR.
+    OrW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit left shift.
+    LeftShiftW {
+        left: Source<u16>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit right shift.
+    RightShiftW {
+        left: Source<u16>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit multiply.
+    MulW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit divide.
+    DivW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit remainder.
+    RemW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit arithmetic right shift (of a signed value, keeping its sign).
+    RightShiftSW {
+        left: Source<u16>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit signed divide (rounds towards zero).
+    DivSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit signed remainder (of the sign of the dividend).
+    RemSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean equals.
+    Eq {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean not-equals.
+    NotEq {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean greater-than.
+    Greater {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean greater-or-equal-than.
+    GreaterEq {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean less-than.
+    Less {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit boolean less-or-equal-than.
+    LessEq {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed boolean greater-than.
+    GreaterS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed boolean greater-or-equal-than.
+    GreaterEqS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed boolean less-than.
+    LessS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 8bit signed boolean less-or-equal-than.
+    LessEqS {
+        left: Source<u8>,
+        right: Source<u8>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean equals.
+    EqW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean not-equals.
+    NotEqW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean greater-than.
+    GreaterW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean greater-or-equal-than.
+    GreaterEqW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean less-than.
+    LessW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit boolean less-or-equal-than.
+    LessEqW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit signed boolean greater-than.
+    GreaterSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit signed boolean greater-or-equal-than.
+    GreaterEqSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit signed boolean less-than.
+    LessSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// 16bit signed boolean less-or-equal-than.
+    LessEqSW {
+        left: Source<u16>,
+        right: Source<u16>,
+        destination: Destination,
+    },
+
+    /// Jump to location.
+    Jmp { location: Location },
+
+    /// Conditional jump to location.
+    /// Jumps to the given location if `source` resolves to non-zero.
+    JmpCmp {
+        location: Location,
+        source: Source<u8>,
+    },
+
+    /// Conditional jump
+    /// Jumps to the given location if `source` resolves to zero.
+    JmpCmpNot {
+        location: Location,
+        source: Source<u8>,
+    },
+
+    /// Routine call.
+    Call {
+        /// Routine index.
+        routine: usize,
+
+        /// range of the current stack frame corresponding to the beginning of
+        /// the new function's stack frame.
+        #[cfg_attr(feature = "serde", serde(serialize_with = "ser_range_from"))]
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "de_range_from"))]
+        range: RangeFrom<u16>,
+    },
+
+    /// Routine call through a function pointer.
+    CallIndirect {
+        /// Routine index (value of the function pointer).
+        routine: Source<u16>,
+
+        /// range of the current stack frame corresponding to the beginning of
+        /// the new function's stack frame.
+        #[cfg_attr(feature = "serde", serde(serialize_with = "ser_range_from"))]
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "de_range_from"))]
+        range: RangeFrom<u16>,
+    },
+
+    /// Return from routine.
+    Ret,
+
+    /// Map the given ROM bank, saving the currently mapped one.
+    PushBank { bank: Bank },
+
+    /// Map the ROM bank saved by the last `PushBank`.
+    PopBank,
+
+    /// Copy `len` bytes from the address in `source` to the address in
+    /// `destination` (`builtin::memcpy`).
+    Memcpy {
+        source: Source<u16>,
+        destination: Source<u16>,
+        len: Source<u16>,
+    },
+
+    /// Set `len` bytes starting at the address in `destination` to `value`
+    /// (`builtin::memset`).
+    Memset {
+        value: Source<u8>,
+        destination: Source<u16>,
+        len: Source<u16>,
+    },
+
+    /// Swap the high and low nibbles of a byte (`builtin::swap_nibbles`).
+    SwapNibbles {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// Convert a byte (modulo 100) to packed BCD (`builtin::bcd_adjust`).
+    BcdAdjust {
+        source: Source<u8>,
+        destination: Destination,
+    },
+
+    /// Suspend execution until an interrupt is requested (`builtin::halt`).
+    Halt,
+
+    /// Enable the handling of interrupts (`builtin::enable_interrupts`).
+    EnableInterrupts,
+}
+
+#[cfg(feature = "serde")]
+fn ser_range_from<S: Serializer>(range_from: &RangeFrom<u16>, ser: S) -> Result<S::Ok, S::Error> {
+    ser.serialize_u16(range_from.start)
+}
+
+#[cfg(feature = "serde")]
+fn de_range_from<'de, D: Deserializer<'de>>(de: D) -> Result<RangeFrom<u16>, D::Error> {
+    u16::deserialize(de).map(|start| start..)
+}
@@ -0,0 +1,1415 @@
+//! Optimization passes over the IR.
+//!
+//! Unlike [`Ir::optimize`], which only simplifies the control flow of the
+//! routines, the passes of this module rewrite the operations of the program.
+//! They are run on demand, and preserve the spans of the rewritten statements.
+//!
+//! ```
+//! use ir::{byteorder::NativeEndian, Ir};
+//!
+//! let text = "routine 0 - stack 0 args 0 return 0
+//!     ld 2 -> r0
+//!     add r0, 1 -> [static 0]
+//! end";
+//! let mut ir: Ir<NativeEndian> = ir::parse_text(text).unwrap();
+//!
+//! assert!(ir::opt::const_fold(&mut ir));
+//! assert_eq!("ld 3 -> [static 0]", ir.main().statements[1].to_string());
+//! ```
+//!
+//! Repeated operations are reused with [`cse`], intermediate registers are
+//! removed with [`copy_propagation`], calls to small routines are replaced by
+//! their statements with [`inline`], invariant statements are moved out of
+//! loops with [`licm`], multiplications by literals become shifts with
+//! [`strength_reduction`], chains of jumps are simplified with
+//! [`simplify_branches`], and rewrites of small windows of statements are
+//! implemented as [`peephole`] rules. The virtual registers are fit into the
+//! registers of the target with [`allocate_registers`].
+//!
+//! A [`Pipeline`] runs a sequence of passes, either one of the presets of
+//! [`OptLevel`] or a custom one, and reports how each pass changed the program.
+use crate::{
+    analysis::{self, Liveness, Slot},
+    opcodes::{Destination, Location, Pointer, Register, Source, Statement},
+    ByteOrder, Ir, Overflow, Routine,
+};
+use parser::lex::span::Span;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+    marker::PhantomData,
+    mem,
+};
+
+pub use copy::copy_propagation;
+pub use cse::cse;
+pub use inline::inline;
+pub use jumps::simplify_branches;
+pub use loops::licm;
+pub use pipeline::{IrPass, OptLevel, PassStats, Pipeline, Stats};
+pub use registers::allocate_registers;
+pub(crate) use registers::allocate_routine;
+pub use strength::strength_reduction;
+
+mod copy;
+mod cse;
+mod inline;
+mod jumps;
+mod loops;
+pub mod peephole;
+mod pipeline;
+mod registers;
+mod strength;
+
+/// Constant folding and propagation.
+///
+/// Operations whose operands are literals, or reads of const memory, are
+/// evaluated at compile time and replaced by a load of their result
+/// (`add 1, 2 -> r0` becomes `ld 3 -> r0`). The values loaded into registers
+/// are propagated to the statements that read them, until the end of the basic
+/// block (a jump, a call, or the target of a jump).
+///
+/// Operations that would trap at runtime (division by zero, and overflows with
+/// [`Overflow::Trapping`] semantics) are left for the program to trap.
+///
+/// Returns true if any statement was rewritten.
+pub fn const_fold<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let mut fold = Fold::<B> {
+        const_: &ir.const_,
+        overflow: ir.overflow,
+        reg8: HashMap::new(),
+        reg16: HashMap::new(),
+        _phantom: PhantomData,
+    };
+    let mut folded = false;
+    for routine in ir.routines.iter_mut() {
+        folded |= fold.routine(&mut routine.statements);
+    }
+    folded
+}
+
+// result of a folded operation.
+enum Folded {
+    Byte(u8),
+    Word(u16),
+}
+
+// state of the constant folding pass.
+struct Fold<'a, B> {
+    const_: &'a [u8],
+    overflow: Overflow,
+    // values of the registers known at the statement being folded
+    reg8: HashMap<Register, u8>,
+    reg16: HashMap<Register, u16>,
+    _phantom: PhantomData<B>,
+}
+
+impl<B: ByteOrder> Fold<'_, B> {
+    fn routine(&mut self, statements: &mut [Statement]) -> bool {
+        let targets = jump_targets(statements);
+        let mut folded = false;
+        self.forget();
+        for (index, statement) in statements.iter_mut().enumerate() {
+            if targets.contains(&index) {
+                self.forget();
+            }
+            let mut new = statement.clone();
+            visit_sources(&mut new, self);
+            if let Some((value, destination)) = self.eval(&new) {
+                new = match value {
+                    Folded::Byte(value) => Statement::Ld {
+                        source: Source::Literal(value),
+                        destination: destination.clone(),
+                    },
+                    Folded::Word(value) => Statement::LdW {
+                        source: Source::Literal(value),
+                        destination: destination.clone(),
+                    },
+                };
+            }
+            self.update(&new);
+            if new != *statement {
+                *statement = new;
+                folded = true;
+            }
+        }
+        folded
+    }
+
+    // forget the values of the registers (start of a basic block).
+    fn forget(&mut self) {
+        self.reg8.clear();
+        self.reg16.clear();
+    }
+
+    // record the values of the registers written by the statement.
+    fn update(&mut self, statement: &Statement) {
+        use Statement::{Call, CallIndirect, Jmp, JmpCmp, JmpCmpNot, Ld, LdW, Ret, Stop};
+        match statement {
+            Ld {
+                source: Source::Literal(value),
+                destination: Destination::Register(register),
+            } => {
+                self.reg8.insert(*register, *value);
+            }
+            LdW {
+                source: Source::Literal(value),
+                destination: Destination::Register(register),
+            } => {
+                self.reg16.insert(*register, *value);
+            }
+            // registers aren't assumed to survive calls
+            Jmp { .. }
+            | JmpCmp { .. }
+            | JmpCmpNot { .. }
+            | Call { .. }
+            | CallIndirect { .. }
+            | Ret
+            | Stop(_) => self.forget(),
+            statement => match destination(statement) {
+                Some((Destination::Register(register), true)) => {
+                    self.reg16.remove(register);
+                }
+                Some((Destination::Register(register), false)) => {
+                    self.reg8.remove(register);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    // result of an operation whose operands are all known.
+    fn eval<'s>(&self, statement: &'s Statement) -> Option<(Folded, &'s Destination)> {
+        use Folded::{Byte, Word};
+        use Source::Literal as L;
+        #[allow(clippy::enum_glob_use)]
+        use Statement::*;
+        let folded = match statement {
+            Ext {
+                source: L(s),
+                destination,
+            } => (Word(u16::from(*s)), destination),
+            SignExt {
+                source: L(s),
+                destination,
+            } => (Word(*s as i8 as u16), destination),
+            Trunc {
+                source: L(s),
+                destination,
+            } => (Byte(*s as u8), destination),
+            Inc {
+                source: L(s),
+                destination,
+            } => {
+                let value = self.overflow(s.checked_add(1), s.wrapping_add(1), s.saturating_add(1));
+                (Byte(value?), destination)
+            }
+            Dec {
+                source: L(s),
+                destination,
+            } => {
+                let value = self.overflow(s.checked_sub(1), s.wrapping_sub(1), s.saturating_sub(1));
+                (Byte(value?), destination)
+            }
+            IncW {
+                source: L(s),
+                destination,
+            } => {
+                let value = self.overflow(s.checked_add(1), s.wrapping_add(1), s.saturating_add(1));
+                (Word(value?), destination)
+            }
+            DecW {
+                source: L(s),
+                destination,
+            } => {
+                let value = self.overflow(s.checked_sub(1), s.wrapping_sub(1), s.saturating_sub(1));
+                (Word(value?), destination)
+            }
+            SwapNibbles {
+                source: L(s),
+                destination,
+            } => (Byte(s.rotate_left(4)), destination),
+            BcdAdjust {
+                source: L(s),
+                destination,
+            } => {
+                let s = s % 100;
+                (Byte(((s / 10) << 4) | (s % 10)), destination)
+            }
+
+            Add {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_add(*r), l.wrapping_add(*r), l.saturating_add(*r));
+                (Byte(value?), destination)
+            }
+            Sub {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_sub(*r), l.wrapping_sub(*r), l.saturating_sub(*r));
+                (Byte(value?), destination)
+            }
+            Mul {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_mul(*r), l.wrapping_mul(*r), l.saturating_mul(*r));
+                (Byte(value?), destination)
+            }
+            // division by zero always traps
+            Div {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l.checked_div(*r)?), destination),
+            Rem {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l.checked_rem(*r)?), destination),
+            // the sign is kept by shifting it in (shifting all the bits out
+            // leaves either a 0 or a -1)
+            RightShiftS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((*l as i8 >> (*r).min(7)) as u8), destination),
+            // the quotient of the lowest value by -1 wraps around
+            DivS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value = (*r != 0).then(|| (*l as i8).wrapping_div(*r as i8));
+                (Byte(value? as u8), destination)
+            }
+            RemS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value = (*r != 0).then(|| (*l as i8).wrapping_rem(*r as i8));
+                (Byte(value? as u8), destination)
+            }
+            And {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l & r), destination),
+            Xor {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l ^ r), destination),
+            Or {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l | r), destination),
+            // shifting all the bits out leaves a 0
+            LeftShift {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l.checked_shl((*r).into()).unwrap_or(0)), destination),
+            RightShift {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(l.checked_shr((*r).into()).unwrap_or(0)), destination),
+
+            AddW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_add(*r), l.wrapping_add(*r), l.saturating_add(*r));
+                (Word(value?), destination)
+            }
+            SubW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_sub(*r), l.wrapping_sub(*r), l.saturating_sub(*r));
+                (Word(value?), destination)
+            }
+            MulW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value =
+                    self.overflow(l.checked_mul(*r), l.wrapping_mul(*r), l.saturating_mul(*r));
+                (Word(value?), destination)
+            }
+            DivW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l.checked_div(*r)?), destination),
+            RemW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l.checked_rem(*r)?), destination),
+            RightShiftSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word((*l as i16 >> (*r).min(15)) as u16), destination),
+            DivSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value = (*r != 0).then(|| (*l as i16).wrapping_div(*r as i16));
+                (Word(value? as u16), destination)
+            }
+            RemSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => {
+                let value = (*r != 0).then(|| (*l as i16).wrapping_rem(*r as i16));
+                (Word(value? as u16), destination)
+            }
+            AndW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l & r), destination),
+            XorW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l ^ r), destination),
+            OrW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l | r), destination),
+            LeftShiftW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l.checked_shl((*r).into()).unwrap_or(0)), destination),
+            RightShiftW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Word(l.checked_shr((*r).into()).unwrap_or(0)), destination),
+
+            Eq {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l == r) as u8), destination),
+            NotEq {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l != r) as u8), destination),
+            Greater {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l > r) as u8), destination),
+            GreaterEq {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l >= r) as u8), destination),
+            Less {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l < r) as u8), destination),
+            LessEq {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l <= r) as u8), destination),
+            GreaterS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i8) > (*r as i8)) as u8), destination),
+            GreaterEqS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i8) >= (*r as i8)) as u8), destination),
+            LessS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i8) < (*r as i8)) as u8), destination),
+            LessEqS {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i8) <= (*r as i8)) as u8), destination),
+            // only the lowest bit of the carry is added
+            AddC {
+                left: L(l),
+                right: L(r),
+                carry: L(c),
+                destination,
+            } => (Byte(l.wrapping_add(*r).wrapping_add(c & 1)), destination),
+            SubC {
+                left: L(l),
+                right: L(r),
+                carry: L(c),
+                destination,
+            } => (Byte(l.wrapping_sub(*r).wrapping_sub(c & 1)), destination),
+            AddCarry {
+                left: L(l),
+                right: L(r),
+                carry: L(c),
+                destination,
+            } => {
+                let sum = u16::from(*l) + u16::from(*r) + u16::from(c & 1);
+                (Byte((sum > 0xff) as u8), destination)
+            }
+            SubBorrow {
+                left: L(l),
+                right: L(r),
+                carry: L(c),
+                destination,
+            } => {
+                let borrow = u16::from(*l) < u16::from(*r) + u16::from(c & 1);
+                (Byte(borrow as u8), destination)
+            }
+            EqW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l == r) as u8), destination),
+            NotEqW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l != r) as u8), destination),
+            GreaterW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l > r) as u8), destination),
+            GreaterEqW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l >= r) as u8), destination),
+            LessW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l < r) as u8), destination),
+            LessEqW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte((l <= r) as u8), destination),
+            GreaterSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i16) > (*r as i16)) as u8), destination),
+            GreaterEqSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i16) >= (*r as i16)) as u8), destination),
+            LessSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i16) < (*r as i16)) as u8), destination),
+            LessEqSW {
+                left: L(l),
+                right: L(r),
+                destination,
+            } => (Byte(((*l as i16) <= (*r as i16)) as u8), destination),
+            _ => return None,
+        };
+        Some(folded)
+    }
+
+    // result of an arithmetic operation, given its checked, wrapping, and
+    // saturating results, following the overflow semantics of the program.
+    // None if the operation traps.
+    fn overflow<T>(&self, checked: Option<T>, wrapping: T, saturating: T) -> Option<T> {
+        match (checked, self.overflow) {
+            (Some(result), _) => Some(result),
+            (None, Overflow::Wrapping) => Some(wrapping),
+            (None, Overflow::Saturating) => Some(saturating),
+            (None, Overflow::Trapping) => None,
+        }
+    }
+}
+
+// replace the sources of a statement with their known values: registers
+// holding a known value, and const memory.
+impl<B: ByteOrder> Visit for Fold<'_, B> {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        let value = match source {
+            Source::Register(register) => T::register(self, *register),
+            Source::Pointer {
+                base: Pointer::Const(address),
+                offset,
+            } => {
+                let offset = match offset.as_deref() {
+                    None => Some(0),
+                    Some(Source::Literal(offset)) => Some(*offset),
+                    Some(_) => None,
+                };
+                offset.and_then(|offset| {
+                    let address = usize::from(*address) + usize::from(offset);
+                    T::read::<B>(self.const_.get(address..)?)
+                })
+            }
+            _ => None,
+        };
+        if let Some(value) = value {
+            *source = Source::Literal(value);
+        }
+    }
+}
+
+// visitor of the sources read by a statement.
+trait Visit {
+    fn source<T: Value>(&mut self, source: &mut Source<T>);
+
+    // sources read to compute the address of a pointer: its dynamic offset, or
+    // the pointer of a dereference.
+    fn address<T>(&mut self, source: &mut Source<T>) {
+        match source {
+            Source::Pointer {
+                offset: Some(offset),
+                ..
+            } => self.source(offset),
+            Source::Indirect(pointer) => self.source(pointer),
+            _ => {}
+        }
+    }
+}
+
+// visit the sources read by a statement, including the ones to compute the
+// address of its destination. Only the offset of the pointer of `ld_addr` is
+// read.
+fn visit_sources<V: Visit>(statement: &mut Statement, visit: &mut V) {
+    #[allow(clippy::enum_glob_use)]
+    use Statement::*;
+    match statement {
+        Ld { source, .. }
+        | Ext { source, .. }
+        | SignExt { source, .. }
+        | Inc { source, .. }
+        | Dec { source, .. }
+        | SwapNibbles { source, .. }
+        | BcdAdjust { source, .. }
+        | JmpCmp { source, .. }
+        | JmpCmpNot { source, .. } => visit.source(source),
+        LdW { source, .. } | Trunc { source, .. } | IncW { source, .. } | DecW { source, .. } => {
+            visit.source(source)
+        }
+        // the address of the pointer is taken, not the value it points to
+        LdAddr { source, .. } => visit.address(source),
+        Add { left, right, .. }
+        | Sub { left, right, .. }
+        | And { left, right, .. }
+        | Xor { left, right, .. }
+        | Or { left, right, .. }
+        | LeftShift { left, right, .. }
+        | RightShift { left, right, .. }
+        | Mul { left, right, .. }
+        | Div { left, right, .. }
+        | Rem { left, right, .. }
+        | RightShiftS { left, right, .. }
+        | DivS { left, right, .. }
+        | RemS { left, right, .. }
+        | Eq { left, right, .. }
+        | NotEq { left, right, .. }
+        | Greater { left, right, .. }
+        | GreaterEq { left, right, .. }
+        | Less { left, right, .. }
+        | LessEq { left, right, .. }
+        | GreaterS { left, right, .. }
+        | GreaterEqS { left, right, .. }
+        | LessS { left, right, .. }
+        | LessEqS { left, right, .. } => {
+            visit.source(left);
+            visit.source(right);
+        }
+        AddW { left, right, .. }
+        | SubW { left, right, .. }
+        | AndW { left, right, .. }
+        | XorW { left, right, .. }
+        | OrW { left, right, .. }
+        | MulW { left, right, .. }
+        | DivW { left, right, .. }
+        | RemW { left, right, .. }
+        | DivSW { left, right, .. }
+        | RemSW { left, right, .. }
+        | EqW { left, right, .. }
+        | NotEqW { left, right, .. }
+        | GreaterW { left, right, .. }
+        | GreaterEqW { left, right, .. }
+        | LessW { left, right, .. }
+        | LessEqW { left, right, .. }
+        | GreaterSW { left, right, .. }
+        | GreaterEqSW { left, right, .. }
+        | LessSW { left, right, .. }
+        | LessEqSW { left, right, .. } => {
+            visit.source(left);
+            visit.source(right);
+        }
+        LeftShiftW { left, right, .. }
+        | RightShiftW { left, right, .. }
+        | RightShiftSW { left, right, .. } => {
+            visit.source(left);
+            visit.source(right);
+        }
+        AddC {
+            left, right, carry, ..
+        }
+        | SubC {
+            left, right, carry, ..
+        }
+        | AddCarry {
+            left, right, carry, ..
+        }
+        | SubBorrow {
+            left, right, carry, ..
+        } => {
+            visit.source(left);
+            visit.source(right);
+            visit.source(carry);
+        }
+        CallIndirect { routine, .. } => visit.source(routine),
+        Memcpy {
+            source,
+            destination,
+            len,
+        } => {
+            visit.source(source);
+            visit.source(destination);
+            visit.source(len);
+        }
+        Memset {
+            value,
+            destination,
+            len,
+        } => {
+            visit.source(value);
+            visit.source(destination);
+            visit.source(len);
+        }
+        Nop(_)
+        | Stop(_)
+        | Jmp { .. }
+        | Call { .. }
+        | Ret
+        | PushBank { .. }
+        | PopBank
+        | Halt
+        | EnableInterrupts => {}
+    }
+    if let Some(Location::Table { index, .. }) = location_mut(statement) {
+        visit.source(index);
+    }
+    match destination_mut(statement) {
+        Some(Destination::Pointer {
+            offset: Some(offset),
+            ..
+        }) => visit.source(offset),
+        Some(Destination::Indirect(pointer)) => visit.source(pointer),
+        _ => {}
+    }
+}
+
+// 8bit and 16bit values.
+trait Value: Sized {
+    // values of this width are held by 16bit registers.
+    const WORD: bool;
+
+    // known value of a register of this width.
+    fn register<B>(fold: &Fold<'_, B>, register: Register) -> Option<Self>;
+
+    // value at the beginning of the bytes.
+    fn read<B: ByteOrder>(bytes: &[u8]) -> Option<Self>;
+}
+
+impl Value for u8 {
+    const WORD: bool = false;
+
+    fn register<B>(fold: &Fold<'_, B>, register: Register) -> Option<Self> {
+        fold.reg8.get(&register).copied()
+    }
+
+    fn read<B: ByteOrder>(bytes: &[u8]) -> Option<Self> {
+        bytes.first().copied()
+    }
+}
+
+impl Value for u16 {
+    const WORD: bool = true;
+
+    fn register<B>(fold: &Fold<'_, B>, register: Register) -> Option<Self> {
+        fold.reg16.get(&register).copied()
+    }
+
+    fn read<B: ByteOrder>(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 2 {
+            return None;
+        }
+        Some(B::read_u16(bytes))
+    }
+}
+
+// indices of the statements that are the target of a jump.
+fn jump_targets(statements: &[Statement]) -> HashSet<usize> {
+    statements
+        .iter()
+        .enumerate()
+        .flat_map(|(index, statement)| targets(index, statement))
+        .collect()
+}
+
+// destination of a statement, and whether it is written a 16bit value.
+fn destination(statement: &Statement) -> Option<(&Destination, bool)> {
+    #[allow(clippy::enum_glob_use)]
+    use Statement::*;
+    match statement {
+        LdW { destination, .. }
+        | LdAddr { destination, .. }
+        | Ext { destination, .. }
+        | SignExt { destination, .. }
+        | IncW { destination, .. }
+        | DecW { destination, .. }
+        | AddW { destination, .. }
+        | SubW { destination, .. }
+        | AndW { destination, .. }
+        | XorW { destination, .. }
+        | OrW { destination, .. }
+        | LeftShiftW { destination, .. }
+        | RightShiftW { destination, .. }
+        | MulW { destination, .. }
+        | DivW { destination, .. }
+        | RemW { destination, .. }
+        | RightShiftSW { destination, .. }
+        | DivSW { destination, .. }
+        | RemSW { destination, .. } => Some((destination, true)),
+        Ld { destination, .. }
+        | Trunc { destination, .. }
+        | Inc { destination, .. }
+        | Dec { destination, .. }
+        | Add { destination, .. }
+        | Sub { destination, .. }
+        | And { destination, .. }
+        | Xor { destination, .. }
+        | Or { destination, .. }
+        | LeftShift { destination, .. }
+        | RightShift { destination, .. }
+        | Mul { destination, .. }
+        | Div { destination, .. }
+        | Rem { destination, .. }
+        | RightShiftS { destination, .. }
+        | DivS { destination, .. }
+        | RemS { destination, .. }
+        | AddC { destination, .. }
+        | SubC { destination, .. }
+        | AddCarry { destination, .. }
+        | SubBorrow { destination, .. }
+        | Eq { destination, .. }
+        | NotEq { destination, .. }
+        | Greater { destination, .. }
+        | GreaterEq { destination, .. }
+        | Less { destination, .. }
+        | LessEq { destination, .. }
+        | GreaterS { destination, .. }
+        | GreaterEqS { destination, .. }
+        | LessS { destination, .. }
+        | LessEqS { destination, .. }
+        | EqW { destination, .. }
+        | NotEqW { destination, .. }
+        | GreaterW { destination, .. }
+        | GreaterEqW { destination, .. }
+        | LessW { destination, .. }
+        | LessEqW { destination, .. }
+        | GreaterSW { destination, .. }
+        | GreaterEqSW { destination, .. }
+        | LessSW { destination, .. }
+        | LessEqSW { destination, .. }
+        | SwapNibbles { destination, .. }
+        | BcdAdjust { destination, .. } => Some((destination, false)),
+        _ => None,
+    }
+}
+
+// mutable destination of a statement.
+fn destination_mut(statement: &mut Statement) -> Option<&mut Destination> {
+    #[allow(clippy::enum_glob_use)]
+    use Statement::*;
+    match statement {
+        Ld { destination, .. }
+        | LdW { destination, .. }
+        | LdAddr { destination, .. }
+        | Ext { destination, .. }
+        | SignExt { destination, .. }
+        | Trunc { destination, .. }
+        | Inc { destination, .. }
+        | Dec { destination, .. }
+        | IncW { destination, .. }
+        | DecW { destination, .. }
+        | Add { destination, .. }
+        | Sub { destination, .. }
+        | And { destination, .. }
+        | Xor { destination, .. }
+        | Or { destination, .. }
+        | LeftShift { destination, .. }
+        | RightShift { destination, .. }
+        | Mul { destination, .. }
+        | Div { destination, .. }
+        | Rem { destination, .. }
+        | RightShiftS { destination, .. }
+        | DivS { destination, .. }
+        | RemS { destination, .. }
+        | AddC { destination, .. }
+        | SubC { destination, .. }
+        | AddCarry { destination, .. }
+        | SubBorrow { destination, .. }
+        | AddW { destination, .. }
+        | SubW { destination, .. }
+        | AndW { destination, .. }
+        | XorW { destination, .. }
+        | OrW { destination, .. }
+        | LeftShiftW { destination, .. }
+        | RightShiftW { destination, .. }
+        | MulW { destination, .. }
+        | DivW { destination, .. }
+        | RemW { destination, .. }
+        | RightShiftSW { destination, .. }
+        | DivSW { destination, .. }
+        | RemSW { destination, .. }
+        | Eq { destination, .. }
+        | NotEq { destination, .. }
+        | Greater { destination, .. }
+        | GreaterEq { destination, .. }
+        | Less { destination, .. }
+        | LessEq { destination, .. }
+        | GreaterS { destination, .. }
+        | GreaterEqS { destination, .. }
+        | LessS { destination, .. }
+        | LessEqS { destination, .. }
+        | EqW { destination, .. }
+        | NotEqW { destination, .. }
+        | GreaterW { destination, .. }
+        | GreaterEqW { destination, .. }
+        | LessW { destination, .. }
+        | LessEqW { destination, .. }
+        | GreaterSW { destination, .. }
+        | GreaterEqSW { destination, .. }
+        | LessSW { destination, .. }
+        | LessEqSW { destination, .. }
+        | SwapNibbles { destination, .. }
+        | BcdAdjust { destination, .. } => Some(destination),
+        _ => None,
+    }
+}
+
+// value read by a statement.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Read {
+    // register, and whether it is a 16bit register
+    Register(Register, bool),
+    Memory(Pointer),
+    // memory at the address held by a source (could be anywhere)
+    Indirect,
+}
+
+// values read by a statement, including the ones to compute the address of its
+// destination. Only the offset of the pointer of `ld_addr` is read.
+fn reads(statement: &Statement) -> Vec<Read> {
+    #[allow(clippy::enum_glob_use)]
+    use Statement::*;
+    let mut reads = Vec::new();
+    match statement {
+        Ld { source, .. }
+        | Ext { source, .. }
+        | SignExt { source, .. }
+        | Inc { source, .. }
+        | Dec { source, .. }
+        | SwapNibbles { source, .. }
+        | BcdAdjust { source, .. }
+        | JmpCmp { source, .. }
+        | JmpCmpNot { source, .. } => source_reads(source, &mut reads),
+        LdW { source, .. } | Trunc { source, .. } | IncW { source, .. } | DecW { source, .. } => {
+            source_reads(source, &mut reads)
+        }
+        LdAddr { source, .. } => match source {
+            Source::Pointer {
+                offset: Some(offset),
+                ..
+            } => source_reads(offset, &mut reads),
+            Source::Indirect(pointer) => source_reads(pointer, &mut reads),
+            _ => {}
+        },
+        Add { left, right, .. }
+        | Sub { left, right, .. }
+        | And { left, right, .. }
+        | Xor { left, right, .. }
+        | Or { left, right, .. }
+        | LeftShift { left, right, .. }
+        | RightShift { left, right, .. }
+        | Mul { left, right, .. }
+        | Div { left, right, .. }
+        | Rem { left, right, .. }
+        | RightShiftS { left, right, .. }
+        | DivS { left, right, .. }
+        | RemS { left, right, .. }
+        | Eq { left, right, .. }
+        | NotEq { left, right, .. }
+        | Greater { left, right, .. }
+        | GreaterEq { left, right, .. }
+        | Less { left, right, .. }
+        | LessEq { left, right, .. }
+        | GreaterS { left, right, .. }
+        | GreaterEqS { left, right, .. }
+        | LessS { left, right, .. }
+        | LessEqS { left, right, .. } => {
+            source_reads(left, &mut reads);
+            source_reads(right, &mut reads);
+        }
+        AddW { left, right, .. }
+        | SubW { left, right, .. }
+        | AndW { left, right, .. }
+        | XorW { left, right, .. }
+        | OrW { left, right, .. }
+        | MulW { left, right, .. }
+        | DivW { left, right, .. }
+        | RemW { left, right, .. }
+        | DivSW { left, right, .. }
+        | RemSW { left, right, .. }
+        | EqW { left, right, .. }
+        | NotEqW { left, right, .. }
+        | GreaterW { left, right, .. }
+        | GreaterEqW { left, right, .. }
+        | LessW { left, right, .. }
+        | LessEqW { left, right, .. }
+        | GreaterSW { left, right, .. }
+        | GreaterEqSW { left, right, .. }
+        | LessSW { left, right, .. }
+        | LessEqSW { left, right, .. } => {
+            source_reads(left, &mut reads);
+            source_reads(right, &mut reads);
+        }
+        LeftShiftW { left, right, .. }
+        | RightShiftW { left, right, .. }
+        | RightShiftSW { left, right, .. } => {
+            source_reads(left, &mut reads);
+            source_reads(right, &mut reads);
+        }
+        AddC {
+            left, right, carry, ..
+        }
+        | SubC {
+            left, right, carry, ..
+        }
+        | AddCarry {
+            left, right, carry, ..
+        }
+        | SubBorrow {
+            left, right, carry, ..
+        } => {
+            source_reads(left, &mut reads);
+            source_reads(right, &mut reads);
+            source_reads(carry, &mut reads);
+        }
+        CallIndirect { routine, .. } => source_reads(routine, &mut reads),
+        Memcpy {
+            source,
+            destination,
+            len,
+        } => {
+            source_reads(source, &mut reads);
+            source_reads(destination, &mut reads);
+            source_reads(len, &mut reads);
+            // the memory it copies from
+            reads.push(Read::Indirect);
+        }
+        Memset {
+            value,
+            destination,
+            len,
+        } => {
+            source_reads(value, &mut reads);
+            source_reads(destination, &mut reads);
+            source_reads(len, &mut reads);
+        }
+        Nop(_)
+        | Stop(_)
+        | Jmp { .. }
+        | Call { .. }
+        | Ret
+        | PushBank { .. }
+        | PopBank
+        | Halt
+        | EnableInterrupts => {}
+    }
+    if let Some(Location::Table { index, .. }) = location(statement) {
+        source_reads(index, &mut reads);
+    }
+    if let Some((destination, _)) = destination(statement) {
+        destination_reads(destination, &mut reads);
+    }
+    reads
+}
+
+// reading the source may have side effects: reads of hardware registers, or
+// through pointers that may point to them.
+fn has_side_effects<T>(source: &Source<T>) -> bool {
+    match source {
+        Source::Pointer {
+            base: Pointer::Absolute(_),
+            ..
+        }
+        | Source::Indirect(_) => true,
+        Source::Pointer {
+            offset: Some(offset),
+            ..
+        } => has_side_effects(offset),
+        _ => false,
+    }
+}
+
+// both pointers point to the same memory space (the same RAM bank, for
+// banked pointers).
+fn same_space(a: &Pointer, b: &Pointer) -> bool {
+    match (a, b) {
+        (Pointer::Banked(a, _), Pointer::Banked(b, _)) => a == b,
+        (a, b) => mem::discriminant(a) == mem::discriminant(b),
+    }
+}
+
+fn source_reads<T: Value>(source: &Source<T>, reads: &mut Vec<Read>) {
+    match source {
+        Source::Pointer { base, offset } => {
+            reads.push(Read::Memory(*base));
+            if let Some(offset) = offset {
+                source_reads(offset, reads);
+            }
+        }
+        Source::Indirect(pointer) => {
+            reads.push(Read::Indirect);
+            source_reads(pointer, reads);
+        }
+        Source::Register(register) => reads.push(Read::Register(*register, T::WORD)),
+        Source::Literal(_) => {}
+    }
+}
+
+// values read to compute the address a destination stores to.
+fn destination_reads(destination: &Destination, reads: &mut Vec<Read>) {
+    match destination {
+        Destination::Pointer {
+            offset: Some(offset),
+            ..
+        } => source_reads(offset, reads),
+        Destination::Indirect(pointer) => source_reads(pointer, reads),
+        _ => {}
+    }
+}
+
+// register of either width, and whether it is a 16bit register.
+type Reg = (Register, bool);
+
+// renames the registers of a routine.
+struct Rename<F>(F);
+
+impl<F: FnMut(Reg) -> Register> Visit for Rename<F> {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        if let Source::Register(register) = source {
+            *register = (self.0)((*register, T::WORD));
+        }
+    }
+}
+
+fn rename(statements: &mut [Statement], rename: impl FnMut(Reg) -> Register) {
+    let mut rename = Rename(rename);
+    for statement in statements {
+        visit_sources(statement, &mut rename);
+        let word = destination(statement).map(|(_, word)| word);
+        if let (Some(Destination::Register(register)), Some(word)) =
+            (destination_mut(statement), word)
+        {
+            *register = (rename.0)((*register, word));
+        }
+    }
+}
+
+// moves the stack pointers from the given address onwards.
+struct Shift {
+    from: u16,
+    shift: u16,
+}
+
+impl Shift {
+    fn pointer(&self, pointer: &mut Pointer) {
+        if let Pointer::Stack(address) = pointer {
+            if *address >= self.from {
+                *address += self.shift;
+            }
+        }
+    }
+}
+
+impl Visit for Shift {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        if let Source::Pointer { base, .. } = source {
+            self.pointer(base);
+        }
+    }
+}
+
+// moves the stack pointers of the statements, and the stack frames of their
+// calls, from the given address onwards by `shift` bytes.
+fn shift_stack(statements: &mut [Statement], from: u16, shift: u16) {
+    let mut visit = Shift { from, shift };
+    for statement in statements {
+        match statement {
+            Statement::Call { range, .. } | Statement::CallIndirect { range, .. }
+                if range.start >= from =>
+            {
+                *range = range.start + shift..;
+            }
+            Statement::LdAddr {
+                source: Source::Pointer { base, .. },
+                ..
+            } => visit.pointer(base),
+            _ => {}
+        }
+        visit_sources(statement, &mut visit);
+        if let Some(Destination::Pointer { base, .. }) = destination_mut(statement) {
+            visit.pointer(base);
+        }
+    }
+}
+
+// registers alive after each statement of a routine (read before they are
+// written, by any of the statements that may run after it).
+fn live_out(statements: &[Statement]) -> Vec<HashSet<Reg>> {
+    liveness(statements).1
+}
+
+// registers alive before and after each statement of a routine.
+fn liveness(statements: &[Statement]) -> (Vec<HashSet<Reg>>, Vec<HashSet<Reg>>) {
+    let liveness = Liveness::new(statements);
+    let registers = |slots: &BTreeSet<Slot>| -> HashSet<Reg> {
+        slots
+            .iter()
+            .filter_map(|slot| match slot {
+                Slot::Register(register) => Some((*register, false)),
+                Slot::RegisterW(register) => Some((*register, true)),
+                Slot::Stack(_) => None,
+            })
+            .collect()
+    };
+    (0..statements.len())
+        .map(|index| {
+            (
+                registers(liveness.live_in(index)),
+                registers(liveness.live_out(index)),
+            )
+        })
+        .unzip()
+}
+
+// registers and bytes of the stack read by the sources of a statement.
+struct Access(analysis::Access);
+
+impl Visit for Access {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        match source {
+            Source::Register(register) => self.0.reads.push(if T::WORD {
+                Slot::RegisterW(*register)
+            } else {
+                Slot::Register(*register)
+            }),
+            Source::Pointer {
+                base: Pointer::Stack(address),
+                offset: None,
+            } => self.0.reads.extend(stack_slots(*address, T::WORD)),
+            Source::Pointer {
+                base: Pointer::Stack(_),
+                offset: Some(_),
+            }
+            | Source::Indirect(_) => self.0.reads_frame = true,
+            _ => {}
+        }
+    }
+}
+
+// bytes of the stack of a value at the given address.
+fn stack_slots(address: u16, word: bool) -> impl Iterator<Item = Slot> {
+    (address..=address + word as u16).map(Slot::Stack)
+}
+
+// slots read and written by a statement, for the analyses of
+// `crate::analysis`.
+pub(crate) fn access(statement: &Statement) -> analysis::Access {
+    let mut visit = Access(analysis::Access::default());
+    visit_sources(&mut statement.clone(), &mut visit);
+    let mut access = visit.0;
+    match statement {
+        // the routines may read and write the stack frame through pointers
+        Statement::Call { .. } | Statement::CallIndirect { .. } | Statement::Memcpy { .. } => {
+            access.reads_frame = true;
+            access.writes_frame = true;
+        }
+        Statement::Memset { .. } => access.writes_frame = true,
+        // the routine jumped to runs in the stack frame
+        _ if matches!(location(statement), Some(Location::Routine(_))) => access.reads_frame = true,
+        _ => {}
+    }
+    match destination(statement) {
+        Some((Destination::Register(register), word)) => access.writes.push(if word {
+            Slot::RegisterW(*register)
+        } else {
+            Slot::Register(*register)
+        }),
+        Some((
+            Destination::Pointer {
+                base: Pointer::Stack(address),
+                offset: None,
+            },
+            word,
+        )) => access.writes.extend(stack_slots(*address, word)),
+        Some((
+            Destination::Pointer {
+                base: Pointer::Stack(_),
+                offset: Some(_),
+            },
+            _,
+        ))
+        | Some((Destination::Indirect(_), _)) => access.writes_frame = true,
+        _ => {}
+    }
+    access
+}
+
+// replace the statement at the given index of a routine with a sequence of
+// statements, moving the jumps over it. Statements without a span take the
+// span of the replaced statement. Returns false, leaving the routine
+// unchanged, if a jump would be out of range.
+fn splice(
+    routine: &mut Routine,
+    at: usize,
+    statements: Vec<Statement>,
+    spans: Vec<Option<Span>>,
+) -> bool {
+    let delta = statements.len() as isize - 1;
+    let position = |index: usize| {
+        if index <= at {
+            index as isize
+        } else {
+            index as isize + delta
+        }
+    };
+    let mut jumps = Vec::new();
+    for (index, statement) in routine.statements.iter().enumerate() {
+        if let Some(location) = location(statement) {
+            match relocate(location, index, position(index), position) {
+                Some(location) => jumps.push((index, location)),
+                None => return false,
+            }
+        }
+    }
+    for (index, location) in jumps {
+        *location_mut(&mut routine.statements[index]).unwrap() = location;
+    }
+    if !routine.spans.is_empty() || spans.iter().any(Option::is_some) {
+        routine.spans.resize(routine.statements.len(), None);
+        let span = routine.spans[at];
+        let spans = (0..statements.len()).map(|i| spans.get(i).copied().flatten().or(span));
+        routine.spans.splice(at..=at, spans);
+    }
+    routine.statements.splice(at..=at, statements);
+    true
+}
+
+// index of the statement a jump jumps to (`None` for jump tables, and tail
+// jumps to other routines).
+fn target(index: usize, statement: &Statement) -> Option<usize> {
+    match location(statement)? {
+        Location::Relative(relative) => Some((index as isize + *relative as isize + 1) as usize),
+        Location::Absolute(target) => Some(*target),
+        Location::Table { .. } | Location::Routine(_) => None,
+    }
+}
+
+// indices of the statements of the routine a jump may jump to (of every entry
+// of jump tables).
+pub(crate) fn targets(index: usize, statement: &Statement) -> Vec<usize> {
+    match location(statement) {
+        Some(Location::Absolute(target)) => vec![*target],
+        Some(location) => location
+            .offsets()
+            .iter()
+            .map(|relative| (index as isize + *relative as isize + 1) as usize)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// location of the jump at `index`, moved to `position`, to the new positions
+// of its targets. `None` if a target would be out of range.
+fn relocate(
+    location: &Location,
+    index: usize,
+    position: isize,
+    new: impl Fn(usize) -> isize,
+) -> Option<Location> {
+    let mut location = location.clone();
+    if let Location::Absolute(target) = &mut location {
+        *target = new(*target) as usize;
+    }
+    for relative in location.offsets_mut() {
+        let target = (index as isize + *relative as isize + 1) as usize;
+        *relative = i8::try_from(new(target) - position - 1).ok()?;
+    }
+    Some(location)
+}
+
+pub(crate) fn location(statement: &Statement) -> Option<&Location> {
+    match statement {
+        Statement::Jmp { location }
+        | Statement::JmpCmp { location, .. }
+        | Statement::JmpCmpNot { location, .. } => Some(location),
+        _ => None,
+    }
+}
+
+pub(crate) fn location_mut(statement: &mut Statement) -> Option<&mut Location> {
+    match statement {
+        Statement::Jmp { location }
+        | Statement::JmpCmp { location, .. }
+        | Statement::JmpCmpNot { location, .. } => Some(location),
+        _ => None,
+    }
+}
@@ -0,0 +1,227 @@
+use super::{
+    destination, has_side_effects, jump_targets, live_out, rename, visit_sources, Reg, Value, Visit,
+};
+use crate::{
+    compile::{optimize::delete_nops, NOP_UNREACHABLE},
+    opcodes::{Destination, Register, Source, Statement},
+    ByteOrder, Ir,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Copy propagation and register coalescing.
+///
+/// The lowering of expressions moves values through intermediate registers
+/// that are read only once. This pass:
+///
+/// - Forwards the sources of register copies (`ld r0 -> r1`) to the
+///   statements that read the copy, until the end of the basic block.
+/// - Removes the loads into registers that are never read afterwards (except
+///   for reads of hardware registers, which may have side effects).
+/// - Merges the registers of the remaining copies when their values are never
+///   alive at the same time, so the copies become no-ops and are removed.
+/// - Renumbers the registers of each routine from `r0`, to keep them within as
+///   few registers as possible.
+///
+/// Returns true if any statement was rewritten.
+pub fn copy_propagation<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let mut optimized = false;
+    for routine in ir.routines.iter_mut() {
+        let statements = routine.statements.clone();
+        forward(&mut routine.statements);
+        remove_dead_loads(&mut routine.statements);
+        coalesce(&mut routine.statements);
+        compact(&mut routine.statements);
+        delete_nops(&mut routine.statements, &mut routine.spans);
+        optimized |= statements != routine.statements;
+    }
+    optimized
+}
+
+// copies of registers known at the statement being visited.
+#[derive(Default)]
+struct Copies {
+    copies: HashMap<Reg, Register>,
+}
+
+impl Visit for Copies {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        if let Source::Register(register) = source {
+            if let Some(copy) = self.copies.get(&(*register, T::WORD)) {
+                *register = *copy;
+            }
+        }
+    }
+}
+
+// replace the reads of copied registers with the register they were copied
+// from.
+fn forward(statements: &mut [Statement]) {
+    use Statement::{Call, CallIndirect, Jmp, JmpCmp, JmpCmpNot, Ld, LdW, Ret, Stop};
+    let targets = jump_targets(statements);
+    let mut copies = Copies::default();
+    for (index, statement) in statements.iter_mut().enumerate() {
+        if targets.contains(&index) {
+            copies.copies.clear();
+        }
+        visit_sources(statement, &mut copies);
+        if let Some((Destination::Register(register), word)) = destination(statement) {
+            let register = (*register, word);
+            copies
+                .copies
+                .retain(|copy, from| *copy != register && (*from, copy.1) != register);
+        }
+        // registers aren't assumed to survive calls
+        if matches!(
+            statement,
+            Jmp { .. }
+                | JmpCmp { .. }
+                | JmpCmpNot { .. }
+                | Call { .. }
+                | CallIndirect { .. }
+                | Ret
+                | Stop(_)
+        ) {
+            copies.copies.clear();
+        }
+        match statement {
+            Ld {
+                source: Source::Register(from),
+                destination: Destination::Register(register),
+            } if from != register => {
+                copies.copies.insert((*register, false), *from);
+            }
+            LdW {
+                source: Source::Register(from),
+                destination: Destination::Register(register),
+            } if from != register => {
+                copies.copies.insert((*register, true), *from);
+            }
+            _ => {}
+        }
+    }
+}
+
+// replace the loads into registers that aren't read afterwards with
+// unreachable Nops.
+fn remove_dead_loads(statements: &mut [Statement]) {
+    use Statement::{Ld, LdAddr, LdW};
+    let live = live_out(statements);
+    for (statement, live) in statements.iter_mut().zip(live) {
+        let register = match destination(statement) {
+            Some((Destination::Register(register), word)) => (*register, word),
+            _ => continue,
+        };
+        let removable = match statement {
+            Ld { source, .. } => !has_side_effects(source),
+            LdW { source, .. } => !has_side_effects(source),
+            LdAddr { .. } => true,
+            _ => false,
+        };
+        if removable && !live.contains(&register) {
+            *statement = Statement::Nop(NOP_UNREACHABLE);
+        }
+    }
+}
+
+// merge the registers of copies whose values are never alive at the same
+// time, and remove the copies.
+fn coalesce(statements: &mut [Statement]) {
+    let live = live_out(statements);
+    let mut registers = Registers::default();
+    for (statement, live) in statements.iter().zip(&live) {
+        if let Some((Destination::Register(register), word)) = destination(statement) {
+            let register = (*register, word);
+            let copied = copy(statement).map(|(from, _)| from);
+            for other in live {
+                if *other != register && other.1 == word && Some(other.0) != copied {
+                    registers.interfere(register, *other);
+                }
+            }
+        }
+    }
+    for statement in statements.iter() {
+        if let Some((from, register)) = copy(statement) {
+            registers.merge(from, register);
+        }
+    }
+    rename(statements, |register| registers.find(register));
+    for statement in statements.iter_mut() {
+        if matches!(copy(statement), Some((from, register)) if from == register.0) {
+            *statement = Statement::Nop(NOP_UNREACHABLE);
+        }
+    }
+}
+
+// renumber the registers of each width, from 0, in the order they appear.
+fn compact(statements: &mut [Statement]) {
+    let mut numbers = HashMap::new();
+    let mut next = [0, 0];
+    rename(statements, |register| {
+        *numbers.entry(register).or_insert_with(|| {
+            let number = next[register.1 as usize];
+            next[register.1 as usize] += 1;
+            number
+        })
+    });
+}
+
+// copy between registers: the register copied from, and the copy.
+fn copy(statement: &Statement) -> Option<(Register, Reg)> {
+    match statement {
+        Statement::Ld {
+            source: Source::Register(from),
+            destination: Destination::Register(register),
+        } => Some((*from, (*register, false))),
+        Statement::LdW {
+            source: Source::Register(from),
+            destination: Destination::Register(register),
+        } => Some((*from, (*register, true))),
+        _ => None,
+    }
+}
+
+// interference of registers, and the registers they have been merged into.
+#[derive(Default)]
+struct Registers {
+    merged: HashMap<Reg, Register>,
+    interference: HashMap<Reg, HashSet<Reg>>,
+}
+
+impl Registers {
+    fn find(&self, mut register: Reg) -> Register {
+        while let Some(merged) = self.merged.get(&register) {
+            register = (*merged, register.1);
+        }
+        register.0
+    }
+
+    fn interfere(&mut self, a: Reg, b: Reg) {
+        self.interference.entry(a).or_default().insert(b);
+        self.interference.entry(b).or_default().insert(a);
+    }
+
+    // merge the copy with the register it is copied from, unless they
+    // interfere. The merged register takes the lower number.
+    fn merge(&mut self, from: Register, register: Reg) {
+        let word = register.1;
+        let a = (self.find((from, word)), word);
+        let b = (self.find(register), word);
+        if a == b
+            || self
+                .interference
+                .get(&a)
+                .is_some_and(|interference| interference.contains(&b))
+        {
+            return;
+        }
+        let (into, from) = if a.0 < b.0 { (a, b) } else { (b, a) };
+        self.merged.insert(from, into.0);
+        for other in self.interference.remove(&from).unwrap_or_default() {
+            let interference = self.interference.entry(other).or_default();
+            interference.remove(&from);
+            interference.insert(into);
+            self.interference.entry(into).or_default().insert(other);
+        }
+    }
+}
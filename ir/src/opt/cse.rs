@@ -0,0 +1,176 @@
+use super::{destination, destination_mut, jump_targets, reads, same_space, Read};
+use crate::{
+    opcodes::{Destination, Pointer, Register, Source, Statement},
+    ByteOrder, Ir,
+};
+use std::collections::HashMap;
+
+/// Common subexpression elimination within basic blocks.
+///
+/// Operations (loads from memory included) whose result is already held by a
+/// register, computed by a previous statement of the same basic block, are
+/// replaced by a load of that register (`add_w [stack 0], 4 -> r1` becomes
+/// `ld_w r0 -> r1`).
+///
+/// A value is forgotten when its register, or any of its operands, is
+/// overwritten. Writes to a memory space (static, stack, a RAM bank, ...)
+/// overwrite every operand read from that space, and writes through a pointer
+/// every operand read from memory. Reads of absolute memory (hardware
+/// registers) are never eliminated.
+///
+/// Returns true if any statement was rewritten.
+pub fn cse<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let mut cse = Cse::default();
+    let mut eliminated = false;
+    for routine in ir.routines.iter_mut() {
+        eliminated |= cse.routine(&mut routine.statements);
+    }
+    eliminated
+}
+
+// register holding the result of an operation.
+struct Value {
+    register: Register,
+    word: bool,
+    // operands of the operation
+    reads: Vec<Read>,
+}
+
+// state of the common subexpression elimination pass.
+#[derive(Default)]
+struct Cse {
+    // operations (with a placeholder destination) computed in the basic block
+    values: HashMap<Statement, Value>,
+}
+
+impl Cse {
+    fn routine(&mut self, statements: &mut [Statement]) -> bool {
+        let targets = jump_targets(statements);
+        let mut eliminated = false;
+        self.values.clear();
+        for (index, statement) in statements.iter_mut().enumerate() {
+            if targets.contains(&index) {
+                self.values.clear();
+            }
+            let mut expression = expression(statement);
+            let value = expression
+                .as_ref()
+                .and_then(|(expression, _)| self.values.get(expression));
+            if let (Some(value), Some((destination, word))) = (value, destination(statement)) {
+                let destination = destination.clone();
+                *statement = if word {
+                    Statement::LdW {
+                        source: Source::Register(value.register),
+                        destination,
+                    }
+                } else {
+                    Statement::Ld {
+                        source: Source::Register(value.register),
+                        destination,
+                    }
+                };
+                // the value is kept in the register that computed it
+                expression = None;
+                eliminated = true;
+            }
+            self.update(statement, expression);
+        }
+        eliminated
+    }
+
+    // forget the values overwritten by the statement, and record the value it
+    // computes.
+    fn update(&mut self, statement: &Statement, expression: Option<(Statement, Vec<Read>)>) {
+        #[allow(clippy::enum_glob_use)]
+        use Statement::*;
+        match statement {
+            Jmp { .. }
+            | JmpCmp { .. }
+            | JmpCmpNot { .. }
+            | Call { .. }
+            | CallIndirect { .. }
+            | Ret
+            | Stop(_)
+            | PushBank { .. }
+            | PopBank
+            | Halt
+            | EnableInterrupts => {
+                self.values.clear();
+                return;
+            }
+            Memcpy { .. } | Memset { .. } => self.write(None),
+            _ => {}
+        }
+        match destination(statement) {
+            Some((Destination::Register(register), word)) => {
+                self.values.retain(|_, value| {
+                    let overwritten = value.register == *register && value.word == word;
+                    !overwritten && !value.reads.contains(&Read::Register(*register, word))
+                });
+                if let Some((expression, reads)) = expression {
+                    if !reads.contains(&Read::Register(*register, word)) {
+                        let value = Value {
+                            register: *register,
+                            word,
+                            reads,
+                        };
+                        self.values.insert(expression, value);
+                    }
+                }
+            }
+            Some((Destination::Pointer { base, .. }, _)) => match base {
+                Pointer::Absolute(_) => self.write(None),
+                base => self.write(Some(*base)),
+            },
+            Some((Destination::Indirect(_), _)) => self.write(None),
+            None => {}
+        }
+    }
+
+    // forget the values read from the memory space of the pointer (from
+    // anywhere in memory if `None`).
+    fn write(&mut self, pointer: Option<Pointer>) {
+        self.values.retain(|_, value| {
+            !value.reads.iter().any(|read| match (read, pointer) {
+                (Read::Register(..), _) => false,
+                (Read::Indirect, _) | (Read::Memory(_), None) => true,
+                (Read::Memory(read), Some(write)) => same_space(read, &write),
+            })
+        });
+    }
+}
+
+// operation computed by a statement, with a placeholder destination, and its
+// operands. Trivial loads (of literals and registers) and reads of absolute
+// memory aren't operations worth reusing.
+fn expression(statement: &Statement) -> Option<(Statement, Vec<Read>)> {
+    match statement {
+        Statement::Ld {
+            source: Source::Literal(_),
+            ..
+        }
+        | Statement::Ld {
+            source: Source::Register(_),
+            ..
+        }
+        | Statement::LdW {
+            source: Source::Literal(_),
+            ..
+        }
+        | Statement::LdW {
+            source: Source::Register(_),
+            ..
+        } => return None,
+        _ => {}
+    }
+    let mut expression = statement.clone();
+    *destination_mut(&mut expression)? = Destination::Register(Register::MAX);
+    let reads = reads(&expression);
+    if reads
+        .iter()
+        .any(|read| matches!(read, Read::Memory(Pointer::Absolute(_))))
+    {
+        return None;
+    }
+    Some((expression, reads))
+}
@@ -0,0 +1,118 @@
+use super::{live_out, location_mut, rename, shift_stack, splice};
+use crate::{
+    opcodes::{Location, Statement},
+    ByteOrder, Inline, Ir, Routine,
+};
+use std::{collections::HashMap, convert::TryFrom};
+
+/// Function inlining.
+///
+/// Calls to small routines are replaced by the statements of the routine: its
+/// stack offsets are moved to the stack frame the call passes it, its registers
+/// are renumbered so they don't overwrite the registers of the caller that are
+/// alive across the call, and its returns become jumps past the end of the
+/// inlined statements.
+///
+/// Routines of up to `threshold` statements are inlined, unless they are
+/// [`Inline::Never`] (`#[inline(never)]`). [`Inline::Always`] routines
+/// (`#[inline]`) are inlined regardless of their size. Banked routines,
+/// recursive calls, calls through function pointers, routines with tail jumps,
+/// and calls that would put a jump out of range are never inlined. The calls of inlined
+/// statements are kept.
+///
+/// Returns true if any call was inlined.
+pub fn inline<B: ByteOrder>(ir: &mut Ir<B>, threshold: usize) -> bool {
+    let routines = ir.routines.clone();
+    let mut inlined = false;
+    for (index, routine) in ir.routines.iter_mut().enumerate() {
+        let calls: Vec<_> = routine
+            .statements
+            .iter()
+            .enumerate()
+            .filter_map(|(at, statement)| match statement {
+                Statement::Call {
+                    routine: callee,
+                    range,
+                } if *callee != index && inlinable(&routines[*callee], threshold) => {
+                    Some((at, *callee, range.start))
+                }
+                _ => None,
+            })
+            .collect();
+        // from the last call, so the indices of the previous ones don't move
+        for (at, callee, start) in calls.into_iter().rev() {
+            inlined |= inline_call(routine, at, &routines[callee], start);
+        }
+    }
+    inlined
+}
+
+fn inlinable(routine: &Routine, threshold: usize) -> bool {
+    routine.bank.is_none()
+        && match routine.inline {
+            Inline::Auto => routine.statements.len() <= threshold,
+            Inline::Always => true,
+            Inline::Never => false,
+        }
+}
+
+// replace the call at the given index of the caller with the statements of
+// the callee, whose stack frame starts at `start`. Returns false if the call
+// can't be inlined.
+fn inline_call(caller: &mut Routine, at: usize, callee: &Routine, start: u16) -> bool {
+    let mut statements = callee.statements.clone();
+    let mut spans: Vec<_> = (0..statements.len()).map(|i| callee.span(i)).collect();
+    if statements.last() == Some(&Statement::Ret) {
+        statements.pop();
+        spans.pop();
+    }
+    let len = statements.len();
+    for (index, statement) in statements.iter_mut().enumerate() {
+        // absolute locations become relative to the jump
+        if let Some(location) = location_mut(statement) {
+            match *location {
+                Location::Routine(_) => return false,
+                Location::Absolute(target) => {
+                    match i8::try_from(target as isize - index as isize - 1) {
+                        Ok(relative) => *location = Location::Relative(relative),
+                        Err(_) => return false,
+                    }
+                }
+                _ => {}
+            }
+        }
+        if *statement == Statement::Ret {
+            match i8::try_from(len - index - 1) {
+                Ok(relative) => {
+                    *statement = Statement::Jmp {
+                        location: Location::Relative(relative),
+                    }
+                }
+                Err(_) => return false,
+            }
+        }
+    }
+    shift_stack(&mut statements, 0, start);
+
+    // registers of the callee take the numbers of the caller registers that
+    // aren't alive after the call
+    let live = &live_out(&caller.statements)[at];
+    let mut numbers = HashMap::new();
+    let mut next = [0, 0];
+    rename(&mut statements, |register| {
+        *numbers.entry(register).or_insert_with(|| {
+            let next = &mut next[register.1 as usize];
+            while live.contains(&(*next, register.1)) {
+                *next += 1;
+            }
+            *next += 1;
+            *next - 1
+        })
+    });
+
+    if !splice(caller, at, statements, spans) {
+        return false;
+    }
+    caller.stack_size = caller.stack_size.max(start + callee.stack_size);
+    true
+}
@@ -0,0 +1,269 @@
+use super::{has_side_effects, jump_targets, location, location_mut, relocate, target, targets};
+use crate::{
+    compile::{
+        optimize::{delete_nops, mark_unreachable},
+        NOP_UNREACHABLE,
+    },
+    opcodes::{Location, Source, Statement},
+    ByteOrder, Ir, Routine,
+};
+use std::convert::TryFrom;
+
+/// Jump threading and branch simplification.
+///
+/// The lowering of nested `if`/`else` statements and loops leaves chains of
+/// jumps behind. Until the routines can't be simplified any further:
+///
+/// - Jumps to unconditional jumps are threaded to their final target, and
+///   unconditional jumps to `ret` and `stop` are replaced by the statement they
+///   jump to.
+/// - Conditional jumps on literals are replaced by a jump if the condition
+///   holds, and removed otherwise. So are jump tables indexed by a literal.
+/// - Conditional jumps over an unconditional jump are inverted
+///   (`jmp_cmp 1, r0` followed by `jmp 4` becomes `jmp_cmp_not 5, r0`), and
+///   jumps to the next statement are removed.
+/// - Blocks of statements reached only by an unconditional jump are moved in
+///   place of the jump.
+/// - The statements that become unreachable are removed.
+///
+/// Returns true if any statement was rewritten.
+pub fn simplify_branches<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let mut simplified = false;
+    for routine in ir.routines.iter_mut() {
+        if routine.statements.is_empty() {
+            continue;
+        }
+        loop {
+            let statements = &mut routine.statements;
+            let mut changed = fold(statements);
+            changed |= thread(statements);
+            changed |= invert(statements);
+            changed |= mark_unreachable(statements);
+            changed |= delete_nops(statements, &mut routine.spans);
+            changed |= merge(routine);
+            if !changed {
+                break;
+            }
+            simplified = true;
+        }
+    }
+    simplified
+}
+
+// replace the conditional jumps on literals (and jump tables indexed by a
+// literal) with a jump, or an unreachable Nop if they don't jump.
+fn fold(statements: &mut [Statement]) -> bool {
+    let mut folded = false;
+    for statement in statements.iter_mut() {
+        if let Some(Location::Table {
+            index: Source::Literal(index),
+            targets,
+        }) = location_mut(statement)
+        {
+            *location_mut(statement).unwrap() = match targets.get(usize::from(*index)) {
+                Some(relative) => Location::Relative(*relative),
+                // out of bounds, falls through
+                None => Location::Relative(0),
+            };
+            folded = true;
+        }
+        let (location, jumps) = match statement {
+            Statement::JmpCmp {
+                location,
+                source: Source::Literal(literal),
+            } => (location.clone(), *literal != 0),
+            Statement::JmpCmpNot {
+                location,
+                source: Source::Literal(literal),
+            } => (location.clone(), *literal == 0),
+            _ => continue,
+        };
+        *statement = if jumps {
+            Statement::Jmp { location }
+        } else {
+            Statement::Nop(NOP_UNREACHABLE)
+        };
+        folded = true;
+    }
+    folded
+}
+
+// move the targets of jumps to unconditional jumps to the end of the chain
+// of jumps.
+fn thread(statements: &mut [Statement]) -> bool {
+    let mut threaded = false;
+    for index in 0..statements.len() {
+        let first = match target(index, &statements[index]) {
+            Some(target) => target,
+            None => continue,
+        };
+        let mut last = first;
+        // chains of jumps may loop
+        for _ in 0..statements.len() {
+            match statements.get(last) {
+                Some(jmp @ Statement::Jmp { .. }) => match target(last, jmp) {
+                    Some(target) => last = target,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        let statement = &statements[index];
+        match (statement, statements.get(last)) {
+            (Statement::Jmp { .. }, Some(ret @ Statement::Ret))
+            | (Statement::Jmp { .. }, Some(ret @ Statement::Stop(_)))
+            | (
+                Statement::Jmp { .. },
+                Some(
+                    ret @ Statement::Jmp {
+                        location: Location::Routine(_),
+                    },
+                ),
+            ) => {
+                statements[index] = ret.clone();
+                threaded = true;
+            }
+            _ if last != first => {
+                if let Ok(relative) = i8::try_from(last as isize - index as isize - 1) {
+                    *location_mut(&mut statements[index]).unwrap() = Location::Relative(relative);
+                    threaded = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    threaded
+}
+
+// invert the conditional jumps over an unconditional jump, and remove the
+// jumps to the next statement.
+fn invert(statements: &mut [Statement]) -> bool {
+    let targets = jump_targets(statements);
+    let mut inverted = false;
+    for index in 0..statements.len() {
+        let relative = match statements.get(index + 1) {
+            Some(Statement::Jmp {
+                location: Location::Relative(relative),
+            }) if !targets.contains(&(index + 1)) => relative.checked_add(1),
+            _ => None,
+        };
+        let inversion = match (&statements[index], relative) {
+            (Statement::JmpCmp { location, source }, Some(relative))
+                if *location == Location::Relative(1) =>
+            {
+                Some(Statement::JmpCmpNot {
+                    location: Location::Relative(relative),
+                    source: source.clone(),
+                })
+            }
+            (Statement::JmpCmpNot { location, source }, Some(relative))
+                if *location == Location::Relative(1) =>
+            {
+                Some(Statement::JmpCmp {
+                    location: Location::Relative(relative),
+                    source: source.clone(),
+                })
+            }
+            _ => None,
+        };
+        if let Some(statement) = inversion {
+            statements[index] = statement;
+            statements[index + 1] = Statement::Nop(NOP_UNREACHABLE);
+            inverted = true;
+            continue;
+        }
+        let removable = match &statements[index] {
+            Statement::Jmp { location } => *location == Location::Relative(0),
+            Statement::JmpCmp { location, source } | Statement::JmpCmpNot { location, source } => {
+                *location == Location::Relative(0) && !has_side_effects(source)
+            }
+            _ => false,
+        };
+        if removable {
+            statements[index] = Statement::Nop(NOP_UNREACHABLE);
+            inverted = true;
+        }
+    }
+    inverted
+}
+
+// move a block of statements reached only by an unconditional jump in place
+// of the jump.
+fn merge(routine: &mut Routine) -> bool {
+    let statements = &routine.statements;
+    let targets: Vec<_> = statements
+        .iter()
+        .enumerate()
+        .map(|(index, statement)| targets(index, statement))
+        .collect();
+    // jump tables fall through when their index is out of bounds
+    let ends = |statement: &Statement| match statement {
+        Statement::Jmp { location } => !matches!(location, Location::Table { .. }),
+        Statement::Ret | Statement::Stop(_) => true,
+        _ => false,
+    };
+    let mut merges = Vec::new();
+    for (jmp, statement) in statements.iter().enumerate() {
+        let start = match (statement, target(jmp, statement)) {
+            (Statement::Jmp { .. }, Some(start)) if start > 0 && start < statements.len() => start,
+            _ => continue,
+        };
+        let reached = ends(&statements[start - 1])
+            && targets
+                .iter()
+                .enumerate()
+                .all(|(index, targets)| index == jmp || !targets.contains(&start));
+        let end = match (start..statements.len()).find(|index| ends(&statements[*index])) {
+            Some(end) if reached && !(start..=end).contains(&jmp) => end,
+            _ => continue,
+        };
+        let len = statements.len();
+        let order: Vec<_> = if start > jmp {
+            (0..jmp)
+                .chain(start..=end)
+                .chain(jmp + 1..start)
+                .chain(end + 1..len)
+                .collect()
+        } else {
+            (0..start)
+                .chain(end + 1..jmp)
+                .chain(start..=end)
+                .chain(jmp + 1..len)
+                .collect()
+        };
+        merges.push((order, jmp, start));
+    }
+    merges
+        .into_iter()
+        .any(|(order, jmp, start)| reorder(routine, &order, jmp, start))
+}
+
+// reorder the statements of a routine, removing the jump at `jmp` to the
+// statement at `start`. Returns false, leaving the routine unchanged, if a
+// jump would be out of range.
+fn reorder(routine: &mut Routine, order: &[usize], jmp: usize, start: usize) -> bool {
+    let len = routine.statements.len();
+    let mut positions = vec![0; len + 1];
+    for (position, index) in order.iter().enumerate() {
+        positions[*index] = position;
+    }
+    positions[len] = order.len();
+    positions[jmp] = positions[start];
+    let mut statements = Vec::with_capacity(order.len());
+    for (position, index) in order.iter().enumerate() {
+        let mut statement = routine.statements[*index].clone();
+        if let Some(location) = location(&statement) {
+            let new = |target: usize| positions[target] as isize;
+            match relocate(location, *index, position as isize, new) {
+                Some(location) => *location_mut(&mut statement).unwrap() = location,
+                None => return false,
+            }
+        }
+        statements.push(statement);
+    }
+    if !routine.spans.is_empty() {
+        routine.spans = order.iter().map(|index| routine.spans[*index]).collect();
+    }
+    routine.statements = statements;
+    true
+}
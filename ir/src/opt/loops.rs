@@ -0,0 +1,262 @@
+use super::{
+    destination, liveness, location, location_mut, reads, relocate, same_space, targets, Read, Reg,
+};
+use crate::{
+    analysis::Cfg,
+    opcodes::{Destination, Pointer, Statement},
+    ByteOrder, Ir, Overflow, Routine,
+};
+use std::{collections::HashMap, ops::Range};
+
+/// Loop-invariant code motion.
+///
+/// Loops are the statements from the target of a backward jump (the header of
+/// the loop) to the last backward jump to it. Loads into registers that compute
+/// the same value on every iteration of a loop (whose operands are literals,
+/// registers that aren't written in the loop, and memory that isn't written in
+/// the loop) are moved before its header, so they only run once. Address
+/// computations (`ld_addr`) are moved the same way.
+///
+/// A load is moved only if its register isn't written by any other statement
+/// of the loop, and isn't alive when the loop is entered nor after it exits.
+/// Loops entered other than by falling through into their header, reads with
+/// side effects (of absolute memory, and through pointers), and operations that
+/// may trap stay where they are. Statics are assumed to be written by every
+/// loop of the programs with interrupt handlers.
+///
+/// Returns true if any statement was moved.
+pub fn licm<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let handlers = &ir.handlers;
+    let interrupts = [
+        handlers.vblank,
+        handlers.lcd_stat,
+        handlers.timer,
+        handlers.serial,
+        handlers.joypad,
+    ]
+    .iter()
+    .any(Option::is_some);
+    let trapping = ir.overflow == Overflow::Trapping;
+    let mut moved = false;
+    for routine in ir.routines.iter_mut() {
+        while let Some((header, index)) = invariant(&routine.statements, trapping, interrupts) {
+            if !hoist(routine, header, index) {
+                break;
+            }
+            moved = true;
+        }
+    }
+    moved
+}
+
+// statement that can be moved out of its loop (of the innermost loops first),
+// and the header of the loop.
+fn invariant(statements: &[Statement], trapping: bool, interrupts: bool) -> Option<(usize, usize)> {
+    let (live_in, _) = liveness(statements);
+    let cfg = Cfg::new(statements);
+    let mut loops = loops(statements);
+    loops.sort_by_key(|loop_| (loop_.len(), loop_.start));
+    for loop_ in loops {
+        let body = &statements[loop_.clone()];
+        let entered = statements.iter().enumerate().any(|(index, statement)| {
+            !loop_.contains(&index) && targets(index, statement).iter().any(|t| loop_.contains(t))
+        });
+        if entered {
+            continue;
+        }
+        let writes = Writes::new(body, interrupts);
+        // statements of the loop that write a register
+        let writes_to = |register| body.iter().filter(|s| written(s) == Some(register)).count();
+        let alive = |register: Reg| {
+            live_in[loop_.start].contains(&register)
+                || loop_
+                    .clone()
+                    .flat_map(|index| cfg.successors(index).iter())
+                    .any(|s| !loop_.contains(s) && live_in[*s].contains(&register))
+        };
+        for index in loop_.clone() {
+            let statement = &statements[index];
+            let register = match written(statement) {
+                Some(register) if movable(statement, trapping) => register,
+                _ => continue,
+            };
+            let invariant = reads(statement).iter().all(|read| match read {
+                Read::Register(register, word) => writes_to((*register, *word)) == 0,
+                Read::Memory(pointer) => !writes.clobbers(pointer),
+                Read::Indirect => false,
+            });
+            if invariant && writes_to(register) == 1 && !alive(register) {
+                return Some((loop_.start, index));
+            }
+        }
+    }
+    None
+}
+
+// moves the statement at the given index before the header of its loop.
+fn hoist(routine: &mut Routine, header: usize, index: usize) -> bool {
+    let moved = |index_: usize| (header..index).contains(&index_);
+    let mut jumps = Vec::new();
+    for (source, statement) in routine.statements.iter().enumerate() {
+        if let Some(location) = location(statement) {
+            // the jumps to the moved statement land on the next one
+            let new = |target: usize| {
+                if (header..=index).contains(&target) {
+                    target as isize + 1
+                } else {
+                    target as isize
+                }
+            };
+            let position = if moved(source) { source + 1 } else { source };
+            match relocate(location, source, position as isize, new) {
+                Some(location) => jumps.push((position, location)),
+                None => return false,
+            }
+        }
+    }
+    routine.statements[header..=index].rotate_right(1);
+    if !routine.spans.is_empty() {
+        routine.spans[header..=index].rotate_right(1);
+    }
+    for (index, location) in jumps {
+        *location_mut(&mut routine.statements[index]).unwrap() = location;
+    }
+    true
+}
+
+// loops of a routine, from their header to the last backward jump to it.
+fn loops(statements: &[Statement]) -> Vec<Range<usize>> {
+    let mut loops = HashMap::new();
+    for (index, statement) in statements.iter().enumerate() {
+        for header in targets(index, statement) {
+            if header <= index {
+                let end = loops.entry(header).or_insert(index);
+                *end = index.max(*end);
+            }
+        }
+    }
+    loops
+        .into_iter()
+        .map(|(header, end)| header..end + 1)
+        .collect()
+}
+
+// register written by a statement.
+fn written(statement: &Statement) -> Option<Reg> {
+    match destination(statement) {
+        Some((Destination::Register(register), word)) => Some((*register, word)),
+        _ => None,
+    }
+}
+
+// the statement can run when the loop wouldn't run it: it doesn't trap, and
+// doesn't depend on the state of the CPU.
+fn movable(statement: &Statement, trapping: bool) -> bool {
+    #[allow(clippy::enum_glob_use)]
+    use Statement::*;
+    match statement {
+        Ld { .. }
+        | LdW { .. }
+        | LdAddr { .. }
+        | Ext { .. }
+        | SignExt { .. }
+        | Trunc { .. }
+        | SwapNibbles { .. }
+        | And { .. }
+        | Xor { .. }
+        | Or { .. }
+        | LeftShift { .. }
+        | RightShift { .. }
+        | AndW { .. }
+        | XorW { .. }
+        | OrW { .. }
+        | LeftShiftW { .. }
+        | RightShiftW { .. }
+        | RightShiftS { .. }
+        | RightShiftSW { .. }
+        | Eq { .. }
+        | NotEq { .. }
+        | Greater { .. }
+        | GreaterEq { .. }
+        | Less { .. }
+        | LessEq { .. }
+        | GreaterS { .. }
+        | GreaterEqS { .. }
+        | LessS { .. }
+        | LessEqS { .. }
+        | EqW { .. }
+        | NotEqW { .. }
+        | GreaterW { .. }
+        | GreaterEqW { .. }
+        | LessW { .. }
+        | LessEqW { .. }
+        | GreaterSW { .. }
+        | GreaterEqSW { .. }
+        | LessSW { .. }
+        | LessEqSW { .. }
+        | AddC { .. }
+        | SubC { .. }
+        | AddCarry { .. }
+        | SubBorrow { .. } => true,
+        Inc { .. }
+        | Dec { .. }
+        | Add { .. }
+        | Sub { .. }
+        | Mul { .. }
+        | IncW { .. }
+        | DecW { .. }
+        | AddW { .. }
+        | SubW { .. }
+        | MulW { .. } => !trapping,
+        _ => false,
+    }
+}
+
+// memory written by the statements of a loop.
+struct Writes {
+    // memory spaces written through pointers
+    pointers: Vec<Pointer>,
+    // written through pointers, by calls, or by copies (could be anywhere)
+    anywhere: bool,
+    // statics written by interrupt handlers
+    interrupts: bool,
+}
+
+impl Writes {
+    fn new(body: &[Statement], interrupts: bool) -> Self {
+        let mut writes = Self {
+            pointers: Vec::new(),
+            anywhere: false,
+            interrupts,
+        };
+        for statement in body {
+            match statement {
+                Statement::Call { .. }
+                | Statement::CallIndirect { .. }
+                | Statement::Memcpy { .. }
+                | Statement::Memset { .. } => writes.anywhere = true,
+                _ => {}
+            }
+            match destination(statement) {
+                Some((Destination::Pointer { base, .. }, _)) => match base {
+                    Pointer::Absolute(_) => writes.anywhere = true,
+                    base => writes.pointers.push(*base),
+                },
+                Some((Destination::Indirect(_), _)) => writes.anywhere = true,
+                _ => {}
+            }
+        }
+        writes
+    }
+
+    // reads of the pointer may read a value written by the loop.
+    fn clobbers(&self, pointer: &Pointer) -> bool {
+        match pointer {
+            Pointer::Absolute(_) => true,
+            Pointer::Static(_) | Pointer::Banked(..) if self.interrupts => true,
+            pointer => {
+                self.anywhere || self.pointers.iter().any(|write| same_space(write, pointer))
+            }
+        }
+    }
+}
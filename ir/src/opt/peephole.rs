@@ -0,0 +1,441 @@
+//! Peephole optimization.
+//!
+//! Small windows of consecutive statements are matched against a set of
+//! [`Rule`]s, and replaced by the (shorter or equivalent) statements they are
+//! rewritten to. Windows never span the target of a jump, so the control flow
+//! of the routine doesn't change.
+//!
+//! ```
+//! use ir::{byteorder::NativeEndian, opt::peephole::Peephole, Ir};
+//!
+//! let text = "routine 0 - stack 0 args 0 return 0
+//!     add [static 0], 0 -> r0
+//!     ld r0 -> [static 1]
+//! end";
+//! let mut ir: Ir<NativeEndian> = ir::parse_text(text).unwrap();
+//!
+//! assert!(Peephole::standard().run(&mut ir));
+//! assert_eq!(1, ir.main().statements.len());
+//! assert_eq!("ld [static 0] -> [static 1]", ir.main().statements[0].to_string());
+//! ```
+use super::{destination, destination_reads, jump_targets, reads, Read};
+use crate::{
+    compile::{optimize::delete_nops, NOP_UNREACHABLE},
+    opcodes::{Destination, Pointer, Register, Source, Statement},
+    parser::lex::span::Span,
+    ByteOrder, Ir,
+};
+use std::collections::HashSet;
+
+/// Rewrite rule of a peephole optimization.
+pub trait Rule {
+    /// Number of consecutive statements matched by the rule.
+    fn window(&self) -> usize;
+
+    /// Statements to replace the window with, or `None` if the rule doesn't
+    /// match it.
+    ///
+    /// The rewrite must not be longer than the window. The statements of the
+    /// window are never the target of a jump, except for the first one.
+    fn rewrite(&self, window: &[Statement], context: &Context<'_>) -> Option<Vec<Statement>>;
+}
+
+/// Location of a window in its routine, to check how the registers it writes
+/// are used by the rest of the routine.
+pub struct Context<'a> {
+    statements: &'a [Statement],
+    targets: &'a HashSet<usize>,
+    start: usize,
+    end: usize,
+}
+
+impl Context<'_> {
+    /// Returns true if the value written to the given 8bit register by the
+    /// window is never read.
+    pub fn is_dead_byte(&self, register: Register) -> bool {
+        self.is_dead(register, false)
+    }
+
+    /// Returns true if the value written to the given 16bit register by the
+    /// window is never read.
+    pub fn is_dead_word(&self, register: Register) -> bool {
+        self.is_dead(register, true)
+    }
+
+    fn is_dead(&self, register: Register, word: bool) -> bool {
+        use Statement::{Jmp, JmpCmp, JmpCmpNot, Ret, Stop};
+        for (index, statement) in self.statements.iter().enumerate().skip(self.end) {
+            if self.targets.contains(&index) {
+                return self.is_unused(register);
+            }
+            if reads_register(statement, register) {
+                return false;
+            }
+            match (statement, destination(statement)) {
+                (_, Some((Destination::Register(r), w))) if *r == register && w == word => {
+                    return true
+                }
+                (Ret, _) | (Stop(_), _) => return true,
+                (Jmp { .. }, _) | (JmpCmp { .. }, _) | (JmpCmpNot { .. }, _) => {
+                    return self.is_unused(register)
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    // the register isn't read outside of the window (conservative fallback
+    // once the control flow is no longer linear).
+    fn is_unused(&self, register: Register) -> bool {
+        self.statements
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !(self.start..self.end).contains(index))
+            .all(|(_, statement)| !reads_register(statement, register))
+    }
+}
+
+/// Set of peephole rules, applied until none of them matches.
+#[derive(Default)]
+pub struct Peephole {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Peephole {
+    /// Empty set of rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Standard set of rules, to clean up the output of the compiler:
+    /// [`StoreBack`], [`SelfLoad`], [`AddZero`], [`DoubleNot`] and
+    /// [`ForwardLoad`].
+    pub fn standard() -> Self {
+        Self::new()
+            .rule(StoreBack)
+            .rule(SelfLoad)
+            .rule(AddZero)
+            .rule(DoubleNot)
+            .rule(ForwardLoad)
+    }
+
+    /// Add a rule to the set. Rules are tried in the order they are added.
+    pub fn rule<R: Rule + 'static>(mut self, rule: R) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Apply the rules to every routine of the program.
+    ///
+    /// Returns true if any statement was rewritten.
+    pub fn run<B: ByteOrder>(&self, ir: &mut Ir<B>) -> bool {
+        let mut optimized = false;
+        for routine in ir.routines.iter_mut() {
+            optimized |= self.routine(&mut routine.statements, &mut routine.spans);
+        }
+        optimized
+    }
+
+    fn routine(&self, statements: &mut Vec<Statement>, spans: &mut Vec<Option<Span>>) -> bool {
+        let window = self.rules.iter().map(|r| r.window()).max().unwrap_or(1);
+        let mut targets = jump_targets(statements);
+        let mut optimized = false;
+        let mut index = 0;
+        while index < statements.len() {
+            if self.rewrite(statements, &targets, index) {
+                delete_nops(statements, spans);
+                targets = jump_targets(statements);
+                optimized = true;
+                // the rewrite may complete a window that starts before it
+                index = index.saturating_sub(window - 1);
+            } else {
+                index += 1;
+            }
+        }
+        optimized
+    }
+
+    // rewrite the window starting at the given index with the first rule that
+    // matches it. Removed statements are replaced by unreachable Nops.
+    fn rewrite(
+        &self,
+        statements: &mut [Statement],
+        targets: &HashSet<usize>,
+        start: usize,
+    ) -> bool {
+        for rule in &self.rules {
+            let end = start + rule.window();
+            if end > statements.len() || (start + 1..end).any(|i| targets.contains(&i)) {
+                continue;
+            }
+            let context = Context {
+                statements,
+                targets,
+                start,
+                end,
+            };
+            let rewrite = match rule.rewrite(&statements[start..end], &context) {
+                Some(rewrite) if rewrite[..] != statements[start..end] => rewrite,
+                _ => continue,
+            };
+            assert!(
+                rewrite.len() <= rule.window(),
+                "Peephole rewrite longer than its window"
+            );
+            let nops = rule.window() - rewrite.len();
+            let window = rewrite
+                .into_iter()
+                .chain(std::iter::repeat_n(Statement::Nop(NOP_UNREACHABLE), nops));
+            for (statement, new) in statements[start..end].iter_mut().zip(window) {
+                *statement = new;
+            }
+            return true;
+        }
+        false
+    }
+}
+
+/// `ld x -> r`, `ld r -> x`: the second load stores back the value just loaded,
+/// so it is removed.
+pub struct StoreBack;
+
+impl Rule for StoreBack {
+    fn window(&self) -> usize {
+        2
+    }
+
+    fn rewrite(&self, window: &[Statement], _: &Context<'_>) -> Option<Vec<Statement>> {
+        use Statement::{Ld, LdW};
+        match window {
+            [Ld {
+                source,
+                destination: Destination::Register(register),
+            }, Ld {
+                source: Source::Register(r),
+                destination,
+            }] if r == register && same(source, destination) => Some(vec![window[0].clone()]),
+            [LdW {
+                source,
+                destination: Destination::Register(register),
+            }, LdW {
+                source: Source::Register(r),
+                destination,
+            }] if r == register && same(source, destination) => Some(vec![window[0].clone()]),
+            _ => None,
+        }
+    }
+}
+
+/// `ld x -> x`: removed.
+pub struct SelfLoad;
+
+impl Rule for SelfLoad {
+    fn window(&self) -> usize {
+        1
+    }
+
+    fn rewrite(&self, window: &[Statement], _: &Context<'_>) -> Option<Vec<Statement>> {
+        use Statement::{Ld, LdW};
+        match window {
+            [Ld {
+                source,
+                destination,
+            }] if same(source, destination) => Some(Vec::new()),
+            [LdW {
+                source,
+                destination,
+            }] if same(source, destination) => Some(Vec::new()),
+            _ => None,
+        }
+    }
+}
+
+/// `add x, 0 -> d`, `add 0, x -> d` and `sub x, 0 -> d` (and their 16bit
+/// versions): `ld x -> d`.
+pub struct AddZero;
+
+impl Rule for AddZero {
+    fn window(&self) -> usize {
+        1
+    }
+
+    fn rewrite(&self, window: &[Statement], _: &Context<'_>) -> Option<Vec<Statement>> {
+        use Statement::{Add, AddW, Ld, LdW, Sub, SubW};
+        match window {
+            [Add {
+                left: source,
+                right: Source::Literal(0),
+                destination,
+            }]
+            | [Add {
+                left: Source::Literal(0),
+                right: source,
+                destination,
+            }]
+            | [Sub {
+                left: source,
+                right: Source::Literal(0),
+                destination,
+            }] => Some(vec![Ld {
+                source: source.clone(),
+                destination: destination.clone(),
+            }]),
+            [AddW {
+                left: source,
+                right: Source::Literal(0),
+                destination,
+            }]
+            | [AddW {
+                left: Source::Literal(0),
+                right: source,
+                destination,
+            }]
+            | [SubW {
+                left: source,
+                right: Source::Literal(0),
+                destination,
+            }] => Some(vec![LdW {
+                source: source.clone(),
+                destination: destination.clone(),
+            }]),
+            _ => None,
+        }
+    }
+}
+
+/// `xor x, 0xff -> r`, `xor r, 0xff -> d` (a double bitwise negation, and its
+/// 16bit version), where `r` isn't read afterwards: `ld x -> d`.
+pub struct DoubleNot;
+
+impl Rule for DoubleNot {
+    fn window(&self) -> usize {
+        2
+    }
+
+    fn rewrite(&self, window: &[Statement], context: &Context<'_>) -> Option<Vec<Statement>> {
+        use Statement::{Ld, LdW, Xor, XorW};
+        match window {
+            [Xor {
+                left: source,
+                right: Source::Literal(0xff),
+                destination: Destination::Register(register),
+            }, Xor {
+                left: Source::Register(r),
+                right: Source::Literal(0xff),
+                destination,
+            }] if r == register
+                && !destination_reads_register(destination, *r)
+                && context.is_dead_byte(*r) =>
+            {
+                Some(vec![Ld {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                }])
+            }
+            [XorW {
+                left: source,
+                right: Source::Literal(0xffff),
+                destination: Destination::Register(register),
+            }, XorW {
+                left: Source::Register(r),
+                right: Source::Literal(0xffff),
+                destination,
+            }] if r == register
+                && !destination_reads_register(destination, *r)
+                && context.is_dead_word(*r) =>
+            {
+                Some(vec![LdW {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                }])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `ld x -> r`, `ld r -> y`, where `r` isn't read afterwards: `ld x -> y`.
+///
+/// Removes the temporary registers the compiler loads values into before
+/// storing them.
+pub struct ForwardLoad;
+
+impl Rule for ForwardLoad {
+    fn window(&self) -> usize {
+        2
+    }
+
+    fn rewrite(&self, window: &[Statement], context: &Context<'_>) -> Option<Vec<Statement>> {
+        use Statement::{Ld, LdW};
+        match window {
+            [Ld {
+                source,
+                destination: Destination::Register(register),
+            }, Ld {
+                source: Source::Register(r),
+                destination,
+            }] if r == register
+                && !destination_reads_register(destination, *r)
+                && context.is_dead_byte(*r) =>
+            {
+                Some(vec![Ld {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                }])
+            }
+            [LdW {
+                source,
+                destination: Destination::Register(register),
+            }, LdW {
+                source: Source::Register(r),
+                destination,
+            }] if r == register
+                && !destination_reads_register(destination, *r)
+                && context.is_dead_word(*r) =>
+            {
+                Some(vec![LdW {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                }])
+            }
+            _ => None,
+        }
+    }
+}
+
+// the source and the destination are the same register, or the same location
+// in memory. Absolute pointers are excluded (reading from, or writing to,
+// hardware registers may have side effects).
+fn same<T>(source: &Source<T>, destination: &Destination) -> bool {
+    match (source, destination) {
+        (Source::Register(s), Destination::Register(d)) => s == d,
+        (
+            Source::Pointer {
+                base: s,
+                offset: None,
+            },
+            Destination::Pointer {
+                base: d,
+                offset: None,
+            },
+        ) => s == d && !matches!(s, Pointer::Absolute(_)),
+        _ => false,
+    }
+}
+
+// the statement reads the register (of either width).
+fn reads_register(statement: &Statement, register: Register) -> bool {
+    reads(statement)
+        .iter()
+        .any(|read| matches!(read, Read::Register(r, _) if *r == register))
+}
+
+// the destination reads the register to compute the address it stores to.
+fn destination_reads_register(destination: &Destination, register: Register) -> bool {
+    let mut reads = Vec::new();
+    destination_reads(destination, &mut reads);
+    reads
+        .iter()
+        .any(|read| matches!(read, Read::Register(r, _) if *r == register))
+}
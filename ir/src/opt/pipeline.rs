@@ -0,0 +1,232 @@
+use super::{
+    const_fold, copy_propagation, cse, inline, licm, peephole::Peephole, simplify_branches,
+    strength_reduction,
+};
+use crate::{opcodes::Statement, ByteOrder, Ir};
+
+// routines of up to this many statements are inlined by `OptLevel::O2`.
+const INLINE_THRESHOLD: usize = 16;
+
+/// Optimization pass over the whole program, run by a [`Pipeline`].
+///
+/// The passes of this module are registered by the presets of [`OptLevel`].
+/// Custom passes implement this trait, and are added with [`Pipeline::pass`].
+pub trait IrPass<B: ByteOrder> {
+    /// Name of the pass, as reported by the [`PassStats`] of its runs.
+    fn name(&self) -> &str;
+
+    /// Run the pass.
+    ///
+    /// Returns true if any statement was rewritten.
+    fn run(&self, ir: &mut Ir<B>) -> bool;
+}
+
+impl<B: ByteOrder> IrPass<B> for Peephole {
+    fn name(&self) -> &str {
+        "peephole"
+    }
+
+    fn run(&self, ir: &mut Ir<B>) -> bool {
+        Self::run(self, ir)
+    }
+}
+
+// pass implemented by one of the functions of the module.
+struct Function<B: ByteOrder> {
+    name: &'static str,
+    run: fn(&mut Ir<B>) -> bool,
+}
+
+impl<B: ByteOrder> IrPass<B> for Function<B> {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn run(&self, ir: &mut Ir<B>) -> bool {
+        (self.run)(ir)
+    }
+}
+
+/// Preset of passes of a [`Pipeline`], like the `-O` flags of other
+/// compilers.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum OptLevel {
+    /// No passes.
+    #[default]
+    O0,
+
+    /// Passes local to basic blocks, run once: [`const_fold`], [`cse`],
+    /// [`copy_propagation`], [`simplify_branches`] and the
+    /// [`Peephole::standard`] rules.
+    O1,
+
+    /// The passes of [`OptLevel::O1`], along with [`inline`] (of routines of
+    /// up to 16 statements), [`strength_reduction`] and [`licm`], run twice so
+    /// every pass sees the output of the others.
+    O2,
+}
+
+/// Size of the program, before and after a pass.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct Stats {
+    /// Number of statements of all the routines.
+    pub statements: usize,
+
+    /// Number of jumps, conditional or not.
+    pub jumps: usize,
+
+    /// Number of calls, direct or not.
+    pub calls: usize,
+}
+
+impl Stats {
+    /// Count the statements of the program.
+    pub fn new<B: ByteOrder>(ir: &Ir<B>) -> Self {
+        let mut stats = Self::default();
+        for statement in ir.routines.iter().flat_map(|r| r.statements.iter()) {
+            stats.statements += 1;
+            match statement {
+                Statement::Jmp { .. } | Statement::JmpCmp { .. } | Statement::JmpCmpNot { .. } => {
+                    stats.jumps += 1
+                }
+                Statement::Call { .. } | Statement::CallIndirect { .. } => stats.calls += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+/// Statistics of a run of a pass.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PassStats {
+    /// Name of the pass ([`IrPass::name`]).
+    pub name: String,
+
+    /// Round of the pipeline the pass ran in, starting at 0.
+    pub round: usize,
+
+    /// Whether the pass rewrote any statement.
+    pub changed: bool,
+
+    /// Size of the program before the pass.
+    pub before: Stats,
+
+    /// Size of the program after the pass.
+    pub after: Stats,
+}
+
+/// Sequence of passes, run in the order they are added.
+///
+/// The sequence runs for a number of rounds (one by default), or until a round
+/// leaves the program unchanged.
+///
+/// ```
+/// use ir::{
+///     byteorder::NativeEndian,
+///     opt::{OptLevel, Pipeline},
+///     Ir,
+/// };
+///
+/// let text = "routine 0 - stack 0 args 0 return 0
+///     ld 2 -> r0
+///     add r0, 1 -> r1
+///     ld r1 -> [static 0]
+/// end";
+/// let mut ir: Ir<NativeEndian> = ir::parse_text(text).unwrap();
+///
+/// let stats = Pipeline::level(OptLevel::O1).run(&mut ir);
+/// assert!(stats.iter().any(|s| s.name == "const_fold" && s.changed));
+/// assert_eq!("ld 3 -> [static 0]", ir.main().statements[0].to_string());
+/// ```
+pub struct Pipeline<B: ByteOrder> {
+    passes: Vec<Box<dyn IrPass<B>>>,
+    rounds: usize,
+}
+
+impl<B: ByteOrder> Default for Pipeline<B> {
+    fn default() -> Self {
+        Self {
+            passes: Vec::new(),
+            rounds: 1,
+        }
+    }
+}
+
+impl<B: ByteOrder + 'static> Pipeline<B> {
+    /// Empty pipeline, of one round.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pipeline of the passes of the given level.
+    pub fn level(level: OptLevel) -> Self {
+        let pipeline = Self::new();
+        match level {
+            OptLevel::O0 => pipeline,
+            OptLevel::O1 => pipeline
+                .function("const_fold", const_fold)
+                .function("cse", cse)
+                .function("copy_propagation", copy_propagation)
+                .function("simplify_branches", simplify_branches)
+                .pass(Peephole::standard()),
+            OptLevel::O2 => pipeline
+                .function("inline", |ir| inline(ir, INLINE_THRESHOLD))
+                .function("const_fold", const_fold)
+                .function("cse", cse)
+                .function("strength_reduction", strength_reduction)
+                .function("licm", licm)
+                .function("copy_propagation", copy_propagation)
+                .function("simplify_branches", simplify_branches)
+                .pass(Peephole::standard())
+                .rounds(2),
+        }
+    }
+
+    /// Add a pass to the end of the pipeline.
+    pub fn pass<P: IrPass<B> + 'static>(mut self, pass: P) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Set the maximum number of times the passes are run.
+    pub fn rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Names of the passes, in the order they run.
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Run the passes over the program.
+    ///
+    /// Returns the statistics of every run of a pass, in the order they ran.
+    pub fn run(&self, ir: &mut Ir<B>) -> Vec<PassStats> {
+        let mut stats = Vec::new();
+        for round in 0..self.rounds {
+            let mut changed = false;
+            for pass in &self.passes {
+                let before = Stats::new(ir);
+                let pass_changed = pass.run(ir);
+                changed |= pass_changed;
+                stats.push(PassStats {
+                    name: pass.name().to_string(),
+                    round,
+                    changed: pass_changed,
+                    before,
+                    after: Stats::new(ir),
+                });
+            }
+            if !changed {
+                break;
+            }
+        }
+        stats
+    }
+
+    fn function(self, name: &'static str, run: fn(&mut Ir<B>) -> bool) -> Self {
+        self.pass(Function { name, run })
+    }
+}
@@ -0,0 +1,286 @@
+use super::{
+    destination, destination_mut, liveness, reads, shift_stack, visit_sources, Read, Reg, Value,
+    Visit,
+};
+use crate::{
+    compile::{optimize::delete_nops, NOP_UNREACHABLE},
+    opcodes::{Destination, Pointer, Register, Source, Statement},
+    ByteOrder, Ir, Routine,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Register allocation.
+///
+/// The compiler gives every intermediate value its own virtual register, and
+/// maps them to as few registers as it can, but large expressions and routines
+/// may still need more registers than the target has. This pass maps the
+/// registers of each routine to at most `registers` registers of each width,
+/// by coloring their interference graph: registers whose values are never
+/// alive at the same time share the same number, copies between registers are
+/// removed when both get the same number, and the registers that don't fit
+/// are spilled to stack slots (the ones with the fewest reads and writes
+/// first).
+///
+/// The operands of the statements may be in memory, so spilled registers are
+/// replaced by their slot (`add r0, r20 -> r21` becomes
+/// `add r0, [stack 2] -> [stack 3]`). The slots go after the arguments of the
+/// routine, so the rest of its stack frame (and the frames of its calls) is
+/// moved by the size of the slots.
+///
+/// Returns true if any register was renumbered or spilled.
+pub fn allocate_registers<B: ByteOrder>(ir: &mut Ir<B>, registers: usize) -> bool {
+    let mut allocated = false;
+    for routine in ir.routines.iter_mut() {
+        allocated |= allocate_routine(routine, registers);
+    }
+    allocated
+}
+
+// maps the registers of the routine to at most `registers` registers of each
+// width, spilling the rest. Returns true if any statement changed.
+pub(crate) fn allocate_routine(routine: &mut Routine, registers: usize) -> bool {
+    let graph = Graph::new(&routine.statements);
+    let allocation = graph.color(registers);
+    let renumbered = allocation
+        .colors
+        .iter()
+        .any(|(register, color)| register.0 != *color);
+    if !renumbered && allocation.spilled.is_empty() {
+        return false;
+    }
+    allocation.rewrite(routine);
+
+    // copies between registers that were given the same number
+    let mut copies = false;
+    for statement in routine.statements.iter_mut() {
+        if let Some((from, register)) = copy(statement) {
+            if from == register.0 {
+                *statement = Statement::Nop(NOP_UNREACHABLE);
+                copies = true;
+            }
+        }
+    }
+    if copies {
+        delete_nops(&mut routine.statements, &mut routine.spans);
+    }
+    true
+}
+
+// interference graph of the registers of a routine.
+struct Graph {
+    // registers of the routine, in the order they first appear
+    registers: Vec<Reg>,
+    // registers of the same width whose values are alive at the same time
+    interference: HashMap<Reg, HashSet<Reg>>,
+    // registers copied to or from each register
+    copies: HashMap<Reg, Vec<Reg>>,
+    // reads and writes of each register
+    uses: HashMap<Reg, usize>,
+}
+
+impl Graph {
+    fn new(statements: &[Statement]) -> Self {
+        let mut graph = Self {
+            registers: Vec::new(),
+            interference: HashMap::new(),
+            copies: HashMap::new(),
+            uses: HashMap::new(),
+        };
+        for statement in statements {
+            let read = reads(statement).into_iter().filter_map(|read| match read {
+                Read::Register(register, word) => Some((register, word)),
+                _ => None,
+            });
+            for register in read.chain(written(statement)) {
+                let uses = graph.uses.entry(register).or_insert(0);
+                if *uses == 0 {
+                    graph.registers.push(register);
+                }
+                *uses += 1;
+            }
+        }
+
+        let (live_in, live_out) = liveness(statements);
+        // registers read before they are written
+        if let Some(live) = live_in.first() {
+            for a in live {
+                for b in live {
+                    graph.interfere(*a, *b);
+                }
+            }
+        }
+        for (statement, live) in statements.iter().zip(&live_out) {
+            let register = match written(statement) {
+                Some(register) => register,
+                None => continue,
+            };
+            // a copy and the register it copies hold the same value, so they
+            // only interfere if either of them is written afterwards
+            let copied = copy(statement).map(|(from, _)| (from, register.1));
+            if let Some(from) = copied {
+                graph.copies.entry(register).or_default().push(from);
+                graph.copies.entry(from).or_default().push(register);
+            }
+            for other in live {
+                if Some(*other) != copied {
+                    graph.interfere(register, *other);
+                }
+            }
+        }
+        graph
+    }
+
+    fn interfere(&mut self, a: Reg, b: Reg) {
+        if a != b && a.1 == b.1 {
+            self.interference.entry(a).or_default().insert(b);
+            self.interference.entry(b).or_default().insert(a);
+        }
+    }
+
+    fn neighbors(&self, register: &Reg) -> impl Iterator<Item = &Reg> {
+        self.interference.get(register).into_iter().flatten()
+    }
+
+    // colors the graph with at most `registers` colors (Chaitin-Briggs): the
+    // registers with fewer neighbors than colors are removed from the graph
+    // until it's empty, and colored in the reverse order, so there is always
+    // a color left for them. When every register has too many neighbors, the
+    // one that is cheapest to spill (fewest uses per neighbor) is removed, and
+    // spilled only if its neighbors end up taking every color.
+    fn color(&self, registers: usize) -> Allocation {
+        let mut degree: HashMap<Reg, usize> = self
+            .registers
+            .iter()
+            .map(|register| (*register, self.neighbors(register).count()))
+            .collect();
+        let mut remaining = self.registers.clone();
+        let mut stack = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let index = remaining
+                .iter()
+                .rposition(|register| degree[register] < registers)
+                .unwrap_or_else(|| {
+                    let cost = |index: &usize| {
+                        let register = &remaining[*index];
+                        (self.uses[register], degree[register])
+                    };
+                    (0..remaining.len())
+                        .rev()
+                        .min_by(|a, b| {
+                            let (a, b) = (cost(a), cost(b));
+                            (a.0 * b.1).cmp(&(b.0 * a.1))
+                        })
+                        .unwrap()
+                });
+            let register = remaining.remove(index);
+            for neighbor in self.neighbors(&register) {
+                if let Some(degree) = degree.get_mut(neighbor) {
+                    *degree = degree.saturating_sub(1);
+                }
+            }
+            degree.remove(&register);
+            stack.push(register);
+        }
+
+        let mut allocation = Allocation::default();
+        while let Some(register) = stack.pop() {
+            let used: HashSet<_> = self
+                .neighbors(&register)
+                .filter_map(|other| allocation.colors.get(other))
+                .collect();
+            // the color of a copy, so the copy can be removed
+            let copy = self
+                .copies
+                .get(&register)
+                .into_iter()
+                .flatten()
+                .filter_map(|other| allocation.colors.get(other))
+                .find(|color| !used.contains(color));
+            match copy
+                .copied()
+                .or_else(|| (0..registers).find(|color| !used.contains(color)))
+            {
+                Some(color) => {
+                    allocation.colors.insert(register, color);
+                }
+                None => allocation.spilled.push(register),
+            }
+        }
+        allocation
+    }
+}
+
+// register written by a statement.
+fn written(statement: &Statement) -> Option<Reg> {
+    match destination(statement) {
+        Some((Destination::Register(register), word)) => Some((*register, word)),
+        _ => None,
+    }
+}
+
+// copy between registers: the register copied from, and the copy.
+fn copy(statement: &Statement) -> Option<(Register, Reg)> {
+    match statement {
+        Statement::Ld {
+            source: Source::Register(from),
+            destination: Destination::Register(register),
+        } => Some((*from, (*register, false))),
+        Statement::LdW {
+            source: Source::Register(from),
+            destination: Destination::Register(register),
+        } => Some((*from, (*register, true))),
+        _ => None,
+    }
+}
+
+// registers assigned to each virtual register, and the slots of the spilled
+// ones.
+#[derive(Default)]
+struct Allocation {
+    colors: HashMap<Reg, Register>,
+    spilled: Vec<Reg>,
+    slots: HashMap<Reg, u16>,
+}
+
+impl Allocation {
+    // replaces the registers of the routine with their color, or their slot.
+    // The slots go after the arguments.
+    fn rewrite(mut self, routine: &mut Routine) {
+        let mut size = 0;
+        for register in &self.spilled {
+            self.slots.insert(*register, routine.args_size + size);
+            size += if register.1 { 2 } else { 1 };
+        }
+        shift_stack(&mut routine.statements, routine.args_size, size);
+        routine.stack_size += size;
+
+        for statement in routine.statements.iter_mut() {
+            visit_sources(statement, &mut self);
+            if let Some(register) = written(statement) {
+                *destination_mut(statement).unwrap() = match self.slots.get(&register) {
+                    Some(slot) => Destination::Pointer {
+                        base: Pointer::Stack(*slot),
+                        offset: None,
+                    },
+                    None => Destination::Register(self.colors[&register]),
+                };
+            }
+        }
+    }
+}
+
+impl Visit for Allocation {
+    fn source<T: Value>(&mut self, source: &mut Source<T>) {
+        self.address(source);
+        if let Source::Register(register) = source {
+            let register = (*register, T::WORD);
+            *source = match self.slots.get(&register) {
+                Some(slot) => Source::Pointer {
+                    base: Pointer::Stack(*slot),
+                    offset: None,
+                },
+                None => Source::Register(self.colors[&register]),
+            };
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use super::{destination, destination_mut, reads, splice, Read};
+use crate::{
+    opcodes::{Destination, Register, Source, Statement},
+    ByteOrder, Ir, Overflow,
+};
+
+// multiplications by literals with more bits set are left as they are.
+const MAX_BITS: u32 = 3;
+
+/// Strength reduction of multiplications by literals.
+///
+/// The LR35902 has no multiplication instruction, so `mul` and `mul_w` are
+/// compiled into loops. Multiplications by a literal with up to three bits set
+/// are replaced by shifts and additions (`mul r0, 10 -> r1` becomes
+/// `left_shift r0, 2 -> r2`, `add r2, r0 -> r2` and `left_shift r2, 1 -> r1`).
+///
+/// Multiplications by 0 and 1 are replaced by loads. The others are only
+/// rewritten with [`Overflow::Wrapping`] semantics, since shifts and additions
+/// don't overflow like the multiplication does.
+///
+/// Returns true if any statement was rewritten.
+pub fn strength_reduction<B: ByteOrder>(ir: &mut Ir<B>) -> bool {
+    let wrapping = ir.overflow == Overflow::Wrapping;
+    let mut reduced = false;
+    for routine in ir.routines.iter_mut() {
+        // registers past the ones of the routine hold the intermediate values
+        let mut temporary = [0, 0];
+        for statement in &routine.statements {
+            let registers = reads(statement).into_iter().filter_map(|read| match read {
+                Read::Register(register, word) => Some((register, word)),
+                _ => None,
+            });
+            let written = match destination(statement) {
+                Some((Destination::Register(register), word)) => Some((*register, word)),
+                _ => None,
+            };
+            for (register, word) in registers.chain(written) {
+                let temporary = &mut temporary[word as usize];
+                *temporary = (*temporary).max(register + 1);
+            }
+        }
+        let mut index = 0;
+        while index < routine.statements.len() {
+            let statements = match &routine.statements[index] {
+                Statement::Mul {
+                    left,
+                    right,
+                    destination,
+                } => reduce(left, right, destination, wrapping, temporary[0]),
+                Statement::MulW {
+                    left,
+                    right,
+                    destination,
+                } => reduce(left, right, destination, wrapping, temporary[1]),
+                _ => None,
+            };
+            match statements {
+                Some(statements) => {
+                    let len = statements.len();
+                    if splice(routine, index, statements, Vec::new()) {
+                        reduced = true;
+                        index += len;
+                    } else {
+                        index += 1;
+                    }
+                }
+                None => index += 1,
+            }
+        }
+    }
+    reduced
+}
+
+// shifts and additions that multiply the non-literal operand by the literal
+// one. The first temporary register holds the intermediate results, and the
+// second the operand, if it isn't a register.
+fn reduce<T: Width>(
+    left: &Source<T>,
+    right: &Source<T>,
+    destination: &Destination,
+    wrapping: bool,
+    temporary: Register,
+) -> Option<Vec<Statement>> {
+    let (operand, literal) = match (left, right) {
+        (Source::Literal(_), Source::Literal(_)) => return None,
+        (operand, Source::Literal(literal)) | (Source::Literal(literal), operand) => {
+            (operand, (*literal).into())
+        }
+        _ => return None,
+    };
+    let destination = destination.clone();
+    match literal {
+        0 => return Some(vec![T::ld(Source::Literal(T::default()), destination)]),
+        1 => return Some(vec![T::ld(operand.clone(), destination)]),
+        _ if !wrapping || literal.count_ones() > MAX_BITS => return None,
+        _ if literal.is_power_of_two() => {
+            let shift = literal.trailing_zeros() as u8;
+            return Some(vec![T::left_shift(operand.clone(), shift, destination)]);
+        }
+        _ => {}
+    }
+
+    // x * 0b1010 == ((x << 2) + x) << 1
+    let mut statements = Vec::new();
+    let operand = match operand {
+        Source::Register(_) => operand.clone(),
+        operand => {
+            let register = temporary + 1;
+            statements.push(T::ld(operand.clone(), Destination::Register(register)));
+            Source::Register(register)
+        }
+    };
+    let bits: Vec<_> = (0..16)
+        .rev()
+        .filter(|bit| literal & (1 << bit) != 0)
+        .collect();
+    let mut value = operand.clone();
+    for bits in bits.windows(2) {
+        let shift = (bits[0] - bits[1]) as u8;
+        statements.push(T::left_shift(
+            value,
+            shift,
+            Destination::Register(temporary),
+        ));
+        statements.push(T::add(
+            Source::Register(temporary),
+            operand.clone(),
+            Destination::Register(temporary),
+        ));
+        value = Source::Register(temporary);
+    }
+    match bits.last() {
+        Some(0) => *destination_mut(statements.last_mut()?)? = destination,
+        Some(bit) => statements.push(T::left_shift(value, *bit as u8, destination)),
+        None => {}
+    }
+    Some(statements)
+}
+
+// statements of the width of the multiplication.
+trait Width: Copy + Default + Into<u16> {
+    fn ld(source: Source<Self>, destination: Destination) -> Statement;
+
+    fn left_shift(left: Source<Self>, shift: u8, destination: Destination) -> Statement;
+
+    fn add(left: Source<Self>, right: Source<Self>, destination: Destination) -> Statement;
+}
+
+impl Width for u8 {
+    fn ld(source: Source<Self>, destination: Destination) -> Statement {
+        Statement::Ld {
+            source,
+            destination,
+        }
+    }
+
+    fn left_shift(left: Source<Self>, shift: u8, destination: Destination) -> Statement {
+        Statement::LeftShift {
+            left,
+            right: Source::Literal(shift),
+            destination,
+        }
+    }
+
+    fn add(left: Source<Self>, right: Source<Self>, destination: Destination) -> Statement {
+        Statement::Add {
+            left,
+            right,
+            destination,
+        }
+    }
+}
+
+impl Width for u16 {
+    fn ld(source: Source<Self>, destination: Destination) -> Statement {
+        Statement::LdW {
+            source,
+            destination,
+        }
+    }
+
+    fn left_shift(left: Source<Self>, shift: u8, destination: Destination) -> Statement {
+        Statement::LeftShiftW {
+            left,
+            right: Source::Literal(shift),
+            destination,
+        }
+    }
+
+    fn add(left: Source<Self>, right: Source<Self>, destination: Destination) -> Statement {
+        Statement::AddW {
+            left,
+            right,
+            destination,
+        }
+    }
+}
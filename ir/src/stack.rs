@@ -0,0 +1,220 @@
+//! Stack usage analysis.
+//!
+//! Stack frames have a fixed size ([`Routine::stack_size`]), and the frame of
+//! a called routine starts within the frame of the caller, at the start of the
+//! `range` of the [`Call`](Statement::Call). The stack used by a routine is the
+//! largest of its own frame and the frames of the chains of calls it makes.
+//! Tail jumps ([`Location::Routine`]) are calls whose frame starts at the start
+//! of the frame of the routine.
+//!
+//! Recursion makes the stack usage unbounded, so recursive programs can't be
+//! given a static stack, and overflow (or corrupt memory) on the target
+//! hardware.
+use crate::{
+    opcodes::{Location, Statement},
+    opt::location,
+    ByteOrder, Ir, Routine,
+};
+use std::{collections::HashMap, fmt};
+
+/// Deepest chain of calls from a routine.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Depth {
+    /// Bytes of stack used by the chain, from the start of the frame of its
+    /// first routine.
+    pub size: u32,
+
+    /// Routines of the chain, starting with the analyzed one.
+    pub chain: Vec<usize>,
+
+    /// Whether any of the reachable routines calls through a function pointer.
+    /// The stack used by these calls can't be known statically, and isn't
+    /// accounted for in the `size`.
+    pub indirect: bool,
+}
+
+/// Cycle in the call graph of a program (direct or mutual recursion).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Recursion {
+    /// Names of the routines of the cycle, in call order. The last one calls
+    /// the first one.
+    pub cycle: Vec<String>,
+}
+
+impl fmt::Display for Recursion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recursive call chain: ")?;
+        for name in &self.cycle {
+            write!(f, "{} -> ", name)?;
+        }
+        write!(f, "{}", self.cycle[0])
+    }
+}
+
+impl std::error::Error for Recursion {}
+
+impl<B: ByteOrder> Ir<B> {
+    /// Maximum stack depth of a routine, and the call chain that reaches it.
+    ///
+    /// Fails if the routine can reach a recursive call.
+    pub fn stack_depth(&self, routine: usize) -> Result<Depth, Recursion> {
+        depth(
+            &self.routines,
+            routine,
+            &mut Vec::new(),
+            &mut HashMap::new(),
+        )
+    }
+
+    /// Maximum stack depth of the entry point, followed by the ones of the
+    /// interrupt handlers of the program.
+    ///
+    /// Fails if any of them can reach a recursive call.
+    pub fn stack_report(&self) -> Result<Vec<Depth>, Recursion> {
+        let handlers = &self.handlers;
+        let mut memo = HashMap::new();
+        Some(handlers.main)
+            .into_iter()
+            .chain(handlers.vblank)
+            .chain(handlers.lcd_stat)
+            .chain(handlers.timer)
+            .chain(handlers.serial)
+            .chain(handlers.joypad)
+            .map(|routine| depth(&self.routines, routine, &mut Vec::new(), &mut memo))
+            .collect()
+    }
+}
+
+// depth of a routine, given the routines being visited (the current call
+// chain), and the depths computed so far.
+fn depth(
+    routines: &[Routine],
+    routine: usize,
+    visiting: &mut Vec<usize>,
+    memo: &mut HashMap<usize, Depth>,
+) -> Result<Depth, Recursion> {
+    if let Some(depth) = memo.get(&routine) {
+        return Ok(depth.clone());
+    }
+    if let Some(i) = visiting.iter().position(|r| *r == routine) {
+        let cycle = visiting[i..]
+            .iter()
+            .map(|&r| match &routines[r].debug_name {
+                Some(name) => name.clone(),
+                None => format!("#{}", r),
+            })
+            .collect();
+        return Err(Recursion { cycle });
+    }
+    visiting.push(routine);
+    let mut result = Depth {
+        size: u32::from(routines[routine].stack_size),
+        chain: vec![routine],
+        indirect: false,
+    };
+    for statement in &routines[routine].statements {
+        let (callee, start) = match (statement, location(statement)) {
+            (Statement::Call { routine, range }, _) => (*routine, range.start),
+            (_, Some(Location::Routine(callee))) => (*callee, 0),
+            (Statement::CallIndirect { .. }, _) => {
+                result.indirect = true;
+                continue;
+            }
+            _ => continue,
+        };
+        let callee = depth(routines, callee, visiting, memo)?;
+        result.indirect |= callee.indirect;
+        // on ties, the longest chain is reported
+        let size = u32::from(start) + callee.size;
+        let longer = callee.chain.len() >= result.chain.len();
+        if size > result.size || (size == result.size && longer) {
+            result.size = size;
+            result.chain = Some(routine).into_iter().chain(callee.chain).collect();
+        }
+    }
+    visiting.pop();
+    memo.insert(routine, result.clone());
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{depth, Recursion};
+    use crate::{
+        opcodes::{Location, Statement},
+        Inline, Routine,
+    };
+    use std::collections::HashMap;
+
+    fn routine(name: &str, stack_size: u16, calls: &[(usize, u16)]) -> Routine {
+        Routine {
+            debug_name: Some(name.to_string()),
+            stack_size,
+            args_size: 0,
+            return_size: 0,
+            bank: None,
+            inline: Inline::Auto,
+            statements: calls
+                .iter()
+                .map(|&(routine, start)| Statement::Call {
+                    routine,
+                    range: start..,
+                })
+                .collect(),
+            spans: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let routines = [
+            routine("leaf", 4, &[]),
+            routine("small", 1, &[]),
+            routine("mid", 2, &[(0, 2), (1, 2)]),
+            routine("main", 8, &[(2, 3), (1, 7)]),
+        ];
+        let depth = depth(&routines, 3, &mut Vec::new(), &mut HashMap::new()).unwrap();
+        // main (3 bytes up to the call) + mid (2 bytes up to the call) + leaf
+        assert_eq!(9, depth.size);
+        assert_eq!(vec![3, 2, 0], depth.chain);
+        assert!(!depth.indirect);
+    }
+
+    #[test]
+    fn frame() {
+        let routines = [routine("leaf", 1, &[]), routine("main", 8, &[(0, 2)])];
+        let depth = depth(&routines, 1, &mut Vec::new(), &mut HashMap::new()).unwrap();
+        assert_eq!(8, depth.size);
+        assert_eq!(vec![1], depth.chain);
+    }
+
+    #[test]
+    fn tail_jump() {
+        let mut routines = [
+            routine("leaf", 4, &[]),
+            routine("mid", 2, &[]),
+            routine("main", 5, &[(1, 3)]),
+        ];
+        routines[1].statements.push(Statement::Jmp {
+            location: Location::Routine(0),
+        });
+        let depth = depth(&routines, 2, &mut Vec::new(), &mut HashMap::new()).unwrap();
+        // the frame of leaf takes the place of the frame of mid
+        assert_eq!(7, depth.size);
+        assert_eq!(vec![2, 1, 0], depth.chain);
+    }
+
+    #[test]
+    fn recursion() {
+        let routines = [
+            routine("a", 1, &[(1, 0)]),
+            routine("b", 1, &[(2, 0)]),
+            routine("c", 1, &[(0, 0)]),
+            routine("main", 1, &[(0, 0)]),
+        ];
+        let error = depth(&routines, 3, &mut Vec::new(), &mut HashMap::new()).unwrap_err();
+        let cycle = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(Recursion { cycle }, error);
+        assert_eq!("Recursive call chain: a -> b -> c -> a", error.to_string());
+    }
+}
@@ -0,0 +1,176 @@
+use ir::{
+    analysis::{Cfg, Liveness, ReachingDefinitions, Slot},
+    byteorder::NativeEndian,
+    opcodes::Statement,
+    parse_text, Ir,
+};
+
+// the statements of the main routine.
+fn statements(statements: &str) -> Vec<Statement> {
+    let text = format!("routine 0 - stack 4 args 0 return 0\n{}\nend", statements);
+    let ir: Ir<NativeEndian> = parse_text(&text).unwrap();
+    ir.main().statements.clone()
+}
+
+// the slots alive after each statement.
+fn live_out(liveness: &Liveness, len: usize) -> Vec<Vec<Slot>> {
+    (0..len)
+        .map(|index| liveness.live_out(index).iter().copied().collect())
+        .collect()
+}
+
+#[test]
+fn cfg() {
+    let statements = statements(
+        "jmp_cmp 1, r0
+         ld 1 -> r1
+         jmp -3
+         stop success",
+    );
+    let cfg = Cfg::new(&statements);
+    assert_eq!(4, cfg.len());
+    assert_eq!(&[1, 2], cfg.successors(0));
+    assert_eq!(&[0], cfg.successors(2));
+    assert!(cfg.successors(3).is_empty());
+    assert_eq!(&[2], cfg.predecessors(0));
+    assert_eq!(&[0, 1], cfg.predecessors(2));
+    assert!(cfg.predecessors(3).is_empty());
+}
+
+#[test]
+fn cfg_absolute() {
+    let statements = statements(
+        "jmp absolute 2
+         ld 1 -> r1
+         jmp_cmp absolute 0, r0
+         stop success",
+    );
+    let cfg = Cfg::new(&statements);
+    assert_eq!(&[2], cfg.successors(0));
+    assert_eq!(&[0, 3], cfg.successors(2));
+    assert!(cfg.predecessors(1).is_empty());
+}
+
+#[test]
+fn liveness_registers() {
+    let statements = statements(
+        "ld 1 -> r0
+         ld_w 2 -> r0
+         add r0, 1 -> r1
+         add_w r0, 1 -> r0
+         ld r1 -> [static 0]
+         ld_w r0 -> [static 1]
+         stop success",
+    );
+    let liveness = Liveness::new(&statements);
+    use Slot::{Register as R, RegisterW as W};
+    assert_eq!(
+        vec![
+            vec![R(0)],
+            vec![R(0), W(0)],
+            vec![R(1), W(0)],
+            vec![R(1), W(0)],
+            vec![W(0)],
+            vec![],
+            vec![],
+        ],
+        live_out(&liveness, statements.len())
+    );
+    assert!(liveness.live_in(0).is_empty());
+}
+
+#[test]
+fn liveness_stack() {
+    let statements = statements(
+        "ld_w 1 -> [stack 0]
+         ld 2 -> [stack 2]
+         ld [stack 1] -> [static 0]
+         ld 3 -> [stack 0]
+         stop success",
+    );
+    let liveness = Liveness::new(&statements);
+    // the stack byte written by the last store is never read
+    assert_eq!(
+        vec![
+            vec![Slot::Stack(1)],
+            vec![Slot::Stack(1)],
+            vec![],
+            vec![],
+            vec![],
+        ],
+        live_out(&liveness, statements.len())
+    );
+}
+
+#[test]
+fn liveness_frame() {
+    // the byte read through a dynamic offset could be any byte of the frame
+    let statements = statements(
+        "ld 1 -> [stack 0]
+         ld 2 -> [stack 1]
+         ld [stack 0 + r0] -> [static 0]
+         stop success",
+    );
+    let liveness = Liveness::new(&statements);
+    assert_eq!(
+        vec![
+            vec![Slot::Register(0), Slot::Stack(0)],
+            vec![Slot::Register(0), Slot::Stack(0), Slot::Stack(1)],
+            vec![],
+            vec![],
+        ],
+        live_out(&liveness, statements.len())
+    );
+}
+
+#[test]
+fn liveness_loop() {
+    let statements = statements(
+        "ld 0 -> r0
+         ld r0 -> [static 0]
+         inc r0 -> r0
+         jmp_cmp -3, r1
+         stop success",
+    );
+    let liveness = Liveness::new(&statements);
+    assert!(liveness.live_out(3).contains(&Slot::Register(0)));
+    assert!(liveness.live_in(0).contains(&Slot::Register(1)));
+    assert!(!liveness.live_in(0).contains(&Slot::Register(0)));
+}
+
+#[test]
+fn reaching_definitions() {
+    let statements = statements(
+        "ld 1 -> r0
+         jmp_cmp 1, r1
+         ld 2 -> r0
+         ld r0 -> [stack 0]
+         ld 3 -> r0
+         ld [stack 0] -> [static 0]
+         stop success",
+    );
+    let definitions = ReachingDefinitions::new(&statements);
+    let reaching = |index, slot| -> Vec<usize> { definitions.definitions(index, slot).collect() };
+    // either branch
+    assert_eq!(vec![0, 2], reaching(3, Slot::Register(0)));
+    assert_eq!(vec![4], reaching(5, Slot::Register(0)));
+    assert_eq!(vec![3], reaching(5, Slot::Stack(0)));
+    assert!(reaching(0, Slot::Register(0)).is_empty());
+    assert!(definitions.reaching(0).is_empty());
+}
+
+#[test]
+fn reaching_definitions_clobbered() {
+    // the store through a pointer may or may not overwrite the stack
+    let statements = statements(
+        "ld 1 -> [stack 0]
+         ld 2 -> *r0
+         ld [stack 0] -> [static 0]
+         ld 3 -> [stack 0]
+         stop success",
+    );
+    let definitions = ReachingDefinitions::new(&statements);
+    let reaching = |index, slot| -> Vec<usize> { definitions.definitions(index, slot).collect() };
+    assert_eq!(vec![0, 1], reaching(2, Slot::Stack(0)));
+    assert_eq!(vec![3], reaching(4, Slot::Stack(0)));
+}
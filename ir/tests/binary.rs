@@ -0,0 +1,113 @@
+use ir::{
+    binary::{DecodeError, VERSION},
+    byteorder::{BigEndian, LittleEndian, NativeEndian},
+    parse_text,
+    parser::parse,
+    Ir,
+};
+use vm::{Machine, Opts};
+
+fn ir<B: ir::byteorder::ByteOrder>(input: &str) -> Ir<B> {
+    let ast = parse(input).unwrap();
+    let mut ir = Ir::new(&ast);
+    ir.optimize();
+    ir
+}
+
+const PROGRAM: &str = r#"
+    static@0xff40 volatile LCDC:u8
+    static A:u8
+    static W:u16
+    static P:&u8
+    static S:i8
+    const C:[u8 3] = [1 2 3]
+    #[inline] fn f(x:u8):u8 { return (+ x 1) }
+    fn@vblank on_vblank { (= LCDC 0) }
+    let i:u8 = 0
+    loop {
+        if (== i 3) { break }
+        (= A (+ A i))
+        (= i (+ i 1))
+    }
+    (= W (+ W 300))
+    (= P @A)
+    (= A (+ *P ([2] C)))
+    (f 4)
+    if (< S 0) { (= S (/ S -2)) }
+    match A {
+        0 { (= A 1) }
+        1 { (= A 2) }
+        2 { (= A 3) }
+        3..6 { (= A 0) }
+    }
+"#;
+
+#[test]
+fn round_trip() {
+    let native: Ir<NativeEndian> = ir(PROGRAM);
+    assert_eq!(native, Ir::from_bytes(&native.to_bytes()).unwrap());
+    let big: Ir<BigEndian> = ir(PROGRAM);
+    assert_eq!(big, Ir::from_bytes(&big.to_bytes()).unwrap());
+}
+
+#[test]
+fn locations() {
+    let text = "routine 0 - stack 0 args 0 return 0
+            jmp_cmp absolute 2, r0
+            jmp routine 1
+            stop success
+        end
+        routine 1 - stack 0 args 0 return 0
+            ret
+        end";
+    let ir: Ir<LittleEndian> = parse_text(text).unwrap();
+    assert_eq!(ir, Ir::from_bytes(&ir.to_bytes()).unwrap());
+}
+
+#[test]
+fn run() {
+    let compiled: Ir<NativeEndian> = ir("static A:u8\nstatic B:u8 = 4\n(= A (+ B 3))");
+    let loaded = Ir::from_bytes(&compiled.to_bytes()).unwrap();
+    let memory = Machine::<NativeEndian>::new(&loaded, Opts::default()).run();
+    assert_eq!([7, 4], memory.static_[..2]);
+}
+
+#[test]
+fn errors() {
+    let bytes = ir::<LittleEndian>(PROGRAM).to_bytes();
+
+    assert_eq!(
+        Err(DecodeError::Magic),
+        Ir::<LittleEndian>::from_bytes(b"GG")
+    );
+    assert_eq!(
+        Err(DecodeError::ByteOrder),
+        Ir::<BigEndian>::from_bytes(&bytes)
+    );
+
+    let mut version = bytes.clone();
+    version[6] = VERSION as u8 + 1;
+    assert_eq!(
+        Err(DecodeError::Version(VERSION + 1)),
+        Ir::<LittleEndian>::from_bytes(&version)
+    );
+
+    for len in 4..bytes.len() {
+        assert_eq!(
+            Err(DecodeError::Eof),
+            Ir::<LittleEndian>::from_bytes(&bytes[..len])
+        );
+    }
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Err(DecodeError::TrailingBytes),
+        Ir::<LittleEndian>::from_bytes(&trailing)
+    );
+
+    // the overflow behavior is the last byte
+    let mut tag = bytes;
+    *tag.last_mut().unwrap() = 7;
+    let error = Ir::<LittleEndian>::from_bytes(&tag).unwrap_err();
+    assert_eq!("Invalid overflow tag 7", error.to_string());
+}
@@ -0,0 +1,76 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Source, Statement},
+    parser::parse,
+    Ir,
+};
+
+fn main_statements(input: &str) -> Vec<Statement> {
+    let ast = parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    ir.routines[ir.handlers.main].statements.clone()
+}
+
+#[test]
+fn memory() {
+    let statements = main_statements(
+        r#"
+    static A:[u8 4]
+    static B:[u8 4]
+    (builtin::memcpy @B @A 4)
+    (builtin::memset @A 0xff 4)
+    "#,
+    );
+    // addresses are loaded into registers
+    assert!(statements.iter().any(|s| matches!(
+        s,
+        Statement::Memcpy {
+            source: Source::Register(_),
+            destination: Source::Register(_),
+            len: Source::Literal(4),
+        }
+    )));
+    assert!(statements.iter().any(|s| matches!(
+        s,
+        Statement::Memset {
+            value: Source::Literal(0xff),
+            destination: Source::Register(_),
+            len: Source::Literal(4),
+        }
+    )));
+    // builtins aren't routine calls
+    assert!(!statements
+        .iter()
+        .any(|s| matches!(s, Statement::Call { .. })));
+}
+
+#[test]
+fn bytes() {
+    let statements = main_statements(
+        r#"
+    static A:u8
+    (= A (builtin::swap_nibbles (builtin::bcd_adjust A)))
+    "#,
+    );
+    let bcd = statements
+        .iter()
+        .position(|s| matches!(s, Statement::BcdAdjust { .. }))
+        .unwrap();
+    let swap = statements
+        .iter()
+        .position(|s| matches!(s, Statement::SwapNibbles { .. }))
+        .unwrap();
+    assert!(bcd < swap);
+}
+
+#[test]
+fn interrupts() {
+    let statements = main_statements("(builtin::enable_interrupts)\n(builtin::halt)");
+    assert_eq!(
+        vec![Statement::EnableInterrupts, Statement::Halt],
+        statements
+            .into_iter()
+            .filter(|s| matches!(s, Statement::EnableInterrupts | Statement::Halt))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,222 @@
+use ir::{byteorder::NativeEndian, parser::parse, Ir};
+
+fn _test_const(input: &str, gt: &[u8]) {
+    let ast = parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let result = &ir.const_[..gt.len()];
+    assert_eq!(gt, result);
+}
+
+#[test]
+fn test_const() {
+    _test_const(
+        r#"
+    const s0:u8 = 0
+    const s1:u8 = 1
+    const s2:u8 = 2
+    {
+        const s3:u8 = 3
+        const s4:u8 = 4
+        const s5:u8 = 5
+        {
+            const s6:u8 = 6
+            const s7:u8 = 7
+            {
+                const s8:u8 = 8
+            }
+            const s9:u8 = 9
+        }
+        const s10:u8 = 10
+    }
+    const s11:u8 = 11
+    {
+        const s12:u8 = 12
+        const s13:u8 = 13
+        {
+            const s14:u8 = 14
+        }
+        const s15:u8 = 15
+    }
+    "#,
+        (0..=15).collect::<Vec<_>>().as_ref(),
+    )
+}
+
+#[test]
+fn test_const_fn() {
+    _test_const(
+        r#"
+    const s0:u8 = 0
+    const s1:u8 = 1
+    const s2:u8 = 2
+    fn foo {
+        const s3:u8 = 3
+        const s4:u8 = 4
+        const s5:u8 = 5
+        {
+            const s6:u8 = 6
+            const s7:u8 = 7
+            {
+                const s8:u8 = 8
+            }
+            const s9:u8 = 9
+        }
+        const s10:u8 = 10
+    }
+    const s11:u8 = 11
+    fn bar {
+        const s12:u8 = 12
+        const s13:u8 = 13
+        {
+            const s14:u8 = 14
+        }
+        const s15:u8 = 15
+    }
+    "#,
+        (0..=15).collect::<Vec<_>>().as_ref(),
+    )
+}
+
+#[test]
+fn test_const_char() {
+    _test_const(
+        r#"
+    const a:u8 = 'A'
+    const b:u8 = '\n'
+    const c:u8 = '\x7f'
+    const d:[u8 3] = ['G' 'B' '\0']
+    const e:u8 = (+ 'a' 1)
+    "#,
+        &[b'A', b'\n', 0x7f, b'G', b'B', 0, b'b'],
+    )
+}
+
+#[test]
+fn test_const_str() {
+    _test_const(
+        r#"
+    const a:[u8 5] = "HELLO"
+    const b:[u8 3] = "\x00\n\""
+    let c:&u8 = "HELLO"
+    let d:&u8 = "GB"
+    let e:&u8 = "LO\x00"
+    "#,
+        b"HELLO\0\n\"GB",
+    )
+}
+
+#[test]
+fn test_const_str_charset() {
+    // tiles of the font: 'A'..='Z' followed by ' '
+    fn charset(c: char) -> Option<u8> {
+        match c {
+            'A'..='Z' => Some(c as u8 - b'A'),
+            ' ' => Some(26),
+            _ => None,
+        }
+    }
+    let ast = parse(r#"const a:[u8 6] = "GAME B" let b:&u8 = "OVER""#).unwrap();
+    let ir: Ir<NativeEndian> = Ir::with_charset(&ast, charset);
+    assert_eq!(&[6, 0, 12, 4, 26, 1, 14, 21, 4, 17][..], &ir.const_[..]);
+}
+
+#[test]
+fn test_const_word() {
+    let ast = parse("const a:u16 = 0x1234 const b:i16 = (+ a 1) const c:u8 = 0xff").unwrap();
+    let ir: Ir<ir::byteorder::BigEndian> = Ir::new(&ast);
+    assert_eq!(&[0x12, 0x34, 0x12, 0x35, 0xff][..], &ir.const_[..]);
+}
+
+#[test]
+fn test_const_enum() {
+    _test_const(
+        r#"
+    enum Dir { Up Down = 4 Left Right = (+ Dir::Left 2) }
+    const a:[u8 Dir::Left] = [0 1 2 3 4]
+    "#,
+        &[0, 4, 5, 7, 0, 1, 2, 3, 4],
+    )
+}
+
+#[test]
+fn test_const_expr() {
+    _test_const(
+        r#"
+    const a:u8 = (- 0 1)
+    const b:u8 = (+ -2 3)
+    const c:u8 = (> 3 a)
+    const d:u8 = (<= a 3)
+    const e:u8 = (& (>> 0x1234 4) 0xff)
+    const f:[u8 (* (+ b 2) 1)] = [0 1 2]
+    const g:u8 = (+ ~0 0x10)
+    "#,
+        &[0xff, 1, 0, 0, 0x23, 0, 1, 2, 0x0f],
+    )
+}
+
+#[test]
+fn test_const_local() {
+    _test_const(
+        r#"
+    const N:u8 = 1
+    fn f {
+        const M:u8 = (+ N 1)
+        const a:[u8 M] = [1 2]
+    }
+    {
+        const M:u8 = 3
+        const b:u8 = M
+    }
+    const M:u8 = 4
+    "#,
+        &[1, 2, 1, 2, 3, 3, 4],
+    )
+}
+
+#[test]
+fn test_const_static_assert() {
+    _test_const(
+        r#"
+    type Sprite = struct { y:u8 x:u8 tile:u8 flags:u8 }
+    const TILE_COUNT:u8 = 96
+    static_assert((== (sizeof Sprite) 4), "sprites are 4 bytes long")
+    static_assert((<= TILE_COUNT 128), "too many tiles")
+    fn f {
+        static_assert((== (sizeof [Sprite 40]) 160), "OAM is 160 bytes long")
+    }
+    "#,
+        &[96],
+    )
+}
+
+#[test]
+#[should_panic(expected = "Static assertion failed: sprites are 4 bytes long")]
+fn test_const_static_assert_failed() {
+    _test_const(
+        r#"
+    type Sprite = struct { y:u8 x:u8 tile:u8 }
+    static_assert((== (sizeof Sprite) 4), "sprites are 4 bytes long")
+    "#,
+        &[],
+    )
+}
+
+#[test]
+fn test_const_if_const() {
+    use ir::parser::{parse_with_context, ContextBuilder};
+
+    let input = r#"
+    if const CGB {
+        const BANKS:u8 = 2
+    } else {
+        const BANKS:u8 = 1
+    }
+    const SIZE:u16 = (* BANKS 0x2000)
+    "#;
+    for (cgb, gt) in &[(0, [1, 0x00, 0x20]), (1, [2, 0x00, 0x40])] {
+        let mut context = ContextBuilder::default().define("CGB", *cgb).build();
+        let ast = parse_with_context(input, &mut context).unwrap();
+        let ir: Ir<NativeEndian> = Ir::new(&ast);
+        assert_eq!(gt, &ir.const_[..gt.len()]);
+    }
+}
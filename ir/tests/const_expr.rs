@@ -1,5 +1,5 @@
 // generated (ggbc/src/bin/const_expr_gen.sh)
-use ggbc::{byteorder::NativeEndian, ir::Ir, parser::parse};
+use ir::{byteorder::NativeEndian, parser::parse, Ir};
 use vm::{Machine, Opts};
 
 fn test_const_expr(input: &str) {
@@ -0,0 +1,57 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Source, Statement},
+    parser::parse,
+    Ir,
+};
+
+fn ir(input: &str) -> Ir<NativeEndian> {
+    let ast = parse(input).unwrap();
+    Ir::new(&ast)
+}
+
+#[test]
+fn tables() {
+    let ir = ir(r#"
+    // triangle wave approximating a sine, scaled to 0..=128
+    const fn wave(i:u8):u8 {
+        let x:u8 = (& i 0x0f)
+        if (>= x 8) { (= x (- 16 x)) }
+        return (* x 16)
+    }
+    const fn gradient(i:u8):u16 {
+        let color:u16 = 0
+        for c:u8 in 0..3 { (= color (| (<< color 5) (as i u16))) }
+        return color
+    }
+    const WAVE:[u8 4] = [(wave 0) (wave 4) (wave 8) (wave 12)]
+    const GRADIENT:[u16 2] = [(gradient 1) (gradient 31)]
+    "#);
+    assert_eq!(&[0, 64, 128, 64], &ir.const_[..4]);
+    assert_eq!(
+        &[0x0421u16.to_ne_bytes(), 0x7fffu16.to_ne_bytes()].concat()[..],
+        &ir.const_[4..8]
+    );
+}
+
+#[test]
+fn not_compiled() {
+    // const functions have no routine, and their calls are folded
+    let ir = ir(r#"
+    static X:u8
+    const fn double(x:u8):u8 { return (* x 2) }
+    (= X (double 21))
+    "#);
+    assert_eq!(1, ir.routines.len());
+    let statements = &ir.routines[ir.handlers.main].statements;
+    assert!(statements
+        .iter()
+        .all(|s| !matches!(s, Statement::Call { .. })));
+    assert!(statements.iter().any(|s| matches!(
+        s,
+        Statement::Ld {
+            source: Source::Literal(42),
+            ..
+        }
+    )));
+}
@@ -0,0 +1,105 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Source, Statement},
+    parser::parse,
+    Ir,
+};
+
+fn routine_statements(input: &str, name: &str) -> Vec<Statement> {
+    let ast = parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    ir.routines
+        .iter()
+        .find(|r| r.debug_name.as_deref() == Some(name))
+        .unwrap()
+        .statements
+        .clone()
+}
+
+// positions of the statements loading a literal
+fn loads(statements: &[Statement], literal: u8) -> Vec<usize> {
+    statements
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| {
+            matches!(
+                s,
+                Statement::Ld {
+                    source: Source::Literal(l),
+                    ..
+                } if *l == literal
+            )
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[test]
+fn return_() {
+    let statements = routine_statements(
+        r#"
+    static X:u8
+    fn f {
+        defer { (= X 42) }
+        if (== X 0) { return }
+        (= X 1)
+    }
+    (f)
+    "#,
+        "f",
+    );
+    // once for the return, once for falling through
+    let defers = loads(&statements, 42);
+    assert_eq!(2, defers.len());
+    for i in defers {
+        assert!(matches!(statements[i + 1], Statement::Ret));
+    }
+}
+
+#[test]
+fn return_value() {
+    let statements = routine_statements(
+        r#"
+    static X:u8
+    fn f:u8 {
+        defer { (= X 42) }
+        return 7
+    }
+    "#,
+        "f",
+    );
+    // the value is returned after running the deferred block
+    let value = loads(&statements, 7);
+    let defers = loads(&statements, 42);
+    assert_eq!(1, value.len());
+    assert_eq!(1, defers.len());
+    assert!(value[0] < defers[0]);
+}
+
+#[test]
+fn nested() {
+    let statements = routine_statements(
+        r#"
+    static X:u8
+    fn f {
+        defer { (= X 1) }
+        {
+            defer { (= X 2) }
+            if (== X 0) { return }
+        }
+    }
+    defer { (= X 3) }
+    (f)
+    "#,
+        "f",
+    );
+    // the return runs both blocks, innermost first, then each block runs
+    // when falling through its scope
+    let inner = loads(&statements, 2);
+    let outer = loads(&statements, 1);
+    assert_eq!(2, inner.len());
+    assert_eq!(2, outer.len());
+    assert!(inner[0] < outer[0] && outer[0] < inner[1] && inner[1] < outer[1]);
+    // but not the deferred blocks of the scope defining the function
+    assert!(loads(&statements, 3).is_empty());
+}
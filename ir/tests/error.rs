@@ -0,0 +1,331 @@
+use ir::{
+    byteorder::NativeEndian,
+    parser::{lex::span::Span, parse, parse_with_context, ContextBuilder},
+    CompileError, Ir,
+};
+
+fn error(input: &str) -> CompileError {
+    let ast = parse(input).unwrap();
+    Ir::<NativeEndian>::try_new(&ast).unwrap_err()
+}
+
+fn span(line: usize, min: usize, max: usize) -> Span {
+    Span {
+        min: [line, min],
+        max: [line, max],
+        ..Span::default()
+    }
+}
+
+#[test]
+fn duplicate() {
+    assert_eq!(
+        CompileError::DuplicateSymbol {
+            name: "A".to_string(),
+            span: span(1, 7, 8),
+        },
+        error("static A:u8\nstatic A:u16")
+    );
+    assert_eq!(
+        CompileError::DuplicateSymbol {
+            name: "f".to_string(),
+            span: span(1, 3, 4),
+        },
+        error("fn f {}\nfn f {}")
+    );
+    let e = error("const C:u8 = 1\nstatic@0xc000 C:u8");
+    assert_eq!(Some("C"), e.name());
+    assert_eq!(span(1, 14, 15), e.span());
+    assert_eq!("Duplicate symbol `C`", e.to_string());
+}
+
+#[test]
+fn duplicate_in_loop() {
+    // the enclosing for loops hold registers when the error is found
+    assert_eq!(
+        CompileError::DuplicateSymbol {
+            name: "k".to_string(),
+            span: span(1, 5, 6),
+        },
+        error("for k:u8 in 0..3 {\n for k:u8 in 0..3 { }\n}")
+    );
+    assert_eq!(
+        CompileError::DuplicateSymbol {
+            name: "k".to_string(),
+            span: span(4, 7, 8),
+        },
+        error(
+            "static X:u8\n\
+             for i:u8 in 0..2 {\n if X {\n  for k:u8 in 0..3 {\n   for k:u8 in 0..3 {\n    \
+             continue\n    (= X 1)\n   }\n  }\n }\n}"
+        )
+    );
+}
+
+#[test]
+fn undefined() {
+    // extern symbols are type checked, but not declared by the program
+    let mut context = ContextBuilder::default()
+        .extern_symbol("LCDC", "u8")
+        .build();
+    let ast = parse_with_context("static A:u8\n(= A LCDC)", &mut context).unwrap();
+    let e = Ir::<NativeEndian>::try_new(&ast).unwrap_err();
+    assert_eq!(
+        CompileError::UndefinedSymbol {
+            name: "LCDC".to_string(),
+            span: span(1, 5, 9),
+        },
+        e
+    );
+    assert_eq!("Undefined symbol `LCDC`", e.to_string());
+}
+
+#[test]
+fn erroneous() {
+    let mut context = ContextBuilder::default().error_tolerant(true).build();
+    let ast = parse_with_context("static A:u8\n(= A\n(= A 1)", &mut context).unwrap();
+    let e = Ir::<NativeEndian>::try_new(&ast).unwrap_err();
+    assert!(matches!(e, CompileError::Erroneous { .. }));
+    assert_eq!(None, e.name());
+    assert_eq!([1, 0], e.span().min);
+    assert_eq!("Erroneous statement", e.to_string());
+
+    // `parse` doesn't type check the program
+    assert_eq!(
+        CompileError::UndefinedSymbol {
+            name: "X".to_string(),
+            span: span(0, 3, 4),
+        },
+        error("(= X 1)")
+    );
+}
+
+#[test]
+fn bitfield() {
+    // the parser only checks the bitfields of `u8` and `u16` fields
+    assert_eq!(
+        CompileError::InvalidBitfield {
+            name: "L::a".to_string(),
+            span: span(0, 17, 18),
+        },
+        error("static L:i8 .{ a:1 }")
+    );
+    let e = error("type Byte = u8\nstatic L:Byte .{ a:4 b:5 }");
+    assert_eq!(
+        CompileError::InvalidBitfield {
+            name: "L::b".to_string(),
+            span: span(1, 23, 24),
+        },
+        e
+    );
+    assert_eq!("Invalid bitfield `L::b`", e.to_string());
+}
+
+#[test]
+fn private() {
+    let module = "mod m {\nstatic A:u8\npub static B:u8\nfn f {}\npub fn g {}\n(= A 1)\n(f)\n}\n";
+    let input = format!("{}(= m::B 1)\n(m::g)", module);
+    let ast = parse(&input).unwrap();
+    assert!(Ir::<NativeEndian>::try_new(&ast).is_ok());
+    assert_eq!(
+        CompileError::PrivateSymbol {
+            name: "m::A".to_string(),
+            span: span(8, 3, 7),
+        },
+        error(&format!("{}(= m::A 1)", module))
+    );
+    let e = error(&format!("{}(m::f)", module));
+    assert_eq!(
+        CompileError::PrivateSymbol {
+            name: "m::f".to_string(),
+            span: span(8, 1, 5),
+        },
+        e
+    );
+    assert_eq!("Private symbol `m::f`", e.to_string());
+}
+
+#[test]
+fn mismatched_width() {
+    let e = error("let a:u8 = 1\nlet c:u16 = a");
+    assert_eq!(
+        CompileError::MismatchedWidth {
+            name: "a".to_string(),
+            span: span(1, 12, 13),
+        },
+        e
+    );
+    assert_eq!("Mismatched width of `a`", e.to_string());
+}
+
+#[test]
+fn bank_out_of_range() {
+    let e = error("static@bank(256) A:u8");
+    assert_eq!(
+        CompileError::BankOutOfRange {
+            span: span(0, 12, 15)
+        },
+        e
+    );
+    assert_eq!(None, e.name());
+    assert_eq!("Bank number out of range", e.to_string());
+    assert_eq!(
+        CompileError::BankOutOfRange {
+            span: span(0, 7, 10)
+        },
+        error("#[bank(300)] fn f {}")
+    );
+}
+
+#[test]
+fn initialized_static() {
+    let e = error("static@0xc000 A:u8 = 1");
+    assert_eq!(
+        CompileError::InitializedStatic {
+            name: "A".to_string(),
+            span: span(0, 14, 15),
+        },
+        e
+    );
+    assert_eq!(
+        "Static `A` at a fixed location can't be initialized",
+        e.to_string()
+    );
+    assert_eq!(Some("B"), error("static@bank(1) B:u8 = 1").name());
+}
+
+#[test]
+fn duplicate_handler() {
+    let e = error("fn@vblank f {}\nfn@vblank g {}");
+    assert_eq!(
+        CompileError::DuplicateHandler {
+            name: "vblank".to_string(),
+            span: span(1, 3, 9),
+        },
+        e
+    );
+    assert_eq!("Interrupt handler `vblank` already defined", e.to_string());
+}
+
+#[test]
+fn outside_loop() {
+    let e = error("static R:u8\nif R { break }");
+    assert_eq!(
+        CompileError::OutsideLoop {
+            span: span(1, 7, 12)
+        },
+        e
+    );
+    assert_eq!(None, e.name());
+    assert_eq!("Break or continue outside of a loop", e.to_string());
+    assert_eq!(
+        CompileError::OutsideLoop {
+            span: span(0, 7, 18)
+        },
+        error("loop { continue 'a }")
+    );
+    // loops enclosing a function can't be broken out of
+    assert!(matches!(
+        error("loop { fn f { break } break }"),
+        CompileError::OutsideLoop { .. }
+    ));
+}
+
+#[test]
+fn out_of_range() {
+    // `parse` doesn't check the range of the constants of assignments
+    let e = error("static R:u8\n(= R (+ 200 100))");
+    assert_eq!(
+        CompileError::OutOfRange {
+            span: span(1, 5, 16)
+        },
+        e
+    );
+    assert_eq!(None, e.name());
+    assert_eq!("Constant out of range for a byte", e.to_string());
+    assert_eq!(
+        CompileError::OutOfRange {
+            span: span(1, 6, 9)
+        },
+        error("static R:u8\n(+= R 256)")
+    );
+    // nor of `sizeof` expressions
+    assert_eq!(
+        CompileError::OutOfRange {
+            span: span(1, 11, 21)
+        },
+        error("type T = [u8 300]\nlet x:u8 = (sizeof T)")
+    );
+}
+
+#[test]
+fn unsupported() {
+    let e = error("static A:struct { a:u8 }\nlet p:&struct { a:u8 } = @A\n(= *p A)");
+    assert!(matches!(e, CompileError::Unsupported { .. }));
+    assert_eq!(None, e.name());
+    assert_eq!("Unsupported expression", e.to_string());
+    assert_eq!(
+        CompileError::Unsupported {
+            span: span(1, 5, 10)
+        },
+        error("static W:u16\n(= W (~ W))")
+    );
+    let unsupported = [
+        // value of a call that isn't stored in memory
+        (
+            "fn f(a:u8):u8 { return a }\nstatic R:u8\n(= R (f 4))",
+            span(2, 5, 10),
+        ),
+        // builtins without a value
+        ("static R:u8\n(= R (builtin::halt))", span(1, 5, 20)),
+        ("let x:u8 = (builtin::halt)", span(0, 11, 26)),
+        // expression statement without side effects
+        ("static R:u8\n(+ R 1)", span(1, 0, 7)),
+        // indexing something other than a symbol
+        ("static R:u8\n(= R ([0] [1 2]))", span(1, 10, 15)),
+        ("static P:&[u8 2]\nlet x:u8 = ([0] *P)", span(1, 16, 18)),
+        ("static P:&[u8 2]\nlet x:&u8 = @([0] *P)", span(1, 18, 20)),
+    ];
+    for (input, span) in unsupported.iter() {
+        assert_eq!(CompileError::Unsupported { span: *span }, error(input));
+    }
+}
+
+#[test]
+fn stack_address() {
+    let e = error("let x:u8 = 0\nlet p:&u8 = @x");
+    assert_eq!(
+        CompileError::StackAddress {
+            span: span(1, 12, 14)
+        },
+        e
+    );
+    assert_eq!(None, e.name());
+    assert_eq!("Address of stack memory can't be taken", e.to_string());
+    assert_eq!(
+        CompileError::StackAddress {
+            span: span(1, 12, 19)
+        },
+        error("let a:[u8 2] = [0 0]\nlet p:&u8 = @([1]a)")
+    );
+    assert_eq!(
+        CompileError::StackAddress {
+            span: span(2, 5, 7)
+        },
+        error("static P:&u8\nlet x:u8 = 0\n(= P @x)")
+    );
+    // function arguments are in the stack too
+    assert_eq!(
+        CompileError::StackAddress {
+            span: span(1, 18, 20)
+        },
+        error("static P:&u8\nfn f(x:u8) { (= P @x) }")
+    );
+}
+
+#[test]
+#[should_panic(expected = "Duplicate symbol `A`")]
+fn new_panics() {
+    let ast = parse("static A:u8\nstatic A:u8").unwrap();
+    let _: Ir<NativeEndian> = Ir::new(&ast);
+}
@@ -0,0 +1,55 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Source, Statement},
+    parser::parse,
+    Ir,
+};
+
+fn main_statements(input: &str) -> Vec<Statement> {
+    let ast = parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    ir.routines[ir.handlers.main].statements.clone()
+}
+
+#[test]
+fn literals() {
+    let ast = parse("static A:fx8.8 = 1.5fx\nstatic B:fx8.8 = 0.25fx").unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(&[0x80, 0x01, 0x40, 0x00], &ir.static_[..4]);
+}
+
+#[test]
+fn conversions() {
+    let statements =
+        main_statements("static A:fx8.8\nstatic N:u8\n(= A (as N fx8.8))\n(= N (as A u8))");
+    let shifts: Vec<_> = statements
+        .iter()
+        .filter_map(|s| match s {
+            Statement::LeftShiftW { right, .. } => Some(("<<", right.clone())),
+            Statement::RightShiftW { right, .. } => Some((">>", right.clone())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![("<<", Source::Literal(8)), (">>", Source::Literal(8))],
+        shifts
+    );
+}
+
+#[test]
+fn products() {
+    // products of fixed-point values take four 16bit multiplications
+    let statements = main_statements("static A:fx8.8\nstatic B:fx8.8\n(= A (* A B))");
+    let muls = statements
+        .iter()
+        .filter(|s| matches!(s, Statement::MulW { .. }))
+        .count();
+    assert_eq!(4, muls);
+    // and products by integers only one
+    let statements = main_statements("static A:fx8.8\n(= A (* A 3))");
+    let muls = statements
+        .iter()
+        .filter(|s| matches!(s, Statement::MulW { .. }))
+        .count();
+    assert_eq!(1, muls);
+}
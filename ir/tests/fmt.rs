@@ -0,0 +1,162 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Location, Pointer, Source, Statement},
+    parse_text,
+    parser::parse,
+    Ir,
+};
+use vm::{Machine, Opts};
+
+fn round_trip(input: &str) {
+    let ast = parse(input).unwrap();
+    let mut ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(ir, parse_text(&ir.to_string()).unwrap());
+    ir.optimize();
+    assert_eq!(ir, parse_text(&ir.to_string()).unwrap());
+}
+
+#[test]
+fn programs() {
+    round_trip("");
+    round_trip(
+        r#"
+        static A:u8
+        static B:[u16 2]
+        const C:[u8 3] = [1 2 3]
+        fn f(x:u8):u8 { return (+ x 1) }
+        let i:u8 = 0
+        loop {
+            if (== i 3) { break }
+            (= A (+ A i))
+            (= i (+ i 1))
+        }
+        (f 4)
+        (= A ([2] C))
+        "#,
+    );
+    round_trip(
+        r#"
+        static@0xff40 volatile LCDC:u8
+        static P:&u8
+        static X:u8
+        fn@vblank on_vblank { (= LCDC 0) }
+        #[inline(never)] fn g() { }
+        #[inline] fn h() { (= X 1) }
+        static G:fn()
+        (= P @X)
+        (= *P 0xff)
+        (= G @g)
+        (G)
+        (h)
+        (builtin::memset @X 1 1)
+        (builtin::halt)
+        "#,
+    );
+    round_trip(
+        r#"
+        static A:i8
+        static B:i16
+        let a:i8 = -6
+        (= A (/ a 2))
+        (= B (>> B 1))
+        if (< a 0) { (= A (>> a 1)) }
+        if (>= B 300) { (= B (- B 300)) }
+        "#,
+    );
+    round_trip(
+        r#"
+        static A:u8
+        match A {
+            1 { (= A 4) }
+            2 { (= A 3) }
+            3 { (= A 2) }
+            4 { (= A 1) }
+        }
+        "#,
+    );
+}
+
+#[test]
+fn hand_written() {
+    let text = r#"
+        ; adds 3 to 4 in static memory
+        overflow saturating
+        static_alloc 2
+        static 04 ff
+
+        routine 0 - stack 1 args 0 return 0
+            ld 3 -> [stack 0]
+            add [stack 0], [static 0] -> r0
+            ld r0 -> [static 0 + r1]
+            stop success
+        end
+    "#;
+    let ir: Ir<NativeEndian> = parse_text(text).unwrap();
+    assert_eq!(ir::Overflow::Saturating, ir.overflow);
+    assert_eq!(None, ir.main().debug_name);
+    assert_eq!(
+        Statement::Ld {
+            source: Source::Register(0),
+            destination: ir::opcodes::Destination::Pointer {
+                base: Pointer::Static(0),
+                offset: Some(Box::new(Source::Register(1))),
+            },
+        },
+        ir.main().statements[2]
+    );
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!([7, 0xff], memory.static_[..2]);
+}
+
+#[test]
+fn tail_jump() {
+    let text = r#"
+        static_alloc 2
+
+        ; passes 3 to routine 1, which stores it incremented
+        routine 0 - stack 1 args 0 return 0
+            ld 3 -> [stack 0]
+            jmp routine 1
+        end
+
+        routine 1 - stack 1 args 1 return 0
+            nop 0
+            jmp absolute 3
+            ld 0xff -> [static 1]
+            add [stack 0], 1 -> r0
+            ld r0 -> [static 0]
+            stop success
+        end
+    "#;
+    let ir: Ir<NativeEndian> = parse_text(text).unwrap();
+    assert_eq!(ir, parse_text(&ir.to_string()).unwrap());
+    assert_eq!(
+        Statement::Jmp {
+            location: Location::Routine(1)
+        },
+        ir.main().statements[1]
+    );
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!([4, 0], memory.static_[..2]);
+}
+
+#[test]
+fn errors() {
+    let error = |text: &str| parse_text::<NativeEndian>(text).unwrap_err();
+
+    let e = error("static_alloc 2\nroutine 0 - stack 0 args 0 return 0\nld 1 -> 2\nend");
+    assert_eq!(3, e.line);
+    assert_eq!("line 3: invalid destination `2`", e.to_string());
+    assert_eq!(1, error("static_alloc 0x10000").line);
+    assert_eq!(1, error("static 0").line);
+    assert_eq!(1, error("routine 1 - stack 0 args 0 return 0\nend").line);
+    assert_eq!(
+        2,
+        error("routine 0 - stack 0 args 0 return 0\nfoo\nend").line
+    );
+    assert_eq!(2, error("routine 0 - stack 0 args 0 return 0\nret").line);
+    assert_eq!(
+        3,
+        error("handler vblank 1\nroutine 0 - stack 0 args 0 return 0\nend").line
+    );
+}
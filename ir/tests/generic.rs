@@ -0,0 +1,88 @@
+use ir::{byteorder::NativeEndian, opcodes::Statement, parser::parse, Ir};
+
+fn ir(input: &str) -> Ir<NativeEndian> {
+    let ast = parse(input).unwrap();
+    Ir::new(&ast)
+}
+
+// routine with the given name.
+fn routine(ir: &Ir<NativeEndian>, name: &str) -> usize {
+    ir.routines
+        .iter()
+        .position(|r| r.debug_name.as_deref() == Some(name))
+        .unwrap()
+}
+
+// routines called by a routine, in order.
+fn calls(ir: &Ir<NativeEndian>, routine: usize) -> Vec<usize> {
+    ir.routines[routine]
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Call { routine, .. } => Some(*routine),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn instances() {
+    let ir = ir(r#"
+    static A:[u8 4]
+    static B:[u8 6]
+    fn sum<const N:u8>(xs:[u8 N]):u8 {
+        let s:u8 = 0
+        for i:u8 in 0..N { (+= s ([i] xs)) }
+        return s
+    }
+    let a:u8 = (sum A)
+    let b:u8 = (sum B)
+    let c:u8 = (sum [1 2 3 4])
+    "#);
+    let sum_4 = routine(&ir, "sum<4>");
+    let sum_6 = routine(&ir, "sum<6>");
+    assert_eq!(4, ir.routines[sum_4].args_size);
+    assert_eq!(6, ir.routines[sum_6].args_size);
+    assert_eq!(vec![sum_4, sum_6, sum_4], calls(&ir, ir.handlers.main));
+    // main is compiled last
+    assert_eq!(3, ir.routines.len());
+}
+
+#[test]
+fn nested() {
+    let ir = ir(r#"
+    static A:[u8 4]
+    fn sum<const N:u8>(xs:[u8 N]):u8 { return ([0] xs) }
+    fn total<const N:u8>(xs:[u8 N]):u8 {
+        let s:u8 = (sum xs)
+        return s
+    }
+    fn first(xs:[u8 4]):u8 {
+        let s:u8 = (total xs)
+        return s
+    }
+    let a:u8 = (first A)
+    "#);
+    let sum = routine(&ir, "sum<4>");
+    let total = routine(&ir, "total<4>");
+    let first = routine(&ir, "first");
+    assert_eq!(vec![total], calls(&ir, first));
+    assert_eq!(vec![sum], calls(&ir, total));
+    assert_eq!(vec![first], calls(&ir, ir.handlers.main));
+    assert!(ir.stack_report().is_ok());
+}
+
+#[test]
+#[should_panic(expected = "Can't infer generic parameter `N`")]
+fn infer() {
+    ir("fn f<const N:u8>() { }\n(f)");
+}
+
+#[test]
+fn params() {
+    let ir =
+        ir("static B:[u8 6]\nfn len<const N:u8>(xs:[u8 N]):u8 { return N }\nlet a:u8 = (len B)");
+    // the parameters of each instance are consts
+    assert_eq!(&[6], &ir.const_[..]);
+    assert_eq!(6, ir.routines[routine(&ir, "len<6>")].args_size);
+}
@@ -0,0 +1,413 @@
+use ir::{byteorder::NativeEndian, opcodes::Pointer, Ir};
+
+fn test(size: u16, input: &str) {
+    let ast = ir::parser::parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(size, ir.main().stack_size);
+}
+
+fn test_routine(size: u16, input: &str) {
+    let ast = ir::parser::parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(size, ir.routines[0].stack_size);
+}
+
+#[test]
+fn static_usage_0() {
+    test(
+        7,
+        r#"
+        let s0:u8 = 0
+        let s1:[u8 2] = [1 2]
+        let s2:[[u8 2] 2] = [[0 1] [2 3]]
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_1() {
+    test(
+        5,
+        r#"
+        let s0:u8 = 0
+        {
+            let s1:[u8 2] = [1 2]
+        }
+        let s2:[[u8 2] 2] = [[0 1] [2 3]]
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_2() {
+    test(
+        17,
+        r#"
+        let s0:u8 = 0
+        {
+            let s1:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+        }
+        let s2:[[u8 2] 2] = [[0 1] [2 3]]
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_3() {
+    test(
+        17,
+        r#"
+        let s0:u8 = 0
+        {
+            {
+                let s1:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+            }
+        }
+        let s2:[[u8 2] 2] = [[0 1] [2 3]]
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_4() {
+    test(
+        21,
+        r#"
+        let s0:u8 = 0
+        {
+            let s1:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+        }
+        let s2:[[u8 2] 2] = [[0 1] [2 3]]
+        let s3:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_5() {
+    test_routine(
+        21,
+        r#"
+        fn foo {
+            let s0:u8 = 0
+            {
+                let s1:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+            }
+            let s2:[[u8 2] 2] = [[0 1] [2 3]]
+            let s3:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+        }
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_6() {
+    test_routine(
+        28,
+        r#"
+        fn foo(a0:u8 a1:u8 a3:[u8 4]) {
+            let s0:u8 = 0
+            {
+                let s1:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+            }
+            let s2:[[u8 2] 2] = [[0 1] [2 3]]
+            let s3:[u8 16] = [1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16]
+            let s4:u8 = 0
+        }
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_7() {
+    test(
+        1,
+        r#"
+        fn foo(a0:u8 a1:u8):u8 {return 0}
+        let bar:u8 = (foo 0 1)
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_8() {
+    test(
+        2,
+        r#"
+        fn foo(a0:u8 a1:u8):u8 {return 0}
+        fn baz(a0:u8):u8 {return 0}
+        let bar:u8 = (foo 0 1)
+        let qux:u8 = (baz 2)
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_9() {
+    test(
+        2,
+        r#"
+        fn foo(a0:u8 a1:u8 a2:u8):u8 {return 0}
+        fn baz(a0:u8):u8 {return 0}
+        let bar:u8 = (foo 0 1 2)
+        let qux:u8 = (baz 3)
+        "#,
+    );
+}
+
+#[test]
+fn static_usage_10() {
+    test(
+        0,
+        r#"
+        fn foo(a0:u8 a1:u8 a2:u8):u8 {return 0}
+        "#,
+    );
+}
+
+#[test]
+fn short_circuit() {
+    use ir::opcodes::Statement;
+
+    let ast = ir::parser::parse("static a:u8 static b:bool (= b (&& a (+ a 1)))").unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let statements = &ir.main().statements;
+    let jmp = statements
+        .iter()
+        .position(|s| matches!(s, Statement::JmpCmpNot { .. }))
+        .unwrap();
+    // the right operand is evaluated after the conditional jump
+    assert!(statements[jmp..]
+        .iter()
+        .any(|s| matches!(s, Statement::Add { .. })));
+}
+
+#[test]
+fn match_jump_table() {
+    use ir::opcodes::{Location, Statement};
+
+    let table = |input: &str| {
+        let ast = ir::parser::parse(input).unwrap();
+        let ir: Ir<NativeEndian> = Ir::new(&ast);
+        ir.main().statements.iter().find_map(|s| match s {
+            Statement::Jmp {
+                location: Location::Table { targets, .. },
+            } => Some(targets.len()),
+            _ => None,
+        })
+    };
+    // dense arms, one entry per value from the first to the last one
+    assert_eq!(
+        Some(5),
+        table("static A:u8 match A { 2 { } 3 { (= A 1) } 4..=5 { (= A 2) } 6 { (= A 3) } }")
+    );
+    // sparse arms, and too few of them, are compared one by one
+    assert_eq!(
+        None,
+        table("static A:u8 match A { 0 { } 10 { (= A 1) } 20 { (= A 2) } 30 { (= A 3) } }")
+    );
+    assert_eq!(None, table("static A:u8 match A { 0 { } 1 { (= A 1) } }"));
+}
+
+#[test]
+fn interrupt_handlers() {
+    let ast = ir::parser::parse(
+        r#"
+        static COUNT:u8
+        fn@vblank on_vblank { (++ COUNT) }
+        fn@timer on_timer { }
+        fn update { }
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(3, ir.handlers.main);
+    assert_eq!(Some(0), ir.handlers.vblank);
+    assert_eq!(Some(1), ir.handlers.timer);
+    assert_eq!(None, ir.handlers.joypad);
+    assert_eq!(
+        Some("on_vblank"),
+        ir.vblank().unwrap().debug_name.as_deref()
+    );
+}
+
+#[test]
+fn volatile_statics() {
+    let ast = ir::parser::parse(
+        r#"
+        static@0xff40 LCDC:u8
+        static@0xff44 volatile LY:u8
+        static@0xff04 volatile TIMER:struct { div:u8 tima:u8 }
+        (= LCDC LY)
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(&[0xff44..0xff45, 0xff04..0xff06], &ir.volatile[..]);
+    assert!(ir.is_volatile(Pointer::Absolute(0xff05)));
+    assert!(!ir.is_volatile(Pointer::Absolute(0xff40)));
+    assert!(!ir.is_volatile(Pointer::Static(0xff44)));
+}
+
+#[test]
+fn call_indirect() {
+    use ir::opcodes::{Destination, Source, Statement};
+
+    let ast = ir::parser::parse(
+        r#"
+        static CALLBACK:fn
+        fn tick { }
+        (= CALLBACK @tick)
+        (CALLBACK)
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let statements = &ir.main().statements;
+    let callback = Pointer::Static(0);
+    assert!(statements.contains(&Statement::LdW {
+        source: Source::Literal(0),
+        destination: Destination::Pointer {
+            base: callback,
+            offset: None,
+        },
+    }));
+    assert!(statements.iter().any(|s| matches!(
+        s,
+        Statement::CallIndirect {
+            routine: Source::Pointer { base, offset: None },
+            ..
+        } if *base == callback
+    )));
+}
+
+#[test]
+fn tuple_return() {
+    use ir::opcodes::{Destination, Source, Statement};
+
+    let ast = ir::parser::parse(
+        r#"
+        fn divmod(a:u8 b:u8):(u8 u8) {
+            return [(/ a b) (- a (* (/ a b) b))]
+        }
+        let (q:u8 r:u8) = (divmod 7 2)
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(2, ir.routines[0].return_size);
+    // the returned tuple is copied into the destructured symbols
+    for i in 0..2 {
+        assert!(ir.main().statements.contains(&Statement::Ld {
+            source: Source::Pointer {
+                base: Pointer::Return(i),
+                offset: None,
+            },
+            destination: Destination::Pointer {
+                base: Pointer::Stack(i),
+                offset: None,
+            },
+        }));
+    }
+}
+
+#[test]
+fn banks() {
+    use ir::opcodes::Statement;
+
+    let ast = ir::parser::parse(
+        r#"
+        static@bank(2) LEVEL:[u8 4000]
+        static@bank(2) LEVEL_ID:u16
+        static@bank(1) SAVE:u8
+        static SCORE:u8
+        #[bank(3)] fn draw { (= SAVE 1) }
+        fn update { }
+        (draw)
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(1, ir.static_alloc);
+    let banks: Vec<_> = ir.banks.iter().map(|(b, s)| (*b, *s)).collect();
+    assert_eq!(vec![(1, 1), (2, 4002)], banks);
+
+    // the banked function is followed by its thunk
+    assert_eq!(Some(3), ir.routines[0].bank);
+    assert_eq!(None, ir.routines[1].bank);
+    assert!(ir.routines[1]
+        .statements
+        .contains(&Statement::PushBank { bank: 3 }));
+    assert!(ir.routines[1].statements.contains(&Statement::Call {
+        routine: 0,
+        range: 0..
+    }));
+    assert_eq!(Some("update"), ir.routines[2].debug_name.as_deref());
+    assert!(ir
+        .main()
+        .statements
+        .iter()
+        .any(|s| matches!(s, Statement::Call { routine: 1, .. })));
+}
+
+#[test]
+fn memory_regions() {
+    use ir::{Region, Space};
+
+    let ast = ir::parser::parse(
+        r#"
+        memory { const ROM0:0x0000..0x4000, static WRAM:0xc000..+2, HRAM:0xff80..=0xffff }
+        static A:u16
+        static@0xff80 B:u8
+        "#,
+    )
+    .unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    assert_eq!(
+        &[
+            Region {
+                name: "ROM0".to_string(),
+                range: 0x0000..0x4000,
+                space: Space::Const,
+            },
+            Region {
+                name: "WRAM".to_string(),
+                range: 0xc000..0xc002,
+                space: Space::Static,
+            },
+            Region {
+                name: "HRAM".to_string(),
+                range: 0xff80..0x10000,
+                space: Space::Absolute,
+            },
+        ],
+        &ir.regions[..]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Memory region `WRAM` overflowed by 1 bytes allocating `B`!")]
+fn memory_regions_overflow() {
+    let ast = ir::parser::parse(
+        r#"
+        memory { static WRAM:0xc000..0xc002 }
+        static A:u16
+        static B:u8
+        "#,
+    )
+    .unwrap();
+    let _: Ir<NativeEndian> = Ir::new(&ast);
+}
+
+#[test]
+#[should_panic(expected = "Absolute static `LCDC` is outside of the memory map!")]
+fn memory_regions_absolute() {
+    let ast = ir::parser::parse(
+        r#"
+        memory { HRAM:0xff80..0xffff }
+        static@0xff40 LCDC:u8
+        "#,
+    )
+    .unwrap();
+    let _: Ir<NativeEndian> = Ir::new(&ast);
+}
@@ -0,0 +1,1010 @@
+use ir::{
+    byteorder::{BigEndian, ByteOrder},
+    opcodes::{Destination, Source, Statement},
+    opt::{
+        allocate_registers, const_fold, copy_propagation, cse, inline, licm,
+        peephole::{Context, Peephole, Rule},
+        simplify_branches, strength_reduction, IrPass, OptLevel, Pipeline, Stats,
+    },
+    parse_text,
+    parser::parse,
+    Inline, Ir,
+};
+use std::cell::Cell;
+use vm::{Machine, Opts};
+
+// the main routine after running `pass` on the program, in the text format.
+// `src` is either a whole program, or the statements of its main routine.
+//
+// Programs that stop by themselves must leave the same static memory, with the
+// same status, before and after running the pass.
+fn run(pass: impl Fn(&mut Ir<BigEndian>), src: &str) -> String {
+    let text = if src.contains("routine ") {
+        src.to_string()
+    } else {
+        format!(
+            "const 12 34\nroutine 0 - stack 4 args 1 return 0\n{}\nend",
+            src
+        )
+    };
+    let expected = stopped(&parse_text(&text).unwrap());
+    let mut ir: Ir<BigEndian> = parse_text(&text).unwrap();
+    pass(&mut ir);
+    if expected.is_some() {
+        assert_eq!(expected, stopped(&ir), "{}", text);
+    }
+    let statements: Vec<_> = ir.main().statements.iter().map(|s| s.to_string()).collect();
+    statements.join("\n")
+}
+
+// written bytes of static memory and error status of the program once it
+// stops. `None` if the main routine returns instead, or it doesn't stop within
+// a few steps.
+fn stopped(ir: &Ir<BigEndian>) -> Option<(Vec<(usize, u8)>, bool)> {
+    let statements = &ir.main().statements;
+    if statements.contains(&Statement::Ret)
+        || !matches!(statements.last(), Some(Statement::Stop(_)))
+    {
+        return None;
+    }
+    let mut machine = Machine::new(ir, Opts::default());
+    for _ in 0..10_000 {
+        machine.step();
+    }
+    if machine.is_running() {
+        None
+    } else {
+        let memory = machine.memory().static_.iter().copied().enumerate();
+        let written = memory.filter(|(_, b)| *b != 0).collect();
+        Some((written, machine.is_error()))
+    }
+}
+
+#[test]
+fn literals() {
+    // the value is stored and the program stopped, so the VM checks it
+    let fold = |statement: &str| {
+        let text = run(
+            |ir| {
+                const_fold(ir);
+            },
+            &format!("{}\nstop success", statement),
+        );
+        text.lines().next().unwrap().to_string()
+    };
+    assert_eq!("ld 3 -> [static 0]", fold("add 1, 2 -> [static 0]"));
+    assert_eq!("ld 1 -> [static 0]", fold("greater 4, 2 -> [static 0]"));
+    assert_eq!("ld_w 200 -> [static 0]", fold("ext 200 -> [static 0]"));
+    assert_eq!(
+        "ld_w 65535 -> [static 0]",
+        fold("sign_ext 255 -> [static 0]")
+    );
+    assert_eq!("ld 44 -> [static 0]", fold("trunc 300 -> [static 0]"));
+    assert_eq!("ld 0 -> [static 0]", fold("left_shift 1, 8 -> [static 0]"));
+    assert_eq!(
+        "ld_w 256 -> [static 0]",
+        fold("left_shift_w 1, 8 -> [static 0]")
+    );
+    assert_eq!("ld 66 -> [static 0]", fold("bcd_adjust 142 -> [static 0]"));
+    assert_eq!("ld 44 -> [static 0]", fold("add 200, 100 -> [static 0]"));
+    assert_eq!("ld 255 -> [static 0]", fold("dec 0 -> [static 0]"));
+    assert_eq!("ld 255 -> [static 0]", fold("div_s 250, 4 -> [static 0]"));
+    assert_eq!(
+        "ld 255 -> [static 0]",
+        fold("right_shift_s 128, 9 -> [static 0]")
+    );
+    assert_eq!(
+        "ld_w 65534 -> [static 0]",
+        fold("rem_sw 65526, 4 -> [static 0]")
+    );
+    assert_eq!("ld 1 -> [static 0]", fold("less_s 255, 1 -> [static 0]"));
+    assert_eq!(
+        "ld 0 -> [static 0]",
+        fold("greater_eq_s 128, 127 -> [static 0]")
+    );
+    assert_eq!(
+        "ld 1 -> [static 0]",
+        fold("greater_w 1000, 300 -> [static 0]")
+    );
+    assert_eq!(
+        "ld 1 -> [static 0]",
+        fold("less_sw 65036, 200 -> [static 0]")
+    );
+    assert_eq!("ld 0 -> [static 0]", fold("add_c 255, 0, 3 -> [static 0]"));
+    assert_eq!(
+        "ld 1 -> [static 0]",
+        fold("add_carry 255, 0, 1 -> [static 0]")
+    );
+    assert_eq!("ld 254 -> [static 0]", fold("sub_c 0, 1, 1 -> [static 0]"));
+    assert_eq!(
+        "ld 0 -> [static 0]",
+        fold("sub_borrow 1, 0, 1 -> [static 0]")
+    );
+
+    // not every operand is known
+    assert_eq!("add r1, 2 -> r0", fold("add r1, 2 -> r0"));
+    assert_eq!("ld_addr [static 4] -> r0", fold("ld_addr [static 4] -> r0"));
+}
+
+#[test]
+fn overflow() {
+    let fold = |overflow: &str, statement: &str| {
+        let text = format!(
+            "overflow {}\nroutine 0 - stack 4 args 1 return 0\n{}\nstop success\nend",
+            overflow, statement
+        );
+        let text = run(
+            |ir| {
+                const_fold(ir);
+            },
+            &text,
+        );
+        text.lines().next().unwrap().to_string()
+    };
+    assert_eq!(
+        "ld 255 -> [static 0]",
+        fold("saturating", "add 200, 100 -> [static 0]")
+    );
+    assert_eq!(
+        "ld_w 0 -> [static 0]",
+        fold("saturating", "sub_w 1, 2 -> [static 0]")
+    );
+
+    // left to trap at runtime
+    let kept = |overflow, statement| assert_eq!(statement, fold(overflow, statement));
+    kept("trapping", "add 200, 100 -> [static 0]");
+    kept("wrapping", "div 1, 0 -> [static 0]");
+    kept("wrapping", "rem_w 1, 0 -> [static 0]");
+    kept("wrapping", "div_s 1, 0 -> [static 0]");
+    assert_eq!(
+        "ld 1 -> [static 0]",
+        fold("trapping", "add 0, 1 -> [static 0]")
+    );
+}
+
+#[test]
+fn propagation() {
+    let fold = |statements| {
+        run(
+            |ir| {
+                const_fold(ir);
+            },
+            statements,
+        )
+    };
+    let text = fold("ld 3 -> r0\nadd r0, 1 -> r1\nld r1 -> [static 0]\nstop success");
+    assert!(text.contains("ld 4 -> [static 0]"));
+    assert!(!text.contains("add"));
+    // registers in offsets and dereferences
+    let text = fold("ld 1 -> r0\nld_w 0xc000 -> r1\nld [static 2 + r0] -> *r1\nstop success");
+    assert!(text.contains("ld [static 2 + 1] -> *49152"));
+    // 8bit and 16bit registers are different registers
+    let text = fold("ld 3 -> r0\nadd_w r0, 1 -> [static 0]\nstop success");
+    assert!(text.contains("add_w r0, 1 -> [static 0]"));
+    // overwritten registers
+    let text = fold("ld 3 -> r0\nld [stack 0] -> r0\nadd r0, 1 -> [static 0]\nstop success");
+    assert!(text.contains("add r0, 1 -> [static 0]"));
+}
+
+#[test]
+fn const_memory() {
+    let fold = |statements| {
+        run(
+            |ir| {
+                const_fold(ir);
+            },
+            statements,
+        )
+    };
+    assert!(fold("ld [const 1] -> [static 0]\nstop success").contains("ld 52 -> [static 0]"));
+    assert!(fold("ld_w [const 0] -> [static 0]\nstop success").contains("ld_w 4660 -> [static 0]"));
+    let text = fold("ld 1 -> r0\nld [const 0 + r0] -> [static 0]\nstop success");
+    assert!(text.contains("ld 52 -> [static 0]"));
+    // out of bounds
+    assert_eq!("ld_w [const 1] -> r0", fold("ld_w [const 1] -> r0"));
+    // static memory is not constant
+    let text = fold("ld [static 0] -> r0\nld r0 -> [static 1]\nstop success");
+    assert!(text.contains("ld [static 0] -> r0"));
+}
+
+#[test]
+fn basic_blocks() {
+    let fold = |statements| {
+        run(
+            |ir| {
+                const_fold(ir);
+            },
+            statements,
+        )
+    };
+    // target of a jump
+    assert!(fold("ld 3 -> r0\nadd r0, 1 -> r1\njmp -2").contains("add r0, 1 -> r1"));
+    // calls
+    assert!(fold("ld 3 -> r0\ncall 0, 0..\nadd r0, 1 -> r1").contains("add r0, 1 -> r1"));
+    // folded conditions
+    let text = fold("eq 2, 2 -> r0\njmp_cmp 1, r0\nld 1 -> [static 0]\nstop success");
+    assert!(text.contains("jmp_cmp 1, 1"));
+}
+
+#[test]
+fn peephole_rules() {
+    let peephole = |statements| {
+        run(
+            |ir| {
+                Peephole::standard().run(ir);
+            },
+            statements,
+        )
+    };
+    // store back
+    let text =
+        peephole("ld [stack 0] -> r0\nld r0 -> [stack 0]\nadd r0, 1 -> [static 0]\nstop success");
+    assert!(!text.contains("-> [stack 0]"));
+    // self loads (but not of hardware registers)
+    assert_eq!(
+        "stop success",
+        peephole("ld_w r0 -> r0\nld [stack 1] -> [stack 1]\nstop success")
+    );
+    let text = peephole("ld [absolute 0xff40] -> [absolute 0xff40]\nstop success");
+    assert!(text.contains("ld [absolute 0xff40] -> [absolute 0xff40]"));
+    // add zero
+    let text = peephole("add 0, [stack 0] -> [static 1]\nstop success");
+    assert!(text.contains("ld [stack 0] -> [static 1]"));
+    let text = peephole("sub_w r1, 0 -> [static 0]\nstop success");
+    assert!(text.contains("ld_w r1 -> [static 0]"));
+    let text = peephole("sub 0, r1 -> [static 0]\nstop success");
+    assert!(text.contains("sub 0, r1 -> [static 0]"));
+    // double negation
+    let text = peephole("xor [stack 0], 255 -> r0\nxor r0, 255 -> [static 1]\nstop success");
+    assert!(!text.contains("xor"));
+    // forwarded loads
+    let text = peephole("ld_w [stack 0] -> r0\nld_w r0 -> r1\nld_w r1 -> [static 0]\nstop success");
+    assert!(text.contains("ld_w [stack 0] -> [static 0]"));
+    assert!(!text.contains("-> r"));
+}
+
+#[test]
+fn peephole_liveness() {
+    let peephole = |statements| {
+        run(
+            |ir| {
+                Peephole::standard().run(ir);
+            },
+            statements,
+        )
+    };
+    // the temporary register is read afterwards
+    let text =
+        peephole("ld [stack 0] -> r0\nld r0 -> [static 1]\nadd r0, 1 -> [static 2]\nstop success");
+    assert!(text.contains("ld [stack 0] -> r0"));
+    // ...or overwritten before being read
+    let text = peephole(
+        "ld [stack 0] -> r0\nld r0 -> [static 1]\nld 1 -> r0\nadd r0, 1 -> [static 2]\nstop success",
+    );
+    assert!(text.contains("ld [stack 0] -> [static 1]"));
+    // 8bit and 16bit registers are different registers
+    let text = peephole(
+        "ld [stack 0] -> r0\nld r0 -> [static 1]\nld_w 1 -> r0\nadd r0, 1 -> [static 2]\nstop success",
+    );
+    assert!(text.contains("ld [stack 0] -> r0"));
+    // read after a jump
+    let text = peephole(
+        "ld [stack 0] -> r0\nld r0 -> [stack 1]\njmp_cmp 1, [stack 1]\nld 2 -> r1\nld r0 -> [static 0]\nstop success",
+    );
+    assert!(text.contains("ld [stack 0] -> r0"));
+    // windows don't span the target of a jump
+    let text = peephole("ld [stack 0] -> r0\nld r0 -> [stack 1]\njmp -2");
+    assert!(text.contains("ld [stack 0] -> r0"));
+    // jumps over rewritten statements are updated
+    let text = peephole(
+        "ld 1 -> [stack 0]\njmp_cmp 2, [stack 0]\nld [stack 0] -> r0\nld r0 -> [static 1]\nstop success",
+    );
+    assert!(text.contains("jmp_cmp 1, [stack 0]"));
+}
+
+// `inc x -> r`, `dec r -> d` where r isn't read afterwards: `ld x -> d`.
+struct IncDec;
+
+impl Rule for IncDec {
+    fn window(&self) -> usize {
+        2
+    }
+
+    fn rewrite(&self, window: &[Statement], context: &Context<'_>) -> Option<Vec<Statement>> {
+        match window {
+            [Statement::Inc {
+                source,
+                destination: Destination::Register(register),
+            }, Statement::Dec {
+                source: Source::Register(r),
+                destination,
+            }] if r == register && context.is_dead_byte(*r) => Some(vec![Statement::Ld {
+                source: source.clone(),
+                destination: destination.clone(),
+            }]),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn peephole_custom_rule() {
+    let peephole = |statements| {
+        run(
+            |ir| {
+                Peephole::new().rule(IncDec).run(ir);
+            },
+            statements,
+        )
+    };
+    let text = peephole("inc [stack 0] -> r0\ndec r0 -> [static 1]\nstop success");
+    assert!(text.contains("ld [stack 0] -> [static 1]"));
+    assert!(!text.contains("inc"));
+    // not in the set
+    let text = peephole("add r0, 0 -> [static 1]\nstop success");
+    assert!(text.contains("add r0, 0 -> [static 1]"));
+}
+
+#[test]
+fn common_subexpressions() {
+    let cse = |statements| {
+        run(
+            |ir| {
+                cse(ir);
+            },
+            statements,
+        )
+    };
+    let text = cse(
+        "add_w [stack 0], 4 -> r0\nadd_w [stack 0], 4 -> r1\nadd_w [stack 0], 4 -> [static 0]\nstop success",
+    );
+    assert_eq!(1, text.matches("add_w").count());
+    let text = cse("ld [static 0 + r0] -> r1\nld [static 0 + r0] -> r2\nadd r1, r2 -> [static 4]\nstop success");
+    assert_eq!(1, text.matches("[static 0 + r0]").count());
+    // different operations, and operands of different width
+    let kept = |statements| assert_eq!(statements, cse(statements));
+    kept("add r0, 1 -> r1\nsub r0, 1 -> r2\nadd_w r0, 1 -> r3\nstop success");
+    // hardware registers
+    kept("ld [absolute 0xff44] -> r0\nld [absolute 0xff44] -> r1\nstop success");
+}
+
+#[test]
+fn common_subexpressions_invalidation() {
+    let kept = |statements| {
+        let text = run(
+            |ir| {
+                cse(ir);
+            },
+            statements,
+        );
+        assert_eq!(statements, text);
+    };
+    // overwritten operands
+    kept("add r0, 1 -> r1\nld 2 -> r0\nadd r0, 1 -> [static 0]\nstop success");
+    // overwritten results
+    kept("add r0, 1 -> r1\nld 2 -> r1\nadd r0, 1 -> [static 0]\nstop success");
+    kept("add r0, 1 -> r0\nadd r0, 1 -> [static 0]\nstop success");
+    // writes to the same memory space
+    kept("ld [stack 0] -> r0\nld 1 -> [stack 0]\nld [stack 0] -> [static 0]\nstop success");
+    // ...through a pointer
+    kept("ld [static 0] -> r0\nld 1 -> *r2\nld [static 0] -> [static 1]\nstop success");
+    // end of the basic block
+    kept("ld [stack 0] -> r0\ncall 0, 0..\nld [stack 0] -> r1");
+    kept("ld [stack 0] -> r0\nld [stack 0] -> r1\njmp -2");
+
+    // writes to a different memory space
+    let text = run(
+        |ir| {
+            cse(ir);
+        },
+        "ld [stack 0] -> r0\nld 1 -> [static 2]\nld [stack 0] -> [static 3]\nstop success",
+    );
+    assert!(text.contains("ld r0 -> [static 3]"));
+}
+
+#[test]
+fn copy_propagation_forward() {
+    let copies = |statements| {
+        run(
+            |ir| {
+                copy_propagation(ir);
+            },
+            statements,
+        )
+    };
+    let text = copies(
+        "add [stack 0], 1 -> r0\nld r0 -> r1\nld r1 -> r2\nadd r2, r0 -> [static 1]\nstop success",
+    );
+    assert!(text.contains("add r0, r0 -> [static 1]"));
+    assert!(!text.contains("ld r"));
+    // into offsets and dereferences
+    let text = copies(
+        "ld [stack 0] -> r0\nld_w [stack 2] -> r5\nld r0 -> r1\nld_w r5 -> r6\nld [static 0 + r1] -> *r6\nstop success",
+    );
+    assert!(text.contains("ld [static 0 + r0] -> *r0"));
+    assert!(!text.contains("ld r"));
+    assert!(!text.contains("ld_w r"));
+    // copied register overwritten
+    let text =
+        "ld [stack 0] -> r0\nld r0 -> r1\nld 1 -> r0\nadd r1, r0 -> [static 1]\nstop success";
+    assert_eq!(text, copies(text));
+}
+
+#[test]
+fn copy_propagation_dead_loads() {
+    let copies = |statements| {
+        run(
+            |ir| {
+                copy_propagation(ir);
+            },
+            statements,
+        )
+    };
+    let text =
+        copies("ld [stack 0] -> r0\nld_addr [static 0] -> r1\nld 1 -> [stack 0]\nstop success");
+    assert!(!text.contains("-> r"));
+    // hardware registers
+    let text =
+        copies("ld [absolute 0xff00] -> r0\nld_w [stack 2] -> r1\nld *r1 -> r2\nstop success");
+    assert!(text.contains("ld [absolute 0xff00] -> r0"));
+    assert!(text.contains("ld *r0 -> r1"));
+    // read after a jump
+    let text = "ld [stack 0] -> r0\njmp_cmp 1, [stack 1]\nld 1 -> [static 2]\nld r0 -> [static 3]\nstop success";
+    assert_eq!(text, copies(text));
+}
+
+#[test]
+fn copy_propagation_coalesce() {
+    let copies = |statements| {
+        run(
+            |ir| {
+                copy_propagation(ir);
+            },
+            statements,
+        )
+    };
+    // copies to another basic block
+    let text = copies(
+        "ld [stack 0] -> r3\nld r3 -> r5\njmp 0\ninc r5 -> r5\nld r5 -> [static 1]\nstop success",
+    );
+    assert!(text.contains("inc r0 -> r0"));
+    assert!(!text.contains("-> r1"));
+    // registers alive at the same time
+    let text = copies(
+        "ld [stack 0] -> r3\nld r3 -> r5\njmp 0\ninc r3 -> r3\nadd r3, r5 -> [static 1]\nstop success",
+    );
+    assert!(text.contains("ld r0 -> r1"));
+    // 8bit and 16bit registers are numbered on their own
+    let text = copies("ld [stack 0] -> r4\nld_w [stack 2] -> r7\nld r4 -> *r7\nstop success");
+    assert!(text.contains("ld r0 -> *r0"));
+}
+
+#[test]
+fn inline_calls() {
+    // the called routines begin with a `nop`, like the compiled ones
+    let program = "routine 0 - stack 2 args 0 return 0
+        ld 5 -> r0
+        ld 1 -> [stack 1]
+        call 1, 1..
+        add r0, [return 0] -> [static 0]
+        stop success
+    end
+    routine 1 - stack 2 args 1 return 1
+        nop 0
+        add [stack 0], 1 -> r0
+        ld r0 -> [return 0]
+        ret
+    end";
+    let stack_size = Cell::new(0);
+    let text = run(
+        |ir| {
+            assert!(inline(ir, 4));
+            assert!(!inline(ir, 4));
+            stack_size.set(ir.main().stack_size);
+        },
+        program,
+    );
+    assert!(!text.contains("call"));
+    // r0 of the caller is alive across the call
+    assert!(text.contains("add [stack 1], 1 -> r1"));
+    assert!(text.contains("add r0, [return 0] -> [static 0]"));
+    assert_eq!(3, stack_size.get());
+}
+
+#[test]
+fn inline_jumps() {
+    // returns jump past the inlined statements, and the jumps of the caller
+    // over the call are moved
+    let text = run(
+        |ir| {
+            inline(ir, 5);
+        },
+        "routine 0 - stack 2 args 0 return 0
+            ld 1 -> [stack 1]
+            jmp_cmp 2, [stack 0]
+            call 1, 1..
+            ld 1 -> [static 0]
+            stop success
+        end
+        routine 1 - stack 1 args 1 return 0
+            nop 0
+            jmp_cmp 1, [stack 0]
+            ret
+            ld 2 -> [static 1]
+            ret
+        end",
+    );
+    assert!(!text.contains("call"));
+    assert!(text.contains("jmp_cmp 5, [stack 0]"));
+    assert!(text.contains("jmp_cmp 1, [stack 1]\njmp 1"));
+}
+
+#[test]
+fn inline_hints() {
+    let text = run(
+        |ir| {
+            inline(ir, 2);
+        },
+        "routine 0 - stack 0 args 0 return 0
+            call 1, 0..
+            call 2, 0..
+            call 3, 0..
+            call 0, 0..
+            call 4, 0..
+            ret
+        end
+        routine 1 - stack 0 args 0 return 0 inline never
+            ld 1 -> [static 0]
+            ret
+        end
+        routine 2 - stack 0 args 0 return 0
+            ld 1 -> [static 0]
+            ld 2 -> [static 1]
+            ret
+        end
+        routine 3 - stack 0 args 0 return 0 inline always
+            ld 3 -> [static 0]
+            ld 4 -> [static 1]
+            ret
+        end
+        routine 4 - stack 0 args 0 return 0 bank 1
+            ld 5 -> [static 0]
+            ret
+        end",
+    );
+    // never inlined, over the threshold, recursive, and in another bank
+    assert!(text.contains("call 1, 0.."));
+    assert!(text.contains("call 2, 0.."));
+    assert!(text.contains("call 0, 0.."));
+    assert!(text.contains("call 4, 0.."));
+    // always inlined
+    assert!(!text.contains("call 3"));
+    assert!(text.contains("ld 3 -> [static 0]\nld 4 -> [static 1]"));
+
+    let ast = parse("#[inline] fn f() { } #[inline(never)] fn g() { } fn h() { }").unwrap();
+    let ir: Ir<BigEndian> = Ir::new(&ast);
+    let inline = |name: &str| {
+        let routine = ir
+            .routines
+            .iter()
+            .find(|r| r.debug_name.as_deref() == Some(name));
+        routine.unwrap().inline
+    };
+    assert_eq!(Inline::Always, inline("f"));
+    assert_eq!(Inline::Never, inline("g"));
+    assert_eq!(Inline::Auto, inline("h"));
+}
+
+#[test]
+fn loop_invariants() {
+    let hoisted = |statements| {
+        run(
+            |ir| {
+                licm(ir);
+            },
+            statements,
+        )
+    };
+    // the statement is moved above the first statement of the loop
+    let above = |text: &str, statement: &str, first: &str| {
+        assert!(text.find(statement).unwrap() < text.find(first).unwrap());
+    };
+    // header of the loop
+    let text = hoisted(
+        "ld 0 -> [stack 0]\nadd [static 0], 1 -> r0\nadd [stack 0], r0 -> [stack 0]\njmp_cmp_not -3, [stack 0]\nld [stack 0] -> [static 1]\nstop success",
+    );
+    assert!(text.contains("jmp_cmp_not -2, [stack 0]"));
+    // address computations
+    let text = hoisted(
+        "ld 0 -> [stack 0]\ninc [stack 0] -> [stack 0]\nld_addr [static 4 + r2] -> r0\nld [stack 0] -> *r0\njmp_cmp -4, [stack 0]\nstop success",
+    );
+    above(&text, "ld_addr", "inc");
+    // invariants of the inner loop, that are invariants of the outer one
+    let text = hoisted(
+        "inc [stack 0] -> [stack 0]\nld [static 0] -> r0\nadd [stack 1], r0 -> [stack 1]\njmp_cmp -3, [stack 1]\njmp_cmp -5, [stack 0]\nstop success",
+    );
+    above(&text, "ld [static 0] -> r0", "inc");
+}
+
+#[test]
+fn loop_variants() {
+    let hoisted = |overflow, statements| {
+        let text = format!(
+            "overflow {}\nroutine 0 - stack 4 args 1 return 0\n{}\nend",
+            overflow, statements
+        );
+        run(
+            |ir| {
+                licm(ir);
+            },
+            &text,
+        )
+    };
+    let kept = |statements| assert_eq!(statements, hoisted("wrapping", statements));
+    // memory written by the loop
+    kept("ld [static 0] -> r0\nadd r0, 1 -> [static 1]\njmp -3");
+    kept("ld [static 0] -> r0\nld r0 -> [stack 0]\ncall 0, 2..\njmp -4");
+    // registers written by the loop
+    kept("ld [stack 1] -> r0\nadd r0, 1 -> r1\nld r1 -> [stack 1]\njmp -4");
+    kept("ld [static 0] -> r0\nld r0 -> [stack 0]\nld 1 -> r0\njmp -4");
+    // registers alive at the header, and after the loop
+    kept("add r0, 1 -> [stack 0]\nld [static 0] -> r0\njmp -3");
+    kept("ld [static 0] -> r0\njmp_cmp -2, [stack 0]\nld r0 -> [static 1]\nstop success");
+    // loops entered through a jump
+    kept("jmp 1\nld [static 0] -> r0\nld r0 -> [stack 0]\njmp -3");
+    // reads with side effects
+    kept("ld [absolute 0xff00] -> r0\nld r0 -> [stack 0]\njmp -3");
+    kept("ld *r0 -> r1\nld r1 -> [stack 0]\njmp -3");
+    // operations that may trap
+    kept("div [static 0], 2 -> r0\nld r0 -> [stack 0]\njmp -3");
+    let text = "add [static 0], 1 -> r0\nld r0 -> [stack 0]\njmp -3";
+    assert_eq!(text, hoisted("trapping", text));
+    assert_ne!(text, hoisted("wrapping", text));
+}
+
+#[test]
+fn strength() {
+    // r0 holds a known value, so the VM checks the products
+    let reduced = |overflow, statement| {
+        let text = format!(
+            "overflow {}\nroutine 0 - stack 4 args 1 return 0\nld 7 -> r0\nld_w 7 -> r0\n{}\nstop success\nend",
+            overflow, statement
+        );
+        run(
+            |ir| {
+                strength_reduction(ir);
+            },
+            &text,
+        )
+    };
+    let reduced = |statement| reduced("wrapping", statement);
+    assert!(reduced("mul r0, 0 -> [static 0]").contains("ld 0 -> [static 0]"));
+    assert!(reduced("mul 1, [stack 0] -> [static 0]").contains("ld [stack 0] -> [static 0]"));
+    assert!(reduced("mul r0, 8 -> [static 0]").contains("left_shift r0, 3 -> [static 0]"));
+    assert!(!reduced("mul r0, 3 -> [static 0]").contains("mul"));
+    assert!(!reduced("mul r0, 10 -> [static 0]").contains("mul"));
+    assert!(!reduced("mul_w r0, 10 -> [static 0]").contains("mul"));
+    // operands read from memory are read once
+    let text = reduced("mul_w [stack 0], 7 -> [static 0]");
+    assert!(!text.contains("mul"));
+    assert_eq!(1, text.matches("[stack 0]").count());
+    // too many bits set
+    assert!(reduced("mul r0, 15 -> [static 0]").contains("mul"));
+    assert!(reduced("mul r0, r0 -> [static 0]").contains("mul"));
+    // jumps over the multiplication
+    let text = reduced("jmp_cmp 1, [stack 1]\nmul r0, 3 -> r1\nld r1 -> [static 0]");
+    assert!(!text.contains("mul"));
+    assert!(text.contains("jmp_cmp 2, [stack 1]"));
+    // shifts don't overflow like the multiplication
+    let text = run(
+        |ir| {
+            strength_reduction(ir);
+        },
+        "overflow saturating\nroutine 0 - stack 4 args 1 return 0\nld 40 -> r0\nmul r0, 8 -> [static 0]\nstop success\nend",
+    );
+    assert!(text.contains("mul r0, 8 -> [static 0]"));
+    let text = run(
+        |ir| {
+            strength_reduction(ir);
+        },
+        "overflow trapping\nroutine 0 - stack 4 args 1 return 0\nld 7 -> r0\nmul r0, 1 -> [static 0]\nstop success\nend",
+    );
+    assert!(text.contains("ld r0 -> [static 0]"));
+}
+
+#[test]
+fn branches_threading() {
+    let simplified = |statements| {
+        run(
+            |ir| {
+                simplify_branches(ir);
+            },
+            statements,
+        )
+    };
+    // jumps to jumps
+    let text = simplified(
+        "jmp_cmp 1, r0\nld 1 -> [static 0]\njmp 1\nld 2 -> [static 0]\nld 3 -> [static 1]\nstop success",
+    );
+    assert!(!text.contains("jmp 1"));
+    assert!(!text.contains("ld 2"));
+    let text =
+        simplified("jmp_cmp 2, r0\nld 1 -> [static 0]\nret\njmp -2\nld 2 -> [static 0]\nret");
+    assert!(text.contains("jmp_cmp 1, r0"));
+    assert!(!text.contains("jmp -2"));
+    // jumps to ret and stop
+    let text = simplified("ld 1 -> [static 0]\njmp 2\nld 2 -> [static 0]\nret\nstop success");
+    assert!(!text.contains("jmp"));
+    // nested if/else
+    let text = simplified(
+        "ld 1 -> r1\njmp_cmp_not 3, r0\njmp_cmp_not 1, r1\njmp 2\njmp 1\nld 1 -> [static 0]\nld 2 -> [static 1]\nstop success",
+    );
+    assert_eq!(3, text.matches("jmp").count());
+    assert!(!text.contains("jmp 2"));
+}
+
+#[test]
+fn branches_folding() {
+    let simplified = |statements| {
+        run(
+            |ir| {
+                simplify_branches(ir);
+            },
+            statements,
+        )
+    };
+    let text = simplified("jmp_cmp 1, 0\nld 1 -> [static 0]\nld 2 -> [static 1]\nstop success");
+    assert!(!text.contains("jmp"));
+    assert!(text.contains("ld 1 -> [static 0]"));
+    let text = simplified("jmp_cmp 1, 2\nld 1 -> [static 0]\nld 2 -> [static 1]\nstop success");
+    assert!(!text.contains("jmp"));
+    assert!(!text.contains("ld 1"));
+    let text = simplified("jmp_cmp_not 1, 0\nld 1 -> [static 0]\nld 2 -> [static 1]\nstop success");
+    assert!(!text.contains("jmp"));
+    assert!(!text.contains("ld 1"));
+    // jumps to the first statement
+    let text = simplified("ld 1 -> [static 0]\njmp_cmp_not 0, 1\njmp -3");
+    assert!(!text.contains("jmp_cmp"));
+    assert!(text.contains("jmp -2"));
+}
+
+#[test]
+fn branches_inversion() {
+    let simplified = |statements| {
+        run(
+            |ir| {
+                simplify_branches(ir);
+            },
+            statements,
+        )
+    };
+    let text = simplified(
+        "ld 1 -> r0\njmp_cmp 1, r0\njmp 1\nld 1 -> [static 0]\nld 2 -> [static 1]\nstop success",
+    );
+    assert!(text.contains("jmp_cmp_not 1, r0"));
+    assert!(!text.contains("jmp 1"));
+    // jumps to the next statement
+    let text = simplified("jmp 0\nld 1 -> [static 0]\njmp_cmp 0, r0\nstop success");
+    assert!(!text.contains("jmp"));
+    // reads with side effects
+    let text = "jmp_cmp 0, [absolute 0xff00]\nstop success";
+    assert_eq!(text, simplified(text));
+}
+
+#[test]
+fn branches_merging() {
+    let simplified = |statements| {
+        run(
+            |ir| {
+                simplify_branches(ir);
+            },
+            statements,
+        )
+    };
+    let text = simplified(
+        "ld 1 -> [static 0]\njmp 2\nld 2 -> [static 0]\nstop success\nld 3 -> [static 1]\njmp -4",
+    );
+    assert!(!text.contains("jmp"));
+    // blocks reached by falling through
+    let text = "jmp_cmp 2, r0\nld 1 -> [static 0]\njmp 1\nld 2 -> [static 0]\nld 3 -> [static 1]\nstop success";
+    assert_eq!(text, simplified(text));
+    // loops
+    let text = "ld 1 -> [static 0]\njmp -2";
+    assert_eq!(text, simplified(text));
+}
+
+// custom pass, that removes the first statement of the main routine.
+struct RemoveFirst;
+
+impl<B: ByteOrder> IrPass<B> for RemoveFirst {
+    fn name(&self) -> &str {
+        "remove_first"
+    }
+
+    fn run(&self, ir: &mut Ir<B>) -> bool {
+        let statements = &mut ir.routines[ir.handlers.main].statements;
+        if statements.len() > 1 {
+            statements.remove(0);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn pipeline_levels() {
+    let names = |level| Pipeline::<BigEndian>::level(level).names().join(" ");
+    assert_eq!("", names(OptLevel::O0));
+    assert_eq!(
+        "const_fold cse copy_propagation simplify_branches peephole",
+        names(OptLevel::O1)
+    );
+    assert_eq!(
+        "inline const_fold cse strength_reduction licm copy_propagation simplify_branches peephole",
+        names(OptLevel::O2)
+    );
+
+    let text = run(
+        |ir| {
+            assert!(Pipeline::level(OptLevel::O0).run(ir).is_empty());
+            Pipeline::level(OptLevel::O2).run(ir);
+        },
+        "ld 2 -> r0\nmul r0, 4 -> [static 0]\nstop success",
+    );
+    assert_eq!("ld 8 -> [static 0]\nstop success", text);
+}
+
+#[test]
+fn pipeline_stats() {
+    let text =
+        "routine 0 - stack 2 args 0 return 0\nld 1 -> [static 0]\njmp 0\nld 2 -> [static 1]\nend";
+    let mut ir: Ir<BigEndian> = parse_text(text).unwrap();
+    let stats = Pipeline::new()
+        .pass(RemoveFirst)
+        .pass(Peephole::standard())
+        .rounds(4)
+        .run(&mut ir);
+    let runs: Vec<_> = stats
+        .iter()
+        .map(|s| (s.name.as_str(), s.round, s.changed))
+        .collect();
+    // the passes stop once a round doesn't change the program
+    assert_eq!(
+        vec![
+            ("remove_first", 0, true),
+            ("peephole", 0, false),
+            ("remove_first", 1, true),
+            ("peephole", 1, false),
+            ("remove_first", 2, false),
+            ("peephole", 2, false),
+        ],
+        runs
+    );
+    assert_eq!(
+        Stats {
+            statements: 3,
+            jumps: 1,
+            calls: 0
+        },
+        stats[0].before
+    );
+    assert_eq!(
+        Stats {
+            statements: 2,
+            jumps: 1,
+            calls: 0
+        },
+        stats[0].after
+    );
+    assert_eq!(stats[2].after, stats[5].after);
+}
+
+#[test]
+fn registers_allocation() {
+    // the routine has a stack of 4 bytes, and one byte of arguments
+    let allocated = |registers, statements| {
+        let stack_size = Cell::new(0);
+        let text = run(
+            |ir| {
+                allocate_registers(ir, registers);
+                stack_size.set(ir.main().stack_size);
+            },
+            statements,
+        );
+        assert_eq!(4, stack_size.get());
+        text
+    };
+    // registers never alive at the same time
+    let text = allocated(
+        4,
+        "ld 1 -> r3\nld r3 -> [static 0]\nld 2 -> r5\nld r5 -> [static 1]\nstop success",
+    );
+    assert_eq!(2, text.matches("-> r0").count());
+    let text = allocated(
+        4,
+        "ld 1 -> r3\nld 2 -> r5\nadd r3, r5 -> [static 0]\nstop success",
+    );
+    assert!(text.contains("add r0, r1 -> [static 0]"));
+    // registers of different widths
+    let text = allocated(
+        1,
+        "ld 1 -> r1\nld_w 2 -> r2\nadd_w r2, 1 -> [static 0]\nld r1 -> [static 2]\nstop success",
+    );
+    assert!(text.contains("add_w r0, 1 -> [static 0]"));
+    assert!(text.contains("ld r0 -> [static 2]"));
+    let text = "ld 1 -> r0\nld 2 -> r1\nadd r0, r1 -> [static 0]\nstop success";
+    assert_eq!(text, allocated(2, text));
+}
+
+#[test]
+fn registers_spilling() {
+    let allocated = |statements| {
+        let stack_size = Cell::new(0);
+        let text = run(
+            |ir| {
+                allocate_registers(ir, 1);
+                stack_size.set(ir.main().stack_size);
+            },
+            statements,
+        );
+        (text, stack_size.get())
+    };
+    // the slots go after the arguments
+    let (text, stack_size) = allocated(
+        "ld [stack 1] -> r0\nld [stack 0] -> r1\nadd r0, r1 -> [stack 1]\nld_addr [stack 1] -> r2\nld_w r2 -> [static 0]\ncall 0, 2..",
+    );
+    assert!(text.contains("ld [stack 0] -> [stack 1]"));
+    assert!(text.contains("ld [stack 2] -> r0"));
+    assert!(text.contains("ld_addr [stack 2] -> r0"));
+    assert!(text.contains("call 0, 3.."));
+    assert_eq!(5, stack_size);
+    // 16bit slots
+    let (text, stack_size) = allocated(
+        "ld_w 1 -> r0\nld_w 2 -> r1\nld_w 3 -> r2\nadd_w r0, r1 -> r0\nadd_w r0, r2 -> [static 0]\nstop success",
+    );
+    assert!(text.contains("ld_w 2 -> [stack 1]"));
+    assert!(text.contains("ld_w 3 -> [stack 3]"));
+    assert_eq!(8, stack_size);
+}
+
+#[test]
+fn registers_copies() {
+    let allocated = |statements| {
+        run(
+            |ir| {
+                allocate_registers(ir, 4);
+            },
+            statements,
+        )
+    };
+    // copies whose registers are given the same number are removed
+    let text = allocated("ld [stack 0] -> r4\nld r4 -> r7\nadd r7, 1 -> [static 0]\nstop success");
+    assert!(!text.contains("ld r0 -> r"));
+    let text = allocated(
+        "ld [stack 0] -> r4\nld r4 -> r7\nadd r7, 1 -> [static 0]\nld r4 -> [static 1]\nstop success",
+    );
+    assert!(!text.contains("ld r0 -> r"));
+    assert!(text.contains("ld r0 -> [static 1]"));
+    // the copy is written while the register it copies is alive
+    let text =
+        "ld [stack 0] -> r0\nld r0 -> r1\ninc r1 -> r1\nadd r0, r1 -> [static 0]\nstop success";
+    assert_eq!(text, allocated(text));
+}
+
+#[test]
+fn registers_compiled() {
+    // the compiler gives each value its own register, and then allocates them
+    let input = "static R:u8
+let x:u8 = 1
+let y:u8 = 2
+(= R (+ (+ x y) (* (+ x y) (- x y))))
+(= R (& (+ x 1) (+ y 1)))";
+    let ir: Ir<BigEndian> = Ir::new(&parse(input).unwrap());
+    let statements: Vec<_> = ir.main().statements.iter().map(|s| s.to_string()).collect();
+    let text = statements.join("\n");
+    assert!(text.contains("mul r1, r2 -> r1"));
+    assert!(!text.contains("r3"));
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!(2, memory.static_[0]);
+}
@@ -0,0 +1,112 @@
+use ir::{
+    byteorder::NativeEndian,
+    parse_text,
+    parser::{lex::span::Span, parse},
+    Ir,
+};
+
+const PROGRAM: &str = "static A:u8
+let a:u8 = 2
+if (== a 2) {
+    (= A (+ a 1))
+}
+loop { break }";
+
+fn ir(input: &str) -> Ir<NativeEndian> {
+    Ir::new(&parse(input).unwrap())
+}
+
+fn span(min: [usize; 2], max: [usize; 2]) -> Option<Span> {
+    Some(Span {
+        min,
+        max,
+        ..Span::default()
+    })
+}
+
+#[test]
+fn statements() {
+    let ir = ir(PROGRAM);
+    let main = ir.main();
+    assert_eq!(main.statements.len(), main.spans.len());
+
+    let let_ = span([1, 0], [1, 12]);
+    let if_ = span([2, 0], [4, 1]);
+    let assign = span([3, 4], [3, 17]);
+    let break_ = span([5, 7], [5, 12]);
+    let loop_ = span([5, 0], [5, 14]);
+    #[rustfmt::skip]
+    assert_eq!(
+        vec![
+            None,          // nop
+            let_,          // ld 2 -> [stack 0]
+            if_, if_,      // eq, jmp_cmp_not
+            assign, assign,
+            break_,
+            loop_,         // jump back to the start of the loop
+            None,          // stop
+        ],
+        main.spans
+    );
+    assert_eq!(assign, main.span(4));
+    assert_eq!(None, main.span(100));
+}
+
+#[test]
+fn optimize() {
+    let mut ir = ir(PROGRAM);
+    let unoptimized = ir.main().statements.len();
+    ir.optimize();
+    let main = ir.main();
+    // the jump back to the start of the loop is unreachable
+    assert!(main.statements.len() < unoptimized);
+    assert_eq!(main.statements.len(), main.spans.len());
+    assert_eq!(span([3, 4], [3, 17]), main.span(4));
+}
+
+#[test]
+fn functions() {
+    let program = ir("static A:u8\nfn f(x:u8) {\n    (= A x)\n}\n(f 2)");
+    let f = &program.routines[0];
+    assert_eq!(None, f.span(0));
+    assert_eq!(span([2, 4], [2, 11]), f.span(1));
+    assert_eq!(span([4, 0], [4, 5]), program.main().span(1));
+
+    // compiler generated routines have no debug info
+    let banked = ir("#[bank(1)] fn f {}\n(f)");
+    assert!(banked.routines.iter().any(|r| r.spans.is_empty()));
+}
+
+#[test]
+fn text() {
+    let ir = ir(PROGRAM);
+    let text = ir.to_string();
+    assert!(text.contains("ld 2 -> [stack 0] @ 1:0..1:12\n"));
+    assert_eq!(ir, parse_text(&text).unwrap());
+
+    let text = "routine 0 - stack 0 args 0 return 0
+        nop 0
+        stop success @ 2:4..2:6 source 1
+    end";
+    let ir: Ir<NativeEndian> = parse_text(text).unwrap();
+    let span = ir.main().span(1).unwrap();
+    assert_eq!(
+        ([2, 4], [2, 6], 1),
+        (span.min, span.max, span.source.index())
+    );
+    assert_eq!(None, ir.main().span(0));
+    assert!(ir
+        .to_string()
+        .contains("stop success @ 2:4..2:6 source 1\n"));
+
+    // statements without spans
+    let ir: Ir<NativeEndian> = parse_text("routine 0 - stack 0 args 0 return 0\nret\nend").unwrap();
+    assert!(ir.main().spans.is_empty());
+}
+
+#[test]
+fn binary() {
+    let ir = ir(PROGRAM);
+    let decoded = Ir::<NativeEndian>::from_bytes(&ir.to_bytes()).unwrap();
+    assert_eq!(ir.main().spans, decoded.main().spans);
+}
@@ -0,0 +1,40 @@
+use ir::{byteorder::NativeEndian, parser::parse, Ir};
+
+fn ir(input: &str) -> Ir<NativeEndian> {
+    let ast = parse(input).unwrap();
+    Ir::new(&ast)
+}
+
+#[test]
+fn report() {
+    let ir = ir(r#"
+    fn b(x:u8):u8 { return x }
+    fn a(x:u8):u8 {
+        let t:u8 = (b x)
+        return t
+    }
+    fn@vblank on_vblank { }
+    let r:u8 = (a 1)
+    "#);
+    let report = ir.stack_report().unwrap();
+    // main, then the vblank handler
+    assert_eq!(2, report.len());
+    assert_eq!(ir.handlers.main, report[0].chain[0]);
+    assert_eq!(ir.handlers.vblank, Some(report[1].chain[0]));
+    assert!(report.iter().all(|depth| !depth.indirect));
+}
+
+#[test]
+fn recursion() {
+    let ir = ir("fn f() { (f) }\n(f)");
+    let error = ir.stack_report().unwrap_err();
+    assert_eq!(vec!["f"], error.cycle);
+    assert_eq!("Recursive call chain: f -> f", error.to_string());
+}
+
+#[test]
+fn function_pointers() {
+    let ir = ir("fn f() { }\nstatic F:fn()\n(= F @f)\n(F)");
+    let depth = ir.stack_depth(ir.handlers.main).unwrap();
+    assert!(depth.indirect);
+}
@@ -0,0 +1,590 @@
+use ir::{byteorder::NativeEndian, parser::parse, Ir};
+use vm::{Machine, Opts};
+
+fn _test_static(input: &str, gt: &[u8]) {
+    let ast = parse(input).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let result = &Machine::new(&ir, Opts::default()).run().static_[..gt.len()];
+    assert_eq!(gt, result);
+}
+
+#[test]
+fn test_static() {
+    _test_static(
+        r#"
+    static s0:u8 (= s0 0)
+    static s1:u8 (= s1 1)
+    static s2:u8 (= s2 2)
+    {
+        static s3:u8 (= s3 3)
+        static s4:u8 (= s4 4)
+        static s5:u8 (= s5 5)
+        {
+            static s6:u8 (= s6 6)
+            static s7:u8 (= s7 7)
+            {
+                static s8:u8 (= s8 8)
+            }
+            static s9:u8 (= s9 9)
+        }
+        static s10:u8 (= s10 10)
+    }
+    static s11:u8 (= s11 11)
+    {
+        static s12:u8 (= s12 12)
+        static s13:u8 (= s13 13)
+        {
+            static s14:u8 (= s14 14)
+        }
+        static s15:u8 (= s15 15)
+    }
+    "#,
+        (0..=15).collect::<Vec<_>>().as_ref(),
+    )
+}
+
+#[test]
+fn test_static_fn() {
+    _test_static(
+        r#"
+    static s0:u8 (= s0 0)
+    static s1:u8 (= s1 1)
+    static s2:u8 (= s2 2)
+    fn foo {
+        static s3:u8 (= s3 3)
+        static s4:u8 (= s4 4)
+        static s5:u8 (= s5 5)
+        {
+            static s6:u8 (= s6 6)
+            static s7:u8 (= s7 7)
+            {
+                static s8:u8 (= s8 8)
+            }
+            static s9:u8 (= s9 9)
+        }
+        static s10:u8 (= s10 10)
+    }
+    static s11:u8 (= s11 11)
+    fn bar {
+        static s12:u8 (= s12 12)
+        static s13:u8 (= s13 13)
+        {
+            static s14:u8 (= s14 14)
+        }
+        static s15:u8 (= s15 15)
+    }
+    (foo)
+    (bar)
+    "#,
+        (0..=15).collect::<Vec<_>>().as_ref(),
+    )
+}
+
+#[test]
+fn test_static_word() {
+    _test_static(
+        r#"
+    const K:u16 = 0x1234
+    static a:u16
+    static b:i16
+    static c:u16
+    (= a 0x1ff)
+    (= b (+ a K))
+    (= c (& b 0xff00))
+    (-= c 1)
+    let d:u16 = (+ c a)
+    (= a d)
+    "#,
+        &[0xfe, 0x15, 0x33, 0x14, 0xff, 0x13],
+    )
+}
+
+#[test]
+fn test_static_logical() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static c:bool
+    static d:bool
+    static e:bool
+    static f:u8
+    (= a 0)
+    (= b 3)
+    (= c (|| a b))
+    (= d (&& a b))
+    (= e (&& b true))
+    if (|| (== a 1) (&& (== b 3) (~= a b))) { (= f 42) }
+    "#,
+        &[0, 3, 1, 0, 1, 42],
+    )
+}
+
+#[test]
+fn test_static_enum() {
+    _test_static(
+        r#"
+    enum Dir { Up Down Left Right }
+    static dir:Dir
+    static flag:u8
+    (= dir Dir::Left)
+    if (== dir Dir::Left) { (= flag 1) }
+    "#,
+        &[2, 1],
+    )
+}
+
+#[test]
+fn test_static_match() {
+    _test_static(
+        r#"
+    enum Dir { Up Down Left Right }
+    static out:[u8 12]
+    static w:u16
+    static word:[u8 4]
+    for i:u8 in 0..12 {
+        match i {
+            0 { (= ([i]out) 0xa) }
+            Dir::Down { (= ([i]out) 0xb) }
+            2..4 { (= ([i]out) 0xc) }
+            4..=5 { (= ([i]out) 0xd) }
+            8..+2 { }
+            else { (= ([i]out) 0xe) }
+        }
+    }
+    (= w 0x1234)
+    match w {
+        0x34 { (= ([0]word) 1) }
+        0x1200..0x1234 { (= ([0]word) 2) }
+        0x1234 { (= ([0]word) 3) }
+    }
+    match w {
+        0x1000..=0x1233 { (= ([1]word) 1) }
+        0x1235..0x2000 { (= ([1]word) 2) }
+        else { (= ([1]word) 3) }
+    }
+    match w {
+        0..0x1234 { (= ([2]word) 1) }
+        0x1234..+0x100 { (= ([2]word) 2) }
+    }
+    match Dir::Left {
+        Dir::Left { (= ([3]word) 4) }
+        else { (= ([3]word) 5) }
+    }
+    "#,
+        &[
+            0xa, 0xb, 0xc, 0xc, 0xd, 0xd, 0xe, 0xe, 0, 0, 0xe, 0xe, 0x34, 0x12, 3, 3, 2, 4,
+        ],
+    )
+}
+
+#[test]
+fn test_static_match_table() {
+    // dense arms, dispatched by a jump table
+    _test_static(
+        r#"
+    static out:[u8 12]
+    for i:u8 in 0..12 {
+        match i {
+            3 { (= ([i]out) 0xa) }
+            4..6 { (= ([i]out) 0xb) }
+            5 { (= ([i]out) 0xc) }
+            7 { }
+            8..=9 { (= ([i]out) 0xd) }
+            else { (= ([i]out) 0xe) }
+        }
+    }
+    "#,
+        &[0xe, 0xe, 0xe, 0xa, 0xb, 0xb, 0xe, 0, 0xd, 0xd, 0xe, 0xe],
+    )
+}
+
+#[test]
+fn test_static_while() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static c:u8
+    while (< a 10) {
+        (+= a 1)
+        if (== (& a 1) 0) { continue }
+        (+= b 1)
+    }
+    while false { (= c 1) }
+    while true {
+        (+= c 2)
+        if (> c 5) { break }
+    }
+    "#,
+        &[10, 5, 6],
+    )
+}
+
+#[test]
+fn test_static_increment() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static w:u16
+    static arr:[u8 2]
+    (++ a) (++ a)
+    (-- b)
+    (= w 0xff)
+    (++ w)
+    (-- ([1] arr))
+    (++ ([a] arr))
+    "#,
+        &[2, 0xff, 0x00, 0x01, 0x00, 0xff],
+    )
+}
+
+#[test]
+fn test_static_sizeof() {
+    _test_static(
+        r#"
+    enum Dir { Left Right }
+    static arr:[u8 5]
+    static a:u8
+    static b:u8
+    static c:u8
+    static d:u8
+    static e:u8
+    static f:u16
+    (= a (sizeof arr))
+    (= b (sizeof u16))
+    (= c (sizeof [struct { x:u8 y:&u8 } 3]))
+    (= d (+ (sizeof (as a u16)) (sizeof Dir)))
+    (= e (sizeof f))
+    (= f (sizeof [u8 0x100]))
+    "#,
+        &[0, 0, 0, 0, 0, 5, 2, 9, 3, 2, 0x00, 0x01],
+    )
+}
+
+#[test]
+fn test_static_cast() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:i8
+    static w:u16
+    static x:i16
+    static c:u8
+    static d:u8
+    (= a 0xf0)
+    (= b (as 0x1fe i8))
+    (= w (as a u16))
+    (= x (as b i16))
+    (= c (as x u8))
+    (= d (as (as a i8) i16))
+    "#,
+        &[0xf0, 0xfe, 0xf0, 0x00, 0xfe, 0xff, 0xfe, 0xf0],
+    )
+}
+
+#[test]
+fn test_static_conditional() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static c:u8
+    static w:u16
+    (= a 3)
+    (= b (if (> a 2) (+ a 1) 0))
+    (= c (+ (if (== a 0) 1 2) 10))
+    (= w (if (== b 4) 0x1234 0))
+    (if (== a 3) (++ a) (-- a))
+    "#,
+        &[4, 4, 12, 0x34, 0x12],
+    )
+}
+
+#[test]
+fn test_static_for() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static c:u8
+    static d:u8
+    static n:u8
+    (= n 3)
+    for i:u8 in 0..10 {
+        if (== (& i 1) 0) { continue }
+        (+= a i)
+        if (== i 7) { break }
+    }
+    for i:u8 in n..+4 { (+= b i) }
+    for i:u8 in 2..=n { (+= c 1) }
+    for i:u8 in 4..4 { (= d 1) }
+    for i:u8 in 4..+0 { (= d 1) }
+    "#,
+        &[16, 18, 2, 0],
+    )
+}
+
+#[test]
+fn test_static_label() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    static c:u8
+    'outer: for i:u8 in 0..4 {
+        (+= a 1)
+        'inner: loop {
+            while true {
+                (+= b 1)
+                if (== i 2) { break 'outer }
+                continue 'outer
+            }
+        }
+    }
+    'done: loop {
+        for i:u8 in 0..8 {
+            (+= c 1)
+            if (== i 4) { break 'done }
+        }
+    }
+    "#,
+        &[3, 3, 5],
+    )
+}
+
+#[test]
+fn test_static_infix() {
+    use ir::parser::{parse_with_context, ContextBuilder};
+
+    let input = r#"
+    static a:u8
+    static b:u8
+    static c:u8
+    a = 2 + 3 * 4 - 1
+    b = (a - 3) << 1 | 1
+    if a == 13 && b > a { c = b - a * 2 }
+    "#;
+    let mut context = ContextBuilder::default().infix(true).build();
+    let ast = parse_with_context(input, &mut context).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let result = &Machine::new(&ir, Opts::default()).run().static_[..3];
+    assert_eq!(&[13, 21, 251], result);
+}
+
+#[test]
+fn test_static_pointer() {
+    _test_static(
+        r#"
+    static arr:[u8 4]
+    static w:u16
+    static p:&u8
+    static q:&u16
+    static a:u8
+    static b:u8
+    (= p @([1] arr))
+    (= *p 7)
+    (= *(+ p 1) 8)
+    (++ p)
+    (+= p 1)
+    (= *p (+ *(- p 1) 1))
+    (= q @w)
+    (= *q 0x1234)
+    (= a *(- p 2))
+    (= b *(+ @([0] arr) (- a 4)))
+    "#,
+        &[0, 7, 8, 9, 0x34, 0x12, 0x03, 0x00, 0x04, 0x00, 7, 9],
+    )
+}
+
+#[test]
+fn test_static_init() {
+    _test_static(
+        r#"
+    const N:u8 = 3
+    static a:u8 = 0x42
+    static tiles:[u8 4] = [1 2 N (+ N 1)]
+    static w:u16 = 0x1234
+    static b:u8
+    {
+        static c:u8 = 7
+    }
+    static d:u8 = (sizeof tiles)
+    (= b ([2] tiles))
+    "#,
+        &[0x42, 1, 2, 3, 4, 0x34, 0x12, 3, 7, 4],
+    )
+}
+
+#[test]
+fn test_static_struct() {
+    _test_static(
+        r#"
+    static a:struct { x:u8 y:u16 pos:struct { x:u8 y:u8 } }
+    static b:struct { x:u8 y:u16 pos:struct { x:u8 y:u8 } }
+    static c:u8
+    (= a { y:0x1234 x:1 pos:{ y:3 } })
+    (= b a)
+    (= b { x:(+ a::x 4) })
+    {
+        let s:struct { x:u8 y:u8 } = { x:6 y:7 }
+        (= c (+ s::x s::y))
+    }
+    "#,
+        &[1, 0x34, 0x12, 0, 3, 5, 0x34, 0x12, 0, 3, 13],
+    )
+}
+
+#[test]
+fn test_static_init_struct() {
+    _test_static(
+        r#"
+    static s:struct {
+        pos:struct { x:u8 y:u8 }
+        tile:u16
+        data:union { a:u8 b:u16 }
+        tiles:[u8 2]
+    } = { tile:0x1234 pos:{ x:8 y:16 } data:{ b:0xabcd } tiles:[1 2] }
+    static t:struct { x:u8 y:u8 } = { y:4 }
+    const C:struct { x:u8 y:u8 } = { x:3 y:2 }
+    static u:u8 = (+ C::x C::y)
+    "#,
+        &[8, 16, 0x34, 0x12, 0xcd, 0xab, 1, 2, 0, 4, 5],
+    )
+}
+
+#[test]
+fn test_static_type_alias() {
+    _test_static(
+        r#"
+    type Word = u16
+    type Point = struct { x:u8 y:u8 }
+    type Tile = [u8 2]
+    type Sprite = struct { pos:Point tile:Tile }
+    static a:Sprite = { pos:{ x:8 y:16 } tile:[1 2] }
+    static b:Point
+    static w:Word
+    (= b a::pos)
+    (++ b::y)
+    (= w (sizeof Sprite))
+    "#,
+        &[8, 16, 1, 2, 8, 17, 4, 0],
+    )
+}
+
+#[test]
+fn test_static_bits() {
+    _test_static(
+        r#"
+    static lcdc:u8 .{ bg_on:1 obj_on:1 obj_size:1 mode:2 lcd_on:1 } = { obj_on:1 mode:2 }
+    static w:u16 .{ lo:4, mid:8, hi:4 } = { lo:0xf hi:1 }
+    static s:struct { tile:u8 flags:u8 .{ palette:3 flip:2 } }
+    static a:u8
+    static b:u8
+    static c:u16
+    (= lcdc::bg_on 1)
+    (= lcdc::mode (+ lcdc::mode 1))
+    (= lcdc::obj_on 0)
+    (= s::flags::palette 0xff)
+    (= s::flags::flip 2)
+    (= w::mid 0xab)
+    (= a lcdc::mode)
+    (= b (+ s::flags::palette 1))
+    (= c w::mid)
+    "#,
+        &[0x19, 0xbf, 0x1a, 0, 0x17, 3, 8, 0xab, 0],
+    )
+}
+
+#[test]
+fn test_static_anonymous_members() {
+    _test_static(
+        r#"
+    static regs:struct { ctrl:u8 union { word:u16 struct { lo:u8 hi:u8 } } stat:u8 } = { ctrl:1 stat:4 }
+    static size:u8 = (sizeof regs)
+    (= regs::lo 2)
+    (= regs::hi 3)
+    "#,
+        &[1, 2, 3, 4, 4],
+    )
+}
+
+#[test]
+fn test_static_let_tuple() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u16
+    static c:u8
+    let (x:u8 y:u16 z:u8) = [1 0x0302 4]
+    (= a z)
+    (= b y)
+    (= c x)
+    "#,
+        &[4, 2, 3, 1],
+    )
+}
+
+#[test]
+fn test_static_ident_raw_unicode() {
+    _test_static(
+        r#"
+    static r#loop:u8
+    static ñandú:u8
+    static r#über:u8
+    (= r#loop 1)
+    (= r#ñandú 2)
+    (= über (+ r#loop 2))
+    "#,
+        &[1, 2, 3],
+    )
+}
+
+#[test]
+fn test_static_separated() {
+    _test_static(
+        r#"
+    static a:struct { x:u8, y:u8, }
+    static b:[u8 3] = [4, 5, 6,]
+    (= a { x:1, y:2, })
+    (= ([0]b) 7)
+    "#,
+        &[1, 2, 7, 5, 6],
+    )
+}
+
+#[test]
+fn test_static_macro() {
+    _test_static(
+        r#"
+    static a:u8
+    static b:u8
+    macro swap(x, y) {
+        let tmp:u8 = x
+        (= x y)
+        (= y tmp)
+    }
+    (= a 1)
+    (= b 2)
+    swap!(a, b)
+    let tmp:u8 = 3
+    (= b tmp)
+    "#,
+        &[2, 3],
+    )
+}
+
+#[test]
+fn test_static_align() {
+    _test_static(
+        r#"
+    type Entry = #[align(4)] struct { y:u8, x:u8, tile:u8 }
+    static pad:u8 = 1
+    static a:Entry = { y:2, x:3, tile:4 }
+    static b:struct { c:u8, #[align(2)] struct { d:u8 }, e:u16 } = { c:5, d:6, e:7 }
+    static p:#[repr(packed)] struct { c:u8, e:Entry } = { c:8, e:{ y:9, x:10, tile:11 } }
+    "#,
+        &[1, 0, 0, 0, 2, 3, 4, 0, 5, 0, 6, 0, 7, 0, 8, 9, 10, 11, 0],
+    )
+}
@@ -0,0 +1,10 @@
+//! Arbitrary bytes must be parsed, or rejected with an error, but never panic.
+//!
+//! `cargo +nightly fuzz run parse` from the `parser` directory.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parser::parse_no_panic(data);
+});
@@ -0,0 +1,263 @@
+//! Arena allocation of syntax tree nodes.
+//!
+//! The recursive nodes of the [`Ast`](crate::Ast) (most expressions, array
+//! and pointer types, ...) are not boxed one by one. They are allocated by
+//! the [`Arena`] of the parsing [`Context`](crate::ast::Context) instead,
+//! which hands out [`Node`]s from large chunks of memory, so parsing a program
+//! performs one allocation per chunk rather than one per node.
+//!
+//! A [`Node`] owns its value like a [`Box`] does, and keeps its chunk alive,
+//! so nodes can be moved out of the `Ast` (or outlive it). A chunk is freed
+//! once all the nodes allocated in it have been dropped.
+use std::{
+    alloc::{self, Layout},
+    fmt,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    sync::Arc,
+};
+
+// size of the chunks shared by many nodes.
+const CHUNK_SIZE: usize = 32 * 1024;
+
+// alignment of the chunks. Nodes with greater alignment, or larger than a
+// fraction of a chunk, are allocated in chunks of their own.
+const CHUNK_ALIGN: usize = 16;
+
+// block of memory holding nodes.
+struct Chunk {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+// the chunk is only a block of memory. Access to the nodes it holds is
+// synchronized by the nodes themselves.
+unsafe impl Send for Chunk {}
+unsafe impl Sync for Chunk {}
+
+impl Chunk {
+    fn new(size: usize, align: usize) -> Arc<Self> {
+        let layout = Layout::from_size_align(size.max(1), align.max(CHUNK_ALIGN))
+            .expect("Node too large to be allocated");
+        // SAFETY: the layout has a non-zero size.
+        let ptr = unsafe { alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Arc::new(Self { ptr, layout })
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `Chunk::new` with the same layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+/// Bump allocator of [`Node`]s.
+#[derive(Default)]
+pub struct Arena {
+    chunk: Option<Arc<Chunk>>,
+    // offset of the free memory of the current chunk
+    offset: usize,
+}
+
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+impl Arena {
+    /// Creates an empty arena. No memory is allocated until the first node.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves a value into the arena.
+    pub fn alloc<T>(&mut self, value: T) -> Node<T> {
+        let size = mem::size_of::<T>();
+        let align = mem::align_of::<T>();
+        if align > CHUNK_ALIGN || size > CHUNK_SIZE / 8 {
+            // SAFETY: the chunk fits the layout of T.
+            return unsafe { Node::write(Chunk::new(size, align), 0, value) };
+        }
+        let offset = (self.offset + align - 1) & !(align - 1);
+        match &self.chunk {
+            Some(chunk) if offset + size <= CHUNK_SIZE => {
+                self.offset = offset + size;
+                // SAFETY: the memory is aligned, in bounds, and not in use.
+                unsafe { Node::write(Arc::clone(chunk), offset, value) }
+            }
+            _ => {
+                let chunk = Chunk::new(CHUNK_SIZE, CHUNK_ALIGN);
+                self.chunk = Some(Arc::clone(&chunk));
+                self.offset = size;
+                // SAFETY: the chunk is new and aligned to CHUNK_ALIGN.
+                unsafe { Node::write(chunk, 0, value) }
+            }
+        }
+    }
+}
+
+/// Owned pointer to a value allocated by an [`Arena`].
+///
+/// Derefs to the value, like a [`Box`].
+pub struct Node<T> {
+    ptr: NonNull<T>,
+    // keeps the memory of the value alive
+    chunk: Arc<Chunk>,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Node<T> {}
+unsafe impl<T: Sync> Sync for Node<T> {}
+
+impl<T> Node<T> {
+    /// Allocates a value by itself, outside of any arena.
+    pub fn new(value: T) -> Self {
+        // SAFETY: the chunk fits the layout of T.
+        unsafe {
+            Self::write(
+                Chunk::new(mem::size_of::<T>(), mem::align_of::<T>()),
+                0,
+                value,
+            )
+        }
+    }
+
+    /// Moves the value out of the node.
+    pub fn into_inner(node: Self) -> T {
+        let node = ManuallyDrop::new(node);
+        // SAFETY: the node is never used (nor dropped) again. The reference to
+        // the chunk is released once the value has been read.
+        unsafe {
+            let value = ptr::read(node.ptr.as_ptr());
+            drop(ptr::read(&node.chunk));
+            value
+        }
+    }
+
+    // SAFETY: the memory at `offset` must be aligned for T, in bounds of the
+    // chunk, and not in use by any other node.
+    unsafe fn write(chunk: Arc<Chunk>, offset: usize, value: T) -> Self {
+        let ptr = chunk.ptr.as_ptr().add(offset).cast::<T>();
+        ptr.write(value);
+        Self {
+            ptr: NonNull::new_unchecked(ptr),
+            chunk,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        // SAFETY: the value is owned by the node. The memory is released by
+        // the chunk once it is no longer referenced.
+        unsafe { ptr::drop_in_place(self.ptr.as_ptr()) }
+    }
+}
+
+impl<T> Deref for Node<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the value is owned by the node.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for Node<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the value is owned by the node.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Node<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Node<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Arena, Node, CHUNK_SIZE};
+    use std::{cell::Cell, rc::Rc};
+
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut arena = Arena::new();
+        let nodes: Vec<_> = (0..CHUNK_SIZE)
+            .map(|_| arena.alloc(Counted(Rc::clone(&drops))))
+            .collect();
+        std::mem::drop(arena);
+        assert_eq!(0, drops.get());
+        std::mem::drop(nodes);
+        assert_eq!(CHUNK_SIZE, drops.get());
+    }
+
+    #[test]
+    fn into_inner() {
+        let drops = Rc::new(Cell::new(0));
+        let node = Arena::new().alloc(Counted(Rc::clone(&drops)));
+        let value = Node::into_inner(node);
+        assert_eq!(0, drops.get());
+        std::mem::drop(value);
+        assert_eq!(1, drops.get());
+    }
+
+    #[test]
+    fn layout() {
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        let mut arena = Arena::new();
+        let byte = arena.alloc(1u8);
+        let word = arena.alloc(0x1234u16);
+        let aligned = arena.alloc(Aligned(2));
+        let large = arena.alloc([3u8; CHUNK_SIZE]);
+        let unit = arena.alloc(());
+        assert_eq!(1, *byte);
+        assert_eq!(0x1234, *word);
+        assert_eq!(0, &*word as *const u16 as usize % 2);
+        assert_eq!(2, aligned.0);
+        assert_eq!(0, &*aligned as *const Aligned as usize % 64);
+        assert!(large.iter().all(|b| *b == 3));
+        assert_eq!((), *unit);
+    }
+
+    #[test]
+    fn deref_mut() {
+        let mut node = Node::new(vec![1, 2]);
+        node.push(3);
+        assert_eq!(vec![1, 2, 3], *node);
+    }
+}
@@ -8,30 +8,57 @@
 //! [`Pointer`]: ./struct.Pointer.html
 //! [`AddressOf`]: ./expressions/struct.AddressOf.html
 use crate::{
+    arena::Node,
     lex,
     lex::{
         span,
         span::{Span, Spanned},
         Token, Tokens,
     },
-    Error,
+    Error, LanguageVersion,
 };
 use std::iter::Peekable;
 
 // re-exports
+pub use builtin::Builtin;
+pub use completion::{completions_at, CompletionItem};
 pub use context::{Context, ContextBuilder};
+pub use doc::Doc;
 pub use expression::Expression;
+pub use hover::{type_at, TypeInfo};
+pub use import::*;
+pub use memory::*;
 pub use path::Path;
+pub use r#enum::*;
+pub use r#macro::*;
+pub use r#match::*;
 pub use r#static::*;
 pub use types::Type;
+pub use visit::Visitor;
+pub use visit_mut::VisitorMut;
 
 #[macro_use]
 mod macros;
+mod builtin;
+mod completion;
+mod const_fn;
 mod context;
+pub mod diff;
+mod doc;
+mod r#enum;
 pub mod expression;
+pub mod fmt;
+mod hover;
+mod import;
+mod r#macro;
+mod r#match;
+mod memory;
+pub(crate) mod owned;
 mod path;
 mod r#static;
 pub mod types;
+pub mod visit;
+pub mod visit_mut;
 
 /// Trait for parseable types.
 pub trait Grammar<'a>: Sized {
@@ -42,12 +69,13 @@ pub trait Grammar<'a>: Sized {
     ) -> Result<Self, Error<'a>>;
 }
 
-impl<'a, P: Grammar<'a>> Grammar<'a> for Box<P> {
+impl<'a, P: Grammar<'a>> Grammar<'a> for Node<P> {
     fn parse(
         context: &mut Context<'a>,
         tokens: &mut Peekable<Tokens<'a>>,
     ) -> Result<Self, Error<'a>> {
-        P::parse(context, tokens).map(Self::new)
+        let node = P::parse(context, tokens)?;
+        Ok(context.alloc(node))
     }
 }
 
@@ -67,6 +95,111 @@ where
     }
 }
 
+/// List of items, optionally separated by `,` (`[1 2 3]`, `[1, 2, 3,]`).
+///
+/// Either every item but the last is followed by a `,`, or none is. Lists
+/// that mix both fail with a [`MissingSeparator`](Error::MissingSeparator)
+/// error. The last item may always be followed by a (trailing) `,`.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a, T: serde::Deserialize<'de>"))
+)]
+#[derive(Debug)]
+pub struct Separated<'a, T> {
+    /// The items of the list.
+    pub items: Vec<T>,
+
+    /// `,` tokens.
+    pub commas: Vec<lex::Comma<'a>>,
+}
+
+impl<T> std::ops::Deref for Separated<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> std::ops::DerefMut for Separated<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items
+    }
+}
+
+impl<'s, T> IntoIterator for &'s Separated<'_, T> {
+    type Item = &'s T;
+    type IntoIter = std::slice::Iter<'s, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'s, T> IntoIterator for &'s mut Separated<'_, T> {
+    type Item = &'s mut T;
+    type IntoIter = std::slice::IterMut<'s, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter_mut()
+    }
+}
+
+impl<'a, P> Grammar<'a> for Separated<'a, P>
+where
+    P: Spanned,
+    Option<P>: Grammar<'a>,
+{
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        let mut items: Vec<P> = Vec::new();
+        let mut commas: Vec<lex::Comma<'a>> = Vec::new();
+        while let Some(item) = Grammar::parse(context, tokens)? {
+            items.push(item);
+            let comma: Option<lex::Comma<'a>> = Grammar::parse(context, tokens)?;
+            match comma {
+                // the first `,` of the list must follow the first item
+                Some(comma) if commas.len() + 1 < items.len() => {
+                    return Err(Error::MissingSeparator {
+                        item: items[commas.len() + 1].span(),
+                        comma,
+                    })
+                }
+                Some(comma) => commas.push(comma),
+                // an item with no `,` ends a comma-separated list
+                None if !commas.is_empty() => match Option::<P>::parse(context, tokens)? {
+                    Some(item) => {
+                        return Err(Error::MissingSeparator {
+                            item: item.span(),
+                            comma: commas.pop().unwrap(),
+                        });
+                    }
+                    None => break,
+                },
+                None => {}
+            }
+        }
+        Ok(Self { items, commas })
+    }
+}
+
+impl<T: crate::incremental::Remap> crate::incremental::Remap for Separated<'_, T> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.items.remap(f);
+        self.commas.remap(f);
+    }
+}
+
+impl<'a, T: crate::ast::owned::Own<'a>> crate::ast::owned::Own<'a> for Separated<'a, T> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.items.own(f);
+        self.commas.own(f);
+    }
+}
+
 parse! {
     /// Program statements.
     #[derive(Debug)]
@@ -77,6 +210,9 @@ parse! {
         // IfElse statement.
         IfElse(IfElse<'a>),
 
+        /// Conditional compilation statement.
+        IfConst(IfConst<'a>),
+
         /// Scope (aka block).
         Scope(Scope<'a>),
 
@@ -86,21 +222,51 @@ parse! {
         /// Module definition statement.
         Mod(Mod<'a>),
 
+        /// Import statement (module defined in another file).
+        Import(Import<'a>),
+
+        /// Macro definition statement.
+        Macro(Macro<'a>),
+
+        /// Macro invocation statement.
+        MacroCall(MacroCall<'a>),
+
+        /// Memory map statement.
+        Memory(Memory<'a>),
+
         /// Static statement (static symbol definition).
         Static(Static<'a>),
 
         /// Static const statement (const symbol definition).
         Const(Const<'a>),
 
+        /// Static assertion statement.
+        StaticAssert(StaticAssert<'a>),
+
+        /// Enum declaration statement.
+        Enum(Enum<'a>),
+
+        /// Type alias declaration statement.
+        TypeAlias(TypeAlias<'a>),
+
         /// Let statement (stack symbol definition).
         Let(Let<'a>),
 
+        /// Let statement destructuring a tuple.
+        LetTuple(LetTuple<'a>),
+
         /// For loop statement.
         For(For<'a>),
 
         /// Loop statement.
         Loop(Loop<'a>),
 
+        /// While loop statement.
+        While(While<'a>),
+
+        /// Match statement.
+        Match(Match<'a>),
+
         /// Continue statement (flow control).
         Continue(Continue<'a>),
 
@@ -115,47 +281,528 @@ parse! {
 
         /// Return statement.
         Return(Return<'a>),
+
+        /// Deferred block, run when leaving the enclosing scope.
+        Defer(Defer<'a>),
+
+        /// Erroneous statement.
+        ///
+        /// Only produced when parsing in error-tolerant mode.
+        Error(ErrorNode<'a>),
     }
 }
 
-impl<'a> Grammar<'a> for Option<Statement<'a>> {
-    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
-        let statement = match tokens.peek() {
-            Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+/// Region of the source code that failed to parse.
+///
+/// See [`ContextBuilder::error_tolerant`](ContextBuilder::error_tolerant).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct ErrorNode<'a> {
+    /// Tokens skipped while recovering from the error.
+    pub tokens: Vec<Token<'a>>,
+
+    /// Span of the region, from the beginning of the statement up to the
+    /// last skipped token.
+    pub span: Span,
+}
 
-            None | Some(Ok(Token::RightBracket(_))) | Some(Ok(Token::Eof(_))) => return Ok(None),
+impl Spanned for ErrorNode<'_> {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
 
-            Some(Ok(Token::If(_))) => {
-                let if_ = Grammar::parse(ctx, tokens)?;
+impl<'a> ErrorNode<'a> {
+    // skip tokens up to the next statement boundary.
+    fn recover(start: Span, error: &Error<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Self {
+        let mut span = start;
+        if let Some(error) = error.span() {
+            span = span::union(&span, &error);
+        }
 
-                if let Some(Ok(Token::Else(_))) = tokens.peek() {
-                    Statement::IfElse(IfElse {
-                        if_,
-                        else_: Grammar::parse(ctx, tokens)?,
-                    })
-                } else {
-                    Statement::If(if_)
+        let mut skipped = Vec::new();
+        loop {
+            let progress = peek_span(tokens) != Some(start) || !skipped.is_empty();
+            match tokens.peek() {
+                None | Some(Ok(Token::Eof(_))) | Some(Ok(Token::RightBracket(_))) => break,
+                // statements usually begin on a new line
+                Some(Ok(token))
+                    if progress
+                        && (is_statement_boundary(token) || token.span().min[0] > span.max[0]) =>
+                {
+                    break
+                }
+                Some(Ok(_)) => {
+                    let token = tokens.next().unwrap().unwrap();
+                    span = span::union(&span, &token.span());
+                    skipped.push(token);
+                }
+                // lexer errors within the skipped region are not reported
+                Some(Err(_)) => {
+                    let error = tokens.next().unwrap().unwrap_err();
+                    if let Some(error) = error.span() {
+                        span = span::union(&span, &error);
+                    }
                 }
             }
-            Some(Ok(Token::LeftBracket(_))) => Statement::Scope(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::BangBang(_))) => Statement::Panic(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Mod(_))) => Statement::Mod(Grammar::parse(ctx, tokens)?),
-            #[cfg(todo_asm)]
-            Some(Ok(Token::Asm(_))) => Statement::Asm(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Static(_))) => Statement::Static(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Const(_))) => Statement::Const(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::For(_))) => Statement::For(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Loop(_))) => Statement::Loop(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Let(_))) => Statement::Let(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Fn(_))) => Statement::Fn(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Continue(_))) => Statement::Continue(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Break(_))) => Statement::Break(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Return(_))) => Statement::Return(Grammar::parse(ctx, tokens)?),
-            Some(Ok(_)) => Statement::Inline(Grammar::parse(ctx, tokens)?),
+        }
+
+        Self {
+            tokens: skipped,
+            span,
+        }
+    }
+}
+
+fn peek_span<'a>(tokens: &mut Peekable<Tokens<'a>>) -> Option<Span> {
+    match tokens.peek()? {
+        Ok(token) => Some(token.span()),
+        Err(error) => error.span(),
+    }
+}
+
+// tokens that begin a new statement.
+fn is_statement_boundary(token: &Token<'_>) -> bool {
+    matches!(
+        token,
+        Token::If(_)
+            | Token::LeftBracket(_)
+            | Token::BangBang(_)
+            | Token::Mod(_)
+            | Token::Import(_)
+            | Token::Macro(_)
+            | Token::Memory(_)
+            | Token::Hash(_)
+            | Token::Pub(_)
+            | Token::Static(_)
+            | Token::Const(_)
+            | Token::StaticAssert(_)
+            | Token::Enum(_)
+            | Token::Type(_)
+            | Token::For(_)
+            | Token::Loop(_)
+            | Token::While(_)
+            | Token::Label(_)
+            | Token::Match(_)
+            | Token::Let(_)
+            | Token::Fn(_)
+            | Token::Continue(_)
+            | Token::Break(_)
+            | Token::Return(_)
+            | Token::Defer(_)
+    )
+}
+
+impl<'a> Grammar<'a> for Option<Statement<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        if !ctx.is_error_tolerant() {
+            return ctx.nested(tokens, parse_statement);
+        }
+        let start = match peek_span(tokens) {
+            Some(span) => span,
+            None => return Ok(None),
         };
+        let consts = ctx.const_count();
+        match ctx.nested(tokens, parse_statement) {
+            Ok(statement) => Ok(statement),
+            Err(error) => {
+                ctx.end_scope(consts);
+                let node = ErrorNode::recover(start, &error, tokens);
+                ctx.push_error(error);
+                Ok(Some(Statement::Error(node)))
+            }
+        }
+    }
+}
+
+fn parse_statement<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Option<Statement<'a>>, Error<'a>> {
+    let consts = ctx.const_count();
+    let statement = match tokens.peek() {
+        Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+
+        None | Some(Ok(Token::RightBracket(_))) | Some(Ok(Token::Eof(_))) => return Ok(None),
+
+        Some(Ok(Token::If(_))) => {
+            let if_token = Grammar::parse(ctx, tokens)?;
+            if let Some(Ok(Token::Const(_))) = tokens.peek() {
+                return parse_if_const(ctx, tokens, if_token).map(Some);
+            }
+            let if_ = If {
+                if_: if_token,
+                expression: Grammar::parse(ctx, tokens)?,
+                left_bracket: Grammar::parse(ctx, tokens)?,
+                inner: Grammar::parse(ctx, tokens)?,
+                right_bracket: Grammar::parse(ctx, tokens)?,
+            };
+
+            if let Some(Ok(Token::Else(_))) = tokens.peek() {
+                Statement::IfElse(IfElse {
+                    if_,
+                    else_: Grammar::parse(ctx, tokens)?,
+                })
+            } else {
+                Statement::If(if_)
+            }
+        }
+        Some(Ok(Token::LeftBracket(_))) => Statement::Scope(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::BangBang(_))) => Statement::Panic(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Mod(_))) => {
+            let parent = ctx.begin_module();
+            let mod_: Mod<'a> = Grammar::parse(ctx, tokens)?;
+            ctx.end_module(parent, mod_.ident.name());
+            Statement::Mod(mod_)
+        }
+        Some(Ok(Token::Import(_))) => {
+            let parent = ctx.begin_module();
+            let import: Import<'a> = Grammar::parse(ctx, tokens)?;
+            ctx.end_module(parent, &import.name());
+            Statement::Import(import)
+        }
+        Some(Ok(Token::Macro(_))) => {
+            let macro_ = Grammar::parse(ctx, tokens)?;
+            ctx.define_macro(&macro_)?;
+            Statement::Macro(macro_)
+        }
+        Some(Ok(Token::Memory(_))) => {
+            let memory = Grammar::parse(ctx, tokens)?;
+            memory::check_regions(&memory)?;
+            Statement::Memory(memory)
+        }
+        Some(Ok(Token::Ident(ident))) if ctx.macro_params(ident.name()).is_some() => {
+            Statement::MacroCall(Grammar::parse(ctx, tokens)?)
+        }
+        #[cfg(todo_asm)]
+        Some(Ok(Token::Asm(_))) => Statement::Asm(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Pub(_))) => parse_pub(ctx, tokens)?,
+        Some(Ok(Token::Static(_))) => Statement::Static(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Const(_))) => {
+            let doc = Grammar::parse(ctx, tokens)?;
+            parse_const(ctx, tokens, doc, None)?
+        }
+        Some(Ok(Token::StaticAssert(_))) => {
+            let assert = Grammar::parse(ctx, tokens)?;
+            ctx.static_assert(&assert)?;
+            Statement::StaticAssert(assert)
+        }
+        Some(Ok(Token::Enum(_))) => Statement::Enum(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Type(_))) => Statement::TypeAlias(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::For(_))) => Statement::For(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Loop(_))) => Statement::Loop(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Label(_))) => {
+            let label = Some(Grammar::parse(ctx, tokens)?);
+            match tokens.peek() {
+                Some(Ok(Token::For(_))) => Statement::For(For {
+                    label,
+                    ..Grammar::parse(ctx, tokens)?
+                }),
+                Some(Ok(Token::While(_))) => Statement::While(While {
+                    label,
+                    ..Grammar::parse(ctx, tokens)?
+                }),
+                _ => Statement::Loop(Loop {
+                    label,
+                    ..Grammar::parse(ctx, tokens)?
+                }),
+            }
+        }
+        Some(Ok(Token::While(_))) => Statement::While(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Match(_))) => Statement::Match(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Let(_))) => parse_let(ctx, tokens)?,
+        Some(Ok(Token::Fn(_))) => Statement::Fn(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Hash(_))) => {
+            let fn_: Fn<'a> = Grammar::parse(ctx, tokens)?;
+            types::check_attributes(
+                &fn_.attributes,
+                &["bank", "inline"],
+                "`bank` or `inline` attribute",
+            )?;
+            if fn_.const_.is_some() {
+                const_fn::check(&fn_)?;
+            }
+            Statement::Fn(fn_)
+        }
+        Some(Ok(Token::Continue(_))) => Statement::Continue(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Break(_))) => Statement::Break(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Return(_))) => Statement::Return(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Defer(_))) => {
+            let defer: Defer<'a> = Grammar::parse(ctx, tokens)?;
+            check_defer(&defer.inner, &mut Vec::new())?;
+            Statement::Defer(defer)
+        }
+        Some(Ok(_)) => {
+            let inline: Inline<'a> = Grammar::parse(ctx, tokens)?;
+            if let Some(error) = newer_keyword(ctx, &inline, tokens) {
+                return Err(error);
+            }
+            if let Some(error) = misspelled_keyword(ctx, &inline, tokens) {
+                return Err(error);
+            }
+            Statement::Inline(inline)
+        }
+    };
+
+    // consts defined within the blocks of a statement are local to them
+    if !matches!(
+        statement,
+        Statement::Const(_) | Statement::Mod(_) | Statement::Import(_) | Statement::IfConst(_)
+    ) && !matches!(&statement, Statement::Fn(fn_) if fn_.const_.is_some())
+    {
+        ctx.end_scope(consts);
+    }
+
+    Ok(Some(statement))
+}
+
+// `if const` statement, following the `if` token.
+// The declarations of the taken branch belong to the enclosing block, so they
+// outlive the statement. The consts of the other branch are discarded.
+fn parse_if_const<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    if_: lex::If<'a>,
+) -> Result<Statement<'a>, Error<'a>> {
+    let const_: lex::Const<'a> = Grammar::parse(ctx, tokens)?;
+    ctx.require(
+        LanguageVersion::V2,
+        "`if const`",
+        span::union(&if_.span(), &const_.span()),
+    )?;
+    let expression = Grammar::parse(ctx, tokens)?;
+    let value = ctx.eval_condition(&expression)?;
+    let consts = ctx.const_count();
+    let left_bracket = Grammar::parse(ctx, tokens)?;
+    let inner = Grammar::parse(ctx, tokens)?;
+    let right_bracket = Grammar::parse(ctx, tokens)?;
+    let then = ctx.take_consts(consts);
+    let else_ = match tokens.peek() {
+        Some(Ok(Token::Else(_))) => Some(Grammar::parse(ctx, tokens)?),
+        _ => None,
+    };
+    if value != 0 {
+        ctx.end_scope(consts);
+        ctx.restore_consts(then);
+    }
+    Ok(Statement::IfConst(IfConst {
+        if_,
+        const_,
+        expression,
+        left_bracket,
+        inner,
+        right_bracket,
+        else_,
+        value,
+    }))
+}
+
+// `let` statement, destructuring a tuple if the fields are parenthesized.
+fn parse_let<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Statement<'a>, Error<'a>> {
+    let let_ = Grammar::parse(ctx, tokens)?;
+    let statement = match tokens.peek() {
+        Some(Ok(Token::LeftPar(_))) => Statement::LetTuple(LetTuple {
+            let_,
+            left_par: Grammar::parse(ctx, tokens)?,
+            fields: Grammar::parse(ctx, tokens)?,
+            right_par: Grammar::parse(ctx, tokens)?,
+            assign: Grammar::parse(ctx, tokens)?,
+            expression: Grammar::parse(ctx, tokens)?,
+        }),
+        _ => {
+            let let_ = Let {
+                let_,
+                field: Grammar::parse(ctx, tokens)?,
+                assign: Grammar::parse(ctx, tokens)?,
+                expression: Grammar::parse(ctx, tokens)?,
+            };
+            types::check_range(&let_.field.type_, &let_.expression, &|e| ctx.eval(e))?;
+            Statement::Let(let_)
+        }
+    };
+    Ok(statement)
+}
 
-        Ok(Some(statement))
+// `const` statement, or `const fn` declaration, following the doc comment and
+// the `pub` token.
+fn parse_const<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    doc: Option<Doc<'a>>,
+    pub_: Option<lex::Pub<'a>>,
+) -> Result<Statement<'a>, Error<'a>> {
+    let const_ = Grammar::parse(ctx, tokens)?;
+    if let Some(Ok(Token::Fn(_))) = tokens.peek() {
+        return parse_const_fn(ctx, tokens, doc, pub_, const_);
     }
+    let const_ = Const {
+        doc,
+        pub_,
+        const_,
+        field: Grammar::parse(ctx, tokens)?,
+        assign: Grammar::parse(ctx, tokens)?,
+        expression: Grammar::parse(ctx, tokens)?,
+    };
+    ctx.define_const(&const_)?;
+    Ok(Statement::Const(const_))
+}
+
+// `const fn` declaration, following the `const` token.
+// The function is parsed twice from the same tokens: into the statement, and
+// into the definition the context evaluates the calls with.
+fn parse_const_fn<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    doc: Option<Doc<'a>>,
+    pub_: Option<lex::Pub<'a>>,
+    const_: lex::Const<'a>,
+) -> Result<Statement<'a>, Error<'a>> {
+    ctx.require(LanguageVersion::V2, "`const fn`", const_.span())?;
+    let mut taken = r#macro::until(tokens, |token, depth| {
+        depth == 1 && matches!(token, Token::RightBracket(_))
+    })?;
+    let right_bracket: lex::RightBracket<'a> = Grammar::parse(ctx, tokens)?;
+    let end = r#macro::expansion_end(&right_bracket);
+    taken.push(Token::RightBracket(right_bracket));
+
+    let body = ctx.set_const_fn_body(true);
+    let fn_ = Fn::parse(ctx, &mut Tokens::replay(taken.clone(), end).peekable());
+    ctx.set_const_fn_body(body);
+    let fn_ = Fn {
+        doc,
+        pub_,
+        const_: Some(const_),
+        ..fn_?
+    };
+    const_fn::check(&fn_)?;
+    let mut scratch = ctx.scratch();
+    let def = Fn::parse(&mut scratch, &mut Tokens::replay(taken, end).peekable())?;
+    ctx.define_const_fn(def)?;
+    Ok(Statement::Fn(fn_))
+}
+
+// public declaration (`pub static FOO:u8`).
+fn parse_pub<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Statement<'a>, Error<'a>> {
+    // the doc comment precedes the `pub` token
+    let doc = Grammar::parse(ctx, tokens)?;
+    let pub_ = Some(Grammar::parse(ctx, tokens)?);
+    let statement = match tokens.peek() {
+        Some(Ok(Token::Static(_))) => Statement::Static(Static {
+            doc,
+            pub_,
+            ..Grammar::parse(ctx, tokens)?
+        }),
+        Some(Ok(Token::Const(_))) => parse_const(ctx, tokens, doc, pub_)?,
+        Some(Ok(Token::Enum(_))) => Statement::Enum(Enum {
+            doc,
+            pub_,
+            ..Grammar::parse(ctx, tokens)?
+        }),
+        Some(Ok(Token::Type(_))) => Statement::TypeAlias(TypeAlias {
+            doc,
+            pub_,
+            ..Grammar::parse(ctx, tokens)?
+        }),
+        Some(Ok(Token::Fn(_))) => Statement::Fn(Fn {
+            doc,
+            pub_,
+            ..Grammar::parse(ctx, tokens)?
+        }),
+        Some(Ok(token)) => {
+            return Err(Error::Expected {
+                expected: "declaration",
+                found: token.clone(),
+            })
+        }
+        Some(Err(_)) => return Err(tokens.next().unwrap().unwrap_err()),
+        None => return Err(Error::Eof),
+    };
+    Ok(statement)
+}
+
+// Keywords that begin a statement.
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "if",
+    "else",
+    "mod",
+    "import",
+    "macro",
+    "memory",
+    "pub",
+    "static",
+    "const",
+    "static_assert",
+    "enum",
+    "for",
+    "loop",
+    "while",
+    "match",
+    "let",
+    "fn",
+    "continue",
+    "break",
+    "return",
+    "defer",
+];
+
+// A keyword of a newer version of the language followed by another token on
+// the same line (`defer { }`) is likely the statement it begins in that
+// version, rather than an identifier followed by another statement.
+fn newer_keyword<'a>(
+    ctx: &Context<'a>,
+    inline: &Inline<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Option<Error<'a>> {
+    let ident = match &inline.inner {
+        Expression::Path(path) if path.tail.is_empty() && !path.head.is_raw() => &path.head,
+        _ => return None,
+    };
+    match tokens.peek() {
+        Some(Ok(Token::Eof(_))) | None => return None,
+        Some(Ok(next)) if next.span().min[0] == ident.span().max[0] => {}
+        _ => return None,
+    }
+    let version = LanguageVersion::of_keyword(ident.name())?;
+    ctx.require(version, format!("`{}`", ident.name()), ident.span())
+        .err()
+}
+
+// An identifier followed by another token on the same line (`statc FOO:u8`)
+// is likely a misspelled keyword rather than two expressions.
+fn misspelled_keyword<'a>(
+    ctx: &Context<'a>,
+    inline: &Inline<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Option<Error<'a>> {
+    let ident = match &inline.inner {
+        // raw identifiers are never misspelled keywords
+        Expression::Path(path) if path.tail.is_empty() && !path.head.is_raw() => &path.head,
+        _ => return None,
+    };
+    match tokens.peek() {
+        Some(Ok(Token::Ident(next))) if next.span().min[0] == ident.span().max[0] => {}
+        Some(Ok(Token::Lit(next))) if next.span().min[0] == ident.span().max[0] => {}
+        _ => return None,
+    }
+    let keywords = STATEMENT_KEYWORDS
+        .iter()
+        .copied()
+        .filter(|keyword| ctx.version().has_keyword(keyword));
+    let suggestion = crate::error::suggest(&ident.to_string(), keywords)?;
+    Some(Error::UnknownKeyword {
+        ident: ident.clone(),
+        suggestion,
+    })
 }
 
 impl<'a> Grammar<'a> for Statement<'a> {
@@ -163,8 +810,11 @@ impl<'a> Grammar<'a> for Statement<'a> {
         if let Some(statement) = Grammar::parse(ctx, tokens)? {
             Ok(statement)
         } else {
-            let token = tokens.next().expect("Token please")?;
-            Err(Error::UnexpectedToken(token))
+            match tokens.peek() {
+                Some(Ok(token)) => Err(Error::UnexpectedToken(token.clone())),
+                Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+                None => Err(Error::Eof),
+            }
         }
     }
 }
@@ -180,41 +830,170 @@ span!(Scope {
     right_bracket
 });
 span!(Panic { bang_bang });
+span!(StaticAssert {
+    static_assert,
+    right_par
+});
 span!(Mod {
     mod_,
     right_bracket
 });
-span!(Const { const_, expression });
+
+// span of a (possibly public) declaration.
+pub(crate) fn pub_span(pub_: &Option<lex::Pub<'_>>, span: Span) -> Span {
+    match pub_ {
+        Some(pub_) => span::union(&pub_.span(), &span),
+        None => span,
+    }
+}
+
+impl Spanned for Const<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.const_.span(), &self.expression.span());
+        pub_span(&self.pub_, span)
+    }
+}
+
+impl Spanned for TypeAlias<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.type_.span(), &self.inner.span());
+        pub_span(&self.pub_, span)
+    }
+}
+
 span!(Let { let_, expression });
-span!(For {
-    for_,
-    right_bracket
-});
-span!(Loop {
-    loop_,
-    right_bracket
-});
-span!(Continue { continue_ });
-span!(Break { break_ });
+span!(LetTuple { let_, expression });
+span!(Range { left, right });
+span!(LoopLabel { label, colon });
+
+// span of a (possibly labeled) loop statement.
+fn loop_span(label: &Option<LoopLabel<'_>>, span: Span) -> Span {
+    match label {
+        Some(label) => span::union(&label.span(), &span),
+        None => span,
+    }
+}
+
+impl Spanned for For<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.for_.span(), &self.right_bracket.span());
+        loop_span(&self.label, span)
+    }
+}
+
+impl Spanned for Loop<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.loop_.span(), &self.right_bracket.span());
+        loop_span(&self.label, span)
+    }
+}
+
+impl Spanned for While<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.while_.span(), &self.right_bracket.span());
+        loop_span(&self.label, span)
+    }
+}
+
+impl Spanned for Continue<'_> {
+    fn span(&self) -> Span {
+        match &self.label {
+            Some(label) => span::union(&self.continue_.span(), &label.span()),
+            None => self.continue_.span(),
+        }
+    }
+}
+
+impl Spanned for Break<'_> {
+    fn span(&self) -> Span {
+        match &self.label {
+            Some(label) => span::union(&self.break_.span(), &label.span()),
+            None => self.break_.span(),
+        }
+    }
+}
 span!(Inline { inner });
-span!(Fn { fn_, right_bracket });
+impl Spanned for Fn<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.fn_.span(), &self.right_bracket.span());
+        let span = pub_span(&self.pub_, span);
+        match self.attributes.first() {
+            Some(attribute) => span::union(&attribute.span(), &span),
+            None => span,
+        }
+    }
+}
+span!(FnInterrupt { at, ident });
+span!(Generics { less, greater });
+span!(GenericParam { const_, field });
 span!(FnReturn { colon, type_ });
 span!(FnArg {
     left_par,
     right_par
 });
 span!(Return { return_ });
-span!(Field { ident, type_ });
+span!(Defer {
+    defer_,
+    right_bracket
+});
+impl Spanned for Field<'_> {
+    fn span(&self) -> Span {
+        match &self.bits {
+            Some(bits) => span::union(&self.ident.span(), &bits.span()),
+            None => span::union(&self.ident.span(), &self.type_.span()),
+        }
+    }
+}
+span!(Bits { dot, right_bracket });
+span!(BitField { ident, width });
 span!(FieldGroup { head, type_ });
 
-parse! {
-    #[derive(Debug)]
-    pub struct Ast<'a> {
-        /// Inner statements.
-        pub inner: Vec<Statement<'a>>,
+/// Abstract syntax tree of a program.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Ast<'a> {
+    /// Inner statements.
+    pub inner: Vec<Statement<'a>>,
+
+    /// EOF token.
+    pub eof: lex::Eof<'a>,
+}
+
+impl<'a> Grammar<'a> for Ast<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let mut inner: Vec<Statement<'a>> = Grammar::parse(ctx, tokens)?;
+        while let Some(error) = unbalanced_bracket(ctx, tokens) {
+            inner.push(error);
+            inner.extend(Vec::<Statement<'a>>::parse(ctx, tokens)?);
+        }
+        Ok(Self {
+            inner,
+            eof: Grammar::parse(ctx, tokens)?,
+        })
+    }
+}
 
-        /// EOF token.
-        pub eof: lex::Eof<'a>,
+// unbalanced closing bracket at the top level of the program.
+// Only skipped in error-tolerant mode.
+pub(crate) fn unbalanced_bracket<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Option<Statement<'a>> {
+    match tokens.peek() {
+        Some(Ok(Token::RightBracket(_))) if ctx.is_error_tolerant() => {
+            let token = tokens.next().unwrap().unwrap();
+            let span = token.span();
+            ctx.push_error(Error::UnexpectedToken(token.clone()));
+            Some(Statement::Error(ErrorNode {
+                tokens: vec![token],
+                span,
+            }))
+        }
+        _ => None,
     }
 }
 
@@ -279,8 +1058,20 @@ parse! {
 }
 
 parse! {
+    /// `const <field> = <expression>`
+    ///
+    /// Consts defined within a block (function bodies, loops, scopes, ...) are
+    /// only visible until the end of the block. A const can't redefine nor
+    /// shadow a const visible from the same module, which is reported as an
+    /// [`Error::ShadowIdent`](Error::ShadowIdent).
     #[derive(Debug)]
     pub struct Const<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Optional `pub` token.
+        pub pub_: Option<lex::Pub<'a>>,
+
         /// `const` tokens.
         pub const_: lex::Const<'a>,
 
@@ -295,6 +1086,115 @@ parse! {
     }
 }
 
+/// `static_assert(<expression>, "<message>")`
+///
+/// Asserts that a constant expression is true (non-zero). Assertions that
+/// only depend on literals and consts are checked by the parser, which fails
+/// with an [`Error::StaticAssert`](Error::StaticAssert). The rest (`sizeof`
+/// a type, casts, ...) are checked when the program is compiled.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct StaticAssert<'a> {
+    /// `static_assert` token.
+    pub static_assert: lex::StaticAssert<'a>,
+
+    /// `(` token.
+    pub left_par: lex::LeftPar<'a>,
+
+    /// Asserted expression tokens.
+    pub expression: Expression<'a>,
+
+    /// `,` token.
+    pub comma: lex::Comma<'a>,
+
+    /// Message (string literal token).
+    pub message: lex::Lit<'a>,
+
+    /// `)` token.
+    pub right_par: lex::RightPar<'a>,
+}
+
+impl StaticAssert<'_> {
+    /// Message of the assertion.
+    pub fn message(&self) -> String {
+        self.message.str_value().expect("Validated message")
+    }
+}
+
+impl<'a> Grammar<'a> for StaticAssert<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let static_assert = Grammar::parse(ctx, tokens)?;
+        let left_par = Grammar::parse(ctx, tokens)?;
+        let expression = Grammar::parse(ctx, tokens)?;
+        let comma = Grammar::parse(ctx, tokens)?;
+        let message: lex::Lit<'a> = Grammar::parse(ctx, tokens)?;
+        if message.str_value().is_none() {
+            return Err(Error::Expected {
+                expected: "string literal",
+                found: Token::Lit(message),
+            });
+        }
+        Ok(Self {
+            static_assert,
+            left_par,
+            expression,
+            comma,
+            message,
+            right_par: Grammar::parse(ctx, tokens)?,
+        })
+    }
+}
+
+impl crate::incremental::Remap for StaticAssert<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.static_assert.remap(f);
+        self.left_par.remap(f);
+        self.expression.remap(f);
+        self.comma.remap(f);
+        self.message.remap(f);
+        self.right_par.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for StaticAssert<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.static_assert.own(f);
+        self.left_par.own(f);
+        self.expression.own(f);
+        self.comma.own(f);
+        self.message.own(f);
+        self.right_par.own(f);
+    }
+}
+
+parse! {
+    /// `type <ident> = <type>`
+    #[derive(Debug)]
+    pub struct TypeAlias<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Optional `pub` token.
+        pub pub_: Option<lex::Pub<'a>>,
+
+        /// `type` token.
+        pub type_: lex::Type<'a>,
+
+        /// Alias identifier token.
+        pub ident: lex::Ident<'a>,
+
+        /// `=` token.
+        pub assign: lex::Assign<'a>,
+
+        /// Aliased type tokens.
+        pub inner: Type<'a>,
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct Let<'a> {
@@ -312,6 +1212,33 @@ parse! {
     }
 }
 
+parse! {
+    /// `let ( <fields> ) = <expression>`
+    ///
+    /// Destructures a tuple into stack symbols, which are allocated
+    /// contiguously (`let (q:u8 r:u8) = (divmod 7 2)`).
+    #[derive(Debug)]
+    pub struct LetTuple<'a> {
+        /// `let` token.
+        pub let_: lex::Let<'a>,
+
+        /// `(` token.
+        pub left_par: lex::LeftPar<'a>,
+
+        /// [`Field`](Field) tokens of the tuple elements.
+        pub fields: Vec<Field<'a>>,
+
+        /// `)` token.
+        pub right_par: lex::RightPar<'a>,
+
+        /// `=` token.
+        pub assign: lex::Assign<'a>,
+
+        /// Expression tokens.
+        pub expression: Expression<'a>,
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct IfElse<'a> {
@@ -343,6 +1270,90 @@ parse! {
     }
 }
 
+/// `if const <expression> { <statements> } else { <statements> }`
+///
+/// Conditional compilation: the condition is evaluated by the parser, using
+/// the consts defined so far and the names defined with
+/// [`ContextBuilder::define`](crate::ContextBuilder::define), and only the
+/// statements of the taken branch are compiled. Unlike the blocks of an `if`
+/// statement, the declarations of the taken branch belong to the enclosing
+/// block. The `else` block is optional.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct IfConst<'a> {
+    /// `if` token.
+    pub if_: lex::If<'a>,
+
+    /// `const` token.
+    pub const_: lex::Const<'a>,
+
+    /// Condition expression tokens.
+    pub expression: Expression<'a>,
+
+    /// `{` token.
+    pub left_bracket: lex::LeftBracket<'a>,
+
+    /// Statements compiled if the condition is true.
+    pub inner: Vec<Statement<'a>>,
+
+    /// `}` token.
+    pub right_bracket: lex::RightBracket<'a>,
+
+    /// Statements compiled if the condition is false.
+    pub else_: Option<Else<'a>>,
+
+    /// Value of the condition.
+    pub value: u16,
+}
+
+impl<'a> IfConst<'a> {
+    /// Statements of the taken branch.
+    pub fn statements(&self) -> &[Statement<'a>] {
+        match (self.value, &self.else_) {
+            (0, Some(else_)) => &else_.inner,
+            (0, None) => &[],
+            _ => &self.inner,
+        }
+    }
+}
+
+impl Spanned for IfConst<'_> {
+    fn span(&self) -> Span {
+        match &self.else_ {
+            Some(else_) => span::union(&self.if_.span(), &else_.span()),
+            None => span::union(&self.if_.span(), &self.right_bracket.span()),
+        }
+    }
+}
+
+impl crate::incremental::Remap for IfConst<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.if_.remap(f);
+        self.const_.remap(f);
+        self.expression.remap(f);
+        self.left_bracket.remap(f);
+        self.inner.remap(f);
+        self.right_bracket.remap(f);
+        self.else_.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for IfConst<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.if_.own(f);
+        self.const_.own(f);
+        self.expression.own(f);
+        self.left_bracket.own(f);
+        self.inner.own(f);
+        self.right_bracket.own(f);
+        self.else_.own(f);
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct Else<'a> {
@@ -383,6 +1394,9 @@ parse! {
 parse! {
     #[derive(Debug)]
     pub struct For<'a> {
+        /// Optional loop label.
+        pub label: Option<LoopLabel<'a>>,
+
         /// `for` token.
         pub for_: lex::For<'a>,
 
@@ -406,9 +1420,34 @@ parse! {
     }
 }
 
+parse! {
+    /// `'<ident>:` label of a loop statement.
+    #[derive(Debug)]
+    pub struct LoopLabel<'a> {
+        /// Label token.
+        pub label: lex::Label<'a>,
+
+        /// `:` token.
+        pub colon: lex::Colon<'a>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<LoopLabel<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Label(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(ctx, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct Loop<'a> {
+        /// Optional loop label.
+        pub label: Option<LoopLabel<'a>>,
+
         /// `loop` token.
         pub loop_: lex::Loop<'a>,
 
@@ -423,6 +1462,29 @@ parse! {
     }
 }
 
+parse! {
+    #[derive(Debug)]
+    pub struct While<'a> {
+        /// Optional loop label.
+        pub label: Option<LoopLabel<'a>>,
+
+        /// `while` token.
+        pub while_: lex::While<'a>,
+
+        /// Loop condition tokens.
+        pub expression: Expression<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Inner statements.
+        pub inner: Vec<Statement<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct FnReturn<'a> {
@@ -447,12 +1509,42 @@ impl<'a> Grammar<'a> for Option<FnReturn<'a>> {
 parse! {
     #[derive(Debug)]
     pub struct Fn<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Attributes (`#[bank(N)]`, `#[inline]`, `#[inline(never)]`).
+        ///
+        /// Functions in a switchable ROM bank are called through a thunk, which
+        /// maps the bank for the duration of the call. The inlining attributes
+        /// control which calls are replaced by the body of the function when
+        /// the program is optimized.
+        pub attributes: Vec<types::Attribute<'a>>,
+
+        /// Optional `pub` token.
+        pub pub_: Option<lex::Pub<'a>>,
+
+        /// Optional `const` token.
+        ///
+        /// Calls to const functions are evaluated by the parser, so const
+        /// functions are never compiled. Their values initialize consts,
+        /// statics, and the elements of const arrays (lookup tables).
+        pub const_: Option<lex::Const<'a>>,
+
         /// `fn` token.
         pub fn_: lex::Fn<'a>,
 
+        /// Optional [`FnInterrupt`](FnInterrupt) tokens.
+        pub interrupt: Option<FnInterrupt<'a>>,
+
         /// Function identifier token.
         pub ident: lex::Ident<'a>,
 
+        /// Optional [`Generics`](Generics) tokens.
+        ///
+        /// Generic functions are compiled once for each set of parameters they
+        /// are called with.
+        pub generics: Option<Generics<'a>>,
+
         /// Function argument tokens.
         pub fn_arg: Option<FnArg<'a>>,
 
@@ -470,6 +1562,160 @@ parse! {
     }
 }
 
+/// Interrupts of the Game Boy.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Interrupt {
+    /// `vblank`
+    VBlank,
+
+    /// `lcd_stat`
+    LcdStat,
+
+    /// `timer`
+    Timer,
+
+    /// `serial`
+    Serial,
+
+    /// `joypad`
+    Joypad,
+}
+
+impl Interrupt {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vblank" => Some(Self::VBlank),
+            "lcd_stat" => Some(Self::LcdStat),
+            "timer" => Some(Self::Timer),
+            "serial" => Some(Self::Serial),
+            "joypad" => Some(Self::Joypad),
+            _ => None,
+        }
+    }
+}
+
+/// `@<interrupt>`
+///
+/// Registers a function as the handler of an interrupt
+/// (`fn@vblank on_vblank { ... }`).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct FnInterrupt<'a> {
+    /// `@` token.
+    pub at: lex::At<'a>,
+
+    /// Interrupt identifier token.
+    pub ident: lex::Ident<'a>,
+}
+
+impl FnInterrupt<'_> {
+    /// Interrupt handled by the function.
+    pub fn interrupt(&self) -> Interrupt {
+        Interrupt::from_name(self.ident.name()).expect("Validated interrupt")
+    }
+}
+
+impl<'a> Grammar<'a> for FnInterrupt<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let at = Grammar::parse(ctx, tokens)?;
+        let ident: lex::Ident<'a> = Grammar::parse(ctx, tokens)?;
+        if Interrupt::from_name(ident.name()).is_none() {
+            return Err(Error::Expected {
+                expected: "interrupt",
+                found: Token::Ident(ident),
+            });
+        }
+        Ok(Self { at, ident })
+    }
+}
+
+impl<'a> Grammar<'a> for Option<FnInterrupt<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::At(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(ctx, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl crate::incremental::Remap for FnInterrupt<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.at.remap(f);
+        self.ident.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for FnInterrupt<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.at.own(f);
+        self.ident.own(f);
+    }
+}
+
+parse! {
+    /// `< const <ident>:<type> ... >`
+    ///
+    /// Generic parameters of a function (`fn sum<const N:u8>(xs:[u8 N]):u8`).
+    /// Parameters are consts whose value is inferred from the lengths of the
+    /// array arguments of each call.
+    #[derive(Debug)]
+    pub struct Generics<'a> {
+        /// `<` token.
+        pub less: lex::Less<'a>,
+
+        /// Generic parameters.
+        pub params: Vec<GenericParam<'a>>,
+
+        /// `>` token.
+        pub greater: lex::Greater<'a>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Generics<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Less(less))) = tokens.peek() {
+            ctx.require(LanguageVersion::V2, "Generic parameter list", less.span())?;
+            Ok(Some(Grammar::parse(ctx, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+parse! {
+    /// `const <ident>:<type>`
+    #[derive(Debug)]
+    pub struct GenericParam<'a> {
+        /// `const` token.
+        pub const_: lex::Const<'a>,
+
+        /// Parameter field tokens.
+        pub field: Field<'a>,
+
+        /// Optional `,` separator.
+        pub comma: Option<lex::Comma<'a>>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<GenericParam<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            Some(Ok(Token::Const(_))) => {
+                let param = Grammar::parse(ctx, tokens)?;
+                ctx.define_generic(&param)?;
+                Ok(Some(param))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct FnArg<'a> {
@@ -494,17 +1740,112 @@ impl<'a> Grammar<'a> for Option<FnArg<'a>> {
     }
 }
 
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Field<'a> {
+    /// Field identifier.
+    pub ident: lex::Ident<'a>,
+
+    /// `:` token.
+    pub colon: lex::Colon<'a>,
+
+    /// Type tokens.
+    pub type_: Type<'a>,
+
+    /// Optional bitfields of the (integer) field.
+    pub bits: Option<Bits<'a>>,
+}
+
+impl<'a> Grammar<'a> for Field<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let ident = Grammar::parse(ctx, tokens)?;
+        let colon = Grammar::parse(ctx, tokens)?;
+        let type_: Type<'a> = Grammar::parse(ctx, tokens)?;
+        // the bitfields begin with a `.`, so a `{` after the type is always the
+        // block of a new statement
+        let bits = match tokens.peek() {
+            Some(Ok(Token::Dot(_))) => {
+                let bits = Grammar::parse(ctx, tokens)?;
+                ctx.check_bits(&type_, &bits)?;
+                Some(bits)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            ident,
+            colon,
+            type_,
+            bits,
+        })
+    }
+}
+
+impl crate::incremental::Remap for Field<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.ident.remap(f);
+        self.colon.remap(f);
+        self.type_.remap(f);
+        self.bits.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Field<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.ident.own(f);
+        self.colon.own(f);
+        self.type_.own(f);
+        self.bits.own(f);
+    }
+}
+
+parse! {
+    /// `.{ <ident>:<width> ... }`
+    ///
+    /// Bitfields of an integer field, packed LSB-first.
+    #[derive(Debug)]
+    pub struct Bits<'a> {
+        /// `.` token.
+        pub dot: lex::Dot<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Bitfields, from the least significant bit.
+        pub fields: Vec<BitField<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
 parse! {
+    /// `<ident>:<width>`
     #[derive(Debug)]
-    pub struct Field<'a> {
-        /// Field identifier.
+    pub struct BitField<'a> {
+        /// Bitfield identifier.
         pub ident: lex::Ident<'a>,
 
         /// `:` token.
         pub colon: lex::Colon<'a>,
 
-        /// Type tokens.
-        pub type_: Type<'a>,
+        /// Width (in bits) expression tokens.
+        pub width: Expression<'a>,
+
+        /// Optional `,` separator.
+        pub comma: Option<lex::Comma<'a>>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<BitField<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            Some(Ok(Token::Ident(_))) => Ok(Some(Grammar::parse(ctx, tokens)?)),
+            _ => Ok(None),
+        }
     }
 }
 
@@ -549,6 +1890,9 @@ parse! {
     pub struct Continue<'a> {
         /// `continue` token.
         pub continue_: lex::Continue<'a>,
+
+        /// Optional label of the continued loop.
+        pub label: Option<lex::Label<'a>>,
     }
 }
 
@@ -557,6 +1901,9 @@ parse! {
     pub struct Break<'a> {
         /// `break` token.
         pub break_: lex::Break<'a>,
+
+        /// Optional label of the loop to break out of.
+        pub label: Option<lex::Label<'a>>,
     }
 }
 
@@ -571,14 +1918,167 @@ parse! {
     }
 }
 
+parse! {
+    /// `defer { ... }` block.
+    ///
+    /// The block runs on every exit path of the enclosing scope (falling
+    /// through its end, `break`, `continue`, and `return`), in the reverse
+    /// order of the `defer` statements.
+    #[derive(Debug)]
+    pub struct Defer<'a> {
+        /// `defer` token.
+        pub defer_: lex::Defer<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Inner statements.
+        pub inner: Vec<Statement<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+// Deferred blocks run while leaving a scope, so control flow can only leave
+// the loops nested within them. `loops` holds the labels of those loops.
+fn check_defer<'a>(
+    statements: &[Statement<'a>],
+    loops: &mut Vec<Option<String>>,
+) -> Result<(), Error<'a>> {
+    // whether a `break` or `continue` targets one of the nested loops
+    let nested = |loops: &[Option<String>], label: &Option<lex::Label<'a>>| match label {
+        Some(label) => loops.contains(&Some(label.to_string())),
+        None => !loops.is_empty(),
+    };
+    for statement in statements {
+        let span = match statement {
+            Statement::Return(return_) => return_.span(),
+            Statement::Break(break_) if !nested(loops, &break_.label) => break_.span(),
+            Statement::Continue(continue_) if !nested(loops, &continue_.label) => continue_.span(),
+            _ => {
+                check_defer_inner(statement, loops)?;
+                continue;
+            }
+        };
+        return Err(Error::DeferFlow { span });
+    }
+    Ok(())
+}
+
+fn check_defer_inner<'a>(
+    statement: &Statement<'a>,
+    loops: &mut Vec<Option<String>>,
+) -> Result<(), Error<'a>> {
+    let label = |label: &Option<LoopLabel<'_>>| label.as_ref().map(|l| l.label.to_string());
+    match statement {
+        Statement::If(if_) => check_defer(&if_.inner, loops),
+        Statement::IfElse(if_else) => {
+            check_defer(&if_else.if_.inner, loops)?;
+            check_defer(&if_else.else_.inner, loops)
+        }
+        Statement::IfConst(if_const) => check_defer(if_const.statements(), loops),
+        Statement::Scope(scope) => check_defer(&scope.inner, loops),
+        Statement::MacroCall(call) => check_defer(&call.inner, loops),
+        Statement::Defer(defer) => check_defer(&defer.inner, loops),
+        Statement::Match(match_) => {
+            for arm in &match_.arms {
+                check_defer(&arm.inner, loops)?;
+            }
+            match &match_.else_ {
+                Some(else_) => check_defer(&else_.inner, loops),
+                None => Ok(()),
+            }
+        }
+        Statement::For(for_) => check_defer_loop(&for_.inner, label(&for_.label), loops),
+        Statement::Loop(loop_) => check_defer_loop(&loop_.inner, label(&loop_.label), loops),
+        Statement::While(while_) => check_defer_loop(&while_.inner, label(&while_.label), loops),
+        _ => Ok(()),
+    }
+}
+
+fn check_defer_loop<'a>(
+    statements: &[Statement<'a>],
+    label: Option<String>,
+    loops: &mut Vec<Option<String>>,
+) -> Result<(), Error<'a>> {
+    loops.push(label);
+    let result = check_defer(statements, loops);
+    loops.pop();
+    result
+}
+
 #[cfg(test)]
 mod test {
-    use crate::ast::Ast;
+    use crate::{
+        ast::{Ast, Statement},
+        ContextBuilder, Error,
+    };
 
     fn parse_program(input: &str) -> Ast<'_> {
         crate::parse(input).unwrap()
     }
 
+    fn parse_tolerant(input: &str) -> (Ast<'_>, Vec<Error<'_>>) {
+        let mut context = ContextBuilder::default().error_tolerant(true).build();
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        (ast, context.take_errors())
+    }
+
+    #[test]
+    fn error_tolerant() {
+        let (ast, errors) = parse_tolerant("let = 1\nfn foo { (+ 1 } static X:u8");
+        assert_eq!(2, errors.len());
+        assert_eq!(3, ast.inner.len());
+        match &ast.inner[0] {
+            Statement::Error(node) => {
+                assert_eq!(2, node.tokens.len());
+                assert_eq!([0, 0], node.span.min);
+                assert_eq!([0, 7], node.span.max);
+            }
+            _ => panic!(),
+        }
+        match &ast.inner[1] {
+            Statement::Fn(fn_) => assert!(matches!(fn_.inner[..], [Statement::Error(_)])),
+            _ => panic!(),
+        }
+        assert!(matches!(ast.inner[2], Statement::Static(_)));
+    }
+
+    #[test]
+    fn error_tolerant_brackets() {
+        let (ast, errors) = parse_tolerant("} static X:u8 }");
+        assert_eq!(2, errors.len());
+        assert!(matches!(
+            ast.inner[..],
+            [
+                Statement::Error(_),
+                Statement::Static(_),
+                Statement::Error(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn error_tolerant_lexer() {
+        let (ast, errors) = parse_tolerant("let x:u8 = $ static Y:u8");
+        assert!(matches!(
+            errors[..],
+            [Error::UnexpectedByte { byte: b'$', .. }]
+        ));
+        assert!(matches!(
+            ast.inner[..],
+            [Statement::Error(_), Statement::Static(_)]
+        ));
+    }
+
+    #[test]
+    fn error_tolerant_valid() {
+        let (ast, errors) = parse_tolerant("static X:u8 fn foo { (= X 1) }");
+        assert!(errors.is_empty());
+        assert_eq!(2, ast.inner.len());
+    }
+
     #[test]
     fn if_else_() {
         parse_program("if 42 { }");
@@ -659,6 +2159,26 @@ mod test {
         parse_program("const FOO : u8 = 42");
     }
 
+    #[test]
+    fn const_value() {
+        let input = "const A:u8 = 0x10\n\
+                     const B:u16 = (- (<< A 4) 1)\n\
+                     const C:u16 = (+ B 1)\n\
+                     mod m { const A:u8 = (* A 2) }\n\
+                     const D:u8 = (+ m::A FOO)";
+        let mut context = ContextBuilder::default().build();
+        crate::parse_with_context(input, &mut context).unwrap();
+        assert_eq!(Some(0x10), context.const_value("A"));
+        assert_eq!(Some(0xff), context.const_value("B"));
+        assert_eq!(Some(0x100), context.const_value("C"));
+        assert_eq!(Some(0x20), context.const_value("m::A"));
+        assert_eq!(None, context.const_value("D"));
+
+        // values that don't fit in the type of the const
+        let error = crate::parse("const B:u16 = 0xff\nconst C:u8 = (+ B 1)").unwrap_err();
+        assert_eq!("Literal out of range for `u8`: 256", error.to_string());
+    }
+
     #[test]
     #[should_panic]
     fn const_panic() {
@@ -679,10 +2199,23 @@ mod test {
         parse_program("loop {loop{}} loop {}");
     }
 
+    #[test]
+    fn defer() {
+        parse_program("loop { defer { } break }");
+        parse_program("fn f { defer { for i:u8 in 0..4 { continue } 'a: loop { break 'a } } }");
+        for input in &[
+            "fn f { defer { return } }",
+            "loop { defer { break } }",
+            "'a: loop { defer { loop { continue 'a } } }",
+        ] {
+            assert!(matches!(crate::parse(input), Err(Error::DeferFlow { .. })));
+        }
+    }
+
     #[test]
     fn let_() {
         parse_program("let foo:u8 = 42");
-        parse_program("let foo_bar:u8 = 0xffff");
+        parse_program("let foo_bar:u16 = 0xffff");
     }
 
     #[test]
@@ -0,0 +1,94 @@
+use crate::ast::Path;
+
+/// Intrinsic functions of the `builtin::` namespace.
+///
+/// Builtins are called like regular functions (`(builtin::memset @buf 0 16)`),
+/// but they aren't defined in the program. They are lowered to dedicated
+/// statements of the intermediate representation instead of routine calls.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Builtin {
+    /// `builtin::memcpy dst src len`
+    Memcpy,
+
+    /// `builtin::memset dst value len`
+    Memset,
+
+    /// `builtin::swap_nibbles byte`
+    SwapNibbles,
+
+    /// `builtin::bcd_adjust byte`
+    BcdAdjust,
+
+    /// `builtin::halt`
+    Halt,
+
+    /// `builtin::enable_interrupts`
+    EnableInterrupts,
+}
+
+impl Builtin {
+    /// Namespace of the builtins.
+    pub const NAMESPACE: &'static str = "builtin";
+
+    /// All the builtins, in declaration order.
+    pub const ALL: [Self; 6] = [
+        Self::Memcpy,
+        Self::Memset,
+        Self::SwapNibbles,
+        Self::BcdAdjust,
+        Self::Halt,
+        Self::EnableInterrupts,
+    ];
+
+    /// Builtin named by a path, if it is one.
+    pub fn from_path(path: &Path<'_>) -> Option<Self> {
+        match path.tail.as_slice() {
+            [(_, name)] if is_namespace(path) => Self::from_name(name.name()),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|b| b.name() == name)
+    }
+
+    /// Name of the builtin, without the namespace.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Memcpy => "memcpy",
+            Self::Memset => "memset",
+            Self::SwapNibbles => "swap_nibbles",
+            Self::BcdAdjust => "bcd_adjust",
+            Self::Halt => "halt",
+            Self::EnableInterrupts => "enable_interrupts",
+        }
+    }
+
+    /// Number of arguments of the builtin.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Memcpy | Self::Memset => 3,
+            Self::SwapNibbles | Self::BcdAdjust => 1,
+            Self::Halt | Self::EnableInterrupts => 0,
+        }
+    }
+}
+
+/// Returns whether the path is within the `builtin::` namespace.
+pub(crate) fn is_namespace(path: &Path<'_>) -> bool {
+    path.head.name() == Builtin::NAMESPACE
+}
+
+#[cfg(test)]
+mod test {
+    use super::Builtin;
+
+    #[test]
+    fn names() {
+        for builtin in Builtin::ALL.iter().copied() {
+            assert_eq!(Some(builtin), Builtin::from_name(builtin.name()));
+        }
+        assert_eq!(None, Builtin::from_name("memmove"));
+    }
+}
@@ -0,0 +1,524 @@
+//! Completion of identifiers.
+use crate::{
+    ast::{types::Member, Ast, Field, Fn, Statement, Type},
+    lex::{
+        self,
+        span::{LineIndex, Span, Spanned},
+    },
+    resolve::SymbolKind,
+};
+use std::cmp::Reverse;
+
+// maximum number of type aliases followed to find the members of a type.
+const ALIAS_LIMIT: usize = 16;
+
+/// Symbol that can be completed at a location.
+///
+/// Returned by [`completions_at`](completions_at).
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompletionItem {
+    /// Name of the symbol, relative to the qualifier being completed.
+    pub label: String,
+
+    /// Kind of the symbol.
+    pub kind: SymbolKind,
+
+    /// Span of the declared identifier.
+    pub declaration: Span,
+}
+
+/// Symbols visible at a byte offset of the source code of a program, sorted by
+/// name.
+///
+/// The identifier being typed before the offset filters the symbols by
+/// prefix, and its qualifier (`m::`, `FOO::`, `Enum::`) selects the
+/// declarations of a module, the members (fields and bitfields) of a static,
+/// local or argument, or the variants of an enum. Locals are visible after
+/// their declaration, following the scoping rules of the language.
+///
+/// The `Ast` must have been parsed from `input`. For incomplete programs,
+/// parse them in [error-tolerant](crate::ContextBuilder::error_tolerant) mode:
+/// the scopes of the statements that failed to parse are lost, but the rest of
+/// the program is still visible.
+///
+/// ```
+/// use parser::ast::completions_at;
+///
+/// let input = "static PLAYER:struct { x:u8 y:u8 }\nfn f(pos:u8) { (= PLAYER::x p) }";
+/// let ast = parser::parse(input).unwrap();
+///
+/// let labels = |offset| -> Vec<_> {
+///     completions_at(&ast, input, offset)
+///         .into_iter()
+///         .map(|item| item.label)
+///         .collect()
+/// };
+/// assert_eq!(vec!["pos"], labels(input.len() - 3));
+/// assert_eq!(vec!["x", "y"], labels(input.len() - 6));
+/// assert_eq!(vec!["PLAYER", "f", "pos"], labels(input.len() - 16));
+/// ```
+pub fn completions_at(ast: &Ast<'_>, input: &str, offset: usize) -> Vec<CompletionItem> {
+    let mut offset = offset.min(input.len());
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let (qualifier, partial) = prefix(&input[..offset]);
+    let mut scopes = Scopes {
+        position: LineIndex::new(input).position(offset),
+        scopes: vec![Vec::new()],
+        modules: Vec::new(),
+        visible: None,
+    };
+    scopes.statements(&ast.inner);
+    let visible = match scopes.visible {
+        Some(visible) => visible,
+        None => return Vec::new(),
+    };
+
+    // candidates, with the depth of the scope and module they are visible
+    // from, so that the innermost ones shadow the rest
+    let mut candidates = Vec::new();
+    for (depth, symbol) in &visible.symbols {
+        if let Some(level) = visible.relative(&symbol.path, &qualifier) {
+            let label = symbol.path.last().unwrap().clone();
+            candidates.push(((*depth, level), label, symbol.kind, symbol.declaration));
+        }
+    }
+    for member in visible.members(&qualifier) {
+        let label = member.name().to_string();
+        candidates.push(((0, 0), label, SymbolKind::Member, member.span()));
+    }
+    candidates.sort_by_key(|candidate| Reverse(candidate.0));
+
+    let mut items: Vec<CompletionItem> = Vec::new();
+    for (_, label, kind, declaration) in candidates {
+        if label.starts_with(partial) && items.iter().all(|item| item.label != label) {
+            items.push(CompletionItem {
+                label,
+                kind,
+                declaration,
+            });
+        }
+    }
+    items.sort_by(|l, r| l.label.cmp(&r.label));
+    items
+}
+
+// qualifier and identifier being typed at the end of the input
+// (`m::FOO::ba` is `(["m", "FOO"], "ba")`).
+fn prefix(input: &str) -> (Vec<String>, &str) {
+    // start of the identifier at the end of the input
+    let ident = |input: &str| {
+        input
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    };
+    let mut start = ident(input);
+    let partial = &input[start..];
+    let mut qualifier = Vec::new();
+    while input[..start].ends_with("::") {
+        let end = start - 2;
+        start = ident(&input[..end]);
+        if start == end {
+            break;
+        }
+        qualifier.push(input[start..end].to_string());
+    }
+    qualifier.reverse();
+    (qualifier, partial)
+}
+
+// declared symbol.
+#[derive(Clone)]
+struct Symbol<'b, 'a> {
+    // name, prefixed with the modules it was declared in
+    path: Vec<String>,
+    kind: SymbolKind,
+    declaration: Span,
+    // declared field of statics, consts, locals, and arguments
+    field: Option<&'b Field<'a>>,
+    // aliased type of type aliases
+    alias: Option<&'b Type<'a>>,
+}
+
+// symbols visible at a location, with the depth of the scope they were
+// declared in.
+struct Visible<'b, 'a> {
+    modules: Vec<String>,
+    symbols: Vec<(usize, Symbol<'b, 'a>)>,
+}
+
+impl<'b, 'a> Visible<'b, 'a> {
+    // depth of the innermost module from which a path is the given qualifier
+    // followed by a single name.
+    fn relative(&self, path: &[String], qualifier: &[String]) -> Option<usize> {
+        (0..=self.modules.len()).rev().find(|&i| {
+            let prefix = self.modules[..i].iter().chain(qualifier);
+            path.len() == i + qualifier.len() + 1 && prefix.eq(&path[..path.len() - 1])
+        })
+    }
+
+    // innermost visible symbol with the given name.
+    fn find(&self, name: &[String]) -> Option<&Symbol<'b, 'a>> {
+        let (last, qualifier) = name.split_last()?;
+        self.symbols
+            .iter()
+            .filter(|(_, symbol)| symbol.path.last() == Some(last))
+            .filter_map(|(depth, symbol)| {
+                let level = self.relative(&symbol.path, qualifier)?;
+                Some(((*depth, level), symbol))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, symbol)| symbol)
+    }
+
+    // fields and bitfields of the value a qualifier names.
+    fn members(&self, qualifier: &[String]) -> Vec<&'b lex::Ident<'a>> {
+        let field = match self.field(qualifier) {
+            Some(field) => field,
+            None => return Vec::new(),
+        };
+        let fields = self.fields(&field.type_, 0).into_iter().map(|f| &f.ident);
+        let bits = field.bits.iter().flat_map(|b| &b.fields).map(|b| &b.ident);
+        fields.chain(bits).collect()
+    }
+
+    // field a qualifier names: a static, local or argument (`FOO`), or one of
+    // their fields (`FOO::bar`).
+    fn field(&self, qualifier: &[String]) -> Option<&'b Field<'a>> {
+        (1..=qualifier.len()).rev().find_map(|len| {
+            let mut field = self.find(&qualifier[..len])?.field?;
+            for name in &qualifier[len..] {
+                field = self
+                    .fields(&field.type_, 0)
+                    .into_iter()
+                    .find(|f| f.ident.name() == name)?;
+            }
+            Some(field)
+        })
+    }
+
+    // fields of a struct or union type, including the ones of its anonymous
+    // members, following type aliases.
+    fn fields(&self, type_: &'b Type<'a>, aliases: usize) -> Vec<&'b Field<'a>> {
+        fn flatten<'b, 'a>(members: &'b [Member<'a>], fields: &mut Vec<&'b Field<'a>>) {
+            for member in members {
+                match member {
+                    Member::Field(field) => fields.push(field),
+                    Member::Struct(struct_) => flatten(&struct_.fields, fields),
+                    Member::Union(union) => flatten(&union.fields, fields),
+                }
+            }
+        }
+
+        let mut fields = Vec::new();
+        match type_ {
+            Type::Struct(struct_) => flatten(&struct_.fields, &mut fields),
+            Type::Union(union) => flatten(&union.fields, &mut fields),
+            Type::Path(path) if aliases < ALIAS_LIMIT => {
+                let name: Vec<_> = path.iter().map(|i| i.name().to_string()).collect();
+                if let Some(alias) = self.find(&name).and_then(|symbol| symbol.alias) {
+                    fields = self.fields(alias, aliases + 1);
+                }
+            }
+            _ => {}
+        }
+        fields
+    }
+}
+
+struct Scopes<'b, 'a> {
+    // location being completed
+    position: [usize; 2],
+    // symbols of the visible scopes, innermost last
+    scopes: Vec<Vec<Symbol<'b, 'a>>>,
+    // modules being visited, innermost last
+    modules: Vec<String>,
+    // symbols visible from the location, once found
+    visible: Option<Visible<'b, 'a>>,
+}
+
+impl<'b, 'a> Scopes<'b, 'a> {
+    fn declare(&mut self, ident: &lex::Ident<'a>, kind: SymbolKind, field: Option<&'b Field<'a>>) {
+        self.declare_in(self.modules.clone(), ident, kind, field, None);
+    }
+
+    fn declare_in(
+        &mut self,
+        mut path: Vec<String>,
+        ident: &lex::Ident<'a>,
+        kind: SymbolKind,
+        field: Option<&'b Field<'a>>,
+        alias: Option<&'b Type<'a>>,
+    ) {
+        path.push(ident.name().to_string());
+        self.scopes.last_mut().unwrap().push(Symbol {
+            path,
+            kind,
+            declaration: ident.span(),
+            field,
+            alias,
+        });
+    }
+
+    // declare the symbols that can be used before their definition, including
+    // the ones of nested modules.
+    fn hoist(&mut self, modules: &[String], statements: &'b [Statement<'a>]) {
+        for statement in statements {
+            let path = modules.to_vec();
+            match statement {
+                Statement::Fn(fn_) => {
+                    self.declare_in(path, &fn_.ident, SymbolKind::Function, None, None)
+                }
+                Statement::Static(static_) => {
+                    let field = Some(&static_.field);
+                    self.declare_in(path, &static_.field.ident, SymbolKind::Static, field, None)
+                }
+                Statement::Const(const_) => {
+                    let field = Some(&const_.field);
+                    self.declare_in(path, &const_.field.ident, SymbolKind::Const, field, None)
+                }
+                Statement::TypeAlias(alias) => {
+                    let type_ = Some(&alias.inner);
+                    self.declare_in(path, &alias.ident, SymbolKind::TypeAlias, None, type_)
+                }
+                Statement::Enum(enum_) => {
+                    let mut variants = path.clone();
+                    variants.push(enum_.ident.name().to_string());
+                    self.declare_in(path, &enum_.ident, SymbolKind::Enum, None, None);
+                    for variant in &enum_.variants {
+                        let kind = SymbolKind::Variant;
+                        self.declare_in(variants.clone(), &variant.ident, kind, None, None);
+                    }
+                }
+                Statement::Mod(mod_) => {
+                    let mut inner = path.clone();
+                    inner.push(mod_.ident.name().to_string());
+                    self.declare_in(path, &mod_.ident, SymbolKind::Module, None, None);
+                    self.hoist(&inner, &mod_.inner);
+                }
+                Statement::Import(import) => {
+                    let mut inner = path;
+                    inner.push(import.name());
+                    self.hoist(&inner, &import.inner);
+                }
+                Statement::IfConst(if_const) => self.hoist(modules, if_const.statements()),
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &'b [Statement<'a>]) {
+        self.scopes.push(Vec::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &'b [Statement<'a>]) {
+        let modules = self.modules.clone();
+        self.hoist(&modules, statements);
+        for statement in statements {
+            if self.visible.is_some() {
+                return;
+            }
+            let span = statement.span();
+            if span.min > self.position {
+                break;
+            } else if span.max <= self.position {
+                self.declare_locals(statement);
+            } else {
+                self.statement(statement);
+                break;
+            }
+        }
+        if self.visible.is_none() {
+            let symbols = self
+                .scopes
+                .iter()
+                .enumerate()
+                .flat_map(|(depth, scope)| scope.iter().map(move |s| (depth, s.clone())))
+                .collect();
+            self.visible = Some(Visible {
+                modules: self.modules.clone(),
+                symbols,
+            });
+        }
+    }
+
+    // declare the locals of a statement located before the completion.
+    fn declare_locals(&mut self, statement: &'b Statement<'a>) {
+        match statement {
+            Statement::Let(let_) => {
+                self.declare(&let_.field.ident, SymbolKind::Local, Some(&let_.field))
+            }
+            Statement::LetTuple(let_) => {
+                for field in &let_.fields {
+                    self.declare(&field.ident, SymbolKind::Local, Some(field));
+                }
+            }
+            Statement::Macro(macro_) => self.declare(&macro_.ident, SymbolKind::Macro, None),
+            Statement::IfConst(if_const) => {
+                for statement in if_const.statements() {
+                    self.declare_locals(statement);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // visit the statement the completion is located in.
+    fn statement(&mut self, statement: &'b Statement<'a>) {
+        match statement {
+            Statement::If(if_) => self.block(&if_.inner),
+            Statement::IfElse(if_else) => {
+                if if_else.else_.span().min <= self.position {
+                    self.block(&if_else.else_.inner);
+                } else {
+                    self.block(&if_else.if_.inner);
+                }
+            }
+            Statement::IfConst(if_const) => self.statements(if_const.statements()),
+            Statement::Scope(scope) => self.block(&scope.inner),
+            Statement::Defer(defer) => self.block(&defer.inner),
+            Statement::Loop(loop_) => self.block(&loop_.inner),
+            Statement::While(while_) => self.block(&while_.inner),
+            Statement::Match(match_) => {
+                let arm = match_
+                    .arms
+                    .iter()
+                    .find(|arm| arm.span().min <= self.position && self.position < arm.span().max);
+                match (arm, &match_.else_) {
+                    (Some(arm), _) => self.block(&arm.inner),
+                    (None, Some(else_)) if else_.span().min <= self.position => {
+                        self.block(&else_.inner)
+                    }
+                    _ => {}
+                }
+            }
+            Statement::For(for_) => {
+                self.scopes.push(Vec::new());
+                if for_.left_bracket.span().max <= self.position {
+                    self.declare(&for_.field.ident, SymbolKind::Local, Some(&for_.field));
+                }
+                self.statements(&for_.inner);
+                self.scopes.pop();
+            }
+            Statement::Mod(mod_) => {
+                self.modules.push(mod_.ident.name().to_string());
+                self.statements(&mod_.inner);
+                self.modules.pop();
+            }
+            Statement::Fn(fn_) => self.fn_(fn_),
+            _ => {}
+        }
+    }
+
+    fn fn_(&mut self, fn_: &'b Fn<'a>) {
+        self.scopes.push(Vec::new());
+        for param in fn_.generics.iter().flat_map(|g| &g.params) {
+            self.declare(&param.field.ident, SymbolKind::Const, Some(&param.field));
+        }
+        for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+            self.declare(&field.ident, SymbolKind::Argument, Some(field));
+        }
+        self.statements(&fn_.inner);
+        self.scopes.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::completions_at;
+    use crate::resolve::SymbolKind;
+
+    // labels and kinds of the completions at the `|` of the (incomplete)
+    // input.
+    fn complete(input: &str) -> Vec<(String, SymbolKind)> {
+        let offset = input.find('|').unwrap();
+        let input = input.replacen('|', " ", 1);
+        let (ast, _) = crate::parse_recovering(&input);
+        completions_at(&ast, &input, offset)
+            .into_iter()
+            .map(|item| (item.label, item.kind))
+            .collect()
+    }
+
+    fn labels(input: &str) -> Vec<String> {
+        complete(input)
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect()
+    }
+
+    #[test]
+    fn scopes() {
+        let input = "static A:u8\n\
+                     fn f(x:u8) { let y:u8 = 1 { let z:u8 = 2 } (= A |) let w:u8 = 3 }\n\
+                     const B:u8 = 1";
+        assert_eq!(
+            vec![
+                ("A".to_string(), SymbolKind::Static),
+                ("B".to_string(), SymbolKind::Const),
+                ("f".to_string(), SymbolKind::Function),
+                ("x".to_string(), SymbolKind::Argument),
+                ("y".to_string(), SymbolKind::Local),
+            ],
+            complete(input)
+        );
+        let input = "for i:u8 in 0..4 { let j:u8 = i | }\nlet k:u8 = 0";
+        assert_eq!(vec!["i", "j"], labels(input));
+        assert_eq!(vec!["k"], labels("let k:u8 = 0\n|"));
+    }
+
+    #[test]
+    fn shadowing() {
+        let input = "static x:u16\nfn f(x:u8) { (= x |) }";
+        assert_eq!(
+            vec![
+                ("f".to_string(), SymbolKind::Function),
+                ("x".to_string(), SymbolKind::Argument),
+            ],
+            complete(input)
+        );
+    }
+
+    #[test]
+    fn qualified() {
+        let input = "mod m { static A:u8 fn f { } mod n { const B:u8 = 1 } }\n\
+                     enum E { X Y }\n\
+                     (= m::|)";
+        assert_eq!(vec!["A", "f", "n"], labels(input));
+        let input = "mod m { static A:u8 mod n { const B:u8 = 1 } }\n(= m::n::|)";
+        assert_eq!(vec!["B"], labels(input));
+        let input = "enum E { X Y }\n(= E::|)";
+        assert_eq!(
+            vec![
+                ("X".to_string(), SymbolKind::Variant),
+                ("Y".to_string(), SymbolKind::Variant),
+            ],
+            complete(input)
+        );
+        // declarations of the enclosing modules are visible unqualified
+        let input = "static A:u8\nmod m { static B:u8 fn f { (= B|) } }";
+        assert_eq!(vec!["B"], labels(input));
+    }
+
+    #[test]
+    fn members() {
+        let input = "type Point = struct { x:u8 y:u8 }\n\
+                     static P:struct { pos:Point union { a:u8 b:i8 } }\n\
+                     static F:u8 .{ enabled:1 ready:1 }\n";
+        assert_eq!(vec!["a", "b", "pos"], labels(&format!("{}(= P::|)", input)));
+        assert_eq!(vec!["x", "y"], labels(&format!("{}(= P::pos::|)", input)));
+        assert_eq!(vec!["y"], labels(&format!("{}(= P::pos::y|)", input)));
+        assert_eq!(
+            vec![
+                ("enabled".to_string(), SymbolKind::Member),
+                ("ready".to_string(), SymbolKind::Member),
+            ],
+            complete(&format!("{}(= F::|)", input))
+        );
+    }
+}
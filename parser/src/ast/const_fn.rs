@@ -0,0 +1,538 @@
+//! Compile-time evaluation of `const fn`s.
+//!
+//! Const functions take and return integers and `bool`s. Their bodies are
+//! made of `let` locals, consts, assignments, `if` statements, loops,
+//! `return`, and calls to other const functions, and are run by a small
+//! interpreter whenever a call is parsed. Values are 16bit, and wrap around to
+//! the width of the local (or return type) they are assigned to. Paths that
+//! don't name a local resolve to the consts visible from the call.
+//!
+//! The number of statements executed by a call is bounded, so loops that never
+//! end make the call fail to evaluate, rather than the parser hang.
+use crate::{
+    ast::{expression::eval, types::Type, Context, Expression, Fn, LoopLabel, Statement},
+    lex,
+    lex::span::Spanned,
+    Error,
+};
+
+// statements executed by a call (and the calls it makes) before giving up.
+const MAX_STEPS: usize = 1 << 20;
+
+// calls nested within a call, through recursion.
+const MAX_DEPTH: usize = 64;
+
+// mask of the values of an integer type.
+fn mask(type_: &Type<'_>) -> Option<u16> {
+    match type_ {
+        Type::U8(_) | Type::I8(_) | Type::Bool(_) => Some(0xff),
+        Type::U16(_) | Type::I16(_) => Some(0xffff),
+        _ => None,
+    }
+}
+
+// fails unless the type is an integer or `bool` type.
+fn check_type<'a>(type_: &Type<'a>) -> Result<(), Error<'a>> {
+    match mask(type_) {
+        Some(_) => Ok(()),
+        None => Err(Error::ConstFn { span: type_.span() }),
+    }
+}
+
+/// Check that a `const fn` can be evaluated, reporting the offending part of
+/// its definition as an [`Error::ConstFn`](Error::ConstFn).
+pub(crate) fn check<'a>(fn_: &Fn<'a>) -> Result<(), Error<'a>> {
+    if let Some(attribute) = fn_.attributes.first() {
+        return Err(Error::ConstFn {
+            span: attribute.span(),
+        });
+    }
+    if let Some(interrupt) = &fn_.interrupt {
+        return Err(Error::ConstFn {
+            span: interrupt.span(),
+        });
+    }
+    if let Some(generics) = &fn_.generics {
+        return Err(Error::ConstFn {
+            span: generics.span(),
+        });
+    }
+    for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+        check_type(&field.type_)?;
+    }
+    match &fn_.fn_return {
+        Some(fn_return) => check_type(&fn_return.type_)?,
+        None => {
+            return Err(Error::ConstFn {
+                span: fn_.ident.span(),
+            })
+        }
+    }
+    check_statements(&fn_.inner)
+}
+
+fn check_statements<'a>(statements: &[Statement<'a>]) -> Result<(), Error<'a>> {
+    statements.iter().try_for_each(check_statement)
+}
+
+fn check_statement<'a>(statement: &Statement<'a>) -> Result<(), Error<'a>> {
+    use Statement as S;
+    match statement {
+        S::Let(let_) => {
+            check_type(&let_.field.type_)?;
+            check_expression(&let_.expression)
+        }
+        S::Const(const_) => {
+            check_type(&const_.field.type_)?;
+            check_expression(&const_.expression)
+        }
+        S::Inline(inline) => check_expression(&inline.inner),
+        S::If(if_) => {
+            check_expression(&if_.expression)?;
+            check_statements(&if_.inner)
+        }
+        S::IfElse(if_else) => {
+            check_expression(&if_else.if_.expression)?;
+            check_statements(&if_else.if_.inner)?;
+            check_statements(&if_else.else_.inner)
+        }
+        S::Scope(scope) => check_statements(&scope.inner),
+        S::For(for_) => {
+            check_type(&for_.field.type_)?;
+            check_expression(&for_.range.left)?;
+            check_expression(&for_.range.right)?;
+            check_statements(&for_.inner)
+        }
+        S::Loop(loop_) => check_statements(&loop_.inner),
+        S::While(while_) => {
+            check_expression(&while_.expression)?;
+            check_statements(&while_.inner)
+        }
+        S::Continue(_) | S::Break(_) => Ok(()),
+        S::Return(return_) => match &return_.expression {
+            Some(expression) => check_expression(expression),
+            None => Err(Error::ConstFn {
+                span: return_.span(),
+            }),
+        },
+        statement => Err(Error::ConstFn {
+            span: statement.span(),
+        }),
+    }
+}
+
+fn check_expression<'a>(expression: &Expression<'a>) -> Result<(), Error<'a>> {
+    use Expression as E;
+
+    macro_rules! binary {
+        ($node:expr) => {{
+            check_expression(&$node.inner.left)?;
+            check_expression(&$node.inner.right)
+        }};
+    }
+
+    macro_rules! assign {
+        ($node:expr) => {{
+            check_place(&$node.inner.left)?;
+            check_expression(&$node.inner.right)
+        }};
+    }
+
+    match expression {
+        E::Path(_) => Ok(()),
+        E::Lit(lit) if lit.str_value().is_none() && !lit.is_fixed() => Ok(()),
+        E::Minus(e) => check_expression(&e.inner),
+        E::Not(e) => check_expression(&e.inner),
+        E::Increment(e) => check_place(&e.inner.inner),
+        E::Decrement(e) => check_place(&e.inner.inner),
+        E::Conditional(e) => {
+            check_expression(&e.inner.condition)?;
+            check_expression(&e.inner.then)?;
+            check_expression(&e.inner.else_)
+        }
+        E::Cast(e) => {
+            check_type(&e.inner.type_)?;
+            check_expression(&e.inner.inner)
+        }
+        E::Add(e) => binary!(e),
+        E::Sub(e) => binary!(e),
+        E::Mul(e) => binary!(e),
+        E::Div(e) => binary!(e),
+        E::And(e) => binary!(e),
+        E::Or(e) => binary!(e),
+        E::Xor(e) => binary!(e),
+        E::LeftShift(e) => binary!(e),
+        E::RightShift(e) => binary!(e),
+        E::Eq(e) => binary!(e),
+        E::NotEq(e) => binary!(e),
+        E::LessEq(e) => binary!(e),
+        E::GreaterEq(e) => binary!(e),
+        E::Less(e) => binary!(e),
+        E::Greater(e) => binary!(e),
+        E::LogicalAnd(e) => binary!(e),
+        E::LogicalOr(e) => binary!(e),
+        E::Assign(e) => assign!(e),
+        E::PlusAssign(e) => assign!(e),
+        E::MinusAssign(e) => assign!(e),
+        E::MulAssign(e) => assign!(e),
+        E::DivAssign(e) => assign!(e),
+        E::AndAssign(e) => assign!(e),
+        E::OrAssign(e) => assign!(e),
+        E::XorAssign(e) => assign!(e),
+        E::Call(e) => {
+            check_place(&e.inner.left)?;
+            e.inner.args.iter().try_for_each(check_expression)
+        }
+        expression => Err(Error::ConstFn {
+            span: expression.span(),
+        }),
+    }
+}
+
+// locals, and the functions called, are named by a path.
+fn check_place<'a>(expression: &Expression<'a>) -> Result<(), Error<'a>> {
+    match expression {
+        Expression::Path(_) => Ok(()),
+        expression => Err(Error::ConstFn {
+            span: expression.span(),
+        }),
+    }
+}
+
+/// Evaluate a call to a `const fn` with the given arguments.
+///
+/// Returns `None` if the call doesn't return a value (a path that names
+/// neither a local nor a const, a division by zero, a loop that never ends,
+/// ...).
+pub(crate) fn call<'a>(ctx: &Context<'a>, fn_: &Fn<'a>, args: &[u16]) -> Option<u16> {
+    let mut interpreter = Interpreter {
+        ctx,
+        locals: Vec::new(),
+        steps: 0,
+        depth: 0,
+    };
+    interpreter.call(fn_, args)
+}
+
+// control flow out of a statement.
+enum Flow {
+    Next,
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(u16),
+}
+
+struct Interpreter<'c, 'a> {
+    ctx: &'c Context<'a>,
+    // name, value, and mask of the locals in scope, innermost last
+    locals: Vec<(&'a str, u16, u16)>,
+    steps: usize,
+    depth: usize,
+}
+
+impl<'c, 'a> Interpreter<'c, 'a> {
+    fn call(&mut self, fn_: &Fn<'a>, args: &[u16]) -> Option<u16> {
+        if self.depth == MAX_DEPTH {
+            return None;
+        }
+        let params = fn_.fn_arg.iter().flat_map(|a| &a.inner);
+        let locals = params
+            .zip(args)
+            .map(|(field, arg)| {
+                let mask = mask(&field.type_)?;
+                Some((field.ident.name(), arg & mask, mask))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let caller = std::mem::replace(&mut self.locals, locals);
+        self.depth += 1;
+        let flow = self.block(&fn_.inner);
+        self.depth -= 1;
+        self.locals = caller;
+        match flow? {
+            Flow::Return(value) => Some(value & mask(&fn_.fn_return.as_ref()?.type_)?),
+            // falling through (or out of a loop) returns no value
+            _ => None,
+        }
+    }
+
+    // run statements in a scope of their own.
+    fn block(&mut self, statements: &[Statement<'a>]) -> Option<Flow> {
+        // empty blocks count too, for the loops without statements
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return None;
+        }
+        let locals = self.locals.len();
+        let mut flow = Some(Flow::Next);
+        for statement in statements {
+            flow = self.statement(statement);
+            if !matches!(flow, Some(Flow::Next)) {
+                break;
+            }
+        }
+        self.locals.truncate(locals);
+        flow
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) -> Option<Flow> {
+        use Statement as S;
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return None;
+        }
+        match statement {
+            S::Let(let_) => {
+                let mask = mask(&let_.field.type_)?;
+                let value = self.eval(&let_.expression)?;
+                self.locals
+                    .push((let_.field.ident.name(), value & mask, mask));
+            }
+            S::Const(const_) => {
+                let mask = mask(&const_.field.type_)?;
+                let value = self.eval(&const_.expression)?;
+                self.locals
+                    .push((const_.field.ident.name(), value & mask, mask));
+            }
+            S::Inline(inline) => {
+                self.eval(&inline.inner)?;
+            }
+            S::If(if_) => {
+                if self.eval(&if_.expression)? != 0 {
+                    return self.block(&if_.inner);
+                }
+            }
+            S::IfElse(if_else) => {
+                return if self.eval(&if_else.if_.expression)? != 0 {
+                    self.block(&if_else.if_.inner)
+                } else {
+                    self.block(&if_else.else_.inner)
+                };
+            }
+            S::Scope(scope) => return self.block(&scope.inner),
+            S::For(for_) => {
+                let mask = mask(&for_.field.type_)?;
+                let left = u32::from(self.eval(&for_.range.left)?);
+                let mut right = u32::from(self.eval(&for_.range.right)?);
+                if for_.range.plus.is_some() {
+                    right += left;
+                }
+                if for_.range.eq.is_some() {
+                    right += 1;
+                }
+                for i in left..right {
+                    self.locals
+                        .push((for_.field.ident.name(), i as u16 & mask, mask));
+                    let flow = self.block(&for_.inner);
+                    self.locals.pop();
+                    match self.looped(&for_.label, flow?) {
+                        Flow::Next => {}
+                        Flow::Break(None) => break,
+                        flow => return Some(flow),
+                    }
+                }
+            }
+            S::Loop(loop_) => loop {
+                let flow = self.block(&loop_.inner)?;
+                match self.looped(&loop_.label, flow) {
+                    Flow::Next => {}
+                    Flow::Break(None) => break,
+                    flow => return Some(flow),
+                }
+            },
+            S::While(while_) => {
+                while self.eval(&while_.expression)? != 0 {
+                    let flow = self.block(&while_.inner)?;
+                    match self.looped(&while_.label, flow) {
+                        Flow::Next => {}
+                        Flow::Break(None) => break,
+                        flow => return Some(flow),
+                    }
+                }
+            }
+            S::Break(break_) => return Some(Flow::Break(label(&break_.label))),
+            S::Continue(continue_) => return Some(Flow::Continue(label(&continue_.label))),
+            S::Return(return_) => {
+                return Some(Flow::Return(self.eval(return_.expression.as_ref()?)?))
+            }
+            _ => return None,
+        }
+        Some(Flow::Next)
+    }
+
+    // control flow after an iteration of a loop with the given label.
+    // Returns `Flow::Next` to keep looping, and an unlabeled `Flow::Break` to
+    // end the loop.
+    fn looped(&self, label: &Option<LoopLabel<'a>>, flow: Flow) -> Flow {
+        let this = |target: &Option<String>| match (target, label) {
+            (None, _) => true,
+            (Some(target), Some(label)) => *target == label.label.to_string(),
+            (Some(_), None) => false,
+        };
+        match flow {
+            Flow::Continue(target) if this(&target) => Flow::Next,
+            Flow::Break(target) if this(&target) => Flow::Break(None),
+            flow => flow,
+        }
+    }
+
+    fn eval(&mut self, expression: &Expression<'a>) -> Option<u16> {
+        eval::eval(expression, &mut |expression| self.resolve(expression))
+    }
+
+    // operands the evaluator doesn't know the value of.
+    fn resolve(&mut self, expression: &Expression<'a>) -> Option<u16> {
+        use Expression as E;
+
+        macro_rules! assign {
+            ($node:expr, |$l:ident, $r:ident| $value:expr) => {{
+                let $r = self.eval(&$node.inner.right)?;
+                let $l = self.local(&$node.inner.left)?;
+                self.assign(&$node.inner.left, $value)
+            }};
+        }
+
+        match expression {
+            E::Path(path) => match self.local(expression) {
+                Some(value) => Some(value),
+                None => {
+                    let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                    self.ctx.const_value(&name.join("::"))
+                }
+            },
+            E::Cast(e) => Some(self.eval(&e.inner.inner)? & mask(&e.inner.type_)?),
+            E::Assign(e) => assign!(e, |_l, r| r),
+            E::PlusAssign(e) => assign!(e, |l, r| l.wrapping_add(r)),
+            E::MinusAssign(e) => assign!(e, |l, r| l.wrapping_sub(r)),
+            E::MulAssign(e) => assign!(e, |l, r| l.wrapping_mul(r)),
+            E::DivAssign(e) => assign!(e, |l, r| l.checked_div(r)?),
+            E::AndAssign(e) => assign!(e, |l, r| l & r),
+            E::OrAssign(e) => assign!(e, |l, r| l | r),
+            E::XorAssign(e) => assign!(e, |l, r| l ^ r),
+            E::Increment(e) => {
+                let value = self.local(&e.inner.inner)?;
+                self.assign(&e.inner.inner, value.wrapping_add(1))
+            }
+            E::Decrement(e) => {
+                let value = self.local(&e.inner.inner)?;
+                self.assign(&e.inner.inner, value.wrapping_sub(1))
+            }
+            E::Call(e) => {
+                let fn_ = match &e.inner.left {
+                    E::Path(path) => {
+                        let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                        self.ctx.const_fn(&name.join("::"))?
+                    }
+                    _ => return None,
+                };
+                let args = e
+                    .inner
+                    .args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Option<Vec<_>>>()?;
+                if fn_.fn_arg.iter().flat_map(|a| &a.inner).count() != args.len() {
+                    return None;
+                }
+                self.call(fn_, &args)
+            }
+            _ => None,
+        }
+    }
+
+    // value of the local named by a path.
+    fn local(&self, expression: &Expression<'a>) -> Option<u16> {
+        let name = local_name(expression)?;
+        let (_, value, _) = self.locals.iter().rev().find(|(n, ..)| *n == name)?;
+        Some(*value)
+    }
+
+    // assign the local named by a path, returning its new value.
+    fn assign(&mut self, expression: &Expression<'a>, value: u16) -> Option<u16> {
+        let name = local_name(expression)?;
+        let (_, local, mask) = self.locals.iter_mut().rev().find(|(n, ..)| *n == name)?;
+        *local = value & *mask;
+        Some(*local)
+    }
+}
+
+// name of a local, if the path could name one.
+fn local_name<'a>(expression: &Expression<'a>) -> Option<&'a str> {
+    match expression {
+        Expression::Path(path) if path.tail.is_empty() => Some(path.head.name()),
+        _ => None,
+    }
+}
+
+fn label(label: &Option<lex::Label<'_>>) -> Option<String> {
+    label.as_ref().map(|l| l.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::Statement, Error};
+
+    fn consts(input: &str) -> Vec<Option<u16>> {
+        let mut context = crate::ContextBuilder::default().build();
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        ast.inner
+            .iter()
+            .filter_map(|s| match s {
+                Statement::Const(const_) => Some(context.eval(&const_.expression)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn eval() {
+        let input = "const fn sq(x:u8):u8 { return (* x x) }\n\
+                     const fn sum(n:u8):u16 {\n\
+                         let acc:u16 = 0\n\
+                         for i:u8 in 0..=n { (+= acc (sq i)) }\n\
+                         return acc\n\
+                     }\n\
+                     const A:u8 = (sq 12)\n\
+                     const B:u8 = (sq 16)\n\
+                     const C:u16 = (sum 10)";
+        assert_eq!(vec![Some(144), Some(0), Some(385)], consts(input));
+    }
+
+    #[test]
+    fn loops() {
+        let input = "const fn log2(x:u16):u8 {\n\
+                         let n:u8 = 0\n\
+                         while (> x 1) { (= x (>> x 1)) (++ n) }\n\
+                         return n\n\
+                     }\n\
+                     const fn first(mask:u8):u8 {\n\
+                         let i:u8 = 0\n\
+                         loop { if (& mask (<< 1 i)) { break } (++ i) }\n\
+                         return i\n\
+                     }\n\
+                     const X:u8 = (log2 1024)\n\
+                     const Y:u8 = (first 0x30)";
+        assert_eq!(vec![Some(10), Some(4)], consts(input));
+    }
+
+    #[test]
+    fn not_const() {
+        let input = "const fn forever():u8 { loop {} }\nconst X:u8 = (forever)";
+        assert!(matches!(crate::parse(input), Err(Error::NotConst { .. })));
+        let input = "const fn id(x:u8):u8 { return x }\nstatic S:u8\nstatic T:u8 = (id S)";
+        assert!(matches!(crate::parse(input), Err(Error::NotConst { .. })));
+        let input = "static S:u8\nconst fn f():u8 { return S }\nconst X:u8 = (f)";
+        assert!(matches!(crate::parse(input), Err(Error::NotConst { .. })));
+    }
+
+    #[test]
+    fn unsupported() {
+        for input in &[
+            "const fn f(p:&u8):u8 { return 0 }",
+            "const fn f() { }",
+            "const fn f():u8 { static X:u8 }",
+            "const fn f():u8 { return @f }",
+        ] {
+            assert!(matches!(crate::parse(input), Err(Error::ConstFn { .. })));
+        }
+    }
+}
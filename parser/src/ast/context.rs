@@ -1,24 +1,280 @@
-use crate::ast::Path;
-use std::collections::HashSet;
+use crate::{
+    arena::{Arena, Node},
+    ast::{
+        expression::eval,
+        r#macro::expansion_end,
+        types::{self, Type},
+        Bits, Const, Expression, Fn, GenericParam, Grammar, Macro, Path, Statement, StaticAssert,
+    },
+    lex,
+    lex::span::{LineIndex, SourceId, SourceMap, Span, Spanned},
+    resolve::Resolutions,
+    Error, LanguageVersion, Tokens,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+};
 
-#[derive(Default, Debug)]
-pub struct ContextBuilder {
-    _phantom: std::marker::PhantomData<()>,
+// default nesting limit of blocks, expressions, and types.
+const NESTING_LIMIT: usize = 128;
+
+// id and source code of an imported file, given its path.
+type Resolver<'a> = Box<dyn FnMut(&str) -> Option<(SourceId, &'a str)> + 'a>;
+
+#[derive(Default)]
+pub struct ContextBuilder<'a> {
+    error_tolerant: bool,
+    infix: bool,
+    source: SourceId,
+    resolver: Option<Resolver<'a>>,
+    defines: HashMap<String, u16>,
+    nesting_limit: Option<usize>,
+    version: LanguageVersion,
+    // names and types of the symbols provided by the environment
+    externs: Vec<(String, &'a str)>,
+    // library of each symbol of an unlinked library
+    unlinked: HashMap<String, String>,
+}
+
+impl std::fmt::Debug for ContextBuilder<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextBuilder")
+            .field("error_tolerant", &self.error_tolerant)
+            .field("infix", &self.infix)
+            .field("source", &self.source)
+            .field("resolver", &self.resolver.is_some())
+            .field("defines", &self.defines)
+            .field("nesting_limit", &self.nesting_limit)
+            .field("version", &self.version)
+            .field("externs", &self.externs)
+            .field("unlinked", &self.unlinked)
+            .finish()
+    }
 }
 
-impl ContextBuilder {
-    pub fn build<'a>(self) -> Context<'a> {
-        Context {
+impl<'a> ContextBuilder<'a> {
+    /// Keep parsing after a syntax error.
+    ///
+    /// Statements that fail to parse are replaced by
+    /// [`Statement::Error`](crate::ast::Statement::Error) nodes, and the
+    /// errors are collected in the [`Context`](Context) instead of being
+    /// returned, so that a best-effort `Ast` is produced for any input.
+    pub fn error_tolerant(mut self, error_tolerant: bool) -> Self {
+        self.error_tolerant = error_tolerant;
+        self
+    }
+
+    /// Accept conventional infix expressions (`RESULT + 1`) alongside the
+    /// prefix form (`(+ 1 RESULT)`).
+    ///
+    /// See [`expression::infix`](crate::ast::expression::infix) for the
+    /// operator precedence.
+    pub fn infix(mut self, infix: bool) -> Self {
+        self.infix = infix;
+        self
+    }
+
+    /// Id of the parsed source, attached to the spans of its tokens.
+    ///
+    /// Defaults to the default [`SourceId`](SourceId).
+    pub fn source(mut self, source: SourceId) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Resolve the files of [`import`](crate::ast::Import) statements.
+    ///
+    /// The resolver returns the source code of the file with the given path,
+    /// or `None` if the file doesn't exist. Without a resolver, every import
+    /// fails to resolve.
+    ///
+    /// The spans of the imported files get consecutive ids, starting at `1`,
+    /// in the order the files are first imported. Use a
+    /// [`source_map`](Self::source_map) to control the ids instead.
+    pub fn resolver<F>(mut self, mut resolver: F) -> Self
+    where
+        F: FnMut(&str) -> Option<&'a str> + 'a,
+    {
+        let mut paths: Vec<String> = Vec::new();
+        self.resolver = Some(Box::new(move |path| {
+            let source = resolver(path)?;
+            let index = match paths.iter().position(|p| p == path) {
+                Some(index) => index,
+                None => {
+                    paths.push(path.to_string());
+                    paths.len() - 1
+                }
+            };
+            Some((SourceId::new(index as u32 + 1), source))
+        }));
+        self
+    }
+
+    /// Define a name for the conditions of [`if const`](crate::ast::IfConst)
+    /// statements (`if const CGB { .. }`).
+    ///
+    /// Defined names select which parts of the program are compiled (DMG or
+    /// CGB hardware, debug or release builds, ...) without an external
+    /// preprocessor. They are only visible to the conditions, and consts of
+    /// the program with the same name take precedence over them.
+    pub fn define(mut self, name: &str, value: u16) -> Self {
+        self.defines.insert(name.to_string(), value);
+        self
+    }
+
+    /// Maximum depth of nested blocks, expressions, and types.
+    ///
+    /// Deeper programs fail to parse with an
+    /// [`Error::NestingLimit`](Error::NestingLimit), rather than overflowing
+    /// the stack of the parser. Defaults to `128`.
+    pub fn nesting_limit(mut self, limit: usize) -> Self {
+        self.nesting_limit = Some(limit);
+        self
+    }
+
+    /// Version of the language to parse.
+    ///
+    /// The keywords of newer versions are parsed as identifiers, and newer
+    /// syntax fails with an
+    /// [`Error::RequiresVersion`](Error::RequiresVersion). Defaults to the
+    /// [latest](LanguageVersion::LATEST) version. See the
+    /// [`version`](crate::version) module.
+    pub fn version(mut self, version: LanguageVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Resolve the files of [`import`](crate::ast::Import) statements by
+    /// their name in a [`SourceMap`](SourceMap).
+    ///
+    /// The spans of the imported files carry their id in the map.
+    pub fn source_map(mut self, map: &'a SourceMap) -> Self {
+        self.resolver = Some(Box::new(move |path| {
+            let id = map.id(path)?;
+            Some((id, map.source(id)))
+        }));
+        self
+    }
+
+    /// Declare a symbol provided by the environment of the program (hardware
+    /// registers, the functions of a runtime library, ...), of the given type
+    /// (`u8`, `fn(u8):u16`, ...).
+    ///
+    /// Programs reference the symbol without declaring it, and
+    /// [`check_with_context`](crate::check::check_with_context) type checks
+    /// its uses.
+    ///
+    /// # Panics
+    ///
+    /// [`build`](Self::build) panics if the type doesn't parse.
+    pub fn extern_symbol(mut self, name: &str, type_: &'a str) -> Self {
+        self.externs.push((name.to_string(), type_));
+        self
+    }
+
+    /// Declare a symbol of a library that isn't linked with the program.
+    ///
+    /// Programs referencing the symbol fail to
+    /// [check](crate::check::check_with_context) with an
+    /// [`Error::UndefinedSymbol`](Error::UndefinedSymbol) that names the
+    /// library, rather than reporting a symbol that was never defined.
+    pub fn unlinked_symbol(mut self, name: &str, library: &str) -> Self {
+        self.unlinked.insert(name.to_string(), library.to_string());
+        self
+    }
+
+    pub fn build(self) -> Context<'a> {
+        let mut context = Context {
             paths: HashSet::new(),
-            _phantom: std::marker::PhantomData,
+            consts: Vec::new(),
+            module: 0,
+            error_tolerant: self.error_tolerant,
+            infix: self.infix,
+            errors: Vec::new(),
+            source: None,
+            source_id: self.source,
+            resolver: self.resolver,
+            imports: Vec::new(),
+            defines: self.defines,
+            macros: Vec::new(),
+            expanding: Vec::new(),
+            const_fn_body: false,
+            arena: Arena::new(),
+            depth: 0,
+            nesting_limit: self.nesting_limit.unwrap_or(NESTING_LIMIT),
+            version: self.version,
+            externs: Vec::new(),
+            unlinked: self.unlinked,
+            resolutions: Resolutions::default(),
+        };
+        for (name, type_) in self.externs {
+            let mut tokens = Tokens::new(type_).peekable();
+            let type_ = match Type::parse(&mut context, &mut tokens) {
+                Ok(type_) => type_,
+                Err(error) => panic!("Invalid type of extern symbol `{}`: {}", name, error),
+            };
+            if let Err(error) = lex::Eof::parse(&mut context, &mut tokens) {
+                panic!("Invalid type of extern symbol `{}`: {}", name, error);
+            }
+            context.externs.push((name, type_));
         }
+        context
     }
 }
 
+// const visible from the statement being parsed.
+pub(crate) struct ConstDef<'a> {
+    // name, prefixed with the modules it was defined in
+    name: String,
+    ident: lex::Ident<'a>,
+    // value, if it could be evaluated
+    value: Option<u16>,
+    // definition of a `const fn`, which share their names with the consts
+    fn_: Option<Box<Fn<'a>>>,
+}
+
+// macro defined so far.
+struct MacroDef<'a> {
+    ident: lex::Ident<'a>,
+    params: Vec<String>,
+    body: Vec<lex::Token<'a>>,
+    // location of the end of the expanded statements
+    end: Span,
+}
+
 #[allow(unused)]
 pub struct Context<'a> {
     paths: HashSet<String>,
-    _phantom: std::marker::PhantomData<&'a ()>,
+    // consts visible from the statement being parsed, in definition order
+    consts: Vec<ConstDef<'a>>,
+    // index of the first const of the module being parsed
+    module: usize,
+    error_tolerant: bool,
+    infix: bool,
+    errors: Vec<Error<'a>>,
+    source: Option<LineIndex<'a>>,
+    source_id: SourceId,
+    resolver: Option<Resolver<'a>>,
+    // paths of the files being imported, innermost last
+    imports: Vec<String>,
+    // names defined for the conditions of `if const` statements
+    defines: HashMap<String, u16>,
+    macros: Vec<MacroDef<'a>>,
+    // names of the macros being expanded, innermost last
+    expanding: Vec<String>,
+    // whether the body of a `const fn` is being parsed
+    const_fn_body: bool,
+    // memory of the nodes of the syntax tree
+    arena: Arena,
+    // depth of the node being parsed, and its maximum
+    depth: usize,
+    nesting_limit: usize,
+    version: LanguageVersion,
+    externs: Vec<(String, Type<'a>)>,
+    unlinked: HashMap<String, String>,
+    // names of the last parsed program
+    resolutions: Resolutions,
 }
 
 impl<'a> Context<'a> {
@@ -31,4 +287,434 @@ impl<'a> Context<'a> {
     pub(crate) fn is_defined(&self, path: &Path<'a>) -> bool {
         true
     }
+
+    // define a const, recording its value if it can be evaluated with the
+    // consts defined before it.
+    // Fails if the const shadows another one visible from the same module.
+    pub(crate) fn define_const(&mut self, const_: &Const<'a>) -> Result<(), Error<'a>> {
+        let ident = &const_.field.ident;
+        let name = ident.name().to_string();
+        self.check_shadow(ident)?;
+        types::check_range(&const_.field.type_, &const_.expression, &|e| self.eval(e))?;
+        let mask = match &const_.field.type_ {
+            Type::U8(_) | Type::I8(_) | Type::Bool(_) => Some(0xff),
+            Type::U16(_) | Type::I16(_) => Some(0xffff),
+            _ => None,
+        };
+        let value = mask.and_then(|mask| Some(self.eval(&const_.expression)? & mask));
+        self.consts.push(ConstDef {
+            name,
+            ident: ident.clone(),
+            value,
+            fn_: None,
+        });
+        Ok(())
+    }
+
+    // define a `const fn`, to be evaluated by the calls that follow it.
+    // Fails if it shadows a const visible from the same module.
+    pub(crate) fn define_const_fn(&mut self, fn_: Fn<'a>) -> Result<(), Error<'a>> {
+        let ident = &fn_.ident;
+        self.check_shadow(ident)?;
+        self.consts.push(ConstDef {
+            name: ident.name().to_string(),
+            ident: ident.clone(),
+            value: None,
+            fn_: Some(Box::new(fn_)),
+        });
+        Ok(())
+    }
+
+    // definition of a previously parsed `const fn` (`foo`, `a::foo`).
+    pub(crate) fn const_fn(&self, name: &str) -> Option<&Fn<'a>> {
+        self.consts
+            .iter()
+            .rev()
+            .find(|c| c.name == name)
+            .and_then(|c| c.fn_.as_deref())
+    }
+
+    // begin or end parsing the body of a `const fn`.
+    // Returns whether a body was being parsed before.
+    pub(crate) fn set_const_fn_body(&mut self, body: bool) -> bool {
+        std::mem::replace(&mut self.const_fn_body, body)
+    }
+
+    pub(crate) fn is_const_fn_body(&self) -> bool {
+        self.const_fn_body
+    }
+
+    /// Maximum depth of nested blocks, expressions, and types.
+    ///
+    /// See [`ContextBuilder::nesting_limit`](ContextBuilder::nesting_limit).
+    pub fn nesting_limit(&self) -> usize {
+        self.nesting_limit
+    }
+
+    /// Version of the language being parsed.
+    pub fn version(&self) -> LanguageVersion {
+        self.version
+    }
+
+    // fail unless the version being parsed is at least `version`.
+    pub(crate) fn require(
+        &self,
+        version: LanguageVersion,
+        feature: impl Into<String>,
+        span: Span,
+    ) -> Result<(), Error<'a>> {
+        if self.version >= version {
+            Ok(())
+        } else {
+            Err(Error::RequiresVersion {
+                span,
+                feature: feature.into(),
+                version,
+            })
+        }
+    }
+
+    // parse a node nested within the one being parsed, unless the nesting
+    // limit has been reached.
+    pub(crate) fn nested<T>(
+        &mut self,
+        tokens: &mut Peekable<Tokens<'a>>,
+        parse: impl FnOnce(&mut Self, &mut Peekable<Tokens<'a>>) -> Result<T, Error<'a>>,
+    ) -> Result<T, Error<'a>> {
+        if self.depth >= self.nesting_limit {
+            return match tokens.peek() {
+                Some(Ok(token)) => Err(Error::NestingLimit {
+                    span: token.span(),
+                    limit: self.nesting_limit,
+                }),
+                Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+                None => Err(Error::Eof),
+            };
+        }
+        self.depth += 1;
+        let node = parse(self, tokens);
+        self.depth -= 1;
+        node
+    }
+
+    // moves a node of the syntax tree into the arena.
+    pub(crate) fn alloc<T>(&mut self, node: T) -> Node<T> {
+        self.arena.alloc(node)
+    }
+
+    // context to parse a second copy of a `const fn` with, without the side
+    // effects on this one (macro definitions, collected errors, ...).
+    pub(crate) fn scratch(&self) -> Self {
+        let mut ctx = ContextBuilder::default()
+            .infix(self.infix)
+            .nesting_limit(self.nesting_limit)
+            .version(self.version)
+            .build();
+        ctx.const_fn_body = true;
+        ctx
+    }
+
+    // define a generic parameter of a function. Its value is only known once
+    // the function is instantiated, so it can't be evaluated.
+    pub(crate) fn define_generic(&mut self, param: &GenericParam<'a>) -> Result<(), Error<'a>> {
+        let ident = &param.field.ident;
+        self.check_shadow(ident)?;
+        self.consts.push(ConstDef {
+            name: ident.name().to_string(),
+            ident: ident.clone(),
+            value: None,
+            fn_: None,
+        });
+        Ok(())
+    }
+
+    // fails if a const of the given name is visible from the same module.
+    fn check_shadow(&self, ident: &lex::Ident<'a>) -> Result<(), Error<'a>> {
+        let name = ident.name();
+        match self.consts[self.module..].iter().find(|c| c.name == name) {
+            Some(def) => Err(Error::ShadowIdent {
+                ident: Box::new(def.ident.clone()),
+                shadow: ident.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // check a static assertion, if it can be evaluated with the consts
+    // defined so far.
+    pub(crate) fn static_assert(&self, assert: &StaticAssert<'a>) -> Result<(), Error<'a>> {
+        match self.eval(&assert.expression) {
+            Some(0) => Err(Error::StaticAssert {
+                span: assert.expression.span(),
+                message: assert.message.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // check the widths of the bitfields of an unsigned integer field, if they
+    // can be evaluated with the consts defined so far.
+    pub(crate) fn check_bits(&self, type_: &Type<'a>, bits: &Bits<'a>) -> Result<(), Error<'a>> {
+        let size = match type_ {
+            Type::U8(_) => 8,
+            Type::U16(_) => 16,
+            _ => return Ok(()),
+        };
+        let mut used = 0;
+        for field in &bits.fields {
+            let width = match self.eval(&field.width) {
+                Some(width) => width,
+                None => continue,
+            };
+            let available = size - used;
+            if width == 0 || width > available {
+                return Err(Error::BitfieldWidth {
+                    span: field.width.span(),
+                    width,
+                    available,
+                });
+            }
+            used += width;
+        }
+        Ok(())
+    }
+
+    // number of consts visible so far.
+    pub(crate) fn const_count(&self) -> usize {
+        self.consts.len()
+    }
+
+    // end the scope that began after the first `from` consts were defined.
+    // The consts defined within it are no longer visible.
+    pub(crate) fn end_scope(&mut self, from: usize) {
+        self.consts.truncate(from);
+        self.module = self.module.min(from);
+    }
+
+    // remove the consts defined after the first `from` consts.
+    pub(crate) fn take_consts(&mut self, from: usize) -> Vec<ConstDef<'a>> {
+        self.module = self.module.min(from);
+        self.consts.split_off(from)
+    }
+
+    // make previously taken consts visible again.
+    pub(crate) fn restore_consts(&mut self, consts: Vec<ConstDef<'a>>) {
+        self.consts.extend(consts);
+    }
+
+    // evaluate the condition of an `if const` statement, resolving paths to
+    // the consts parsed so far, and to the defined names.
+    pub(crate) fn eval_condition(&self, expression: &Expression<'a>) -> Result<u16, Error<'a>> {
+        let value = eval::eval(expression, &mut |expression| match expression {
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name().to_string()).collect();
+                let name = name.join("::");
+                match self.consts.iter().rev().find(|c| c.name == name) {
+                    Some(def) => def.value,
+                    None => self.defines.get(&name).copied(),
+                }
+            }
+            Expression::Call(call) => call.inner.value,
+            _ => None,
+        });
+        value.ok_or_else(|| Error::NotConst {
+            span: expression.span(),
+        })
+    }
+
+    // begin parsing a module.
+    // Returns the beginning of the parent module, to be passed to `end_module`.
+    pub(crate) fn begin_module(&mut self) -> usize {
+        std::mem::replace(&mut self.module, self.consts.len())
+    }
+
+    // end parsing a module, prefixing its consts with its name.
+    pub(crate) fn end_module(&mut self, parent: usize, module: &str) {
+        for def in &mut self.consts[self.module..] {
+            def.name = format!("{}::{}", module, def.name);
+        }
+        self.module = parent;
+    }
+
+    // parse the statements of an imported file.
+    pub(crate) fn import(&mut self, path: &lex::Lit<'a>) -> Result<Vec<Statement<'a>>, Error<'a>> {
+        let file = path.str_value().expect("String literal import path");
+        if self.imports.contains(&file) {
+            return Err(Error::CyclicImport(path.clone()));
+        }
+        let (id, source) = match self.resolver.as_mut().and_then(|resolve| resolve(&file)) {
+            Some(source) => source,
+            None => return Err(Error::UnresolvedImport(path.clone())),
+        };
+
+        let parent = self.source.replace(LineIndex::new(source));
+        let parent_id = std::mem::replace(&mut self.source_id, id);
+        self.imports.push(file);
+        let mut tokens = Tokens::with_source(source, id)
+            .with_version(self.version)
+            .peekable();
+        let inner = Grammar::parse(self, &mut tokens)
+            .and_then(|inner| lex::Eof::parse(self, &mut tokens).map(|_| inner));
+        self.imports.pop();
+        self.source_id = parent_id;
+        self.source = parent;
+        inner
+    }
+
+    // define a macro, to be expanded by the invocations that follow it.
+    // Fails if another macro with the same name has been defined.
+    pub(crate) fn define_macro(&mut self, macro_: &Macro<'a>) -> Result<(), Error<'a>> {
+        let ident = &macro_.ident;
+        if let Some(def) = self.macros.iter().find(|m| m.ident.name() == ident.name()) {
+            return Err(Error::ShadowIdent {
+                ident: Box::new(def.ident.clone()),
+                shadow: ident.clone(),
+            });
+        }
+        self.macros.push(MacroDef {
+            ident: ident.clone(),
+            params: macro_.params.iter().map(|p| p.name().to_string()).collect(),
+            body: macro_.body.clone(),
+            end: expansion_end(&macro_.right_bracket),
+        });
+        Ok(())
+    }
+
+    // number of parameters of a defined macro.
+    pub(crate) fn macro_params(&self, name: &str) -> Option<usize> {
+        let def = self.macros.iter().find(|m| m.ident.name() == name)?;
+        Some(def.params.len())
+    }
+
+    // parse the statements of a macro invocation, replacing the parameters of
+    // the body with the tokens of the arguments.
+    pub(crate) fn expand(
+        &mut self,
+        call: &lex::Ident<'a>,
+        args: &[Vec<lex::Token<'a>>],
+    ) -> Result<Vec<Statement<'a>>, Error<'a>> {
+        let name = call.name().to_string();
+        if self.expanding.contains(&name) {
+            return Err(Error::RecursiveMacro(call.clone()));
+        }
+        let def = self
+            .macros
+            .iter()
+            .find(|m| m.ident.name() == name)
+            .expect("Defined macro");
+        let end = def.end;
+        let mut expansion = Vec::with_capacity(def.body.len());
+        for token in &def.body {
+            match token {
+                lex::Token::Ident(ident) => match def.params.iter().position(|p| p == ident.name())
+                {
+                    Some(param) => expansion.extend(args[param].iter().cloned()),
+                    None => expansion.push(token.clone()),
+                },
+                _ => expansion.push(token.clone()),
+            }
+        }
+
+        // the expanded tokens don't belong to a single source, so docs can't
+        // be located within it
+        let parent = self.source.take();
+        self.expanding.push(name);
+        let mut tokens = Tokens::replay(expansion, end).peekable();
+        let inner = Grammar::parse(self, &mut tokens)
+            .and_then(|inner| lex::Eof::parse(self, &mut tokens).map(|_| inner));
+        self.expanding.pop();
+        self.source = parent;
+        inner.map_err(|error| Error::Macro {
+            call: call.clone(),
+            error: Box::new(error),
+        })
+    }
+
+    /// Value of a previously parsed const (`FOO`, `a::FOO`).
+    ///
+    /// Only consts of integer and `bool` types with a constant expression
+    /// made of literals, other consts, and calls to `const fn`s have a value. Consts defined within
+    /// a block are only visible until the end of the block.
+    pub fn const_value(&self, name: &str) -> Option<u16> {
+        self.consts
+            .iter()
+            .rev()
+            .find(|c| c.name == name)
+            .and_then(|c| c.value)
+    }
+
+    /// Evaluate a constant expression, resolving paths to the consts parsed so
+    /// far.
+    ///
+    /// See [`expression::eval`](crate::ast::expression::eval).
+    pub fn eval(&self, expression: &Expression<'a>) -> Option<u16> {
+        eval::eval(expression, &mut |expression| match expression {
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name().to_string()).collect();
+                self.const_value(&name.join("::"))
+            }
+            Expression::Call(call) => call.inner.value,
+            _ => None,
+        })
+    }
+
+    pub(crate) fn is_error_tolerant(&self) -> bool {
+        self.error_tolerant
+    }
+
+    pub(crate) fn is_infix(&self) -> bool {
+        self.infix
+    }
+
+    pub(crate) fn set_source(&mut self, source: &'a str) {
+        self.source = Some(LineIndex::new(source));
+    }
+
+    pub(crate) fn source(&self) -> Option<&LineIndex<'a>> {
+        self.source.as_ref()
+    }
+
+    /// Id of the source being parsed.
+    pub fn source_id(&self) -> SourceId {
+        self.source_id
+    }
+
+    pub(crate) fn push_error(&mut self, error: Error<'a>) {
+        self.errors.push(error);
+    }
+
+    /// Errors collected while parsing in error-tolerant mode.
+    pub fn errors(&self) -> &[Error<'a>] {
+        &self.errors
+    }
+
+    /// Take the collected errors out of the context.
+    pub fn take_errors(&mut self) -> Vec<Error<'a>> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Names and types of the symbols provided by the environment.
+    ///
+    /// See [`ContextBuilder::extern_symbol`](ContextBuilder::extern_symbol).
+    pub fn externs(&self) -> &[(String, Type<'a>)] {
+        &self.externs
+    }
+
+    /// Library providing a symbol, if it is the symbol of an unlinked library.
+    ///
+    /// See [`ContextBuilder::unlinked_symbol`](ContextBuilder::unlinked_symbol).
+    pub fn unlinked_library(&self, name: &str) -> Option<&str> {
+        self.unlinked.get(name).map(String::as_str)
+    }
+
+    /// Resolved names of the last program parsed with this context.
+    ///
+    /// See the [`resolve`](crate::resolve) module.
+    pub fn resolutions(&self) -> &Resolutions {
+        &self.resolutions
+    }
+
+    pub(crate) fn set_resolutions(&mut self, resolutions: Resolutions) {
+        self.resolutions = resolutions;
+    }
 }
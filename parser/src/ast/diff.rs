@@ -0,0 +1,361 @@
+//! Semantic differences between programs.
+//!
+//! [`diff`] compares two programs declaration by declaration, ignoring the
+//! way they're written (layout, comments, `,` separators, ...):
+//!
+//! - Declarations (statics, consts, functions, enums, type aliases, macros,
+//!   and memory maps) are matched by kind and path, wherever they are in
+//!   their module. A declaration is modified if its canonical source code
+//!   (see [`fmt`](crate::ast::fmt)) differs, doc comment included.
+//! - Modules (`mod` and `import` statements) are compared item by item.
+//! - The rest of the statements are matched in order. A statement replaced by
+//!   another one is modified.
+//!
+//! ```
+//! use parser::ast::diff::{diff, ChangeKind, ItemKind};
+//!
+//! let old = parser::parse("static A:u8\nconst B:u8 = 1").unwrap();
+//! let new = parser::parse("const B:u8 = 2 // changed\nstatic   A:u8").unwrap();
+//! let changes = diff(&old, &new);
+//! assert_eq!(1, changes.len());
+//! assert_eq!(ChangeKind::Modified, changes[0].kind);
+//! assert_eq!(ItemKind::Const, changes[0].item);
+//! assert_eq!(Some("B"), changes[0].name.as_deref());
+//! ```
+use crate::{
+    ast::{fmt, Ast, Statement},
+    lex::span::{Span, Spanned},
+};
+use std::collections::HashMap;
+
+/// Kind of a [`Change`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ChangeKind {
+    /// Item only found in the new program.
+    Added,
+
+    /// Item only found in the old program.
+    Removed,
+
+    /// Item found in both programs, with differences.
+    Modified,
+}
+
+/// Kind of the item of a [`Change`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ItemKind {
+    /// `static` declaration.
+    Static,
+
+    /// `const` declaration.
+    Const,
+
+    /// Function declaration.
+    Fn,
+
+    /// `enum` declaration.
+    Enum,
+
+    /// `type` alias declaration.
+    TypeAlias,
+
+    /// `macro` definition.
+    Macro,
+
+    /// Module (`mod` or `import` statement).
+    Mod,
+
+    /// `memory` map.
+    Memory,
+
+    /// Any other statement.
+    Statement,
+}
+
+/// Difference between two programs.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+    /// Kind of change.
+    pub kind: ChangeKind,
+
+    /// Kind of the changed item.
+    pub item: ItemKind,
+
+    /// Path of the changed declaration (`gfx::VRAM`).
+    ///
+    /// Memory maps and statements have no name.
+    pub name: Option<String>,
+
+    /// Span of the item in the old program. `None` if it was added.
+    pub old: Option<Span>,
+
+    /// Span of the item in the new program. `None` if it was removed.
+    pub new: Option<Span>,
+}
+
+/// Returns the changes from the `old` program to the `new` one.
+///
+/// Within each module, the changes of its declarations come first, in the
+/// order of the old program (added declarations last), followed by the
+/// changes of the rest of its statements.
+pub fn diff(old: &Ast<'_>, new: &Ast<'_>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    module("", &old.inner, &new.inner, &mut changes);
+    changes
+}
+
+// kind and path of the declaration (or statement) key of a statement.
+type Key = (ItemKind, Option<String>);
+
+fn key(prefix: &str, statement: &Statement<'_>) -> Key {
+    let (kind, name) = match statement {
+        Statement::Static(static_) => (ItemKind::Static, static_.field.ident.name().to_string()),
+        Statement::Const(const_) => (ItemKind::Const, const_.field.ident.name().to_string()),
+        Statement::Fn(fn_) => (ItemKind::Fn, fn_.ident.name().to_string()),
+        Statement::Enum(enum_) => (ItemKind::Enum, enum_.ident.name().to_string()),
+        Statement::TypeAlias(alias) => (ItemKind::TypeAlias, alias.ident.name().to_string()),
+        Statement::Macro(macro_) => (ItemKind::Macro, macro_.ident.name().to_string()),
+        Statement::Mod(mod_) => (ItemKind::Mod, mod_.ident.name().to_string()),
+        Statement::Import(import) => (ItemKind::Mod, import.name()),
+        Statement::Memory(_) => return (ItemKind::Memory, None),
+        _ => return (ItemKind::Statement, None),
+    };
+    (kind, Some(format!("{}{}", prefix, name)))
+}
+
+// statements of a module.
+fn inner<'s, 'a>(statement: &'s Statement<'a>) -> Option<&'s [Statement<'a>]> {
+    match statement {
+        Statement::Mod(mod_) => Some(&mod_.inner),
+        Statement::Import(import) => Some(&import.inner),
+        _ => None,
+    }
+}
+
+fn change(kind: ChangeKind, (item, name): Key, old: Option<Span>, new: Option<Span>) -> Change {
+    Change {
+        kind,
+        item,
+        name,
+        old,
+        new,
+    }
+}
+
+fn module(prefix: &str, old: &[Statement<'_>], new: &[Statement<'_>], changes: &mut Vec<Change>) {
+    // declarations of the new module, in order, for each key
+    let mut declarations: HashMap<Key, Vec<(&Statement<'_>, bool)>> = HashMap::new();
+    let mut new_statements = Vec::new();
+    for statement in new {
+        match key(prefix, statement) {
+            (ItemKind::Statement, _) => new_statements.push(statement),
+            key => declarations
+                .entry(key)
+                .or_default()
+                .push((statement, false)),
+        }
+    }
+
+    // repeated declarations are matched in order
+    let mut seen: HashMap<Key, usize> = HashMap::new();
+    let mut old_statements = Vec::new();
+    for statement in old {
+        let key = key(prefix, statement);
+        if key.0 == ItemKind::Statement {
+            old_statements.push(statement);
+            continue;
+        }
+        let count = seen.entry(key.clone()).or_default();
+        let index = *count;
+        *count += 1;
+        let matched = declarations
+            .get_mut(&key)
+            .and_then(|declarations| declarations.get_mut(index));
+        let other = match matched {
+            Some((other, matched)) => {
+                *matched = true;
+                *other
+            }
+            None => {
+                changes.push(change(
+                    ChangeKind::Removed,
+                    key,
+                    Some(statement.span()),
+                    None,
+                ));
+                continue;
+            }
+        };
+        match (inner(statement), inner(other)) {
+            (Some(old), Some(new)) => {
+                let prefix = format!("{}::", key.1.as_deref().unwrap_or_default());
+                module(&prefix, old, new, changes);
+            }
+            _ if fmt::canonical(statement) != fmt::canonical(other) => {
+                let (old, new) = (Some(statement.span()), Some(other.span()));
+                changes.push(change(ChangeKind::Modified, key, old, new));
+            }
+            _ => {}
+        }
+    }
+    for statement in new {
+        let key = key(prefix, statement);
+        let declaration = declarations.get(&key).and_then(|declarations| {
+            declarations
+                .iter()
+                .find(|(s, _)| std::ptr::eq(*s, statement))
+        });
+        let added = matches!(declaration, Some((_, false)));
+        if added {
+            changes.push(change(ChangeKind::Added, key, None, Some(statement.span())));
+        }
+    }
+
+    statements(&old_statements, &new_statements, changes);
+}
+
+// diff of the statements that declare nothing, matched in order.
+fn statements(old: &[&Statement<'_>], new: &[&Statement<'_>], changes: &mut Vec<Change>) {
+    let old_text: Vec<_> = old.iter().map(|s| fmt::canonical(s)).collect();
+    let new_text: Vec<_> = new.iter().map(|s| fmt::canonical(s)).collect();
+
+    // lengths of the longest common subsequences of each pair of suffixes
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old_text[i] == new_text[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old_text[i] == new_text[j] {
+            replaced(&mut removed, &mut added, changes);
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(new[j].span());
+            j += 1;
+        } else {
+            removed.push(old[i].span());
+            i += 1;
+        }
+    }
+    replaced(&mut removed, &mut added, changes);
+}
+
+// statements removed and added between two unchanged ones. Each removed
+// statement is replaced by (modified into) the added statement in its place.
+fn replaced(removed: &mut Vec<Span>, added: &mut Vec<Span>, changes: &mut Vec<Change>) {
+    let key = || (ItemKind::Statement, None);
+    for i in 0..removed.len().max(added.len()) {
+        let kind = match (removed.get(i), added.get(i)) {
+            (Some(_), Some(_)) => ChangeKind::Modified,
+            (Some(_), None) => ChangeKind::Removed,
+            _ => ChangeKind::Added,
+        };
+        changes.push(change(
+            kind,
+            key(),
+            removed.get(i).copied(),
+            added.get(i).copied(),
+        ));
+    }
+    removed.clear();
+    added.clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, Change, ChangeKind, ItemKind};
+
+    fn changes(old: &str, new: &str) -> Vec<(ChangeKind, ItemKind, Option<String>)> {
+        let old = crate::parse(old).unwrap();
+        let new = crate::parse(new).unwrap();
+        diff(&old, &new)
+            .into_iter()
+            .map(
+                |Change {
+                     kind, item, name, ..
+                 }| (kind, item, name),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn formatting() {
+        let old = "static A:[u8 2] = [1 2]\nfn f(a:u8) { (= A a) }";
+        let new = "// comment\n\n\
+                   fn f ( a:u8 ) {\n    \
+                       (= A\n        \
+                           a)\n\
+                   }\n\
+                   static A:[u8 2] = [1\n    2]";
+        assert!(changes(old, new).is_empty());
+    }
+
+    #[test]
+    fn declarations() {
+        let old = "static A:u8\nconst B:u8 = 1\nfn f { }";
+        let new = "/// Doc.\nconst B:u8 = 1\nfn f { return }\nenum E { X }";
+        assert_eq!(
+            vec![
+                (ChangeKind::Removed, ItemKind::Static, Some("A".to_string())),
+                (ChangeKind::Modified, ItemKind::Const, Some("B".to_string())),
+                (ChangeKind::Modified, ItemKind::Fn, Some("f".to_string())),
+                (ChangeKind::Added, ItemKind::Enum, Some("E".to_string())),
+            ],
+            changes(old, new)
+        );
+    }
+
+    #[test]
+    fn modules() {
+        let old = "mod gfx { static VRAM:u8 mod oam { const N:u8 = 40 } }";
+        let new = "mod gfx { static VRAM:u8 mod oam { const N:u8 = 10 } }\nmod sfx { }";
+        assert_eq!(
+            vec![
+                (
+                    ChangeKind::Modified,
+                    ItemKind::Const,
+                    Some("gfx::oam::N".to_string())
+                ),
+                (ChangeKind::Added, ItemKind::Mod, Some("sfx".to_string())),
+            ],
+            changes(old, new)
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let old = "static X:u8\n(= X 1)\n(= X 2)\n(= X 3)";
+        let new = "static X:u8\n(= X 0)\n(= X 1)\n(= X 4)\n(= X 3)\n(= X 5)";
+        let spans: Vec<_> = {
+            let old = crate::parse(old).unwrap();
+            let new = crate::parse(new).unwrap();
+            diff(&old, &new)
+                .into_iter()
+                .map(|change| {
+                    let line = |span: Option<crate::lex::span::Span>| span.map(|s| s.min[0]);
+                    (change.kind, line(change.old), line(change.new))
+                })
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                (ChangeKind::Added, None, Some(1)),
+                (ChangeKind::Modified, Some(2), Some(3)),
+                (ChangeKind::Added, None, Some(5)),
+            ],
+            spans
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Documentation comments.
+use crate::{
+    ast::{Context, Grammar},
+    lex::{
+        span::{LineIndex, Span, Spanned},
+        Tokens,
+    },
+    Error,
+};
+use std::iter::Peekable;
+
+/// Documentation comment (`///`) of a declaration.
+///
+/// Doc comments are the consecutive `///` lines immediately preceding the
+/// declaration. Comments beginning with four or more slashes are regular
+/// comments.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug, Clone)]
+pub struct Doc<'a> {
+    /// Lines of the comment, without the leading `///`.
+    pub lines: Vec<&'a str>,
+
+    /// Span of the comment.
+    pub span: Span,
+}
+
+impl Doc<'_> {
+    /// Text of the comment.
+    ///
+    /// Lines are joined with `\n`, and the space after the `///` is removed.
+    pub fn text(&self) -> String {
+        let lines: Vec<_> = self
+            .lines
+            .iter()
+            .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+            .collect();
+        lines.join("\n")
+    }
+}
+
+impl<'a> Doc<'a> {
+    // doc comment of the declaration beginning at `at`.
+    pub(crate) fn before(index: &LineIndex<'a>, at: Span) -> Option<Self> {
+        let position = at.min;
+        let source = index.source();
+        let line_start = index.offset([position[0], 0]);
+        if !source[line_start..index.offset(position)].trim().is_empty() {
+            return None;
+        }
+
+        let mut lines = Vec::new();
+        let mut span: Option<Span> = None;
+        for line in (0..position[0]).rev() {
+            let start = index.offset([line, 0]);
+            let end = index.offset([line + 1, 0]);
+            let text = source[start..end].trim_end_matches(&['\n', '\r'][..]);
+            let indent = text.len() - text.trim_start().len();
+            match text.trim_start().strip_prefix("///") {
+                Some(doc) if !doc.starts_with('/') => {
+                    lines.push(doc);
+                    let min = [line, indent];
+                    let max = span.map(|s| s.max).unwrap_or([line, text.len()]);
+                    span = Some(Span {
+                        min,
+                        max,
+                        source: at.source,
+                    });
+                }
+                _ => break,
+            }
+        }
+        lines.reverse();
+        span.map(|span| Self { lines, span })
+    }
+}
+
+impl Spanned for Doc<'_> {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Doc<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        // doc comments are not tokens, so they're read from the source code
+        let span = match tokens.peek() {
+            Some(Ok(token)) => token.span(),
+            _ => return Ok(None),
+        };
+        Ok(context.source().and_then(|index| Doc::before(index, span)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::{types::Type, Statement},
+        lex::span::Span,
+    };
+
+    #[test]
+    fn declarations() {
+        let input = "/// The answer.\n\
+                     ///\n\
+                     ///   Indented.\n\
+                     static FOO:u8\n\
+                     \n\
+                     //// Not a doc comment.\n\
+                     const BAR:u8 = 0\n\
+                     /// Detached.\n\
+                     \n\
+                     fn baz {\n    \
+                         /// Inner.\n    \
+                         static QUX: union { a:u8 }\n\
+                     }";
+        let ast = crate::parse(input).unwrap();
+        match &ast.inner[0] {
+            Statement::Static(static_) => {
+                let doc = static_.doc.as_ref().unwrap();
+                assert_eq!(vec![" The answer.", "", "   Indented."], doc.lines);
+                assert_eq!("The answer.\n\n  Indented.", doc.text());
+                assert_eq!(
+                    Span {
+                        min: [0, 0],
+                        max: [2, 15],
+                        ..Default::default()
+                    },
+                    doc.span
+                );
+            }
+            _ => panic!(),
+        }
+        match &ast.inner[1] {
+            Statement::Const(const_) => assert!(const_.doc.is_none()),
+            _ => panic!(),
+        }
+        match &ast.inner[2] {
+            Statement::Fn(fn_) => {
+                assert!(fn_.doc.is_none());
+                match &fn_.inner[0] {
+                    Statement::Static(static_) => {
+                        assert_eq!("Inner.", static_.doc.as_ref().unwrap().text());
+                        match &static_.field.type_ {
+                            Type::Union(union) => assert!(union.doc.is_none()),
+                            _ => panic!(),
+                        }
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+    }
+}
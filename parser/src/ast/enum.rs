@@ -0,0 +1,104 @@
+use crate::{
+    ast::{pub_span, Context, Doc, Expression, Grammar},
+    lex,
+    lex::{
+        span,
+        span::{Span, Spanned},
+        Token,
+    },
+    Error, Tokens,
+};
+use std::iter::Peekable;
+
+parse! {
+    /// `enum <ident> { <variants> }`
+    #[derive(Debug)]
+    pub struct Enum<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Optional `pub` token.
+        pub pub_: Option<lex::Pub<'a>>,
+
+        /// `enum` token.
+        pub enum_: lex::Enum<'a>,
+
+        /// Enum identifier token.
+        pub ident: lex::Ident<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Enum variants.
+        pub variants: Vec<Variant<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+parse! {
+    /// `<ident>` or `<ident> = <expression>`
+    #[derive(Debug)]
+    pub struct Variant<'a> {
+        /// Variant identifier token.
+        pub ident: lex::Ident<'a>,
+
+        /// Optional explicit discriminant.
+        pub discriminant: Option<Discriminant<'a>>,
+    }
+}
+
+parse! {
+    #[derive(Debug)]
+    pub struct Discriminant<'a> {
+        /// `=` token.
+        pub assign: lex::Assign<'a>,
+
+        /// Discriminant const expression.
+        pub expression: Expression<'a>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Variant<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Ident(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(context, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Discriminant<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Assign(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(context, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Spanned for Enum<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.enum_.span(), &self.right_bracket.span());
+        pub_span(&self.pub_, span)
+    }
+}
+span!(Discriminant { assign, expression });
+
+impl Spanned for Variant<'_> {
+    fn span(&self) -> Span {
+        match &self.discriminant {
+            Some(discriminant) => span::union(&self.ident.span(), &discriminant.span()),
+            None => self.ident.span(),
+        }
+    }
+}
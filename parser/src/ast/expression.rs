@@ -1,47 +1,72 @@
 //! Expression grammars.
+//!
+//! Expressions are written in prefix form (`(+ 1 RESULT)`). The conventional
+//! infix form (`RESULT + 1`) is also accepted when enabled with
+//! [`ContextBuilder::infix`](crate::ast::ContextBuilder::infix). See the
+//! [`infix`](infix) module for the operator precedence.
 use crate::{
-    ast::{Context, Grammar, Path},
+    arena::Node,
+    ast::{
+        builtin::{self, Builtin},
+        const_fn,
+        types::Type,
+        Context, Grammar, Path, Separated,
+    },
     lex,
-    lex::{Token, Tokens},
+    lex::{
+        span::{self, Span, Spanned},
+        Token, Tokens,
+    },
     Error,
 };
 use std::iter::Peekable;
 
+pub mod eval;
+pub mod infix;
+
 parse! {
     #[derive(Debug)]
     pub enum Expression<'a> {
         Path(Path<'a>),
         Lit(lex::Lit<'a>),
         Array(Array<'a>),
-        Minus(Box<Minus<'a>>),
-        AddressOf(Box<AddressOf<'a>>),
-        Deref(Box<Deref<'a>>),
-        Not(Box<Not<'a>>),
-        Add(Box<LispNode<'a, Add<'a>>>),
-        Sub(Box<LispNode<'a, Sub<'a>>>),
-        Mul(Box<LispNode<'a, Mul<'a>>>),
-        Div(Box<LispNode<'a, Div<'a>>>),
-        And(Box<LispNode<'a, And<'a>>>),
-        Or(Box<LispNode<'a, Or<'a>>>),
-        Xor(Box<LispNode<'a, Xor<'a>>>),
-        Assign(Box<LispNode<'a, Assign<'a>>>),
-        PlusAssign(Box<LispNode<'a, PlusAssign<'a>>>),
-        MinusAssign(Box<LispNode<'a, MinusAssign<'a>>>),
-        MulAssign(Box<LispNode<'a, MulAssign<'a>>>),
-        DivAssign(Box<LispNode<'a, DivAssign<'a>>>),
-        AndAssign(Box<LispNode<'a, AndAssign<'a>>>),
-        OrAssign(Box<LispNode<'a, OrAssign<'a>>>),
-        XorAssign(Box<LispNode<'a, XorAssign<'a>>>),
-        LeftShift(Box<LispNode<'a, LeftShift<'a>>>),
-        RightShift(Box<LispNode<'a, RightShift<'a>>>),
-        Index(Box<LispNode<'a, Index<'a>>>),
-        Eq(Box<LispNode<'a, Eq<'a>>>),
-        NotEq(Box<LispNode<'a, NotEq<'a>>>),
-        LessEq(Box<LispNode<'a, LessEq<'a>>>),
-        GreaterEq(Box<LispNode<'a, GreaterEq<'a>>>),
-        Less(Box<LispNode<'a, Less<'a>>>),
-        Greater(Box<LispNode<'a, Greater<'a>>>),
-        Call(Box<LispNode<'a, Call<'a>>>),
+        StructLit(StructLit<'a>),
+        Minus(Node<Minus<'a>>),
+        AddressOf(Node<AddressOf<'a>>),
+        Deref(Node<Deref<'a>>),
+        Not(Node<Not<'a>>),
+        Increment(Node<LispNode<'a, Increment<'a>>>),
+        Decrement(Node<LispNode<'a, Decrement<'a>>>),
+        Conditional(Node<LispNode<'a, Conditional<'a>>>),
+        Cast(Node<LispNode<'a, Cast<'a>>>),
+        SizeOf(Node<LispNode<'a, SizeOf<'a>>>),
+        Add(Node<LispNode<'a, Add<'a>>>),
+        Sub(Node<LispNode<'a, Sub<'a>>>),
+        Mul(Node<LispNode<'a, Mul<'a>>>),
+        Div(Node<LispNode<'a, Div<'a>>>),
+        And(Node<LispNode<'a, And<'a>>>),
+        Or(Node<LispNode<'a, Or<'a>>>),
+        Xor(Node<LispNode<'a, Xor<'a>>>),
+        Assign(Node<LispNode<'a, Assign<'a>>>),
+        PlusAssign(Node<LispNode<'a, PlusAssign<'a>>>),
+        MinusAssign(Node<LispNode<'a, MinusAssign<'a>>>),
+        MulAssign(Node<LispNode<'a, MulAssign<'a>>>),
+        DivAssign(Node<LispNode<'a, DivAssign<'a>>>),
+        AndAssign(Node<LispNode<'a, AndAssign<'a>>>),
+        OrAssign(Node<LispNode<'a, OrAssign<'a>>>),
+        XorAssign(Node<LispNode<'a, XorAssign<'a>>>),
+        LeftShift(Node<LispNode<'a, LeftShift<'a>>>),
+        RightShift(Node<LispNode<'a, RightShift<'a>>>),
+        Index(Node<LispNode<'a, Index<'a>>>),
+        Eq(Node<LispNode<'a, Eq<'a>>>),
+        NotEq(Node<LispNode<'a, NotEq<'a>>>),
+        LessEq(Node<LispNode<'a, LessEq<'a>>>),
+        GreaterEq(Node<LispNode<'a, GreaterEq<'a>>>),
+        Less(Node<LispNode<'a, Less<'a>>>),
+        Greater(Node<LispNode<'a, Greater<'a>>>),
+        LogicalAnd(Node<LispNode<'a, LogicalAnd<'a>>>),
+        LogicalOr(Node<LispNode<'a, LogicalOr<'a>>>),
+        Call(Node<LispNode<'a, Call<'a>>>),
     }
 }
 
@@ -50,84 +75,195 @@ impl<'a> Grammar<'a> for Option<Expression<'a>> {
         context: &mut Context<'a>,
         tokens: &mut Peekable<Tokens<'a>>,
     ) -> Result<Self, Error<'a>> {
-        macro_rules! prefix_match_arm {
-            ($var:ident, $left_par:expr) => {{
-                Expression::$var(Box::new(LispNode {
-                    left_par: $left_par,
-                    inner: Grammar::parse(context, tokens)?,
-                    right_par: Grammar::parse(context, tokens)?,
-                }))
-            }};
+        if context.is_infix() {
+            context.nested(tokens, infix::parse)
+        } else {
+            context.nested(tokens, parse_prefix)
         }
+    }
+}
 
-        let expression = match tokens.peek() {
-            None => {
-                let _ = tokens.next();
-                return Err(Error::Eof);
-            }
-            Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+// parse an expression in prefix form.
+// In infix mode, also parses the operands of infix expressions.
+fn parse_prefix<'a>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Option<Expression<'a>>, Error<'a>> {
+    macro_rules! prefix_match_arm {
+        ($var:ident, $left_par:expr) => {{
+            Expression::$var(parse_lisp_node(context, tokens, $left_par)?)
+        }};
+    }
 
-            Some(Ok(Token::Lit(_))) => Expression::Lit(Grammar::parse(context, tokens)?),
-            Some(Ok(Token::Ident(_))) => {
-                let path = Grammar::parse(context, tokens)?;
-                if !context.is_defined(&path) {
-                    return Err(Error::InvalidPath(path));
-                }
-                Expression::Path(path)
+    let expression = match tokens.peek() {
+        None => {
+            let _ = tokens.next();
+            return Err(Error::Eof);
+        }
+        Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+
+        Some(Ok(Token::Lit(_))) => Expression::Lit(Grammar::parse(context, tokens)?),
+        Some(Ok(Token::Ident(_))) => {
+            let path = Grammar::parse(context, tokens)?;
+            if !context.is_defined(&path) {
+                return Err(Error::InvalidPath(path));
             }
-            // array
-            Some(Ok(Token::LeftSquare(_))) => Expression::Array(Grammar::parse(context, tokens)?),
-            // unary ops
-            Some(Ok(Token::Minus(_))) => Expression::Minus(Grammar::parse(context, tokens)?),
-            Some(Ok(Token::At(_))) => Expression::AddressOf(Grammar::parse(context, tokens)?),
-            Some(Ok(Token::Star(_))) => Expression::Deref(Grammar::parse(context, tokens)?),
-            Some(Ok(Token::Tilde(_))) => Expression::Not(Grammar::parse(context, tokens)?),
-
-            // others
-            Some(Ok(Token::LeftPar(_))) => {
-                let left_par = Grammar::parse(context, tokens)?;
-                match tokens.peek() {
-                    // arithmetic
-                    Some(Ok(Token::Plus(_))) => prefix_match_arm!(Add, left_par),
-                    Some(Ok(Token::Minus(_))) => prefix_match_arm!(Sub, left_par),
-                    Some(Ok(Token::Star(_))) => prefix_match_arm!(Mul, left_par),
-                    Some(Ok(Token::Slash(_))) => prefix_match_arm!(Div, left_par),
-                    Some(Ok(Token::Ampersand(_))) => prefix_match_arm!(And, left_par),
-                    Some(Ok(Token::Pipe(_))) => prefix_match_arm!(Or, left_par),
-                    Some(Ok(Token::Caret(_))) => prefix_match_arm!(Xor, left_par),
-                    // assignment
-                    Some(Ok(Token::Assign(_))) => prefix_match_arm!(Assign, left_par),
-                    Some(Ok(Token::PlusAssign(_))) => prefix_match_arm!(PlusAssign, left_par),
-                    Some(Ok(Token::MinusAssign(_))) => prefix_match_arm!(MinusAssign, left_par),
-                    Some(Ok(Token::StarAssign(_))) => prefix_match_arm!(MulAssign, left_par),
-                    Some(Ok(Token::SlashAssign(_))) => prefix_match_arm!(DivAssign, left_par),
-                    Some(Ok(Token::AmpersandAssign(_))) => prefix_match_arm!(AndAssign, left_par),
-                    Some(Ok(Token::PipeAssign(_))) => prefix_match_arm!(OrAssign, left_par),
-                    Some(Ok(Token::CaretAssign(_))) => prefix_match_arm!(XorAssign, left_par),
-                    // indexing
-                    Some(Ok(Token::LeftSquare(_))) => prefix_match_arm!(Index, left_par),
-                    // compare
-                    Some(Ok(Token::LessLess(_))) => prefix_match_arm!(LeftShift, left_par),
-                    Some(Ok(Token::GreatGreat(_))) => prefix_match_arm!(RightShift, left_par),
-                    Some(Ok(Token::Eq(_))) => prefix_match_arm!(Eq, left_par),
-                    Some(Ok(Token::TildeEq(_))) => prefix_match_arm!(NotEq, left_par),
-                    Some(Ok(Token::LessEq(_))) => prefix_match_arm!(LessEq, left_par),
-                    Some(Ok(Token::GreaterEq(_))) => prefix_match_arm!(GreaterEq, left_par),
-                    Some(Ok(Token::Less(_))) => prefix_match_arm!(Less, left_par),
-                    Some(Ok(Token::Greater(_))) => prefix_match_arm!(Greater, left_par),
-                    // calls
-                    Some(Ok(_)) => prefix_match_arm!(Call, left_par),
-                    // fallbacks
-                    // errors
-                    None => unimplemented!(),
-                    Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+            if builtin::is_namespace(&path) && Builtin::from_path(&path).is_none() {
+                return Err(unknown_builtin(&path));
+            }
+            Expression::Path(path)
+        }
+        // array
+        Some(Ok(Token::LeftSquare(_))) => Expression::Array(Grammar::parse(context, tokens)?),
+        // struct
+        Some(Ok(Token::LeftBracket(_))) => Expression::StructLit(Grammar::parse(context, tokens)?),
+        // unary ops
+        Some(Ok(Token::Minus(_))) => Expression::Minus(Grammar::parse(context, tokens)?),
+        Some(Ok(Token::At(_))) => Expression::AddressOf(Grammar::parse(context, tokens)?),
+        Some(Ok(Token::Star(_))) => Expression::Deref(Grammar::parse(context, tokens)?),
+        Some(Ok(Token::Tilde(_))) => Expression::Not(Grammar::parse(context, tokens)?),
+
+        // others
+        Some(Ok(Token::LeftPar(_))) => {
+            let left_par = Grammar::parse(context, tokens)?;
+            match tokens.peek() {
+                // arithmetic
+                Some(Ok(Token::Plus(_))) => prefix_match_arm!(Add, left_par),
+                Some(Ok(Token::Minus(_))) => prefix_match_arm!(Sub, left_par),
+                Some(Ok(Token::Star(_))) => prefix_match_arm!(Mul, left_par),
+                Some(Ok(Token::Slash(_))) => prefix_match_arm!(Div, left_par),
+                Some(Ok(Token::Ampersand(_))) => prefix_match_arm!(And, left_par),
+                Some(Ok(Token::Pipe(_))) => prefix_match_arm!(Or, left_par),
+                Some(Ok(Token::Caret(_))) => prefix_match_arm!(Xor, left_par),
+                // assignment
+                Some(Ok(Token::Assign(_))) => prefix_match_arm!(Assign, left_par),
+                Some(Ok(Token::PlusAssign(_))) => prefix_match_arm!(PlusAssign, left_par),
+                Some(Ok(Token::MinusAssign(_))) => prefix_match_arm!(MinusAssign, left_par),
+                Some(Ok(Token::StarAssign(_))) => prefix_match_arm!(MulAssign, left_par),
+                Some(Ok(Token::SlashAssign(_))) => prefix_match_arm!(DivAssign, left_par),
+                Some(Ok(Token::AmpersandAssign(_))) => prefix_match_arm!(AndAssign, left_par),
+                Some(Ok(Token::PipeAssign(_))) => prefix_match_arm!(OrAssign, left_par),
+                Some(Ok(Token::CaretAssign(_))) => prefix_match_arm!(XorAssign, left_par),
+                Some(Ok(Token::PlusPlus(_))) => prefix_match_arm!(Increment, left_par),
+                Some(Ok(Token::MinusMinus(_))) => prefix_match_arm!(Decrement, left_par),
+                // cast
+                Some(Ok(Token::As(_))) => prefix_match_arm!(Cast, left_par),
+                Some(Ok(Token::SizeOf(_))) => prefix_match_arm!(SizeOf, left_par),
+                // conditional
+                Some(Ok(Token::If(_))) => {
+                    let node = LispNode {
+                        left_par: Some(left_par),
+                        inner: Conditional {
+                            if_: Some(Grammar::parse(context, tokens)?),
+                            condition: Grammar::parse(context, tokens)?,
+                            question: None,
+                            then: Grammar::parse(context, tokens)?,
+                            colon: None,
+                            else_: Grammar::parse(context, tokens)?,
+                        },
+                        right_par: Some(Grammar::parse(context, tokens)?),
+                    };
+                    Expression::Conditional(context.alloc(node))
                 }
+                // indexing
+                Some(Ok(Token::LeftSquare(_))) => prefix_match_arm!(Index, left_par),
+                // compare
+                Some(Ok(Token::LessLess(_))) => prefix_match_arm!(LeftShift, left_par),
+                Some(Ok(Token::GreatGreat(_))) => prefix_match_arm!(RightShift, left_par),
+                Some(Ok(Token::Eq(_))) => prefix_match_arm!(Eq, left_par),
+                Some(Ok(Token::TildeEq(_))) => prefix_match_arm!(NotEq, left_par),
+                Some(Ok(Token::LessEq(_))) => prefix_match_arm!(LessEq, left_par),
+                Some(Ok(Token::GreaterEq(_))) => prefix_match_arm!(GreaterEq, left_par),
+                Some(Ok(Token::Less(_))) => prefix_match_arm!(Less, left_par),
+                Some(Ok(Token::Greater(_))) => prefix_match_arm!(Greater, left_par),
+                // logical
+                Some(Ok(Token::AmpersandAmpersand(_))) => {
+                    prefix_match_arm!(LogicalAnd, left_par)
+                }
+                Some(Ok(Token::PipePipe(_))) => prefix_match_arm!(LogicalOr, left_par),
+                // calls (or parenthesized infix expressions)
+                Some(Ok(_)) if context.is_infix() => {
+                    let call = infix::group(prefix_match_arm!(Call, left_par));
+                    const_call(context, call)?
+                }
+                Some(Ok(_)) => {
+                    let call = prefix_match_arm!(Call, left_par);
+                    const_call(context, call)?
+                }
+                // fallbacks
+                // errors
+                None => unimplemented!(),
+                Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
             }
-            Some(Ok(_)) => return Ok(None),
-        };
+        }
+        Some(Ok(_)) => return Ok(None),
+    };
 
-        Ok(Some(expression))
+    Ok(Some(expression))
+}
+
+// evaluate a call to a `const fn`. Other expressions are returned as they are.
+fn const_call<'a>(
+    context: &Context<'a>,
+    expression: Expression<'a>,
+) -> Result<Expression<'a>, Error<'a>> {
+    let mut node = match expression {
+        Expression::Call(node) if !context.is_const_fn_body() => node,
+        expression => return Ok(expression),
+    };
+    let fn_ = match &node.inner.left {
+        Expression::Path(path) => {
+            let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+            context.const_fn(&name.join("::"))
+        }
+        _ => None,
+    };
+    // calls with the wrong number of arguments are reported by the checker
+    let fn_ = match fn_ {
+        Some(fn_) if fn_.fn_arg.iter().flat_map(|a| &a.inner).count() == node.inner.args.len() => {
+            fn_
+        }
+        _ => return Ok(Expression::Call(node)),
+    };
+    let args: Option<Vec<u16>> = node.inner.args.iter().map(|a| context.eval(a)).collect();
+    node.inner.value = args.and_then(|args| const_fn::call(context, fn_, &args));
+    if node.inner.value.is_none() {
+        return Err(Error::NotConst { span: node.span() });
     }
+    Ok(Expression::Call(node))
+}
+
+// error of a path within the `builtin::` namespace that names no builtin.
+fn unknown_builtin<'a>(path: &Path<'a>) -> Error<'a> {
+    let name = path
+        .iter()
+        .map(|i| i.name().to_string())
+        .collect::<Vec<_>>()
+        .join("::");
+    let suggestion = path.tail.last().and_then(|(_, ident)| {
+        crate::error::suggest(ident.name(), Builtin::ALL.iter().map(|b| b.name()))
+    });
+    Error::UnknownBuiltin {
+        span: path.span(),
+        name,
+        suggestion,
+    }
+}
+
+// parse the rest of a prefix expression after its `(` token.
+// Kept out of line so the frame of the (recursive) parse_prefix stays small.
+#[inline(never)]
+fn parse_lisp_node<'a, I: Grammar<'a>>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    left_par: lex::LeftPar<'a>,
+) -> Result<Node<LispNode<'a, I>>, Error<'a>> {
+    let node = LispNode {
+        left_par: Some(left_par),
+        inner: Grammar::parse(context, tokens)?,
+        right_par: Some(Grammar::parse(context, tokens)?),
+    };
+    Ok(context.alloc(node))
 }
 
 impl<'a> Grammar<'a> for Expression<'a> {
@@ -138,8 +274,14 @@ impl<'a> Grammar<'a> for Expression<'a> {
         if let Some(statement) = Grammar::parse(context, tokens)? {
             Ok(statement)
         } else {
-            let token = tokens.next().unwrap()?;
-            Err(Error::UnexpectedToken(token))
+            match tokens.peek() {
+                Some(Ok(token)) => Err(Error::Expected {
+                    expected: "expression",
+                    found: token.clone(),
+                }),
+                Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+                None => Err(Error::Eof),
+            }
         }
     }
 }
@@ -148,57 +290,157 @@ span!(Array {
     left_square,
     right_square
 });
-span!(LispNode<I> {
-    left_par,
-    right_par
+span!(StructLit {
+    left_bracket,
+    right_bracket
+});
+span!(FieldInit {
+    ident,
+    colon,
+    expression
 });
-span!(Add { plus, right });
-span!(Sub { minus, right });
-span!(Mul { star, right });
-span!(Div { slash, right });
-span!(And { ampersand, right });
-span!(Or { pipe, right });
-span!(Xor { caret, right });
+span!(Add { left, plus, right });
+span!(Sub { left, minus, right });
+span!(Mul { left, star, right });
+span!(Div { left, slash, right });
+span!(And {
+    left,
+    ampersand,
+    right
+});
+span!(Or { left, pipe, right });
+span!(Xor { left, caret, right });
 span!(Minus { minus, inner });
 span!(AddressOf { at, inner });
 span!(Deref { star, inner });
 span!(Not { tilde, inner });
-span!(Assign { assign, right });
-span!(PlusAssign { plus_assign, right });
+span!(Increment { plus_plus, inner });
+span!(Decrement { minus_minus, inner });
+span!(Cast { as_, inner, type_ });
+span!(SizeOf { sizeof, inner });
+
+impl Spanned for Conditional<'_> {
+    fn span(&self) -> Span {
+        let min = match &self.if_ {
+            Some(if_) => if_.span(),
+            None => self.condition.span(),
+        };
+        span::union(&min, &self.else_.span())
+    }
+}
+span!(Assign {
+    left,
+    assign,
+    right
+});
+span!(PlusAssign {
+    left,
+    plus_assign,
+    right
+});
 span!(MinusAssign {
+    left,
     minus_assign,
     right
 });
-span!(MulAssign { star_assign, right });
+span!(MulAssign {
+    left,
+    star_assign,
+    right
+});
 span!(DivAssign {
+    left,
     slash_assign,
     right
 });
 span!(AndAssign {
+    left,
     ampersand_assign,
     right
 });
-span!(OrAssign { pipe_assign, right });
+span!(OrAssign {
+    left,
+    pipe_assign,
+    right
+});
 span!(XorAssign {
+    left,
     caret_assign,
     right
 });
 span!(Call { left });
 span!(Index { left_square, right });
-span!(LeftShift { less_less, right });
-span!(RightShift { great_great, right });
-span!(Eq { eq, right });
-span!(NotEq { tilde_eq, right });
-span!(LessEq { less_eq, right });
-span!(GreaterEq { greater_eq, right });
-span!(Less { less, right });
-span!(Greater { greater, right });
+span!(LeftShift {
+    left,
+    less_less,
+    right
+});
+span!(RightShift {
+    left,
+    great_great,
+    right
+});
+span!(Eq { left, eq, right });
+span!(NotEq {
+    left,
+    tilde_eq,
+    right
+});
+span!(LessEq {
+    left,
+    less_eq,
+    right
+});
+span!(GreaterEq {
+    left,
+    greater_eq,
+    right
+});
+span!(Less { left, less, right });
+span!(Greater {
+    left,
+    greater,
+    right
+});
+span!(LogicalAnd {
+    left,
+    ampersand_ampersand,
+    right
+});
+span!(LogicalOr {
+    left,
+    pipe_pipe,
+    right
+});
 
+/// Binary expression (or function call) node.
+///
+/// The parenthesis are always present in the prefix form (`(+ a b)`), and
+/// optional in the infix form (`a + b`, `(a + b)`).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a, I: serde::Deserialize<'de>"))
+)]
 #[derive(Debug)]
 pub struct LispNode<'a, I> {
-    pub left_par: lex::LeftPar<'a>,
+    /// `(` token.
+    pub left_par: Option<lex::LeftPar<'a>>,
+
+    /// Inner expression.
     pub inner: I,
-    pub right_par: lex::RightPar<'a>,
+
+    /// `)` token.
+    pub right_par: Option<lex::RightPar<'a>>,
+}
+
+impl<I: Spanned> Spanned for LispNode<'_, I> {
+    fn span(&self) -> Span {
+        match (&self.left_par, &self.right_par) {
+            (Some(left_par), Some(right_par)) => span::union(&left_par.span(), &right_par.span()),
+            _ => self.inner.span(),
+        }
+    }
 }
 
 parse! {
@@ -209,13 +451,57 @@ parse! {
         pub left_square: lex::LeftSquare<'a>,
 
         /// Inner expression tokens.
-        pub inner: Vec<Expression<'a>>,
+        pub inner: Separated<'a, Expression<'a>>,
 
         /// `]` token.
         pub right_square: lex::RightSquare<'a>,
     }
 }
 
+parse! {
+    /// `{ <field>:<expression> ... }`
+    ///
+    /// The struct type is given by the value the literal is assigned to.
+    #[derive(Debug)]
+    pub struct StructLit<'a> {
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Field initializers.
+        pub fields: Separated<'a, FieldInit<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+parse! {
+    #[derive(Debug)]
+    pub struct FieldInit<'a> {
+        /// Field identifier.
+        pub ident: lex::Ident<'a>,
+
+        /// `:` token.
+        pub colon: lex::Colon<'a>,
+
+        /// Field value expression.
+        pub expression: Expression<'a>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<FieldInit<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Ident(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(context, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct Add<'a> {
@@ -346,6 +632,114 @@ parse! {
     }
 }
 
+parse! {
+    /// Increment statement (`(++ x)`, or `x++` in infix form).
+    #[derive(Debug)]
+    pub struct Increment<'a> {
+        /// `++` token.
+        pub plus_plus: lex::PlusPlus<'a>,
+
+        /// Incremented expression tokens.
+        pub inner: Expression<'a>,
+    }
+}
+
+parse! {
+    /// Decrement statement (`(-- x)`, or `x--` in infix form).
+    #[derive(Debug)]
+    pub struct Decrement<'a> {
+        /// `--` token.
+        pub minus_minus: lex::MinusMinus<'a>,
+
+        /// Decremented expression tokens.
+        pub inner: Expression<'a>,
+    }
+}
+
+parse! {
+    /// Cast expression (`(as x u16)`, or `x as u16` in infix form).
+    #[derive(Debug)]
+    pub struct Cast<'a> {
+        /// `as` token.
+        pub as_: lex::As<'a>,
+
+        /// Cast expression tokens.
+        pub inner: Expression<'a>,
+
+        /// Type the expression is cast to.
+        pub type_: Type<'a>,
+    }
+}
+
+parse! {
+    /// Size in bytes of a type or of the value of an expression (`(sizeof u16)`,
+    /// `(sizeof FOO)`), known at compile time.
+    #[derive(Debug)]
+    pub struct SizeOf<'a> {
+        /// `sizeof` token.
+        pub sizeof: lex::SizeOf<'a>,
+
+        /// Type or expression tokens.
+        pub inner: SizeOfOperand<'a>,
+    }
+}
+
+parse! {
+    /// Operand of a `sizeof` expression.
+    #[derive(Debug)]
+    pub enum SizeOfOperand<'a> {
+        /// Type operand.
+        Type(Type<'a>),
+
+        /// Expression operand.
+        /// Paths are parsed as expressions, even if they name a type.
+        /// Parenthesized operands are always expressions.
+        Expression(Expression<'a>),
+    }
+}
+
+impl<'a> Grammar<'a> for SizeOfOperand<'a> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            // a `(` begins an expression (tuple types must be named with an alias)
+            Some(Ok(Token::Ident(_))) | Some(Ok(Token::LeftPar(_))) => {
+                Ok(SizeOfOperand::Expression(Grammar::parse(context, tokens)?))
+            }
+            _ => match Grammar::parse(context, tokens)? {
+                Some(type_) => Ok(SizeOfOperand::Type(type_)),
+                None => Ok(SizeOfOperand::Expression(Grammar::parse(context, tokens)?)),
+            },
+        }
+    }
+}
+
+parse! {
+    /// Conditional expression (`(if c a b)`, or `c ? a : b` in infix form).
+    #[derive(Debug)]
+    pub struct Conditional<'a> {
+        /// `if` token (prefix form).
+        pub if_: Option<lex::If<'a>>,
+
+        /// Condition expression tokens.
+        pub condition: Expression<'a>,
+
+        /// `?` token (infix form).
+        pub question: Option<lex::Question<'a>>,
+
+        /// Expression tokens evaluated if the condition holds.
+        pub then: Expression<'a>,
+
+        /// `:` token (infix form).
+        pub colon: Option<lex::Colon<'a>>,
+
+        /// Expression tokens evaluated otherwise.
+        pub else_: Expression<'a>,
+    }
+}
+
 parse! {
     #[derive(Debug)]
     pub struct Assign<'a> {
@@ -458,11 +852,46 @@ parse! {
     }
 }
 
-parse! {
-    #[derive(Debug)]
-    pub struct Call<'a> {
-        pub left: Expression<'a>,
-        pub args: Vec<Expression<'a>>,
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Call<'a> {
+    pub left: Expression<'a>,
+    pub args: Separated<'a, Expression<'a>>,
+
+    /// Value of the call, if it calls a `const fn`.
+    ///
+    /// Calls to const functions are evaluated as they are parsed, and fail
+    /// with an [`Error::NotConst`](Error::NotConst) unless their arguments
+    /// are constant expressions. The calls within the body of a const
+    /// function are evaluated along with it, so they have no value.
+    pub value: Option<u16>,
+}
+
+impl<'a> Grammar<'a> for Call<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        Ok(Self {
+            left: Grammar::parse(ctx, tokens)?,
+            args: Grammar::parse(ctx, tokens)?,
+            value: None,
+        })
+    }
+}
+
+impl crate::incremental::Remap for Call<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.left.remap(f);
+        self.args.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Call<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.left.own(f);
+        self.args.own(f);
     }
 }
 
@@ -594,3 +1023,31 @@ parse! {
         pub right: Expression<'a>,
     }
 }
+
+parse! {
+    #[derive(Debug)]
+    pub struct LogicalAnd<'a> {
+        /// `&&` token.
+        pub ampersand_ampersand: lex::AmpersandAmpersand<'a>,
+
+        /// lhs expression tokens.
+        pub left: Expression<'a>,
+
+        /// rhs expression tokens.
+        pub right: Expression<'a>,
+    }
+}
+
+parse! {
+    #[derive(Debug)]
+    pub struct LogicalOr<'a> {
+        /// `||` token.
+        pub pipe_pipe: lex::PipePipe<'a>,
+
+        /// lhs expression tokens.
+        pub left: Expression<'a>,
+
+        /// rhs expression tokens.
+        pub right: Expression<'a>,
+    }
+}
@@ -0,0 +1,149 @@
+//! Constant expression evaluation.
+//!
+//! Evaluates the expressions whose value must be known at compile time (array
+//! lengths, `static@ADDR` addresses, enum discriminants, ...). Values are 16bit
+//! and the arithmetic wraps around. The comparison and logical operators
+//! evaluate to `0` or `1`.
+//!
+//! The evaluator only knows about literals and operators. The value of the
+//! remaining operands (paths to previously defined consts, casts, `sizeof`,
+//! ...) is provided by the caller:
+//!
+//! ```
+//! use parser::ast::{expression::eval, Expression, Statement};
+//!
+//! let ast = parser::parse("const FOO:u8 = (+ (<< 1 4) BAR)").unwrap();
+//! let expression = match &ast.inner[0] {
+//!     Statement::Const(const_) => &const_.expression,
+//!     _ => unreachable!(),
+//! };
+//! let value = eval::eval(expression, &mut |e| match e {
+//!     Expression::Path(path) if path.head.to_string() == "BAR" => Some(2),
+//!     _ => None,
+//! });
+//! assert_eq!(Some(18), value);
+//! ```
+use crate::ast::Expression;
+
+/// Evaluate a constant expression.
+///
+/// `resolve` is called with the operands the evaluator doesn't know the value
+/// of. Returns `None` if the expression, or any of its evaluated operands, is
+/// not a constant expression, or if it divides by zero.
+pub fn eval<'a, F>(expression: &Expression<'a>, resolve: &mut F) -> Option<u16>
+where
+    F: FnMut(&Expression<'a>) -> Option<u16>,
+{
+    use Expression as E;
+
+    macro_rules! binary {
+        ($node:expr, |$l:ident, $r:ident| $value:expr) => {{
+            let $l = eval(&$node.inner.left, resolve)?;
+            let $r = eval(&$node.inner.right, resolve)?;
+            Some($value)
+        }};
+    }
+
+    match expression {
+        E::Lit(lit) => {
+            if let Some(b) = lit.bool_value() {
+                return Some(b.into());
+            }
+            if let Some(c) = lit.char_value() {
+                return Some(c.into());
+            }
+            lit.num_value()
+        }
+        E::Minus(e) => Some(eval(&e.inner, resolve)?.wrapping_neg()),
+        E::Not(e) => Some(!eval(&e.inner, resolve)?),
+        E::Add(e) => binary!(e, |l, r| l.wrapping_add(r)),
+        E::Sub(e) => binary!(e, |l, r| l.wrapping_sub(r)),
+        E::Mul(e) => binary!(e, |l, r| l.wrapping_mul(r)),
+        E::Div(e) => binary!(e, |l, r| l.checked_div(r)?),
+        E::And(e) => binary!(e, |l, r| l & r),
+        E::Or(e) => binary!(e, |l, r| l | r),
+        E::Xor(e) => binary!(e, |l, r| l ^ r),
+        // shifting all the bits out leaves a 0
+        E::LeftShift(e) => binary!(e, |l, r| l.checked_shl(r.into()).unwrap_or(0)),
+        E::RightShift(e) => binary!(e, |l, r| l.checked_shr(r.into()).unwrap_or(0)),
+        E::Eq(e) => binary!(e, |l, r| (l == r).into()),
+        E::NotEq(e) => binary!(e, |l, r| (l != r).into()),
+        E::Greater(e) => binary!(e, |l, r| (l > r).into()),
+        E::GreaterEq(e) => binary!(e, |l, r| (l >= r).into()),
+        E::Less(e) => binary!(e, |l, r| (l < r).into()),
+        E::LessEq(e) => binary!(e, |l, r| (l <= r).into()),
+        E::Conditional(e) => match eval(&e.inner.condition, resolve)? {
+            0 => eval(&e.inner.else_, resolve),
+            _ => eval(&e.inner.then, resolve),
+        },
+        // the right operand is not evaluated if the left one determines the result
+        E::LogicalAnd(e) => match eval(&e.inner.left, resolve)? {
+            0 => Some(0),
+            _ => Some((eval(&e.inner.right, resolve)? != 0).into()),
+        },
+        E::LogicalOr(e) => match eval(&e.inner.left, resolve)? {
+            0 => Some((eval(&e.inner.right, resolve)? != 0).into()),
+            _ => Some(1),
+        },
+        expression => resolve(expression),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::eval;
+    use crate::{
+        ast::{Expression, Statement},
+        ContextBuilder,
+    };
+
+    fn eval_infix(input: &str) -> Option<u16> {
+        let input = format!("const X:i16 = {}", input);
+        let mut context = ContextBuilder::default().infix(true).build();
+        let ast = crate::parse_with_context(&input, &mut context).unwrap();
+        match &ast.inner[0] {
+            Statement::Const(const_) => eval(&const_.expression, &mut |_| None),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Some(7), eval_infix("1 + 2 * 3"));
+        assert_eq!(Some(0xfffe), eval_infix("-2"));
+        assert_eq!(Some(0xffff), eval_infix("0 - 1"));
+        assert_eq!(Some(0), eval_infix("0xffff + 1"));
+        assert_eq!(Some(0x10), eval_infix("0x8 << 1"));
+        assert_eq!(Some(0), eval_infix("1 << 16"));
+        assert_eq!(Some(0xf0), eval_infix("~0x0f & 0xff"));
+        assert_eq!(Some(4), eval_infix("(7 - 3) / 1"));
+        assert_eq!(None, eval_infix("1 / 0"));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(Some(1), eval_infix("1 < 2"));
+        assert_eq!(Some(0), eval_infix("2 < 1"));
+        assert_eq!(Some(0), eval_infix("1 == 2"));
+        assert_eq!(Some(1), eval_infix("1 ~= 2"));
+        assert_eq!(Some(3), eval_infix("2 >= 2 ? 3 : 4"));
+        assert_eq!(Some(1), eval_infix("0 || 'a' > 0"));
+        // the right operand is not evaluated
+        assert_eq!(Some(0), eval_infix("false && FOO"));
+        assert_eq!(None, eval_infix("true && FOO"));
+    }
+
+    #[test]
+    fn resolve() {
+        let ast = crate::parse("const X:u8 = (* 2 (+ FOO 1))").unwrap();
+        let expression = match &ast.inner[0] {
+            Statement::Const(const_) => &const_.expression,
+            _ => unreachable!(),
+        };
+        let value = eval(expression, &mut |e| match e {
+            Expression::Path(path) if path.head.to_string() == "FOO" => Some(3),
+            _ => None,
+        });
+        assert_eq!(Some(8), value);
+    }
+}
@@ -0,0 +1,330 @@
+//! Infix expressions.
+//!
+//! Enabled with [`ContextBuilder::infix`](crate::ast::ContextBuilder::infix).
+//! Binary operators, from lowest to highest precedence:
+//!
+//! | Operators                               | Associativity |
+//! |-----------------------------------------|---------------|
+//! | `=` `+=` `-=` `*=` `/=` `&=` `\|=` `^=` | right         |
+//! | `?:`                                    | right         |
+//! | `\|\|`                                  | left          |
+//! | `&&`                                    | left          |
+//! | `==` `~=`                               | left          |
+//! | `<` `<=` `>` `>=`                       | left          |
+//! | `\|`                                    | left          |
+//! | `^`                                     | left          |
+//! | `&`                                     | left          |
+//! | `<<` `>>`                               | left          |
+//! | `+` `-`                                 | left          |
+//! | `*` `/`                                 | left          |
+//! | `as`                                    | left          |
+//!
+//! Unary operators (`-`, `@`, `*` and `~`) bind tighter than any binary
+//! operator and `as` (`-a as u16` casts `-a`), and the postfix `++` and `--`
+//! bind tighter than unary operators (`*p++` increments `p`).
+//!
+//! # Remarks
+//! - The prefix form is still accepted. A `(` followed by an operator begins a
+//!   prefix expression, so `(-a + b)` must be written as `-a + b` or
+//!   `(0 - a + b)`.
+//! - A parenthesized path is a function call (`(foo)`, `(foo a b)`), as in
+//!   the prefix form.
+//! - A binary operator must be on the same line as the end of its left
+//!   operand. Otherwise it begins a new statement.
+//! - The branch between `?` and `:` can be any expression, as in C.
+//!
+//! ```
+//! use parser::{ast::Statement, ContextBuilder};
+//!
+//! let mut context = ContextBuilder::default().infix(true).build();
+//! let input = "a = b + 1 * c\n*p = (a + 2) << 1";
+//! let ast = parser::parse_with_context(input, &mut context).unwrap();
+//! assert_eq!(2, ast.inner.len());
+//! ```
+use crate::{
+    arena::Node,
+    ast::{
+        expression::{self as e, Expression, LispNode},
+        Context, Grammar,
+    },
+    lex::{span::Spanned, Token, Tokens},
+    Error,
+};
+use std::iter::Peekable;
+
+// left binding power of `as`.
+const CAST: u8 = 25;
+
+// binding power of the operand of unary operators.
+const UNARY: u8 = 27;
+
+pub(super) fn parse<'a>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Option<Expression<'a>>, Error<'a>> {
+    parse_bp(context, tokens, 0)
+}
+
+// (left, right) binding power of a binary operator.
+fn binding_power(token: &Token<'_>) -> Option<(u8, u8)> {
+    Some(match token {
+        Token::Assign(_)
+        | Token::PlusAssign(_)
+        | Token::MinusAssign(_)
+        | Token::StarAssign(_)
+        | Token::SlashAssign(_)
+        | Token::AmpersandAssign(_)
+        | Token::PipeAssign(_)
+        | Token::CaretAssign(_) => (2, 1),
+        Token::Question(_) => (4, 3),
+        Token::PipePipe(_) => (5, 6),
+        Token::AmpersandAmpersand(_) => (7, 8),
+        Token::Eq(_) | Token::TildeEq(_) => (9, 10),
+        Token::Less(_) | Token::LessEq(_) | Token::Greater(_) | Token::GreaterEq(_) => (11, 12),
+        Token::Pipe(_) => (13, 14),
+        Token::Caret(_) => (15, 16),
+        Token::Ampersand(_) => (17, 18),
+        Token::LessLess(_) | Token::GreatGreat(_) => (19, 20),
+        Token::Plus(_) | Token::Minus(_) => (21, 22),
+        Token::Star(_) | Token::Slash(_) => (23, 24),
+        _ => return None,
+    })
+}
+
+fn parse_bp<'a>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    min_bp: u8,
+) -> Result<Option<Expression<'a>>, Error<'a>> {
+    macro_rules! unary {
+        ($var:ident, $op:ident) => {{
+            let node = e::$var {
+                $op: Grammar::parse(context, tokens)?,
+                inner: operand(context, tokens, UNARY)?,
+            };
+            Expression::$var(context.alloc(node))
+        }};
+    }
+
+    let mut left = match tokens.peek() {
+        Some(Ok(Token::Minus(_))) => unary!(Minus, minus),
+        Some(Ok(Token::At(_))) => unary!(AddressOf, at),
+        Some(Ok(Token::Star(_))) => unary!(Deref, star),
+        Some(Ok(Token::Tilde(_))) => unary!(Not, tilde),
+        _ => match super::parse_prefix(context, tokens)? {
+            Some(expression) => expression,
+            None => return Ok(None),
+        },
+    };
+
+    loop {
+        // postfix operators
+        match tokens.peek() {
+            Some(Ok(Token::PlusPlus(t))) if t.span().min[0] == left.span().max[0] => {
+                let node = LispNode {
+                    left_par: None,
+                    inner: e::Increment {
+                        inner: left,
+                        plus_plus: Grammar::parse(context, tokens)?,
+                    },
+                    right_par: None,
+                };
+                left = Expression::Increment(context.alloc(node));
+                continue;
+            }
+            Some(Ok(Token::MinusMinus(t))) if t.span().min[0] == left.span().max[0] => {
+                let node = LispNode {
+                    left_par: None,
+                    inner: e::Decrement {
+                        inner: left,
+                        minus_minus: Grammar::parse(context, tokens)?,
+                    },
+                    right_par: None,
+                };
+                left = Expression::Decrement(context.alloc(node));
+                continue;
+            }
+            Some(Ok(Token::As(t))) if t.span().min[0] == left.span().max[0] && CAST >= min_bp => {
+                let node = LispNode {
+                    left_par: None,
+                    inner: e::Cast {
+                        inner: left,
+                        as_: Grammar::parse(context, tokens)?,
+                        type_: Grammar::parse(context, tokens)?,
+                    },
+                    right_par: None,
+                };
+                left = Expression::Cast(context.alloc(node));
+                continue;
+            }
+            _ => {}
+        }
+
+        let (left_bp, right_bp) = match tokens.peek() {
+            Some(Ok(token)) if token.span().min[0] == left.span().max[0] => {
+                match binding_power(token) {
+                    Some(bp) => bp,
+                    None => break,
+                }
+            }
+            _ => break,
+        };
+        if left_bp < min_bp {
+            break;
+        }
+        left = binary(context, tokens, left, right_bp)?;
+    }
+
+    Ok(Some(left))
+}
+
+fn operand<'a>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    min_bp: u8,
+) -> Result<Expression<'a>, Error<'a>> {
+    let expression = context.nested(tokens, |context, tokens| parse_bp(context, tokens, min_bp))?;
+    match expression {
+        Some(expression) => Ok(expression),
+        None => match tokens.peek() {
+            Some(Ok(token)) => Err(Error::Expected {
+                expected: "expression",
+                found: token.clone(),
+            }),
+            Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+            None => Err(Error::Eof),
+        },
+    }
+}
+
+// parse the operator and the rhs of a binary expression.
+fn binary<'a>(
+    context: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+    left: Expression<'a>,
+    right_bp: u8,
+) -> Result<Expression<'a>, Error<'a>> {
+    macro_rules! binary {
+        ($var:ident, $op:ident) => {{
+            let $op = Grammar::parse(context, tokens)?;
+            let right = operand(context, tokens, right_bp)?;
+            Expression::$var(context.alloc(LispNode {
+                left_par: None,
+                inner: e::$var { $op, left, right },
+                right_par: None,
+            }))
+        }};
+    }
+
+    Ok(match tokens.peek() {
+        Some(Ok(Token::Plus(_))) => binary!(Add, plus),
+        Some(Ok(Token::Minus(_))) => binary!(Sub, minus),
+        Some(Ok(Token::Star(_))) => binary!(Mul, star),
+        Some(Ok(Token::Slash(_))) => binary!(Div, slash),
+        Some(Ok(Token::Ampersand(_))) => binary!(And, ampersand),
+        Some(Ok(Token::Pipe(_))) => binary!(Or, pipe),
+        Some(Ok(Token::Caret(_))) => binary!(Xor, caret),
+        Some(Ok(Token::Assign(_))) => binary!(Assign, assign),
+        Some(Ok(Token::PlusAssign(_))) => binary!(PlusAssign, plus_assign),
+        Some(Ok(Token::MinusAssign(_))) => binary!(MinusAssign, minus_assign),
+        Some(Ok(Token::StarAssign(_))) => binary!(MulAssign, star_assign),
+        Some(Ok(Token::SlashAssign(_))) => binary!(DivAssign, slash_assign),
+        Some(Ok(Token::AmpersandAssign(_))) => binary!(AndAssign, ampersand_assign),
+        Some(Ok(Token::PipeAssign(_))) => binary!(OrAssign, pipe_assign),
+        Some(Ok(Token::CaretAssign(_))) => binary!(XorAssign, caret_assign),
+        Some(Ok(Token::LessLess(_))) => binary!(LeftShift, less_less),
+        Some(Ok(Token::GreatGreat(_))) => binary!(RightShift, great_great),
+        Some(Ok(Token::Eq(_))) => binary!(Eq, eq),
+        Some(Ok(Token::TildeEq(_))) => binary!(NotEq, tilde_eq),
+        Some(Ok(Token::LessEq(_))) => binary!(LessEq, less_eq),
+        Some(Ok(Token::GreaterEq(_))) => binary!(GreaterEq, greater_eq),
+        Some(Ok(Token::Less(_))) => binary!(Less, less),
+        Some(Ok(Token::Greater(_))) => binary!(Greater, greater),
+        Some(Ok(Token::AmpersandAmpersand(_))) => binary!(LogicalAnd, ampersand_ampersand),
+        Some(Ok(Token::PipePipe(_))) => binary!(LogicalOr, pipe_pipe),
+        Some(Ok(Token::Question(_))) => {
+            let question = Some(Grammar::parse(context, tokens)?);
+            let then = operand(context, tokens, 0)?;
+            let colon = Some(Grammar::parse(context, tokens)?);
+            let else_ = operand(context, tokens, right_bp)?;
+            Expression::Conditional(context.alloc(LispNode {
+                left_par: None,
+                inner: e::Conditional {
+                    if_: None,
+                    condition: left,
+                    question,
+                    then,
+                    colon,
+                    else_,
+                },
+                right_par: None,
+            }))
+        }
+        _ => unreachable!(),
+    })
+}
+
+// A parenthesized expression is parsed as a function call. Unless it is a call
+// to a path (`(foo)`, `(foo a b)`), it's a parenthesized infix expression
+// instead, and the parenthesis are kept in its node.
+pub(super) fn group(call: Expression<'_>) -> Expression<'_> {
+    let node = match call {
+        Expression::Call(node) => node,
+        _ => unreachable!(),
+    };
+    if !node.inner.args.is_empty() || matches!(node.inner.left, Expression::Path(_)) {
+        return Expression::Call(node);
+    }
+
+    let LispNode {
+        left_par,
+        inner,
+        right_par,
+    } = Node::into_inner(node);
+    let mut expression = inner.left;
+
+    macro_rules! group {
+        ($($var:ident),*) => {
+            match &mut expression {
+                $(Expression::$var(node) if node.left_par.is_none() => {
+                    node.left_par = left_par;
+                    node.right_par = right_par;
+                })*
+                _ => {}
+            }
+        };
+    }
+
+    group!(
+        Increment,
+        Decrement,
+        Conditional,
+        Cast,
+        Add,
+        Sub,
+        Mul,
+        Div,
+        And,
+        Or,
+        Xor,
+        Assign,
+        PlusAssign,
+        MinusAssign,
+        MulAssign,
+        DivAssign,
+        AndAssign,
+        OrAssign,
+        XorAssign,
+        LeftShift,
+        RightShift,
+        Eq,
+        NotEq,
+        LessEq,
+        GreaterEq,
+        Less,
+        Greater,
+        LogicalAnd,
+        LogicalOr
+    );
+    expression
+}
@@ -0,0 +1,1190 @@
+//! Canonical source formatting.
+//!
+//! [`format`] prints an [`Ast`] back into GGB source code, laid out in a
+//! canonical way: one statement per line, the statements of a block indented
+//! one level, and single spaces between tokens. Expressions and types are
+//! printed on a single line, unless they don't fit in the line width. Then:
+//!
+//! - Prefix expressions and calls keep their first operand on the line of the
+//!   `(`, and put each of the rest on a line of its own.
+//! - Struct literals, struct and union types, enums, bitfields, and memory
+//!   maps put each of their items on a line of their own.
+//! - Array literals fill each line with as many elements as fit.
+//!
+//! Formatting a formatted program gives back the same source code.
+//!
+//! # Remarks
+//! - Regular comments (`//`) are not part of the AST, so they're dropped. Doc
+//!   comments (`///`) of declarations are kept. Use the [`cst`](crate::cst)
+//!   for lossless transformations.
+//! - Expressions are printed in the form they were written in (prefix or
+//!   infix), and lists written with `,` separators keep them.
+//! - Macro invocations and imports are printed as written, not expanded.
+//! - Up to one blank line between two statements is kept.
+//!
+//! ```
+//! use parser::ast::fmt::{format, FormatOptions};
+//!
+//! let ast = parser::parse("let  x:u8=(+ 1   2 ) fn f(a:u8) {(= x a)}").unwrap();
+//! let source = format(&ast, FormatOptions::default());
+//! assert_eq!("let x:u8 = (+ 1 2)\nfn f(a:u8) {\n    (= x a)\n}\n", source);
+//! ```
+use crate::{
+    ast::{
+        expression::{self as e, Expression, LispNode, SizeOfOperand},
+        types::{self, Member, Type},
+        Ast, Doc, Field, Pattern, Range, RegionSpace, Separated, Statement,
+    },
+    lex::{self, span::Spanned, Token},
+};
+use std::fmt::Display;
+
+/// Options of the [`format`] function.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    /// Number of spaces of each indentation level.
+    pub indent: usize,
+
+    /// Maximum width of a line, in characters.
+    ///
+    /// Lines are only broken between the items of a list, so some (long
+    /// identifiers, literals, infix expressions, ...) may still exceed it.
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            line_width: 100,
+        }
+    }
+}
+
+/// Formats a program into canonical source code.
+pub fn format(ast: &Ast<'_>, options: FormatOptions) -> String {
+    let mut formatter = Formatter {
+        options,
+        out: String::new(),
+        level: 0,
+        column: 0,
+        blank_lines: true,
+    };
+    formatter.statements(&ast.inner);
+    formatter.out
+}
+
+// canonical source code of a statement, independent of the way it was
+// written (blank lines included).
+pub(crate) fn canonical(statement: &Statement<'_>) -> String {
+    let mut formatter = Formatter {
+        options: FormatOptions::default(),
+        out: String::new(),
+        level: 0,
+        column: 0,
+        blank_lines: false,
+    };
+    formatter.statements(std::slice::from_ref(statement));
+    formatter.out
+}
+
+// Layout of a piece of source code, printed on a single line if it fits.
+enum Layout {
+    Text(String),
+
+    // pieces printed one after the other.
+    Concat(Vec<Self>),
+
+    List(List),
+}
+
+struct List {
+    open: String,
+    items: Vec<Layout>,
+    close: String,
+
+    // whether the items are separated by `,`.
+    commas: bool,
+
+    // whether there's a space between the delimiters and the items (only
+    // after the opening delimiter of hanging lists: `(+ a b)`).
+    padded: bool,
+
+    style: Style,
+}
+
+// Way a list is broken over several lines.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Style {
+    // first item on the line of the opening delimiter, the rest on their own
+    // lines, and the closing delimiter after the last one: `(+ a` `b)`.
+    Hanging,
+
+    // every item on its own line, and the delimiters on lines of their own.
+    Block,
+
+    // as many items per line as fit, and the delimiters on lines of their own.
+    Fill,
+}
+
+fn text(text: impl Display) -> Layout {
+    Layout::Text(text.to_string())
+}
+
+impl Layout {
+    // source code of the layout, printed on a single line.
+    fn flat(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Concat(pieces) => pieces.iter().map(Self::flat).collect(),
+            Self::List(list) => {
+                let sep = if list.commas { ", " } else { " " };
+                let items: Vec<_> = list.items.iter().map(Self::flat).collect();
+                let items = items.join(sep);
+                match (list.padded, list.style) {
+                    _ if items.is_empty() => format!("{}{}", list.open, list.close),
+                    (true, Style::Hanging) => format!("{} {}{}", list.open, items, list.close),
+                    (true, _) => format!("{} {} {}", list.open, items, list.close),
+                    (false, _) => format!("{}{}{}", list.open, items, list.close),
+                }
+            }
+        }
+    }
+
+    // width of the flat layout.
+    fn width(&self) -> usize {
+        self.flat().chars().count()
+    }
+}
+
+// width of a sequence of pieces up to the first place it can be broken at,
+// followed by `trailing` characters.
+fn head_width(pieces: &[Layout], trailing: usize) -> usize {
+    fn head(pieces: &[Layout], width: &mut usize) -> bool {
+        for piece in pieces {
+            match piece {
+                Layout::Text(text) => *width += text.chars().count(),
+                Layout::Concat(pieces) if head(pieces, width) => return true,
+                Layout::Concat(_) => {}
+                Layout::List(list) => {
+                    *width += list.open.chars().count();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    let mut width = 0;
+    if !head(pieces, &mut width) {
+        width += trailing;
+    }
+    width
+}
+
+struct Formatter {
+    options: FormatOptions,
+    out: String,
+
+    // indentation level.
+    level: usize,
+
+    // column of the end of the output.
+    column: usize,
+
+    // whether the blank lines between statements are kept.
+    blank_lines: bool,
+}
+
+impl Formatter {
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.column += text.chars().count();
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.column = 0;
+    }
+
+    fn indent(&mut self, level: usize) {
+        let indent = " ".repeat(level * self.options.indent);
+        self.write(&indent);
+    }
+
+    // print a layout at the current column, followed by `trailing` characters
+    // on the same line.
+    fn layout(&mut self, layout: &Layout, trailing: usize) {
+        let flat = layout.flat();
+        if self.column + flat.chars().count() + trailing <= self.options.line_width {
+            self.write(&flat);
+            return;
+        }
+        match layout {
+            Layout::Text(text) => self.write(text),
+            Layout::Concat(pieces) => {
+                for (i, piece) in pieces.iter().enumerate() {
+                    self.layout(piece, head_width(&pieces[i + 1..], trailing));
+                }
+            }
+            Layout::List(list) => self.list(list, trailing),
+        }
+    }
+
+    fn list(&mut self, list: &List, trailing: usize) {
+        let level = self.level;
+        let comma = if list.commas { "," } else { "" };
+        let last = list.items.len().saturating_sub(1);
+        self.write(&list.open);
+        if list.items.is_empty() {
+            self.write(&list.close);
+            return;
+        }
+        self.level += 1;
+        match list.style {
+            Style::Hanging => {
+                for (i, item) in list.items.iter().enumerate() {
+                    if i == 0 {
+                        if list.padded {
+                            self.write(" ");
+                        }
+                    } else {
+                        self.newline();
+                        self.indent(level + 1);
+                    }
+                    if i == last {
+                        self.layout(item, list.close.chars().count() + trailing);
+                    } else {
+                        self.layout(item, comma.len());
+                        self.write(comma);
+                    }
+                }
+                self.level = level;
+                self.write(&list.close);
+                return;
+            }
+            Style::Block => {
+                for item in &list.items {
+                    self.newline();
+                    self.indent(level + 1);
+                    self.layout(item, comma.len());
+                    self.write(comma);
+                }
+            }
+            Style::Fill => {
+                self.newline();
+                self.indent(level + 1);
+                let start = self.column;
+                for item in &list.items {
+                    let width = item.width() + comma.len();
+                    if self.column > start {
+                        if self.column + 1 + width > self.options.line_width {
+                            self.newline();
+                            self.indent(level + 1);
+                        } else {
+                            self.write(" ");
+                        }
+                    }
+                    self.layout(item, comma.len());
+                    self.write(comma);
+                }
+            }
+        }
+        self.level = level;
+        self.newline();
+        self.indent(level);
+        self.write(&list.close);
+    }
+
+    fn statements(&mut self, statements: &[Statement<'_>]) {
+        let mut last: Option<usize> = None;
+        for statement in statements {
+            let (first, end) = lines(statement);
+            if self.blank_lines && matches!(last, Some(last) if first > last + 1) {
+                self.newline();
+            }
+            self.indent(self.level);
+            self.statement(statement);
+            self.newline();
+            last = Some(end);
+        }
+    }
+
+    // `{ <statements> }`, or `{}` if the block is empty.
+    fn block(&mut self, statements: &[Statement<'_>]) {
+        if statements.is_empty() {
+            self.write("{}");
+            return;
+        }
+        self.write("{");
+        self.newline();
+        self.level += 1;
+        self.statements(statements);
+        self.level -= 1;
+        self.indent(self.level);
+        self.write("}");
+    }
+
+    // `<header> { <statements> }`
+    fn header_block(&mut self, header: &Layout, statements: &[Statement<'_>]) {
+        // the ` {` follows the header
+        self.layout(header, 2);
+        self.write(" ");
+        self.block(statements);
+    }
+
+    fn doc(&mut self, doc: &Option<Doc<'_>>) {
+        if let Some(doc) = doc {
+            for line in &doc.lines {
+                self.write(&format!("///{}", line.trim_end()));
+                self.newline();
+                self.indent(self.level);
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'_>) {
+        match statement {
+            Statement::If(if_) => {
+                let header = Layout::Concat(vec![text("if "), expression(&if_.expression)]);
+                self.header_block(&header, &if_.inner);
+            }
+            Statement::IfElse(if_else) => {
+                let header = Layout::Concat(vec![text("if "), expression(&if_else.if_.expression)]);
+                self.header_block(&header, &if_else.if_.inner);
+                self.write(" else ");
+                self.block(&if_else.else_.inner);
+            }
+            Statement::IfConst(if_const) => {
+                let header =
+                    Layout::Concat(vec![text("if const "), expression(&if_const.expression)]);
+                self.header_block(&header, &if_const.inner);
+                if let Some(else_) = &if_const.else_ {
+                    self.write(" else ");
+                    self.block(&else_.inner);
+                }
+            }
+            Statement::Scope(scope) => self.block(&scope.inner),
+            Statement::Panic(_) => self.write("!!"),
+            Statement::Mod(mod_) => {
+                self.header_block(&text(format!("mod {}", mod_.ident)), &mod_.inner)
+            }
+            Statement::Import(import) => self.write(&format!("import {}", import.path)),
+            Statement::Macro(macro_) => {
+                let params = separated(&macro_.params, text);
+                let header = Layout::Concat(vec![
+                    text(format!("macro {}", macro_.ident)),
+                    Layout::List(List {
+                        open: "(".to_string(),
+                        close: ")".to_string(),
+                        padded: false,
+                        style: Style::Hanging,
+                        ..params
+                    }),
+                ]);
+                self.layout(&header, 2);
+                self.write(" ");
+                self.macro_body(&macro_.body);
+            }
+            Statement::MacroCall(call) => {
+                let mut args = String::new();
+                for (i, arg) in call.args.iter().enumerate() {
+                    args.push_str(&tokens(arg));
+                    if i < call.commas.len() {
+                        args.push_str(if i + 1 < call.args.len() { ", " } else { "," });
+                    }
+                }
+                self.write(&format!("{}!({})", call.ident, args));
+            }
+            Statement::Memory(memory) => {
+                let regions = separated(&memory.regions, |region| {
+                    let space = match &region.space {
+                        Some(RegionSpace::Static(static_)) => format!("{} ", static_),
+                        Some(RegionSpace::Const(const_)) => format!("{} ", const_),
+                        None => String::new(),
+                    };
+                    Layout::Concat(vec![
+                        text(format!("{}{}:", space, region.ident)),
+                        range(&region.range),
+                    ])
+                });
+                let layout = Layout::List(List {
+                    open: "memory {".to_string(),
+                    close: "}".to_string(),
+                    padded: true,
+                    style: Style::Block,
+                    ..regions
+                });
+                self.layout(&layout, 0);
+            }
+            Statement::Static(static_) => {
+                self.doc(&static_.doc);
+                let mut pieces = vec![text(format!("{}{}", pub_(&static_.pub_), static_.static_))];
+                if let Some(offset) = &static_.offset {
+                    pieces.push(text("@"));
+                    pieces.push(expression(&offset.expression));
+                    if let Some(volatile) = &offset.volatile {
+                        pieces.push(text(format!(" {}", volatile)));
+                    }
+                }
+                if let Some(bank) = &static_.bank {
+                    pieces.push(text(format!("@{}(", bank.bank)));
+                    pieces.push(expression(&bank.expression));
+                    pieces.push(text(")"));
+                }
+                pieces.push(text(" "));
+                pieces.push(field(&static_.field));
+                if let Some(init) = &static_.init {
+                    pieces.push(text(" = "));
+                    pieces.push(expression(&init.expression));
+                }
+                self.layout(&Layout::Concat(pieces), 0);
+            }
+            Statement::Const(const_) => {
+                self.doc(&const_.doc);
+                let layout = Layout::Concat(vec![
+                    text(format!("{}const ", pub_(&const_.pub_))),
+                    field(&const_.field),
+                    text(" = "),
+                    expression(&const_.expression),
+                ]);
+                self.layout(&layout, 0);
+            }
+            Statement::StaticAssert(assert) => {
+                let layout = Layout::Concat(vec![
+                    text("static_assert("),
+                    expression(&assert.expression),
+                    text(format!(", {})", assert.message)),
+                ]);
+                self.layout(&layout, 0);
+            }
+            Statement::Enum(enum_) => {
+                self.doc(&enum_.doc);
+                let variants = enum_
+                    .variants
+                    .iter()
+                    .map(|variant| match &variant.discriminant {
+                        Some(discriminant) => Layout::Concat(vec![
+                            text(format!("{} = ", variant.ident)),
+                            expression(&discriminant.expression),
+                        ]),
+                        None => text(&variant.ident),
+                    })
+                    .collect();
+                let layout = Layout::List(List {
+                    open: format!("{}enum {} {{", pub_(&enum_.pub_), enum_.ident),
+                    items: variants,
+                    close: "}".to_string(),
+                    commas: false,
+                    padded: true,
+                    style: Style::Block,
+                });
+                self.layout(&layout, 0);
+            }
+            Statement::TypeAlias(alias) => {
+                self.doc(&alias.doc);
+                let layout = Layout::Concat(vec![
+                    text(format!("{}type {} = ", pub_(&alias.pub_), alias.ident)),
+                    type_(&alias.inner),
+                ]);
+                self.layout(&layout, 0);
+            }
+            Statement::Let(let_) => {
+                let layout = Layout::Concat(vec![
+                    text("let "),
+                    field(&let_.field),
+                    text(" = "),
+                    expression(&let_.expression),
+                ]);
+                self.layout(&layout, 0);
+            }
+            Statement::LetTuple(let_) => {
+                let fields = let_.fields.iter().map(field).collect();
+                let layout = Layout::Concat(vec![
+                    Layout::List(List {
+                        open: "let (".to_string(),
+                        items: fields,
+                        close: ")".to_string(),
+                        commas: false,
+                        padded: false,
+                        style: Style::Hanging,
+                    }),
+                    text(" = "),
+                    expression(&let_.expression),
+                ]);
+                self.layout(&layout, 0);
+            }
+            Statement::For(for_) => {
+                let header = Layout::Concat(vec![
+                    text(format!("{}for ", label(&for_.label))),
+                    field(&for_.field),
+                    text(" in "),
+                    range(&for_.range),
+                ]);
+                self.header_block(&header, &for_.inner);
+            }
+            Statement::Loop(loop_) => {
+                self.write(&format!("{}loop ", label(&loop_.label)));
+                self.block(&loop_.inner);
+            }
+            Statement::While(while_) => {
+                let header = Layout::Concat(vec![
+                    text(format!("{}while ", label(&while_.label))),
+                    expression(&while_.expression),
+                ]);
+                self.header_block(&header, &while_.inner);
+            }
+            Statement::Match(match_) => {
+                let header = Layout::Concat(vec![text("match "), expression(&match_.expression)]);
+                self.layout(&header, 2);
+                if match_.arms.is_empty() && match_.else_.is_none() {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" {");
+                self.newline();
+                self.level += 1;
+                for arm in &match_.arms {
+                    self.indent(self.level);
+                    let pattern = match &arm.pattern {
+                        Pattern::Expression(expr) => expression(expr),
+                        Pattern::Range(range_) => range(range_),
+                    };
+                    self.header_block(&pattern, &arm.inner);
+                    self.newline();
+                }
+                if let Some(else_) = &match_.else_ {
+                    self.indent(self.level);
+                    self.write("else ");
+                    self.block(&else_.inner);
+                    self.newline();
+                }
+                self.level -= 1;
+                self.indent(self.level);
+                self.write("}");
+            }
+            Statement::Continue(continue_) => match &continue_.label {
+                Some(label) => self.write(&format!("continue {}", label)),
+                None => self.write("continue"),
+            },
+            Statement::Break(break_) => match &break_.label {
+                Some(label) => self.write(&format!("break {}", label)),
+                None => self.write("break"),
+            },
+            Statement::Inline(inline) => self.layout(&expression(&inline.inner), 0),
+            Statement::Fn(fn_) => self.fn_(fn_),
+            Statement::Return(return_) => match &return_.expression {
+                Some(expr) => {
+                    let layout = Layout::Concat(vec![text("return "), expression(expr)]);
+                    self.layout(&layout, 0);
+                }
+                None => self.write("return"),
+            },
+            Statement::Defer(defer) => {
+                self.write("defer ");
+                self.block(&defer.inner);
+            }
+            Statement::Error(error) => self.write(&tokens(&error.tokens)),
+        }
+    }
+
+    fn fn_(&mut self, fn_: &crate::ast::Fn<'_>) {
+        self.doc(&fn_.doc);
+        for attribute in &fn_.attributes {
+            self.layout(&attribute_(attribute), 0);
+            self.newline();
+            self.indent(self.level);
+        }
+        let mut header = pub_(&fn_.pub_).to_string();
+        if fn_.const_.is_some() {
+            header.push_str("const ");
+        }
+        header.push_str("fn");
+        if let Some(interrupt) = &fn_.interrupt {
+            header.push_str(&format!("@{}", interrupt.ident));
+        }
+        header.push_str(&format!(" {}", fn_.ident));
+        let mut pieces = vec![text(header)];
+        if let Some(generics) = &fn_.generics {
+            let commas = generics.params.iter().any(|param| param.comma.is_some());
+            let params = generics
+                .params
+                .iter()
+                .map(|param| Layout::Concat(vec![text("const "), field(&param.field)]))
+                .collect();
+            pieces.push(Layout::List(List {
+                open: "<".to_string(),
+                items: params,
+                close: ">".to_string(),
+                commas,
+                padded: false,
+                style: Style::Hanging,
+            }));
+        }
+        if let Some(fn_arg) = &fn_.fn_arg {
+            pieces.push(Layout::List(List {
+                open: "(".to_string(),
+                items: fn_arg.inner.iter().map(field).collect(),
+                close: ")".to_string(),
+                commas: false,
+                padded: false,
+                style: Style::Hanging,
+            }));
+        }
+        if let Some(fn_return) = &fn_.fn_return {
+            pieces.push(text(":"));
+            pieces.push(type_(&fn_return.type_));
+        }
+        self.header_block(&Layout::Concat(pieces), &fn_.inner);
+    }
+
+    // tokens of a macro body, keeping the lines they were written on.
+    fn macro_body(&mut self, body: &[Token<'_>]) {
+        if body.is_empty() {
+            self.write("{}");
+            return;
+        }
+        self.write("{");
+        let level = self.level;
+        let mut depth = 0;
+        let mut line: Vec<&Token<'_>> = Vec::new();
+        let flush = |formatter: &mut Self, line: &mut Vec<&Token<'_>>, depth: &mut usize| {
+            // a line beginning with closing delimiters is indented like the
+            // line that opened them
+            let closing = line
+                .iter()
+                .take_while(|token| matches!(token, Token::RightBracket(_)))
+                .count();
+            formatter.newline();
+            formatter.indent(level + 1 + depth.saturating_sub(closing));
+            for token in line.iter() {
+                match token {
+                    Token::LeftBracket(_) => *depth += 1,
+                    Token::RightBracket(_) => *depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            let tokens: Vec<_> = line.drain(..).cloned().collect();
+            formatter.write(&self::tokens(&tokens));
+        };
+        for token in body {
+            if matches!(line.last(), Some(last) if last.span().max[0] != token.span().min[0]) {
+                flush(self, &mut line, &mut depth);
+            }
+            line.push(token);
+        }
+        flush(self, &mut line, &mut depth);
+        self.newline();
+        self.indent(level);
+        self.write("}");
+    }
+}
+
+// first and last lines of a statement, including its doc comment.
+fn lines(statement: &Statement<'_>) -> (usize, usize) {
+    let span = statement.span();
+    let doc = match statement {
+        Statement::Static(static_) => static_.doc.as_ref(),
+        Statement::Const(const_) => const_.doc.as_ref(),
+        Statement::Enum(enum_) => enum_.doc.as_ref(),
+        Statement::TypeAlias(alias) => alias.doc.as_ref(),
+        Statement::Fn(fn_) => fn_.doc.as_ref(),
+        _ => None,
+    };
+    let first = doc.map_or(span.min[0], |doc| doc.span.min[0]);
+    let last = match statement {
+        // the span of a return statement is the `return` token
+        Statement::Return(return_) => match &return_.expression {
+            Some(expression) => expression.span().max[0],
+            None => span.max[0],
+        },
+        _ => span.max[0],
+    };
+    (first, last)
+}
+
+fn pub_(pub_: &Option<lex::Pub<'_>>) -> &'static str {
+    if pub_.is_some() {
+        "pub "
+    } else {
+        ""
+    }
+}
+
+fn label(label: &Option<crate::ast::LoopLabel<'_>>) -> String {
+    match label {
+        Some(label) => format!("{}: ", label.label),
+        None => String::new(),
+    }
+}
+
+// tokens as written, separated by a space unless adjacent in the source.
+fn tokens(tokens: &[Token<'_>]) -> String {
+    let mut text = String::new();
+    let mut prev: Option<&Token<'_>> = None;
+    for token in tokens {
+        if matches!(prev, Some(prev) if prev.span().max != token.span().min) {
+            text.push(' ');
+        }
+        text.push_str(&token.to_string());
+        prev = Some(token);
+    }
+    text
+}
+
+// list of the items of a `Separated`, to be completed with its delimiters.
+fn separated<'s, T>(list: &'s Separated<'_, T>, item: impl FnMut(&'s T) -> Layout) -> List {
+    List {
+        open: String::new(),
+        items: list.items.iter().map(item).collect(),
+        close: String::new(),
+        commas: !list.commas.is_empty(),
+        padded: false,
+        style: Style::Block,
+    }
+}
+
+fn range(range: &Range<'_>) -> Layout {
+    let mut op = range.dot_dot.to_string();
+    if range.eq.is_some() {
+        op.push('=');
+    }
+    if range.plus.is_some() {
+        op.push('+');
+    }
+    Layout::Concat(vec![
+        expression(&range.left),
+        text(op),
+        expression(&range.right),
+    ])
+}
+
+fn field(field: &Field<'_>) -> Layout {
+    let mut pieces = vec![text(format!("{}:", field.ident)), type_(&field.type_)];
+    if let Some(bits) = &field.bits {
+        let commas = bits.fields.iter().any(|field| field.comma.is_some());
+        let fields = bits
+            .fields
+            .iter()
+            .map(|field| {
+                Layout::Concat(vec![
+                    text(format!("{}:", field.ident)),
+                    expression(&field.width),
+                ])
+            })
+            .collect();
+        pieces.push(text(" "));
+        pieces.push(Layout::List(List {
+            open: ".{".to_string(),
+            items: fields,
+            close: "}".to_string(),
+            commas,
+            padded: true,
+            style: Style::Block,
+        }));
+    }
+    Layout::Concat(pieces)
+}
+
+fn attribute_(attribute: &types::Attribute<'_>) -> Layout {
+    match &attribute.expression {
+        Some(argument) => Layout::Concat(vec![
+            text(format!("#[{}(", attribute.ident)),
+            expression(argument),
+            text(")]"),
+        ]),
+        None => text(format!("#[{}]", attribute.ident)),
+    }
+}
+
+// struct or union type.
+fn aggregate(
+    attributes: &[types::Attribute<'_>],
+    keyword: impl Display,
+    fields: &Separated<'_, Member<'_>>,
+) -> Layout {
+    let mut pieces = Vec::new();
+    for attribute in attributes {
+        pieces.push(attribute_(attribute));
+        pieces.push(text(" "));
+    }
+    let members = separated(fields, |member| match member {
+        Member::Field(field_) => field(field_),
+        Member::Struct(struct_) => {
+            aggregate(&struct_.attributes, &struct_.struct_, &struct_.fields)
+        }
+        Member::Union(union) => aggregate(&union.attributes, &union.union, &union.fields),
+    });
+    pieces.push(Layout::List(List {
+        open: format!("{} {{", keyword),
+        close: "}".to_string(),
+        padded: true,
+        ..members
+    }));
+    Layout::Concat(pieces)
+}
+
+fn type_(type_: &Type<'_>) -> Layout {
+    match type_ {
+        Type::U8(token) => text(token),
+        Type::I8(token) => text(token),
+        Type::U16(token) => text(token),
+        Type::I16(token) => text(token),
+        Type::Bool(token) => text(token),
+        Type::Fixed(token) => text(token),
+        Type::Array(array) => Layout::Concat(vec![
+            text("["),
+            self::type_(&array.type_),
+            text(if array.semi_colon.is_some() {
+                "; "
+            } else {
+                " "
+            }),
+            expression(&array.len),
+            text("]"),
+        ]),
+        Type::Struct(struct_) => aggregate(&struct_.attributes, &struct_.struct_, &struct_.fields),
+        Type::Union(union) => aggregate(&union.attributes, &union.union, &union.fields),
+        Type::Pointer(pointer) => Layout::Concat(vec![text("&"), self::type_(&pointer.type_)]),
+        Type::Fn(fn_) => {
+            let mut pieces = vec![text("fn")];
+            if let Some(args) = &fn_.args {
+                pieces.push(Layout::List(List {
+                    open: "(".to_string(),
+                    items: args.inner.iter().map(self::type_).collect(),
+                    close: ")".to_string(),
+                    commas: false,
+                    padded: false,
+                    style: Style::Hanging,
+                }));
+            }
+            if let Some(fn_return) = &fn_.fn_return {
+                pieces.push(text(":"));
+                pieces.push(self::type_(&fn_return.type_));
+            }
+            Layout::Concat(pieces)
+        }
+        Type::Tuple(tuple) => Layout::List(List {
+            open: "(".to_string(),
+            items: tuple.inner.iter().map(self::type_).collect(),
+            close: ")".to_string(),
+            commas: false,
+            padded: false,
+            style: Style::Hanging,
+        }),
+        Type::Path(path) => text(path_(path)),
+    }
+}
+
+fn path_(path: &crate::ast::Path<'_>) -> String {
+    let idents: Vec<_> = path.iter().map(|ident| ident.to_string()).collect();
+    idents.join("::")
+}
+
+// prefix (`(<op> <operands>)`) or infix (`<operand> <op> <operand>`) node.
+fn node<I>(
+    node: &LispNode<'_, I>,
+    op: impl Display,
+    infix: bool,
+    operands: Vec<Layout>,
+    infix_layout: impl FnOnce(Vec<Layout>) -> Vec<Layout>,
+) -> Layout {
+    if !infix {
+        return Layout::List(List {
+            open: format!("({}", op),
+            items: operands,
+            close: ")".to_string(),
+            commas: false,
+            padded: true,
+            style: Style::Hanging,
+        });
+    }
+    let mut pieces = infix_layout(operands);
+    if node.left_par.is_some() {
+        pieces.insert(0, text("("));
+        pieces.push(text(")"));
+    }
+    Layout::Concat(pieces)
+}
+
+fn binary<I>(
+    lisp: &LispNode<'_, I>,
+    op: &(impl Display + Spanned),
+    left: &Expression<'_>,
+    right: &Expression<'_>,
+) -> Layout {
+    // the operator of a prefix node precedes its operands
+    let infix = left.span().min < op.span().min;
+    let operands = vec![expression(left), expression(right)];
+    node(lisp, op, infix, operands, |mut operands| {
+        let right = operands.pop().unwrap();
+        let left = operands.pop().unwrap();
+        vec![left, text(format!(" {} ", op)), right]
+    })
+}
+
+// unary operator. `--` is a token of its own, so `-` can't be followed by `-`.
+fn unary(op: impl Display, inner: &Expression<'_>) -> Layout {
+    let inner = expression(inner);
+    let op = op.to_string();
+    if op == "-" && inner.flat().starts_with('-') {
+        Layout::Concat(vec![text("- "), inner])
+    } else {
+        Layout::Concat(vec![text(op), inner])
+    }
+}
+
+fn expression(expression: &Expression<'_>) -> Layout {
+    macro_rules! binary {
+        ($node:expr, $op:ident) => {
+            binary(
+                $node,
+                &$node.inner.$op,
+                &$node.inner.left,
+                &$node.inner.right,
+            )
+        };
+    }
+
+    match expression {
+        Expression::Path(path) => text(path_(path)),
+        Expression::Lit(lit) => text(lit),
+        Expression::Array(array) => Layout::List(List {
+            open: "[".to_string(),
+            close: "]".to_string(),
+            style: Style::Fill,
+            ..separated(&array.inner, self::expression)
+        }),
+        Expression::StructLit(lit) => Layout::List(List {
+            open: "{".to_string(),
+            close: "}".to_string(),
+            padded: true,
+            ..separated(&lit.fields, |init| {
+                Layout::Concat(vec![
+                    text(format!("{}:", init.ident)),
+                    self::expression(&init.expression),
+                ])
+            })
+        }),
+        Expression::Minus(minus) => unary(&minus.minus, &minus.inner),
+        Expression::AddressOf(address_of) => unary(&address_of.at, &address_of.inner),
+        Expression::Deref(deref) => unary(&deref.star, &deref.inner),
+        Expression::Not(not) => unary(&not.tilde, &not.inner),
+        Expression::Increment(lisp) => {
+            let e::Increment { plus_plus, inner } = &lisp.inner;
+            let infix = inner.span().min < plus_plus.span().min;
+            node(
+                lisp,
+                plus_plus,
+                infix,
+                vec![self::expression(inner)],
+                |mut operands| {
+                    operands.push(text(plus_plus));
+                    operands
+                },
+            )
+        }
+        Expression::Decrement(lisp) => {
+            let e::Decrement { minus_minus, inner } = &lisp.inner;
+            let infix = inner.span().min < minus_minus.span().min;
+            node(
+                lisp,
+                minus_minus,
+                infix,
+                vec![self::expression(inner)],
+                |mut operands| {
+                    operands.push(text(minus_minus));
+                    operands
+                },
+            )
+        }
+        Expression::Conditional(lisp) => {
+            let conditional = &lisp.inner;
+            let operands = vec![
+                self::expression(&conditional.condition),
+                self::expression(&conditional.then),
+                self::expression(&conditional.else_),
+            ];
+            node(
+                lisp,
+                "if",
+                conditional.if_.is_none(),
+                operands,
+                |mut operands| {
+                    let else_ = operands.pop().unwrap();
+                    let then = operands.pop().unwrap();
+                    let condition = operands.pop().unwrap();
+                    vec![condition, text(" ? "), then, text(" : "), else_]
+                },
+            )
+        }
+        Expression::Cast(lisp) => {
+            let e::Cast {
+                as_,
+                inner,
+                type_: cast,
+            } = &lisp.inner;
+            let infix = inner.span().min < as_.span().min;
+            let operands = vec![self::expression(inner), type_(cast)];
+            node(lisp, as_, infix, operands, |mut operands| {
+                let cast = operands.pop().unwrap();
+                let inner = operands.pop().unwrap();
+                vec![inner, text(format!(" {} ", as_)), cast]
+            })
+        }
+        Expression::SizeOf(lisp) => {
+            let operand = match &lisp.inner.inner {
+                SizeOfOperand::Type(operand) => type_(operand),
+                SizeOfOperand::Expression(operand) => self::expression(operand),
+            };
+            node(lisp, &lisp.inner.sizeof, false, vec![operand], |operands| {
+                operands
+            })
+        }
+        Expression::Add(lisp) => binary!(lisp, plus),
+        Expression::Sub(lisp) => binary!(lisp, minus),
+        Expression::Mul(lisp) => binary!(lisp, star),
+        Expression::Div(lisp) => binary!(lisp, slash),
+        Expression::And(lisp) => binary!(lisp, ampersand),
+        Expression::Or(lisp) => binary!(lisp, pipe),
+        Expression::Xor(lisp) => binary!(lisp, caret),
+        Expression::Assign(lisp) => binary!(lisp, assign),
+        Expression::PlusAssign(lisp) => binary!(lisp, plus_assign),
+        Expression::MinusAssign(lisp) => binary!(lisp, minus_assign),
+        Expression::MulAssign(lisp) => binary!(lisp, star_assign),
+        Expression::DivAssign(lisp) => binary!(lisp, slash_assign),
+        Expression::AndAssign(lisp) => binary!(lisp, ampersand_assign),
+        Expression::OrAssign(lisp) => binary!(lisp, pipe_assign),
+        Expression::XorAssign(lisp) => binary!(lisp, caret_assign),
+        Expression::LeftShift(lisp) => binary!(lisp, less_less),
+        Expression::RightShift(lisp) => binary!(lisp, great_great),
+        Expression::Eq(lisp) => binary!(lisp, eq),
+        Expression::NotEq(lisp) => binary!(lisp, tilde_eq),
+        Expression::LessEq(lisp) => binary!(lisp, less_eq),
+        Expression::GreaterEq(lisp) => binary!(lisp, greater_eq),
+        Expression::Less(lisp) => binary!(lisp, less),
+        Expression::Greater(lisp) => binary!(lisp, greater),
+        Expression::LogicalAnd(lisp) => binary!(lisp, ampersand_ampersand),
+        Expression::LogicalOr(lisp) => binary!(lisp, pipe_pipe),
+        Expression::Index(lisp) => Layout::Concat(vec![
+            text("(["),
+            self::expression(&lisp.inner.left),
+            text("] "),
+            self::expression(&lisp.inner.right),
+            text(")"),
+        ]),
+        Expression::Call(lisp) => Layout::List(List {
+            open: format!("({}", self::expression(&lisp.inner.left).flat()),
+            close: ")".to_string(),
+            padded: true,
+            style: Style::Hanging,
+            ..separated(&lisp.inner.args, self::expression)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format, FormatOptions};
+    use crate::ContextBuilder;
+
+    fn fmt(input: &str) -> String {
+        format(&crate::parse(input).unwrap(), FormatOptions::default())
+    }
+
+    fn fmt_width(input: &str, line_width: usize) -> String {
+        let options = FormatOptions {
+            line_width,
+            ..Default::default()
+        };
+        format(&crate::parse(input).unwrap(), options)
+    }
+
+    #[test]
+    fn statements() {
+        let input = "let x:u8 = 0 if x { (= x 1) } else { }\n\
+                     \n\
+                     \n\
+                     loop { while (< x 4) { (++ x) break } }";
+        let output = "let x:u8 = 0\n\
+                      if x {\n    \
+                          (= x 1)\n\
+                      } else {}\n\
+                      \n\
+                      loop {\n    \
+                          while (< x 4) {\n        \
+                              (++ x)\n        \
+                              break\n    \
+                          }\n\
+                      }\n";
+        assert_eq!(output, fmt(input));
+    }
+
+    #[test]
+    fn declarations() {
+        let input = "/// Doc.\n\
+                     pub static@0xff40 volatile  LCDC:u8 .{on:1,off:1}\n\
+                     #[bank(2)] fn@vblank f ( a:u8  b:&u8 ) :u8 { return  a }\n\
+                     enum Dir { Up Down=4 }\n\
+                     static S:#[align(2)] struct{x:u8,y:u8,} = {x:1,y:2}";
+        let output = "/// Doc.\n\
+                      pub static@0xff40 volatile LCDC:u8 .{ on:1, off:1 }\n\
+                      #[bank(2)]\n\
+                      fn@vblank f(a:u8 b:&u8):u8 {\n    \
+                          return a\n\
+                      }\n\
+                      enum Dir { Up Down = 4 }\n\
+                      static S:#[align(2)] struct { x:u8, y:u8 } = { x:1, y:2 }\n";
+        assert_eq!(output, fmt(input));
+    }
+
+    #[test]
+    fn attributes() {
+        let input = "#[inline] fn f {}\n#[inline(never)] #[bank(1)] fn g {}";
+        let output = "#[inline]\n\
+                      fn f {}\n\
+                      #[inline(never)]\n\
+                      #[bank(1)]\n\
+                      fn g {}\n";
+        assert_eq!(output, fmt(input));
+    }
+
+    #[test]
+    fn line_width() {
+        let input = "static A:[u8 12] = [1 2 3 4 5 6 7 8 9 10 11 12]\n\
+                     (= A (+ (* 1000 1000) (* 2000 2000)))\n\
+                     static P:struct { x:u8 y:u8 } = { x:(+ 1 2) y:3 }";
+        let output = "static A:[u8 12] = [\n    \
+                          1 2 3 4 5 6 7 8 9 10\n    \
+                          11 12\n\
+                      ]\n\
+                      (= A\n    \
+                          (+ (* 1000 1000)\n        \
+                              (* 2000 2000)))\n\
+                      static P:struct {\n    \
+                          x:u8\n    \
+                          y:u8\n\
+                      } = { x:(+ 1 2) y:3 }\n";
+        assert_eq!(output, fmt_width(input, 24));
+    }
+
+    #[test]
+    fn infix() {
+        let mut context = ContextBuilder::default().infix(true).build();
+        let input = "let x:u8 = 0\nx = (x+1)*2\nx++\nx = x > 2 ? -x as u8 : x";
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        let output = "let x:u8 = 0\nx = (x + 1) * 2\nx++\nx = x > 2 ? -x as u8 : x\n";
+        assert_eq!(output, format(&ast, FormatOptions::default()));
+    }
+
+    #[test]
+    fn macros() {
+        let input = "macro inc(a) { (+= a 1)\n (+= a 1) }\nstatic X:u8\ninc!(X)";
+        let output = "macro inc(a) {\n    \
+                          (+= a 1)\n    \
+                          (+= a 1)\n\
+                      }\n\
+                      static X:u8\n\
+                      inc!(X)\n";
+        assert_eq!(output, fmt(input));
+    }
+}
@@ -0,0 +1,157 @@
+//! Type of the node at a location, for hover tooltips.
+use crate::{
+    ast::Ast,
+    lex::span::{LineIndex, Span, Spanned},
+    resolve::{self, Resolution},
+};
+
+/// Type and declaration of the node at a location.
+///
+/// Returned by [`type_at`](type_at).
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeInfo {
+    /// Span of the expression or identifier at the location.
+    pub span: Span,
+
+    /// Type of the node (`u8`, `&[u8 4]`, `fn(u8):bool`, `integer constant`,
+    /// ...), or `None` if it isn't known.
+    pub type_: Option<String>,
+
+    /// Declaration of the identifier at the location, if it resolves to one.
+    pub declaration: Option<Resolution>,
+}
+
+/// Type and declaration of the expression or identifier at a byte offset of
+/// the source code of a program.
+///
+/// The type is the one of the innermost expression at the offset, as inferred
+/// by the [type checker](crate::check), or the declared type of the
+/// identifier of a declaration. Paths to consts have the declared type of the
+/// const, rather than the one of their value. The declaration is looked up
+/// with the [name resolution](crate::resolve) of the program.
+///
+/// The `Ast` must have been parsed from `input`. Returns `None` if there is no
+/// expression nor identifier at the offset.
+///
+/// ```
+/// use parser::{ast::type_at, resolve::SymbolKind};
+///
+/// let input = "static FOO:[u8 4]\nfn f(x:u8):u8 { return (+ ([1] FOO) x) }";
+/// let ast = parser::parse(input).unwrap();
+///
+/// let info = type_at(&ast, input, input.find("FOO)").unwrap()).unwrap();
+/// assert_eq!(Some("[u8 4]".to_string()), info.type_);
+/// assert_eq!(SymbolKind::Static, info.declaration.unwrap().kind);
+///
+/// let info = type_at(&ast, input, input.find("(+").unwrap()).unwrap();
+/// assert_eq!(Some("u8".to_string()), info.type_);
+/// assert_eq!(None, info.declaration);
+/// ```
+pub fn type_at(ast: &Ast<'_>, input: &str, offset: usize) -> Option<TypeInfo> {
+    let mut offset = offset.min(input.len());
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let source = ast.eof.span().source;
+    let position = LineIndex::new(input).position(offset);
+    let resolutions = resolve::resolve(ast);
+    let resolved = resolutions.at(source, position);
+    match crate::check::type_at(ast, source, position) {
+        Some((span, type_)) => Some(TypeInfo {
+            span,
+            type_,
+            declaration: resolved
+                .filter(|(use_, _)| *use_ == span)
+                .map(|(_, resolution)| *resolution),
+        }),
+        // names the type checker doesn't visit, such as the ones in types
+        None => resolved.map(|(span, resolution)| TypeInfo {
+            span,
+            type_: None,
+            declaration: Some(*resolution),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::type_at;
+    use crate::resolve::SymbolKind;
+
+    // span, type, and kind of the declaration at the `|` of the input.
+    fn hover(input: &str) -> Option<([usize; 2], Option<String>, Option<SymbolKind>)> {
+        let offset = input.find('|').unwrap();
+        let input = input.replacen('|', "", 1);
+        let ast = crate::parse(&input).unwrap();
+        type_at(&ast, &input, offset).map(|info| {
+            (
+                info.span.min,
+                info.type_,
+                info.declaration.map(|resolution| resolution.kind),
+            )
+        })
+    }
+
+    #[test]
+    fn expressions() {
+        let input = "static A:u16\nstatic B:&u8\n(= A (+ A (as |@B u16)))";
+        assert_eq!(
+            Some(([2, 14], Some("&&u8".to_string()), None)),
+            hover(input)
+        );
+        let input = "static A:u16\nstatic B:&u8\n(= A (+ A (as @|B u16)))";
+        assert_eq!(
+            Some(([2, 15], Some("&u8".to_string()), Some(SymbolKind::Static))),
+            hover(input)
+        );
+        let input = "static A:u16\n(= A |(+ A 1))";
+        assert_eq!(Some(([1, 5], Some("u16".to_string()), None)), hover(input));
+        let input = "fn f(x:u8):bool { return (== x 0) }\n(|f 1)";
+        assert_eq!(
+            Some((
+                [1, 1],
+                Some("fn(u8):bool".to_string()),
+                Some(SymbolKind::Function)
+            )),
+            hover(input)
+        );
+        assert_eq!(None, hover("static A:u8\n|"));
+    }
+
+    #[test]
+    fn constants() {
+        let input = "const N:u8 = 4\nstatic A:u8 = (+ |N 1)";
+        assert_eq!(
+            Some(([1, 17], Some("u8".to_string()), Some(SymbolKind::Const))),
+            hover(input)
+        );
+        let input = "const N:u8 = 4\nstatic A:u8 = |(+ N 1)";
+        assert_eq!(
+            Some(([1, 14], Some("integer constant".to_string()), None)),
+            hover(input)
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let input = "static |A:[u8 4]";
+        assert_eq!(
+            Some(([0, 7], Some("[u8 4]".to_string()), None)),
+            hover(input)
+        );
+        let input = "fn |f(x:&u8) { }";
+        assert_eq!(
+            Some(([0, 3], Some("fn(&u8)".to_string()), None)),
+            hover(input)
+        );
+        let input = "fn f(|x:&u8) { }";
+        assert_eq!(Some(([0, 5], Some("&u8".to_string()), None)), hover(input));
+        // names of types aren't checked, but are resolved
+        let input = "type T = u8\nstatic A:|T";
+        assert_eq!(
+            Some(([1, 9], None, Some(SymbolKind::TypeAlias))),
+            hover(input)
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    ast::{Context, Grammar, Statement},
+    lex,
+    lex::Token,
+    Error, Tokens,
+};
+use std::iter::Peekable;
+
+span!(Import { import, path });
+
+/// `import "<path>"`
+///
+/// Imports the statements of another source file into a module named after
+/// the file (`import "gfx/sprites.ggb"` defines the `sprites` module). The
+/// source of the file is provided by the resolver of the
+/// [`ContextBuilder`](crate::ContextBuilder::resolver).
+///
+/// The spans of the imported statements refer to the imported file, and carry
+/// its [`SourceId`](crate::lex::span::SourceId).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Import<'a> {
+    /// `import` token.
+    pub import: lex::Import<'a>,
+
+    /// Path of the imported file (string literal token).
+    pub path: lex::Lit<'a>,
+
+    /// Statements of the imported file.
+    pub inner: Vec<Statement<'a>>,
+}
+
+impl Import<'_> {
+    /// Name of the module defined by the import (the file stem of its path).
+    pub fn name(&self) -> String {
+        module_name(&self.path).expect("Validated import path")
+    }
+}
+
+// module name of an import path, if its file stem is a valid identifier.
+fn module_name(path: &lex::Lit<'_>) -> Option<String> {
+    let path = path.str_value()?;
+    let file = path.rsplit('/').next()?;
+    let stem = file.split('.').next()?;
+    let mut chars = stem.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return None,
+    }
+    if chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(stem.to_string())
+    } else {
+        None
+    }
+}
+
+impl<'a> Grammar<'a> for Import<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let import = Grammar::parse(ctx, tokens)?;
+        let path: lex::Lit<'a> = Grammar::parse(ctx, tokens)?;
+        if module_name(&path).is_none() {
+            return Err(Error::Expected {
+                expected: "import path",
+                found: Token::Lit(path),
+            });
+        }
+        let inner = ctx.import(&path)?;
+        Ok(Self {
+            import,
+            path,
+            inner,
+        })
+    }
+}
+
+// the imported statements belong to another file, so they are left untouched
+impl crate::incremental::Remap for Import<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.import.remap(f);
+        self.path.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Import<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.import.own(f);
+        self.path.own(f);
+        self.inner.own(f);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::{Statement, Visitor},
+        lex::Ident,
+        ContextBuilder, Error,
+    };
+
+    fn resolve(path: &str) -> Option<&'static str> {
+        match path {
+            "sprites.ggb" => Some("const COUNT:u8 = 40\nstatic OAM:[u8 COUNT]"),
+            "gfx/tiles.ggb" => Some("import \"sprites.ggb\"\nconst SIZE:u8 = 16"),
+            "cycle.ggb" => Some("import \"cycle.ggb\""),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn import() {
+        let mut context = ContextBuilder::default().resolver(resolve).build();
+        let input = "import \"gfx/tiles.ggb\"\nstatic TILES:[u8 tiles::SIZE]";
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        match &ast.inner[0] {
+            Statement::Import(import) => {
+                assert_eq!("tiles", import.name());
+                assert_eq!(2, import.inner.len());
+                assert!(matches!(import.inner[0], Statement::Import(_)));
+            }
+            _ => panic!(),
+        }
+        assert_eq!(Some(40), context.const_value("tiles::sprites::COUNT"));
+        assert_eq!(Some(16), context.const_value("tiles::SIZE"));
+        assert_eq!(None, context.const_value("SIZE"));
+    }
+
+    #[test]
+    fn visit() {
+        struct Idents(Vec<String>);
+
+        impl<'a> Visitor<'a> for Idents {
+            fn visit_ident(&mut self, node: &Ident<'a>) {
+                self.0.push(node.to_string());
+            }
+        }
+
+        let mut context = ContextBuilder::default().resolver(resolve).build();
+        let ast = crate::parse_with_context("import \"sprites.ggb\"", &mut context).unwrap();
+        let mut idents = Idents(Vec::new());
+        idents.visit_ast(&ast);
+        assert_eq!(vec!["COUNT", "OAM", "COUNT"], idents.0);
+    }
+
+    #[test]
+    fn unresolved() {
+        let mut context = ContextBuilder::default().resolver(resolve).build();
+        let error = crate::parse_with_context("import \"foo.ggb\"", &mut context).unwrap_err();
+        assert!(matches!(error, Error::UnresolvedImport(_)));
+        assert!(matches!(
+            crate::parse("import \"sprites.ggb\""),
+            Err(Error::UnresolvedImport(_))
+        ));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut context = ContextBuilder::default().resolver(resolve).build();
+        let error = crate::parse_with_context("import \"cycle.ggb\"", &mut context).unwrap_err();
+        assert!(matches!(error, Error::CyclicImport(_)));
+    }
+
+    #[test]
+    fn invalid_path() {
+        assert!(matches!(
+            crate::parse("import \"0.ggb\""),
+            Err(Error::Expected { .. })
+        ));
+        assert!(matches!(
+            crate::parse("import 42"),
+            Err(Error::Expected { .. })
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{
+    ast::{Context, Grammar, Separated, Statement},
+    lex,
+    lex::{
+        span::{Span, Spanned},
+        Token,
+    },
+    Error, Tokens,
+};
+use std::iter::Peekable;
+
+span!(Macro {
+    macro_,
+    right_bracket
+});
+span!(MacroCall { ident, right_par });
+
+/// `macro <ident>(<params>) { <tokens> }`
+///
+/// Declarative macro. The body is kept as a sequence of tokens, which is
+/// expanded into statements wherever the macro is invoked (`<ident>!(<args>)`)
+/// after replacing the parameters with the tokens of the arguments. Macros can
+/// be invoked from anywhere after their definition.
+///
+/// The expansion is a block of its own, so the declarations of the body aren't
+/// visible from the call site once the expansion ends. The spans
+/// of the expanded tokens point to the definition, and the ones of the
+/// arguments to the call site. Errors in the expansion are reported as an
+/// [`Error::Macro`](Error::Macro), which also locates the invocation.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Macro<'a> {
+    /// `macro` token.
+    pub macro_: lex::Macro<'a>,
+
+    /// Macro identifier.
+    pub ident: lex::Ident<'a>,
+
+    /// `(` token.
+    pub left_par: lex::LeftPar<'a>,
+
+    /// Parameter identifiers.
+    pub params: Separated<'a, lex::Ident<'a>>,
+
+    /// `)` token.
+    pub right_par: lex::RightPar<'a>,
+
+    /// `{` token.
+    pub left_bracket: lex::LeftBracket<'a>,
+
+    /// Tokens of the body.
+    pub body: Vec<Token<'a>>,
+
+    /// `}` token.
+    pub right_bracket: lex::RightBracket<'a>,
+}
+
+impl<'a> Grammar<'a> for Macro<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let macro_ = Grammar::parse(ctx, tokens)?;
+        let ident = Grammar::parse(ctx, tokens)?;
+        let left_par = Grammar::parse(ctx, tokens)?;
+        let params = Grammar::parse(ctx, tokens)?;
+        let right_par = Grammar::parse(ctx, tokens)?;
+        let left_bracket = Grammar::parse(ctx, tokens)?;
+        let body = until(tokens, |token, depth| {
+            depth == 0 && matches!(token, Token::RightBracket(_))
+        })?;
+        Ok(Self {
+            macro_,
+            ident,
+            left_par,
+            params,
+            right_par,
+            left_bracket,
+            body,
+            right_bracket: Grammar::parse(ctx, tokens)?,
+        })
+    }
+}
+
+impl crate::incremental::Remap for Macro<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.macro_.remap(f);
+        self.ident.remap(f);
+        self.left_par.remap(f);
+        self.params.remap(f);
+        self.right_par.remap(f);
+        self.left_bracket.remap(f);
+        self.body.remap(f);
+        self.right_bracket.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Macro<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.macro_.own(f);
+        self.ident.own(f);
+        self.left_par.own(f);
+        self.params.own(f);
+        self.right_par.own(f);
+        self.left_bracket.own(f);
+        self.body.own(f);
+        self.right_bracket.own(f);
+    }
+}
+
+/// `<ident>!(<tokens>, ...)`
+///
+/// Invocation of a [`Macro`](Macro). The arguments are sequences of tokens,
+/// separated by `,`.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct MacroCall<'a> {
+    /// Macro identifier.
+    pub ident: lex::Ident<'a>,
+
+    /// `!` token.
+    pub bang: lex::Bang<'a>,
+
+    /// `(` token.
+    pub left_par: lex::LeftPar<'a>,
+
+    /// Tokens of the arguments.
+    pub args: Vec<Vec<Token<'a>>>,
+
+    /// `,` tokens.
+    pub commas: Vec<lex::Comma<'a>>,
+
+    /// `)` token.
+    pub right_par: lex::RightPar<'a>,
+
+    /// Statements of the expansion.
+    pub inner: Vec<Statement<'a>>,
+}
+
+impl<'a> Grammar<'a> for MacroCall<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let ident: lex::Ident<'a> = Grammar::parse(ctx, tokens)?;
+        let bang = Grammar::parse(ctx, tokens)?;
+        let left_par = Grammar::parse(ctx, tokens)?;
+        let params = ctx.macro_params(ident.name()).expect("Defined macro");
+        let mut args = Vec::new();
+        let mut commas = Vec::new();
+        if !matches!(tokens.peek(), Some(Ok(Token::RightPar(_)))) {
+            loop {
+                args.push(until(tokens, |token, depth| {
+                    depth == 0 && matches!(token, Token::Comma(_) | Token::RightPar(_))
+                })?);
+                match Grammar::parse(ctx, tokens)? {
+                    Some(comma) if args.len() < params => commas.push(comma),
+                    Some(comma) => {
+                        return Err(Error::Expected {
+                            expected: "`)`",
+                            found: Token::Comma(comma),
+                        })
+                    }
+                    None => break,
+                }
+            }
+        }
+        if args.len() < params {
+            if let Some(Ok(token)) = tokens.peek() {
+                return Err(Error::Expected {
+                    expected: "macro argument",
+                    found: token.clone(),
+                });
+            }
+        }
+        let right_par = Grammar::parse(ctx, tokens)?;
+        let inner = ctx.expand(&ident, &args)?;
+        Ok(Self {
+            ident,
+            bang,
+            left_par,
+            args,
+            commas,
+            right_par,
+            inner,
+        })
+    }
+}
+
+impl crate::incremental::Remap for MacroCall<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.ident.remap(f);
+        self.bang.remap(f);
+        self.left_par.remap(f);
+        self.args.remap(f);
+        self.commas.remap(f);
+        self.right_par.remap(f);
+        // the expanded statements point into the definition of the macro, not
+        // the call. Calls after an edit are expanded again instead.
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for MacroCall<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.ident.own(f);
+        self.bang.own(f);
+        self.left_par.own(f);
+        self.args.own(f);
+        self.commas.own(f);
+        self.right_par.own(f);
+        self.inner.own(f);
+    }
+}
+
+// take the tokens up to the first one (not consumed) for which `end` returns
+// true, given the nesting depth of the brackets, parenthesis, and square
+// brackets it is in.
+pub(crate) fn until<'a, F>(
+    tokens: &mut Peekable<Tokens<'a>>,
+    end: F,
+) -> Result<Vec<Token<'a>>, Error<'a>>
+where
+    F: std::ops::Fn(&Token<'a>, usize) -> bool,
+{
+    let mut taken = Vec::new();
+    let mut depth = 0_usize;
+    loop {
+        match tokens.peek() {
+            Some(Ok(Token::Eof(_))) | None => return Ok(taken),
+            Some(Ok(token)) if end(token, depth) => return Ok(taken),
+            Some(Ok(_)) => {}
+            Some(Err(_)) => return Err(tokens.next().unwrap().unwrap_err()),
+        }
+        let token = tokens.next().unwrap().unwrap();
+        match token {
+            Token::LeftBracket(_) | Token::LeftPar(_) | Token::LeftSquare(_) => depth += 1,
+            Token::RightBracket(_) | Token::RightPar(_) | Token::RightSquare(_) => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+        taken.push(token);
+    }
+}
+
+// end of the expansion of a macro, for errors at the end of its statements.
+pub(crate) fn expansion_end(right_bracket: &lex::RightBracket<'_>) -> Span {
+    let span = right_bracket.span();
+    Span {
+        max: span.min,
+        ..span
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::{Statement, Visitor},
+        lex::{span::Spanned, Ident},
+        Error,
+    };
+
+    #[test]
+    fn expand() {
+        struct Spans(Vec<[[usize; 2]; 2]>);
+
+        impl<'a> Visitor<'a> for Spans {
+            fn visit_ident(&mut self, node: &Ident<'a>) {
+                let span = node.span();
+                self.0.push([span.min, span.max]);
+            }
+        }
+
+        let input = "macro set(a, v) { (= a v) }\n\
+                     static X:u8\n\
+                     set!(X, (+ 1 2))";
+        let ast = crate::parse(input).unwrap();
+        match &ast.inner[2] {
+            Statement::MacroCall(call) => {
+                assert_eq!(2, call.args.len());
+                assert_eq!(1, call.inner.len());
+                // the expanded tokens point to the definition
+                let span = call.inner[0].span();
+                assert_eq!([[0, 18], [0, 25]], [span.min, span.max]);
+            }
+            _ => panic!(),
+        }
+        // the arguments point to the call site
+        let mut spans = Spans(Vec::new());
+        spans.visit_statement(&ast.inner[2]);
+        assert_eq!(vec![[[2, 0], [2, 3]], [[2, 5], [2, 6]]], spans.0);
+    }
+
+    #[test]
+    fn visit() {
+        struct Idents(Vec<String>);
+
+        impl<'a> Visitor<'a> for Idents {
+            fn visit_ident(&mut self, node: &Ident<'a>) {
+                self.0.push(node.to_string());
+            }
+        }
+
+        let input = "macro wait_vblank() { loop { if (== LY 144) { break } } }\n\
+                     wait_vblank!()\n\
+                     wait_vblank!()";
+        let ast = crate::parse(input).unwrap();
+        let mut idents = Idents(Vec::new());
+        idents.visit_ast(&ast);
+        assert_eq!(
+            vec!["wait_vblank", "wait_vblank", "LY", "wait_vblank", "LY"],
+            idents.0
+        );
+    }
+
+    #[test]
+    fn args() {
+        let input = "macro f(a b) { }\n";
+        for call in &["f!(1)", "f!(1, 2, 3)", "f!(1 2)"] {
+            let input = format!("{}{}", input, call);
+            assert!(matches!(crate::parse(&input), Err(Error::Expected { .. })));
+        }
+        assert!(crate::parse(&format!("{}f!([1 2], (g 3 4))", input)).is_ok());
+    }
+
+    #[test]
+    fn error() {
+        let input = "macro f(a) { let x:u8 = a\nlet x:u8 = }\nf!(1)";
+        match crate::parse(input) {
+            Err(error @ Error::Macro { .. }) => {
+                assert_eq!("E0003", error.code());
+                assert_eq!([1, 11], error.span().unwrap().min);
+                assert_eq!([2, 0], error.related().last().unwrap().0.min);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn recursive() {
+        let input = "macro f() { f!() }\nf!()";
+        assert!(matches!(
+            crate::parse(input),
+            Err(Error::Macro { error, .. }) if matches!(*error, Error::RecursiveMacro(_))
+        ));
+        assert_eq!("E0014", crate::parse(input).unwrap_err().code());
+    }
+}
@@ -9,17 +9,6 @@ macro_rules! span {
             }
         }
     };
-    // TODO rework
-    ($ident:ident<I> { $head:ident $(, $tail:ident)* }) => {
-        impl<I> $crate::lex::span::Spanned for $ident<'_, I> {
-            fn span(&self) -> $crate::lex::span::Span {
-                #[allow(unused)]
-                let mut union = self.$head.span();
-                $(union = $crate::lex::span::union(&union, &self.$tail.span());)*
-                union
-            }
-        }
-    }
 }
 
 macro_rules! parse {
@@ -30,6 +19,11 @@ macro_rules! parse {
             $var_name:ident ( $var_type:ty ) ,)*
     }) => {
         $(#[$($meta)+])*
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(bound(deserialize = "'de: 'a"))
+        )]
         pub enum $enum_name<'a> {
             $( $(#[$($var_meta)+])* $var_name( $var_type ) ,)*
         }
@@ -39,6 +33,18 @@ macro_rules! parse {
                 match self { $($enum_name::$var_name(s) => s.span(),)* }
             }
         }
+
+        impl crate::incremental::Remap for $enum_name<'_> {
+            fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+                match self { $($enum_name::$var_name(s) => s.remap(f),)* }
+            }
+        }
+
+        impl<'a> crate::ast::owned::Own<'a> for $enum_name<'a> {
+            fn own(&mut self, f: &mut dyn std::ops::FnMut(&'a str) -> &'a str) {
+                match self { $($enum_name::$var_name(s) => s.own(f),)* }
+            }
+        }
     };
 
     // struct parsing
@@ -49,6 +55,11 @@ macro_rules! parse {
      $({ $($phantom_fields:ident: $phantom_ty:ty,)* })?
     ) => {
         $(#[$($meta)+])*
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(bound(deserialize = "'de: 'a"))
+        )]
         pub struct $ident<'a> {
             $( $(#[$field_meta])* pub $field: $ty, )*
             $( $($phantom_fields: $phantom_ty,)* )?
@@ -62,5 +73,697 @@ macro_rules! parse {
                           $($($phantom_fields: std::marker::PhantomData,)*)? })
             }
         }
+
+        impl crate::incremental::Remap for $ident<'_> {
+            fn remap(&mut self, #[allow(unused)] f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+                $(crate::incremental::Remap::remap(&mut self.$field, f);)*
+            }
+        }
+
+        impl<'a> crate::ast::owned::Own<'a> for $ident<'a> {
+            fn own(&mut self, #[allow(unused)] f: &mut dyn std::ops::FnMut(&'a str) -> &'a str) {
+                $(crate::ast::owned::Own::own(&mut self.$field, f);)*
+            }
+        }
+    };
+}
+
+// Generates the `Visitor` (immutable) and `VisitorMut` (mutable) traits, along
+// with their `walk_*` functions.
+macro_rules! visitor {
+    ($(#[$meta:meta])* $trait:ident $(, $mut:tt)?) => {
+        use crate::{
+            ast::{
+                expression::{self, Expression},
+                types::{self, Type},
+                Ast, BitField, Bits, Break, Const, Continue, Defer, Else, Enum, ErrorNode, Field, Fn, FnArg, FnInterrupt, FnReturn, For, GenericParam, Generics,
+                If, IfConst, IfElse, Import, Inline, Let, LetTuple, Loop, Macro, MacroCall, Match, MatchArm, Memory, Region, Mod, Panic, Path, StaticAssert, Pattern, Range, Return, Scope, Statement,
+                Static, StaticBank, StaticInit, StaticOffset, TypeAlias, Variant, While,
+            },
+            lex,
+        };
+
+        // reference to a node, mutable or not
+        macro_rules! node_ref {
+            ($ty:ty) => { & $($mut)? $ty };
+        }
+        macro_rules! field_ref {
+            ($field:expr) => { & $($mut)? $field };
+        }
+
+        visitor! {
+            @trait $(#[$meta])* $trait, v;
+
+            /// Program root.
+            fn visit_ast, walk_ast(node: Ast) {
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Any statement.
+            fn visit_statement, walk_statement(node: Statement) {
+                match node {
+                    Statement::If(node) => v.visit_if(node),
+                    Statement::IfElse(node) => v.visit_if_else(node),
+                    Statement::IfConst(node) => v.visit_if_const(node),
+                    Statement::Scope(node) => v.visit_scope(node),
+                    Statement::Panic(node) => v.visit_panic(node),
+                    Statement::StaticAssert(node) => v.visit_static_assert(node),
+                    Statement::Mod(node) => v.visit_mod(node),
+                    Statement::Import(node) => v.visit_import(node),
+                    Statement::Macro(node) => v.visit_macro(node),
+                    Statement::MacroCall(node) => v.visit_macro_call(node),
+                    Statement::Memory(node) => v.visit_memory(node),
+                    Statement::Static(node) => v.visit_static(node),
+                    Statement::Const(node) => v.visit_const(node),
+                    Statement::Enum(node) => v.visit_enum(node),
+                    Statement::TypeAlias(node) => v.visit_type_alias(node),
+                    Statement::Let(node) => v.visit_let(node),
+                    Statement::LetTuple(node) => v.visit_let_tuple(node),
+                    Statement::For(node) => v.visit_for(node),
+                    Statement::Loop(node) => v.visit_loop(node),
+                    Statement::While(node) => v.visit_while(node),
+                    Statement::Match(node) => v.visit_match(node),
+                    Statement::Continue(node) => v.visit_continue(node),
+                    Statement::Break(node) => v.visit_break(node),
+                    Statement::Inline(node) => v.visit_inline(node),
+                    Statement::Fn(node) => v.visit_fn(node),
+                    Statement::Return(node) => v.visit_return(node),
+                    Statement::Defer(node) => v.visit_defer(node),
+                    Statement::Error(node) => v.visit_error_node(node),
+                }
+            }
+
+            /// `if` statement.
+            fn visit_if, walk_if(node: If) {
+                v.visit_expression(& $($mut)? node.expression);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `if` statement with an `else` block.
+            fn visit_if_else, walk_if_else(node: IfElse) {
+                v.visit_if(& $($mut)? node.if_);
+                v.visit_else(& $($mut)? node.else_);
+            }
+
+            /// `if const` statement (both branches).
+            fn visit_if_const, walk_if_const(node: IfConst) {
+                v.visit_expression(& $($mut)? node.expression);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+                if let Some(else_) = & $($mut)? node.else_ {
+                    v.visit_else(else_);
+                }
+            }
+
+            /// `else` block.
+            fn visit_else, walk_else(node: Else) {
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Scope (block) statement.
+            fn visit_scope, walk_scope(node: Scope) {
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Panic statement.
+            fn visit_panic, walk_panic(_node: Panic) {}
+
+            /// Module definition.
+            fn visit_mod, walk_mod(node: Mod) {
+                v.visit_ident(& $($mut)? node.ident);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `import` statement.
+            fn visit_import, walk_import(node: Import) {
+                v.visit_lit(& $($mut)? node.path);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `macro` definition.
+            fn visit_macro, walk_macro(node: Macro) {
+                v.visit_ident(& $($mut)? node.ident);
+                for param in & $($mut)? node.params {
+                    v.visit_ident(param);
+                }
+            }
+
+            /// Macro invocation, along with its expansion.
+            fn visit_macro_call, walk_macro_call(node: MacroCall) {
+                v.visit_ident(& $($mut)? node.ident);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `memory` map.
+            fn visit_memory, walk_memory(node: Memory) {
+                for region in & $($mut)? node.regions {
+                    v.visit_region(region);
+                }
+            }
+
+            /// Region of a `memory` map.
+            fn visit_region, walk_region(node: Region) {
+                v.visit_ident(& $($mut)? node.ident);
+                v.visit_range(& $($mut)? node.range);
+            }
+
+            /// `static` definition.
+            fn visit_static, walk_static(node: Static) {
+                if let Some(offset) = & $($mut)? node.offset {
+                    v.visit_static_offset(offset);
+                }
+                if let Some(bank) = & $($mut)? node.bank {
+                    v.visit_static_bank(bank);
+                }
+                v.visit_field(& $($mut)? node.field);
+                if let Some(init) = & $($mut)? node.init {
+                    v.visit_static_init(init);
+                }
+            }
+
+            /// Absolute offset of a `static` definition.
+            fn visit_static_offset, walk_static_offset(node: StaticOffset) {
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// RAM bank of a `static` definition.
+            fn visit_static_bank, walk_static_bank(node: StaticBank) {
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// Initializer of a `static` definition.
+            fn visit_static_init, walk_static_init(node: StaticInit) {
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// `const` definition.
+            fn visit_const, walk_const(node: Const) {
+                v.visit_field(& $($mut)? node.field);
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// Static assertion.
+            fn visit_static_assert, walk_static_assert(node: StaticAssert) {
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// `type` alias declaration.
+            fn visit_type_alias, walk_type_alias(node: TypeAlias) {
+                v.visit_ident(& $($mut)? node.ident);
+                v.visit_type(& $($mut)? node.inner);
+            }
+
+            /// `enum` declaration.
+            fn visit_enum, walk_enum(node: Enum) {
+                v.visit_ident(& $($mut)? node.ident);
+                for variant in & $($mut)? node.variants {
+                    v.visit_variant(variant);
+                }
+            }
+
+            /// `enum` variant.
+            fn visit_variant, walk_variant(node: Variant) {
+                v.visit_ident(& $($mut)? node.ident);
+                if let Some(discriminant) = & $($mut)? node.discriminant {
+                    v.visit_expression(& $($mut)? discriminant.expression);
+                }
+            }
+
+            /// `let` definition.
+            fn visit_let, walk_let(node: Let) {
+                v.visit_field(& $($mut)? node.field);
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// `let` definition destructuring a tuple.
+            fn visit_let_tuple, walk_let_tuple(node: LetTuple) {
+                for field in & $($mut)? node.fields {
+                    v.visit_field(field);
+                }
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// `for` loop.
+            fn visit_for, walk_for(node: For) {
+                v.visit_field(& $($mut)? node.field);
+                v.visit_range(& $($mut)? node.range);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Range of a `for` loop or a `match` arm.
+            fn visit_range, walk_range(node: Range) {
+                v.visit_expression(& $($mut)? node.left);
+                v.visit_expression(& $($mut)? node.right);
+            }
+
+            /// `loop` statement.
+            fn visit_loop, walk_loop(node: Loop) {
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `while` loop.
+            fn visit_while, walk_while(node: While) {
+                v.visit_expression(& $($mut)? node.expression);
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `match` statement.
+            fn visit_match, walk_match(node: Match) {
+                v.visit_expression(& $($mut)? node.expression);
+                for arm in & $($mut)? node.arms {
+                    v.visit_match_arm(arm);
+                }
+                if let Some(else_) = & $($mut)? node.else_ {
+                    v.visit_else(else_);
+                }
+            }
+
+            /// Arm of a `match` statement.
+            fn visit_match_arm, walk_match_arm(node: MatchArm) {
+                match & $($mut)? node.pattern {
+                    Pattern::Expression(expression) => v.visit_expression(expression),
+                    Pattern::Range(range) => v.visit_range(range),
+                }
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// `continue` statement.
+            fn visit_continue, walk_continue(_node: Continue) {}
+
+            /// `break` statement.
+            fn visit_break, walk_break(_node: Break) {}
+
+            /// Expression statement.
+            fn visit_inline, walk_inline(node: Inline) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// Function definition.
+            fn visit_fn, walk_fn(node: Fn) {
+                for attribute in & $($mut)? node.attributes {
+                    v.visit_attribute(attribute);
+                }
+                if let Some(interrupt) = & $($mut)? node.interrupt {
+                    v.visit_fn_interrupt(interrupt);
+                }
+                v.visit_ident(& $($mut)? node.ident);
+                if let Some(generics) = & $($mut)? node.generics {
+                    v.visit_generics(generics);
+                }
+                if let Some(fn_arg) = & $($mut)? node.fn_arg {
+                    v.visit_fn_arg(fn_arg);
+                }
+                if let Some(fn_return) = & $($mut)? node.fn_return {
+                    v.visit_fn_return(fn_return);
+                }
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Interrupt handled by a function.
+            fn visit_fn_interrupt, walk_fn_interrupt(node: FnInterrupt) {
+                v.visit_ident(& $($mut)? node.ident);
+            }
+
+            /// Generic parameters of a function.
+            fn visit_generics, walk_generics(node: Generics) {
+                for param in & $($mut)? node.params {
+                    v.visit_generic_param(param);
+                }
+            }
+
+            /// Generic parameter.
+            fn visit_generic_param, walk_generic_param(node: GenericParam) {
+                v.visit_field(& $($mut)? node.field);
+            }
+
+            /// Function arguments.
+            fn visit_fn_arg, walk_fn_arg(node: FnArg) {
+                for field in & $($mut)? node.inner {
+                    v.visit_field(field);
+                }
+            }
+
+            /// Function return type.
+            fn visit_fn_return, walk_fn_return(node: FnReturn) {
+                v.visit_type(& $($mut)? node.type_);
+            }
+
+            /// `return` statement.
+            fn visit_return, walk_return(node: Return) {
+                if let Some(expression) = & $($mut)? node.expression {
+                    v.visit_expression(expression);
+                }
+            }
+
+            /// `defer` block.
+            fn visit_defer, walk_defer(node: Defer) {
+                for statement in & $($mut)? node.inner {
+                    v.visit_statement(statement);
+                }
+            }
+
+            /// Region that failed to parse.
+            fn visit_error_node, walk_error_node(_node: ErrorNode) {}
+
+            /// Typed field (`ident: type`).
+            fn visit_field, walk_field(node: Field) {
+                v.visit_ident(& $($mut)? node.ident);
+                v.visit_type(& $($mut)? node.type_);
+                if let Some(bits) = & $($mut)? node.bits {
+                    v.visit_bits(bits);
+                }
+            }
+
+            /// Bitfields of a field.
+            fn visit_bits, walk_bits(node: Bits) {
+                for field in & $($mut)? node.fields {
+                    v.visit_bit_field(field);
+                }
+            }
+
+            /// Bitfield.
+            fn visit_bit_field, walk_bit_field(node: BitField) {
+                v.visit_ident(& $($mut)? node.ident);
+                v.visit_expression(& $($mut)? node.width);
+            }
+
+            /// Any type.
+            fn visit_type, walk_type(node: Type) {
+                match node {
+                    Type::U8(_)
+                    | Type::I8(_)
+                    | Type::U16(_)
+                    | Type::I16(_)
+                    | Type::Bool(_)
+                    | Type::Fixed(_) => {}
+                    Type::Array(node) => v.visit_type_array(node),
+                    Type::Struct(node) => v.visit_struct(node),
+                    Type::Union(node) => v.visit_union(node),
+                    Type::Pointer(node) => v.visit_pointer(node),
+                    Type::Fn(node) => v.visit_fn_type(node),
+                    Type::Tuple(node) => v.visit_tuple(node),
+                    Type::Path(node) => v.visit_path(node),
+                }
+            }
+
+            /// Array type.
+            fn visit_type_array, walk_type_array(node: types::Array) {
+                v.visit_type(& $($mut)? node.type_);
+                v.visit_expression(& $($mut)? node.len);
+            }
+
+            /// Struct type.
+            fn visit_struct, walk_struct(node: types::Struct) {
+                for attribute in & $($mut)? node.attributes {
+                    v.visit_attribute(attribute);
+                }
+                for member in & $($mut)? node.fields {
+                    v.visit_member(member);
+                }
+            }
+
+            /// Union type.
+            fn visit_union, walk_union(node: types::Union) {
+                for attribute in & $($mut)? node.attributes {
+                    v.visit_attribute(attribute);
+                }
+                for member in & $($mut)? node.fields {
+                    v.visit_member(member);
+                }
+            }
+
+            /// Attribute of a struct or union type, or of a function.
+            fn visit_attribute, walk_attribute(node: types::Attribute) {
+                // the arguments of `#[repr(packed)]` and `#[inline(never)]`
+                // aren't expressions
+                if let (false, None, Some(expression)) =
+                    (node.is_packed(), node.inline(), & $($mut)? node.expression)
+                {
+                    v.visit_expression(expression);
+                }
+            }
+
+            /// Struct or union member.
+            fn visit_member, walk_member(node: types::Member) {
+                match node {
+                    types::Member::Field(node) => v.visit_field(node),
+                    types::Member::Struct(node) => v.visit_struct(node),
+                    types::Member::Union(node) => v.visit_union(node),
+                }
+            }
+
+            /// Pointer type.
+            fn visit_pointer, walk_pointer(node: types::Pointer) {
+                v.visit_type(& $($mut)? node.type_);
+            }
+
+            /// Tuple type.
+            fn visit_tuple, walk_tuple(node: types::Tuple) {
+                for type_ in & $($mut)? node.inner {
+                    v.visit_type(type_);
+                }
+            }
+
+            /// Function pointer type.
+            fn visit_fn_type, walk_fn_type(node: types::Fn) {
+                if let Some(args) = & $($mut)? node.args {
+                    for type_ in & $($mut)? args.inner {
+                        v.visit_type(type_);
+                    }
+                }
+                if let Some(fn_return) = & $($mut)? node.fn_return {
+                    v.visit_fn_return(fn_return);
+                }
+            }
+
+            /// Any expression.
+            fn visit_expression, walk_expression(node: Expression) {
+                match node {
+                    Expression::Path(node) => v.visit_path(node),
+                    Expression::Lit(node) => v.visit_lit(node),
+                    Expression::Array(node) => v.visit_array(node),
+                    Expression::StructLit(node) => v.visit_struct_lit(node),
+                    Expression::Minus(node) => v.visit_minus(node),
+                    Expression::AddressOf(node) => v.visit_address_of(node),
+                    Expression::Deref(node) => v.visit_deref(node),
+                    Expression::Not(node) => v.visit_not(node),
+                    Expression::Increment(node) => v.visit_increment(& $($mut)? node.inner),
+                    Expression::Decrement(node) => v.visit_decrement(& $($mut)? node.inner),
+                    Expression::Conditional(node) => v.visit_conditional(& $($mut)? node.inner),
+                    Expression::Cast(node) => v.visit_cast(& $($mut)? node.inner),
+                    Expression::SizeOf(node) => v.visit_size_of(& $($mut)? node.inner),
+                    Expression::Add(node) => v.visit_add(& $($mut)? node.inner),
+                    Expression::Sub(node) => v.visit_sub(& $($mut)? node.inner),
+                    Expression::Mul(node) => v.visit_mul(& $($mut)? node.inner),
+                    Expression::Div(node) => v.visit_div(& $($mut)? node.inner),
+                    Expression::And(node) => v.visit_and(& $($mut)? node.inner),
+                    Expression::Or(node) => v.visit_or(& $($mut)? node.inner),
+                    Expression::Xor(node) => v.visit_xor(& $($mut)? node.inner),
+                    Expression::Assign(node) => v.visit_assign(& $($mut)? node.inner),
+                    Expression::PlusAssign(node) => v.visit_plus_assign(& $($mut)? node.inner),
+                    Expression::MinusAssign(node) => v.visit_minus_assign(& $($mut)? node.inner),
+                    Expression::MulAssign(node) => v.visit_mul_assign(& $($mut)? node.inner),
+                    Expression::DivAssign(node) => v.visit_div_assign(& $($mut)? node.inner),
+                    Expression::AndAssign(node) => v.visit_and_assign(& $($mut)? node.inner),
+                    Expression::OrAssign(node) => v.visit_or_assign(& $($mut)? node.inner),
+                    Expression::XorAssign(node) => v.visit_xor_assign(& $($mut)? node.inner),
+                    Expression::LeftShift(node) => v.visit_left_shift(& $($mut)? node.inner),
+                    Expression::RightShift(node) => v.visit_right_shift(& $($mut)? node.inner),
+                    Expression::Index(node) => v.visit_index(& $($mut)? node.inner),
+                    Expression::Eq(node) => v.visit_eq(& $($mut)? node.inner),
+                    Expression::NotEq(node) => v.visit_not_eq(& $($mut)? node.inner),
+                    Expression::LessEq(node) => v.visit_less_eq(& $($mut)? node.inner),
+                    Expression::GreaterEq(node) => v.visit_greater_eq(& $($mut)? node.inner),
+                    Expression::Less(node) => v.visit_less(& $($mut)? node.inner),
+                    Expression::Greater(node) => v.visit_greater(& $($mut)? node.inner),
+                    Expression::LogicalAnd(node) => v.visit_logical_and(& $($mut)? node.inner),
+                    Expression::LogicalOr(node) => v.visit_logical_or(& $($mut)? node.inner),
+                    Expression::Call(node) => v.visit_call(& $($mut)? node.inner),
+                }
+            }
+
+            /// Array expression.
+            fn visit_array, walk_array(node: expression::Array) {
+                for expression in & $($mut)? node.inner {
+                    v.visit_expression(expression);
+                }
+            }
+
+            /// Struct literal expression.
+            fn visit_struct_lit, walk_struct_lit(node: expression::StructLit) {
+                for field in & $($mut)? node.fields {
+                    v.visit_field_init(field);
+                }
+            }
+
+            /// Field initializer of a struct literal.
+            fn visit_field_init, walk_field_init(node: expression::FieldInit) {
+                v.visit_ident(& $($mut)? node.ident);
+                v.visit_expression(& $($mut)? node.expression);
+            }
+
+            /// `-` unary expression.
+            fn visit_minus, walk_minus(node: expression::Minus) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `@` unary expression.
+            fn visit_address_of, walk_address_of(node: expression::AddressOf) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `*` unary expression.
+            fn visit_deref, walk_deref(node: expression::Deref) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `~` unary expression.
+            fn visit_not, walk_not(node: expression::Not) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `++` increment.
+            fn visit_increment, walk_increment(node: expression::Increment) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `--` decrement.
+            fn visit_decrement, walk_decrement(node: expression::Decrement) {
+                v.visit_expression(& $($mut)? node.inner);
+            }
+
+            /// `as` cast expression.
+            fn visit_cast, walk_cast(node: expression::Cast) {
+                v.visit_expression(& $($mut)? node.inner);
+                v.visit_type(& $($mut)? node.type_);
+            }
+
+            /// `sizeof` expression.
+            fn visit_size_of, walk_size_of(node: expression::SizeOf) {
+                match & $($mut)? node.inner {
+                    expression::SizeOfOperand::Type(type_) => v.visit_type(type_),
+                    expression::SizeOfOperand::Expression(expression) => v.visit_expression(expression),
+                }
+            }
+
+            /// Conditional expression.
+            fn visit_conditional, walk_conditional(node: expression::Conditional) {
+                v.visit_expression(& $($mut)? node.condition);
+                v.visit_expression(& $($mut)? node.then);
+                v.visit_expression(& $($mut)? node.else_);
+            }
+
+            /// Function call expression.
+            fn visit_call, walk_call(node: expression::Call) {
+                v.visit_expression(& $($mut)? node.left);
+                for expression in & $($mut)? node.args {
+                    v.visit_expression(expression);
+                }
+            }
+
+            /// Path.
+            fn visit_path, walk_path(node: Path) {
+                v.visit_ident(& $($mut)? node.head);
+                for (_, ident) in & $($mut)? node.tail {
+                    v.visit_ident(ident);
+                }
+            }
+
+            /// Identifier token.
+            fn visit_ident, walk_ident(_node: lex::Ident) {}
+
+            /// Literal token.
+            fn visit_lit, walk_lit(_node: lex::Lit) {}
+
+            // binary expressions
+            @binary
+            visit_add, walk_add(expression::Add),
+            visit_sub, walk_sub(expression::Sub),
+            visit_mul, walk_mul(expression::Mul),
+            visit_div, walk_div(expression::Div),
+            visit_and, walk_and(expression::And),
+            visit_or, walk_or(expression::Or),
+            visit_xor, walk_xor(expression::Xor),
+            visit_assign, walk_assign(expression::Assign),
+            visit_plus_assign, walk_plus_assign(expression::PlusAssign),
+            visit_minus_assign, walk_minus_assign(expression::MinusAssign),
+            visit_mul_assign, walk_mul_assign(expression::MulAssign),
+            visit_div_assign, walk_div_assign(expression::DivAssign),
+            visit_and_assign, walk_and_assign(expression::AndAssign),
+            visit_or_assign, walk_or_assign(expression::OrAssign),
+            visit_xor_assign, walk_xor_assign(expression::XorAssign),
+            visit_left_shift, walk_left_shift(expression::LeftShift),
+            visit_right_shift, walk_right_shift(expression::RightShift),
+            visit_index, walk_index(expression::Index),
+            visit_eq, walk_eq(expression::Eq),
+            visit_not_eq, walk_not_eq(expression::NotEq),
+            visit_less_eq, walk_less_eq(expression::LessEq),
+            visit_greater_eq, walk_greater_eq(expression::GreaterEq),
+            visit_less, walk_less(expression::Less),
+            visit_greater, walk_greater(expression::Greater),
+            visit_logical_and, walk_logical_and(expression::LogicalAnd),
+            visit_logical_or, walk_logical_or(expression::LogicalOr),
+        }
+    };
+
+    (@trait $(#[$meta:meta])* $trait:ident, $v:ident;
+     $(
+        $(#[doc = $doc:literal])*
+        fn $visit:ident, $walk:ident($node:ident: $($ty:ident)::+) $body:block
+     )*
+     @binary
+     $($bvisit:ident, $bwalk:ident($($bty:ident)::+),)*
+    ) => {
+        $(#[$meta])*
+        pub trait $trait<'a> {
+            $(
+                $(#[doc = $doc])*
+                fn $visit(&mut self, node: node_ref!($($ty)::+<'a>)) {
+                    $walk(self, node)
+                }
+            )*
+            $(
+                /// Binary expression.
+                fn $bvisit(&mut self, node: node_ref!($($bty)::+<'a>)) {
+                    $bwalk(self, node)
+                }
+            )*
+        }
+
+        $(
+            #[allow(unused_variables)]
+            pub fn $walk<'a, V: $trait<'a> + ?Sized>($v: &mut V, $node: node_ref!($($ty)::+<'a>)) $body
+        )*
+        $(
+            pub fn $bwalk<'a, V: $trait<'a> + ?Sized>(v: &mut V, node: node_ref!($($bty)::+<'a>)) {
+                v.visit_expression(field_ref!(node.left));
+                v.visit_expression(field_ref!(node.right));
+            }
+        )*
     };
 }
@@ -0,0 +1,117 @@
+use crate::{
+    arena::Node,
+    ast::{Context, Else, Expression, Grammar, Range, Statement},
+    lex,
+    lex::Token,
+    Error, Tokens,
+};
+use std::iter::Peekable;
+
+parse! {
+    /// `match <expression> { <arms> else { <statements> } }`
+    #[derive(Debug)]
+    pub struct Match<'a> {
+        /// `match` token.
+        pub match_: lex::Match<'a>,
+
+        /// Scrutinee expression tokens.
+        pub expression: Expression<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Match arms.
+        pub arms: Vec<MatchArm<'a>>,
+
+        /// Optional default arm.
+        pub else_: Option<Else<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+parse! {
+    /// `<pattern> { <statements> }`
+    #[derive(Debug)]
+    pub struct MatchArm<'a> {
+        /// Arm pattern.
+        pub pattern: Pattern<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Inner statements.
+        pub inner: Vec<Statement<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+parse! {
+    /// Pattern of a match arm.
+    #[derive(Debug)]
+    pub enum Pattern<'a> {
+        /// Single value (`<expression>`).
+        Expression(Expression<'a>),
+
+        /// Range of values (`<expression>..<expression>`).
+        Range(Node<Range<'a>>),
+    }
+}
+
+impl<'a> Grammar<'a> for Pattern<'a> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        let left = Grammar::parse(context, tokens)?;
+        if let Some(Ok(Token::DotDot(_))) = tokens.peek() {
+            let range = Range {
+                left,
+                dot_dot: Grammar::parse(context, tokens)?,
+                eq: Grammar::parse(context, tokens)?,
+                plus: Grammar::parse(context, tokens)?,
+                right: Grammar::parse(context, tokens)?,
+            };
+            Ok(Pattern::Range(context.alloc(range)))
+        } else {
+            Ok(Pattern::Expression(left))
+        }
+    }
+}
+
+impl<'a> Grammar<'a> for Option<MatchArm<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            Some(Ok(Token::Else(_))) | Some(Ok(Token::RightBracket(_))) => Ok(None),
+            _ => Ok(Some(Grammar::parse(context, tokens)?)),
+        }
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Else<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Else(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(context, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+span!(Match {
+    match_,
+    right_bracket
+});
+span!(MatchArm {
+    pattern,
+    right_bracket
+});
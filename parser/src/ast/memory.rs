@@ -0,0 +1,126 @@
+use crate::{
+    ast::{Context, Grammar, Range, Separated},
+    lex,
+    lex::{
+        span,
+        span::{Span, Spanned},
+        Token,
+    },
+    Error, Tokens,
+};
+use std::iter::Peekable;
+
+span!(Memory {
+    memory,
+    right_bracket
+});
+
+parse! {
+    /// `memory { <regions> }`
+    ///
+    /// Memory map of the target, similar to the memory regions of a linker
+    /// script (`memory { const ROM0:0x0000..0x4000, static WRAM:0xc000..0xe000,
+    /// HRAM:0xff80..0xffff }`).
+    ///
+    /// A `const` region holds the const memory of the program, and a `static`
+    /// region its static memory. The rest of the regions only hold statics at
+    /// absolute addresses (`static@<address>`), which must fall within one of the
+    /// regions of the map. Allocations that don't fit in their region fail to
+    /// compile.
+    #[derive(Debug)]
+    pub struct Memory<'a> {
+        /// `memory` token.
+        pub memory: lex::Memory<'a>,
+
+        /// `{` token.
+        pub left_bracket: lex::LeftBracket<'a>,
+
+        /// Memory regions.
+        pub regions: Separated<'a, Region<'a>>,
+
+        /// `}` token.
+        pub right_bracket: lex::RightBracket<'a>,
+    }
+}
+
+// fails if two regions of the memory map have the same name.
+pub(crate) fn check_regions<'a>(memory: &Memory<'a>) -> Result<(), Error<'a>> {
+    let regions = &memory.regions;
+    for (i, region) in regions.iter().enumerate() {
+        let name = region.ident.name();
+        if let Some(first) = regions[..i].iter().find(|r| r.ident.name() == name) {
+            return Err(Error::ShadowIdent {
+                ident: Box::new(first.ident.clone()),
+                shadow: region.ident.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+parse! {
+    /// `<ident>:<range>`, `static <ident>:<range>`, or `const <ident>:<range>`
+    ///
+    /// Region of a [`Memory`](Memory) map. The range is evaluated at compile
+    /// time, and follows the rules of the `for` ranges (`0xff80..0xffff`,
+    /// `0xff80..=0xfffe`, `0xff80..+127`).
+    #[derive(Debug)]
+    pub struct Region<'a> {
+        /// Optional memory space of the region.
+        pub space: Option<RegionSpace<'a>>,
+
+        /// Region identifier.
+        pub ident: lex::Ident<'a>,
+
+        /// `:` token.
+        pub colon: lex::Colon<'a>,
+
+        /// Address range of the region.
+        pub range: Range<'a>,
+    }
+}
+
+impl Spanned for Region<'_> {
+    fn span(&self) -> Span {
+        let span = span::union(&self.ident.span(), &self.range.span());
+        match &self.space {
+            Some(space) => span::union(&space.span(), &span),
+            None => span,
+        }
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Region<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            Some(Ok(Token::Ident(_))) | Some(Ok(Token::Static(_))) | Some(Ok(Token::Const(_))) => {
+                Ok(Some(Grammar::parse(ctx, tokens)?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+parse! {
+    /// Memory space held by a [`Region`](Region).
+    #[derive(Debug)]
+    pub enum RegionSpace<'a> {
+        /// Static memory (`static`).
+        Static(lex::Static<'a>),
+
+        /// Const memory (`const`).
+        Const(lex::Const<'a>),
+    }
+}
+
+impl<'a> Grammar<'a> for Option<RegionSpace<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        match tokens.peek() {
+            Some(Ok(Token::Static(_))) => {
+                Ok(Some(RegionSpace::Static(Grammar::parse(ctx, tokens)?)))
+            }
+            Some(Ok(Token::Const(_))) => Ok(Some(RegionSpace::Const(Grammar::parse(ctx, tokens)?))),
+            _ => Ok(None),
+        }
+    }
+}
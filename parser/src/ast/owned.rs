@@ -0,0 +1,143 @@
+//! Syntax trees that outlive their source code.
+//!
+//! The tokens of the `Ast` borrow their text (identifiers, literals, ...)
+//! from the source code, so parsing allocates no strings. Once parsed, an
+//! `Ast<'a>` can be turned into an `Ast<'static>` with
+//! [`Ast::into_owned`](crate::Ast::into_owned).
+use crate::{
+    arena::Node,
+    ast::{expression::LispNode, Ast, Doc, ErrorNode, Path},
+    lex::span::Span,
+};
+use std::collections::HashMap;
+
+// Replace the text borrowed by a node.
+pub(crate) trait Own<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str);
+}
+
+impl<'a> Own<'a> for &'a str {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        *self = f(self);
+    }
+}
+
+impl<'a> Own<'a> for Span {
+    fn own(&mut self, _: &mut dyn FnMut(&'a str) -> &'a str) {}
+}
+
+impl<'a, T: Own<'a>> Own<'a> for Node<T> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        (**self).own(f);
+    }
+}
+
+impl<'a, T: Own<'a>> Own<'a> for Option<T> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        if let Some(inner) = self {
+            inner.own(f);
+        }
+    }
+}
+
+impl<'a, T: Own<'a>> Own<'a> for Vec<T> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        for inner in self {
+            inner.own(f);
+        }
+    }
+}
+
+impl<'a, A: Own<'a>, B: Own<'a>> Own<'a> for (A, B) {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.0.own(f);
+        self.1.own(f);
+    }
+}
+
+impl<'a> Own<'a> for Path<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.head.own(f);
+        self.tail.own(f);
+    }
+}
+
+impl<'a, I: Own<'a>> Own<'a> for LispNode<'a, I> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.left_par.own(f);
+        self.inner.own(f);
+        self.right_par.own(f);
+    }
+}
+
+impl<'a> Own<'a> for Doc<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.lines.own(f);
+    }
+}
+
+impl<'a> Own<'a> for ErrorNode<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.tokens.own(f);
+    }
+}
+
+impl<'a> Own<'a> for Ast<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.inner.own(f);
+        self.eof.own(f);
+    }
+}
+
+impl Ast<'_> {
+    /// Returns a copy of the `Ast` that doesn't borrow the source code, for
+    /// syntax trees that must outlive it.
+    ///
+    /// The text of the tokens is copied once per distinct identifier or
+    /// literal, and never freed: this is meant for syntax trees that live for
+    /// as long as the program does.
+    ///
+    /// ```
+    /// let ast: parser::Ast<'static> = {
+    ///     let input = String::from("static FOO:u8");
+    ///     parser::parse(&input).unwrap().into_owned()
+    /// };
+    /// assert_eq!(1, ast.inner.len());
+    /// ```
+    pub fn into_owned(mut self) -> Ast<'static> {
+        let mut texts: HashMap<&str, &'static str> = HashMap::new();
+        self.own(&mut |text| texts.entry(text).or_insert_with(|| Box::leak(text.into())));
+        // SAFETY: the text borrowed by the tree has been replaced by 'static
+        // copies, and the lifetime of the tree is only that of its text.
+        unsafe { std::mem::transmute::<Ast<'_>, Ast<'static>>(self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::{Statement, Visitor},
+        lex::Ident,
+    };
+
+    #[test]
+    fn into_owned() {
+        let input = "/// Doc.\nstatic BAR:u8\nstatic FOO:[u8 2] = [1 (+ BAR 2)]\nmacro m(x) { (= FOO x) }\nm!(3)"
+            .to_string();
+        let expected = format!("{:?}", crate::parse(&input).unwrap());
+        let ast = crate::parse(&input).unwrap().into_owned();
+        std::mem::drop(input);
+        assert_eq!(expected, format!("{:?}", ast));
+
+        struct Idents(Vec<String>);
+        impl<'a> Visitor<'a> for Idents {
+            fn visit_ident(&mut self, node: &Ident<'a>) {
+                self.0.push(node.to_string());
+            }
+        }
+        let mut idents = Idents(Vec::new());
+        idents.visit_ast(&ast);
+        assert!(idents.0.contains(&"BAR".to_string()));
+        assert!(matches!(ast.inner[0], Statement::Static(_)));
+    }
+}
@@ -9,6 +9,11 @@ use crate::{
 };
 use std::iter::Peekable;
 
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
 #[derive(Debug)]
 pub struct Path<'a> {
     /// Head identifier token.
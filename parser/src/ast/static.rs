@@ -1,5 +1,5 @@
 use crate::{
-    ast::{Context, Expression, Field, Grammar},
+    ast::{pub_span, types, Context, Doc, Expression, Field, Grammar},
     lex,
     lex::{
         span,
@@ -18,6 +18,12 @@ parse! {
 
         /// Offset expression.
         pub expression: Expression<'a>,
+
+        /// Optional `volatile` token.
+        ///
+        /// Accesses to volatile memory (usually hardware registers) have side
+        /// effects, so they must be neither cached nor eliminated.
+        pub volatile: Option<lex::Volatile<'a>>,
     }
 }
 
@@ -35,21 +41,160 @@ impl<'a> Grammar<'a> for Option<StaticOffset<'a>> {
 }
 
 parse! {
+    /// `@bank(<expression>)`
+    ///
+    /// Switchable RAM bank of a static. Each bank is an address space of its
+    /// own, so the static must only be accessed while its bank is mapped.
     #[derive(Debug)]
-    pub struct Static<'a> {
-        /// `static` token.
-        pub static_: lex::Static<'a>,
+    pub struct StaticBank<'a> {
+        /// `@` token.
+        pub at: lex::At<'a>,
 
-        /// Optional [`StaticOffset`](StaticOffset) tokens.
-        pub offset: Option<StaticOffset<'a>>,
+        /// `bank` identifier.
+        pub bank: lex::Ident<'a>,
+
+        /// `(` token.
+        pub left_par: lex::LeftPar<'a>,
+
+        /// Bank number expression.
+        pub expression: Expression<'a>,
 
-        /// [`Field`](Field) tokens.
-        pub field: Field<'a>,
+        /// `)` token.
+        pub right_par: lex::RightPar<'a>,
     }
 }
 
+parse! {
+    #[derive(Debug)]
+    pub struct StaticInit<'a> {
+        /// `=` token.
+        pub assign: lex::Assign<'a>,
+
+        /// Initializer expression (evaluated at compile time).
+        pub expression: Expression<'a>,
+    }
+}
+
+impl<'a> Grammar<'a> for Option<StaticInit<'a>> {
+    fn parse(
+        context: &mut Context<'a>,
+        tokens: &mut Peekable<Tokens<'a>>,
+    ) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Assign(_))) = tokens.peek() {
+            Ok(Some(Grammar::parse(context, tokens)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Static<'a> {
+    /// Doc comment.
+    pub doc: Option<Doc<'a>>,
+
+    /// Optional `pub` token.
+    pub pub_: Option<lex::Pub<'a>>,
+
+    /// `static` token.
+    pub static_: lex::Static<'a>,
+
+    /// Optional [`StaticOffset`](StaticOffset) tokens.
+    pub offset: Option<StaticOffset<'a>>,
+
+    /// Optional [`StaticBank`](StaticBank) tokens.
+    pub bank: Option<StaticBank<'a>>,
+
+    /// [`Field`](Field) tokens.
+    pub field: Field<'a>,
+
+    /// Optional [`StaticInit`](StaticInit) tokens.
+    pub init: Option<StaticInit<'a>>,
+}
+
+impl<'a> Grammar<'a> for Static<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let doc = Grammar::parse(ctx, tokens)?;
+        let pub_ = Grammar::parse(ctx, tokens)?;
+        let static_ = Grammar::parse(ctx, tokens)?;
+        let (mut offset, mut bank) = (None, None);
+        if let Some(Ok(Token::At(_))) = tokens.peek() {
+            let at = Grammar::parse(ctx, tokens)?;
+            // `bank` can't name the const of an offset
+            match tokens.peek() {
+                Some(Ok(Token::Ident(ident))) if ident.name() == "bank" => {
+                    bank = Some(StaticBank {
+                        at,
+                        bank: Grammar::parse(ctx, tokens)?,
+                        left_par: Grammar::parse(ctx, tokens)?,
+                        expression: Grammar::parse(ctx, tokens)?,
+                        right_par: Grammar::parse(ctx, tokens)?,
+                    })
+                }
+                _ => {
+                    offset = Some(StaticOffset {
+                        at,
+                        expression: Grammar::parse(ctx, tokens)?,
+                        volatile: Grammar::parse(ctx, tokens)?,
+                    })
+                }
+            }
+        }
+        let field: Field<'a> = Grammar::parse(ctx, tokens)?;
+        let init: Option<StaticInit<'a>> = Grammar::parse(ctx, tokens)?;
+        if let Some(init) = &init {
+            types::check_range(&field.type_, &init.expression, &|e| ctx.eval(e))?;
+        }
+        Ok(Self {
+            doc,
+            pub_,
+            static_,
+            offset,
+            bank,
+            field,
+            init,
+        })
+    }
+}
+
+impl crate::incremental::Remap for Static<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.doc.remap(f);
+        self.pub_.remap(f);
+        self.static_.remap(f);
+        self.offset.remap(f);
+        self.bank.remap(f);
+        self.field.remap(f);
+        self.init.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Static<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.doc.own(f);
+        self.pub_.own(f);
+        self.static_.own(f);
+        self.offset.own(f);
+        self.bank.own(f);
+        self.field.own(f);
+        self.init.own(f);
+    }
+}
+
+span!(StaticBank { at, right_par });
+span!(StaticInit { assign, expression });
+
 impl Spanned for Static<'_> {
     fn span(&self) -> Span {
-        span::union(&self.static_.span(), &self.field.span())
+        let span = match &self.init {
+            Some(init) => span::union(&self.static_.span(), &init.span()),
+            None => span::union(&self.static_.span(), &self.field.span()),
+        };
+        pub_span(&self.pub_, span)
     }
 }
@@ -1,8 +1,13 @@
 //! Data type grammars.
 use crate::{
-    ast::{expression::Expression, Context, Field, Grammar, Path},
+    arena::Node,
+    ast::{expression::Expression, Context, Doc, Field, FnReturn, Grammar, Path, Separated},
     lex,
-    lex::{Token, Tokens},
+    lex::{
+        span,
+        span::{Span, Spanned},
+        Token, Tokens,
+    },
     Error,
 };
 use std::iter::Peekable;
@@ -16,8 +21,20 @@ parse! {
         /// i8 type.
         I8(lex::I8<'a>),
 
+        /// u16 type.
+        U16(lex::U16<'a>),
+
+        /// i16 type.
+        I16(lex::I16<'a>),
+
+        /// bool type.
+        Bool(lex::Bool<'a>),
+
+        /// Fixed-point type (`fx8.8`).
+        Fixed(lex::Fixed<'a>),
+
         /// Array type.
-        Array(Box<Array<'a>>),
+        Array(Node<Array<'a>>),
 
         /// Struct type.
         Struct(Struct<'a>),
@@ -26,7 +43,13 @@ parse! {
         Union(Union<'a>),
 
         /// Pointer type.
-        Pointer(Box<Pointer<'a>>),
+        Pointer(Node<Pointer<'a>>),
+
+        /// Function pointer type.
+        Fn(Node<Fn<'a>>),
+
+        /// Tuple type.
+        Tuple(Tuple<'a>),
 
         /// Path type.
         Path(Path<'a>),
@@ -35,26 +58,56 @@ parse! {
 
 impl<'a> Grammar<'a> for Option<Type<'a>> {
     fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
-        let type_ = match tokens.peek() {
-            Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
-            Some(Ok(Token::U8(_))) => Type::U8(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::I8(_))) => Type::I8(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::LeftSquare(_))) => Type::Array(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Struct(_))) => Type::Struct(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Union(_))) => Type::Union(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Ampersand(_))) => Type::Pointer(Grammar::parse(ctx, tokens)?),
-            Some(Ok(Token::Ident(_))) => {
-                let path = Grammar::parse(ctx, tokens)?;
-                if !ctx.is_type(&path) {
-                    return Err(Error::InvalidPath(path));
-                }
-                Type::Path(path)
+        ctx.nested(tokens, parse_type)
+    }
+}
+
+fn parse_type<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Option<Type<'a>>, Error<'a>> {
+    let type_ = match tokens.peek() {
+        Some(Err(_)) => return Err(tokens.next().unwrap().err().unwrap()),
+        Some(Ok(Token::U8(_))) => Type::U8(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::I8(_))) => Type::I8(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::U16(_))) => Type::U16(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::I16(_))) => Type::I16(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Bool(_))) => Type::Bool(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Fixed(_))) => Type::Fixed(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::LeftSquare(_))) => Type::Array(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Struct(_))) => Type::Struct(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Union(_))) => Type::Union(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Hash(_))) => parse_attributed(ctx, tokens)?,
+        Some(Ok(Token::Ampersand(_))) => Type::Pointer(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::Fn(_))) => Type::Fn(Grammar::parse(ctx, tokens)?),
+        Some(Ok(Token::LeftPar(_))) => Type::Tuple(Grammar::parse(ctx, tokens)?),
+        // `&&` is lexed as a single token
+        Some(Ok(Token::AmpersandAmpersand(_))) => {
+            let (outer, inner) = match tokens.next() {
+                Some(Ok(Token::AmpersandAmpersand(token))) => token.split(),
+                _ => unreachable!(),
+            };
+            let type_ = Grammar::parse(ctx, tokens)?;
+            let inner = ctx.alloc(Pointer {
+                ampersand: inner,
+                type_,
+            });
+            Type::Pointer(ctx.alloc(Pointer {
+                ampersand: outer,
+                type_: Type::Pointer(inner),
+            }))
+        }
+        Some(Ok(Token::Ident(_))) => {
+            let path = Grammar::parse(ctx, tokens)?;
+            if !ctx.is_type(&path) {
+                return Err(Error::InvalidPath(path));
             }
-            _ => return Ok(None),
-        };
+            Type::Path(path)
+        }
+        _ => return Ok(None),
+    };
 
-        Ok(Some(type_))
-    }
+    Ok(Some(type_))
 }
 
 impl<'a> Grammar<'a> for Type<'a> {
@@ -62,7 +115,14 @@ impl<'a> Grammar<'a> for Type<'a> {
         if let Some(statement) = Grammar::parse(ctx, tokens)? {
             Ok(statement)
         } else {
-            Err(Error::UnexpectedToken(tokens.next().unwrap()?))
+            match tokens.peek() {
+                Some(Ok(token)) => Err(Error::Expected {
+                    expected: "type",
+                    found: token.clone(),
+                }),
+                Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+                None => Err(Error::Eof),
+            }
         }
     }
 }
@@ -75,15 +135,326 @@ span!(Union {
     union,
     right_bracket
 });
+span!(Attribute { hash, right_square });
 span!(Array {
     left_square,
     right_square
 });
 span!(Pointer { ampersand, type_ });
+span!(Tuple {
+    left_par,
+    right_par
+});
+span!(FnArgs {
+    left_par,
+    right_par
+});
+
+impl Spanned for Fn<'_> {
+    fn span(&self) -> Span {
+        match (&self.args, &self.fn_return) {
+            (_, Some(fn_return)) => span::union(&self.fn_.span(), &fn_return.span()),
+            (Some(args), None) => span::union(&self.fn_.span(), &args.span()),
+            (None, None) => self.fn_.span(),
+        }
+    }
+}
+
+// struct or union type with attributes.
+fn parse_attributed<'a>(
+    ctx: &mut Context<'a>,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Type<'a>, Error<'a>> {
+    let doc = Grammar::parse(ctx, tokens)?;
+    let attributes: Vec<Attribute<'a>> = Grammar::parse(ctx, tokens)?;
+    check_attributes(
+        &attributes,
+        &["repr", "align"],
+        "`repr` or `align` attribute",
+    )?;
+    match tokens.peek() {
+        Some(Ok(Token::Struct(_))) => Ok(Type::Struct(Struct {
+            doc,
+            attributes,
+            ..Grammar::parse(ctx, tokens)?
+        })),
+        Some(Ok(Token::Union(_))) => Ok(Type::Union(Union {
+            doc,
+            attributes,
+            ..Grammar::parse(ctx, tokens)?
+        })),
+        Some(Ok(token)) => Err(Error::Expected {
+            expected: "`struct` or `union`",
+            found: token.clone(),
+        }),
+        Some(Err(_)) => Err(tokens.next().unwrap().unwrap_err()),
+        None => Err(Error::Eof),
+    }
+}
+
+// fail if any of the attributes isn't one of the allowed ones.
+pub(crate) fn check_attributes<'a>(
+    attributes: &[Attribute<'a>],
+    allowed: &[&str],
+    expected: &'static str,
+) -> Result<(), Error<'a>> {
+    match attributes
+        .iter()
+        .find(|attribute| !allowed.contains(&attribute.ident.name()))
+    {
+        Some(attribute) => Err(Error::Expected {
+            expected,
+            found: Token::Ident(attribute.ident.clone()),
+        }),
+        None => Ok(()),
+    }
+}
+
+// range of values of an integer type.
+pub(crate) fn int_range(type_: &str) -> (i32, i32) {
+    match type_ {
+        "u8" => (0, 0xff),
+        "i8" => (-0x80, 0x7f),
+        "u16" => (0, 0xffff),
+        "i16" => (-0x8000, 0x7fff),
+        _ => unreachable!(),
+    }
+}
+
+// fail if a number literal (`300`, `-129`, `300.0fx`), or the value of a
+// constant expression (`(+ 250 10)`), assigned to a value of the given type
+// doesn't fit in it. The elements of array literals are checked one by one.
+// `eval` evaluates the constant expressions.
+pub(crate) fn check_range<'a>(
+    type_: &Type<'a>,
+    expression: &Expression<'a>,
+    eval: &dyn std::ops::Fn(&Expression<'a>) -> Option<u16>,
+) -> Result<(), Error<'a>> {
+    let name = match (type_, expression) {
+        (Type::U8(_), _) => "u8",
+        (Type::I8(_), _) => "i8",
+        (Type::U16(_), _) => "u16",
+        (Type::I16(_), _) => "i16",
+        (Type::Fixed(_), Expression::Lit(lit)) if lit.is_fixed() => {
+            return match lit.fixed_value() {
+                Some(_) => Ok(()),
+                None => Err(Error::OutOfRange {
+                    span: expression.span(),
+                    value: lit.to_string(),
+                    type_: "fx8.8",
+                }),
+            };
+        }
+        (Type::Array(array), Expression::Array(literal)) => {
+            for expression in &literal.inner {
+                check_range(&array.type_, expression, eval)?;
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    check_int(name, expression, eval)
+}
+
+// fail if a number literal, or the value of a constant expression, doesn't
+// fit in the integer type of the given name (`u8`, `i8`, `u16` or `i16`).
+pub(crate) fn check_int<'a>(
+    name: &'static str,
+    expression: &Expression<'a>,
+    eval: &dyn std::ops::Fn(&Expression<'a>) -> Option<u16>,
+) -> Result<(), Error<'a>> {
+    let (lit, sign) = match expression {
+        Expression::Lit(lit) => (lit, 1),
+        Expression::Minus(minus) => match &minus.inner {
+            Expression::Lit(lit) => (lit, -1),
+            _ => return check_value(name, expression, eval),
+        },
+        _ => return check_value(name, expression, eval),
+    };
+    // literals of other types are reported by the type checker
+    if lit.char_value().is_some()
+        || lit.bool_value().is_some()
+        || lit.str_value().is_some()
+        || lit.is_fixed()
+    {
+        return Ok(());
+    }
+    let (min, max) = int_range(name);
+    // number literals that don't fit in 16 bits have no value
+    match lit.num_value().map(|value| sign * i32::from(value)) {
+        Some(value) if min <= value && value <= max => Ok(()),
+        _ => Err(Error::OutOfRange {
+            span: expression.span(),
+            value: format!("{}{}", if sign < 0 { "-" } else { "" }, lit),
+            type_: name,
+        }),
+    }
+}
+
+// the arithmetic of constant expressions wraps around at 16 bits, so only the
+// values of byte types can be checked. Small negative values wrap around into
+// bytes (`(- 0 1)` is `0xff`).
+fn check_value<'a>(
+    name: &'static str,
+    expression: &Expression<'a>,
+    eval: &dyn std::ops::Fn(&Expression<'a>) -> Option<u16>,
+) -> Result<(), Error<'a>> {
+    let fits = |value: u16| match name {
+        "u8" => value <= 0xff || value >= 0xff80,
+        "i8" => value <= 0x7f || value >= 0xff80,
+        _ => true,
+    };
+    match eval(expression) {
+        Some(value) if !fits(value) => Err(Error::OutOfRange {
+            span: expression.span(),
+            value: match name {
+                "i8" => (value as i16).to_string(),
+                _ => value.to_string(),
+            },
+            type_: name,
+        }),
+        _ => Ok(()),
+    }
+}
+
+parse! {
+    /// `#[repr(packed)]`, `#[align(<expression>)]`, `#[bank(<expression>)]`,
+    /// `#[inline]`, or `#[inline(never)]`
+    ///
+    /// Layout attribute of a struct or union type, or bank and inlining
+    /// attributes of a function (see [`Fn`](crate::ast::Fn)).
+    ///
+    /// By default, the members of a struct are laid out in declaration order,
+    /// each one at the first offset following the previous member that is a
+    /// multiple of its alignment. Every type is aligned to 1 byte (so there is
+    /// no padding), except:
+    ///
+    /// - `#[align(N)]` types, aligned to `N` bytes (a power of two). Their size
+    ///   is padded to a multiple of `N`.
+    /// - Structs, unions, and arrays, aligned like their most aligned member.
+    /// - `#[repr(packed)]` structs and unions, aligned to 1 byte (or to `N`, if
+    ///   also `#[align(N)]`). Their members are laid out with no padding,
+    ///   whatever their alignment.
+    ///
+    /// Tuples are always laid out with no padding.
+    #[derive(Debug)]
+    pub struct Attribute<'a> {
+        /// `#` token.
+        pub hash: lex::Hash<'a>,
+
+        /// `[` token.
+        pub left_square: lex::LeftSquare<'a>,
+
+        /// Attribute identifier (`repr`, `align`, `bank`, or `inline`).
+        pub ident: lex::Ident<'a>,
+
+        /// `(` token (`None` for `#[inline]`).
+        pub left_par: Option<lex::LeftPar<'a>>,
+
+        /// Attribute argument (`None` for `#[inline]`).
+        pub expression: Option<Expression<'a>>,
+
+        /// `)` token (`None` for `#[inline]`).
+        pub right_par: Option<lex::RightPar<'a>>,
+
+        /// `]` token.
+        pub right_square: lex::RightSquare<'a>,
+    }
+}
+
+impl<'a> Attribute<'a> {
+    /// Returns true for the `#[repr(packed)]` attribute.
+    pub fn is_packed(&self) -> bool {
+        self.ident.name() == "repr"
+    }
+
+    /// Expression of the alignment of an `#[align(N)]` attribute.
+    pub fn align(&self) -> Option<&Expression<'a>> {
+        match self.ident.name() {
+            "align" => self.expression.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Expression of the bank number of a `#[bank(N)]` attribute.
+    pub fn bank(&self) -> Option<&Expression<'a>> {
+        match self.ident.name() {
+            "bank" => self.expression.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(true)` for the `#[inline]` attribute, and `Some(false)`
+    /// for `#[inline(never)]`.
+    pub fn inline(&self) -> Option<bool> {
+        match self.ident.name() {
+            "inline" => Some(self.expression.is_none()),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Attribute<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        if let Some(Ok(Token::Hash(_))) = tokens.peek() {
+            let hash = Grammar::parse(ctx, tokens)?;
+            let left_square = Grammar::parse(ctx, tokens)?;
+            let ident: lex::Ident<'a> = Grammar::parse(ctx, tokens)?;
+            // `#[inline]` is the only attribute without an argument
+            if ident.name() == "inline" && !matches!(tokens.peek(), Some(Ok(Token::LeftPar(_)))) {
+                return Ok(Some(Attribute {
+                    hash,
+                    left_square,
+                    ident,
+                    left_par: None,
+                    expression: None,
+                    right_par: None,
+                    right_square: Grammar::parse(ctx, tokens)?,
+                }));
+            }
+            let left_par: lex::LeftPar<'a> = Grammar::parse(ctx, tokens)?;
+            // which attributes are allowed depends on what they precede
+            match (ident.name(), tokens.peek()) {
+                ("repr", Some(Ok(Token::Ident(packed)))) if packed.name() == "packed" => {}
+                ("inline", Some(Ok(Token::Ident(never)))) if never.name() == "never" => {}
+                ("repr", Some(Ok(token))) => {
+                    return Err(Error::Expected {
+                        expected: "`packed`",
+                        found: token.clone(),
+                    })
+                }
+                ("inline", Some(Ok(token))) => {
+                    return Err(Error::Expected {
+                        expected: "`never`",
+                        found: token.clone(),
+                    })
+                }
+                _ => {}
+            }
+            Ok(Some(Attribute {
+                hash,
+                left_square,
+                ident,
+                left_par: Some(left_par),
+                expression: Some(Grammar::parse(ctx, tokens)?),
+                right_par: Some(Grammar::parse(ctx, tokens)?),
+                right_square: Grammar::parse(ctx, tokens)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
 
 parse! {
     #[derive(Debug)]
     pub struct Struct<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Layout attributes.
+        pub attributes: Vec<Attribute<'a>>,
+
         /// `struct` token.
         pub struct_: lex::Struct<'a>,
 
@@ -91,7 +462,7 @@ parse! {
         pub left_bracket: lex::LeftBracket<'a>,
 
         /// Inner fields.
-        pub fields: Vec<Field<'a>>,
+        pub fields: Separated<'a, Member<'a>>,
 
         /// `}` token.
         pub right_bracket: lex::RightBracket<'a>,
@@ -101,6 +472,12 @@ parse! {
 parse! {
     #[derive(Debug)]
     pub struct Union<'a> {
+        /// Doc comment.
+        pub doc: Option<Doc<'a>>,
+
+        /// Layout attributes.
+        pub attributes: Vec<Attribute<'a>>,
+
         /// `union` token.
         pub union: lex::Union<'a>,
 
@@ -108,13 +485,45 @@ parse! {
         pub left_bracket: lex::LeftBracket<'a>,
 
         /// Inner fields.
-        pub fields: Vec<Field<'a>>,
+        pub fields: Separated<'a, Member<'a>>,
 
         /// `}` token.
         pub right_bracket: lex::RightBracket<'a>,
     }
 }
 
+parse! {
+    /// Member of a struct or union type.
+    #[derive(Debug)]
+    pub enum Member<'a> {
+        /// Named field.
+        Field(Node<Field<'a>>),
+
+        /// Anonymous struct, whose fields belong to the enclosing type.
+        Struct(Struct<'a>),
+
+        /// Anonymous union, whose fields belong to the enclosing type.
+        Union(Union<'a>),
+    }
+}
+
+impl<'a> Grammar<'a> for Option<Member<'a>> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let member = match tokens.peek() {
+            Some(Ok(Token::Ident(_))) => Member::Field(Grammar::parse(ctx, tokens)?),
+            Some(Ok(Token::Struct(_))) => Member::Struct(Grammar::parse(ctx, tokens)?),
+            Some(Ok(Token::Union(_))) => Member::Union(Grammar::parse(ctx, tokens)?),
+            Some(Ok(Token::Hash(_))) => match parse_attributed(ctx, tokens)? {
+                Type::Struct(struct_) => Member::Struct(struct_),
+                Type::Union(union) => Member::Union(union),
+                _ => unreachable!(),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(member))
+    }
+}
+
 parse! {
     /// `& <type>`
     #[derive(Debug)]
@@ -127,6 +536,97 @@ parse! {
     }
 }
 
+parse! {
+    /// `( <types> )`
+    ///
+    /// Tuple type. Its elements are laid out contiguously, like the fields of a
+    /// struct, and tuple values are written as array literals (`[q r]`).
+    #[derive(Debug)]
+    pub struct Tuple<'a> {
+        /// `(` token.
+        pub left_par: lex::LeftPar<'a>,
+
+        /// Element types.
+        pub inner: Vec<Type<'a>>,
+
+        /// `)` token.
+        pub right_par: lex::RightPar<'a>,
+    }
+}
+
+/// `fn ( <types> ) : <type>`
+///
+/// Type of a pointer to a function taking arguments of the given types.
+/// Both the arguments and the return type are optional, like in function
+/// definitions.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(deserialize = "'de: 'a"))
+)]
+#[derive(Debug)]
+pub struct Fn<'a> {
+    /// `fn` token.
+    pub fn_: lex::Fn<'a>,
+
+    /// Function argument types.
+    pub args: Option<FnArgs<'a>>,
+
+    /// Function return tokens.
+    pub fn_return: Option<FnReturn<'a>>,
+}
+
+impl<'a> Grammar<'a> for Fn<'a> {
+    fn parse(ctx: &mut Context<'a>, tokens: &mut Peekable<Tokens<'a>>) -> Result<Self, Error<'a>> {
+        let fn_: lex::Fn<'a> = Grammar::parse(ctx, tokens)?;
+        // the arguments must begin on the same line as the `fn` token. Otherwise
+        // the `(` begins an expression statement
+        let args = match tokens.peek() {
+            Some(Ok(Token::LeftPar(t))) if t.span().min[0] == fn_.span().max[0] => {
+                Some(Grammar::parse(ctx, tokens)?)
+            }
+            _ => None,
+        };
+        let fn_return = Grammar::parse(ctx, tokens)?;
+        Ok(Self {
+            fn_,
+            args,
+            fn_return,
+        })
+    }
+}
+
+impl crate::incremental::Remap for Fn<'_> {
+    fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+        self.fn_.remap(f);
+        self.args.remap(f);
+        self.fn_return.remap(f);
+    }
+}
+
+impl<'a> crate::ast::owned::Own<'a> for Fn<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        self.fn_.own(f);
+        self.args.own(f);
+        self.fn_return.own(f);
+    }
+}
+
+parse! {
+    /// `( <types> )`
+    #[derive(Debug)]
+    pub struct FnArgs<'a> {
+        /// `(` token.
+        pub left_par: lex::LeftPar<'a>,
+
+        /// Argument types.
+        pub inner: Vec<Type<'a>>,
+
+        /// `)` token.
+        pub right_par: lex::RightPar<'a>,
+    }
+}
+
 parse! {
     /// `[ <type> ; <length> ]`
     #[derive(Debug)]
@@ -0,0 +1,58 @@
+//! Read-only AST traversal.
+//!
+//! Implement [`Visitor`] overriding the methods of the nodes of interest. The
+//! default methods call the matching `walk_*` function, which visits the
+//! children of the node, so overridden methods should call it too in order to
+//! keep traversing the tree.
+//!
+//! ```
+//! use parser::ast::{visit, Fn, Visitor};
+//!
+//! #[derive(Default)]
+//! struct Functions(Vec<String>);
+//!
+//! impl<'a> Visitor<'a> for Functions {
+//!     fn visit_fn(&mut self, node: &Fn<'a>) {
+//!         self.0.push(node.ident.to_string());
+//!         visit::walk_fn(self, node);
+//!     }
+//! }
+//!
+//! let ast = parser::parse("fn foo { fn bar { } } fn baz { }").unwrap();
+//! let mut functions = Functions::default();
+//! functions.visit_ast(&ast);
+//! assert_eq!(vec!["foo", "bar", "baz"], functions.0);
+//! ```
+visitor! {
+    /// Read-only AST visitor.
+    Visitor
+}
+
+#[cfg(test)]
+mod test {
+    use super::Visitor;
+    use crate::lex::Ident;
+
+    #[derive(Default)]
+    struct Idents(Vec<String>);
+
+    impl<'a> Visitor<'a> for Idents {
+        fn visit_ident(&mut self, node: &Ident<'a>) {
+            self.0.push(node.to_string());
+        }
+    }
+
+    #[test]
+    fn idents() {
+        let input = "mod m { static FOO:[u8 N] } \
+                     fn f(a:u8):&T { if (== a 0) { return @(g a::b) } } \
+                     for i:u8 in 0..(- x 1) { let y:u8 = ([i]arr) }";
+        let ast = crate::parse(input).unwrap();
+        let mut idents = Idents::default();
+        idents.visit_ast(&ast);
+        let gt = vec![
+            "m", "FOO", "N", "f", "a", "T", "a", "g", "a", "b", "i", "x", "y", "i", "arr",
+        ];
+        assert_eq!(gt, idents.0);
+    }
+}
@@ -0,0 +1,39 @@
+//! Mutable AST traversal.
+//!
+//! Same as [`visit`](super::visit), but the nodes are visited through mutable
+//! references, so they can be rewritten in place.
+visitor! {
+    /// Mutable AST visitor.
+    VisitorMut, mut
+}
+
+#[cfg(test)]
+mod test {
+    use super::{walk_scope, VisitorMut};
+    use crate::ast::{Scope, Statement};
+
+    // removes panic statements from scopes
+    struct NoPanic;
+
+    impl<'a> VisitorMut<'a> for NoPanic {
+        fn visit_scope(&mut self, node: &mut Scope<'a>) {
+            node.inner.retain(|s| !matches!(s, Statement::Panic(_)));
+            walk_scope(self, node);
+        }
+    }
+
+    #[test]
+    fn rewrite() {
+        let mut ast = crate::parse("{ !! { !! (foo) } } !!").unwrap();
+        NoPanic.visit_ast(&mut ast);
+        match &ast.inner[..] {
+            [Statement::Scope(scope), Statement::Panic(_)] => match &scope.inner[..] {
+                [Statement::Scope(scope)] => {
+                    assert!(matches!(scope.inner[..], [Statement::Inline(_)]))
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+}
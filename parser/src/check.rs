@@ -0,0 +1,1550 @@
+//! Type checking.
+//!
+//! Validates the types of a parsed [`Ast`](crate::Ast) before it is lowered
+//! into IR: the operands of expressions, assignments, the arguments of function
+//! calls, and array indexing. Type errors are reported as
+//! [`Error::MismatchedTypes`](Error::MismatchedTypes) and
+//! [`Error::ArgCount`](Error::ArgCount), located in the source code.
+//!
+//! ```
+//! let ast = parser::parse("static FOO:u16\nstatic BAR:u8\n(= BAR FOO)").unwrap();
+//! let errors = parser::check::check(&ast);
+//! assert_eq!(
+//!     "Mismatched types: expected `u8`, found `u16`",
+//!     errors[0].to_string()
+//! );
+//! ```
+//!
+//! # Coercion rules
+//!
+//! - Integer constants (number literals, `sizeof`, and constant expressions of
+//!   them and of other consts) coerce to any integer, `bool`, enum, or pointer
+//!   type.
+//! - `u8`, `i8`, `bool`, and enums are bytes, and `u16` and `i16` are words.
+//!   Values coerce to any type of the same width, but bytes and words don't mix
+//!   without a cast (`(as FOO u16)`).
+//! - Pointers only coerce to pointers to the same type, and functions to
+//!   function pointers with the same signature.
+//! - Arrays coerce to arrays of the same type and length. Array literals
+//!   coerce to arrays and tuples element by element, and string literals to
+//!   byte arrays and byte pointers.
+//! - Struct literals coerce to the struct and union types with the given
+//!   fields.
+//! - `fx8.8` values don't coerce to nor from any other type, not even integer
+//!   constants. Fixed-point literals (`1.5fx`) and casts (`(as X fx8.8)`) make
+//!   them. They are added to and compared with other `fx8.8` values,
+//!   multiplied by words (`u16`, `i16`, and integer constants) and `fx8.8`
+//!   values, and divided by words.
+//!
+//! Generic functions (`fn sum<const N:u8>(xs:[u8 N]):u8`) take arrays of any
+//! length, but the arrays passed to arguments whose lengths are given by the
+//! same generic parameter must be of the same length.
+//!
+//! Functions with a return type must return on every path of their control
+//! flow (or never reach the end of their body, as with `loop { }` and
+//! `!!`). Otherwise, an [`Error::MissingReturn`](Error::MissingReturn)
+//! points at the closing bracket of the function.
+//!
+//! The builtins (`builtin::memcpy`, ...) have the signatures of functions, so
+//! their calls are checked like any other:
+//! `memcpy(dst:&_ src:&_ len:u16)`, `memset(dst:&_ value:u8 len:u16)`,
+//! `swap_nibbles(byte:u8):u8`, `bcd_adjust(byte:u8):u8`, `halt()`, and
+//! `enable_interrupts()`.
+//!
+//! Paths the checker can't resolve have an unknown type, which coerces to and
+//! from any other type, so only the mismatches that are certain are reported.
+//!
+//! # Extern symbols
+//!
+//! Programs can use symbols they don't declare, provided by their environment
+//! (hardware registers, the functions of a runtime library, ...). When checked
+//! with the [`Context`](crate::ast::Context) they were parsed with
+//! ([`check_with_context`](check_with_context)), the symbols declared with
+//! [`ContextBuilder::extern_symbol`](crate::ContextBuilder::extern_symbol) are
+//! typed, and paths that are neither declared nor provided are reported as
+//! [`Error::UndefinedSymbol`](Error::UndefinedSymbol).
+//!
+//! ```
+//! use parser::{check, ContextBuilder, Error};
+//!
+//! let mut context = ContextBuilder::default()
+//!     .extern_symbol("LCDC", "u8")
+//!     .unlinked_symbol("rand", "runtime")
+//!     .build();
+//! let input = "static X:u16\n(= LCDC X)\n(= X (rand))\n(= X Y)";
+//! let ast = parser::parse_with_context(input, &mut context).unwrap();
+//! let errors = check::check_with_context(&ast, &context);
+//! assert_eq!(
+//!     "Mismatched types: expected `u8`, found `u16`",
+//!     errors[0].to_string()
+//! );
+//! assert!(matches!(&errors[1], Error::UndefinedSymbol { library: Some(l), .. } if l == "runtime"));
+//! assert!(matches!(&errors[2], Error::UndefinedSymbol { library: None, .. }));
+//! ```
+//!
+//! # Strict mode
+//!
+//! The VM zero-fills its memory, so programs that read statics or locals
+//! before assigning them work there, but not on real hardware. In strict mode
+//! ([`check_strict`](check_strict)), these reads are reported as
+//! [`Error::Uninitialized`](Error::Uninitialized), either as warnings or as
+//! errors.
+//!
+//! ```
+//! use parser::check::Level;
+//!
+//! let ast = parser::parse("static FOO:u8\nstatic BAR:u8 = 0\n(= BAR FOO)").unwrap();
+//! let report = parser::check::check_strict(&ast, Level::Warn);
+//! assert!(report.errors.is_empty());
+//! assert_eq!(
+//!     "Use of possibly uninitialized `FOO`",
+//!     report.warnings[0].to_string()
+//! );
+//! ```
+use crate::{
+    ast::{
+        self, expression::eval, types, Ast, Builtin, Context, Expression, Field, Pattern,
+        Statement, Type,
+    },
+    lex::span::{SourceId, Span, Spanned},
+    Error,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+mod init;
+mod lint;
+
+/// Type check a program.
+///
+/// Returns the type errors of the program, in the order they are found. The
+/// program is well typed if there are none.
+pub fn check<'a>(ast: &Ast<'a>) -> Vec<Error<'a>> {
+    let mut checker = Checker::new();
+    checker.statements(&ast.inner);
+    checker.errors
+}
+
+/// Type check a program, along with the symbols provided by its environment.
+///
+/// On top of the type errors of [`check`](check), the uses of the
+/// [extern symbols](crate::ContextBuilder::extern_symbol) of the context are
+/// checked, and the paths that are neither declared by the program nor
+/// provided by the context are reported as
+/// [`Error::UndefinedSymbol`](Error::UndefinedSymbol), after the type errors.
+pub fn check_with_context<'a>(ast: &Ast<'a>, context: &Context<'a>) -> Vec<Error<'a>> {
+    let mut checker = Checker::new();
+    for (name, type_) in context.externs() {
+        let ty = checker.resolve(type_);
+        checker.define(name.clone(), ty);
+    }
+    checker.statements(&ast.inner);
+    let Checker {
+        declared,
+        unresolved,
+        mut errors,
+        ..
+    } = checker;
+    for (modules, name, span) in unresolved {
+        let defined = (0..=modules.len()).any(|i| {
+            let mut path = modules[..i].to_vec();
+            path.push(name.clone());
+            declared.contains(&path.join("::"))
+        });
+        if !defined {
+            errors.push(Error::UndefinedSymbol {
+                library: context.unlinked_library(&name).map(str::to_string),
+                name,
+                span,
+            });
+        }
+    }
+    errors
+}
+
+// type of the node at a position of a source: the innermost expression, or a
+// declared identifier. `None` if the type is unknown.
+pub(crate) fn type_at(
+    ast: &Ast<'_>,
+    source: SourceId,
+    position: [usize; 2],
+) -> Option<(Span, Option<String>)> {
+    let mut checker = Checker::new();
+    checker.probe = Some((source, position));
+    checker.statements(&ast.inner);
+    checker.probed.map(|(span, ty)| (span, ty.hover()))
+}
+
+/// Lint a program.
+///
+/// Returns warnings about code that is valid, but most likely a mistake:
+/// unused functions, statics, consts, and locals
+/// ([`Error::Unused`](Error::Unused)), and statements that never run
+/// ([`Error::Unreachable`](Error::Unreachable)), in the order of their
+/// location.
+///
+/// ```
+/// let ast = parser::parse("static FOO:u8\nloop { }\n(= FOO 1)").unwrap();
+/// let warnings = parser::check::lint(&ast);
+/// assert_eq!("Unreachable statement", warnings[0].to_string());
+/// ```
+pub fn lint<'a>(ast: &Ast<'a>) -> Vec<Error<'a>> {
+    lint::lint(ast)
+}
+
+/// How the findings of the strict mode are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// As warnings, which don't make the program invalid.
+    Warn,
+
+    /// As errors.
+    Deny,
+}
+
+/// Errors and warnings of a program checked in strict mode.
+#[derive(Debug, Default)]
+pub struct Report<'a> {
+    /// Errors, in the order they are found.
+    pub errors: Vec<Error<'a>>,
+
+    /// Warnings, in the order they are found.
+    pub warnings: Vec<Error<'a>>,
+}
+
+/// Type check a program in strict mode.
+///
+/// On top of the type errors of [`check`](check), reports the reads of locals
+/// and statics that may happen before they are assigned, with the given
+/// level.
+pub fn check_strict<'a>(ast: &Ast<'a>, level: Level) -> Report<'a> {
+    let mut report = Report {
+        errors: check(ast),
+        warnings: Vec::new(),
+    };
+    let uninitialized = init::uninitialized(ast);
+    match level {
+        Level::Warn => report.warnings = uninitialized,
+        Level::Deny => report.errors.extend(uninitialized),
+    }
+    report
+}
+
+// type of a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    U8,
+    I8,
+    U16,
+    I16,
+    Bool,
+    // fx8.8
+    Fixed,
+    // integer constant (number literals, sizeof, constant expressions)
+    Int,
+    // string literal
+    Str,
+    Enum(String),
+    Array(Box<Self>, Option<u16>),
+    Pointer(Box<Self>),
+    Fn(Vec<Self>, Option<Box<Self>>),
+    Tuple(Vec<Self>),
+    // struct or union, with its (flattened) members
+    Struct(String, Vec<(String, Self)>),
+    // expressions that don't evaluate to a value (assignments, calls to
+    // functions with no return type, ...)
+    Void,
+    Unknown,
+}
+
+impl Ty {
+    // size of integer types, in bytes.
+    fn width(&self) -> Option<u16> {
+        match self {
+            Self::U8 | Self::I8 | Self::Bool | Self::Enum(_) => Some(1),
+            Self::U16 | Self::I16 => Some(2),
+            _ => None,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, Self::Int | Self::Unknown) || self.width().is_some()
+    }
+
+    fn is_pointer(&self) -> bool {
+        matches!(self, Self::Pointer(_) | Self::Fn(..))
+    }
+
+    // type as shown by hover tooltips, unless unknown.
+    fn hover(&self) -> Option<String> {
+        match self {
+            Self::Unknown => None,
+            Self::Int => Some("integer constant".to_string()),
+            Self::Str => Some("string literal".to_string()),
+            ty => Some(ty.to_string()),
+        }
+    }
+
+    // type as rendered in error messages.
+    fn describe(&self) -> String {
+        match self {
+            Self::Int => "integer constant".to_string(),
+            Self::Str => "string literal".to_string(),
+            Self::Void => "no value".to_string(),
+            ty => format!("`{}`", ty),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // space separated list of types
+        fn list(f: &mut fmt::Formatter<'_>, types: &[Ty]) -> fmt::Result {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", ty)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::U8 => write!(f, "u8"),
+            Self::I8 => write!(f, "i8"),
+            Self::U16 => write!(f, "u16"),
+            Self::I16 => write!(f, "i16"),
+            Self::Bool => write!(f, "bool"),
+            Self::Fixed => write!(f, "fx8.8"),
+            Self::Int | Self::Unknown => write!(f, "_"),
+            Self::Str => write!(f, "[u8 _]"),
+            Self::Enum(name) | Self::Struct(name, _) => write!(f, "{}", name),
+            Self::Array(inner, Some(len)) => write!(f, "[{} {}]", inner, len),
+            Self::Array(inner, None) => write!(f, "[{} _]", inner),
+            Self::Pointer(inner) => write!(f, "&{}", inner),
+            Self::Fn(args, ret) => {
+                write!(f, "fn(")?;
+                list(f, args)?;
+                write!(f, ")")?;
+                match ret {
+                    Some(ret) => write!(f, ":{}", ret),
+                    None => Ok(()),
+                }
+            }
+            Self::Tuple(inner) => {
+                write!(f, "(")?;
+                list(f, inner)?;
+                write!(f, ")")
+            }
+            Self::Void => write!(f, "()"),
+        }
+    }
+}
+
+// whether two types are the same, other than the unknown parts.
+fn same(a: &Ty, b: &Ty) -> bool {
+    match (a, b) {
+        (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+        (Ty::Array(a, a_len), Ty::Array(b, b_len)) => {
+            same(a, b) && (a_len.is_none() || b_len.is_none() || a_len == b_len)
+        }
+        (Ty::Pointer(a), Ty::Pointer(b)) => same(a, b),
+        (Ty::Fn(a_args, a_ret), Ty::Fn(b_args, b_ret)) => {
+            a_args.len() == b_args.len()
+                && a_args.iter().zip(b_args).all(|(a, b)| same(a, b))
+                && match (a_ret, b_ret) {
+                    (Some(a), Some(b)) => same(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        }
+        (Ty::Tuple(a), Ty::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (a, b) => a == b,
+    }
+}
+
+// whether a value of type `found` can be used where one of type `expected` is.
+fn coerces(found: &Ty, expected: &Ty) -> bool {
+    match (found, expected) {
+        (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+        (Ty::Int, expected) => {
+            *expected == Ty::Int || expected.width().is_some() || expected.is_pointer()
+        }
+        (Ty::Str, Ty::Array(inner, _)) | (Ty::Str, Ty::Pointer(inner)) => {
+            matches!(**inner, Ty::U8 | Ty::I8 | Ty::Unknown)
+        }
+        (Ty::Void, Ty::Void) => true,
+        (found, expected) if found.width().is_some() => found.width() == expected.width(),
+        (found, expected) => same(found, expected),
+    }
+}
+
+struct Checker<'a> {
+    // types of the visible names, innermost scope last
+    scopes: Vec<HashMap<String, Ty>>,
+    // names declared so far, in any scope
+    declared: HashSet<String>,
+    // paths that couldn't be resolved where they were used, with the modules
+    // they were used from. They may be declared later on.
+    unresolved: Vec<(Vec<String>, String, Span)>,
+    // type aliases and enums
+    types: HashMap<String, Ty>,
+    // values of the consts that could be evaluated
+    consts: HashMap<String, u16>,
+    // generic functions, and the generic parameter giving the length of each
+    // of their array arguments
+    generics: HashMap<String, Vec<Option<String>>>,
+    // generic parameters of the functions being checked, which can't be
+    // evaluated
+    params: Vec<String>,
+    // modules being checked, innermost last
+    modules: Vec<String>,
+    // return type of the function being checked
+    return_: Option<Ty>,
+    errors: Vec<Error<'a>>,
+    // location to find the type of, and the innermost node found there
+    probe: Option<(SourceId, [usize; 2])>,
+    probed: Option<(Span, Ty)>,
+}
+
+impl<'a> Checker<'a> {
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            declared: HashSet::new(),
+            unresolved: Vec::new(),
+            types: HashMap::new(),
+            consts: HashMap::new(),
+            generics: HashMap::new(),
+            params: Vec::new(),
+            modules: Vec::new(),
+            return_: None,
+            errors: Vec::new(),
+            probe: None,
+            probed: None,
+        }
+    }
+
+    fn mismatch(&mut self, span: Span, expected: String, found: &Ty) {
+        self.errors.push(Error::MismatchedTypes {
+            span,
+            expected,
+            found: found.describe(),
+        });
+    }
+
+    // name of a declaration of the module being checked.
+    fn declared_name(&self, name: &str) -> String {
+        let mut path = self.modules.clone();
+        path.push(name.to_string());
+        path.join("::")
+    }
+
+    // find a name from the innermost module to the outermost one.
+    fn find<T, F>(&self, name: &str, mut f: F) -> Option<T>
+    where
+        F: FnMut(&str) -> Option<T>,
+    {
+        (0..=self.modules.len()).rev().find_map(|i| {
+            let mut path = self.modules[..i].to_vec();
+            path.push(name.to_string());
+            f(&path.join("::"))
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Ty {
+        self.find(name, |name| {
+            self.scopes.iter().rev().find_map(|scope| scope.get(name))
+        })
+        .cloned()
+        .unwrap_or(Ty::Unknown)
+    }
+
+    // define a name (and the members of its type) in the innermost scope.
+    fn define(&mut self, name: String, ty: Ty) {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Ty::Struct(_, members) = &ty {
+            for (member, ty) in members {
+                let member = format!("{}::{}", name, member);
+                self.declared.insert(member.clone());
+                scope.insert(member, ty.clone());
+            }
+        }
+        self.declared.insert(name.clone());
+        scope.insert(name, ty);
+    }
+
+    // whether a node is located at the probed location, and no node was
+    // found there yet.
+    fn probes(&self, span: Span) -> bool {
+        let at = |(source, p): (SourceId, [usize; 2])| {
+            span.source == source && span.min <= p && p < span.max
+        };
+        self.probed.is_none() && self.probe.map(at).unwrap_or(false)
+    }
+
+    // record the type of a node located at the probed location. Nodes are
+    // checked before the ones enclosing them, so the first one is kept.
+    fn probe(&mut self, span: Span, ty: &Ty) {
+        if self.probes(span) {
+            self.probed = Some((span, ty.clone()));
+        }
+    }
+
+    // constant expressions aren't checked, but the nodes they are made of are
+    // still probed.
+    fn probe_constant(&mut self, expression: &Expression<'a>) {
+        struct Probe<'c, 'a>(&'c mut Checker<'a>);
+
+        impl<'a> ast::Visitor<'a> for Probe<'_, 'a> {
+            fn visit_expression(&mut self, node: &Expression<'a>) {
+                self.0.expression(node);
+            }
+        }
+
+        if self.probes(expression.span()) {
+            let (errors, unresolved) = (self.errors.len(), self.unresolved.len());
+            ast::visit::walk_expression(&mut Probe(self), expression);
+            self.errors.truncate(errors);
+            self.unresolved.truncate(unresolved);
+        }
+    }
+
+    // define the name of a field, and its bitfields.
+    fn define_field(&mut self, field: &Field<'a>, ty: Ty) {
+        self.probe(field.ident.span(), &ty);
+        let name = self.declared_name(field.ident.name());
+        for bit in field.bits.iter().flat_map(|b| &b.fields) {
+            self.define(format!("{}::{}", name, bit.ident.name()), ty.clone());
+        }
+        self.define(name, ty);
+    }
+
+    fn eval(&self, expression: &Expression<'a>) -> Option<u16> {
+        eval::eval(expression, &mut |expression| match expression {
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                if name.len() == 1 && self.params.iter().any(|p| p == name[0]) {
+                    return None;
+                }
+                self.find(&name.join("::"), |name| self.consts.get(name).copied())
+            }
+            _ => None,
+        })
+    }
+
+    fn resolve(&self, type_: &Type<'a>) -> Ty {
+        match type_ {
+            Type::U8(_) => Ty::U8,
+            Type::I8(_) => Ty::I8,
+            Type::U16(_) => Ty::U16,
+            Type::I16(_) => Ty::I16,
+            Type::Bool(_) => Ty::Bool,
+            Type::Fixed(_) => Ty::Fixed,
+            Type::Array(array) => {
+                Ty::Array(Box::new(self.resolve(&array.type_)), self.eval(&array.len))
+            }
+            Type::Pointer(pointer) => Ty::Pointer(Box::new(self.resolve(&pointer.type_))),
+            Type::Fn(fn_) => Ty::Fn(
+                fn_.args
+                    .iter()
+                    .flat_map(|a| &a.inner)
+                    .map(|type_| self.resolve(type_))
+                    .collect(),
+                fn_.fn_return
+                    .as_ref()
+                    .map(|r| Box::new(self.resolve(&r.type_))),
+            ),
+            Type::Tuple(tuple) => Ty::Tuple(tuple.inner.iter().map(|t| self.resolve(t)).collect()),
+            Type::Struct(struct_) => {
+                Ty::Struct("struct".to_string(), self.members(&struct_.fields))
+            }
+            Type::Union(union) => Ty::Struct("union".to_string(), self.members(&union.fields)),
+            Type::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                self.find(&name.join("::"), |name| self.types.get(name).cloned())
+                    .unwrap_or(Ty::Unknown)
+            }
+        }
+    }
+
+    // members of a struct or union type, including the ones of its nested
+    // types (`field::member`), and its bitfields (`field::bitfield`).
+    fn members(&self, members: &[types::Member<'a>]) -> Vec<(String, Ty)> {
+        let mut flat = Vec::new();
+        for member in members {
+            match member {
+                types::Member::Field(field) => {
+                    let name = field.ident.name();
+                    let ty = self.resolve(&field.type_);
+                    if let Ty::Struct(_, members) = &ty {
+                        for (member, ty) in members {
+                            flat.push((format!("{}::{}", name, member), ty.clone()));
+                        }
+                    }
+                    for bit in field.bits.iter().flat_map(|b| &b.fields) {
+                        flat.push((format!("{}::{}", name, bit.ident.name()), ty.clone()));
+                    }
+                    flat.push((name.to_string(), ty));
+                }
+                // the fields of anonymous members belong to the enclosing type
+                types::Member::Struct(struct_) => flat.extend(self.members(&struct_.fields)),
+                types::Member::Union(union) => flat.extend(self.members(&union.fields)),
+            }
+        }
+        flat
+    }
+
+    fn statements(&mut self, statements: &[Statement<'a>]) {
+        // functions can be called before their definition
+        for statement in statements {
+            if let Statement::Fn(fn_) = statement {
+                let params = generic_params(fn_);
+                let depth = self.params.len();
+                self.params.extend(params.iter().cloned());
+                let args = fn_
+                    .fn_arg
+                    .iter()
+                    .flat_map(|a| &a.inner)
+                    .map(|field| self.resolve(&field.type_))
+                    .collect();
+                let ret = fn_
+                    .fn_return
+                    .as_ref()
+                    .map(|r| Box::new(self.resolve(&r.type_)));
+                self.params.truncate(depth);
+                let name = self.declared_name(fn_.ident.name());
+                if !params.is_empty() {
+                    let lengths = fn_
+                        .fn_arg
+                        .iter()
+                        .flat_map(|a| &a.inner)
+                        .map(|field| length_param(&field.type_, &params))
+                        .collect();
+                    self.generics.insert(name.clone(), lengths);
+                }
+                let ty = Ty::Fn(args, ret);
+                self.probe(fn_.ident.span(), &ty);
+                self.define(name, ty);
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement<'a>]) {
+        self.scopes.push(HashMap::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) {
+        match statement {
+            Statement::If(if_) => {
+                self.coerce(&if_.expression, &Ty::Bool);
+                self.block(&if_.inner);
+            }
+            Statement::IfElse(if_else) => {
+                self.coerce(&if_else.if_.expression, &Ty::Bool);
+                self.block(&if_else.if_.inner);
+                self.block(&if_else.else_.inner);
+            }
+            Statement::IfConst(if_const) => self.statements(if_const.statements()),
+            Statement::Scope(scope) => self.block(&scope.inner),
+            Statement::Defer(defer) => self.block(&defer.inner),
+            Statement::Mod(mod_) => {
+                self.modules.push(mod_.ident.name().to_string());
+                self.statements(&mod_.inner);
+                self.modules.pop();
+            }
+            Statement::Import(import) => {
+                self.modules.push(import.name());
+                self.statements(&import.inner);
+                self.modules.pop();
+            }
+            Statement::MacroCall(call) => self.block(&call.inner),
+            Statement::Static(static_) => {
+                let ty = self.resolve(&static_.field.type_);
+                if let Some(init) = &static_.init {
+                    self.coerce(&init.expression, &ty);
+                }
+                self.define_field(&static_.field, ty);
+            }
+            Statement::Const(const_) => {
+                let ty = self.resolve(&const_.field.type_);
+                self.coerce(&const_.expression, &ty);
+                if let Some(value) = self.eval(&const_.expression) {
+                    let name = self.declared_name(const_.field.ident.name());
+                    self.consts.insert(name, value);
+                }
+                self.define_field(&const_.field, ty);
+            }
+            Statement::Enum(enum_) => {
+                let name = self.declared_name(enum_.ident.name());
+                let ty = Ty::Enum(name.clone());
+                for variant in &enum_.variants {
+                    if let Some(discriminant) = &variant.discriminant {
+                        self.coerce(&discriminant.expression, &Ty::U8);
+                    }
+                    self.define(format!("{}::{}", name, variant.ident.name()), ty.clone());
+                }
+                self.types.insert(name, ty);
+            }
+            Statement::TypeAlias(alias) => {
+                let name = self.declared_name(alias.ident.name());
+                let ty = match self.resolve(&alias.inner) {
+                    Ty::Struct(_, members) => Ty::Struct(name.clone(), members),
+                    ty => ty,
+                };
+                self.types.insert(name, ty);
+            }
+            Statement::Let(let_) => {
+                let ty = self.resolve(&let_.field.type_);
+                self.coerce(&let_.expression, &ty);
+                self.define_field(&let_.field, ty);
+            }
+            Statement::LetTuple(let_) => {
+                let types: Vec<_> = let_.fields.iter().map(|f| self.resolve(&f.type_)).collect();
+                self.coerce(&let_.expression, &Ty::Tuple(types.clone()));
+                for (field, ty) in let_.fields.iter().zip(types) {
+                    self.define_field(field, ty);
+                }
+            }
+            Statement::For(for_) => {
+                let ty = self.resolve(&for_.field.type_);
+                self.coerce(&for_.range.left, &ty);
+                self.coerce(&for_.range.right, &ty);
+                self.scopes.push(HashMap::new());
+                self.define_field(&for_.field, ty);
+                self.statements(&for_.inner);
+                self.scopes.pop();
+            }
+            Statement::Loop(loop_) => self.block(&loop_.inner),
+            Statement::While(while_) => {
+                self.coerce(&while_.expression, &Ty::Bool);
+                self.block(&while_.inner);
+            }
+            Statement::Match(match_) => {
+                let ty = self.expression(&match_.expression);
+                for arm in &match_.arms {
+                    match &arm.pattern {
+                        Pattern::Expression(expression) => self.coerce(expression, &ty),
+                        Pattern::Range(range) => {
+                            self.coerce(&range.left, &ty);
+                            self.coerce(&range.right, &ty);
+                        }
+                    }
+                    self.block(&arm.inner);
+                }
+                if let Some(else_) = &match_.else_ {
+                    self.block(&else_.inner);
+                }
+            }
+            Statement::Inline(inline) => {
+                self.expression(&inline.inner);
+            }
+            Statement::Fn(fn_) => {
+                let depth = self.params.len();
+                self.params.extend(generic_params(fn_));
+                let return_ = fn_
+                    .fn_return
+                    .as_ref()
+                    .map_or(Ty::Void, |r| self.resolve(&r.type_));
+                let parent = self.return_.replace(return_);
+                self.scopes.push(HashMap::new());
+                for param in fn_.generics.iter().flat_map(|g| &g.params) {
+                    let ty = self.resolve(&param.field.type_);
+                    if !ty.is_integer() {
+                        self.mismatch(param.field.type_.span(), "integer".to_string(), &ty);
+                    }
+                    self.define_field(&param.field, ty);
+                }
+                for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+                    let ty = self.resolve(&field.type_);
+                    self.define_field(field, ty);
+                }
+                self.statements(&fn_.inner);
+                self.scopes.pop();
+                self.return_ = parent;
+                self.params.truncate(depth);
+                if fn_.fn_return.is_some() && !lint::block_diverges(&fn_.inner) {
+                    self.errors.push(Error::MissingReturn {
+                        ident: fn_.ident.clone(),
+                        span: fn_.right_bracket.span(),
+                    });
+                }
+            }
+            Statement::Return(return_) => match (&return_.expression, self.return_.clone()) {
+                (Some(expression), Some(ty)) => self.coerce(expression, &ty),
+                (None, Some(ty)) if ty != Ty::Void => {
+                    self.mismatch(return_.span(), ty.describe(), &Ty::Void)
+                }
+                _ => {}
+            },
+            Statement::Panic(_)
+            | Statement::Macro(_)
+            | Statement::Memory(_)
+            | Statement::StaticAssert(_)
+            | Statement::Continue(_)
+            | Statement::Break(_)
+            | Statement::Error(_) => {}
+        }
+    }
+
+    // check that an expression can be used where a value of the expected type
+    // is.
+    fn coerce(&mut self, expression: &Expression<'a>, expected: &Ty) {
+        use Expression as E;
+        match (expression, expected) {
+            (E::Array(array), Ty::Array(inner, len)) => {
+                match len {
+                    Some(len) if *len as usize != array.inner.len() => {
+                        let found = Ty::Array(inner.clone(), Some(array.inner.len() as u16));
+                        self.mismatch(expression.span(), expected.describe(), &found);
+                    }
+                    _ => {}
+                }
+                for expression in &array.inner {
+                    self.coerce(expression, inner);
+                }
+            }
+            (E::Array(array), Ty::Tuple(inner)) if array.inner.len() == inner.len() => {
+                for (expression, ty) in array.inner.iter().zip(inner) {
+                    self.coerce(expression, ty);
+                }
+            }
+            (E::StructLit(lit), Ty::Struct(_, members)) => {
+                for field in &lit.fields {
+                    let name = field.ident.name();
+                    match members.iter().find(|(member, _)| member == name) {
+                        Some((_, ty)) => self.coerce(&field.expression, ty),
+                        None => self.errors.push(Error::MismatchedTypes {
+                            span: field.ident.span(),
+                            expected: format!("field of {}", expected.describe()),
+                            found: format!("`{}`", name),
+                        }),
+                    }
+                }
+            }
+            (E::Conditional(node), _) => {
+                self.coerce(&node.inner.condition, &Ty::Bool);
+                self.coerce(&node.inner.then, expected);
+                self.coerce(&node.inner.else_, expected);
+            }
+            _ => {
+                let found = self.expression(expression);
+                if !coerces(&found, expected) {
+                    self.mismatch(expression.span(), expected.describe(), &found);
+                } else if found == Ty::Int {
+                    self.range(expression, expected);
+                }
+            }
+        }
+    }
+
+    // check that the value of an integer constant fits in the integer type it
+    // is used as.
+    fn range(&mut self, expression: &Expression<'a>, ty: &Ty) {
+        let name = match ty {
+            Ty::U8 => "u8",
+            Ty::I8 => "i8",
+            Ty::U16 => "u16",
+            Ty::I16 => "i16",
+            _ => return,
+        };
+        if let Err(error) = types::check_int(name, expression, &|e| self.eval(e)) {
+            self.errors.push(error);
+        }
+    }
+
+    // check that an expression is an integer.
+    // Returns its type, or `Unknown` if it isn't an integer.
+    fn integer(&mut self, expression: &Expression<'a>) -> Ty {
+        let ty = self.expression(expression);
+        if ty.is_integer() {
+            ty
+        } else {
+            self.mismatch(expression.span(), "integer".to_string(), &ty);
+            Ty::Unknown
+        }
+    }
+
+    // type of an arithmetic (or bitwise) operation.
+    // Both operands are integers of the same width.
+    fn arithmetic(&mut self, left: &Expression<'a>, right: &Expression<'a>) -> Ty {
+        let left_ty = self.integer(left);
+        self.arithmetic_of(left_ty, right)
+    }
+
+    // type of an arithmetic operation with a left operand of the given type.
+    fn arithmetic_of(&mut self, left_ty: Ty, right: &Expression<'a>) -> Ty {
+        let right_ty = self.integer(right);
+        if right_ty == Ty::Int {
+            self.range(right, &left_ty);
+        }
+        match (&left_ty, &right_ty) {
+            (Ty::Int, _) => right_ty,
+            (_, Ty::Int) | (_, Ty::Unknown) | (Ty::Unknown, _) => left_ty,
+            _ if left_ty.width() == right_ty.width() => left_ty,
+            _ => {
+                self.mismatch(right.span(), left_ty.describe(), &right_ty);
+                Ty::Unknown
+            }
+        }
+    }
+
+    // type of a comparison. Both operands are integers of the same width, or
+    // pointers.
+    fn compare(&mut self, left: &Expression<'a>, right: &Expression<'a>) -> Ty {
+        let left_ty = self.expression(left);
+        if left_ty.is_pointer() {
+            self.coerce(right, &left_ty);
+        } else if left_ty.is_integer() {
+            self.arithmetic_with(left_ty, right);
+        } else if left_ty == Ty::Fixed {
+            self.coerce(right, &left_ty);
+        } else {
+            self.mismatch(left.span(), "integer or pointer".to_string(), &left_ty);
+        }
+        Ty::Bool
+    }
+
+    // check the right operand of an arithmetic operation with a left operand
+    // of the given type.
+    fn arithmetic_with(&mut self, left_ty: Ty, right: &Expression<'a>) {
+        let right_ty = self.integer(right);
+        if right_ty == Ty::Int {
+            self.range(right, &left_ty);
+        }
+        if left_ty.width().is_some()
+            && right_ty.width().is_some()
+            && left_ty.width() != right_ty.width()
+        {
+            self.mismatch(right.span(), left_ty.describe(), &right_ty);
+        }
+    }
+
+    // type of a pointer arithmetic operation (`(+ ptr n)`), if the left
+    // operand is a pointer.
+    fn pointer_arithmetic(&mut self, left: &Expression<'a>, right: &Expression<'a>) -> Ty {
+        match self.expression(left) {
+            ty @ Ty::Pointer(_) => {
+                self.integer(right);
+                ty
+            }
+            Ty::Fixed => {
+                self.coerce(right, &Ty::Fixed);
+                Ty::Fixed
+            }
+            ty if ty.is_integer() => {
+                self.arithmetic_with(ty.clone(), right);
+                match ty {
+                    Ty::Int => self.expression(right),
+                    ty => ty,
+                }
+            }
+            ty => {
+                self.mismatch(left.span(), "integer or pointer".to_string(), &ty);
+                self.expression(right);
+                Ty::Unknown
+            }
+        }
+    }
+
+    // type of a product (or quotient). Fixed-point values are multiplied by
+    // words and by other fixed-point values (if `fixed` is set), but they are
+    // only divided by words.
+    fn product(&mut self, left: &Expression<'a>, right: &Expression<'a>, fixed: bool) -> Ty {
+        let left_ty = self.expression(left);
+        if left_ty != Ty::Fixed {
+            let left_ty = if left_ty.is_integer() {
+                left_ty
+            } else {
+                self.mismatch(left.span(), "integer".to_string(), &left_ty);
+                Ty::Unknown
+            };
+            return self.arithmetic_of(left_ty, right);
+        }
+        let right_ty = self.expression(right);
+        let word = right_ty.is_integer() && coerces(&right_ty, &Ty::U16);
+        if !word && (!fixed || right_ty != Ty::Fixed) {
+            let expected = if fixed { "`u16` or `fx8.8`" } else { "`u16`" };
+            self.mismatch(right.span(), expected.to_string(), &right_ty);
+        }
+        Ty::Fixed
+    }
+
+    // check a compound assignment (`(+= left right)`).
+    fn assign_arithmetic(&mut self, left: &Expression<'a>, right: &Expression<'a>) -> Ty {
+        let left_ty = self.integer(left);
+        self.arithmetic_with(left_ty, right);
+        Ty::Void
+    }
+
+    fn expression(&mut self, expression: &Expression<'a>) -> Ty {
+        let ty = self.expression_ty(expression);
+        if self.probe.is_some() {
+            // the declared type of consts, rather than the type of their value
+            let declared = match expression {
+                Expression::Path(path) if Builtin::from_path(path).is_none() => {
+                    let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                    Some(self.lookup(&name.join("::"))).filter(|ty| *ty != Ty::Unknown)
+                }
+                _ => None,
+            };
+            self.probe(expression.span(), declared.as_ref().unwrap_or(&ty));
+        }
+        ty
+    }
+
+    fn expression_ty(&mut self, expression: &Expression<'a>) -> Ty {
+        use Expression as E;
+        // constant expressions are evaluated at compile time
+        if !matches!(expression, E::Lit(_)) && self.eval(expression).is_some() {
+            self.probe_constant(expression);
+            return Ty::Int;
+        }
+        match expression {
+            E::Path(path) => match Builtin::from_path(path) {
+                Some(builtin) => builtin_signature(builtin),
+                None => {
+                    let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                    let name = name.join("::");
+                    if self.find(&name, |name| self.declared.get(name)).is_none() {
+                        self.unresolved
+                            .push((self.modules.clone(), name.clone(), path.span()));
+                    }
+                    self.lookup(&name)
+                }
+            },
+            E::Lit(lit) if lit.bool_value().is_some() => Ty::Bool,
+            E::Lit(lit) if lit.char_value().is_some() => Ty::U8,
+            E::Lit(lit) if lit.str_value().is_some() => Ty::Str,
+            E::Lit(lit) if lit.is_fixed() => Ty::Fixed,
+            E::Lit(_) => Ty::Int,
+            E::Array(array) => {
+                for expression in &array.inner {
+                    self.expression(expression);
+                }
+                Ty::Array(Box::new(Ty::Unknown), Some(array.inner.len() as u16))
+            }
+            E::StructLit(lit) => {
+                for field in &lit.fields {
+                    self.expression(&field.expression);
+                }
+                Ty::Unknown
+            }
+            E::Minus(node) => self.integer(&node.inner),
+            E::Not(node) => self.integer(&node.inner),
+            E::AddressOf(node) => match self.expression(&node.inner) {
+                // the address of a function is a function pointer
+                ty @ Ty::Fn(..) => ty,
+                ty => Ty::Pointer(Box::new(ty)),
+            },
+            E::Deref(node) => match self.expression(&node.inner) {
+                Ty::Pointer(inner) => *inner,
+                Ty::Unknown => Ty::Unknown,
+                ty => {
+                    self.mismatch(node.inner.span(), "pointer".to_string(), &ty);
+                    Ty::Unknown
+                }
+            },
+            E::Increment(node) => self.step(&node.inner.inner),
+            E::Decrement(node) => self.step(&node.inner.inner),
+            E::Conditional(node) => {
+                self.coerce(&node.inner.condition, &Ty::Bool);
+                let then = self.expression(&node.inner.then);
+                let else_ = self.expression(&node.inner.else_);
+                if coerces(&else_, &then) && then != Ty::Int {
+                    then
+                } else if coerces(&then, &else_) {
+                    else_
+                } else {
+                    self.mismatch(node.inner.else_.span(), then.describe(), &else_);
+                    Ty::Unknown
+                }
+            }
+            E::Cast(node) => {
+                let ty = self.expression(&node.inner.inner);
+                if !ty.is_integer() && !ty.is_pointer() && ty != Ty::Fixed {
+                    let span = node.inner.inner.span();
+                    self.mismatch(span, "integer or pointer".to_string(), &ty);
+                }
+                self.resolve(&node.inner.type_)
+            }
+            E::SizeOf(_) => Ty::Int,
+            E::Add(node) => self.pointer_arithmetic(&node.inner.left, &node.inner.right),
+            E::Sub(node) => self.pointer_arithmetic(&node.inner.left, &node.inner.right),
+            E::Mul(node) => self.product(&node.inner.left, &node.inner.right, true),
+            E::Div(node) => self.product(&node.inner.left, &node.inner.right, false),
+            E::And(node) => self.arithmetic(&node.inner.left, &node.inner.right),
+            E::Or(node) => self.arithmetic(&node.inner.left, &node.inner.right),
+            E::Xor(node) => self.arithmetic(&node.inner.left, &node.inner.right),
+            // the shift amount is a byte
+            E::LeftShift(node) => {
+                self.coerce(&node.inner.right, &Ty::U8);
+                self.integer(&node.inner.left)
+            }
+            E::RightShift(node) => {
+                self.coerce(&node.inner.right, &Ty::U8);
+                self.integer(&node.inner.left)
+            }
+            E::Eq(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::NotEq(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::LessEq(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::GreaterEq(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::Less(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::Greater(node) => self.compare(&node.inner.left, &node.inner.right),
+            E::LogicalAnd(node) => {
+                self.coerce(&node.inner.left, &Ty::Bool);
+                self.coerce(&node.inner.right, &Ty::Bool);
+                Ty::Bool
+            }
+            E::LogicalOr(node) => {
+                self.coerce(&node.inner.left, &Ty::Bool);
+                self.coerce(&node.inner.right, &Ty::Bool);
+                Ty::Bool
+            }
+            E::Assign(node) => {
+                let ty = self.expression(&node.inner.left);
+                self.coerce(&node.inner.right, &ty);
+                Ty::Void
+            }
+            E::PlusAssign(node) => {
+                self.pointer_arithmetic(&node.inner.left, &node.inner.right);
+                Ty::Void
+            }
+            E::MinusAssign(node) => {
+                self.pointer_arithmetic(&node.inner.left, &node.inner.right);
+                Ty::Void
+            }
+            E::MulAssign(node) => {
+                self.product(&node.inner.left, &node.inner.right, true);
+                Ty::Void
+            }
+            E::DivAssign(node) => {
+                self.product(&node.inner.left, &node.inner.right, false);
+                Ty::Void
+            }
+            E::AndAssign(node) => self.assign_arithmetic(&node.inner.left, &node.inner.right),
+            E::OrAssign(node) => self.assign_arithmetic(&node.inner.left, &node.inner.right),
+            E::XorAssign(node) => self.assign_arithmetic(&node.inner.left, &node.inner.right),
+            // `([<index>] <array>)`
+            E::Index(node) => {
+                self.coerce(&node.inner.left, &Ty::U8);
+                match self.expression(&node.inner.right) {
+                    Ty::Array(inner, _) | Ty::Pointer(inner) => *inner,
+                    Ty::Str => Ty::U8,
+                    Ty::Unknown => Ty::Unknown,
+                    ty => {
+                        let span = node.inner.right.span();
+                        self.mismatch(span, "array or pointer".to_string(), &ty);
+                        Ty::Unknown
+                    }
+                }
+            }
+            E::Call(node) => match self.expression(&node.inner.left) {
+                Ty::Fn(args, ret) => {
+                    if args.len() == node.inner.args.len() {
+                        for (expression, ty) in node.inner.args.iter().zip(&args) {
+                            self.coerce(expression, ty);
+                        }
+                        if let Some(lengths) = self.generic(&node.inner.left) {
+                            self.generic_call(&lengths, &args, &node.inner.args);
+                        }
+                    } else {
+                        self.errors.push(Error::ArgCount {
+                            span: expression.span(),
+                            expected: args.len(),
+                            found: node.inner.args.len(),
+                        });
+                    }
+                    ret.map_or(Ty::Void, |ret| *ret)
+                }
+                ty => {
+                    if ty != Ty::Unknown {
+                        let span = node.inner.left.span();
+                        self.mismatch(span, "function".to_string(), &ty);
+                    }
+                    for expression in &node.inner.args {
+                        self.expression(expression);
+                    }
+                    Ty::Unknown
+                }
+            },
+        }
+    }
+
+    // generic function named by a path expression, if any.
+    fn generic(&self, expression: &Expression<'a>) -> Option<Vec<Option<String>>> {
+        match expression {
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                self.find(&name.join("::"), |name| self.generics.get(name).cloned())
+            }
+            _ => None,
+        }
+    }
+
+    // check that the array arguments of a call to a generic function agree on
+    // the value of the generic parameters giving their lengths.
+    fn generic_call(&mut self, lengths: &[Option<String>], args: &[Ty], call: &[Expression<'a>]) {
+        let mut values: HashMap<&str, u16> = HashMap::new();
+        for ((param, ty), expression) in lengths.iter().zip(args).zip(call) {
+            let (param, len) = match (param, self.array_len(expression)) {
+                (Some(param), Some(len)) => (param, len),
+                _ => continue,
+            };
+            match values.get(param.as_str()) {
+                Some(&value) if value != len => {
+                    let inner = match ty {
+                        Ty::Array(inner, _) => inner.clone(),
+                        _ => Box::new(Ty::Unknown),
+                    };
+                    let expected = Ty::Array(inner.clone(), Some(value)).describe();
+                    self.mismatch(expression.span(), expected, &Ty::Array(inner, Some(len)));
+                }
+                _ => {
+                    values.insert(param, len);
+                }
+            }
+        }
+    }
+
+    // length of an array (named, or literal) expression, if known.
+    fn array_len(&self, expression: &Expression<'a>) -> Option<u16> {
+        match expression {
+            Expression::Array(array) => Some(array.inner.len() as u16),
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                match self.lookup(&name.join("::")) {
+                    Ty::Array(_, len) => len,
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // check an increment or decrement of an integer or pointer.
+    fn step(&mut self, expression: &Expression<'a>) -> Ty {
+        let ty = self.expression(expression);
+        if !ty.is_integer() && !matches!(ty, Ty::Pointer(_)) {
+            self.mismatch(expression.span(), "integer or pointer".to_string(), &ty);
+        }
+        Ty::Void
+    }
+}
+
+// signature of a builtin function.
+fn builtin_signature(builtin: Builtin) -> Ty {
+    let pointer = || Ty::Pointer(Box::new(Ty::Unknown));
+    match builtin {
+        Builtin::Memcpy => Ty::Fn(vec![pointer(), pointer(), Ty::U16], None),
+        Builtin::Memset => Ty::Fn(vec![pointer(), Ty::U8, Ty::U16], None),
+        Builtin::SwapNibbles | Builtin::BcdAdjust => Ty::Fn(vec![Ty::U8], Some(Box::new(Ty::U8))),
+        Builtin::Halt | Builtin::EnableInterrupts => Ty::Fn(Vec::new(), None),
+    }
+}
+
+// names of the generic parameters of a function.
+fn generic_params(fn_: &ast::Fn<'_>) -> Vec<String> {
+    fn_.generics
+        .iter()
+        .flat_map(|g| &g.params)
+        .map(|param| param.field.ident.name().to_string())
+        .collect()
+}
+
+// generic parameter giving the length of an array type, if any.
+fn length_param(type_: &Type<'_>, params: &[String]) -> Option<String> {
+    match type_ {
+        Type::Array(array) => match &array.len {
+            Expression::Path(path) => {
+                let name: Vec<_> = path.iter().map(|i| i.name()).collect();
+                let name = name.join("::");
+                params.iter().find(|p| **p == name).cloned()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+
+    fn check(input: &str) -> Vec<String> {
+        let ast = crate::parse(input).unwrap();
+        super::check(&ast).iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn widths() {
+        let input = "static A:u8\nstatic B:i8\nstatic C:u16\nstatic D:bool\n\
+                     (= A B)\n(= A D)\n(= A (+ B 100))\n(= C (+ C 1))";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec!["Mismatched types: expected `u8`, found `u16`"],
+            check("static A:u8\nstatic C:u16\n(= A (+ A C))")
+        );
+        assert_eq!(
+            vec!["Mismatched types: expected `u16`, found `u8`"],
+            check("static A:u8\nlet c:u16 = A")
+        );
+        assert!(check("static A:u8\nstatic C:u16\n(= C (as A u16))").is_empty());
+        assert!(check("const A:u8 = 2\nconst C:u16 = (* A 0x1000)").is_empty());
+    }
+
+    #[test]
+    fn ranges() {
+        let out_of_range = |value: &str| format!("Literal out of range for `u8`: {}", value);
+        let input = "static R:u8\nstatic S:i8\nstatic W:u16\nconst C:u16 = 0x100\n\
+                     fn f(a:u8):u8 { return 256 }\n\
+                     (= R 300)\n(+= R 256)\n(= R (+ 200 100))\n(= R C)\n(f 0x100)\n\
+                     (= S (- 0 129))\n(= W (+ 200 100))\n(= R (+ 200 55))";
+        assert_eq!(
+            vec![
+                out_of_range("256"),
+                out_of_range("300"),
+                out_of_range("256"),
+                out_of_range("300"),
+                out_of_range("256"),
+                out_of_range("0x100"),
+                "Literal out of range for `i8`: -129".to_string(),
+            ],
+            check(input)
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        assert!(check("static A:u8\nif (== A 1) {}\nwhile A {}").is_empty());
+        assert!(check("static A:u8\nstatic C:u16\n(= C (if A 0x1234 0))").is_empty());
+        assert_eq!(
+            vec!["Mismatched types: expected `bool`, found `u16`"],
+            check("static C:u16\nif C {}")
+        );
+    }
+
+    #[test]
+    fn pointers() {
+        let input = "static A:u8\nstatic P:&u8\nstatic Q:&u16\n\
+                     (= P @A)\n(= A *P)\n(+= P 2)\n(= P (+ P A))";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec![
+                "Mismatched types: expected `&u16`, found `&u8`",
+                "Mismatched types: expected pointer, found `u8`",
+            ],
+            check("static A:u8\nstatic P:&u8\nstatic Q:&u16\n(= Q P)\n(= A *A)")
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let input = "static A:[u8 4] = [1 2 3 4]\nstatic I:u8\nstatic S:[u8 5] = \"HELLO\"\n\
+                     let p:&u8 = \"HELLO\"\n(= I ([I] A))\n(= I ([2] S))";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec![
+                "Mismatched types: expected `[u8 4]`, found `[u8 3]`",
+                "Mismatched types: expected `u8`, found `u16`",
+                "Mismatched types: expected array or pointer, found `u8`",
+            ],
+            check("static A:[u8 4] = [1 2 3]\nstatic I:u16\nstatic B:u8\n(= B ([I] A))\n(= B ([0] B))")
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let input = "fn add(a:u8 b:u8):u8 { return (+ a b) }\nstatic F:fn(u8 u8):u8\n\
+                     static A:u8\n(= A (add 1 2))\n(= F @add)\n(= A (F A A))";
+        assert!(check(input).is_empty());
+        let errors = check(
+            "fn add(a:u8 b:u8):u8 { return (+ a b) }\nstatic A:u16\n\
+             (= A (add 1 2))\n(add A 1)\n(add 1)\n(A 1)",
+        );
+        assert_eq!(
+            vec![
+                "Mismatched types: expected `u16`, found `u8`",
+                "Mismatched types: expected `u8`, found `u16`",
+                "Expected 2 arguments, found 1",
+                "Mismatched types: expected function, found `u16`",
+            ],
+            errors
+        );
+        assert_eq!(
+            vec!["Mismatched types: expected `u8`, found no value"],
+            check("fn f() {}\nlet a:u8 = (f)")
+        );
+    }
+
+    #[test]
+    fn returns() {
+        let input = "static A:u8\n\
+                     fn f():u8 { if A { return 1 } else { return 2 } }\n\
+                     fn g():u8 { loop { } }\nfn h():u8 { (= A 1) !! }\n\
+                     fn k() { if A { return } }";
+        assert!(check(input).is_empty());
+        let ast = crate::parse("static A:u8\nfn f():u8 {\n    if A { return 1 }\n}").unwrap();
+        let errors = super::check(&ast);
+        assert_eq!("Missing return in function `f`", errors[0].to_string());
+        assert_eq!("E0021", errors[0].code());
+        assert_eq!([3, 0], errors[0].span().unwrap().min);
+        assert_eq!(
+            vec!["Missing return in function `f`"],
+            check("fn f():u8 { loop { break } }")
+        );
+        assert_eq!(
+            vec!["Mismatched types: expected no value, found integer constant"],
+            check("fn f() { return 1 }")
+        );
+    }
+
+    #[test]
+    fn generics() {
+        let input = "fn sum<const N:u8>(xs:[u8 N]):u8 {\n\
+                         let s:u8 = 0\n\
+                         for i:u8 in 0..N { (+= s ([i] xs)) }\n\
+                         return s\n\
+                     }\n\
+                     static A:[u8 4]\nstatic B:[u8 8]\n(sum A)\n(sum B)\n(sum [1 2 3])";
+        assert!(check(input).is_empty());
+        let input = "fn dot<const N:u8>(a:[u8 N] b:[u8 N]):u8 { return ([0] a) }\n\
+                     static A:[u8 4]\nstatic B:[u8 3]\n(dot A A)\n(dot A B)";
+        assert_eq!(
+            vec!["Mismatched types: expected `[u8 4]`, found `[u8 3]`"],
+            check(input)
+        );
+        assert_eq!(
+            vec!["Mismatched types: expected integer, found `&u8`"],
+            check("fn f<const P:&u8>() { }")
+        );
+    }
+
+    #[test]
+    fn builtins() {
+        let input = "static A:[u8 4]\nstatic B:[u8 4]\nstatic X:u8\n\
+                     (builtin::memcpy @A @B 4)\n(builtin::memset @A 0 (sizeof A))\n\
+                     (= X (builtin::swap_nibbles (builtin::bcd_adjust X)))\n\
+                     (builtin::enable_interrupts)\n(builtin::halt)";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec![
+                "Expected 3 arguments, found 2",
+                "Mismatched types: expected `u8`, found `u16`",
+                "Mismatched types: expected `&_`, found `u8`",
+            ],
+            check(
+                "static W:u16\nstatic X:u8\n(builtin::memset @X 0)\n\
+                 (builtin::swap_nibbles W)\n(builtin::memcpy X @X 1)"
+            )
+        );
+    }
+
+    #[test]
+    fn fixed() {
+        let input = "static X:fx8.8 = 1.5fx\nstatic V:fx8.8\nstatic N:u8\nstatic W:u16\n\
+                     (= X (+ X (* V 0.5fx)))\n(*= V 2)\n(/= V W)\n\
+                     (= N (as X u8))\n(= V (as N fx8.8))\nlet b:bool = (< X V)";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec![
+                "Mismatched types: expected `fx8.8`, found integer constant",
+                "Mismatched types: expected `fx8.8`, found `u16`",
+                "Mismatched types: expected `u16`, found `fx8.8`",
+                "Mismatched types: expected `u16` or `fx8.8`, found `u8`",
+                "Mismatched types: expected `u8`, found `fx8.8`",
+            ],
+            check(
+                "static X:fx8.8\nstatic W:u16\nstatic N:u8\n(= X 1)\n(= X W)\n\
+                 (= X (/ X 0.5fx))\n(= X (* X N))\nlet n:u8 = 1.5fx"
+            )
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let input = "type Point = struct { x:u8, y:u8, flags:u8 .{ a:1 b:1 } }\n\
+                     static P:Point\nstatic Q:Point\nstatic T:(u8 u16)\n\
+                     (= P { x:1, y:2 })\n(= P::x P::flags::a)\n(= P Q)\n(= T [1 2])";
+        assert!(check(input).is_empty());
+        assert_eq!(
+            vec![
+                "Mismatched types: expected field of `Point`, found `z`",
+                "Mismatched types: expected `(u8 u8)`, found `[_ 3]`",
+            ],
+            check("type Point = struct { x:u8 }\nstatic P:Point\n(= P { z:1 })\nlet t:(u8 u8) = [1 2 3]")
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        // names the checker can't resolve are not reported
+        assert!(check("(= FOO (+ BAR 1))\n(baz 1 2 3)").is_empty());
+    }
+
+    #[test]
+    fn strict() {
+        let ast = crate::parse("static A:u8\nstatic C:u16\n(= A C)").unwrap();
+        let report = super::check_strict(&ast, super::Level::Deny);
+        assert_eq!(
+            vec!["E0016", "E0018"],
+            report.errors.iter().map(|e| e.code()).collect::<Vec<_>>()
+        );
+        assert!(report.warnings.is_empty());
+        let report = super::check_strict(&ast, super::Level::Warn);
+        assert_eq!(1, report.errors.len());
+        assert_eq!(1, report.warnings.len());
+    }
+
+    #[test]
+    fn error() {
+        let ast = crate::parse("static A:u8\nstatic C:u16\n(= A C)").unwrap();
+        let errors = super::check(&ast);
+        assert!(matches!(&errors[0], Error::MismatchedTypes { .. }));
+        assert_eq!("E0016", errors[0].code());
+        assert_eq!([2, 5], errors[0].span().unwrap().min);
+    }
+
+    #[test]
+    fn extern_symbols() {
+        let mut context = crate::ContextBuilder::default()
+            .extern_symbol("LCDC", "u8")
+            .extern_symbol("rand", "fn():u8")
+            .unlinked_symbol("memcmp", "runtime")
+            .build();
+        let input = "static A:u16\n(= A (rand))\n(= LCDC A)\n(= A B)\n(memcmp)\n\
+                     fn f() { (= A (+ C D)) }\nstatic C:u16\n\
+                     mod m { static D:u16 }\n(= A m::D)";
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        let errors: Vec<_> = super::check_with_context(&ast, &context)
+            .into_iter()
+            .map(|e| match e {
+                Error::UndefinedSymbol { name, library, .. } => {
+                    format!("{} {:?}", name, library)
+                }
+                e => e.to_string(),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "Mismatched types: expected `u16`, found `u8`",
+                "Mismatched types: expected `u8`, found `u16`",
+                "B None",
+                "memcmp Some(\"runtime\")",
+                "D None",
+            ],
+            errors
+        );
+
+        // without a context, the types of undeclared symbols are unknown
+        assert!(super::check(&ast).is_empty());
+    }
+}
@@ -0,0 +1,585 @@
+//! Definite initialization analysis.
+//!
+//! Follows the control flow of the program, tracking the locals and statics
+//! that may not be assigned yet, and reports the reads of any of them.
+//!
+//! - Statics without an initial value are unassigned, other than the ones at
+//!   absolute addresses, which are hardware registers.
+//! - Locals are unassigned while their initializer is evaluated, so `let x:u8 =
+//!   (+ x 1)` reads the new `x`, not a previous one.
+//! - Taking the address of a name (`@FOO`) counts as an assignment, since it
+//!   may be assigned through the pointer.
+//! - A call assigns the names that the function, or any function it calls, may
+//!   assign. Calls through function pointers assign every name.
+//!
+//! Function bodies can run at any point of the program, so only their locals
+//! are checked.
+use crate::{
+    ast::{visit, Ast, Expression, Fn, Pattern, Statement, Visitor},
+    lex::span::Spanned,
+    Error,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Reads of the locals and statics of a program that may happen before they
+/// are assigned, as [`Error::Uninitialized`](Error::Uninitialized).
+pub(crate) fn uninitialized<'a>(ast: &Ast<'a>) -> Vec<Error<'a>> {
+    let mut init = Init {
+        scopes: vec![HashMap::new()],
+        modules: Vec::new(),
+        fns: HashMap::new(),
+        vars: 0,
+        unassigned: HashSet::new(),
+        breaks: Vec::new(),
+        reported: HashSet::new(),
+        errors: Vec::new(),
+    };
+    init.statements(&ast.inner);
+    init.errors
+}
+
+// ids of the variables that may not be assigned yet. Unreachable code has no
+// unassigned variables.
+type State = HashSet<usize>;
+
+// names a function may assign, found syntactically in its body.
+#[derive(Default)]
+struct Summary {
+    // module the function is defined in
+    modules: Vec<String>,
+    // assigned paths (the targets of assignments, and the operands of `@`)
+    assigns: Vec<Vec<String>>,
+    // paths of the called functions
+    calls: Vec<Vec<String>>,
+    // whether it calls anything other than a path (`((if c f g))`)
+    unknown: bool,
+}
+
+impl<'a> Visitor<'a> for Summary {
+    fn visit_expression(&mut self, node: &Expression<'a>) {
+        use Expression as E;
+        let target = match node {
+            E::Assign(node) => Some(&node.inner.left),
+            E::AddressOf(node) => Some(&node.inner),
+            E::Call(node) => {
+                match &node.inner.left {
+                    E::Path(path) => self
+                        .calls
+                        .push(path.iter().map(|i| i.to_string()).collect()),
+                    _ => self.unknown = true,
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(path) = target.and_then(assigned) {
+            self.assigns
+                .push(path.iter().map(|i| i.to_string()).collect());
+        }
+        visit::walk_expression(self, node);
+    }
+}
+
+// path assigned by the left hand side of an assignment, if any. Assigning an
+// element of an array counts as assigning the array.
+fn assigned<'b, 'a>(expression: &'b Expression<'a>) -> Option<&'b crate::ast::Path<'a>> {
+    match expression {
+        Expression::Path(path) => Some(path),
+        Expression::Index(node) => assigned(&node.inner.right),
+        _ => None,
+    }
+}
+
+struct Init<'a> {
+    // variables of the visible names, innermost scope last
+    scopes: Vec<HashMap<String, usize>>,
+    // modules being checked, innermost last
+    modules: Vec<String>,
+    fns: HashMap<String, Summary>,
+    // number of declared variables
+    vars: usize,
+    unassigned: State,
+    // unassigned variables at the `break`s of the loops being checked
+    breaks: Vec<State>,
+    reported: HashSet<usize>,
+    errors: Vec<Error<'a>>,
+}
+
+impl<'a> Init<'a> {
+    // name of a declaration of the module being checked.
+    fn declared_name(&self, name: &str) -> String {
+        let mut path = self.modules.clone();
+        path.push(name.to_string());
+        path.join("::")
+    }
+
+    // declare a variable in the innermost scope.
+    fn declare(&mut self, name: &str, assigned: bool) -> usize {
+        let id = self.vars;
+        self.vars += 1;
+        let name = self.declared_name(name);
+        self.scopes.last_mut().unwrap().insert(name, id);
+        if !assigned {
+            self.unassigned.insert(id);
+        }
+        id
+    }
+
+    // variable of a path, seen from the given module. Paths to members
+    // (`FOO::bar`) resolve to the variable they belong to.
+    fn var(&self, modules: &[String], path: &[String]) -> Option<usize> {
+        (1..=path.len())
+            .rev()
+            .find_map(|len| {
+                (0..=modules.len()).rev().find_map(|i| {
+                    let mut name = modules[..i].to_vec();
+                    name.extend_from_slice(&path[..len]);
+                    let name = name.join("::");
+                    self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+                })
+            })
+            .copied()
+    }
+
+    // function of a path, seen from the given module.
+    fn fn_name(&self, modules: &[String], path: &[String]) -> Option<String> {
+        (0..=modules.len()).rev().find_map(|i| {
+            let mut name = modules[..i].to_vec();
+            name.extend_from_slice(path);
+            let name = name.join("::");
+            if self.fns.contains_key(&name) {
+                Some(name)
+            } else {
+                None
+            }
+        })
+    }
+
+    // merge the unassigned variables of another path of the control flow.
+    fn join(&mut self, other: State) {
+        self.unassigned.extend(other);
+    }
+
+    fn block(&mut self, statements: &[Statement<'a>]) {
+        self.scopes.push(HashMap::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    // check a loop body, which may break out of the loop.
+    // Returns the unassigned variables at its `break`s.
+    fn loop_body(&mut self, statements: &[Statement<'a>]) -> State {
+        self.breaks.push(State::new());
+        self.block(statements);
+        self.breaks.pop().unwrap()
+    }
+
+    fn statements(&mut self, statements: &[Statement<'a>]) {
+        // functions can be called before their definition
+        for statement in statements {
+            if let Statement::Fn(fn_) = statement {
+                let mut summary = Summary {
+                    modules: self.modules.clone(),
+                    ..Summary::default()
+                };
+                summary.visit_fn(fn_);
+                let name = self.declared_name(fn_.ident.name());
+                self.fns.insert(name, summary);
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) {
+        match statement {
+            Statement::If(if_) => {
+                self.expression(&if_.expression);
+                let before = self.unassigned.clone();
+                self.block(&if_.inner);
+                self.join(before);
+            }
+            Statement::IfElse(if_else) => {
+                self.expression(&if_else.if_.expression);
+                let before = self.unassigned.clone();
+                self.block(&if_else.if_.inner);
+                let then = std::mem::replace(&mut self.unassigned, before);
+                self.block(&if_else.else_.inner);
+                self.join(then);
+            }
+            Statement::IfConst(if_const) => self.statements(if_const.statements()),
+            Statement::Scope(scope) => self.block(&scope.inner),
+            // the block runs later, so its assignments don't count here
+            Statement::Defer(defer) => {
+                let before = self.unassigned.clone();
+                self.block(&defer.inner);
+                self.unassigned = before;
+            }
+            Statement::Mod(mod_) => {
+                self.modules.push(mod_.ident.name().to_string());
+                self.statements(&mod_.inner);
+                self.modules.pop();
+            }
+            Statement::Import(import) => {
+                self.modules.push(import.name());
+                self.statements(&import.inner);
+                self.modules.pop();
+            }
+            Statement::MacroCall(call) => self.block(&call.inner),
+            Statement::Static(static_) => {
+                if let Some(init) = &static_.init {
+                    self.expression(&init.expression);
+                }
+                let assigned = static_.init.is_some() || static_.offset.is_some();
+                self.declare(static_.field.ident.name(), assigned);
+            }
+            Statement::Const(const_) => {
+                self.declare(const_.field.ident.name(), true);
+            }
+            Statement::Let(let_) => {
+                let id = self.declare(let_.field.ident.name(), false);
+                self.expression(&let_.expression);
+                self.unassigned.remove(&id);
+            }
+            Statement::LetTuple(let_) => {
+                let ids: Vec<_> = let_
+                    .fields
+                    .iter()
+                    .map(|field| self.declare(field.ident.name(), false))
+                    .collect();
+                self.expression(&let_.expression);
+                for id in ids {
+                    self.unassigned.remove(&id);
+                }
+            }
+            Statement::For(for_) => {
+                self.expression(&for_.range.left);
+                self.expression(&for_.range.right);
+                // the body may not run at all
+                let before = self.unassigned.clone();
+                self.scopes.push(HashMap::new());
+                self.declare(for_.field.ident.name(), true);
+                let breaks = self.loop_body(&for_.inner);
+                self.scopes.pop();
+                self.unassigned = before;
+                self.join(breaks);
+            }
+            Statement::While(while_) => {
+                self.expression(&while_.expression);
+                let before = self.unassigned.clone();
+                let breaks = self.loop_body(&while_.inner);
+                self.unassigned = before;
+                self.join(breaks);
+            }
+            // the body runs at least once, and the loop is only left with a
+            // `break`
+            Statement::Loop(loop_) => self.unassigned = self.loop_body(&loop_.inner),
+            Statement::Match(match_) => {
+                self.expression(&match_.expression);
+                let before = self.unassigned.clone();
+                let mut after = State::new();
+                for arm in &match_.arms {
+                    self.unassigned = before.clone();
+                    if let Pattern::Expression(expression) = &arm.pattern {
+                        self.expression(expression);
+                    }
+                    self.block(&arm.inner);
+                    after.extend(self.unassigned.drain());
+                }
+                self.unassigned = before;
+                if let Some(else_) = &match_.else_ {
+                    self.block(&else_.inner);
+                }
+                self.join(after);
+            }
+            Statement::Inline(inline) => self.expression(&inline.inner),
+            Statement::Fn(fn_) => self.fn_(fn_),
+            Statement::Return(return_) => {
+                if let Some(expression) = &return_.expression {
+                    self.expression(expression);
+                }
+                self.unassigned.clear();
+            }
+            Statement::Break(_) => {
+                let unassigned = std::mem::take(&mut self.unassigned);
+                if let Some(breaks) = self.breaks.last_mut() {
+                    breaks.extend(unassigned);
+                }
+            }
+            Statement::Continue(_) | Statement::Panic(_) => self.unassigned.clear(),
+            Statement::Enum(_)
+            | Statement::TypeAlias(_)
+            | Statement::Macro(_)
+            | Statement::Memory(_)
+            | Statement::StaticAssert(_)
+            | Statement::Error(_) => {}
+        }
+    }
+
+    // check the locals of a function. The function may be called at any
+    // point, so every variable of the enclosing scopes counts as assigned.
+    fn fn_(&mut self, fn_: &Fn<'a>) {
+        let unassigned = std::mem::take(&mut self.unassigned);
+        let breaks = std::mem::take(&mut self.breaks);
+        self.scopes.push(HashMap::new());
+        for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+            self.declare(field.ident.name(), true);
+        }
+        self.statements(&fn_.inner);
+        self.scopes.pop();
+        self.unassigned = unassigned;
+        self.breaks = breaks;
+    }
+
+    fn read(&mut self, expression: &Expression<'a>, path: &[String]) {
+        let id = match self.var(&self.modules, path) {
+            Some(id) => id,
+            None => return,
+        };
+        if self.unassigned.contains(&id) && self.reported.insert(id) {
+            self.errors.push(Error::Uninitialized {
+                span: expression.span(),
+                name: path.join("::"),
+            });
+        }
+    }
+
+    // check the left hand side of an assignment, or the operand of `@`.
+    fn assign(&mut self, expression: &Expression<'a>) {
+        match expression {
+            Expression::Path(path) => {
+                let path: Vec<_> = path.iter().map(|i| i.to_string()).collect();
+                if let Some(id) = self.var(&self.modules, &path) {
+                    self.unassigned.remove(&id);
+                }
+            }
+            Expression::Index(node) => {
+                self.expression(&node.inner.left);
+                self.assign(&node.inner.right);
+            }
+            expression => self.expression(expression),
+        }
+    }
+
+    // assign the variables that a function, and the functions it calls, may
+    // assign.
+    fn call(&mut self, name: String) {
+        let mut pending = vec![name];
+        let mut visited = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            let summary = &self.fns[&name];
+            if summary.unknown {
+                self.unassigned.clear();
+                return;
+            }
+            let mut assigned = Vec::new();
+            for path in &summary.assigns {
+                assigned.extend(self.var(&summary.modules, path));
+            }
+            for path in &summary.calls {
+                match self.fn_name(&summary.modules, path) {
+                    Some(name) => pending.push(name),
+                    // function pointers
+                    None => {
+                        self.unassigned.clear();
+                        return;
+                    }
+                }
+            }
+            for id in assigned {
+                self.unassigned.remove(&id);
+            }
+        }
+    }
+
+    // check the reads of an expression. Evaluating the right operand of `&&`
+    // and `||`, and the branches of `if`, is conditional.
+    fn expression(&mut self, expression: &Expression<'a>) {
+        use Expression as E;
+        match expression {
+            E::Path(path) => {
+                let path: Vec<_> = path.iter().map(|i| i.to_string()).collect();
+                self.read(expression, &path);
+            }
+            E::Lit(_) | E::SizeOf(_) => {}
+            E::Array(array) => {
+                for expression in &array.inner {
+                    self.expression(expression);
+                }
+            }
+            E::StructLit(lit) => {
+                for field in &lit.fields {
+                    self.expression(&field.expression);
+                }
+            }
+            E::Minus(node) => self.expression(&node.inner),
+            E::Not(node) => self.expression(&node.inner),
+            E::Deref(node) => self.expression(&node.inner),
+            E::AddressOf(node) => self.assign(&node.inner),
+            E::Increment(node) => self.expression(&node.inner.inner),
+            E::Decrement(node) => self.expression(&node.inner.inner),
+            E::Cast(node) => self.expression(&node.inner.inner),
+            E::Conditional(node) => {
+                self.expression(&node.inner.condition);
+                let before = self.unassigned.clone();
+                self.expression(&node.inner.then);
+                let then = std::mem::replace(&mut self.unassigned, before);
+                self.expression(&node.inner.else_);
+                self.join(then);
+            }
+            E::LogicalAnd(node) => {
+                self.expression(&node.inner.left);
+                let before = self.unassigned.clone();
+                self.expression(&node.inner.right);
+                self.join(before);
+            }
+            E::LogicalOr(node) => {
+                self.expression(&node.inner.left);
+                let before = self.unassigned.clone();
+                self.expression(&node.inner.right);
+                self.join(before);
+            }
+            E::Assign(node) => {
+                self.expression(&node.inner.right);
+                self.assign(&node.inner.left);
+            }
+            E::Call(node) => {
+                for expression in &node.inner.args {
+                    self.expression(expression);
+                }
+                // functions take precedence over variables, as in the IR
+                let fn_name = match &node.inner.left {
+                    E::Path(path) => {
+                        let path: Vec<_> = path.iter().map(|i| i.to_string()).collect();
+                        self.fn_name(&self.modules, &path)
+                    }
+                    _ => None,
+                };
+                match fn_name {
+                    Some(name) => self.call(name),
+                    None => {
+                        self.expression(&node.inner.left);
+                        self.unassigned.clear();
+                    }
+                }
+            }
+            E::Index(node) => {
+                self.expression(&node.inner.left);
+                self.expression(&node.inner.right);
+            }
+            E::Add(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Sub(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Mul(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Div(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::And(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Or(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Xor(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::LeftShift(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::RightShift(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Eq(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::NotEq(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::LessEq(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::GreaterEq(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Less(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::Greater(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::PlusAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::MinusAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::MulAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::DivAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::AndAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::OrAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+            E::XorAssign(node) => self.binary(&node.inner.left, &node.inner.right),
+        }
+    }
+
+    fn binary(&mut self, left: &Expression<'a>, right: &Expression<'a>) {
+        self.expression(left);
+        self.expression(right);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+
+    fn check(input: &str) -> Vec<String> {
+        let ast = crate::parse(input).unwrap();
+        super::uninitialized(&ast)
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn statics() {
+        assert_eq!(
+            vec!["Use of possibly uninitialized `X`"],
+            check("static X:u8\nstatic Y:u8\n(= Y X)\n(= Y X)")
+        );
+        let input = "static X:u8\nstatic Y:u8 = 2\nstatic@0xff44 LY:u8\n\
+                     (= X 1)\n(= X (+ X (+ Y LY)))";
+        assert!(check(input).is_empty());
+        assert!(
+            check("static A:[u8 4]\nstatic P:&u8\n(= ([0] A) 1)\n(= P @A)\n(= A P)").is_empty()
+        );
+    }
+
+    #[test]
+    fn locals() {
+        assert_eq!(
+            vec!["Use of possibly uninitialized `x`"],
+            check("let x:u8 = 1\n{ let x:u8 = (+ x 1) }")
+        );
+        assert_eq!(
+            vec!["Use of possibly uninitialized `b`"],
+            check("fn f { let (a:u8 b:u8) = [1 b] }")
+        );
+    }
+
+    #[test]
+    fn branches() {
+        let input = "static X:u8\nstatic Y:u8 = 0\nif (== Y 1) { (= X 1) }\n(= Y X)";
+        assert_eq!(vec!["Use of possibly uninitialized `X`"], check(input));
+        let input = "static X:u8\nstatic Y:u8 = 0\n\
+                     if (== Y 1) { (= X 1) } else { (= X 2) }\n\
+                     match Y { 0 { (= X 3) } else { !! } }\n(= Y X)";
+        assert!(check(input).is_empty());
+    }
+
+    #[test]
+    fn loops() {
+        let input = "static X:u8\nstatic Y:u8 = 0\nwhile (== Y 0) { (= X 1) }\n(= Y X)";
+        assert_eq!(vec!["Use of possibly uninitialized `X`"], check(input));
+        let input = "static X:u8\nstatic Y:u8 = 0\nfor i:u8 in 0..4 { (= X i) }\n(= Y X)";
+        assert_eq!(vec!["Use of possibly uninitialized `X`"], check(input));
+        let input = "static X:u8\nstatic Y:u8 = 0\nloop { (= X 1) break }\n(= Y X)";
+        assert!(check(input).is_empty());
+    }
+
+    #[test]
+    fn calls() {
+        let input = "static X:u8\nstatic Y:u8\nfn init { (= X 1) (reset) }\nfn reset { (= Y 0) }\n\
+                     (init)\n(= Y (+ X Y))";
+        assert!(check(input).is_empty());
+        // functions only check their locals
+        assert!(check("static X:u8\nfn f():u8 { return X }").is_empty());
+        assert!(check("fn max(a:u8 b:u8):u8 { let max:u8 = (max a b) return max }").is_empty());
+        assert_eq!(
+            vec!["Use of possibly uninitialized `Y`"],
+            check("static X:u8\nstatic Y:u8\nfn init { (= X 1) }\n(init)\n(= X Y)")
+        );
+    }
+
+    #[test]
+    fn error() {
+        let ast = crate::parse("static X:u8\nstatic Y:u8\n(= Y X)").unwrap();
+        let errors = super::uninitialized(&ast);
+        assert!(matches!(&errors[0], Error::Uninitialized { .. }));
+        assert_eq!("E0018", errors[0].code());
+        assert_eq!([2, 5], errors[0].span().unwrap().min);
+    }
+}
@@ -0,0 +1,416 @@
+//! Lints.
+//!
+//! Code that is valid, but most likely a mistake:
+//!
+//! - Functions, statics, consts, and locals that are never used. Public
+//!   declarations, interrupt handlers, statics at absolute addresses (hardware
+//!   registers), the declarations of imported files, and names starting with
+//!   `_` are not reported.
+//! - Statements following a `break`, `continue`, `return`, `!!` (panic), or a
+//!   `loop` without any `break`, which never run.
+use crate::{
+    ast::{Ast, Expression, Field, Fn, Path, Pattern, Statement, Visitor},
+    lex,
+    lex::span::Spanned,
+    Error,
+};
+use std::collections::HashMap;
+
+/// Warnings of a program, in the order of their location.
+pub(crate) fn lint<'a>(ast: &Ast<'a>) -> Vec<Error<'a>> {
+    let mut lint = Lint {
+        scopes: vec![HashMap::new()],
+        modules: Vec::new(),
+        decls: Vec::new(),
+        imports: 0,
+        errors: Vec::new(),
+    };
+    lint.statements(&ast.inner);
+    let mut errors = lint.errors;
+    for decl in lint.decls {
+        if decl.lint && !decl.used {
+            errors.push(Error::Unused {
+                ident: decl.ident,
+                kind: decl.kind,
+            });
+        }
+    }
+    errors.sort_by_key(|error| error.span().map(|span| span.min));
+    errors
+}
+
+struct Decl<'a> {
+    ident: lex::Ident<'a>,
+    kind: &'static str,
+    used: bool,
+    // whether to report it if unused
+    lint: bool,
+}
+
+// paths referenced by a node.
+#[derive(Default)]
+struct Paths(Vec<Vec<String>>);
+
+impl<'a> Visitor<'a> for Paths {
+    fn visit_path(&mut self, node: &Path<'a>) {
+        self.0.push(node.iter().map(|i| i.to_string()).collect());
+    }
+}
+
+struct Lint<'a> {
+    // declarations of the visible names, innermost scope last
+    scopes: Vec<HashMap<String, usize>>,
+    // modules being linted, innermost last
+    modules: Vec<String>,
+    decls: Vec<Decl<'a>>,
+    // depth of the imported files being linted
+    imports: usize,
+    errors: Vec<Error<'a>>,
+}
+
+impl<'a> Lint<'a> {
+    // declare a name in the innermost scope.
+    fn declare(&mut self, ident: &lex::Ident<'a>, kind: &'static str, lint: bool) {
+        let mut path = self.modules.clone();
+        path.push(ident.name().to_string());
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(path.join("::"), self.decls.len());
+        self.decls.push(Decl {
+            ident: ident.clone(),
+            kind,
+            used: false,
+            lint: lint && self.imports == 0 && !ident.name().starts_with('_'),
+        });
+    }
+
+    // mark the declarations of the referenced paths as used. Paths to members
+    // (`FOO::bar`) use the declaration they belong to.
+    fn use_paths(&mut self, paths: Paths) {
+        for path in paths.0 {
+            let decl = (1..=path.len()).rev().find_map(|len| {
+                (0..=self.modules.len()).rev().find_map(|i| {
+                    let mut name = self.modules[..i].to_vec();
+                    name.extend_from_slice(&path[..len]);
+                    let name = name.join("::");
+                    self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+                })
+            });
+            if let Some(&decl) = decl {
+                self.decls[decl].used = true;
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression<'a>) {
+        let mut paths = Paths::default();
+        paths.visit_expression(expression);
+        self.use_paths(paths);
+    }
+
+    // use the names in the type of a field (`[u8 LEN]`).
+    fn field(&mut self, field: &Field<'a>) {
+        let mut paths = Paths::default();
+        paths.visit_field(field);
+        self.use_paths(paths);
+    }
+
+    fn block(&mut self, statements: &[Statement<'a>]) {
+        self.scopes.push(HashMap::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Statement<'a>]) {
+        // functions, statics, and consts can be used before their definition
+        for statement in statements {
+            match statement {
+                Statement::Fn(fn_) => {
+                    let lint = fn_.pub_.is_none() && fn_.interrupt.is_none();
+                    self.declare(&fn_.ident, "function", lint);
+                }
+                Statement::Static(static_) => {
+                    let lint = static_.pub_.is_none() && static_.offset.is_none();
+                    self.declare(&static_.field.ident, "static", lint);
+                }
+                Statement::Const(const_) => {
+                    self.declare(&const_.field.ident, "const", const_.pub_.is_none());
+                }
+                _ => {}
+            }
+        }
+        // statement that control flow never returns from
+        let mut diverged = None;
+        // only the first unreachable statement is reported
+        let mut reported = false;
+        for statement in statements {
+            match diverged {
+                Some(after) if !reported && is_executable(statement) => {
+                    self.errors.push(Error::Unreachable {
+                        span: statement.span(),
+                        after,
+                    });
+                    reported = true;
+                }
+                None if diverges(statement) => diverged = Some(statement.span()),
+                _ => {}
+            }
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) {
+        match statement {
+            Statement::If(if_) => {
+                self.expression(&if_.expression);
+                self.block(&if_.inner);
+            }
+            Statement::IfElse(if_else) => {
+                self.expression(&if_else.if_.expression);
+                self.block(&if_else.if_.inner);
+                self.block(&if_else.else_.inner);
+            }
+            // the names used by either branch count as used, whatever the
+            // condition evaluates to
+            Statement::IfConst(if_const) => {
+                let mut paths = Paths::default();
+                paths.visit_if_const(if_const);
+                self.use_paths(paths);
+                self.statements(if_const.statements());
+            }
+            Statement::Scope(scope) => self.block(&scope.inner),
+            Statement::Defer(defer) => self.block(&defer.inner),
+            Statement::Mod(mod_) => {
+                self.modules.push(mod_.ident.name().to_string());
+                self.statements(&mod_.inner);
+                self.modules.pop();
+            }
+            Statement::Import(import) => {
+                self.modules.push(import.name());
+                self.imports += 1;
+                self.statements(&import.inner);
+                self.imports -= 1;
+                self.modules.pop();
+            }
+            Statement::MacroCall(call) => self.block(&call.inner),
+            Statement::Static(static_) => {
+                let mut paths = Paths::default();
+                paths.visit_static(static_);
+                self.use_paths(paths);
+            }
+            Statement::Const(const_) => {
+                self.field(&const_.field);
+                self.expression(&const_.expression);
+            }
+            Statement::Let(let_) => {
+                self.field(&let_.field);
+                self.expression(&let_.expression);
+                self.declare(&let_.field.ident, "local", true);
+            }
+            Statement::LetTuple(let_) => {
+                for field in &let_.fields {
+                    self.field(field);
+                }
+                self.expression(&let_.expression);
+                for field in &let_.fields {
+                    self.declare(&field.ident, "local", true);
+                }
+            }
+            Statement::For(for_) => {
+                self.field(&for_.field);
+                self.expression(&for_.range.left);
+                self.expression(&for_.range.right);
+                self.scopes.push(HashMap::new());
+                // the counter of a loop isn't always needed
+                self.declare(&for_.field.ident, "local", false);
+                self.statements(&for_.inner);
+                self.scopes.pop();
+            }
+            Statement::Loop(loop_) => self.block(&loop_.inner),
+            Statement::While(while_) => {
+                self.expression(&while_.expression);
+                self.block(&while_.inner);
+            }
+            Statement::Match(match_) => {
+                self.expression(&match_.expression);
+                for arm in &match_.arms {
+                    match &arm.pattern {
+                        Pattern::Expression(expression) => self.expression(expression),
+                        Pattern::Range(range) => {
+                            self.expression(&range.left);
+                            self.expression(&range.right);
+                        }
+                    }
+                    self.block(&arm.inner);
+                }
+                if let Some(else_) = &match_.else_ {
+                    self.block(&else_.inner);
+                }
+            }
+            Statement::Inline(inline) => self.expression(&inline.inner),
+            Statement::Fn(fn_) => self.fn_(fn_),
+            Statement::Return(return_) => {
+                if let Some(expression) = &return_.expression {
+                    self.expression(expression);
+                }
+            }
+            Statement::Enum(_)
+            | Statement::TypeAlias(_)
+            | Statement::Macro(_)
+            | Statement::Memory(_)
+            | Statement::StaticAssert(_)
+            | Statement::Panic(_)
+            | Statement::Continue(_)
+            | Statement::Break(_)
+            | Statement::Error(_) => {
+                let mut paths = Paths::default();
+                paths.visit_statement(statement);
+                self.use_paths(paths);
+            }
+        }
+    }
+
+    fn fn_(&mut self, fn_: &Fn<'a>) {
+        self.scopes.push(HashMap::new());
+        for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+            self.field(field);
+            // arguments are part of the signature, even when unused
+            self.declare(&field.ident, "local", false);
+        }
+        if let Some(fn_return) = &fn_.fn_return {
+            let mut paths = Paths::default();
+            paths.visit_type(&fn_return.type_);
+            self.use_paths(paths);
+        }
+        self.statements(&fn_.inner);
+        self.scopes.pop();
+    }
+}
+
+// whether a statement runs when control flow reaches it. Declarations don't,
+// other than locals.
+fn is_executable(statement: &Statement<'_>) -> bool {
+    !matches!(
+        statement,
+        Statement::Fn(_)
+            | Statement::Static(_)
+            | Statement::Const(_)
+            | Statement::Enum(_)
+            | Statement::TypeAlias(_)
+            | Statement::Macro(_)
+            | Statement::Memory(_)
+            | Statement::StaticAssert(_)
+            | Statement::Mod(_)
+            | Statement::Import(_)
+            | Statement::Error(_)
+    )
+}
+
+// whether control flow never continues after a statement.
+pub(super) fn diverges(statement: &Statement<'_>) -> bool {
+    match statement {
+        Statement::Return(_)
+        | Statement::Break(_)
+        | Statement::Continue(_)
+        | Statement::Panic(_) => true,
+        Statement::Loop(loop_) => !breaks(&loop_.inner),
+        Statement::IfElse(if_else) => {
+            block_diverges(&if_else.if_.inner) && block_diverges(&if_else.else_.inner)
+        }
+        Statement::Scope(scope) => block_diverges(&scope.inner),
+        Statement::MacroCall(call) => block_diverges(&call.inner),
+        Statement::IfConst(if_const) => block_diverges(if_const.statements()),
+        Statement::Match(match_) => match &match_.else_ {
+            Some(else_) => {
+                match_.arms.iter().all(|arm| block_diverges(&arm.inner))
+                    && block_diverges(&else_.inner)
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// whether control flow never reaches the end of a block.
+pub(super) fn block_diverges(statements: &[Statement<'_>]) -> bool {
+    statements.iter().any(diverges)
+}
+
+// whether a loop body breaks out of the loop.
+pub(super) fn breaks(statements: &[Statement<'_>]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Break(_) => true,
+        Statement::If(if_) => breaks(&if_.inner),
+        Statement::IfElse(if_else) => breaks(&if_else.if_.inner) || breaks(&if_else.else_.inner),
+        Statement::Scope(scope) => breaks(&scope.inner),
+        Statement::MacroCall(call) => breaks(&call.inner),
+        Statement::IfConst(if_const) => breaks(if_const.statements()),
+        Statement::Match(match_) => {
+            match_.arms.iter().any(|arm| breaks(&arm.inner))
+                || match_.else_.iter().any(|else_| breaks(&else_.inner))
+        }
+        // nested loops and functions have breaks of their own
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+
+    fn lint(input: &str) -> Vec<String> {
+        let ast = crate::parse(input).unwrap();
+        super::lint(&ast).iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn unused() {
+        let input = "static A:u8\nconst B:u8 = 1\nfn f { let x:u8 = 1 }\nfn g { }\n(g)";
+        assert_eq!(
+            vec![
+                "Unused static `A`",
+                "Unused const `B`",
+                "Unused function `f`",
+                "Unused local `x`",
+            ],
+            lint(input)
+        );
+        let input = "pub static A:u8\nstatic@0xff44 LY:u8\nconst LEN:u8 = 4\n\
+                     static _B:u8\nstatic C:[u8 LEN]\nfn f(a:u8) { }\n\
+                     fn main { for i:u8 in 0..4 { (f ([0] C)) } }\n(main)";
+        assert!(lint(input).is_empty());
+    }
+
+    #[test]
+    fn shadow() {
+        let input = "let x:u8 = 1\n{ let x:u8 = (+ x 1) }";
+        assert_eq!(vec!["Unused local `x`"], lint(input));
+        assert!(
+            lint("fn max(a:u8 b:u8):u8 { let max:u8 = (max a b) return max }\n(max 1 2)")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let input = "static A:u8\nloop {}\n(= A 1)\n(= A 2)";
+        assert_eq!(vec!["Unreachable statement"], lint(input));
+        let input =
+            "static A:u8\nfn f():u8 { if (== A 0) { return 1 } else { return 2 } (= A 1) }\n(f)";
+        assert_eq!(vec!["Unreachable statement"], lint(input));
+        // declarations after the main loop are fine
+        let input = "static A:u8\nloop { if (== A 0) { break } }\n(= A 1)\nloop {}\npub fn f { }";
+        assert!(lint(input).is_empty());
+    }
+
+    #[test]
+    fn error() {
+        let ast = crate::parse("static A:u8\nloop { }\n(= A 1)").unwrap();
+        let errors = super::lint(&ast);
+        assert!(matches!(&errors[0], Error::Unreachable { .. }));
+        assert_eq!("E0020", errors[0].code());
+        assert_eq!([2, 0], errors[0].span().unwrap().min);
+        assert_eq!([1, 0], errors[0].related()[0].0.min);
+    }
+}
@@ -0,0 +1,206 @@
+//! Lossless concrete syntax tree.
+//!
+//! The [`Ast`](crate::Ast) only keeps the tokens of a program. The [`Cst`]
+//! keeps everything else too (whitespace, comments, and bytes the lexer
+//! couldn't tokenize) attached to the token that follows them, so the original
+//! source can be reconstructed byte for byte, which is what tools like
+//! formatters need.
+//!
+//! AST nodes can be matched with the CST through their spans:
+//!
+//! ```
+//! use parser::lex::span::Spanned;
+//!
+//! let input = "// the answer\nstatic FOO:u8";
+//! let (ast, cst) = parser::parse_lossless(input).unwrap();
+//!
+//! assert_eq!(input, cst.to_string());
+//! let comments: Vec<_> = cst
+//!     .leading_trivia(ast.inner[0].span())
+//!     .iter()
+//!     .map(|t| t.as_str())
+//!     .collect();
+//! assert_eq!(vec!["// the answer", "\n"], comments);
+//! ```
+use crate::lex::{
+    span::{LineIndex, Span, Spanned},
+    Token, Tokens,
+};
+use std::fmt;
+
+/// Source code that is not part of any token.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Trivia<'a> {
+    /// Whitespace.
+    Whitespace(&'a str),
+
+    /// Line comment, from `//` up to (and excluding) the end of the line.
+    Comment(&'a str),
+
+    /// Bytes the lexer couldn't tokenize.
+    Unexpected(&'a str),
+}
+
+impl<'a> Trivia<'a> {
+    /// Source code of the trivia.
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            Trivia::Whitespace(s) | Trivia::Comment(s) | Trivia::Unexpected(s) => s,
+        }
+    }
+}
+
+/// Token with its leading trivia.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct CstToken<'a> {
+    /// Trivia between the previous token and this one.
+    pub leading: Vec<Trivia<'a>>,
+
+    /// The token.
+    pub token: Token<'a>,
+}
+
+/// Lossless concrete syntax tree.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct Cst<'a> {
+    tokens: Vec<CstToken<'a>>,
+}
+
+impl<'a> Cst<'a> {
+    /// Build the CST of the given source code.
+    ///
+    /// Any input is accepted: lexer errors are kept as
+    /// [`Trivia::Unexpected`](Trivia::Unexpected).
+    pub fn new(input: &'a str) -> Self {
+        let index = LineIndex::new(input);
+
+        let mut tokens = Vec::new();
+        let mut leading = Vec::new();
+        let mut cursor = 0;
+        for token in Tokens::new(input) {
+            let span = match &token {
+                Ok(token) => token.span(),
+                Err(error) => match error.span() {
+                    Some(span) => span,
+                    None => continue,
+                },
+            };
+            let (min, max) = (index.offset(span.min), index.offset(span.max));
+            trivia(&input[cursor..min], &mut leading);
+            match token {
+                Ok(token) => tokens.push(CstToken {
+                    leading: std::mem::take(&mut leading),
+                    token,
+                }),
+                Err(_) => leading.push(Trivia::Unexpected(&input[min..max])),
+            }
+            cursor = max;
+        }
+        Self { tokens }
+    }
+
+    /// Tokens of the program, in source order.
+    ///
+    /// The last token is always [`Token::Eof`](Token::Eof), which carries the
+    /// trailing trivia of the program.
+    pub fn tokens(&self) -> &[CstToken<'a>] {
+        &self.tokens
+    }
+
+    /// Trivia preceding the token that begins at the start of `span`.
+    ///
+    /// Returns an empty slice if no token begins there.
+    pub fn leading_trivia(&self, span: Span) -> &[Trivia<'a>] {
+        self.tokens
+            .binary_search_by_key(&span.min, |t| t.token.span().min)
+            .map(|i| &self.tokens[i].leading[..])
+            .unwrap_or(&[])
+    }
+}
+
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            for trivia in &token.leading {
+                f.write_str(trivia.as_str())?;
+            }
+            token.token.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+// split the text between two tokens into whitespace and comments.
+fn trivia<'a>(mut text: &'a str, out: &mut Vec<Trivia<'a>>) {
+    while !text.is_empty() {
+        let len = if text.starts_with("//") {
+            text.find('\n').unwrap_or(text.len())
+        } else {
+            text.find("//").unwrap_or(text.len())
+        };
+        let (head, tail) = text.split_at(len);
+        if head.starts_with("//") {
+            out.push(Trivia::Comment(head));
+        } else {
+            out.push(Trivia::Whitespace(head));
+        }
+        text = tail;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cst, Trivia};
+    use crate::lex::{span::Spanned, Token};
+
+    #[test]
+    fn lossless() {
+        let inputs = [
+            "",
+            "  \n",
+            "// comment",
+            "static FOO:u8 // comment\n\n  // another\r\n(= FOO 0x42)  \n",
+            "let s:u8 = \"// not a comment\"",
+            "static $ FOO:u8 $$\n",
+        ];
+        for input in &inputs {
+            assert_eq!(*input, Cst::new(input).to_string());
+        }
+    }
+
+    #[test]
+    fn trivia() {
+        let cst = Cst::new("fn // foo\n  foo $ {}");
+        let tokens = cst.tokens();
+        assert_eq!(5, tokens.len());
+        assert!(tokens[0].leading.is_empty());
+        assert_eq!(
+            vec![
+                Trivia::Whitespace(" "),
+                Trivia::Comment("// foo"),
+                Trivia::Whitespace("\n  "),
+            ],
+            tokens[1].leading
+        );
+        assert_eq!(
+            vec![
+                Trivia::Whitespace(" "),
+                Trivia::Unexpected("$"),
+                Trivia::Whitespace(" "),
+            ],
+            tokens[2].leading
+        );
+        assert!(matches!(tokens[4].token, Token::Eof(_)));
+        assert_eq!(
+            &[
+                Trivia::Whitespace(" "),
+                Trivia::Unexpected("$"),
+                Trivia::Whitespace(" ")
+            ],
+            cst.leading_trivia(tokens[2].token.span())
+        );
+    }
+}
@@ -0,0 +1,581 @@
+//! Parsing errors and diagnostics rendering.
+use crate::{
+    ast, lex,
+    lex::span::{Span, Spanned},
+};
+use thiserror::Error;
+
+pub use diagnostic::Diagnostic;
+pub use render::{render, Theme};
+pub use suggest::suggest;
+
+mod diagnostic;
+mod render;
+mod suggest;
+
+#[derive(Error, Debug)]
+pub enum Error<'a> {
+    #[error("Early EOF")]
+    Eof,
+
+    #[error("Unexpected token: `{0}`")]
+    UnexpectedToken(lex::Token<'a>),
+
+    #[error("Expected {expected}, found {}", describe(.found))]
+    Expected {
+        /// Description of what the parser expected.
+        expected: &'static str,
+
+        /// The token found instead.
+        found: lex::Token<'a>,
+    },
+
+    #[error("Unknown keyword `{ident}`, did you mean `{suggestion}`?")]
+    UnknownKeyword {
+        /// Identifier that looks like a misspelled keyword.
+        ident: lex::Ident<'a>,
+
+        /// The keyword most similar to the identifier.
+        suggestion: &'static str,
+    },
+
+    #[error("Invalid path: {0:?}")]
+    InvalidPath(ast::Path<'a>),
+
+    #[error("Use of reserved keyword: `{key_word}`")]
+    ReservedKeyword {
+        /// The keyword itself.
+        key_word: &'a str,
+
+        /// Location of the keyword in the programs source.
+        span: Span,
+    },
+
+    #[error("Unexpected byte: {byte:02x}")]
+    UnexpectedByte {
+        /// The unexpected byte.
+        byte: u8,
+
+        /// Location of the byte in the programs source code.
+        span: Span,
+    },
+
+    #[error("Unresolved import: {0}")]
+    UnresolvedImport(lex::Lit<'a>),
+
+    #[error("Cyclic import: {0}")]
+    CyclicImport(lex::Lit<'a>),
+
+    #[error("Shadowed identifier")]
+    ShadowIdent {
+        /// An already defined and previously validated identifier (boxed, so
+        /// the error stays small).
+        ident: Box<lex::Ident<'a>>,
+
+        /// The new identifier shadowing the one above.
+        shadow: lex::Ident<'a>,
+    },
+
+    #[error("Static assertion failed: {}", .message.str_value().unwrap_or_default())]
+    StaticAssert {
+        /// Location of the asserted expression.
+        span: Span,
+
+        /// Message of the assertion (string literal token).
+        message: lex::Lit<'a>,
+    },
+
+    #[error("Expected constant expression")]
+    NotConst {
+        /// Location of the expression.
+        span: Span,
+    },
+
+    #[error("Missing `,` separator")]
+    MissingSeparator {
+        /// Location of the item that isn't preceded by a `,`.
+        item: Span,
+
+        /// A `,` separating other items of the same list.
+        comma: lex::Comma<'a>,
+    },
+
+    #[error("{error}")]
+    Macro {
+        /// Identifier of the macro invocation.
+        call: lex::Ident<'a>,
+
+        /// The error within the expansion of the macro.
+        error: Box<Self>,
+    },
+
+    #[error("Recursive macro: {0}")]
+    RecursiveMacro(lex::Ident<'a>),
+
+    #[error("Literal out of range for `{type_}`: {value}")]
+    OutOfRange {
+        /// Location of the literal.
+        span: Span,
+
+        /// Value of the literal, as written in the source (or the value of
+        /// the constant expression).
+        value: String,
+
+        /// Integer type the literal is assigned to.
+        type_: &'static str,
+    },
+
+    #[error("Mismatched types: expected {expected}, found {found}")]
+    MismatchedTypes {
+        /// Location of the offending expression.
+        span: Span,
+
+        /// Description of the expected type.
+        expected: String,
+
+        /// Description of the type found instead.
+        found: String,
+    },
+
+    #[error("Expected {expected} arguments, found {found}")]
+    ArgCount {
+        /// Location of the function call.
+        span: Span,
+
+        /// Number of arguments of the function.
+        expected: usize,
+
+        /// Number of arguments of the call.
+        found: usize,
+    },
+
+    #[error("Use of possibly uninitialized `{name}`")]
+    Uninitialized {
+        /// Location of the read.
+        span: Span,
+
+        /// Path of the local or static, as written in the source.
+        name: String,
+    },
+
+    #[error("Unused {kind} `{ident}`")]
+    Unused {
+        /// Identifier of the declaration.
+        ident: lex::Ident<'a>,
+
+        /// Kind of declaration (`function`, `static`, `const`, or `local`).
+        kind: &'static str,
+    },
+
+    #[error("Unreachable statement")]
+    Unreachable {
+        /// Location of the unreachable statement.
+        span: Span,
+
+        /// Location of the statement that control flow never returns from.
+        after: Span,
+    },
+
+    #[error("Unknown builtin `{name}`")]
+    UnknownBuiltin {
+        /// Location of the path.
+        span: Span,
+
+        /// Path of the builtin, as written in the source.
+        name: String,
+
+        /// The builtin most similar to the path, if any.
+        suggestion: Option<&'static str>,
+    },
+
+    #[error("Missing return in function `{ident}`")]
+    MissingReturn {
+        /// Identifier of the function.
+        ident: lex::Ident<'a>,
+
+        /// Location of the closing bracket of the function.
+        span: Span,
+    },
+
+    #[error("Not supported in `const fn`")]
+    ConstFn {
+        /// Location of the offending statement, expression, or type.
+        span: Span,
+    },
+
+    #[error("Control flow can't leave a `defer` block")]
+    DeferFlow {
+        /// Location of the `break`, `continue`, or `return` statement.
+        span: Span,
+    },
+
+    #[error("Nesting limit of {limit} reached")]
+    NestingLimit {
+        /// Location of the first node past the limit.
+        span: Span,
+
+        /// The nesting limit of the context.
+        limit: usize,
+    },
+
+    #[error("{feature} requires language version {version}")]
+    RequiresVersion {
+        /// Location of the syntax.
+        span: Span,
+
+        /// Description of the syntax (`` `defer` ``, `` `const fn` ``, ...).
+        feature: String,
+
+        /// Version that introduced the syntax.
+        version: crate::LanguageVersion,
+    },
+
+    #[error("Undefined symbol `{name}`")]
+    UndefinedSymbol {
+        /// The path, as written.
+        name: String,
+
+        /// Location of the path.
+        span: Span,
+
+        /// Unlinked library providing the symbol, if any.
+        library: Option<String>,
+    },
+
+    #[error("`{name}` is not a valid identifier")]
+    InvalidName {
+        /// Location of the declaration being renamed.
+        span: Span,
+
+        /// The new name.
+        name: String,
+    },
+
+    #[error("No declaration found")]
+    UnknownDeclaration {
+        /// The span given as the declaration.
+        span: Span,
+    },
+
+    #[error("Renaming to `{name}` conflicts with another declaration")]
+    RenameConflict {
+        /// Location where the renamed declaration conflicts.
+        span: Span,
+
+        /// The new name.
+        name: String,
+
+        /// Location of the conflicting declaration.
+        declaration: Span,
+    },
+
+    #[error("Invalid bitfield width `{width}`")]
+    BitfieldWidth {
+        /// Location of the width expression.
+        span: Span,
+
+        /// Value of the width.
+        width: u16,
+
+        /// Bits of the field left for the bitfield.
+        available: u16,
+    },
+}
+
+// token as rendered in error messages.
+fn describe(token: &lex::Token<'_>) -> String {
+    match token {
+        lex::Token::Eof(_) => "end of file".to_string(),
+        token => format!("`{}`", token),
+    }
+}
+
+impl Error<'_> {
+    /// Stable code identifying the kind of error.
+    ///
+    /// Codes are never reused, so tools can rely on them across versions.
+    /// Errors within the expansion of a macro have the code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Macro { error, .. } => error.code(),
+            Error::Eof => "E0001",
+            Error::UnexpectedToken(_) => "E0002",
+            Error::Expected { .. } => "E0003",
+            Error::InvalidPath(_) => "E0004",
+            Error::ReservedKeyword { .. } => "E0005",
+            Error::UnexpectedByte { .. } => "E0006",
+            Error::ShadowIdent { .. } => "E0007",
+            Error::UnknownKeyword { .. } => "E0008",
+            Error::UnresolvedImport(_) => "E0009",
+            Error::CyclicImport(_) => "E0010",
+            Error::MissingSeparator { .. } => "E0011",
+            Error::StaticAssert { .. } => "E0012",
+            Error::NotConst { .. } => "E0013",
+            Error::RecursiveMacro(_) => "E0014",
+            Error::OutOfRange { .. } => "E0015",
+            Error::MismatchedTypes { .. } => "E0016",
+            Error::ArgCount { .. } => "E0017",
+            Error::Uninitialized { .. } => "E0018",
+            Error::Unused { .. } => "E0019",
+            Error::Unreachable { .. } => "E0020",
+            Error::MissingReturn { .. } => "E0021",
+            Error::UnknownBuiltin { .. } => "E0022",
+            Error::ConstFn { .. } => "E0023",
+            Error::DeferFlow { .. } => "E0024",
+            Error::NestingLimit { .. } => "E0025",
+            Error::RequiresVersion { .. } => "E0026",
+            Error::UndefinedSymbol { .. } => "E0027",
+            Error::InvalidName { .. } => "E0028",
+            Error::UnknownDeclaration { .. } => "E0029",
+            Error::RenameConflict { .. } => "E0030",
+            Error::BitfieldWidth { .. } => "E0031",
+        }
+    }
+
+    /// Structured representation of the error.
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::from(self)
+    }
+
+    /// Location of the error in the programs source code (if known).
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Eof => None,
+            Error::UnexpectedToken(token) => Some(token.span()),
+            Error::Expected { found, .. } => Some(found.span()),
+            Error::UnknownKeyword { ident, .. } => Some(ident.span()),
+            Error::InvalidPath(path) => Some(path.span()),
+            Error::ReservedKeyword { span, .. } => Some(*span),
+            Error::UnexpectedByte { span, .. } => Some(*span),
+            Error::ShadowIdent { shadow, .. } => Some(shadow.span()),
+            Error::RecursiveMacro(ident) => Some(ident.span()),
+            Error::Macro { error, .. } => error.span(),
+            Error::MissingSeparator { item, .. } => Some(*item),
+            Error::StaticAssert { span, .. } | Error::NotConst { span } => Some(*span),
+            Error::OutOfRange { span, .. }
+            | Error::MismatchedTypes { span, .. }
+            | Error::ArgCount { span, .. }
+            | Error::Uninitialized { span, .. }
+            | Error::Unreachable { span, .. }
+            | Error::MissingReturn { span, .. }
+            | Error::UnknownBuiltin { span, .. }
+            | Error::ConstFn { span }
+            | Error::DeferFlow { span }
+            | Error::NestingLimit { span, .. }
+            | Error::RequiresVersion { span, .. }
+            | Error::UndefinedSymbol { span, .. }
+            | Error::InvalidName { span, .. }
+            | Error::UnknownDeclaration { span }
+            | Error::RenameConflict { span, .. }
+            | Error::BitfieldWidth { span, .. } => Some(*span),
+            Error::Unused { ident, .. } => Some(ident.span()),
+            Error::UnresolvedImport(path) | Error::CyclicImport(path) => Some(path.span()),
+        }
+    }
+
+    /// Short description of the offending source, rendered next to the
+    /// underline of a diagnostic.
+    pub fn label(&self) -> String {
+        match self {
+            Error::Eof | Error::UnexpectedToken(lex::Token::Eof(_)) => {
+                "unexpected end of file".to_string()
+            }
+            Error::UnexpectedToken(_) => "unexpected token".to_string(),
+            Error::Expected { expected, .. } => format!("expected {}", expected),
+            Error::UnknownKeyword { .. } => "unknown keyword".to_string(),
+            Error::InvalidPath(_) => "invalid path".to_string(),
+            Error::ReservedKeyword { .. } => "reserved keyword".to_string(),
+            Error::UnexpectedByte { .. } => "unexpected byte".to_string(),
+            Error::ShadowIdent { shadow, .. } => format!("`{}` redefined here", shadow),
+            Error::UnresolvedImport(_) => "unresolved import".to_string(),
+            Error::CyclicImport(_) => "file imported by itself".to_string(),
+            Error::MissingSeparator { .. } => "expected `,` before this item".to_string(),
+            Error::StaticAssert { .. } => "evaluates to false".to_string(),
+            Error::NotConst { .. } => "not a constant expression".to_string(),
+            Error::Macro { error, .. } => error.label(),
+            Error::RecursiveMacro(_) => "macro invoked within its own expansion".to_string(),
+            Error::OutOfRange { value, type_, .. } => {
+                format!("`{}` doesn't fit in `{}`", value, type_)
+            }
+            Error::MismatchedTypes {
+                expected, found, ..
+            } => {
+                format!("expected {}, found {}", expected, found)
+            }
+            Error::ArgCount { expected, .. } => format!("expected {} arguments", expected),
+            Error::Uninitialized { .. } => "read before it is assigned".to_string(),
+            Error::Unused { .. } => "never used".to_string(),
+            Error::Unreachable { .. } => "unreachable statement".to_string(),
+            Error::MissingReturn { .. } => {
+                "end of the function reached without a return".to_string()
+            }
+            Error::UnknownBuiltin { .. } => "not a builtin".to_string(),
+            Error::ConstFn { .. } => "not supported in a `const fn`".to_string(),
+            Error::DeferFlow { .. } => "leaves the `defer` block".to_string(),
+            Error::NestingLimit { .. } => "nested too deeply".to_string(),
+            Error::RequiresVersion { version, .. } => format!("requires version {}", version),
+            Error::UndefinedSymbol { library: None, .. } => "never defined".to_string(),
+            Error::UndefinedSymbol { .. } => "not linked".to_string(),
+            Error::InvalidName { .. } => "cannot be renamed to this name".to_string(),
+            Error::UnknownDeclaration { .. } => "not a declaration".to_string(),
+            Error::RenameConflict { name, .. } => {
+                format!("`{}` would refer to another declaration", name)
+            }
+            Error::BitfieldWidth { width: 0, .. } => "empty bitfield".to_string(),
+            Error::BitfieldWidth { available, .. } => {
+                format!("only {} bits left in the field", available)
+            }
+        }
+    }
+
+    /// Other locations relevant to the error, with their labels.
+    pub fn related(&self) -> Vec<(Span, &'static str)> {
+        match self {
+            Error::ShadowIdent { ident, .. } => vec![(ident.span(), "first defined here")],
+            Error::MissingSeparator { comma, .. } => {
+                vec![(comma.span(), "the list is separated by `,` here")]
+            }
+            Error::Macro { call, error } => {
+                let mut related = error.related();
+                related.push((call.span(), "in this macro invocation"));
+                related
+            }
+            Error::Unreachable { after, .. } => {
+                vec![(*after, "any code following this statement is unreachable")]
+            }
+            Error::MissingReturn { ident, .. } => {
+                vec![(ident.span(), "function declared with a return type here")]
+            }
+            Error::RenameConflict { declaration, .. } => {
+                vec![(*declaration, "conflicting declaration")]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Additional explanation of the error, if any.
+    pub fn note(&self) -> Option<String> {
+        match self {
+            Error::Eof
+            | Error::UnexpectedToken(lex::Token::Eof(_))
+            | Error::Expected {
+                found: lex::Token::Eof(_),
+                ..
+            } => Some("the program ended in the middle of a statement".to_string()),
+            Error::UnexpectedToken(_) | Error::Expected { .. } | Error::UnknownKeyword { .. } => {
+                None
+            }
+            Error::InvalidPath(_) => {
+                Some("paths are identifiers separated by `::`, as in `foo::bar`".to_string())
+            }
+            Error::ReservedKeyword { key_word, .. } => Some(format!(
+                "`{}` is reserved for future use and can't be used yet",
+                key_word
+            )),
+            Error::UnexpectedByte { byte, .. } if byte.is_ascii() => None,
+            Error::UnexpectedByte { .. } => Some(
+                "non-ASCII characters are only allowed in identifiers, strings, and comments"
+                    .to_string(),
+            ),
+            Error::ShadowIdent { .. } => {
+                Some("identifiers can't be redefined, nor shadowed in an inner block".to_string())
+            }
+            Error::UnresolvedImport(_) => {
+                Some("imported files are provided by the resolver of the context".to_string())
+            }
+            Error::CyclicImport(_) | Error::StaticAssert { .. } | Error::ArgCount { .. } => None,
+            Error::Macro { error, .. } => error.note(),
+            Error::RecursiveMacro(_) => {
+                Some("macros can invoke other macros, but not themselves".to_string())
+            }
+            Error::NotConst { .. } => Some(
+                "constant expressions are made of literals, consts, and the names defined with \
+                 `ContextBuilder::define`"
+                    .to_string(),
+            ),
+            Error::MissingSeparator { .. } => Some(
+                "the items of a list are either all separated by `,`, or none of them is"
+                    .to_string(),
+            ),
+            Error::OutOfRange { type_: "fx8.8", .. } => {
+                Some("the range of `fx8.8` is `0fx..=255.99609375fx`".to_string())
+            }
+            Error::OutOfRange { type_, .. } => {
+                let (min, max) = crate::ast::types::int_range(type_);
+                Some(format!("the range of `{}` is `{}..={}`", type_, min, max))
+            }
+            Error::MismatchedTypes { .. } => Some(
+                "bytes and words don't mix implicitly. Values are converted with a cast, as in \
+                 `(as FOO u16)`"
+                    .to_string(),
+            ),
+            Error::Uninitialized { .. } => Some(
+                "statics without an initial value and locals start with whatever was left in \
+                 memory, which is only zero in the VM"
+                    .to_string(),
+            ),
+            Error::Unused { .. } => {
+                Some("declarations whose name starts with `_` are never reported".to_string())
+            }
+            Error::Unreachable { .. } => None,
+            Error::MissingReturn { .. } => Some(
+                "falling through leaves whatever was in memory as the return value".to_string(),
+            ),
+            Error::UnknownBuiltin { .. } => Some(format!(
+                "the builtins are {}",
+                crate::ast::Builtin::ALL
+                    .iter()
+                    .map(|b| format!("`builtin::{}`", b.name()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Error::ConstFn { .. } => Some(
+                "`const fn`s take and return integers and `bool`s. Their bodies are made of \
+                 `let` locals, assignments, `if`, loops, `return`, and calls to other `const fn`s"
+                    .to_string(),
+            ),
+            Error::DeferFlow { .. } => Some(
+                "deferred blocks run while leaving their scope, so they can't `break`, \
+                 `continue`, or `return` themselves"
+                    .to_string(),
+            ),
+            Error::NestingLimit { limit, .. } => Some(format!(
+                "blocks, expressions, and types can be nested at most {} levels deep. The limit \
+                 is set with `ContextBuilder::nesting_limit`",
+                limit
+            )),
+            Error::RequiresVersion { .. } => Some(format!(
+                "the version of the language is set with `ContextBuilder::version`. The latest \
+                 version is {}",
+                crate::LanguageVersion::LATEST
+            )),
+            Error::UndefinedSymbol { library: None, .. } => Some(
+                "symbols are declared by the program, or provided by its environment with \
+                 `ContextBuilder::extern_symbol`"
+                    .to_string(),
+            ),
+            Error::UndefinedSymbol {
+                name,
+                library: Some(library),
+                ..
+            } => Some(format!(
+                "`{}` is provided by `{}`, which isn't linked with the program",
+                name, library
+            )),
+            Error::InvalidName { .. } => {
+                Some("keywords can only be used as names in their raw form (`r#fn`)".to_string())
+            }
+            Error::UnknownDeclaration { .. } => Some(
+                "declarations are given by the span of their identifier, as in \
+                 `Resolution::declaration`"
+                    .to_string(),
+            ),
+            Error::RenameConflict { .. } => None,
+            Error::BitfieldWidth { .. } => Some(
+                "bitfields are at least 1 bit wide, and are packed LSB-first into the bits of \
+                 their `u8` or `u16` field"
+                    .to_string(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Structured diagnostics.
+use crate::{error::Error, lex::span::Span};
+
+/// Structured representation of an [`Error`](Error).
+///
+/// Unlike the error itself, a diagnostic owns all of its data, so it can
+/// outlive the source code and be sent to other processes, such as the client
+/// of a language server.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// Stable error code (`E0001`, ...).
+    pub code: &'static str,
+
+    /// Error message.
+    pub message: String,
+
+    /// Location of the error, if known.
+    pub span: Option<Span>,
+
+    /// What the parser expected to find, if known.
+    pub expected: Vec<&'static str>,
+
+    /// Source code of the offending token, if any.
+    pub found: Option<String>,
+
+    /// Short description of the offending source.
+    pub label: String,
+
+    /// Replacements for the offending source that would fix the error.
+    pub suggestions: Vec<String>,
+
+    /// Other relevant locations, with their labels.
+    pub related: Vec<(Span, &'static str)>,
+
+    /// Additional explanation of the error.
+    pub note: Option<String>,
+}
+
+impl From<&Error<'_>> for Diagnostic {
+    fn from(error: &Error<'_>) -> Self {
+        if let Error::Macro { error: inner, .. } = error {
+            return Self {
+                related: error.related(),
+                ..Self::from(&**inner)
+            };
+        }
+        let (expected, found) = match error {
+            Error::Eof => (Vec::new(), None),
+            Error::UnexpectedToken(token) => (Vec::new(), Some(token.to_string())),
+            Error::Expected { expected, found } => (vec![*expected], Some(found.to_string())),
+            Error::UnknownKeyword { ident, .. } => (Vec::new(), Some(ident.to_string())),
+            Error::InvalidPath(_) => (Vec::new(), None),
+            Error::ReservedKeyword { key_word, .. } => (Vec::new(), Some(key_word.to_string())),
+            Error::UnexpectedByte { byte, .. } => (Vec::new(), Some(format!("{:#04x}", byte))),
+            Error::ShadowIdent { shadow, .. } => (Vec::new(), Some(shadow.to_string())),
+            Error::UnresolvedImport(path) | Error::CyclicImport(path) => {
+                (Vec::new(), Some(path.to_string()))
+            }
+            Error::MissingSeparator { .. } => (vec!["`,`"], None),
+            Error::StaticAssert { .. } => (Vec::new(), None),
+            Error::NotConst { .. } => (vec!["constant expression"], None),
+            Error::RecursiveMacro(ident) => (Vec::new(), Some(ident.to_string())),
+            Error::OutOfRange { value, .. } => (Vec::new(), Some(value.clone())),
+            Error::MismatchedTypes { found, .. } => (Vec::new(), Some(found.clone())),
+            Error::ArgCount { found, .. } => (Vec::new(), Some(found.to_string())),
+            Error::Uninitialized { name, .. } => (Vec::new(), Some(name.clone())),
+            Error::Unused { ident, .. } => (Vec::new(), Some(ident.to_string())),
+            Error::Unreachable { .. } => (Vec::new(), None),
+            Error::MissingReturn { ident, .. } => (vec!["`return`"], Some(ident.to_string())),
+            Error::UnknownBuiltin { name, .. } | Error::UndefinedSymbol { name, .. } => {
+                (Vec::new(), Some(name.clone()))
+            }
+            Error::InvalidName { name, .. } => (vec!["identifier"], Some(name.clone())),
+            Error::BitfieldWidth { width, .. } => (Vec::new(), Some(width.to_string())),
+            Error::ConstFn { .. }
+            | Error::UnknownDeclaration { .. }
+            | Error::RenameConflict { .. }
+            | Error::DeferFlow { .. }
+            | Error::NestingLimit { .. }
+            | Error::RequiresVersion { .. } => (Vec::new(), None),
+            Error::Macro { .. } => unreachable!(),
+        };
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            span: error.span(),
+            expected,
+            found,
+            label: error.label(),
+            suggestions: match error {
+                Error::UnknownKeyword { suggestion, .. } => vec![suggestion.to_string()],
+                Error::UnknownBuiltin {
+                    suggestion: Some(suggestion),
+                    ..
+                } => vec![format!("builtin::{}", suggestion)],
+                _ => Vec::new(),
+            },
+            related: error.related(),
+            note: error.note(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lex::span::Span;
+
+    #[test]
+    fn expected() {
+        let diagnostic = crate::parse("static FOO:u8\n(= FOO")
+            .unwrap_err()
+            .diagnostic();
+        assert_eq!("E0003", diagnostic.code);
+        assert_eq!("Expected expression, found end of file", diagnostic.message);
+        assert_eq!(vec!["expression"], diagnostic.expected);
+        assert_eq!(Some(String::new()), diagnostic.found);
+        assert_eq!(
+            Some(Span {
+                min: [1, 6],
+                max: [1, 6],
+                ..Default::default()
+            }),
+            diagnostic.span
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let diagnostic = crate::parse("statc FOO:u8").unwrap_err().diagnostic();
+        assert_eq!("E0008", diagnostic.code);
+        assert_eq!(
+            "Unknown keyword `statc`, did you mean `static`?",
+            diagnostic.message
+        );
+        assert_eq!(vec!["static".to_string()], diagnostic.suggestions);
+    }
+
+    #[test]
+    fn unknown_builtin() {
+        let diagnostic = crate::parse("(builtin::memcpi 0 0 0)")
+            .unwrap_err()
+            .diagnostic();
+        assert_eq!("E0022", diagnostic.code);
+        assert_eq!("Unknown builtin `builtin::memcpi`", diagnostic.message);
+        assert_eq!("not a builtin", diagnostic.label);
+        assert_eq!(vec!["builtin::memcpy".to_string()], diagnostic.suggestions);
+    }
+
+    #[test]
+    fn codes() {
+        let inputs = [
+            ("{ }}", "E0003"),
+            ("let foo:u8 = }", "E0003"),
+            ("static FOO:u8 (= FOO", "E0003"),
+            ("static FOO:u8 $", "E0006"),
+        ];
+        for (input, code) in &inputs {
+            assert_eq!(*code, crate::parse(input).unwrap_err().code());
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Diagnostic rendering.
+use crate::{error::Error, lex::span::Span};
+use std::{env, fmt::Write, io::IsTerminal};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Environment variable used to override the number of context lines.
+pub const CONTEXT_LINES_VAR: &str = "GGB_CONTEXT_LINES";
+
+/// Diagnostic rendering theme.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Theme {
+    /// Emit ANSI color escape sequences.
+    pub color: bool,
+
+    /// Use Unicode box-drawing characters instead of plain ASCII.
+    pub unicode: bool,
+
+    /// Number of source lines rendered before and after the offending lines.
+    pub context_lines: usize,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::plain()
+    }
+}
+
+impl Theme {
+    /// No color, ASCII only. Suitable for log files and CI output.
+    pub fn plain() -> Self {
+        Self {
+            color: false,
+            unicode: false,
+            context_lines: 1,
+        }
+    }
+
+    /// Colored output with Unicode box-drawing characters.
+    pub fn fancy() -> Self {
+        Self {
+            color: true,
+            unicode: true,
+            context_lines: 1,
+        }
+    }
+
+    /// Select a theme based on the environment.
+    ///
+    /// - Color is disabled by `NO_COLOR`, `CLICOLOR=0`, `TERM=dumb`, or when
+    ///   stderr is not a terminal, and forced by `CLICOLOR_FORCE`.
+    /// - Unicode is enabled when the locale (`LC_ALL`, `LC_CTYPE`, `LANG`) is
+    ///   UTF-8.
+    /// - The number of context lines is read from `GGB_CONTEXT_LINES`.
+    pub fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let term = var("TERM");
+
+        let color = if var("NO_COLOR").is_some() {
+            false
+        } else if var("CLICOLOR_FORCE").map(|v| v != "0").unwrap_or(false) {
+            true
+        } else if var("CLICOLOR").map(|v| v == "0").unwrap_or(false)
+            || term.as_deref() == Some("dumb")
+        {
+            false
+        } else {
+            std::io::stderr().is_terminal()
+        };
+
+        let unicode = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| var(name))
+            .map(|locale| {
+                let locale = locale.to_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            })
+            .unwrap_or(false)
+            && term.as_deref() != Some("linux");
+
+        let context_lines = var(CONTEXT_LINES_VAR)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| Self::plain().context_lines);
+
+        Self {
+            color,
+            unicode,
+            context_lines,
+        }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn arrow(&self) -> &'static str {
+        if self.unicode {
+            "╭─▶"
+        } else {
+            "-->"
+        }
+    }
+
+    fn bar(&self) -> &'static str {
+        if self.unicode {
+            "│"
+        } else {
+            "|"
+        }
+    }
+
+    fn dots(&self) -> &'static str {
+        if self.unicode {
+            "├─▶"
+        } else {
+            ":::"
+        }
+    }
+
+    fn underline(&self) -> &'static str {
+        if self.unicode {
+            "━"
+        } else {
+            "^"
+        }
+    }
+
+    fn secondary(&self) -> &'static str {
+        if self.unicode {
+            "─"
+        } else {
+            "-"
+        }
+    }
+}
+
+/// Render an error, annotated with the offending lines of `input`.
+///
+/// The offending tokens are underlined and labeled, related locations (such
+/// as the previous definition of a shadowed identifier) are rendered below
+/// them, and the output ends with a note explaining the error, if any.
+pub fn render(input: &str, error: &Error<'_>, theme: &Theme) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}: {}",
+        theme.paint(RED, &format!("error[{}]", error.code())),
+        theme.paint(BOLD, &error.to_string())
+    );
+
+    // errors without a location (early EOF) point at the end of the input
+    let span = error.span().unwrap_or_else(|| {
+        let end = [
+            input.split('\n').count() - 1,
+            input.rsplit('\n').next().unwrap_or("").len(),
+        ];
+        Span {
+            min: end,
+            max: end,
+            ..Default::default()
+        }
+    });
+    let related = error.related();
+    let width = related
+        .iter()
+        .map(|(span, _)| span.max[0])
+        .chain(Some(span.max[0]))
+        .map(|line| (line + theme.context_lines + 1).to_string().len())
+        .max()
+        .unwrap_or(1);
+    let pad = " ".repeat(width);
+
+    let _ = writeln!(
+        out,
+        "{}{} {}",
+        pad,
+        theme.paint(BLUE, theme.arrow()),
+        span.location(input)
+    );
+    snippet(&mut out, input, span, &error.label(), true, width, theme);
+    for (span, label) in related {
+        let _ = writeln!(
+            out,
+            "{}{} {}",
+            pad,
+            theme.paint(BLUE, theme.dots()),
+            span.location(input)
+        );
+        snippet(&mut out, input, span, label, false, width, theme);
+    }
+    if let Some(note) = error.note() {
+        let _ = writeln!(out, "{} {}", pad, theme.paint(BLUE, theme.bar()));
+        let _ = writeln!(
+            out,
+            "{} {} {}: {}",
+            pad,
+            theme.paint(BLUE, "="),
+            theme.paint(BOLD, "note"),
+            note
+        );
+    }
+    out
+}
+
+// byte offset within a line into a char column.
+fn column(line: &str, offset: usize) -> usize {
+    line.get(..offset)
+        .map(|s| s.chars().count())
+        .unwrap_or(offset)
+}
+
+fn snippet(
+    out: &mut String,
+    input: &str,
+    Span { min, max, .. }: Span,
+    label: &str,
+    primary: bool,
+    width: usize,
+    theme: &Theme,
+) {
+    let lines: Vec<_> = input.lines().collect();
+    let first = min[0];
+    let last = max[0].max(first);
+    let from = first.saturating_sub(theme.context_lines);
+    let to = (last + theme.context_lines).min(lines.len().saturating_sub(1).max(last));
+    let pad = " ".repeat(width);
+    let bar = theme.paint(BLUE, theme.bar());
+    let (style, mark) = if primary {
+        (RED, theme.underline())
+    } else {
+        (BLUE, theme.secondary())
+    };
+
+    let _ = writeln!(out, "{} {}", pad, bar);
+    for n in from..=to {
+        let line = lines.get(n).copied().unwrap_or("");
+        let number = theme.paint(BLUE, &format!("{:>width$}", n + 1, width = width));
+        let _ = writeln!(out, "{} {} {}", number, bar, line);
+
+        if n >= first && n <= last {
+            let start = if n == first { column(line, min[1]) } else { 0 };
+            let end = if n == last {
+                column(line, max[1])
+            } else {
+                line.chars().count()
+            };
+            let mut underline = mark.repeat(end.saturating_sub(start).max(1));
+            if n == last && !label.is_empty() {
+                underline.push(' ');
+                underline.push_str(label);
+            }
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                pad,
+                bar,
+                " ".repeat(start),
+                theme.paint(style, &underline)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render, Theme};
+
+    fn render_err(input: &str, theme: &Theme) -> String {
+        let error = crate::parse(input).unwrap_err();
+        render(input, &error, theme)
+    }
+
+    #[test]
+    fn plain() {
+        let input = "let foo:u8 = 0\n}\nlet bar:u8 = 1\nlet baz:u8 = 2";
+        let gt = "error[E0003]: Expected end of file, found `}`\n \
+                  --> 2:1\n  \
+                  |\n\
+                  1 | let foo:u8 = 0\n\
+                  2 | }\n  \
+                  | ^ expected end of file\n\
+                  3 | let bar:u8 = 1\n";
+        assert_eq!(gt, render_err(input, &Theme::plain()));
+    }
+
+    #[test]
+    fn no_context() {
+        let input = "let foo:u8 = 0\nlet bar:u8 = }";
+        let theme = Theme {
+            context_lines: 0,
+            ..Theme::plain()
+        };
+        let gt = "error[E0003]: Expected expression, found `}`\n \
+                  --> 2:14\n  \
+                  |\n\
+                  2 | let bar:u8 = }\n  \
+                  |              ^ expected expression\n";
+        assert_eq!(gt, render_err(input, &theme));
+    }
+
+    #[test]
+    fn unicode() {
+        let theme = Theme {
+            unicode: true,
+            ..Theme::plain()
+        };
+        let rendered = render_err("{ }}", &theme);
+        assert!(rendered.contains("╭─▶ 1:4"));
+        assert!(rendered.contains("│    ━"));
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn eof() {
+        let input = "static FOO:u8\n(= FOO";
+        let gt = "error[E0003]: Expected expression, found end of file\n \
+                  --> 2:7\n  \
+                  |\n\
+                  1 | static FOO:u8\n\
+                  2 | (= FOO\n  \
+                  |       ^ expected expression\n  \
+                  |\n  \
+                  = note: the program ended in the middle of a statement\n";
+        assert_eq!(gt, render_err(input, &Theme::plain()));
+    }
+
+    #[test]
+    fn related() {
+        use crate::lex::{Token, Tokens};
+
+        let input = "let foo:u8 = 0\nlet foo:u8 = 1";
+        let idents: Vec<_> = Tokens::new(input)
+            .filter_map(|t| match t {
+                Ok(Token::Ident(ident)) => Some(ident),
+                _ => None,
+            })
+            .collect();
+        let error = crate::Error::ShadowIdent {
+            ident: Box::new(idents[0].clone()),
+            shadow: idents[1].clone(),
+        };
+        let theme = Theme {
+            context_lines: 0,
+            ..Theme::plain()
+        };
+        let gt = "error[E0007]: Shadowed identifier\n \
+                  --> 2:5\n  \
+                  |\n\
+                  2 | let foo:u8 = 1\n  \
+                  |     ^^^ `foo` redefined here\n \
+                  ::: 1:5\n  \
+                  |\n\
+                  1 | let foo:u8 = 0\n  \
+                  |     --- first defined here\n  \
+                  |\n  \
+                  = note: identifiers can't be redefined, nor shadowed in an inner block\n";
+        assert_eq!(gt, render(input, &error, &theme));
+    }
+
+    #[test]
+    fn missing_separator() {
+        let theme = Theme {
+            context_lines: 0,
+            ..Theme::plain()
+        };
+        let gt = "error[E0011]: Missing `,` separator\n \
+                  --> 1:9\n  \
+                  |\n\
+                  1 | (f a, b c)\n  \
+                  |         ^ expected `,` before this item\n \
+                  ::: 1:5\n  \
+                  |\n\
+                  1 | (f a, b c)\n  \
+                  |     - the list is separated by `,` here\n  \
+                  |\n  \
+                  = note: the items of a list are either all separated by `,`, or none of them is\n";
+        assert_eq!(gt, render_err("(f a, b c)", &theme));
+    }
+
+    #[test]
+    fn ident_raw_unicode() {
+        let input = "const ñandú:u8 = 1 const r#ñandú:u8 = 2";
+        let theme = Theme {
+            context_lines: 0,
+            ..Theme::plain()
+        };
+        let gt = "error[E0007]: Shadowed identifier\n \
+                  --> 1:26\n  \
+                  |\n\
+                  1 | const ñandú:u8 = 1 const r#ñandú:u8 = 2\n  \
+                  |                          ^^^^^^^ `r#ñandú` redefined here\n \
+                  ::: 1:7\n  \
+                  |\n\
+                  1 | const ñandú:u8 = 1 const r#ñandú:u8 = 2\n  \
+                  |       ----- first defined here\n  \
+                  |\n  \
+                  = note: identifiers can't be redefined, nor shadowed in an inner block\n";
+        assert_eq!(gt, render_err(input, &theme));
+    }
+
+    #[test]
+    fn color() {
+        let theme = Theme {
+            color: true,
+            ..Theme::plain()
+        };
+        assert!(render_err("{ }}", &theme).contains("\x1b[1;31merror[E0003]\x1b[0m"));
+    }
+}
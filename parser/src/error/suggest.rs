@@ -0,0 +1,76 @@
+//! "Did you mean" suggestions.
+
+/// Pick the candidate most similar to `word`, if any is similar enough to
+/// be worth suggesting.
+///
+/// Similarity is measured as the number of single-character edits
+/// (insertions, deletions, substitutions and transpositions of adjacent
+/// characters) needed to turn one word into the other.
+///
+/// ```
+/// use parser::error::suggest;
+///
+/// assert_eq!(Some("static"), suggest("statc", vec!["static", "const"]));
+/// assert_eq!(None, suggest("foo", vec!["static", "const"]));
+/// ```
+pub fn suggest<'c, I>(word: &str, candidates: I) -> Option<&'c str>
+where
+    I: IntoIterator<Item = &'c str>,
+{
+    let max = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// optimal string alignment distance.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{distance, suggest};
+
+    #[test]
+    fn distances() {
+        assert_eq!(0, distance("let", "let"));
+        assert_eq!(1, distance("lett", "let"));
+        assert_eq!(1, distance("statc", "static"));
+        assert_eq!(1, distance("retrun", "return"));
+        assert_eq!(3, distance("", "for"));
+    }
+
+    #[test]
+    fn closest() {
+        let keywords = vec!["let", "loop", "const", "continue"];
+        assert_eq!(Some("loop"), suggest("lop", keywords.clone()));
+        assert_eq!(Some("continue"), suggest("contineu", keywords.clone()));
+        assert_eq!(None, suggest("let", keywords.clone()));
+        assert_eq!(None, suggest("foo", keywords));
+    }
+}
@@ -0,0 +1,170 @@
+//! HTML rendering of source code.
+//!
+//! Renders the source of a parsed program with syntax highlighting. Every
+//! token is wrapped in a `<span>` with a CSS class describing it, and the
+//! identifiers of `fn`, `static`, `const`, `type` and `mod` declarations get an
+//! anchor, so they can be linked to (`#fn.main`, `#static.a::FOO`, ...).
+use crate::{
+    ast::{Ast, Statement},
+    cst::{Cst, CstToken, Trivia},
+    lex::{span::Spanned, Category, Ident, Token},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+/// Render the source code of a parsed program as HTML.
+///
+/// `input` must be the same source the `ast` was parsed from.
+pub fn render(input: &str, ast: &Ast<'_>) -> String {
+    let mut anchors = HashMap::new();
+    let mut ids = HashSet::new();
+    declarations(&ast.inner, "", &mut |kind, prefix, ident| {
+        let mut id = format!("{}.{}{}", kind, prefix, ident);
+        let mut n = 1;
+        while ids.contains(&id) {
+            n += 1;
+            id = format!("{}.{}{}-{}", kind, prefix, ident, n);
+        }
+        ids.insert(id.clone());
+        anchors.insert(ident.span().min, id);
+    });
+
+    let mut out = String::from("<pre class=\"ggb\"><code>");
+    for CstToken { leading, token } in Cst::new(input).tokens() {
+        for trivia in leading {
+            let text = escape(trivia.as_str());
+            let _ = match trivia {
+                Trivia::Whitespace(_) => write!(out, "{}", text),
+                Trivia::Comment(_) => write!(out, "<span class=\"comment\">{}</span>", text),
+                Trivia::Unexpected(_) => write!(out, "<span class=\"error\">{}</span>", text),
+            };
+        }
+        if let Token::Eof(_) = token {
+            break;
+        }
+        let text = escape(&token.to_string());
+        let class = class(token);
+        let _ = match anchors.get(&token.span().min) {
+            Some(id) => write!(
+                out,
+                "<a class=\"{} decl\" id=\"{}\" href=\"#{}\">{}</a>",
+                class,
+                escape(id),
+                escape(id),
+                text
+            ),
+            None => write!(out, "<span class=\"{}\">{}</span>", class, text),
+        };
+    }
+    out.push_str("</code></pre>");
+    out
+}
+
+// css class of a token.
+fn class(token: &Token<'_>) -> &'static str {
+    match Category::of(token) {
+        Some(Category::Keyword) => "keyword",
+        Some(Category::Type) => "type",
+        Some(Category::Number) => "number",
+        Some(Category::String) => "string",
+        Some(Category::Identifier) => "ident",
+        Some(Category::Label) => "label",
+        Some(Category::Register) => "register",
+        Some(Category::Comment) => "comment",
+        Some(Category::Unexpected) => "error",
+        Some(Category::Operator) | Some(Category::Punctuation) | None => "operator",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// visit declarations (kind, module prefix, identifier).
+fn declarations<'a, F>(statements: &[Statement<'a>], prefix: &str, fun: &mut F)
+where
+    F: FnMut(&str, &str, &Ident<'a>),
+{
+    for statement in statements {
+        match statement {
+            Statement::Fn(fn_) => {
+                fun("fn", prefix, &fn_.ident);
+                declarations(&fn_.inner, prefix, fun);
+            }
+            Statement::Static(static_) => fun("static", prefix, &static_.field.ident),
+            Statement::Const(const_) => fun("const", prefix, &const_.field.ident),
+            Statement::Enum(enum_) => fun("enum", prefix, &enum_.ident),
+            Statement::TypeAlias(alias) => fun("type", prefix, &alias.ident),
+            Statement::Mod(mod_) => {
+                fun("mod", prefix, &mod_.ident);
+                let prefix = format!("{}{}::", prefix, mod_.ident);
+                declarations(&mod_.inner, &prefix, fun);
+            }
+            Statement::If(if_) => declarations(&if_.inner, prefix, fun),
+            Statement::IfElse(if_else) => {
+                declarations(&if_else.if_.inner, prefix, fun);
+                declarations(&if_else.else_.inner, prefix, fun);
+            }
+            Statement::Scope(scope) => declarations(&scope.inner, prefix, fun),
+            Statement::Defer(defer) => declarations(&defer.inner, prefix, fun),
+            Statement::For(for_) => declarations(&for_.inner, prefix, fun),
+            Statement::Loop(loop_) => declarations(&loop_.inner, prefix, fun),
+            Statement::While(while_) => declarations(&while_.inner, prefix, fun),
+            Statement::Match(match_) => {
+                for arm in &match_.arms {
+                    declarations(&arm.inner, prefix, fun);
+                }
+                if let Some(else_) = &match_.else_ {
+                    declarations(&else_.inner, prefix, fun);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::render;
+
+    #[test]
+    fn highlight() {
+        let input = "static FOO:u8 // <foo>\n(= FOO 0x42)";
+        let ast = crate::parse(input).unwrap();
+        let gt = "<pre class=\"ggb\"><code>\
+                  <span class=\"keyword\">static</span> \
+                  <a class=\"ident decl\" id=\"static.FOO\" href=\"#static.FOO\">FOO</a>\
+                  <span class=\"operator\">:</span>\
+                  <span class=\"type\">u8</span> \
+                  <span class=\"comment\">// &lt;foo&gt;</span>\n\
+                  <span class=\"operator\">(</span>\
+                  <span class=\"operator\">=</span> \
+                  <span class=\"ident\">FOO</span> \
+                  <span class=\"number\">0x42</span>\
+                  <span class=\"operator\">)</span>\
+                  </code></pre>";
+        assert_eq!(gt, render(input, &ast));
+    }
+
+    #[test]
+    fn anchors() {
+        let input = "mod a { fn foo { } } fn foo { } { fn foo { } }";
+        let html = render(input, &crate::parse(input).unwrap());
+        assert!(html.contains("id=\"mod.a\""));
+        assert!(html.contains("id=\"fn.a::foo\""));
+        assert!(html.contains("id=\"fn.foo\""));
+        assert!(html.contains("id=\"fn.foo-2\""));
+    }
+}
@@ -0,0 +1,466 @@
+//! Incremental re-parsing.
+//!
+//! When a program is edited, only the top-level statements touched by the
+//! edit need to be parsed again. The statements before the edit are reused
+//! as they are, and the statements after it are reused with their spans
+//! shifted, as soon as the parser reaches the beginning of one of them.
+//!
+//! Declarations that the statements after them are parsed with (consts,
+//! macros, ...) are parsed again instead of being reused. If the edit touches
+//! one of them, nothing after the edit is reused. The statements after the
+//! edit that invoke a macro are parsed again too, expanding the macro.
+//!
+//! Because the `Ast` borrows the source code, the reused statements keep
+//! borrowing the previous source, which must outlive the new `Ast`.
+//!
+//! # Example
+//! ```
+//! use parser::incremental::Edit;
+//!
+//! let before = "static FOO:u8\nstatic BAR:u8\n";
+//! let after = "static FOO:u8\nstatic BAZ:u8\n";
+//! let ast = parser::parse(before).unwrap();
+//!
+//! let edit = Edit {
+//!     range: 21..24,
+//!     text: "BAZ",
+//! };
+//! let ast = parser::reparse(ast, before, &edit, after).unwrap();
+//! assert_eq!(format!("{:?}", parser::parse(after).unwrap()), format!("{:?}", ast));
+//! ```
+use crate::{
+    arena::Node,
+    ast::{self, Ast, Context, Doc, ErrorNode, Grammar, MacroCall, Path, Statement, Visitor},
+    lex::{
+        span::{LineIndex, Span, Spanned},
+        Tokens,
+    },
+    Error,
+};
+use std::ops::Range;
+
+/// Edit of the source code.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Edit<'e> {
+    /// Byte range of the previous source code replaced by the edit.
+    pub range: Range<usize>,
+
+    /// Text inserted in place of the range.
+    pub text: &'e str,
+}
+
+// Update the positions of the spans of a node.
+pub(crate) trait Remap {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]);
+}
+
+impl Remap for Span {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.min = f(self.min);
+        self.max = f(self.max);
+    }
+}
+
+impl<T: Remap> Remap for Node<T> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        (**self).remap(f);
+    }
+}
+
+impl<T: Remap> Remap for Option<T> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        if let Some(inner) = self {
+            inner.remap(f);
+        }
+    }
+}
+
+impl<T: Remap> Remap for Vec<T> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        for inner in self {
+            inner.remap(f);
+        }
+    }
+}
+
+impl<A: Remap, B: Remap> Remap for (A, B) {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.0.remap(f);
+        self.1.remap(f);
+    }
+}
+
+impl Remap for Path<'_> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.head.remap(f);
+        self.tail.remap(f);
+    }
+}
+
+impl<I: Remap> Remap for ast::expression::LispNode<'_, I> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.left_par.remap(f);
+        self.inner.remap(f);
+        self.right_par.remap(f);
+    }
+}
+
+impl Remap for Doc<'_> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.span.remap(f);
+    }
+}
+
+impl Remap for ErrorNode<'_> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.tokens.remap(f);
+        self.span.remap(f);
+    }
+}
+
+impl Remap for Ast<'_> {
+    fn remap(&mut self, f: &dyn Fn([usize; 2]) -> [usize; 2]) {
+        self.inner.remap(f);
+        self.eof.remap(f);
+    }
+}
+
+pub(crate) fn reparse<'a>(
+    context: &mut Context<'a>,
+    previous: Ast<'a>,
+    previous_input: &str,
+    edit: &Edit<'_>,
+    input: &'a str,
+) -> Result<Ast<'a>, Error<'a>> {
+    debug_assert_eq!(
+        (
+            &previous_input[..edit.range.start],
+            &previous_input[edit.range.end..]
+        ),
+        (
+            &input[..edit.range.start],
+            &input[edit.range.start + edit.text.len()..]
+        ),
+        "the edit doesn't match the new source code"
+    );
+    let old = LineIndex::new(previous_input);
+    let new = LineIndex::new(input);
+    let start = old.position(edit.range.start);
+    let old_end = old.position(edit.range.end);
+    let new_end = new.position(edit.range.start + edit.text.len());
+
+    // positions after the edit, from the previous source into the new one
+    let remap = move |[line, col]: [usize; 2]| {
+        if line == old_end[0] {
+            [new_end[0], col - old_end[1] + new_end[1]]
+        } else {
+            [line - old_end[0] + new_end[0], col]
+        }
+    };
+
+    let Ast {
+        inner: mut statements,
+        mut eof,
+    } = previous;
+
+    // statements after the edit are candidates for reuse
+    let split = statements
+        .iter()
+        .position(|s| s.span().min >= old_end)
+        .unwrap_or(statements.len());
+    let mut after = statements.split_off(split).into_iter().peekable();
+
+    // statements before the edit are reused, except for the last one, as it
+    // may continue past its end (an `if` followed by an `else`, ...)
+    let keep = statements
+        .iter()
+        .take_while(|s| s.span().max < start)
+        .count()
+        .saturating_sub(1);
+    let position = match keep {
+        0 => [0, 0],
+        _ => statements[keep].span().min,
+    };
+
+    // the statements after an edited declaration may depend on it, so none of
+    // them is reused
+    let mut edited = statements[keep..]
+        .iter()
+        .any(|s| declares(s) && s.span().max >= start);
+    statements.truncate(keep);
+
+    let (source_id, version) = (context.source_id(), context.version());
+    let tokens_at = |position| {
+        Tokens::with_position(input, source_id, new.offset(position), position)
+            .with_version(version)
+            .peekable()
+    };
+
+    // the reused declarations are parsed again, for the context to know them
+    for statement in &mut statements {
+        if declares(statement) {
+            let mut tokens = tokens_at(statement.span().min);
+            if let Some(declaration) = Grammar::parse(context, &mut tokens)? {
+                *statement = declaration;
+            }
+        }
+    }
+
+    let mut tokens = tokens_at(position);
+    let mut synced = false;
+    'parse: loop {
+        if let (false, Some(Ok(token))) = (edited, tokens.peek()) {
+            let span = token.span();
+            let position = span.min;
+            while let Some(statement) = after.peek() {
+                let min = remap(statement.span().min);
+                if min > position {
+                    break;
+                }
+                let mut statement = after.next().unwrap();
+                if min < position {
+                    // replaced by the statement being parsed
+                    edited |= declares(&statement);
+                    continue;
+                }
+                synced = true;
+                if !reusable(&statement) {
+                    break;
+                }
+                // the rest of the program is unchanged, except for the doc
+                // comment of the first statement, which precedes it
+                statement.remap(&remap);
+                if let (Some(doc), Some(source)) = (doc_mut(&mut statement), context.source()) {
+                    *doc = Doc::before(source, span);
+                }
+                statements.push(statement);
+                while let Some(mut statement) = after.next_if(reusable) {
+                    statement.remap(&remap);
+                    statements.push(statement);
+                }
+                match after.peek() {
+                    // parse the next statement again, and keep reusing after it
+                    Some(statement) => {
+                        tokens = tokens_at(remap(statement.span().min));
+                        continue 'parse;
+                    }
+                    None => {
+                        eof.remap(&remap);
+                        return Ok(Ast {
+                            inner: statements,
+                            eof,
+                        });
+                    }
+                }
+            }
+        }
+        match Grammar::parse(context, &mut tokens)? {
+            Some(statement) => {
+                if !synced && declares(&statement) && statement.span().max >= start {
+                    edited = true;
+                }
+                statements.push(statement)
+            }
+            None => match ast::unbalanced_bracket(context, &mut tokens) {
+                Some(error) => statements.push(error),
+                None => break,
+            },
+        }
+    }
+
+    Ok(Ast {
+        inner: statements,
+        eof: Grammar::parse(context, &mut tokens)?,
+    })
+}
+
+// whether the statement declares something visible to the statements after
+// it while they are parsed (the consts of `if const` conditions, macros, ...).
+// The context only knows about the statements it parses.
+fn declares(statement: &Statement<'_>) -> bool {
+    match statement {
+        Statement::Const(_)
+        | Statement::Enum(_)
+        | Statement::Macro(_)
+        | Statement::Mod(_)
+        | Statement::Import(_)
+        | Statement::IfConst(_) => true,
+        Statement::Fn(fn_) => fn_.const_.is_some(),
+        _ => false,
+    }
+}
+
+// whether a statement after the edit can be reused with its spans shifted.
+// The statements expanded by a macro call point into the definition of the
+// macro, so the call is expanded again instead.
+fn reusable(statement: &Statement<'_>) -> bool {
+    struct Calls(bool);
+
+    impl<'a> Visitor<'a> for Calls {
+        fn visit_macro_call(&mut self, _: &MacroCall<'a>) {
+            self.0 = true;
+        }
+    }
+
+    let mut calls = Calls(false);
+    calls.visit_statement(statement);
+    !declares(statement) && !calls.0
+}
+
+// doc comment of a declaration.
+fn doc_mut<'s, 'a>(statement: &'s mut Statement<'a>) -> Option<&'s mut Option<Doc<'a>>> {
+    match statement {
+        Statement::Fn(fn_) => Some(&mut fn_.doc),
+        Statement::Static(static_) => Some(&mut static_.doc),
+        Statement::Const(const_) => Some(&mut const_.doc),
+        Statement::Enum(enum_) => Some(&mut enum_.doc),
+        Statement::TypeAlias(alias) => Some(&mut alias.doc),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Edit;
+    use crate::ContextBuilder;
+
+    const INPUT: &str = "static FOO:u8\n\
+                         fn foo {\n    \
+                             if (== FOO 0) { (= FOO 1) }\n\
+                         }\n\
+                         static BAR:u8 let baz:u8 = (+ FOO 1)\n\
+                         (foo)\n";
+
+    fn apply(input: &str, edit: &Edit<'_>) -> String {
+        let mut output = input.to_string();
+        output.replace_range(edit.range.clone(), edit.text);
+        output
+    }
+
+    fn assert_reparse(input: &str, edit: Edit<'_>) {
+        let output = apply(input, &edit);
+        let ast = crate::parse(input).unwrap();
+        let gt = crate::parse(&output);
+        let ast = crate::reparse(ast, input, &edit, &output);
+        assert_eq!(format!("{:?}", gt), format!("{:?}", ast), "{:?}", edit);
+    }
+
+    #[test]
+    fn reparse() {
+        let edits = [
+            // same line
+            (7..10, "FOOO"),
+            // inside a fn
+            (31..32, "1"),
+            (59..60, " else { (= FOO 2) }\n"),
+            // new lines
+            (0..0, "\n\n"),
+            (14..14, "static QUX:u8\n"),
+            // merge & split lines
+            (51..61, ""),
+            (65..66, "\n"),
+            // whole program
+            (0..INPUT.len(), "static FOO:u8"),
+            (INPUT.len()..INPUT.len(), "(foo)"),
+            // doc comments
+            (14..14, "/// Foo.\n"),
+            (0..0, "/// Foo.\n"),
+            // errors
+            (4..5, "}"),
+            (60..61, ""),
+        ];
+        for (range, text) in edits.iter().cloned() {
+            assert_reparse(INPUT, Edit { range, text });
+        }
+    }
+
+    #[test]
+    fn reparse_declarations() {
+        // statements evaluated with the consts declared before them
+        let input = "const C:bool = false\n\
+                     static X:u8\n\
+                     if const C { (= X 1) } else { (= X 2) }\n\
+                     static_assert((== C 0), \"C is false\")\n";
+        let edits = [
+            // edited const
+            (15..20, "true"),
+            (0..21, ""),
+            (0..0, "fn f {\n"),
+            // before and after the const
+            (0..0, "static Y:u8\n"),
+            (32..32, " "),
+            (68..69, "3"),
+        ];
+        for (range, text) in edits.iter().cloned() {
+            assert_reparse(input, Edit { range, text });
+        }
+    }
+
+    #[test]
+    fn reparse_macro_calls() {
+        // the expanded statements point into the definition of the macro
+        let input = "macro m() { (= X 1) }\n\
+                     static X:u8\n\
+                     m!()\n\
+                     fn f { m!() }\n";
+        let edits = [
+            (29..29, " "),
+            (22..22, "static Y:u8\n"),
+            (0..0, "static Y:u8\n"),
+            (17..18, "2"),
+        ];
+        for (range, text) in edits.iter().cloned() {
+            assert_reparse(input, Edit { range, text });
+        }
+    }
+
+    #[test]
+    fn reparse_recovering() {
+        // every single character insertion and deletion
+        let mut edits = Vec::new();
+        for i in 0..=INPUT.len() {
+            for text in &["x", "}", "\n", " ", "/// x\n"] {
+                edits.push(Edit { range: i..i, text });
+            }
+            if i < INPUT.len() {
+                edits.push(Edit {
+                    range: i..i + 1,
+                    text: "",
+                });
+            }
+        }
+        for edit in edits {
+            let output = apply(INPUT, &edit);
+            let (ast, _) = crate::parse_recovering(INPUT);
+            let mut context = ContextBuilder::default().error_tolerant(true).build();
+            let ast = crate::reparse_with_context(ast, INPUT, &edit, &output, &mut context);
+            assert_eq!(
+                format!("{:?}", crate::parse_recovering(&output).0),
+                format!("{:?}", ast.unwrap()),
+                "{:?}",
+                edit
+            );
+        }
+    }
+
+    #[test]
+    fn reuse() {
+        // errors in the reused statements are not reported again
+        let input = "static FOO:u8\n(= FOO\nstatic BAR:u8\nstatic BAZ:u8\n";
+        let edit = Edit {
+            range: 42..45,
+            text: "QUX",
+        };
+        let output = apply(input, &edit);
+
+        let mut context = ContextBuilder::default().error_tolerant(true).build();
+        let ast = crate::parse_with_context(input, &mut context).unwrap();
+        assert_eq!(1, context.take_errors().len());
+        let ast = crate::reparse_with_context(ast, input, &edit, &output, &mut context).unwrap();
+        assert!(context.errors().is_empty(), "{:?}", context.errors());
+        assert_eq!(
+            format!("{:?}", crate::parse_recovering(&output).0),
+            format!("{:?}", ast)
+        );
+    }
+}
@@ -1,16 +1,67 @@
 //! Token definitions and lexical analysis.
-use crate::{lex::span::Span, Error};
+use crate::{
+    lex::span::{SourceId, Span},
+    Error, LanguageVersion,
+};
 
 #[macro_use]
 mod macros;
+mod classify;
 mod raw;
 pub mod span;
 
+pub use classify::{classify, Category};
+
+/// Streaming lexer.
+///
+/// Tokens are produced lazily, one at a time, without parsing the program,
+/// which makes this iterator suitable for syntax highlighters and other
+/// tools that don't need an [`Ast`](crate::Ast). Whitespace and comments are
+/// skipped, and the stream always ends with a [`Token::Eof`](Token::Eof).
+///
+/// Lexing errors are yielded in place of the offending source and don't end
+/// the stream.
+///
+/// ```
+/// use parser::lex::{Token, Tokens};
+///
+/// let mut tokens = Tokens::new("static FOO:u8 // comment").spanned();
+/// let (token, span) = tokens.next().unwrap().unwrap();
+/// assert!(matches!(token, Token::Static(_)));
+/// assert_eq!([0, 0], span.min);
+/// assert_eq!(4, tokens.count());
+/// ```
+#[derive(Debug, Clone)]
 pub struct Tokens<'a> {
     ended: bool,
     raw: raw::Tokens<'a>,
+    // previously lexed tokens, emitted instead of lexing the input
+    replay: Option<std::vec::IntoIter<Token<'a>>>,
 }
 
+/// Iterator over tokens and their spans.
+///
+/// Returned by [`Tokens::spanned`](Tokens::spanned).
+#[derive(Debug, Clone)]
+pub struct SpannedTokens<'a> {
+    tokens: Tokens<'a>,
+}
+
+impl<'a> Iterator for SpannedTokens<'a> {
+    type Item = Result<(Token<'a>, Span), Error<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next().map(|token| {
+            token.map(|token| {
+                let span = span::Spanned::span(&token);
+                (token, span)
+            })
+        })
+    }
+}
+
+impl std::iter::FusedIterator for SpannedTokens<'_> {}
+
 tokens! {
     // two character tokens
 
@@ -20,6 +71,12 @@ tokens! {
     /// `..`
     ".." => DotDot,
 
+    /// `&&`
+    "&&" => AmpersandAmpersand,
+
+    /// `||`
+    "||" => PipePipe,
+
     /// `::`
     "::" => Square,
 
@@ -44,6 +101,12 @@ tokens! {
     /// `^=`
     "^=" => CaretAssign,
 
+    /// `++`
+    "++" => PlusPlus,
+
+    /// `--`
+    "--" => MinusMinus,
+
     /// `<<`
     "<<" => LessLess,
 
@@ -124,6 +187,15 @@ tokens! {
     /// `@`
     "@" => At,
 
+    /// `?`
+    "?" => Question,
+
+    /// `!`
+    "!" => Bang,
+
+    /// `#`
+    "#" => Hash,
+
     /// `>`
     ">" => Greater,
 
@@ -147,36 +219,66 @@ tokens! {
     /// `in`
     "in" => In,
 
+    /// `as`
+    "as" => As,
+
+    /// `sizeof`
+    "sizeof" => SizeOf,
+
     /// `enum`
     "enum" => Enum,
 
+    /// `type`
+    "type" => Type,
+
     /// `use`
     "use" => Use,
 
+    /// `import`
+    "import" => Import,
+
     /// `asm`
     "asm" => Asm,
 
     /// `static`
     "static" => Static,
 
+    /// `static_assert`
+    "static_assert" => StaticAssert,
+
     /// `const`
     "const" => Const,
 
     /// `pub`
     "pub" => Pub,
 
+    /// `volatile`
+    "volatile" => Volatile,
+
     /// `for`
     "for" => For,
 
     /// `loop`
     "loop" => Loop,
 
+    /// `match`
+    "match" => Match,
+
+    /// `while`
+    "while" => While,
+
     /// `let`
     "let" => Let,
 
     /// `fn`
     "fn" => Fn,
 
+    /// `macro`
+    "macro" => Macro,
+
+    /// `memory`
+    "memory" => Memory,
+
     /// `if`
     "if" => If,
 
@@ -192,6 +294,9 @@ tokens! {
     /// `return`
     "return" => Return,
 
+    /// `defer`
+    "defer" => Defer,
+
     /// `in`
     "read" => Read,
 
@@ -206,6 +311,18 @@ tokens! {
     /// `i8`
     "i8" => I8,
 
+    /// `u16`
+    "u16" => U16,
+
+    /// `i16`
+    "i16" => I16,
+
+    /// `bool`
+    "bool" => Bool,
+
+    /// `fx8.8`
+    "fx8.8" => Fixed,
+
     // asm registers
 
     /// `%a`
@@ -329,45 +446,273 @@ tokens! {
     /// Literal
     "" => Lit,
 
+    /// Loop label
+    "" => Label,
+
     // misc tokens
 
     /// `EOF`
     "" => Eof,
 }
 
+impl<'a> Ident<'a> {
+    /// Name of the identifier, without the `r#` prefix of a raw identifier.
+    ///
+    /// `r#foo` and `foo` are the same identifier, so names should be compared
+    /// with this method rather than with the text of the token.
+    pub fn name(&self) -> &'a str {
+        let text = match (self.0).0 {
+            raw::RawToken::Ident(text) => text,
+            _ => unreachable!(),
+        };
+        text.strip_prefix("r#").unwrap_or(text)
+    }
+
+    /// Returns whether the identifier is a raw identifier (`r#loop`), which
+    /// can be named after a keyword.
+    pub fn is_raw(&self) -> bool {
+        matches!((self.0).0, raw::RawToken::Ident(text) if text.starts_with("r#"))
+    }
+}
+
+impl Lit<'_> {
+    /// Value of a character literal (`'A'`, `'\n'`, `'\xFF'`, ...).
+    ///
+    /// Returns `None` if the literal is not a character literal.
+    pub fn char_value(&self) -> Option<u8> {
+        let text = match (self.0).0 {
+            raw::RawToken::Lit(text) => text,
+            _ => unreachable!(),
+        };
+        let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+        match unescape(inner)? {
+            (c, len) if len == inner.len() => Some(c as u8),
+            _ => None,
+        }
+    }
+
+    /// Value of a number literal (`42`, `0x2a`, `052`, ...).
+    ///
+    /// Numbers with a leading `0` are octal. Returns `None` if the literal is
+    /// not a number literal, or if it doesn't fit in 16 bits.
+    pub fn num_value(&self) -> Option<u16> {
+        let text = match (self.0).0 {
+            raw::RawToken::Lit(text) => text,
+            _ => unreachable!(),
+        };
+        if let Some(hex) = text.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16).ok()
+        } else if text.starts_with('0') && text.len() > 1 {
+            u16::from_str_radix(&text[1..], 8).ok()
+        } else {
+            text.parse().ok()
+        }
+    }
+
+    /// Returns whether the literal is a fixed-point literal (`1.5fx`, `3fx`).
+    pub fn is_fixed(&self) -> bool {
+        match (self.0).0 {
+            raw::RawToken::Lit(text) => {
+                text.ends_with("fx") && text.starts_with(|c: char| c.is_ascii_digit())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Value of a fixed-point literal (`1.5fx`, `0.25fx`, `3fx`), as the raw
+    /// bits of an `fx8.8` value (`1.5fx` is `0x0180`).
+    ///
+    /// The fractional part is rounded to the nearest multiple of `1/256`.
+    /// Returns `None` if the literal is not a fixed-point literal, or if it
+    /// doesn't fit in `fx8.8`.
+    pub fn fixed_value(&self) -> Option<u16> {
+        let text = match (self.0).0 {
+            raw::RawToken::Lit(text) => text,
+            _ => unreachable!(),
+        };
+        let number = text.strip_suffix("fx")?;
+        let (int, frac) = match number.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (number, "0"),
+        };
+        if int.is_empty() || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let int: u32 = int.parse().ok()?;
+        let frac: f64 = format!("0.{}", frac).parse().ok()?;
+        match int.checked_mul(256)? + (frac * 256.0).round() as u32 {
+            value if value <= 0xffff => Some(value as u16),
+            _ => None,
+        }
+    }
+
+    /// Value of a boolean literal (`true` or `false`).
+    ///
+    /// Returns `None` if the literal is not a boolean literal.
+    pub fn bool_value(&self) -> Option<bool> {
+        match (self.0).0 {
+            raw::RawToken::Lit("true") => Some(true),
+            raw::RawToken::Lit("false") => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Value of a string literal (`"HELLO"`, `"\n"`, ...), with its escape
+    /// sequences resolved.
+    ///
+    /// `\xHH` escapes are resolved into the character `U+00HH`. Returns `None`
+    /// if the literal is not a string literal.
+    pub fn str_value(&self) -> Option<String> {
+        let text = match (self.0).0 {
+            raw::RawToken::Lit(text) => text,
+            _ => unreachable!(),
+        };
+        let mut inner = text.strip_prefix('"')?.strip_suffix('"')?;
+        let mut value = String::with_capacity(inner.len());
+        while !inner.is_empty() {
+            let (c, len) = unescape(inner)?;
+            value.push(c);
+            inner = &inner[len..];
+        }
+        Some(value)
+    }
+}
+
+// first (possibly escaped) character of a literal, and its length in bytes.
+fn unescape(text: &str) -> Option<(char, usize)> {
+    Some(match text.as_bytes() {
+        [b'\\', b'x', ..] => (u8::from_str_radix(text.get(2..4)?, 16).ok()?.into(), 4),
+        [b'\\', b'n', ..] => ('\n', 2),
+        [b'\\', b'r', ..] => ('\r', 2),
+        [b'\\', b't', ..] => ('\t', 2),
+        [b'\\', b'0', ..] => ('\0', 2),
+        [b'\\', c, ..] => ((*c).into(), 2),
+        _ => {
+            let c = text.chars().next()?;
+            (c, c.len_utf8())
+        }
+    })
+}
+
+impl<'a> AmpersandAmpersand<'a> {
+    // split into two `&` tokens, for pointer to pointer types (`&&u8`)
+    pub(crate) fn split(self) -> (Ampersand<'a>, Ampersand<'a>) {
+        let (_, span) = self.0;
+        let mid = [span.min[0], span.min[1] + 1];
+        (
+            Ampersand((
+                raw::RawToken::Keyword("&"),
+                Span {
+                    min: span.min,
+                    max: mid,
+                    source: span.source,
+                },
+            )),
+            Ampersand((
+                raw::RawToken::Keyword("&"),
+                Span {
+                    min: mid,
+                    max: span.max,
+                    source: span.source,
+                },
+            )),
+        )
+    }
+}
+
+// human-readable description of a token, used in error messages.
+pub(crate) fn describe(name: &str, text: &'static str) -> &'static str {
+    match name {
+        "Ident" => "identifier",
+        "Lit" => "literal",
+        "Label" => "label",
+        "Eof" => "end of file",
+        _ => text,
+    }
+}
+
 impl<'a> Tokens<'a> {
     /// Create new Tokens.
     pub fn new(input: &'a str) -> Self {
-        let kwords = KEYWORDS.iter().map(|s| s.to_string()).collect();
+        let kwords = KEYWORDS.iter().copied().collect();
         Self {
             ended: false,
             raw: raw::Tokens::new(input, kwords),
+            replay: None,
         }
     }
 
+    // lex the input from a byte offset, located at the given position.
+    pub(crate) fn with_position(
+        input: &'a str,
+        source: SourceId,
+        offset: usize,
+        position: [usize; 2],
+    ) -> Self {
+        let kwords = KEYWORDS.iter().copied().collect();
+        Self {
+            ended: false,
+            raw: raw::Tokens::with_position(input, kwords, offset, position).with_source(source),
+            replay: None,
+        }
+    }
+
+    /// Create new Tokens, whose spans belong to the given source.
+    pub fn with_source(input: &'a str, source: SourceId) -> Self {
+        Self {
+            ended: false,
+            raw: Self::new(input).raw.with_source(source),
+            replay: None,
+        }
+    }
+
+    // emit the given tokens (the expansion of a macro), followed by an end of
+    // file located at `end`.
+    pub(crate) fn replay(mut tokens: Vec<Token<'a>>, end: Span) -> Self {
+        tokens.push(Token::Eof(Eof((raw::RawToken::Eof, end))));
+        Self {
+            replay: Some(tokens.into_iter()),
+            ..Self::new("")
+        }
+    }
+
+    /// Lex the keywords of the given version of the language only.
+    ///
+    /// The keywords of newer versions are lexed as identifiers.
+    pub fn with_version(self, version: LanguageVersion) -> Self {
+        Self {
+            raw: self.raw.retain_keywords(|kword| version.has_keyword(kword)),
+            ..self
+        }
+    }
+
+    /// Iterate over the tokens along with their spans.
+    pub fn spanned(self) -> SpannedTokens<'a> {
+        SpannedTokens { tokens: self }
+    }
+
     fn next_token(&mut self) -> Option<Result<Token<'a>, Error<'a>>> {
         if self.ended {
             return None;
         }
+        if let Some(replay) = &mut self.replay {
+            let token = replay.next();
+            self.ended = matches!(token, Some(Token::Eof(_)) | None);
+            return token.map(Ok);
+        }
         loop {
             match self.raw.next() {
-                Some(ts) if ts.0.is_unexpected() => {
-                    // TODO
-                    self.ended = true;
-                    return Some(Err(Error::UnexpectedByte {
-                        byte: 0,
-                        span: ts.1,
-                    }));
+                Some((raw::RawToken::Unexpected(byte), span)) => {
+                    return Some(Err(Error::UnexpectedByte { byte, span }));
                 }
                 Some(ts) if ts.0.is_ident() => return Some(Ok(Token::Ident(Ident(ts)))),
                 Some(ts) if ts.0.is_lit() => return Some(Ok(Token::Lit(Lit(ts)))),
+                Some(ts) if ts.0.is_label() => return Some(Ok(Token::Label(Label(ts)))),
                 Some(ts) if ts.0.is_eof() => {
                     self.ended = true;
                     return Some(Ok(Token::Eof(Eof(ts))));
                 }
-                Some((raw::RawToken::Keyword(keyword), span)) => {
-                    return match_token(self, keyword, span)
-                }
+                Some((raw::RawToken::Keyword(keyword), span)) => return match_token(keyword, span),
                 None => return None,
                 _ => unreachable!(),
             }
@@ -382,3 +727,5 @@ impl<'a> Iterator for Tokens<'a> {
         self.next_token()
     }
 }
+
+impl std::iter::FusedIterator for Tokens<'_> {}
@@ -0,0 +1,201 @@
+//! Classification of tokens for syntax highlighting.
+use crate::{
+    cst::{Cst, Trivia},
+    lex::{
+        span::{LineIndex, Span, Spanned},
+        Token,
+    },
+};
+
+/// Semantic category of a token.
+///
+/// Returned by [`classify`](classify).
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Category {
+    /// Keywords (`fn`, `static`, `if`, ...), the `true` and `false` literals,
+    /// and assembly instructions (`.ld`, `.halt`, ...).
+    Keyword,
+
+    /// Primitive types (`u8`, `i16`, `bool`, ...).
+    Type,
+
+    /// Numeric literals (`42`, `0xff`, `1.5fx`, ...).
+    Number,
+
+    /// String and character literals.
+    String,
+
+    /// Identifiers.
+    Identifier,
+
+    /// Loop labels (`'outer`).
+    Label,
+
+    /// Assembly registers (`%a`, `%hl`, ...).
+    Register,
+
+    /// Line comments (`// ...`).
+    Comment,
+
+    /// Operators (`+`, `<<=`, `&&`, `@`, ...).
+    Operator,
+
+    /// Delimiters and separators (`(`, `}`, `,`, `:`, `::`, ...).
+    Punctuation,
+
+    /// Bytes the lexer couldn't tokenize.
+    Unexpected,
+}
+
+impl Category {
+    /// Category of a token.
+    ///
+    /// Returns `None` for [`Token::Eof`](Token::Eof).
+    pub fn of(token: &Token<'_>) -> Option<Self> {
+        Some(match token {
+            Token::Eof(_) => return None,
+            Token::Ident(_) => Self::Identifier,
+            Token::Lit(lit) if lit.to_string().starts_with('"') => Self::String,
+            Token::Lit(lit) if lit.char_value().is_some() => Self::String,
+            Token::Lit(lit) if lit.bool_value().is_some() => Self::Keyword,
+            Token::Lit(_) => Self::Number,
+            Token::Label(_) => Self::Label,
+            Token::U8(_)
+            | Token::I8(_)
+            | Token::U16(_)
+            | Token::I16(_)
+            | Token::Bool(_)
+            | Token::Fixed(_) => Self::Type,
+            Token::LeftPar(_)
+            | Token::RightPar(_)
+            | Token::LeftBracket(_)
+            | Token::RightBracket(_)
+            | Token::LeftSquare(_)
+            | Token::RightSquare(_)
+            | Token::Comma(_)
+            | Token::Colon(_)
+            | Token::SemiColon(_)
+            | Token::Square(_)
+            | Token::Dot(_)
+            | Token::DotDot(_)
+            | Token::Hash(_) => Self::Punctuation,
+            token => {
+                let text = token.to_string();
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), _) if c.is_ascii_alphabetic() => Self::Keyword,
+                    (Some('%'), _) => Self::Register,
+                    (Some('.'), Some(c)) if c.is_ascii_alphabetic() => Self::Keyword,
+                    _ => Self::Operator,
+                }
+            }
+        })
+    }
+}
+
+/// Classify the tokens and comments of the given source code, in source order.
+///
+/// Any input is accepted: the program isn't parsed, and bytes the lexer
+/// couldn't tokenize are classified as
+/// [`Category::Unexpected`](Category::Unexpected).
+///
+/// ```
+/// use parser::lex::{classify, Category};
+///
+/// let classes: Vec<_> = classify("static FOO:u8 // foo")
+///     .into_iter()
+///     .map(|(category, _)| category)
+///     .collect();
+/// assert_eq!(
+///     vec![
+///         Category::Keyword,
+///         Category::Identifier,
+///         Category::Punctuation,
+///         Category::Type,
+///         Category::Comment,
+///     ],
+///     classes
+/// );
+/// ```
+pub fn classify(input: &str) -> Vec<(Category, Span)> {
+    let index = LineIndex::new(input);
+    let span = |min, max| Span {
+        min: index.position(min),
+        max: index.position(max),
+        source: Default::default(),
+    };
+
+    let mut classes = Vec::new();
+    let mut offset = 0;
+    for token in Cst::new(input).tokens() {
+        for trivia in &token.leading {
+            let len = trivia.as_str().len();
+            match trivia {
+                Trivia::Whitespace(_) => {}
+                Trivia::Comment(_) => classes.push((Category::Comment, span(offset, offset + len))),
+                Trivia::Unexpected(_) => {
+                    classes.push((Category::Unexpected, span(offset, offset + len)))
+                }
+            }
+            offset += len;
+        }
+        let token_span = token.token.span();
+        if let Some(category) = Category::of(&token.token) {
+            classes.push((category, token_span));
+        }
+        offset = index.offset(token_span.max);
+    }
+    classes
+}
+
+#[cfg(test)]
+mod test {
+    use super::{classify, Category};
+
+    #[test]
+    fn classify_all() {
+        let input = "fn f(x:&u8):bool { // é\n\
+                     'a: loop { (= x (+ 0x42 \"s\")) break 'a } $\n\
+                     return true }";
+        let classes: Vec<_> = classify(input)
+            .into_iter()
+            .map(|(category, span)| (category, span.min, span.max))
+            .collect();
+        let expected = [
+            (Category::Keyword, [0, 0], [0, 2]),
+            (Category::Identifier, [0, 3], [0, 4]),
+            (Category::Punctuation, [0, 4], [0, 5]),
+            (Category::Identifier, [0, 5], [0, 6]),
+            (Category::Punctuation, [0, 6], [0, 7]),
+            (Category::Operator, [0, 7], [0, 8]),
+            (Category::Type, [0, 8], [0, 10]),
+            (Category::Punctuation, [0, 10], [0, 11]),
+            (Category::Punctuation, [0, 11], [0, 12]),
+            (Category::Type, [0, 12], [0, 16]),
+            (Category::Punctuation, [0, 17], [0, 18]),
+            (Category::Comment, [0, 19], [0, 24]),
+            (Category::Label, [1, 0], [1, 2]),
+            (Category::Punctuation, [1, 2], [1, 3]),
+            (Category::Keyword, [1, 4], [1, 8]),
+            (Category::Punctuation, [1, 9], [1, 10]),
+            (Category::Punctuation, [1, 11], [1, 12]),
+            (Category::Operator, [1, 12], [1, 13]),
+            (Category::Identifier, [1, 14], [1, 15]),
+            (Category::Punctuation, [1, 16], [1, 17]),
+            (Category::Operator, [1, 17], [1, 18]),
+            (Category::Number, [1, 19], [1, 23]),
+            (Category::String, [1, 24], [1, 27]),
+            (Category::Punctuation, [1, 27], [1, 28]),
+            (Category::Punctuation, [1, 28], [1, 29]),
+            (Category::Keyword, [1, 30], [1, 35]),
+            (Category::Label, [1, 36], [1, 38]),
+            (Category::Punctuation, [1, 39], [1, 40]),
+            (Category::Unexpected, [1, 41], [1, 42]),
+            (Category::Keyword, [2, 0], [2, 6]),
+            (Category::Keyword, [2, 7], [2, 11]),
+            (Category::Punctuation, [2, 12], [2, 13]),
+        ];
+        assert_eq!(&expected[..], &classes[..]);
+    }
+}
@@ -6,14 +6,11 @@ macro_rules! tokens {
         const KEYWORDS: &[&str] = &[$($token_expr),+];
 
         #[allow(unused)]
-        fn match_token<'a>(tokens: &mut Tokens<'a>, kword: &'a str, span: Span) -> Option<Result<Token<'a>, Error<'a>>> {
+        fn match_token<'a>(kword: &'a str, span: Span) -> Option<Result<Token<'a>, Error<'a>>> {
             match kword {
                 // FIXME lints
                 $($token_expr => Some(Ok(Token::$token($token((raw::RawToken::Keyword(kword), span))))),)+
-                _ => {
-                    tokens.ended = true;
-                    return Some(Err(Error::ReservedKeyword { key_word: kword, span }));
-                }
+                _ => Some(Err(Error::ReservedKeyword { key_word: kword, span })),
             }
         }
 
@@ -34,16 +31,70 @@ macro_rules! tokens {
                 }
             }
 
+            impl crate::incremental::Remap for $token<'_> {
+                fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+                    (self.0).1.remap(f);
+                }
+            }
+
+            impl<'a> crate::ast::owned::Own<'a> for $token<'a> {
+                fn own(&mut self, f: &mut dyn std::ops::FnMut(&'a str) -> &'a str) {
+                    (self.0).0.own(f);
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $token<'_> {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    use serde::ser::SerializeStruct;
+                    let mut state = serializer.serialize_struct(stringify!($token), 2)?;
+                    state.serialize_field("text", &self.to_string())?;
+                    state.serialize_field("span", &(self.0).1)?;
+                    state.end()
+                }
+            }
+
+            // the kind of raw token is given by the type of the token
+            #[cfg(feature = "serde")]
+            impl<'de: 'a, 'a> serde::Deserialize<'de> for $token<'a> {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    #[derive(serde::Deserialize)]
+                    struct Repr<'a> {
+                        text: &'a str,
+                        span: crate::lex::span::Span,
+                    }
+                    let Repr { text, span } = serde::Deserialize::deserialize(deserializer)?;
+                    let raw = match stringify!($token) {
+                        "Ident" => raw::RawToken::Ident(text),
+                        "Lit" => raw::RawToken::Lit(text),
+                        "Label" => raw::RawToken::Label(text),
+                        "Eof" => raw::RawToken::Eof,
+                        _ => raw::RawToken::Keyword(text),
+                    };
+                    Ok(Self((raw, span)))
+                }
+            }
+
             impl<'a> crate::ast::Grammar<'a> for $token<'a> {
                 fn parse(
                     _: &mut crate::ast::Context<'a>,
                     tokens: &mut std::iter::Peekable<crate::lex::Tokens<'a>>,
                 ) -> Result<Self, crate::Error<'a>> {
+                    // the unexpected token is not consumed, so the parser can recover from it
+                    match tokens.peek() {
+                        Some(Ok(Token::$token(_))) => {}
+                        Some(Ok(token)) => {
+                            return Err(crate::Error::Expected {
+                                expected: crate::lex::describe(stringify!($token), concat!("`", $token_expr, "`")),
+                                found: token.clone(),
+                            })
+                        }
+                        Some(Err(_)) => return Err(tokens.next().unwrap().unwrap_err()),
+                        None => return Err(crate::Error::Eof),
+                    }
                     match tokens.next() {
                         Some(Ok(Token::$token(token))) => Ok(token),
-                        Some(Ok(token)) => Err(crate::Error::UnexpectedToken(token)),
-                        Some(Err(err)) => Err(err),
-                        None => unimplemented!(),
+                        _ => unreachable!(),
                     }
                 }
             }
@@ -62,6 +113,11 @@ macro_rules! tokens {
             }
         )+
 
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(bound(deserialize = "'de: 'a"))
+        )]
         #[derive(Debug, Clone)]
         pub enum Token<'a> {
             $($token($token<'a>),)+
@@ -82,5 +138,21 @@ macro_rules! tokens {
                 }
             }
         }
+
+        impl crate::incremental::Remap for Token<'_> {
+            fn remap(&mut self, f: &dyn std::ops::Fn([usize; 2]) -> [usize; 2]) {
+                match self {
+                    $(Token::$token(var) => var.remap(f),)+
+                }
+            }
+        }
+
+        impl<'a> crate::ast::owned::Own<'a> for Token<'a> {
+            fn own(&mut self, f: &mut dyn std::ops::FnMut(&'a str) -> &'a str) {
+                match self {
+                    $(Token::$token(var) => var.own(f),)+
+                }
+            }
+        }
     }
 }
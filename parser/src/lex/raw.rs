@@ -1,4 +1,4 @@
-use crate::lex::span::{Span, Spanned};
+use crate::lex::span::{SourceId, Span, Spanned};
 use std::{collections::HashSet, iter::Peekable, str::Bytes};
 
 pub type RawTokenSpan<'a> = (RawToken<'a>, Span);
@@ -20,6 +20,8 @@ pub enum RawToken<'a> {
     /// Tokens literal.
     /// Quoted strings & numeric values.
     Lit(&'a str),
+    /// Loop label (`'outer`).
+    Label(&'a str),
     /// Unexpected byte.
     Unexpected(u8),
     /// End of file.
@@ -32,12 +34,24 @@ impl std::fmt::Display for RawToken<'_> {
             RawToken::Keyword(s) => s.fmt(f),
             RawToken::Ident(s) => s.fmt(f),
             RawToken::Lit(s) => s.fmt(f),
+            RawToken::Label(s) => s.fmt(f),
             RawToken::Unexpected(s) => s.fmt(f),
             RawToken::Eof => Ok(()),
         }
     }
 }
 
+impl<'a> crate::ast::owned::Own<'a> for RawToken<'a> {
+    fn own(&mut self, f: &mut dyn FnMut(&'a str) -> &'a str) {
+        match self {
+            RawToken::Keyword(s) | RawToken::Ident(s) | RawToken::Lit(s) | RawToken::Label(s) => {
+                *s = f(s)
+            }
+            RawToken::Unexpected(_) | RawToken::Eof => {}
+        }
+    }
+}
+
 impl RawToken<'_> {
     pub fn is_kword(&self) -> bool {
         matches!(self, RawToken::Keyword(_))
@@ -51,6 +65,10 @@ impl RawToken<'_> {
         matches!(self, RawToken::Lit(_))
     }
 
+    pub fn is_label(&self) -> bool {
+        matches!(self, RawToken::Label(_))
+    }
+
     pub fn is_unexpected(&self) -> bool {
         matches!(self, RawToken::Unexpected(_))
     }
@@ -61,19 +79,21 @@ impl RawToken<'_> {
 }
 
 /// Input string Tokens.
+#[derive(Debug, Clone)]
 pub struct Tokens<'a> {
     ended: bool,
-    kwords: HashSet<String>,
+    kwords: HashSet<&'static str>,
     offset: usize,
     input: &'a str,
     chars: Peekable<Bytes<'a>>,
     line: usize,
     line_offset: usize,
+    source: SourceId,
 }
 
 impl<'a> Tokens<'a> {
     /// Create new Tokens.
-    pub fn new(input: &'a str, kwords: HashSet<String>) -> Self {
+    pub fn new(input: &'a str, kwords: HashSet<&'static str>) -> Self {
         let chars = input.bytes().peekable();
         Self {
             ended: false,
@@ -83,6 +103,36 @@ impl<'a> Tokens<'a> {
             chars,
             line: 0,
             line_offset: 0,
+            source: SourceId::default(),
+        }
+    }
+
+    /// Tag the spans of the tokens with the given source.
+    pub fn with_source(self, source: SourceId) -> Self {
+        Self { source, ..self }
+    }
+
+    /// Lex only the keywords for which `f` returns `true`. The others are
+    /// lexed as identifiers.
+    pub fn retain_keywords(mut self, f: impl FnMut(&&'static str) -> bool) -> Self {
+        self.kwords.retain(f);
+        self
+    }
+
+    /// Create new Tokens that begin lexing at the given byte `offset` of the
+    /// input, located at `position` (line and byte column).
+    pub fn with_position(
+        input: &'a str,
+        kwords: HashSet<&'static str>,
+        offset: usize,
+        [line, line_offset]: [usize; 2],
+    ) -> Self {
+        Self {
+            offset,
+            chars: input[offset..].bytes().peekable(),
+            line,
+            line_offset,
+            ..Self::new(input, kwords)
         }
     }
 
@@ -123,6 +173,37 @@ impl<'a> Tokens<'a> {
             _ => None,
         }
     }
+
+    // char at the cursor, which may span more than one byte.
+    fn peek_utf8(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    fn next_utf8(&mut self) -> Option<char> {
+        let c = self.peek_utf8()?;
+        for _ in 0..c.len_utf8() {
+            self.next_char().unwrap();
+        }
+        Some(c)
+    }
+
+    // `r#` followed by an identifier
+    fn raw_ident_ahead(&self) -> bool {
+        let mut chars = self.input[self.offset..].chars();
+        chars.next() == Some('r')
+            && chars.next() == Some('#')
+            && chars.next().map(is_ident_start).unwrap_or(false)
+    }
+
+    fn skip_ident(&mut self) {
+        while let Some(c) = self.peek_utf8() {
+            if !is_ident_continue(c) {
+                break;
+            }
+            self.next_utf8().unwrap();
+        }
+    }
+
     fn update_cursor(&mut self, b: u8) {
         if b == b'\n' {
             self.line += 1;
@@ -169,6 +250,7 @@ impl<'a> Tokens<'a> {
             }
         }
 
+        let fixed_lit_len = self.fixed_lit_len();
         match self.peek_char() {
             None => {
                 self.ended = true;
@@ -177,6 +259,7 @@ impl<'a> Tokens<'a> {
                     Span {
                         min: self.cursor(),
                         max: self.cursor(),
+                        source: self.source,
                     },
                 ))
             }
@@ -185,30 +268,148 @@ impl<'a> Tokens<'a> {
                 let min = self.cursor();
                 let lit = self.next_str_lit();
                 let max = self.cursor();
-                Some((Lit(lit), Span { min, max }))
+                Some((
+                    lit,
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                ))
+            }
+            /* char lit | label */
+            Some(b'\'') => {
+                let min = self.cursor();
+                let lit = self.next_char_lit();
+                let max = self.cursor();
+                Some((
+                    lit,
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                ))
+            }
+            /* fixed-point lit */
+            Some(_) if fixed_lit_len.is_some() => {
+                let min = self.cursor();
+                let cursor = self.offset;
+                for _ in 0..fixed_lit_len.unwrap() {
+                    self.next_char().unwrap();
+                }
+                let max = self.cursor();
+                Some((
+                    Lit(&self.input[cursor..self.offset]),
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                ))
             }
             /* num lit (decimal) */
             Some(b) if b.is_ascii_digit() && *b != b'0' => {
                 let min = self.cursor();
                 let lit = self.next_num_lit();
                 let max = self.cursor();
-                Some(((Lit(lit)), Span { min, max }))
+                Some((
+                    (Lit(lit)),
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                ))
             }
             /* ident | keyword | num lit (hex) */ _ => Some(self.next_ident_kword_hex_lit()),
         }
     }
 
-    fn next_str_lit(&mut self) -> &'a str {
+    // "HELLO", "\n", "\x41", ...
+    // unterminated literals and invalid escapes are lexed as an unexpected `"`
+    fn next_str_lit(&mut self) -> RawToken<'a> {
         let cursor = self.offset;
-        assert_eq!(Some(b'"'), self.next_char());
+        let bytes = &self.input.as_bytes()[cursor..];
+        let mut len = 1;
         loop {
-            let c = self.next_char();
-            match c {
-                Some(b'"') => return &self.input[cursor..self.offset],
-                None => panic!("EOF"),
-                _ => {}
+            match &bytes[len..] {
+                [b'"', ..] => break,
+                [b'\\', b'x', h, l, ..] if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() => {
+                    len += 4
+                }
+                [b'\\', e, ..] if b"nrt0\\'\"".contains(e) => len += 2,
+                [c, ..] if *c != b'\\' && *c != b'\n' => len += 1,
+                _ => return RawToken::Unexpected(self.next_char().unwrap()),
             }
         }
+        for _ in 0..=len {
+            self.next_char().unwrap();
+        }
+        RawToken::Lit(&self.input[cursor..self.offset])
+    }
+
+    // 'A', '\n', '\x41', ...
+    // or a label ('outer) if the quote is followed by an identifier
+    fn next_char_lit(&mut self) -> RawToken<'a> {
+        let cursor = self.offset;
+        let len = match &self.input.as_bytes()[cursor..] {
+            [b'\'', b'\\', b'x', h, l, b'\'', ..]
+                if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() =>
+            {
+                6
+            }
+            [b'\'', b'\\', e, b'\'', ..] if b"nrt0\\'\"".contains(e) => 4,
+            [b'\'', c, b'\'', ..]
+                if c.is_ascii() && !c.is_ascii_control() && *c != b'\\' && *c != b'\'' =>
+            {
+                3
+            }
+            [b'\'', ..] if self.input[cursor + 1..].starts_with(is_ident_start) => {
+                self.next_char().unwrap();
+                self.skip_ident();
+                return RawToken::Label(&self.input[cursor..self.offset]);
+            }
+            _ => return RawToken::Unexpected(self.next_char().unwrap()),
+        };
+        for _ in 0..len {
+            self.next_char().unwrap();
+        }
+        RawToken::Lit(&self.input[cursor..self.offset])
+    }
+
+    // length of the fixed-point literal ahead (`1.5fx`, `0.25fx`, `3fx`), if any.
+    fn fixed_lit_len(&self) -> Option<usize> {
+        let bytes = &self.input.as_bytes()[self.offset..];
+        let digits = |bytes: &[u8]| bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+        let mut len = digits(bytes);
+        if bytes.get(len) == Some(&b'.') {
+            match digits(&bytes[len + 1..]) {
+                0 => return None,
+                frac => len += 1 + frac,
+            }
+        }
+        if len == 0 || !bytes[len..].starts_with(b"fx") {
+            return None;
+        }
+        len += 2;
+        match self.input[self.offset + len..].chars().next() {
+            Some(c) if is_ident_continue(c) => None,
+            _ => Some(len),
+        }
+    }
+
+    // keyword with a `.` after its first character (`fx8.8`), if one is ahead.
+    fn dotted_kword_ahead(&self) -> Option<&'a str> {
+        let input = &self.input[self.offset..];
+        let len = self
+            .kwords
+            .iter()
+            .filter(|k| k.len() > 1 && k[1..].contains('.') && input.starts_with(*k))
+            .filter(|k| !input[k.len()..].starts_with(is_ident_continue))
+            .map(|k| k.len())
+            .max()?;
+        Some(&self.input[self.offset..self.offset + len])
     }
 
     fn next_num_lit(&mut self) -> &'a str {
@@ -225,20 +426,49 @@ impl<'a> Tokens<'a> {
     }
 
     fn next_ident_kword_hex_lit(&mut self) -> RawTokenSpan<'a> {
-        match self.peek_char() {
+        match self.peek_utf8() {
             /* ident | kword */
-            Some(b) if b.is_ascii_alphanumeric() || *b == b'_' => {
+            Some(c) if c.is_ascii_alphanumeric() || is_ident_start(c) => {
                 let min = self.cursor();
                 let token = self.next_ident_kword_hex_lit_2();
                 let max = self.cursor();
-                (token, Span { min, max })
+                (
+                    token,
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                )
+            }
+            /* non-ASCII char that can't begin an identifier */
+            Some(c) if !c.is_ascii() => {
+                let min = self.cursor();
+                let byte = *self.peek_char().unwrap();
+                self.next_utf8().unwrap();
+                let max = self.cursor();
+                (
+                    RawToken::Unexpected(byte),
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                )
             }
             /* kword */
             Some(_) => {
                 let min = self.cursor();
                 let keyword = self.next_kword();
                 let max = self.cursor();
-                (keyword, Span { min, max })
+                (
+                    keyword,
+                    Span {
+                        min,
+                        max,
+                        source: self.source,
+                    },
+                )
             }
             None => panic!("EOF"),
         }
@@ -246,14 +476,20 @@ impl<'a> Tokens<'a> {
 
     fn next_ident_kword_hex_lit_2(&mut self) -> RawToken<'a> {
         let cursor = self.offset;
-        loop {
-            match self.peek_char() {
-                Some(b) if b.is_ascii_alphanumeric() || *b == b'_' => {
-                    self.next_char().unwrap();
-                }
-                _ => break,
+        // raw identifiers (r#loop) are never keywords nor literals
+        if self.raw_ident_ahead() {
+            self.next_char().unwrap();
+            self.next_char().unwrap();
+            self.skip_ident();
+            return RawToken::Ident(&self.input[cursor..self.offset]);
+        }
+        if let Some(kword) = self.dotted_kword_ahead() {
+            for _ in 0..kword.len() {
+                self.next_char().unwrap();
             }
+            return RawToken::Keyword(kword);
         }
+        self.skip_ident();
         let token_str = &self.input[cursor..self.offset];
         let token = token_str;
         if self.has_kword(token) {
@@ -264,6 +500,8 @@ impl<'a> Tokens<'a> {
             if token_str.bytes().all(|b| b.is_ascii_digit())
                 || token_str.starts_with("0x")
                     && token_str[2..].bytes().all(|b| b.is_ascii_hexdigit())
+                || token_str == "true"
+                || token_str == "false"
             {
                 RawToken::Lit(token)
             } else {
@@ -287,6 +525,8 @@ impl<'a> Tokens<'a> {
             match self.peek_char() {
                 None => break,
                 Some(b) if b.is_ascii_alphanumeric() | b.is_ascii_whitespace() => break,
+                // keywords are ASCII
+                Some(b) if !b.is_ascii() => break,
                 Some(_) => {
                     self.next_char().unwrap();
 
@@ -306,8 +546,12 @@ impl<'a> Tokens<'a> {
         }
 
         if keyword.is_empty() {
-            let byte = keyword.as_bytes()[self.offset];
-            RawToken::Unexpected(byte)
+            // skip the unexpected byte so lexing can resume after it
+            self.chars = chars;
+            self.offset = offset;
+            self.line = line;
+            self.line_offset = line_offset;
+            RawToken::Unexpected(self.next_char().unwrap())
         } else {
             self.chars = chars;
             self.offset = offset;
@@ -328,6 +572,15 @@ impl<'a> Tokens<'a> {
     }
 }
 
+// identifiers follow UAX #31 (XID_Start, or `_`, followed by XID_Continue).
+fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
 impl<'a> Iterator for Tokens<'a> {
     type Item = RawTokenSpan<'a>;
 
@@ -341,13 +594,13 @@ mod test {
     use crate::lex::raw::{RawToken, Tokens};
     use std::collections::HashSet;
 
-    fn rust_kwords() -> HashSet<String> {
+    fn rust_kwords() -> HashSet<&'static str> {
         [
             "if", "else", "let", "loop", "fn", "->", ">=", "<=", "=>", "~=", "==", "::", "~", "&",
             "|", ";", "{", "}", ",", ".", ":", "=", "(", ")", "[", "]", "<", ">", "+", "-", "/",
         ]
         .iter()
-        .map(|s| s.to_string())
+        .copied()
         .collect()
     }
 
@@ -364,6 +617,60 @@ mod test {
         assert_eq!(None, tokens.next().map(|t| t.0));
     }
 
+    #[test]
+    fn lit_char() {
+        use RawToken::{Label, Lit, Unexpected};
+
+        let input = r"'A' '\n' '\x4f' '\'' 'AB '_a1 '";
+        let mut tokens = Tokens::new(input, HashSet::new());
+
+        assert_eq!(Some(Lit("'A'")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit(r"'\n'")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit(r"'\x4f'")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit(r"'\''")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Label("'AB")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Label("'_a1")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Unexpected(b'\'')), tokens.next().map(|t| t.0));
+    }
+
+    #[test]
+    fn lit_fixed() {
+        use RawToken::{Eof, Ident, Keyword, Lit};
+
+        let kwords = ["fx8.8", ":", "."].iter().copied().collect();
+        let input = "1.5fx 0.25fx 3fx x:fx8.8 1.fx 2fxy";
+        let mut tokens = Tokens::new(input, kwords);
+
+        assert_eq!(Some(Lit("1.5fx")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit("0.25fx")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit("3fx")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("x")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Keyword(":")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Keyword("fx8.8")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit("1")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Keyword(".")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("fx")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit("2")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("fxy")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Eof), tokens.next().map(|t| t.0));
+    }
+
+    #[test]
+    fn lit_str() {
+        use RawToken::{Ident, Lit, Unexpected};
+
+        let input = r#""HELLO" "\"\\\x41" "\q "foo"#;
+        let mut tokens = Tokens::new(input, HashSet::new());
+
+        assert_eq!(Some(Lit("\"HELLO\"")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Lit(r#""\"\\\x41""#)), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Unexpected(b'"')), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Unexpected(b'\\')), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("q")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Unexpected(b'"')), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("foo")), tokens.next().map(|t| t.0));
+    }
+
     #[test]
     fn lit_numeric_hex() {
         use RawToken::{Eof, Lit};
@@ -427,6 +734,41 @@ mod test {
         assert_eq!(None, tokens.next().map(|t| t.0));
     }
 
+    #[test]
+    fn ident_unicode() {
+        use RawToken::{Eof, Ident, Label, Unexpected};
+
+        let input = "ñandú _über2 'ñu λ→x";
+        let mut tokens = Tokens::new(input, rust_kwords());
+
+        assert_eq!(Some(Ident("ñandú")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("_über2")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Label("'ñu")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("λ")), tokens.next().map(|t| t.0));
+        let unexpected = tokens.next().unwrap();
+        assert_eq!(Unexpected(0xe2), unexpected.0);
+        assert_eq!([[0, 23], [0, 26]], [unexpected.1.min, unexpected.1.max]);
+        assert_eq!(Some(Ident("x")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Eof), tokens.next().map(|t| t.0));
+    }
+
+    #[test]
+    fn ident_raw() {
+        use RawToken::{Eof, Ident, Keyword};
+
+        let input = "r#loop loop r#foo r#true r# r";
+        let mut tokens = Tokens::new(input, rust_kwords());
+
+        assert_eq!(Some(Ident("r#loop")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Keyword("loop")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("r#foo")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("r#true")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Ident("r")), tokens.next().map(|t| t.0));
+        assert!(tokens.next().unwrap().0.is_unexpected());
+        assert_eq!(Some(Ident("r")), tokens.next().map(|t| t.0));
+        assert_eq!(Some(Eof), tokens.next().map(|t| t.0));
+    }
+
     #[test]
     fn test() {
         use RawToken::{Eof, Ident, Keyword, Lit};
@@ -1,24 +1,233 @@
 //! Location within programs code.
 
-use std::ops::Deref;
+use std::{fmt, ops::Deref};
 
 pub trait Spanned {
     fn span(&self) -> Span;
 }
 
-impl<T: Spanned> Spanned for Box<T> {
+impl<T: Spanned> Spanned for crate::arena::Node<T> {
     fn span(&self) -> Span {
         self.deref().span()
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct Span {
     /// Position of the left-most char.
     pub min: [usize; 2],
 
     /// Position of the right-most char.
     pub max: [usize; 2],
+
+    /// Source the span belongs to.
+    pub source: SourceId,
+}
+
+/// Identifier of a source in a [`SourceMap`](SourceMap).
+///
+/// The default id (`0`) is the one of the spans of sources parsed on their
+/// own, and of the first source added to a `SourceMap`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SourceId(u32);
+
+impl SourceId {
+    /// Create a source id from its index in a `SourceMap`.
+    pub fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    /// Index of the source in its `SourceMap`.
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Span {
+    /// Line and column of the beginning of the span.
+    ///
+    /// For repeated lookups over the same source, use a
+    /// [`LineIndex`](LineIndex) instead.
+    pub fn location(&self, source: &str) -> LineColumn {
+        line_column(source, self.min)
+    }
+
+    /// Line and column of the end of the span.
+    pub fn end_location(&self, source: &str) -> LineColumn {
+        line_column(source, self.max)
+    }
+}
+
+/// Collection of named sources.
+///
+/// The map owns the sources of a program made of more than one input (the
+/// files of [`import`](crate::ast::Import) statements, REPL snippets, ...),
+/// hands out the [`SourceId`](SourceId) of each one, and resolves spans back
+/// to the file, line, and column they came from.
+///
+/// ```
+/// use parser::{lex::span::SourceMap, ContextBuilder};
+///
+/// let mut map = SourceMap::new();
+/// let main = map.add("main.ggb", "import \"sprites.ggb\"");
+/// let sprites = map.add("sprites.ggb", "static OAM:[u8 160]");
+///
+/// let mut context = ContextBuilder::default().source_map(&map).build();
+/// let ast = parser::parse_with_context(map.source(main), &mut context).unwrap();
+/// let span = parser::lex::span::Spanned::span(&ast.inner[0]);
+/// assert_eq!(main, span.source);
+/// assert_eq!(Some(sprites), map.id("sprites.ggb"));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SourceMap {
+    sources: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    /// Create an empty source map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a named source, returning its id.
+    pub fn add<N: Into<String>, S: Into<String>>(&mut self, name: N, source: S) -> SourceId {
+        self.sources.push((name.into(), source.into()));
+        SourceId(self.sources.len() as u32 - 1)
+    }
+
+    /// Id of the source with the given name.
+    pub fn id(&self, name: &str) -> Option<SourceId> {
+        self.sources
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|i| SourceId(i as u32))
+    }
+
+    /// Name of a source.
+    ///
+    /// Panics if the source is not in the map.
+    pub fn name(&self, id: SourceId) -> &str {
+        &self.sources[id.0 as usize].0
+    }
+
+    /// Source code of a source.
+    ///
+    /// Panics if the source is not in the map.
+    pub fn source(&self, id: SourceId) -> &str {
+        &self.sources[id.0 as usize].1
+    }
+
+    /// Name of the source of a span, along with the line and column of its
+    /// beginning.
+    ///
+    /// Panics if the source is not in the map.
+    pub fn location(&self, span: &Span) -> (&str, LineColumn) {
+        let (name, source) = &self.sources[span.source.0 as usize];
+        (name, span.location(source))
+    }
+
+    /// Iterate over the ids, names and sources of the map.
+    pub fn iter(&self) -> impl Iterator<Item = (SourceId, &str, &str)> {
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(i, (name, source))| (SourceId(i as u32), &name[..], &source[..]))
+    }
+}
+
+/// Human-readable position in the source code.
+///
+/// Both the line and the column start at 1. Unlike the positions in a
+/// [`Span`](Span), which count bytes, the column counts characters.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct LineColumn {
+    /// Line number.
+    pub line: usize,
+
+    /// Column number.
+    pub column: usize,
+}
+
+impl fmt::Display for LineColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Precomputed index of the lines of a source.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    lines: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Index the lines of the given source.
+    pub fn new(source: &'a str) -> Self {
+        let mut lines = vec![0];
+        lines.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self { source, lines }
+    }
+
+    /// The indexed source.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Byte offset of a span position.
+    ///
+    /// Positions past the end of the source are clamped to its length.
+    pub fn offset(&self, [line, col]: [usize; 2]) -> usize {
+        self.lines
+            .get(line)
+            .map(|l| (l + col).min(self.source.len()))
+            .unwrap_or(self.source.len())
+    }
+
+    /// Span position (line and byte column) of a byte offset.
+    ///
+    /// Offsets past the end of the source are clamped to its length.
+    pub fn position(&self, offset: usize) -> [usize; 2] {
+        let offset = offset.min(self.source.len());
+        let line = match self.lines.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        [line, offset - self.lines[line]]
+    }
+
+    /// Line and column of a span position.
+    pub fn line_column(&self, position: [usize; 2]) -> LineColumn {
+        let line = self
+            .lines
+            .get(position[0])
+            .copied()
+            .unwrap_or(self.source.len());
+        let offset = self.offset(position).max(line);
+        LineColumn {
+            line: position[0] + 1,
+            column: chars(&self.source[line..], offset - line) + 1,
+        }
+    }
+}
+
+fn line_column(source: &str, [line, col]: [usize; 2]) -> LineColumn {
+    let text = source.split('\n').nth(line).unwrap_or("");
+    LineColumn {
+        line: line + 1,
+        column: chars(text, col) + 1,
+    }
+}
+
+// number of chars in the first `bytes` bytes of `text`.
+fn chars(text: &str, bytes: usize) -> usize {
+    match text.get(..bytes) {
+        Some(prefix) => prefix.chars().count(),
+        None => bytes,
+    }
 }
 
 pub fn union(l: &Span, r: &Span) -> Span {
@@ -30,23 +239,128 @@ pub fn union(l: &Span, r: &Span) -> Span {
     if r.max[0] > max[0] || r.max[0] == max[0] && r.max[1] > max[1] {
         max = r.max;
     }
-    Span { min, max }
+    Span {
+        min,
+        max,
+        source: l.source,
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::lex::span::{union, Span};
+    use crate::{
+        ast::Statement,
+        lex::span::{union, LineColumn, LineIndex, SourceId, SourceMap, Span, Spanned},
+        ContextBuilder,
+    };
+
+    #[test]
+    fn source_map() {
+        let mut map = SourceMap::new();
+        let main = map.add("main.ggb", "// main\nimport \"gfx/oam.ggb\"");
+        let oam = map.add("gfx/oam.ggb", "\n  static OAM:[u8 160]");
+        assert_eq!(SourceId::default(), main);
+        assert_eq!(Some(oam), map.id("gfx/oam.ggb"));
+        assert_eq!(None, map.id("oam.ggb"));
+        assert_eq!("gfx/oam.ggb", map.name(oam));
+
+        let mut context = ContextBuilder::default().source_map(&map).build();
+        let ast = crate::parse_with_context(map.source(main), &mut context).unwrap();
+        let import = match &ast.inner[0] {
+            Statement::Import(import) => import,
+            _ => panic!(),
+        };
+        let span = import.span();
+        assert_eq!(main, span.source);
+        assert_eq!(
+            ("main.ggb", LineColumn { line: 2, column: 1 }),
+            map.location(&span)
+        );
+        let span = import.inner[0].span();
+        assert_eq!(oam, span.source);
+        assert_eq!(
+            ("gfx/oam.ggb", LineColumn { line: 2, column: 3 }),
+            map.location(&span)
+        );
+    }
+
+    #[test]
+    fn resolver_ids() {
+        let mut context = ContextBuilder::default()
+            .source(SourceId::new(7))
+            .resolver(|_| Some("!!"))
+            .build();
+        let ast =
+            crate::parse_with_context("import \"a.ggb\"\nimport \"b.ggb\"", &mut context).unwrap();
+        let sources: Vec<_> = ast.inner.iter().map(|s| s.span().source.index()).collect();
+        assert_eq!(vec![7, 7], sources);
+        let sources: Vec<_> = ast
+            .inner
+            .iter()
+            .map(|s| match s {
+                Statement::Import(import) => import.inner[0].span().source.index(),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(vec![1, 2], sources);
+    }
+
+    #[test]
+    fn location() {
+        let source = "let a:u8 = 0\n// ñandú\n  let b:u8 = 1";
+        let span = Span {
+            min: [2, 6],
+            max: [2, 7],
+            ..Default::default()
+        };
+        assert_eq!(LineColumn { line: 3, column: 7 }, span.location(source));
+        assert_eq!(LineColumn { line: 3, column: 8 }, span.end_location(source));
+        assert_eq!("3:7", span.location(source).to_string());
+
+        // columns count chars, not bytes
+        let span = Span {
+            min: [1, 5],
+            max: [1, 10],
+            ..Default::default()
+        };
+        assert_eq!(LineColumn { line: 2, column: 5 }, span.location(source));
+        assert_eq!(LineColumn { line: 2, column: 9 }, span.end_location(source));
+    }
+
+    #[test]
+    fn line_index() {
+        let source = "let a:u8 = 0\n// ñandú\n  let b:u8 = 1";
+        let index = LineIndex::new(source);
+        assert_eq!(0, index.offset([0, 0]));
+        assert_eq!(13, index.offset([1, 0]));
+        assert_eq!(source.len(), index.offset([2, 14]));
+        assert_eq!(source.len(), index.offset([7, 0]));
+        assert_eq!([0, 0], index.position(0));
+        assert_eq!([0, 12], index.position(12));
+        assert_eq!([1, 0], index.position(13));
+        assert_eq!([2, 14], index.position(1000));
+        for position in &[[0, 0], [0, 4], [1, 5], [1, 10], [2, 6]] {
+            let span = Span {
+                min: *position,
+                max: *position,
+                ..Default::default()
+            };
+            assert_eq!(span.location(source), index.line_column(*position));
+        }
+    }
 
     #[test]
     fn same() {
         let l = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
         let r = l;
         let gt = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
 
         assert_eq!(gt, union(&l, &r));
@@ -58,14 +372,17 @@ mod test {
         let l = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
         let r = Span {
             min: [43, 0],
             max: [84, 84],
+            ..Default::default()
         };
         let gt = Span {
             min: [0, 0],
             max: [84, 84],
+            ..Default::default()
         };
 
         assert_eq!(gt, union(&l, &r));
@@ -77,14 +394,17 @@ mod test {
         let l = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
         let r = Span {
             min: [12, 0],
             max: [24, 24],
+            ..Default::default()
         };
         let gt = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
 
         assert_eq!(gt, union(&l, &r));
@@ -96,14 +416,17 @@ mod test {
         let l = Span {
             min: [0, 0],
             max: [24, 42],
+            ..Default::default()
         };
         let r = Span {
             min: [24, 24],
             max: [42, 42],
+            ..Default::default()
         };
         let gt = Span {
             min: [0, 0],
             max: [42, 42],
+            ..Default::default()
         };
 
         assert_eq!(gt, union(&l, &r));
@@ -25,16 +25,24 @@
     nonstandard_style
 )]
 
+pub mod arena;
 pub mod ast;
+pub mod check;
+pub mod cst;
+pub mod error;
+pub mod html;
+pub mod incremental;
 pub mod lex;
+pub mod resolve;
+pub mod version;
 
 use ast::{Context, Grammar};
-use lex::span::Span;
-use thiserror::Error;
 
 // re-exports
 pub use ast::{Ast, ContextBuilder};
+pub use error::Error;
 pub use lex::Tokens;
+pub use version::LanguageVersion;
 
 /// Parse input source code.
 pub fn parse(input: &str) -> Result<Ast<'_>, Error<'_>> {
@@ -42,50 +50,143 @@ pub fn parse(input: &str) -> Result<Ast<'_>, Error<'_>> {
     parse_with_context(input, &mut context)
 }
 
-/// Parse input source code with a context.
-pub fn parse_with_context<'a>(
-    input: &'a str,
-    context: &mut Context<'a>,
-) -> Result<Ast<'a>, Error<'a>> {
-    let mut tokens = Tokens::new(input).peekable();
-    Grammar::parse(context, &mut tokens)
+/// Parse input source code, recovering from syntax errors.
+///
+/// Unlike [`parse`](parse), parsing doesn't stop at the first error. The
+/// parser synchronizes at the next statement boundary instead, so all the
+/// syntax errors are reported in one pass, alongside a partial `Ast` where the
+/// offending statements are replaced by
+/// [`Statement::Error`](ast::Statement::Error) nodes.
+pub fn parse_recovering(input: &str) -> (Ast<'_>, Vec<Error<'_>>) {
+    let mut context = ContextBuilder::default().error_tolerant(true).build();
+    let ast = parse_with_context(input, &mut context)
+        .expect("Error-tolerant parsing always produces an Ast");
+    (ast, context.take_errors())
 }
 
-#[derive(Error, Debug)]
-pub enum Error<'a> {
-    #[error("Early EOF")]
-    Eof,
+/// Parse arbitrary bytes as source code, without ever panicking.
+///
+/// Any input is either parsed into an `Ast`, or rejected with an `Error`:
+///
+/// - Input that isn't valid UTF-8 fails with an
+///   [`Error::UnexpectedByte`](Error::UnexpectedByte) at the first invalid
+///   byte.
+/// - Programs nested deeper than the default
+///   [nesting limit](ContextBuilder::nesting_limit) fail with an
+///   [`Error::NestingLimit`](Error::NestingLimit).
+///
+/// The parser runs on a thread of its own, with a stack large enough for the
+/// nesting limit, so deeply nested input can't overflow the stack of the
+/// caller either. A panic within this function is a bug of the parser. The
+/// fuzz targets of the crate (in `parser/fuzz`) look for them.
+pub fn parse_no_panic(input: &[u8]) -> Result<Ast<'_>, Error<'_>> {
+    // stack of the parser thread, per nesting level
+    const STACK_PER_LEVEL: usize = 256 * 1024;
 
-    #[error("Unexpected token: `{0}`")]
-    UnexpectedToken(lex::Token<'a>),
+    let input = match std::str::from_utf8(input) {
+        Ok(input) => input,
+        Err(error) => {
+            let offset = error.valid_up_to();
+            let valid = std::str::from_utf8(&input[..offset]).unwrap_or_default();
+            let min = lex::span::LineIndex::new(valid).position(offset);
+            return Err(Error::UnexpectedByte {
+                byte: input[offset],
+                span: lex::span::Span {
+                    min,
+                    max: [min[0], min[1] + 1],
+                    source: Default::default(),
+                },
+            });
+        }
+    };
+    let stack = ContextBuilder::default().build().nesting_limit() * STACK_PER_LEVEL;
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name("parser".to_string())
+            .stack_size(stack)
+            .spawn_scoped(scope, || parse(input))
+            .expect("Parser thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
 
-    #[error("Invalid path: {0:?}")]
-    InvalidPath(ast::Path<'a>),
+/// Parse input source code, keeping a lossless [`Cst`](cst::Cst) alongside
+/// the `Ast`.
+pub fn parse_lossless(input: &str) -> Result<(Ast<'_>, cst::Cst<'_>), Error<'_>> {
+    let ast = parse(input)?;
+    Ok((ast, cst::Cst::new(input)))
+}
 
-    #[error("Use of reserved keyword: `{key_word}`")]
-    ReservedKeyword {
-        /// The keyword itself.
-        key_word: &'a str,
+/// Parse a single expression, such as `(+ foo 1)`.
+///
+/// The whole input must be consumed by the expression.
+pub fn parse_expression(input: &str) -> Result<ast::Expression<'_>, Error<'_>> {
+    parse_node(input)
+}
 
-        /// Location of the keyword in the programs source.
-        span: Span,
-    },
+/// Parse a single statement, such as `let foo:u8 = 0`.
+///
+/// The whole input must be consumed by the statement.
+pub fn parse_statement(input: &str) -> Result<ast::Statement<'_>, Error<'_>> {
+    parse_node(input)
+}
 
-    #[error("Unexpected byte: {byte:02x}")]
-    UnexpectedByte {
-        /// The unexpected byte.
-        byte: u8,
+// parse a node, followed by the end of the input.
+fn parse_node<'a, G: Grammar<'a>>(input: &'a str) -> Result<G, Error<'a>> {
+    let mut context = ContextBuilder::default().build();
+    context.set_source(input);
+    let mut tokens = Tokens::new(input).peekable();
+    let node = G::parse(&mut context, &mut tokens)?;
+    lex::Eof::parse(&mut context, &mut tokens)?;
+    Ok(node)
+}
 
-        /// Location of the byte in the programs source code.
-        span: Span,
-    },
+/// Parse input source code with a context.
+///
+/// The names of the parsed program are resolved too, and recorded in the
+/// context ([`Context::resolutions`](Context::resolutions)).
+pub fn parse_with_context<'a>(
+    input: &'a str,
+    context: &mut Context<'a>,
+) -> Result<Ast<'a>, Error<'a>> {
+    context.set_source(input);
+    let mut tokens = Tokens::with_source(input, context.source_id())
+        .with_version(context.version())
+        .peekable();
+    let ast = Grammar::parse(context, &mut tokens)?;
+    context.set_resolutions(resolve::resolve(&ast));
+    Ok(ast)
+}
 
-    #[error("Shadowed identifier")]
-    ShadowIdent {
-        /// An already defined and previously validated identifier.
-        ident: lex::Ident<'a>,
+/// Re-parse input source code after an edit, reusing the statements of the
+/// previous `Ast` that the edit didn't touch.
+///
+/// See the [`incremental`](incremental) module.
+pub fn reparse<'a>(
+    previous: Ast<'a>,
+    previous_input: &str,
+    edit: &incremental::Edit<'_>,
+    input: &'a str,
+) -> Result<Ast<'a>, Error<'a>> {
+    let mut context = ContextBuilder::default().build();
+    reparse_with_context(previous, previous_input, edit, input, &mut context)
+}
 
-        /// The new identifier shadowing the one above.
-        shadow: lex::Ident<'a>,
-    },
+/// Re-parse input source code after an edit with a context.
+///
+/// In error-tolerant mode, only the errors of the re-parsed statements are
+/// collected in the context. The reused statements keep their
+/// [`Statement::Error`](ast::Statement::Error) nodes.
+pub fn reparse_with_context<'a>(
+    previous: Ast<'a>,
+    previous_input: &str,
+    edit: &incremental::Edit<'_>,
+    input: &'a str,
+    context: &mut Context<'a>,
+) -> Result<Ast<'a>, Error<'a>> {
+    context.set_source(input);
+    let ast = incremental::reparse(context, previous, previous_input, edit, input)?;
+    context.set_resolutions(resolve::resolve(&ast));
+    Ok(ast)
 }
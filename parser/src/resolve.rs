@@ -0,0 +1,723 @@
+//! Name resolution.
+//!
+//! Maps the uses of names in a program (the paths of expressions and types,
+//! and macro calls) to the declarations they refer to, for features such as
+//! go-to-definition and renaming.
+//!
+//! [`parse_with_context`](crate::parse_with_context) resolves the names of the
+//! programs it parses, and records the result in the
+//! [`Context`](crate::ast::Context) ([`Context::resolutions`]).
+//!
+//! ```
+//! use parser::{resolve::SymbolKind, ContextBuilder};
+//!
+//! let input = "static FOO:u8\nfn f(x:u8) { (= FOO x) }";
+//! let mut context = ContextBuilder::default().build();
+//! parser::parse_with_context(input, &mut context).unwrap();
+//!
+//! let (span, resolution) = context.resolutions().at(Default::default(), [1, 17]).unwrap();
+//! assert_eq!([[1, 16], [1, 19]], [span.min, span.max]);
+//! assert_eq!(SymbolKind::Static, resolution.kind);
+//! assert_eq!([0, 7], resolution.declaration.min);
+//! ```
+//!
+//! Resolution follows the scoping rules of the language: functions, statics,
+//! consts, enums and type aliases can be used before their declaration, and
+//! locals after it. Paths to members (`FOO::bar`) resolve to the declaration
+//! they belong to. Names that don't resolve to any declaration (builtins, or
+//! symbols provided by the environment) aren't recorded.
+//!
+//! [`Context::resolutions`]: crate::ast::Context::resolutions
+use crate::{
+    ast::{Ast, Expression, Field, Fn, Path, Pattern, Statement, Visitor},
+    error::Error,
+    lex::{
+        self,
+        span::{self, SourceId, Span, Spanned},
+        Token, Tokens,
+    },
+};
+use std::collections::{HashMap, HashSet};
+
+/// Kind of a declared symbol.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SymbolKind {
+    /// `fn` declaration.
+    Function,
+
+    /// `static` declaration.
+    Static,
+
+    /// `const` declaration, or generic parameter of a function.
+    Const,
+
+    /// `let` declaration, or `for` loop counter.
+    Local,
+
+    /// Argument of a function.
+    Argument,
+
+    /// `enum` declaration.
+    Enum,
+
+    /// Variant of an `enum`.
+    Variant,
+
+    /// `type` alias declaration.
+    TypeAlias,
+
+    /// `macro` declaration.
+    Macro,
+
+    /// `mod` declaration.
+    Module,
+
+    /// Field of a struct or union.
+    Member,
+}
+
+/// Declaration a name resolves to.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Resolution {
+    /// Span of the declared identifier.
+    pub declaration: Span,
+
+    /// Kind of the declared symbol.
+    pub kind: SymbolKind,
+}
+
+/// Resolved names of a program.
+///
+/// Returned by [`resolve`](resolve).
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Default, Clone)]
+pub struct Resolutions {
+    // span of each use, and its declaration, in source order
+    uses: Vec<(Span, Resolution)>,
+}
+
+impl Resolutions {
+    /// Resolved uses, in source order.
+    pub fn iter(&self) -> impl Iterator<Item = (Span, &Resolution)> {
+        self.uses
+            .iter()
+            .map(|(span, resolution)| (*span, resolution))
+    }
+
+    /// Number of resolved uses.
+    pub fn len(&self) -> usize {
+        self.uses.len()
+    }
+
+    /// Returns `true` if no names were resolved.
+    pub fn is_empty(&self) -> bool {
+        self.uses.is_empty()
+    }
+
+    /// Declaration of the use at the given span.
+    pub fn get(&self, span: Span) -> Option<&Resolution> {
+        self.iter()
+            .find(|(use_, _)| *use_ == span)
+            .map(|(_, resolution)| resolution)
+    }
+
+    /// Use at the given position of a source, and its declaration.
+    pub fn at(&self, source: SourceId, position: [usize; 2]) -> Option<(Span, &Resolution)> {
+        self.iter()
+            .find(|(use_, _)| use_.source == source && use_.min <= position && position < use_.max)
+    }
+
+    /// Spans of the uses of a declaration, in source order.
+    pub fn references(&self, declaration: Span) -> impl Iterator<Item = Span> + '_ {
+        self.iter()
+            .filter(move |(_, resolution)| resolution.declaration == declaration)
+            .map(|(span, _)| span)
+    }
+}
+
+/// Resolve the names of a program.
+pub fn resolve(ast: &Ast<'_>) -> Resolutions {
+    let mut names = Names::new(None);
+    names.statements(&ast.inner);
+    let mut uses: Vec<_> = names
+        .uses
+        .into_iter()
+        .map(|u| (u.span, u.resolution))
+        .collect();
+    uses.sort_by_key(|(span, _)| (span.source, span.min));
+    Resolutions { uses }
+}
+
+/// Spans to edit in order to rename a declaration, in source order: the
+/// declared identifier, and the identifiers of its uses.
+///
+/// The declaration is given by the span of its identifier (as in
+/// [`Resolution::declaration`](Resolution::declaration)). Uses of other
+/// declarations with the same name, such as shadowed locals, aren't edited.
+///
+/// Fails if the new name isn't a valid identifier
+/// ([`Error::InvalidName`](Error::InvalidName)), if there is no declaration at
+/// the span ([`Error::UnknownDeclaration`](Error::UnknownDeclaration)), or if
+/// the renamed program would mean something else
+/// ([`Error::RenameConflict`](Error::RenameConflict)): a use of the
+/// declaration would be shadowed by another one with the new name, a use of
+/// another declaration would be shadowed by the renamed one, or the new name
+/// would be declared twice in the same scope.
+///
+/// ```
+/// use parser::resolve;
+///
+/// let input = "static A:u8\nfn f(x:u8) { let A:u8 = x (= A x) }\n(= A 1)";
+/// let ast = parser::parse(input).unwrap();
+/// let resolutions = resolve::resolve(&ast);
+///
+/// // the local shadowing `A` isn't renamed
+/// let (_, global) = resolutions.at(Default::default(), [2, 3]).unwrap();
+/// let spans = resolve::rename(&ast, global.declaration, "B").unwrap();
+/// let spans: Vec<_> = spans.iter().map(|span| span.min).collect();
+/// assert_eq!(vec![[0, 7], [2, 3]], spans);
+///
+/// // `x` is already used in the scope of the local
+/// let (_, local) = resolutions.at(Default::default(), [1, 29]).unwrap();
+/// assert!(resolve::rename(&ast, local.declaration, "x").is_err());
+/// ```
+pub fn rename<'a>(ast: &Ast<'_>, declaration: Span, name: &str) -> Result<Vec<Span>, Error<'a>> {
+    let mut tokens = Tokens::new(name);
+    let ident = match (tokens.next(), tokens.next()) {
+        (Some(Ok(Token::Ident(ident))), Some(Ok(Token::Eof(_)))) => ident,
+        _ => {
+            return Err(Error::InvalidName {
+                span: declaration,
+                name: name.to_string(),
+            })
+        }
+    };
+
+    let mut before = Names::new(None);
+    before.statements(&ast.inner);
+    if !before.declared.contains(&declaration) {
+        return Err(Error::UnknownDeclaration { span: declaration });
+    }
+    let uses: HashSet<_> = before
+        .uses
+        .iter()
+        .filter(|u| u.resolution.declaration == declaration)
+        .map(|u| u.ident)
+        .collect();
+
+    // resolve the names of the renamed program
+    let mut after = Names::new(Some(Rename {
+        declaration,
+        name: ident.name().to_string(),
+        uses: uses.clone(),
+    }));
+    after.statements(&ast.inner);
+
+    // uses whose declaration would change, and the declarations they would
+    // refer to
+    let resolved: HashMap<_, _> = after.uses.iter().map(|u| (u.span, u.resolution)).collect();
+    let mut conflicts = after.conflicts;
+    for use_ in &before.uses {
+        match resolved.get(&use_.span) {
+            Some(resolution) if resolution.declaration == use_.resolution.declaration => {}
+            Some(resolution) => conflicts.push((use_.span, resolution.declaration)),
+            None => conflicts.push((use_.span, use_.resolution.declaration)),
+        }
+    }
+    let previous: HashSet<_> = before.uses.iter().map(|u| u.span).collect();
+    for use_ in after.uses.iter().filter(|u| !previous.contains(&u.span)) {
+        conflicts.push((use_.span, declaration));
+    }
+    if let Some((span, other)) = conflicts.into_iter().min_by_key(|(span, _)| span.min) {
+        return Err(Error::RenameConflict {
+            span,
+            name: name.to_string(),
+            declaration: other,
+        });
+    }
+
+    let mut spans: Vec<_> = uses.into_iter().chain(Some(declaration)).collect();
+    spans.sort_by_key(|span| (span.source, span.min));
+    Ok(spans)
+}
+
+// paths referenced by a node, with the span of each of their identifiers.
+#[derive(Default)]
+struct Paths(Vec<Vec<(String, Span)>>);
+
+impl<'a> Visitor<'a> for Paths {
+    fn visit_path(&mut self, node: &Path<'a>) {
+        self.0.push(
+            node.iter()
+                .map(|i| (i.name().to_string(), i.span()))
+                .collect(),
+        );
+    }
+}
+
+// use of a declaration.
+struct Use {
+    // span of the path, up to the identifier naming the declaration
+    span: Span,
+    // span of the identifier naming the declaration
+    ident: Span,
+    resolution: Resolution,
+}
+
+// declaration being renamed.
+struct Rename {
+    declaration: Span,
+    name: String,
+    // identifiers of its uses
+    uses: HashSet<Span>,
+}
+
+struct Names {
+    // declarations of the visible names, innermost scope last
+    scopes: Vec<HashMap<String, Resolution>>,
+    // modules being resolved, innermost last
+    modules: Vec<String>,
+    // spans of every declaration
+    declared: HashSet<Span>,
+    // uses in the order they are found, sorted by location once done
+    uses: Vec<Use>,
+    rename: Option<Rename>,
+    // locations where the renamed declaration conflicts with another one, and
+    // the other declaration
+    conflicts: Vec<(Span, Span)>,
+}
+
+impl Names {
+    fn new(rename: Option<Rename>) -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            modules: Vec::new(),
+            declared: HashSet::new(),
+            uses: Vec::new(),
+            rename,
+            conflicts: Vec::new(),
+        }
+    }
+
+    // name of an identifier, as renamed.
+    fn name(&self, ident: Span, name: &str) -> String {
+        match &self.rename {
+            Some(rename) if rename.declaration == ident || rename.uses.contains(&ident) => {
+                rename.name.clone()
+            }
+            _ => name.to_string(),
+        }
+    }
+
+    // declare a name in the innermost scope.
+    fn declare(&mut self, ident: &lex::Ident<'_>, kind: SymbolKind) {
+        let name = self.name(ident.span(), ident.name());
+        self.declare_path(vec![name], ident, kind);
+    }
+
+    // declare a name, relative to the module being resolved, in the innermost
+    // scope.
+    fn declare_path(&mut self, name: Vec<String>, ident: &lex::Ident<'_>, kind: SymbolKind) {
+        let mut path = self.modules.clone();
+        path.extend(name);
+        let path = path.join("::");
+        let resolution = Resolution {
+            declaration: ident.span(),
+            kind,
+        };
+        if let Some(rename) = &self.rename {
+            // names declared twice in a scope (other than locals, which shadow
+            // the previous ones), and consts shadowing consts
+            let scope = self.scopes.last().unwrap();
+            let twice = scope
+                .get(&path)
+                .filter(|r| r.kind != SymbolKind::Local || kind != SymbolKind::Local);
+            let shadowed = self
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.get(&path))
+                .find(|r| r.kind == SymbolKind::Const && kind == SymbolKind::Const);
+            if let Some(other) = twice.or(shadowed) {
+                if other.declaration == rename.declaration {
+                    self.conflicts.push((other.declaration, ident.span()));
+                } else if ident.span() == rename.declaration {
+                    self.conflicts.push((ident.span(), other.declaration));
+                }
+            }
+        }
+        self.declared.insert(ident.span());
+        self.scopes.last_mut().unwrap().insert(path, resolution);
+    }
+
+    // find the declaration of a name, from the innermost module and scope to
+    // the outermost ones.
+    fn find(&self, name: &[String]) -> Option<Resolution> {
+        (0..=self.modules.len()).rev().find_map(|i| {
+            let mut path = self.modules[..i].to_vec();
+            path.extend_from_slice(name);
+            let path = path.join("::");
+            self.scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(&path))
+                .copied()
+        })
+    }
+
+    // resolve the referenced paths. Paths to members (`FOO::bar`) resolve to
+    // the declaration they belong to, and the qualifiers of paths (`Enum` of
+    // `Enum::Variant`) to their own declarations.
+    fn use_paths(&mut self, paths: Paths) {
+        for path in paths.0 {
+            let names: Vec<_> = path
+                .iter()
+                .map(|(name, span)| self.name(*span, name))
+                .collect();
+            let found = (1..=names.len())
+                .rev()
+                .find_map(|len| Some((len, self.find(&names[..len])?)));
+            if let Some((len, resolution)) = found {
+                for qualifier in 1..len {
+                    if let Some(resolution) = self.find(&names[..qualifier]) {
+                        self.use_(&path[..qualifier], resolution);
+                    }
+                }
+                self.use_(&path[..len], resolution);
+            }
+        }
+    }
+
+    // record the use of a declaration, named by the last identifier of a path.
+    fn use_(&mut self, path: &[(String, Span)], resolution: Resolution) {
+        let ident = path[path.len() - 1].1;
+        self.uses.push(Use {
+            span: span::union(&path[0].1, &ident),
+            ident,
+            resolution,
+        });
+    }
+
+    fn expression(&mut self, expression: &Expression<'_>) {
+        let mut paths = Paths::default();
+        paths.visit_expression(expression);
+        self.use_paths(paths);
+    }
+
+    // resolve the names in the type of a field (`[u8 LEN]`).
+    fn field(&mut self, field: &Field<'_>) {
+        let mut paths = Paths::default();
+        paths.visit_field(field);
+        self.use_paths(paths);
+    }
+
+    fn block(&mut self, statements: &[Statement<'_>]) {
+        self.scopes.push(HashMap::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Statement<'_>]) {
+        // declarations that can be used before their definition
+        for statement in statements {
+            match statement {
+                Statement::Fn(fn_) => self.declare(&fn_.ident, SymbolKind::Function),
+                Statement::Static(static_) => {
+                    self.declare(&static_.field.ident, SymbolKind::Static)
+                }
+                Statement::Const(const_) => self.declare(&const_.field.ident, SymbolKind::Const),
+                Statement::TypeAlias(alias) => self.declare(&alias.ident, SymbolKind::TypeAlias),
+                Statement::Enum(enum_) => {
+                    self.declare(&enum_.ident, SymbolKind::Enum);
+                    let enum_name = self.name(enum_.ident.span(), enum_.ident.name());
+                    for variant in &enum_.variants {
+                        let name = self.name(variant.ident.span(), variant.ident.name());
+                        let path = vec![enum_name.clone(), name];
+                        self.declare_path(path, &variant.ident, SymbolKind::Variant);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'_>) {
+        match statement {
+            Statement::If(if_) => {
+                self.expression(&if_.expression);
+                self.block(&if_.inner);
+            }
+            Statement::IfElse(if_else) => {
+                self.expression(&if_else.if_.expression);
+                self.block(&if_else.if_.inner);
+                self.block(&if_else.else_.inner);
+            }
+            // only the taken branch is part of the program
+            Statement::IfConst(if_const) => {
+                self.expression(&if_const.expression);
+                self.statements(if_const.statements());
+            }
+            Statement::Scope(scope) => self.block(&scope.inner),
+            Statement::Defer(defer) => self.block(&defer.inner),
+            Statement::Mod(mod_) => {
+                self.modules.push(mod_.ident.name().to_string());
+                self.statements(&mod_.inner);
+                self.modules.pop();
+            }
+            Statement::Import(import) => {
+                self.modules.push(import.name());
+                self.statements(&import.inner);
+                self.modules.pop();
+            }
+            Statement::Macro(macro_) => self.declare(&macro_.ident, SymbolKind::Macro),
+            Statement::MacroCall(call) => {
+                let name = self.name(call.ident.span(), call.ident.name());
+                if let Some(resolution) = self.find(std::slice::from_ref(&name)) {
+                    self.use_(&[(name, call.ident.span())], resolution);
+                }
+                self.block(&call.inner);
+            }
+            Statement::Const(const_) => {
+                self.field(&const_.field);
+                self.expression(&const_.expression);
+            }
+            Statement::Let(let_) => {
+                self.field(&let_.field);
+                self.expression(&let_.expression);
+                self.declare(&let_.field.ident, SymbolKind::Local);
+            }
+            Statement::LetTuple(let_) => {
+                for field in &let_.fields {
+                    self.field(field);
+                }
+                self.expression(&let_.expression);
+                for field in &let_.fields {
+                    self.declare(&field.ident, SymbolKind::Local);
+                }
+            }
+            Statement::For(for_) => {
+                self.field(&for_.field);
+                self.expression(&for_.range.left);
+                self.expression(&for_.range.right);
+                self.scopes.push(HashMap::new());
+                self.declare(&for_.field.ident, SymbolKind::Local);
+                self.statements(&for_.inner);
+                self.scopes.pop();
+            }
+            Statement::Loop(loop_) => self.block(&loop_.inner),
+            Statement::While(while_) => {
+                self.expression(&while_.expression);
+                self.block(&while_.inner);
+            }
+            Statement::Match(match_) => {
+                self.expression(&match_.expression);
+                for arm in &match_.arms {
+                    match &arm.pattern {
+                        Pattern::Expression(expression) => self.expression(expression),
+                        Pattern::Range(range) => {
+                            self.expression(&range.left);
+                            self.expression(&range.right);
+                        }
+                    }
+                    self.block(&arm.inner);
+                }
+                if let Some(else_) = &match_.else_ {
+                    self.block(&else_.inner);
+                }
+            }
+            Statement::Inline(inline) => self.expression(&inline.inner),
+            Statement::Fn(fn_) => self.fn_(fn_),
+            Statement::Return(return_) => {
+                if let Some(expression) = &return_.expression {
+                    self.expression(expression);
+                }
+            }
+            Statement::Static(_)
+            | Statement::Enum(_)
+            | Statement::TypeAlias(_)
+            | Statement::Memory(_)
+            | Statement::StaticAssert(_)
+            | Statement::Panic(_)
+            | Statement::Continue(_)
+            | Statement::Break(_) => {
+                let mut paths = Paths::default();
+                paths.visit_statement(statement);
+                self.use_paths(paths);
+            }
+            Statement::Error(_) => {}
+        }
+    }
+
+    fn fn_(&mut self, fn_: &Fn<'_>) {
+        self.scopes.push(HashMap::new());
+        for param in fn_.generics.iter().flat_map(|g| &g.params) {
+            self.field(&param.field);
+            self.declare(&param.field.ident, SymbolKind::Const);
+        }
+        for field in fn_.fn_arg.iter().flat_map(|a| &a.inner) {
+            self.field(field);
+            self.declare(&field.ident, SymbolKind::Argument);
+        }
+        if let Some(fn_return) = &fn_.fn_return {
+            let mut paths = Paths::default();
+            paths.visit_type(&fn_return.type_);
+            self.use_paths(paths);
+        }
+        self.statements(&fn_.inner);
+        self.scopes.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SymbolKind;
+    use crate::{error::Error, lex::span::Spanned};
+
+    // (use, declaration, kind) of the resolved names, as `[line, column]`
+    // positions of their first char.
+    fn resolve(input: &str) -> Vec<([usize; 2], [usize; 2], SymbolKind)> {
+        let ast = crate::parse(input).unwrap();
+        super::resolve(&ast)
+            .iter()
+            .map(|(span, resolution)| (span.min, resolution.declaration.min, resolution.kind))
+            .collect()
+    }
+
+    #[test]
+    fn scopes() {
+        let input = "(f A)\n\
+                     static A:u8\n\
+                     fn f(a:u8) { let b:u8 = a { let a:u8 = b (= A a) } }";
+        assert_eq!(
+            vec![
+                ([0, 1], [2, 3], SymbolKind::Function),
+                ([0, 3], [1, 7], SymbolKind::Static),
+                ([2, 24], [2, 5], SymbolKind::Argument),
+                ([2, 39], [2, 17], SymbolKind::Local),
+                ([2, 44], [1, 7], SymbolKind::Static),
+                ([2, 46], [2, 32], SymbolKind::Local),
+            ],
+            resolve(input)
+        );
+        // locals can't be used before their declaration
+        assert!(resolve("(= x 1)\nlet x:u8 = 0").is_empty());
+    }
+
+    #[test]
+    fn paths() {
+        let input = "mod m { const N:u8 = 2 static A:[u8 N] }\n\
+                     enum E { X Y }\n\
+                     type T = E\n\
+                     static B:T = E::Y\n\
+                     (= m::A B)";
+        assert_eq!(
+            vec![
+                ([0, 36], [0, 14], SymbolKind::Const),
+                ([2, 9], [1, 5], SymbolKind::Enum),
+                ([3, 9], [2, 5], SymbolKind::TypeAlias),
+                ([3, 13], [1, 5], SymbolKind::Enum),
+                ([3, 13], [1, 11], SymbolKind::Variant),
+                ([4, 3], [0, 30], SymbolKind::Static),
+                ([4, 8], [3, 7], SymbolKind::Static),
+            ],
+            resolve(input)
+        );
+    }
+
+    #[test]
+    fn references() {
+        let input = "fn sum<const N:u8>(xs:[u8 N]):u8 { return ([0] xs) }\n(sum [1 2])";
+        let ast = crate::parse(input).unwrap();
+        let resolutions = super::resolve(&ast);
+        let decl = resolutions
+            .at(Default::default(), [1, 2])
+            .unwrap()
+            .1
+            .declaration;
+        assert_eq!([0, 3], decl.min);
+        assert_eq!(1, resolutions.references(decl).count());
+        let n = resolutions.at(Default::default(), [0, 26]).unwrap().1;
+        assert_eq!(SymbolKind::Const, n.kind);
+        assert_eq!([0, 13], n.declaration.min);
+        let xs: Vec<_> = resolutions
+            .references(
+                resolutions
+                    .at(Default::default(), [0, 48])
+                    .unwrap()
+                    .1
+                    .declaration,
+            )
+            .map(|span| span.min)
+            .collect();
+        assert_eq!(vec![[0, 47]], xs);
+        assert!(resolutions.at(Default::default(), [0, 50]).is_none());
+    }
+
+    // starts of the spans to edit to rename the declaration at a position.
+    fn renamed(input: &str, position: [usize; 2], name: &str) -> Result<Vec<[usize; 2]>, String> {
+        let ast = crate::parse(input).unwrap();
+        let resolutions = super::resolve(&ast);
+        let declaration = resolutions
+            .iter()
+            .map(|(_, resolution)| resolution.declaration)
+            .find(|span| span.min == position)
+            .unwrap();
+        match super::rename(&ast, declaration, name) {
+            Ok(spans) => Ok(spans.iter().map(|span| span.min).collect()),
+            Err(error) => Err(error.code().to_string()),
+        }
+    }
+
+    #[test]
+    fn rename() {
+        let input = "enum E { X Y }\n\
+                     static A:E = E::X\n\
+                     fn f(a:u8) { let b:u8 = a { let a:u8 = b (= A a) } (= a b) }";
+        assert_eq!(Ok(vec![[0, 9], [1, 16]]), renamed(input, [0, 9], "Z"));
+        assert_eq!(
+            Ok(vec![[0, 5], [1, 9], [1, 13]]),
+            renamed(input, [0, 5], "r#fn")
+        );
+        assert_eq!(Ok(vec![[2, 32], [2, 46]]), renamed(input, [2, 32], "c"));
+        assert_eq!(
+            Ok(vec![[2, 5], [2, 24], [2, 54]]),
+            renamed(input, [2, 5], "c")
+        );
+        // a local can shadow another one
+        assert_eq!(Ok(vec![[2, 32], [2, 46]]), renamed(input, [2, 32], "b"));
+
+        // names declared twice
+        assert_eq!(Err("E0030".to_string()), renamed(input, [0, 9], "Y"));
+        assert_eq!(Err("E0030".to_string()), renamed(input, [0, 5], "A"));
+        // uses shadowed by the new name
+        assert_eq!(Err("E0030".to_string()), renamed(input, [1, 7], "a"));
+        assert_eq!(Err("E0030".to_string()), renamed(input, [2, 17], "a"));
+        // uses shadowing the new name
+        assert_eq!(Err("E0030".to_string()), renamed(input, [2, 32], "A"));
+
+        assert_eq!(Err("E0028".to_string()), renamed(input, [2, 5], "fn"));
+        assert_eq!(Err("E0028".to_string()), renamed(input, [2, 5], "a b"));
+        assert_eq!(Err("E0028".to_string()), renamed(input, [2, 5], ""));
+    }
+
+    #[test]
+    fn rename_consts() {
+        let input = "const N:u8 = 1\nfn f<const M:u8>(xs:[u8 M]) { (= N M) }";
+        assert_eq!(
+            Ok(vec![[1, 11], [1, 24], [1, 35]]),
+            renamed(input, [1, 11], "K")
+        );
+        // consts can't be shadowed
+        assert_eq!(Err("E0030".to_string()), renamed(input, [1, 11], "N"));
+        assert_eq!(Err("E0030".to_string()), renamed(input, [0, 6], "M"));
+
+        let ast = crate::parse(input).unwrap();
+        let span = ast.eof.span();
+        let error = super::rename(&ast, span, "K").unwrap_err();
+        assert!(matches!(error, Error::UnknownDeclaration { .. }));
+    }
+}
@@ -0,0 +1,84 @@
+//! Versions of the language.
+//!
+//! New keywords and syntax are introduced by new versions of the language, so
+//! the grammar can evolve without breaking existing programs. When parsing for
+//! an older version, the keywords of newer versions are plain identifiers, and
+//! newer syntax is rejected with an
+//! [`Error::RequiresVersion`](crate::Error::RequiresVersion).
+//!
+//! The version is set with
+//! [`ContextBuilder::version`](crate::ContextBuilder::version), and defaults to
+//! the latest one.
+//!
+//! ```
+//! use parser::{ContextBuilder, LanguageVersion};
+//!
+//! // `match` was introduced in version 2
+//! let input = "static match:u8";
+//! let mut context = ContextBuilder::default().version(LanguageVersion::V1).build();
+//! assert!(parser::parse_with_context(input, &mut context).is_ok());
+//! assert!(parser::parse(input).is_err());
+//! ```
+use std::fmt;
+
+// keywords introduced in version 2.
+const V2_KEYWORDS: &[&str] = &[
+    "import",
+    "type",
+    "while",
+    "match",
+    "static_assert",
+    "macro",
+    "memory",
+    "defer",
+];
+
+/// Version of the language.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LanguageVersion {
+    /// The original language.
+    V1,
+
+    /// Adds the `import`, `type`, `while`, `match`, `static_assert`, `macro`,
+    /// `memory` and `defer` statements, `if const`, `const fn`, and generic
+    /// functions.
+    V2,
+}
+
+impl LanguageVersion {
+    /// The latest version of the language.
+    pub const LATEST: Self = Self::V2;
+
+    /// Version that introduced a keyword.
+    ///
+    /// Returns `None` for the keywords of every version, and for anything
+    /// that isn't a keyword.
+    pub fn of_keyword(keyword: &str) -> Option<Self> {
+        if V2_KEYWORDS.contains(&keyword) {
+            Some(Self::V2)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the keyword is part of this version of the language.
+    pub fn has_keyword(self, keyword: &str) -> bool {
+        !matches!(Self::of_keyword(keyword), Some(version) if version > self)
+    }
+}
+
+impl Default for LanguageVersion {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+impl fmt::Display for LanguageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1 => write!(f, "1"),
+            Self::V2 => write!(f, "2"),
+        }
+    }
+}
@@ -95,3 +95,65 @@ fn test() {
     assert_eq_token!(RightBracket, tokens);
     assert_eq_token!(Eof, tokens);
 }
+
+#[test]
+fn spanned() {
+    let input = "static $ FOO\n:u8";
+    let tokens: Vec<_> = Tokens::new(input)
+        .spanned()
+        .map(|t| t.map(|(token, span)| (token.to_string(), span.min, span.max)))
+        .collect();
+
+    assert_eq!(6, tokens.len());
+    assert_eq!(
+        ("static".to_string(), [0, 0], [0, 6]),
+        *tokens[0].as_ref().unwrap()
+    );
+    assert!(tokens[1].is_err());
+    assert_eq!(
+        ("FOO".to_string(), [0, 9], [0, 12]),
+        *tokens[2].as_ref().unwrap()
+    );
+    assert_eq!(
+        (":".to_string(), [1, 0], [1, 1]),
+        *tokens[3].as_ref().unwrap()
+    );
+    assert_eq!(
+        ("u8".to_string(), [1, 1], [1, 3]),
+        *tokens[4].as_ref().unwrap()
+    );
+}
+
+#[test]
+fn lit_char() {
+    let input = r"'A' '\n' '\0' '\xFF' '\\' '\''";
+    let values: Vec<_> = Tokens::new(input)
+        .filter_map(|t| match t {
+            Ok(Token::Lit(lit)) => lit.char_value(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec![b'A', b'\n', 0, 0xff, b'\\', b'\''], values);
+
+    match Tokens::new("42").next() {
+        Some(Ok(Token::Lit(lit))) => assert_eq!(None, lit.char_value()),
+        token => panic!("Unexpected token: {:?}", token),
+    }
+}
+
+#[test]
+fn lit_str() {
+    let input = r#""HELLO" "a\nb\x7f\"" """#;
+    let values: Vec<_> = Tokens::new(input)
+        .filter_map(|t| match t {
+            Ok(Token::Lit(lit)) => lit.str_value(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec!["HELLO", "a\nb\x7f\"", ""], values);
+
+    match Tokens::new("'A'").next() {
+        Some(Ok(Token::Lit(lit))) => assert_eq!(None, lit.str_value()),
+        token => panic!("Unexpected token: {:?}", token),
+    }
+}
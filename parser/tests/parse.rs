@@ -2,3 +2,1016 @@
 fn parse() {
     parser::parse(include_str!("programs/parse.ggb")).unwrap();
 }
+
+#[test]
+fn parse_recovering() {
+    let (ast, errors) = parser::parse_recovering(include_str!("programs/errors.ggb"));
+    let lines: Vec<_> = errors
+        .iter()
+        .map(|error| error.span().unwrap().min[0] + 1)
+        .collect();
+    assert_eq!(vec![2, 6, 9, 12], lines);
+    assert_eq!(7, ast.inner.len());
+}
+
+#[test]
+fn parse_recovering_valid() {
+    let (_, errors) = parser::parse_recovering(include_str!("programs/parse.ggb"));
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn parse_lossless() {
+    let input = include_str!("programs/parse.ggb");
+    let (_, cst) = parser::parse_lossless(input).unwrap();
+    assert_eq!(input, cst.to_string());
+}
+
+#[test]
+fn parse_format() {
+    use parser::ast::fmt::{format, FormatOptions};
+
+    for &line_width in &[100, 40] {
+        let options = FormatOptions {
+            line_width,
+            ..Default::default()
+        };
+        let ast = parser::parse(include_str!("programs/parse.ggb")).unwrap();
+        let formatted = format(&ast, options);
+        let reparsed = parser::parse(&formatted).unwrap();
+        assert_eq!(ast.inner.len(), reparsed.inner.len());
+        assert_eq!(formatted, format(&reparsed, options));
+    }
+}
+
+#[test]
+fn parse_expression() {
+    use parser::ast::Expression;
+
+    assert!(matches!(
+        parser::parse_expression("(+ foo 1)"),
+        Ok(Expression::Add(_))
+    ));
+    assert!(matches!(
+        parser::parse_expression(" foo::bar "),
+        Ok(Expression::Path(_))
+    ));
+    assert!(parser::parse_expression("(+ foo 1) 2").is_err());
+    assert!(parser::parse_expression("").is_err());
+}
+
+#[test]
+fn parse_statement() {
+    use parser::ast::Statement;
+
+    assert!(matches!(
+        parser::parse_statement("let foo:u8 = 0"),
+        Ok(Statement::Let(_))
+    ));
+    assert!(matches!(
+        parser::parse_statement("/// Foo.\nfn foo { }"),
+        Ok(Statement::Fn(ref fn_)) if fn_.doc.is_some()
+    ));
+    assert!(parser::parse_statement("let foo:u8 = 0 let bar:u8 = 0").is_err());
+    assert!(parser::parse_statement("}").is_err());
+}
+
+#[test]
+fn parse_logical() {
+    use parser::ast::{Expression, Statement, Type};
+
+    let expression = parser::parse_expression("(&& (|| a true) false)").unwrap();
+    match expression {
+        Expression::LogicalAnd(node) => {
+            assert!(matches!(node.inner.left, Expression::LogicalOr(_)));
+            assert!(
+                matches!(&node.inner.right, Expression::Lit(lit) if lit.bool_value() == Some(false))
+            );
+        }
+        _ => panic!(),
+    }
+
+    // `&&` in types is a pointer to a pointer
+    match parser::parse_statement("static FOO:&&bool").unwrap() {
+        Statement::Static(static_) => match static_.field.type_ {
+            Type::Pointer(ptr) => assert!(
+                matches!(ptr.type_, Type::Pointer(ref ptr) if matches!(ptr.type_, Type::Bool(_)))
+            ),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_enum() {
+    use parser::{ast::Statement, lex::span::Spanned};
+
+    let input = "enum Dir { Up Down = 4 }";
+    match parser::parse_statement(input).unwrap() {
+        Statement::Enum(enum_) => {
+            assert_eq!("Dir", enum_.ident.to_string());
+            assert_eq!(2, enum_.variants.len());
+            assert!(enum_.variants[0].discriminant.is_none());
+            assert!(enum_.variants[1].discriminant.is_some());
+            assert_eq!([0, 14], enum_.variants[1].span().min);
+            assert_eq!([0, 22], enum_.variants[1].span().max);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_type_alias() {
+    use parser::{
+        ast::{Statement, Type},
+        lex::span::Spanned,
+    };
+
+    let input = "type Tile = [u8; 16]";
+    match parser::parse_statement(input).unwrap() {
+        Statement::TypeAlias(alias) => {
+            assert_eq!("Tile", alias.ident.to_string());
+            assert!(matches!(alias.inner, Type::Array(_)));
+            assert_eq!([0, 0], alias.span().min);
+            assert_eq!([0, 20], alias.span().max);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_bitfields() {
+    use parser::{
+        ast::{Statement, Type},
+        lex::span::Spanned,
+    };
+
+    let input = "static FLAGS:u8 .{ sprite_on:1, bg_on:1 mode:2 }";
+    match parser::parse_statement(input).unwrap() {
+        Statement::Static(static_) => {
+            let bits = static_.field.bits.as_ref().unwrap();
+            let idents: Vec<_> = bits.fields.iter().map(|f| f.ident.to_string()).collect();
+            assert_eq!(vec!["sprite_on", "bg_on", "mode"], idents);
+            assert!(bits.fields[0].comma.is_some());
+            assert!(bits.fields[1].comma.is_none());
+            assert!(matches!(static_.field.type_, Type::U8(_)));
+            assert_eq!([0, 48], static_.span().max);
+        }
+        _ => panic!(),
+    }
+
+    // a `{` without the `.` begins a new statement, on any line
+    let ast = parser::parse("static FLAGS:u8\n{ }").unwrap();
+    assert_eq!(2, ast.inner.len());
+    let ast = parser::parse("static FLAGS:u8 { let x:u8 = 1 }").unwrap();
+    assert_eq!(2, ast.inner.len());
+}
+
+#[test]
+fn parse_bitfield_width() {
+    use parser::Error;
+
+    for (input, width, available, min) in &[
+        ("static L:u8 .{ a:5 b:5 }", 5, 3, 21),
+        ("static L:u8 .{ a:0 b:3 }", 0, 8, 17),
+        ("static L:u8 .{ a:9 }", 9, 8, 17),
+        ("const N:u8 = 10\nstatic L:u16 .{ a:N b:(- N 3) }", 7, 6, 22),
+    ] {
+        match parser::parse(input) {
+            Err(error @ Error::BitfieldWidth { .. }) => {
+                assert_eq!(
+                    format!("Invalid bitfield width `{}`", width),
+                    error.to_string()
+                );
+                assert_eq!("E0031", error.code());
+                assert_eq!(*min, error.span().unwrap().min[1]);
+                match error {
+                    Error::BitfieldWidth { available: a, .. } => assert_eq!(*available, a),
+                    _ => unreachable!(),
+                }
+            }
+            other => panic!("{}: {:?}", input, other),
+        }
+    }
+    assert!(parser::parse("static L:u16 .{ a:8 b:8 }").is_ok());
+}
+
+#[test]
+fn parse_anonymous_members() {
+    use parser::ast::{types::Member, Statement, Type};
+
+    let input = "static REGS:struct { ctrl:u8 union { word:u16 struct { lo:u8 hi:u8 } } }";
+    match parser::parse_statement(input).unwrap() {
+        Statement::Static(static_) => match &static_.field.type_ {
+            Type::Struct(struct_) => {
+                assert_eq!(2, struct_.fields.len());
+                assert!(matches!(struct_.fields[0], Member::Field(_)));
+                match &struct_.fields[1] {
+                    Member::Union(union) => {
+                        assert!(matches!(union.fields[0], Member::Field(_)));
+                        assert!(matches!(union.fields[1], Member::Struct(_)));
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_pub() {
+    use parser::{ast::Statement, lex::span::Spanned};
+
+    let ast = parser::parse("/// Doc.\npub static FOO:u8\nstatic BAR:u8\npub fn foo { }").unwrap();
+    match &ast.inner[..] {
+        [Statement::Static(foo), Statement::Static(bar), Statement::Fn(fn_)] => {
+            assert!(foo.pub_.is_some());
+            assert_eq!("Doc.", foo.doc.as_ref().unwrap().text());
+            assert_eq!([1, 0], foo.span().min);
+            assert!(bar.pub_.is_none());
+            assert!(fn_.pub_.is_some());
+            assert_eq!([3, 0], fn_.span().min);
+        }
+        _ => panic!(),
+    }
+
+    assert!(matches!(
+        parser::parse("pub let foo:u8 = 0"),
+        Err(parser::Error::Expected { .. })
+    ));
+}
+
+#[test]
+fn parse_fn_interrupt() {
+    use parser::ast::{Interrupt, Statement};
+
+    let ast = parser::parse("fn@vblank on_vblank { }\nfn@lcd_stat on_stat { }").unwrap();
+    match &ast.inner[..] {
+        [Statement::Fn(vblank), Statement::Fn(stat)] => {
+            let interrupt = vblank.interrupt.as_ref().unwrap();
+            assert_eq!(Interrupt::VBlank, interrupt.interrupt());
+            assert_eq!("on_vblank", vblank.ident.to_string());
+            assert_eq!(
+                Interrupt::LcdStat,
+                stat.interrupt.as_ref().unwrap().interrupt()
+            );
+        }
+        _ => panic!(),
+    }
+
+    assert!(matches!(
+        parser::parse("fn@reset on_reset { }"),
+        Err(parser::Error::Expected { .. })
+    ));
+}
+
+#[test]
+fn parse_fn_generics() {
+    use parser::ast::Statement;
+
+    let ast =
+        parser::parse("fn dot<const N:u8, const M:u8>(a:[u8 N] b:[u8 M]):u8 { return 0 }").unwrap();
+    match &ast.inner[..] {
+        [Statement::Fn(dot)] => {
+            let params = &dot.generics.as_ref().unwrap().params;
+            assert_eq!(2, params.len());
+            assert_eq!("N", params[0].field.ident.to_string());
+            assert_eq!("M", params[1].field.ident.to_string());
+        }
+        _ => panic!(),
+    }
+
+    // generic parameters are only visible from the function
+    assert!(parser::parse("fn f<const N:u8>() { }\nconst N:u8 = 1").is_ok());
+    assert!(matches!(
+        parser::parse("const N:u8 = 1\nfn f<const N:u8>() { }"),
+        Err(parser::Error::ShadowIdent { .. })
+    ));
+    assert!(matches!(
+        parser::parse("fn f<const N:u8>() { if const (== N 1) { } }"),
+        Err(parser::Error::NotConst { .. })
+    ));
+}
+
+#[test]
+fn parse_fn_type() {
+    use parser::ast::{types::Type, Statement};
+
+    let ast = parser::parse("static CB:fn(u8 u8):u8\nstatic TICK:fn\n(TICK)").unwrap();
+    match &ast.inner[..] {
+        [Statement::Static(cb), Statement::Static(tick), Statement::Inline(_)] => {
+            match &cb.field.type_ {
+                Type::Fn(fn_) => {
+                    assert_eq!(2, fn_.args.as_ref().unwrap().inner.len());
+                    assert!(fn_.fn_return.is_some());
+                }
+                _ => panic!(),
+            }
+            assert!(matches!(&tick.field.type_, Type::Fn(fn_) if fn_.args.is_none()));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_const_scope() {
+    use parser::{lex::span::Spanned, ContextBuilder, Error};
+
+    let mut context = ContextBuilder::default().build();
+    let input = "const A:u8 = 1\n\
+                 fn f { const B:u8 = (+ A 1) static X:[u8 B] }\n\
+                 { const B:u8 = 3 }\n\
+                 mod m { const A:u8 = 4 }";
+    parser::parse_with_context(input, &mut context).unwrap();
+    assert_eq!(Some(1), context.const_value("A"));
+    assert_eq!(None, context.const_value("B"));
+    assert_eq!(Some(4), context.const_value("m::A"));
+
+    for input in &[
+        "const A:u8 = 1 const A:u8 = 2",
+        "const A:u8 = 1 { const A:u8 = 2 }",
+    ] {
+        match parser::parse(input) {
+            Err(Error::ShadowIdent { ident, shadow }) => {
+                assert_eq!([0, 6], ident.span().min);
+                assert_ne!(ident.span(), shadow.span());
+            }
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn parse_ident_raw_unicode() {
+    use parser::{ast::Statement, ContextBuilder, Error};
+
+    let mut context = ContextBuilder::default().build();
+    let input = "const r#loop:u8 = 1\n\
+                 const ñandú:u8 = (+ r#loop 1)\n\
+                 static r#fn:[u8 r#ñandú]";
+    let ast = parser::parse_with_context(input, &mut context).unwrap();
+    assert_eq!(Some(1), context.const_value("loop"));
+    assert_eq!(Some(2), context.const_value("ñandú"));
+    match &ast.inner[2] {
+        Statement::Static(static_) => {
+            assert_eq!("r#fn", static_.field.ident.to_string());
+            assert_eq!("fn", static_.field.ident.name());
+            assert!(static_.field.ident.is_raw());
+        }
+        _ => panic!(),
+    }
+
+    assert!(parser::parse("static loop:u8").is_err());
+    match parser::parse("const A:u8 = 1 const r#A:u8 = 2") {
+        Err(Error::ShadowIdent { shadow, .. }) => assert_eq!("r#A", shadow.to_string()),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_separated() {
+    use parser::{
+        ast::{Expression, Statement},
+        Error,
+    };
+
+    let ast = parser::parse("static A:[u8 3] = [1, 2, 3,]\nstatic B:[u8 2] = [1 2]").unwrap();
+    for (statement, (commas, len)) in ast.inner.iter().zip(&[(3, 3), (0, 2)]) {
+        match statement {
+            Statement::Static(static_) => match &static_.init.as_ref().unwrap().expression {
+                Expression::Array(array) => {
+                    assert_eq!(*commas, array.inner.commas.len());
+                    assert_eq!(*len, array.inner.len());
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    for (input, item) in &[
+        ("(f a, b c)", [0, 8]),
+        ("(f a b, c)", [0, 5]),
+        ("static S:struct { a:u8, b:u8 c:u8 }", [0, 29]),
+        ("(= S { a:1 b:2, c:3 })", [0, 11]),
+    ] {
+        match parser::parse(input) {
+            Err(Error::MissingSeparator { item: span, comma }) => {
+                assert_eq!(*item, span.min, "{}", input);
+                assert_eq!(",", comma.to_string());
+            }
+            _ => panic!("{}", input),
+        }
+    }
+}
+
+#[test]
+fn parse_attributes() {
+    use parser::{
+        ast::{types::Type, Statement},
+        Error,
+    };
+
+    let input = "static S:#[repr(packed)] #[align(4)] struct { a:u8, #[align(2)] union { b:u8 } }";
+    let ast = parser::parse(input).unwrap();
+    match &ast.inner[0] {
+        Statement::Static(static_) => match &static_.field.type_ {
+            Type::Struct(struct_) => {
+                assert_eq!(2, struct_.attributes.len());
+                assert!(struct_.attributes[0].is_packed());
+                assert!(struct_.attributes[0].align().is_none());
+                assert!(struct_.attributes[1].align().is_some());
+            }
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+
+    for (input, expected) in &[
+        (
+            "static S:#[cold(4)] struct { }",
+            "`repr` or `align` attribute",
+        ),
+        ("static S:#[repr(C)] struct { }", "`packed`"),
+        ("static S:#[align(4)] u8", "`struct` or `union`"),
+    ] {
+        match parser::parse(input) {
+            Err(Error::Expected { expected: e, .. }) => assert_eq!(expected, &e, "{}", input),
+            _ => panic!("{}", input),
+        }
+    }
+}
+
+#[test]
+fn parse_banks() {
+    use parser::{ast::Statement, Error};
+
+    let input = "static@bank(2) LEVEL_DATA:[u8 4000]\n\
+                 #[bank(3)] fn draw { }\n\
+                 #[bank(3)] pub fn update { }";
+    let ast = parser::parse(input).unwrap();
+    match &ast.inner[0] {
+        Statement::Static(static_) => {
+            assert!(static_.bank.is_some());
+            assert!(static_.offset.is_none());
+        }
+        _ => panic!(),
+    }
+    for statement in &ast.inner[1..] {
+        match statement {
+            Statement::Fn(fn_) => assert!(fn_.attributes[0].bank().is_some()),
+            _ => panic!(),
+        }
+    }
+
+    for (input, expected) in &[
+        ("#[align(2)] fn f { }", "`bank` or `inline` attribute"),
+        (
+            "static S:#[bank(1)] struct { }",
+            "`repr` or `align` attribute",
+        ),
+    ] {
+        match parser::parse(input) {
+            Err(Error::Expected { expected: e, .. }) => assert_eq!(expected, &e, "{}", input),
+            _ => panic!("{}", input),
+        }
+    }
+}
+
+#[test]
+fn parse_inline() {
+    use parser::{ast::Statement, Error};
+
+    let input = "#[inline] fn a { }\n\
+                 #[inline(never)] fn b { }\n\
+                 #[bank(1)] #[inline] fn c { }\n\
+                 fn d { }";
+    let ast = parser::parse(input).unwrap();
+    let inline: Vec<_> = ast
+        .inner
+        .iter()
+        .map(|statement| match statement {
+            Statement::Fn(fn_) => fn_.attributes.iter().find_map(|a| a.inline()),
+            _ => panic!(),
+        })
+        .collect();
+    assert_eq!(vec![Some(true), Some(false), Some(true), None], inline);
+
+    for (input, expected) in &[
+        ("#[inline(always)] fn f { }", "`never`"),
+        ("#[inline(4)] fn f { }", "`never`"),
+        (
+            "static S:#[inline] struct { }",
+            "`repr` or `align` attribute",
+        ),
+    ] {
+        match parser::parse(input) {
+            Err(Error::Expected { expected: e, .. }) => assert_eq!(expected, &e, "{}", input),
+            _ => panic!("{}", input),
+        }
+    }
+}
+
+#[test]
+fn parse_memory() {
+    use parser::{
+        ast::{RegionSpace, Statement},
+        Error,
+    };
+
+    let input = "memory {\n\
+                     const ROM0: 0x0000..0x4000,\n\
+                     static WRAM: 0xc000..0xe000,\n\
+                     HRAM: 0xff80..=0xfffe,\n\
+                 }";
+    let ast = parser::parse(input).unwrap();
+    match &ast.inner[0] {
+        Statement::Memory(memory) => {
+            assert_eq!(3, memory.regions.len());
+            assert!(matches!(
+                memory.regions[0].space,
+                Some(RegionSpace::Const(_))
+            ));
+            assert!(matches!(
+                memory.regions[1].space,
+                Some(RegionSpace::Static(_))
+            ));
+            assert!(memory.regions[2].space.is_none());
+        }
+        _ => panic!(),
+    }
+
+    let input = "memory { HRAM: 0xff80..0xffff HRAM: 0xc000..0xe000 }";
+    assert!(matches!(
+        parser::parse(input),
+        Err(Error::ShadowIdent { .. })
+    ));
+}
+
+#[test]
+fn parse_static_assert() {
+    use parser::{ast::Statement, Error};
+
+    let input = "const N:u8 = 4\n\
+                 static_assert((== N 4), \"N is 4\")\n\
+                 fn f { static_assert((<= (sizeof u16) N), \"fits\") }";
+    let ast = parser::parse(input).unwrap();
+    match &ast.inner[1] {
+        Statement::StaticAssert(assert) => assert_eq!("N is 4", assert.message()),
+        _ => panic!(),
+    }
+
+    let input = "const N:u8 = 4\nstatic_assert((< N 4), \"N is less than 4\")";
+    match parser::parse(input) {
+        Err(error @ Error::StaticAssert { .. }) => {
+            assert_eq!(
+                "Static assertion failed: N is less than 4",
+                error.to_string()
+            );
+            assert_eq!("E0012", error.code());
+            assert_eq!([1, 14], error.span().unwrap().min);
+        }
+        _ => panic!(),
+    }
+    assert!(matches!(
+        parser::parse("static_assert(1, 2)"),
+        Err(Error::Expected { .. })
+    ));
+}
+
+#[test]
+fn parse_out_of_range() {
+    use parser::Error;
+
+    for (input, value, type_) in &[
+        ("let x:u8 = 300", "300", "u8"),
+        ("const X:i8 = 128", "128", "i8"),
+        ("const X:i8 = -129", "-129", "i8"),
+        ("static X:u16 = -1", "-1", "u16"),
+        ("static X:i16 = 70000", "70000", "i16"),
+        ("static X:[u8 3] = [1 0x100 2]", "0x100", "u8"),
+        ("static X:fx8.8 = 256fx", "256fx", "fx8.8"),
+        ("let x:u8 = (+ 255 1)", "256", "u8"),
+        ("const X:i8 = (- 0 129)", "-129", "i8"),
+    ] {
+        match parser::parse(input) {
+            Err(error @ Error::OutOfRange { .. }) => {
+                let message = format!("Literal out of range for `{}`: {}", type_, value);
+                assert_eq!(message, error.to_string());
+                assert_eq!("E0015", error.code());
+                assert!(error.span().is_some());
+            }
+            _ => panic!("{}", input),
+        }
+    }
+
+    let error = parser::parse("let x:u8 = 300").unwrap_err();
+    assert_eq!([0, 11], error.span().unwrap().min);
+    assert_eq!(
+        Some("the range of `u8` is `0..=255`".to_string()),
+        error.note()
+    );
+    for input in &[
+        "let x:u8 = 255",
+        "let x:i8 = -128",
+        "let x:u8 = 'A'",
+        "let x:u16 = 0xffff",
+        "let x:u8 = (+ 250 5)",
+        "let x:i8 = (- 0 128)",
+        "let x:fx8.8 = 255.99fx",
+    ] {
+        assert!(parser::parse(input).is_ok(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_if_const() {
+    use parser::{ast::Statement, ContextBuilder, Error};
+
+    let input = "if const (& CGB DEBUG) { const BANKS:u8 = 2 } else { const BANKS:u8 = 1 }\n\
+                 const DOUBLE:u8 = (* BANKS 2)";
+    for (debug, banks) in &[(0, 1), (1, 2)] {
+        let mut context = ContextBuilder::default()
+            .define("CGB", 1)
+            .define("DEBUG", *debug)
+            .build();
+        let ast = parser::parse_with_context(input, &mut context).unwrap();
+        match &ast.inner[0] {
+            Statement::IfConst(if_const) => {
+                assert_eq!(*debug, if_const.value);
+                assert_eq!(1, if_const.statements().len());
+            }
+            _ => panic!(),
+        }
+        assert_eq!(Some(*banks), context.const_value("BANKS"));
+        assert_eq!(Some(banks * 2), context.const_value("DOUBLE"));
+        // defined names are only visible to the conditions
+        assert_eq!(None, context.const_value("CGB"));
+    }
+
+    match parser::parse("if const CGB { }") {
+        Err(error @ Error::NotConst { .. }) => {
+            assert_eq!("E0013", error.code());
+            assert_eq!([0, 9], error.span().unwrap().min);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_match() {
+    use parser::{
+        ast::{Pattern, Statement},
+        lex::span::Spanned,
+    };
+
+    let input = "match x { 0 { } 1..4 { } else { } }";
+    match parser::parse_statement(input).unwrap() {
+        Statement::Match(match_) => {
+            assert_eq!(2, match_.arms.len());
+            assert!(matches!(match_.arms[0].pattern, Pattern::Expression(_)));
+            assert!(matches!(match_.arms[1].pattern, Pattern::Range(_)));
+            assert!(match_.else_.is_some());
+            assert_eq!([0, 16], match_.arms[1].span().min);
+            assert_eq!([0, 24], match_.arms[1].span().max);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_label() {
+    use parser::{ast::Statement, lex::span::Spanned};
+
+    let input = "'outer: loop { break 'outer }";
+    match parser::parse_statement(input).unwrap() {
+        Statement::Loop(loop_) => {
+            assert_eq!("'outer", loop_.label.as_ref().unwrap().label.to_string());
+            assert_eq!([0, 0], loop_.span().min);
+            match &loop_.inner[0] {
+                Statement::Break(break_) => {
+                    assert_eq!("'outer", break_.label.as_ref().unwrap().to_string());
+                    assert_eq!([0, 27], break_.span().max);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_infix() {
+    use parser::{
+        ast::{Expression, Statement},
+        lex::span::Spanned,
+        ContextBuilder,
+    };
+
+    let input = "a = -b + 2 * c << 1\n*p = (a + b) * (foo a) == 0 && d\n(foo)";
+    let mut context = ContextBuilder::default().infix(true).build();
+    let ast = parser::parse_with_context(input, &mut context).unwrap();
+    assert_eq!(3, ast.inner.len());
+
+    // a = (((-b) + (2 * c)) << 1)
+    match &ast.inner[0] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Assign(assign) => match &assign.inner.right {
+                Expression::LeftShift(shift) => match &shift.inner.left {
+                    Expression::Add(add) => {
+                        assert!(matches!(add.inner.left, Expression::Minus(_)));
+                        assert!(matches!(add.inner.right, Expression::Mul(_)));
+                    }
+                    _ => panic!(),
+                },
+                _ => panic!(),
+            },
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+    // *p = ((((a + b) * (foo a)) == 0) && d)
+    match &ast.inner[1] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Assign(assign) => {
+                assert!(matches!(assign.inner.left, Expression::Deref(_)));
+                match &assign.inner.right {
+                    Expression::LogicalAnd(and) => match &and.inner.left {
+                        Expression::Eq(eq) => match &eq.inner.left {
+                            Expression::Mul(mul) => {
+                                assert!(matches!(mul.inner.left, Expression::Add(_)));
+                                assert!(matches!(mul.inner.right, Expression::Call(_)));
+                                assert_eq!([1, 5], mul.span().min);
+                                assert_eq!([1, 22], mul.span().max);
+                                assert_eq!([1, 5], mul.inner.left.span().min);
+                                assert_eq!([1, 12], mul.inner.left.span().max);
+                            }
+                            _ => panic!(),
+                        },
+                        _ => panic!(),
+                    },
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+    assert!(matches!(&ast.inner[2], Statement::Inline(_)));
+
+    // prefix expressions are still accepted
+    let mut context = ContextBuilder::default().infix(true).build();
+    assert!(parser::parse_with_context("(= a (+ b 1)) b = a - 1", &mut context).is_ok());
+}
+
+#[test]
+fn parse_cast() {
+    use parser::{
+        ast::{types::Type, Expression, Statement},
+        lex::span::Spanned,
+        ContextBuilder,
+    };
+
+    let ast = parser::parse("(as a &u8)").unwrap();
+    match &ast.inner[0] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Cast(cast) => {
+                assert!(matches!(cast.inner.inner, Expression::Path(_)));
+                assert!(matches!(cast.inner.type_, Type::Pointer(_)));
+            }
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+
+    // a = (((-b) as u16) + (c * (d as u16)))
+    let mut context = ContextBuilder::default().infix(true).build();
+    let ast = parser::parse_with_context("a = -b as u16 + c * d as u16", &mut context).unwrap();
+    match &ast.inner[0] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Assign(assign) => match &assign.inner.right {
+                Expression::Add(add) => {
+                    match &add.inner.left {
+                        Expression::Cast(cast) => {
+                            assert!(matches!(cast.inner.inner, Expression::Minus(_)));
+                            assert_eq!([0, 4], add.inner.left.span().min);
+                            assert_eq!([0, 13], add.inner.left.span().max);
+                        }
+                        _ => panic!(),
+                    }
+                    match &add.inner.right {
+                        Expression::Mul(mul) => {
+                            assert!(matches!(mul.inner.right, Expression::Cast(_)))
+                        }
+                        _ => panic!(),
+                    }
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_conditional() {
+    use parser::{
+        ast::{Expression, Statement},
+        lex::span::Spanned,
+        ContextBuilder,
+    };
+
+    let ast = parser::parse("(= a (if b 1 (+ c 1)))").unwrap();
+    match &ast.inner[0] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Assign(assign) => match &assign.inner.right {
+                Expression::Conditional(cond) => {
+                    assert!(cond.inner.if_.is_some());
+                    assert!(matches!(cond.inner.else_, Expression::Add(_)));
+                    assert_eq!([0, 5], assign.inner.right.span().min);
+                    assert_eq!([0, 21], assign.inner.right.span().max);
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+
+    // a = ((b || c) ? 1 : (d ? 2 : 3))
+    let mut context = ContextBuilder::default().infix(true).build();
+    let ast = parser::parse_with_context("a = b || c ? 1 : d ? 2 : 3", &mut context).unwrap();
+    match &ast.inner[0] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Assign(assign) => match &assign.inner.right {
+                Expression::Conditional(cond) => {
+                    assert!(cond.inner.if_.is_none());
+                    assert!(matches!(cond.inner.condition, Expression::LogicalOr(_)));
+                    assert!(matches!(cond.inner.else_, Expression::Conditional(_)));
+                    assert_eq!([0, 4], assign.inner.right.span().min);
+                    assert_eq!([0, 26], assign.inner.right.span().max);
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_increment() {
+    use parser::{
+        ast::{Expression, Statement},
+        lex::span::Spanned,
+        ContextBuilder,
+    };
+
+    let ast = parser::parse("(++ a) (-- ([1] b))").unwrap();
+    assert!(
+        matches!(&ast.inner[0], Statement::Inline(i) if matches!(i.inner, Expression::Increment(_)))
+    );
+    assert!(
+        matches!(&ast.inner[1], Statement::Inline(i) if matches!(i.inner, Expression::Decrement(_)))
+    );
+
+    // postfix in infix form, binding tighter than unary operators
+    let mut context = ContextBuilder::default().infix(true).build();
+    let ast = parser::parse_with_context("a++\n*p--\n(b++)", &mut context).unwrap();
+    assert_eq!(3, ast.inner.len());
+    match &ast.inner[1] {
+        Statement::Inline(inline) => match &inline.inner {
+            Expression::Deref(deref) => {
+                assert!(matches!(deref.inner, Expression::Decrement(_)));
+                assert_eq!([1, 0], inline.inner.span().min);
+                assert_eq!([1, 4], inline.inner.span().max);
+            }
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+    match &ast.inner[2] {
+        Statement::Inline(inline) => assert_eq!([2, 5], inline.inner.span().max),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_nesting_limit() {
+    use parser::{ContextBuilder, Error};
+
+    for (input, infix) in &[
+        ("{ { { { { { { { { { } } } } } } } } } }", false),
+        (
+            "(+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 1)))))))))",
+            false,
+        ),
+        ("static X:[[[[[[[[[[u8 1] 1] 1] 1] 1] 1] 1] 1] 1] 1]", false),
+        ("a = - - - - - - - - - - b", true),
+    ] {
+        let mut context = ContextBuilder::default()
+            .infix(*infix)
+            .nesting_limit(8)
+            .build();
+        match parser::parse_with_context(input, &mut context) {
+            Err(error @ Error::NestingLimit { limit: 8, .. }) => {
+                assert_eq!("E0025", error.code());
+                assert!(error.span().is_some());
+            }
+            _ => panic!("{}", input),
+        }
+        let mut context = ContextBuilder::default().infix(*infix).build();
+        assert!(
+            parser::parse_with_context(input, &mut context).is_ok(),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn parse_no_panic() {
+    use parser::Error;
+
+    assert!(parser::parse_no_panic(include_bytes!("programs/parse.ggb")).is_ok());
+    match parser::parse_no_panic(b"static X:u8\nlet \xff") {
+        Err(Error::UnexpectedByte { byte: 0xff, span }) => assert_eq!([1, 4], span.min),
+        _ => panic!(),
+    }
+    let deep = "{ ".repeat(100_000);
+    assert!(matches!(
+        parser::parse_no_panic(deep.as_bytes()),
+        Err(Error::NestingLimit { .. })
+    ));
+    // used to panic at char boundaries of the lexer
+    for input in &["(f ,é)", "0xé", "'é", "\"é", "static é:u8"] {
+        let _ = parser::parse_no_panic(input.as_bytes());
+    }
+}
+
+#[test]
+fn parse_language_version() {
+    use parser::{ContextBuilder, Error, LanguageVersion};
+
+    let v1 = || {
+        ContextBuilder::default()
+            .version(LanguageVersion::V1)
+            .build()
+    };
+
+    // keywords of newer versions are identifiers
+    let input = "static defer:u8\nstatic match:[u8 2]\n(= defer ([0] match))";
+    assert!(parser::parse_with_context(input, &mut v1()).is_ok());
+    assert!(parser::parse(input).is_err());
+
+    for (input, message, min) in &[
+        (
+            "fn f { defer { } }",
+            "`defer` requires language version 2",
+            [0, 7],
+        ),
+        (
+            "while (x) { }",
+            "`while` requires language version 2",
+            [0, 0],
+        ),
+        (
+            "if const A { }",
+            "`if const` requires language version 2",
+            [0, 0],
+        ),
+        (
+            "const fn f() { }",
+            "`const fn` requires language version 2",
+            [0, 0],
+        ),
+        (
+            "fn f<const N:u8>() { }",
+            "Generic parameter list requires language version 2",
+            [0, 4],
+        ),
+    ] {
+        match parser::parse_with_context(input, &mut v1()) {
+            Err(error @ Error::RequiresVersion { .. }) => {
+                assert_eq!(*message, error.to_string());
+                assert_eq!("E0026", error.code());
+                assert_eq!(*min, error.span().unwrap().min);
+            }
+            _ => panic!("{}", input),
+        }
+    }
+
+    // misspelled keywords are only those of the version
+    assert!(!matches!(
+        parser::parse_with_context("deffer { }", &mut v1()),
+        Err(Error::UnknownKeyword { .. })
+    ));
+    assert_eq!(
+        LanguageVersion::LATEST,
+        ContextBuilder::default().build().version()
+    );
+}
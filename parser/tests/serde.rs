@@ -0,0 +1,48 @@
+#![cfg(feature = "serde")]
+
+#[test]
+fn ast_json() {
+    let ast = parser::parse("static FOO:u8").unwrap();
+    let json = serde_json::to_value(&ast).unwrap();
+    let gt = serde_json::json!({
+        "inner": [{
+            "Static": {
+                "doc": null,
+                "pub_": null,
+                "static_": { "text": "static", "span": { "min": [0, 0], "max": [0, 6], "source": 0 } },
+                "offset": null,
+                "bank": null,
+                "field": {
+                    "ident": { "text": "FOO", "span": { "min": [0, 7], "max": [0, 10], "source": 0 } },
+                    "colon": { "text": ":", "span": { "min": [0, 10], "max": [0, 11], "source": 0 } },
+                    "type_": {
+                        "U8": { "text": "u8", "span": { "min": [0, 11], "max": [0, 13], "source": 0 } }
+                    },
+                    "bits": null
+                },
+                "init": null
+            }
+        }],
+        "eof": { "text": "", "span": { "min": [0, 13], "max": [0, 13], "source": 0 } }
+    });
+    assert_eq!(gt, json);
+}
+
+#[test]
+fn cst_json() {
+    let cst = parser::cst::Cst::new("// foo\n!!");
+    let json = serde_json::to_value(&cst).unwrap();
+    assert_eq!(
+        serde_json::json!([{ "Comment": "// foo" }, { "Whitespace": "\n" }]),
+        json["tokens"][0]["leading"]
+    );
+}
+
+#[test]
+fn ast_round_trip() {
+    let input = "static FOO:[u8 2] = [1 2]\nfn f(x:u8):u8 { return (+ x 1) }\nloop { (= ([0]FOO) (f 2)) break }";
+    let ast = parser::parse(input).unwrap();
+    let json = serde_json::to_string(&ast).unwrap();
+    let de: parser::Ast<'_> = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", ast), format!("{:?}", de));
+}
@@ -5,7 +5,7 @@ use parser::{
 
 macro_rules! span {
     ($tokens:expr, $min:expr, $max:expr) => {
-        let Span { min, max } = $tokens.next().unwrap().unwrap().span();
+        let Span { min, max, .. } = $tokens.next().unwrap().unwrap().span();
         assert_eq!($min, min);
         assert_eq!($max, max);
     };
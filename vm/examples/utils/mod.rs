@@ -1,10 +1,10 @@
-use ggbc::{byteorder::NativeEndian, ir::Ir, parser::Ast};
+use ir::{byteorder::NativeEndian, parser::Ast, Ir};
 use std::ops::Range;
 use vm::{memory::Memory, Machine, Opts};
 
 pub fn run(program: &str, range: Option<Range<usize>>) {
     print_input(program);
-    let ast = ggbc::parser::parse(program).unwrap();
+    let ast = ir::parser::parse(program).unwrap();
     #[cfg(nope)]
     print_ast(&ast);
     let ir = Ir::new(&ast);
@@ -17,12 +17,11 @@
     nonstandard_style
 )]
 
-use ggbc::{
+use ir::{
     byteorder::ByteOrder,
-    ir::{
-        opcodes::{Destination, Location, Pointer, Source, Statement, StopStatus},
-        Ir,
-    },
+    opcodes::{Bank, Destination, Location, Pointer, Source, Statement, StopStatus},
+    parser::lex::span::Span,
+    Ir, Overflow,
 };
 use memory::Memory;
 use registers::Registers;
@@ -61,9 +60,12 @@ pub struct Opts {
 pub struct Machine<'a, B: ByteOrder> {
     running: bool,
     error: bool,
+    interrupts: bool,
     ir: &'a Ir<B>,
     routine: Stack<usize>,
     program_counter: Stack<usize>,
+    // routine and index of the last executed statement
+    executed: Option<(usize, usize)>,
     memory: Memory,
     reg8: Stack<Registers<u8>>,
     reg16: Stack<Registers<u16>>,
@@ -73,13 +75,21 @@ pub struct Machine<'a, B: ByteOrder> {
 impl<'a, B: ByteOrder> Machine<'a, B> {
     /// Create a new VM to run the IR statements.
     pub fn new(ir: &'a Ir<B>, opts: Opts) -> Self {
+        let mut memory = Memory::new(&opts);
+        memory.static_[..ir.static_.len()].copy_from_slice(&ir.static_);
+        for (bank, size) in &ir.banks {
+            let data = vec![0; *size as usize].into_boxed_slice();
+            memory.banks.insert(*bank, data);
+        }
         Self {
             running: true,
             error: false,
+            interrupts: false,
             ir,
             routine: Stack::new(),
             program_counter: vec![0],
-            memory: Memory::new(&opts),
+            executed: None,
+            memory,
             reg8: vec![Registers::with_capacity(opts.registers)],
             reg16: vec![Registers::with_capacity(opts.registers)],
             _phantom: std::marker::PhantomData,
@@ -91,6 +101,16 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         *self.program_counter.last().unwrap()
     }
 
+    /// Span of the source code the last executed statement was compiled from.
+    ///
+    /// Once the program stops with an error, the span of the statement that
+    /// caused it. `None` if no statement has been executed yet, or the program
+    /// has no debug info ([`Routine::spans`](ir::Routine::spans)).
+    pub fn span(&self) -> Option<Span> {
+        let (routine, index) = self.executed?;
+        self.ir.routines[routine].span(index)
+    }
+
     /// Return memory.
     pub fn memory(&self) -> &Memory {
         &self.memory
@@ -101,6 +121,23 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         (self.reg8.last().unwrap(), self.reg16.last().unwrap())
     }
 
+    /// Returns true until the program stops.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Returns true if the program stopped with an error (it panicked, or an
+    /// arithmetic operation overflowed with [`Overflow::Trapping`] semantics).
+    pub fn is_error(&self) -> bool {
+        self.error
+    }
+
+    /// Returns true once the program has enabled the handling of interrupts
+    /// (`builtin::enable_interrupts`).
+    pub fn interrupts_enabled(&self) -> bool {
+        self.interrupts
+    }
+
     /// Run virtual machine to completion.
     /// Returns the memory state at the end of the program execution.
     pub fn run(mut self) -> Memory {
@@ -113,15 +150,18 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
     /// Fetch, decode, and execute next instruction.
     pub fn step(&mut self) {
         if self.running {
-            let routine = self
+            let index = self
                 .routine
                 .last()
-                .map(|i| &self.ir.routines[*i])
-                .unwrap_or(&self.ir.routines[self.ir.handlers.main]);
+                .copied()
+                .unwrap_or(self.ir.handlers.main);
+            let routine = &self.ir.routines[index];
 
+            self.executed = Some((index, self.program_counter()));
             let statement = &routine.statements[self.program_counter()].clone();
             self.execute(&statement);
-            *self.program_counter.last_mut().unwrap() += 1;
+            let program_counter = self.program_counter.last_mut().unwrap();
+            *program_counter = program_counter.wrapping_add(1);
         }
     }
 
@@ -144,6 +184,24 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                 source,
                 destination,
             } => self.ld16(source, destination),
+            Statement::LdAddr {
+                source,
+                destination,
+            } => self.ld_addr(source, destination),
+
+            // width conversions
+            Statement::Ext {
+                source,
+                destination,
+            } => self.ext(source, destination),
+            Statement::SignExt {
+                source,
+                destination,
+            } => self.sign_ext(source, destination),
+            Statement::Trunc {
+                source,
+                destination,
+            } => self.trunc(source, destination),
 
             // arithmetic unary operators
             Statement::Inc {
@@ -214,31 +272,87 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                 right,
                 destination,
             } => self.right_shift(left, right, destination),
+            Statement::RightShiftS {
+                left,
+                right,
+                destination,
+            } => self.right_shift_signed(left, right, destination),
+            Statement::DivS {
+                left,
+                right,
+                destination,
+            } => self.div_signed(left, right, destination),
+            Statement::RemS {
+                left,
+                right,
+                destination,
+            } => self.rem_signed(left, right, destination),
             Statement::MulW {
                 left,
                 right,
                 destination,
-            } => todo!(),
+            } => self.mul16(left, right, destination),
             Statement::DivW {
                 left,
                 right,
                 destination,
-            } => todo!(),
+            } => self.div16(left, right, destination),
             Statement::RemW {
                 left,
                 right,
                 destination,
-            } => todo!(),
+            } => self.rem16(left, right, destination),
             Statement::LeftShiftW {
                 left,
                 right,
                 destination,
-            } => todo!(),
+            } => self.left_shift16(left, right, destination),
             Statement::RightShiftW {
                 left,
                 right,
                 destination,
-            } => todo!(),
+            } => self.right_shift16(left, right, destination),
+            Statement::RightShiftSW {
+                left,
+                right,
+                destination,
+            } => self.right_shift16_signed(left, right, destination),
+            Statement::DivSW {
+                left,
+                right,
+                destination,
+            } => self.div16_signed(left, right, destination),
+            Statement::RemSW {
+                left,
+                right,
+                destination,
+            } => self.rem16_signed(left, right, destination),
+
+            // arithmetic with carry
+            Statement::AddC {
+                left,
+                right,
+                carry,
+                destination,
+            } => self.add_with_carry(left, right, carry, destination),
+            Statement::SubC {
+                left,
+                right,
+                carry,
+                destination,
+            } => self.sub_with_borrow(left, right, carry, destination),
+            Statement::AddCarry {
+                left,
+                right,
+                carry,
+                destination,
+            } => self.add_carry(left, right, carry, destination),
+            Statement::SubBorrow {
+                left,
+                right,
+                carry,
+                destination,
+            } => self.sub_borrow(left, right, carry, destination),
 
             // comparator
             Statement::Eq {
@@ -271,6 +385,76 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                 right,
                 destination,
             } => self.less_eq(left, right, destination),
+            Statement::GreaterS {
+                left,
+                right,
+                destination,
+            } => self.greater_signed(left, right, destination),
+            Statement::GreaterEqS {
+                left,
+                right,
+                destination,
+            } => self.greater_eq_signed(left, right, destination),
+            Statement::LessS {
+                left,
+                right,
+                destination,
+            } => self.less_signed(left, right, destination),
+            Statement::LessEqS {
+                left,
+                right,
+                destination,
+            } => self.less_eq_signed(left, right, destination),
+            Statement::EqW {
+                left,
+                right,
+                destination,
+            } => self.eq16(left, right, destination),
+            Statement::NotEqW {
+                left,
+                right,
+                destination,
+            } => self.not_eq16(left, right, destination),
+            Statement::GreaterW {
+                left,
+                right,
+                destination,
+            } => self.greater16(left, right, destination),
+            Statement::GreaterEqW {
+                left,
+                right,
+                destination,
+            } => self.greater_eq16(left, right, destination),
+            Statement::LessW {
+                left,
+                right,
+                destination,
+            } => self.less16(left, right, destination),
+            Statement::LessEqW {
+                left,
+                right,
+                destination,
+            } => self.less_eq16(left, right, destination),
+            Statement::GreaterSW {
+                left,
+                right,
+                destination,
+            } => self.greater16_signed(left, right, destination),
+            Statement::GreaterEqSW {
+                left,
+                right,
+                destination,
+            } => self.greater_eq16_signed(left, right, destination),
+            Statement::LessSW {
+                left,
+                right,
+                destination,
+            } => self.less16_signed(left, right, destination),
+            Statement::LessEqSW {
+                left,
+                right,
+                destination,
+            } => self.less_eq16_signed(left, right, destination),
 
             // 16bit alu
             Statement::AddW {
@@ -306,12 +490,69 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
 
             // routine instructions
             Statement::Call { routine, range } => self.call(*routine, range),
+            Statement::CallIndirect { routine, range } => {
+                // function pointers are indices in the routine table
+                let routine = self.read_u16(routine) as usize;
+                self.call(routine, range)
+            }
             Statement::Ret => self.ret(),
+            // all the banks are always mapped
+            Statement::PushBank { .. } | Statement::PopBank => {}
 
-            _ => unimplemented!("{:?}", statement),
+            // builtins
+            Statement::Memcpy {
+                source,
+                destination,
+                len,
+            } => self.memcpy(source, destination, len),
+            Statement::Memset {
+                value,
+                destination,
+                len,
+            } => self.memset(value, destination, len),
+            Statement::SwapNibbles {
+                source,
+                destination,
+            } => {
+                let data = self.read(source).rotate_left(4);
+                self.ld(&Source::Literal(data), destination)
+            }
+            Statement::BcdAdjust {
+                source,
+                destination,
+            } => {
+                let data = self.read(source) % 100;
+                self.ld(&Source::Literal((data / 10) << 4 | data % 10), destination)
+            }
+            // no interrupts are ever requested, so execution resumes right away
+            Statement::Halt => {}
+            Statement::EnableInterrupts => self.interrupts = true,
         }
     }
 
+    fn memcpy(&mut self, source: &Source<u16>, destination: &Source<u16>, len: &Source<u16>) {
+        let source = self.read_u16(source) as usize;
+        let destination = self.read_u16(destination) as usize;
+        let len = self.read_u16(len) as usize;
+        self.memory
+            .static_
+            .copy_within(source..source + len, destination);
+    }
+
+    fn memset(&mut self, value: &Source<u8>, destination: &Source<u16>, len: &Source<u16>) {
+        let value = self.read(value);
+        let destination = self.read_u16(destination) as usize;
+        let len = self.read_u16(len) as usize;
+        self.memory.static_[destination..destination + len].fill(value);
+    }
+
+    fn bank_mut(&mut self, bank: Bank) -> &mut [u8] {
+        self.memory
+            .banks
+            .get_mut(&bank)
+            .expect("Unallocated RAM bank")
+    }
+
     fn call(&mut self, routine: usize, range: &RangeFrom<u16>) {
         // push registers
         let reg8 = self.reg8.last().unwrap().clone();
@@ -323,12 +564,9 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         self.program_counter.push(0);
         self.routine.push(routine);
 
-        // initialize new stack frame
-        let current_stack = self.memory.stack.clone();
+        // the new stack frame begins at the arguments, which the caller has
+        // already stored in its own frame.
         self.memory.stack.push(range.start as usize);
-        for i in range.clone() {
-            self.memory.stack[(i - range.start) as usize] = current_stack[i as usize];
-        }
     }
 
     fn ret(&mut self) {
@@ -358,6 +596,25 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                 pc_signed += *rel as isize;
                 *self.program_counter.last_mut().unwrap() = pc_signed as _;
             }
+            // out of bounds indices fall through
+            Location::Table { index, targets } => {
+                if let Some(rel) = targets.get(self.read(index) as usize) {
+                    self.jmp(&Location::Relative(*rel));
+                }
+            }
+            Location::Absolute(index) => {
+                // the program counter moves past the jump
+                *self.program_counter.last_mut().unwrap() = index.wrapping_sub(1);
+            }
+            // the routine takes the place of the current one (registers and
+            // stack frame included), and starts as if it was called
+            Location::Routine(routine) => {
+                match self.routine.last_mut() {
+                    Some(current) => *current = *routine,
+                    None => self.routine.push(*routine),
+                }
+                *self.program_counter.last_mut().unwrap() = 0;
+            }
         }
     }
 
@@ -379,34 +636,160 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         self.ld(&Source::Literal(left ^ right), destination);
     }
 
+    // stop the program with an error.
+    fn trap(&mut self) {
+        self.running = false;
+        self.error = true;
+    }
+
+    // result of an arithmetic operation, given its checked, wrapping, and
+    // saturating results, following the overflow semantics of the program.
+    fn overflow<T>(&mut self, checked: Option<T>, wrapping: T, saturating: T) -> T {
+        match (checked, self.ir.overflow) {
+            (Some(result), _) => result,
+            (None, Overflow::Wrapping) => wrapping,
+            (None, Overflow::Saturating) => saturating,
+            (None, Overflow::Trapping) => {
+                self.trap();
+                wrapping
+            }
+        }
+    }
+
     fn mul(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left * right), destination);
+        let data = self.overflow(
+            left.checked_mul(right),
+            left.wrapping_mul(right),
+            left.saturating_mul(right),
+        );
+        self.ld(&Source::Literal(data), destination);
     }
 
     fn div(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left / right), destination);
+        // division by zero always traps
+        match left.checked_div(right) {
+            Some(data) => self.ld(&Source::Literal(data), destination),
+            None => self.trap(),
+        }
     }
 
     fn rem(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left % right), destination);
+        match left.checked_rem(right) {
+            Some(data) => self.ld(&Source::Literal(data), destination),
+            None => self.trap(),
+        }
     }
 
+    // shifting all the bits out leaves a 0
     fn left_shift(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left << right), destination);
+        let data = left.checked_shl(right.into()).unwrap_or(0);
+        self.ld(&Source::Literal(data), destination);
     }
 
     fn right_shift(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left >> right), destination);
+        let data = left.checked_shr(right.into()).unwrap_or(0);
+        self.ld(&Source::Literal(data), destination);
+    }
+
+    // the sign bit is shifted in, so shifting all the bits out leaves a 0 or
+    // a -1
+    fn right_shift_signed(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = self.read(left) as i8;
+        let right = self.read(right).min(7);
+        self.ld(&Source::Literal((left >> right) as u8), destination);
+    }
+
+    // the quotient of the lowest value by -1 wraps around
+    fn div_signed(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        match right {
+            0 => self.trap(),
+            _ => self.ld(
+                &Source::Literal(left.wrapping_div(right) as u8),
+                destination,
+            ),
+        }
+    }
+
+    fn rem_signed(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        match right {
+            0 => self.trap(),
+            _ => self.ld(
+                &Source::Literal(left.wrapping_rem(right) as u8),
+                destination,
+            ),
+        }
+    }
+
+    // only the lowest bit of the carry is added
+    fn add_with_carry(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        carry: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = self.read(left);
+        let right = self.read(right);
+        let carry = self.read(carry) & 1;
+        let data = left.wrapping_add(right).wrapping_add(carry);
+        self.ld(&Source::Literal(data), destination);
+    }
+
+    fn sub_with_borrow(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        carry: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = self.read(left);
+        let right = self.read(right);
+        let carry = self.read(carry) & 1;
+        let data = left.wrapping_sub(right).wrapping_sub(carry);
+        self.ld(&Source::Literal(data), destination);
+    }
+
+    fn add_carry(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        carry: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let sum = u16::from(self.read(left)) + u16::from(self.read(right));
+        let sum = sum + u16::from(self.read(carry) & 1);
+        self.ld(&Source::Literal((sum > 0xff) as u8), destination);
+    }
+
+    fn sub_borrow(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        carry: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = u16::from(self.read(left));
+        let right = u16::from(self.read(right)) + u16::from(self.read(carry) & 1);
+        self.ld(&Source::Literal((left < right) as u8), destination);
     }
 
     fn eq(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
@@ -463,17 +846,135 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         );
     }
 
+    fn greater_signed(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        self.ld(&Source::Literal((left > right) as u8), destination);
+    }
+
+    fn greater_eq_signed(
+        &mut self,
+        left: &Source<u8>,
+        right: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        self.ld(&Source::Literal((left >= right) as u8), destination);
+    }
+
+    fn less_signed(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        self.ld(&Source::Literal((left < right) as u8), destination);
+    }
+
+    fn less_eq_signed(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read(left) as i8;
+        let right = self.read(right) as i8;
+        self.ld(&Source::Literal((left <= right) as u8), destination);
+    }
+
+    fn eq16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left == right) as u8), destination);
+    }
+
+    fn not_eq16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left != right) as u8), destination);
+    }
+
+    fn greater16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left > right) as u8), destination);
+    }
+
+    fn greater_eq16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left >= right) as u8), destination);
+    }
+
+    fn less16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left < right) as u8), destination);
+    }
+
+    fn less_eq16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        self.ld(&Source::Literal((left <= right) as u8), destination);
+    }
+
+    fn greater16_signed(
+        &mut self,
+        left: &Source<u16>,
+        right: &Source<u16>,
+        destination: &Destination,
+    ) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        self.ld(&Source::Literal((left > right) as u8), destination);
+    }
+
+    fn greater_eq16_signed(
+        &mut self,
+        left: &Source<u16>,
+        right: &Source<u16>,
+        destination: &Destination,
+    ) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        self.ld(&Source::Literal((left >= right) as u8), destination);
+    }
+
+    fn less16_signed(
+        &mut self,
+        left: &Source<u16>,
+        right: &Source<u16>,
+        destination: &Destination,
+    ) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        self.ld(&Source::Literal((left < right) as u8), destination);
+    }
+
+    fn less_eq16_signed(
+        &mut self,
+        left: &Source<u16>,
+        right: &Source<u16>,
+        destination: &Destination,
+    ) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        self.ld(&Source::Literal((left <= right) as u8), destination);
+    }
+
     fn add(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        //println!("{:?} = {} + {}", destination, left, right);
-        self.ld(&Source::Literal(left.wrapping_add(right)), destination);
+        let data = self.overflow(
+            left.checked_add(right),
+            left.wrapping_add(right),
+            left.saturating_add(right),
+        );
+        self.ld(&Source::Literal(data), destination);
     }
 
     fn sub(&mut self, left: &Source<u8>, right: &Source<u8>, destination: &Destination) {
         let left = self.read(left);
         let right = self.read(right);
-        self.ld(&Source::Literal(left.wrapping_sub(right)), destination);
+        let data = self.overflow(
+            left.checked_sub(right),
+            left.wrapping_sub(right),
+            left.saturating_sub(right),
+        );
+        self.ld(&Source::Literal(data), destination);
     }
 
     fn and16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
@@ -488,6 +989,31 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
         self.ld16(&Source::Literal(left | right), destination);
     }
 
+    fn left_shift16(&mut self, left: &Source<u16>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read(right);
+        let data = left.checked_shl(right.into()).unwrap_or(0);
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn right_shift16(&mut self, left: &Source<u16>, right: &Source<u8>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read(right);
+        let data = left.checked_shr(right.into()).unwrap_or(0);
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn right_shift16_signed(
+        &mut self,
+        left: &Source<u16>,
+        right: &Source<u8>,
+        destination: &Destination,
+    ) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read(right).min(15);
+        self.ld16(&Source::Literal((left >> right) as u16), destination);
+    }
+
     fn xor16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
         let left = self.read_u16(left);
         let right = self.read_u16(right);
@@ -497,37 +1023,152 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
     fn add16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
         let left = self.read_u16(left);
         let right = self.read_u16(right);
-        self.ld16(&Source::Literal(left.wrapping_add(right)), destination);
+        let data = self.overflow(
+            left.checked_add(right),
+            left.wrapping_add(right),
+            left.saturating_add(right),
+        );
+        self.ld16(&Source::Literal(data), destination);
     }
 
     fn sub16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
         let left = self.read_u16(left);
         let right = self.read_u16(right);
-        self.ld16(&Source::Literal(left.wrapping_sub(right)), destination);
+        let data = self.overflow(
+            left.checked_sub(right),
+            left.wrapping_sub(right),
+            left.saturating_sub(right),
+        );
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn mul16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        let data = self.overflow(
+            left.checked_mul(right),
+            left.wrapping_mul(right),
+            left.saturating_mul(right),
+        );
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn div16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        match left.checked_div(right) {
+            Some(data) => self.ld16(&Source::Literal(data), destination),
+            None => self.trap(),
+        }
+    }
+
+    fn rem16(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left);
+        let right = self.read_u16(right);
+        match left.checked_rem(right) {
+            Some(data) => self.ld16(&Source::Literal(data), destination),
+            None => self.trap(),
+        }
+    }
+
+    fn div16_signed(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        match right {
+            0 => self.trap(),
+            _ => self.ld16(
+                &Source::Literal(left.wrapping_div(right) as u16),
+                destination,
+            ),
+        }
+    }
+
+    fn rem16_signed(&mut self, left: &Source<u16>, right: &Source<u16>, destination: &Destination) {
+        let left = self.read_u16(left) as i16;
+        let right = self.read_u16(right) as i16;
+        match right {
+            0 => self.trap(),
+            _ => self.ld16(
+                &Source::Literal(left.wrapping_rem(right) as u16),
+                destination,
+            ),
+        }
+    }
+
+    fn ext(&mut self, source: &Source<u8>, destination: &Destination) {
+        let data = u16::from(self.read(source));
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn sign_ext(&mut self, source: &Source<u8>, destination: &Destination) {
+        let data = self.read(source) as i8 as u16;
+        self.ld16(&Source::Literal(data), destination);
+    }
+
+    fn trunc(&mut self, source: &Source<u16>, destination: &Destination) {
+        let data = self.read_u16(source) as u8;
+        self.ld(&Source::Literal(data), destination);
+    }
+
+    // pointers can only address static memory. Taking the address of anything
+    // else stops the program with an error.
+    fn ld_addr(&mut self, source: &Source<u16>, destination: &Destination) {
+        let addr = match source {
+            Source::Pointer { base, offset } => {
+                let offset = offset.as_ref().map(|o| self.read(o)).unwrap_or(0) as u16;
+                match base {
+                    Pointer::Absolute(addr) | Pointer::Static(addr) => *addr + offset,
+                    _ => return self.trap(),
+                }
+            }
+            Source::Indirect(ptr) => self.read_u16(ptr),
+            Source::Register(_) | Source::Literal(_) => return self.trap(),
+        };
+        self.ld16(&Source::Literal(addr), destination);
     }
 
     fn inc(&mut self, source: &Source<u8>, destination: &Destination) {
-        let data = self.read(source).wrapping_add(1);
+        let source = self.read(source);
+        let data = self.overflow(
+            source.checked_add(1),
+            source.wrapping_add(1),
+            source.saturating_add(1),
+        );
         self.ld(&Source::Literal(data), destination);
     }
 
     fn dec(&mut self, source: &Source<u8>, destination: &Destination) {
-        let data = self.read(source).wrapping_sub(1);
+        let source = self.read(source);
+        let data = self.overflow(
+            source.checked_sub(1),
+            source.wrapping_sub(1),
+            source.saturating_sub(1),
+        );
         self.ld(&Source::Literal(data), destination);
     }
 
     fn inc16(&mut self, source: &Source<u16>, destination: &Destination) {
-        let data = self.read_u16(source).wrapping_add(1);
+        let source = self.read_u16(source);
+        let data = self.overflow(
+            source.checked_add(1),
+            source.wrapping_add(1),
+            source.saturating_add(1),
+        );
         self.ld16(&Source::Literal(data), destination);
     }
 
     fn dec16(&mut self, source: &Source<u16>, destination: &Destination) {
-        let data = self.read_u16(source).wrapping_sub(1);
+        let source = self.read_u16(source);
+        let data = self.overflow(
+            source.checked_sub(1),
+            source.wrapping_sub(1),
+            source.saturating_sub(1),
+        );
         self.ld16(&Source::Literal(data), destination);
     }
 
     fn ld(&mut self, source: &Source<u8>, destination: &Destination) {
-        use Pointer::{Absolute, Const, Return, Stack, Static};
+        use Pointer::{Absolute, Banked, Const, Return, Stack, Static};
         let data = self.read(source);
         match destination {
             Destination::Pointer { base, offset } => {
@@ -536,18 +1177,23 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                     Absolute(addr) => self.memory.static_[(*addr + offset) as usize] = data,
                     Static(addr) => self.memory.static_[(*addr + offset) as usize] = data,
                     Return(addr) => self.memory.return_[(*addr + offset) as usize] = data,
+                    Banked(bank, addr) => self.bank_mut(*bank)[(*addr + offset) as usize] = data,
                     // TODO don't panic, rather stop the VM and log the error
                     Const(_) => panic!("Attempted to write to ROM memory!"),
                     Stack(addr) => self.memory.stack[(*addr + offset) as usize] = data,
                 }
             }
+            Destination::Indirect(ptr) => {
+                let addr = self.read_u16(ptr);
+                self.memory.static_[addr as usize] = data
+            }
             Destination::Register(reg) => self.reg8.last_mut().unwrap().set(*reg, data),
         }
     }
 
     // FIXME code repetition with Self::ld (use traits instead)
     fn ld16(&mut self, source: &Source<u16>, destination: &Destination) {
-        use Pointer::{Absolute, Const, Return, Stack, Static};
+        use Pointer::{Absolute, Banked, Const, Return, Stack, Static};
         // load data from source
         let data = self.read_u16(source);
         // store byte on the destination
@@ -564,6 +1210,9 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                     Return(addr) => {
                         B::write_u16(&mut self.memory.return_[(*addr + offset) as usize..], data)
                     }
+                    Banked(bank, addr) => {
+                        B::write_u16(&mut self.bank_mut(*bank)[(*addr + offset) as usize..], data)
+                    }
                     // TODO don't panic, rather stop the VM and log the error
                     Const(_) => panic!("Attempted to write to ROM memory!"),
                     Stack(addr) => {
@@ -571,12 +1220,16 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                     }
                 }
             }
+            Destination::Indirect(ptr) => {
+                let addr = self.read_u16(ptr);
+                B::write_u16(&mut self.memory.static_[addr as usize..], data)
+            }
             Destination::Register(reg) => self.reg16.last_mut().unwrap().set(*reg, data),
         }
     }
 
     fn read(&self, source: &Source<u8>) -> u8 {
-        use Pointer::{Absolute, Const, Return, Stack, Static};
+        use Pointer::{Absolute, Banked, Const, Return, Stack, Static};
         match source {
             Source::Pointer { base, offset } => {
                 let offset = offset.as_ref().map(|o| self.read(o)).unwrap_or(0) as u16;
@@ -584,17 +1237,19 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                     Absolute(addr) => self.memory.static_[(*addr + offset) as usize],
                     Static(addr) => self.memory.static_[(*addr + offset) as usize],
                     Return(addr) => self.memory.return_[(*addr + offset) as usize],
+                    Banked(bank, addr) => self.memory.banks[bank][(*addr + offset) as usize],
                     Const(addr) => self.ir.const_[(*addr + offset) as usize],
                     Stack(addr) => self.memory.stack[(*addr + offset) as usize],
                 }
             }
+            Source::Indirect(ptr) => self.memory.static_[self.read_u16(ptr) as usize],
             Source::Register(reg) => self.reg8.last().unwrap().get(*reg),
             Source::Literal(val) => *val,
         }
     }
 
     fn read_u16(&self, source: &Source<u16>) -> u16 {
-        use Pointer::{Absolute, Const, Return, Stack, Static};
+        use Pointer::{Absolute, Banked, Const, Return, Stack, Static};
         match source {
             Source::Pointer { base: ptr, offset } => {
                 let offset = offset.as_ref().map(|o| self.read(o)).unwrap_or(0) as u16;
@@ -604,10 +1259,16 @@ impl<'a, B: ByteOrder> Machine<'a, B> {
                     }
                     Static(addr) => B::read_u16(&self.memory.static_[(*addr + offset) as usize..]),
                     Return(addr) => B::read_u16(&self.memory.return_[(*addr + offset) as usize..]),
+                    Banked(bank, addr) => {
+                        B::read_u16(&self.memory.banks[bank][(*addr + offset) as usize..])
+                    }
                     Const(addr) => B::read_u16(&self.ir.const_[(*addr + offset) as usize..]),
                     Stack(addr) => B::read_u16(&self.memory.stack[(*addr + offset) as usize..]),
                 }
             }
+            Source::Indirect(ptr) => {
+                B::read_u16(&self.memory.static_[self.read_u16(ptr) as usize..])
+            }
             Source::Register(reg) => self.reg16.last().unwrap().get(*reg),
             Source::Literal(val) => *val,
         }
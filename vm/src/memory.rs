@@ -1,5 +1,9 @@
 use crate::{Opts, Stack};
-use std::ops::{Deref, DerefMut};
+use ir::opcodes::Bank;
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+};
 
 /// Memory space of static memory.
 pub type StaticMemory = Box<[u8]>;
@@ -17,6 +21,11 @@ pub struct Memory {
 
     /// Return memory space data.
     pub return_: ReturnMemory,
+
+    /// Static memory of the switchable RAM banks.
+    ///
+    /// All banks are always accessible, so bank switching has no effect.
+    pub banks: BTreeMap<Bank, StaticMemory>,
 }
 
 impl Memory {
@@ -25,6 +34,7 @@ impl Memory {
             stack: StackMemory::with_capacity(opts.stack_size),
             static_: vec![0; opts.static_size].into_boxed_slice(),
             return_: vec![0; opts.return_size].into_boxed_slice(),
+            banks: BTreeMap::new(),
         }
     }
 }
@@ -0,0 +1,10 @@
+mod utils;
+
+#[test]
+fn builtin() {
+    let memory = utils::run(include_str!("programs/builtin.ggb"));
+    assert_eq!(
+        &[1, 2, 3, 4, 1, 2, 3, 4, 0xaa, 0xaa, 0xaa, 0xaa, 0x21, 0x42],
+        &memory.static_[..14]
+    )
+}
@@ -0,0 +1,30 @@
+use ir::{byteorder::NativeEndian, parse_text, Ir};
+use vm::{Machine, Opts};
+
+// 24bit add and subtract of the numbers at 0 and 3 (least significant byte
+// first), into 6 and 9.
+const PROGRAM: &str = "
+static_alloc 12
+static ff ff 01 01 00 00
+routine 0 - stack 0 args 0 return 0
+    add_c [static 0], [static 3], 0 -> [static 6]
+    add_carry [static 0], [static 3], 0 -> r0
+    add_c [static 1], [static 4], r0 -> [static 7]
+    add_carry [static 1], [static 4], r0 -> r0
+    add_c [static 2], [static 5], r0 -> [static 8]
+    sub_c [static 6], [static 3], 0 -> [static 9]
+    sub_borrow [static 6], [static 3], 0 -> r0
+    sub_c [static 7], [static 4], r0 -> [static 10]
+    sub_borrow [static 7], [static 4], r0 -> r0
+    sub_c [static 8], [static 5], r0 -> [static 11]
+    stop success
+end";
+
+#[test]
+fn carry() {
+    let ir: Ir<NativeEndian> = parse_text(PROGRAM).unwrap();
+    let memory = Machine::new(&ir, Opts::default()).run();
+    // 0x01ffff + 0x000001, and back
+    assert_eq!([0x00, 0x00, 0x02], memory.static_[6..9]);
+    assert_eq!([0xff, 0xff, 0x01], memory.static_[9..12]);
+}
@@ -0,0 +1,8 @@
+mod utils;
+
+#[test]
+fn defer() {
+    let memory = utils::run(include_str!("programs/defer.ggb"));
+    assert_eq!(&[3, 2, 1, 4, 10, 11, 12, 6, 5, 7], &memory.static_[..10]);
+    assert_eq!(10, memory.static_[12]);
+}
@@ -1,8 +1,35 @@
+use ir::{
+    byteorder::NativeEndian,
+    opcodes::{Pointer, Source, Statement},
+    Ir,
+};
+use vm::{Machine, Opts};
+
 mod utils;
 
 #[test]
-#[ignore]
 fn deref() {
     let memory = utils::run(include_str!("programs/deref.ggb"));
     assert_eq!(&[1, 2, 3], &memory.static_[..3])
 }
+
+#[test]
+fn stack_address() {
+    // the compiler rejects the address of a local, so patch it into the IR
+    let ast = ir::parser::parse("static A:u8\nstatic P:&u8\n(= P @A)").unwrap();
+    let mut ir: Ir<NativeEndian> = Ir::new(&ast);
+    let main = ir.handlers.main;
+    for statement in ir.routines[main].statements.iter_mut() {
+        if let Statement::LdAddr { source, .. } = statement {
+            *source = Source::Pointer {
+                base: Pointer::Stack(0),
+                offset: None,
+            };
+        }
+    }
+    let mut machine = Machine::new(&ir, Opts::default());
+    while machine.is_running() {
+        machine.step();
+    }
+    assert!(machine.is_error());
+}
@@ -0,0 +1,19 @@
+mod utils;
+
+#[test]
+fn fixed() {
+    let memory = utils::run(include_str!("programs/fixed.ggb"));
+    let word =
+        |offset: usize| u16::from_ne_bytes([memory.static_[offset], memory.static_[offset + 1]]);
+    // 10.5 + 4 * 1.25
+    assert_eq!(0x0f80, word(0));
+    // 1.25 * 3
+    assert_eq!(0x03c0, word(2));
+    // 1.25 * 2.5
+    assert_eq!(0x0320, word(4));
+    // 15.5 / 2
+    assert_eq!(0x07c0, word(6));
+    assert_eq!(15, memory.static_[8]);
+    // 0.25 + 0.25
+    assert_eq!(0x0080, word(11));
+}
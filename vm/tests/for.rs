@@ -5,3 +5,9 @@ fn for_() {
     let memory = utils::run(include_str!("programs/for.ggb"));
     assert_eq!(&[120], &memory.static_[..1])
 }
+
+#[test]
+fn for_once() {
+    let memory = utils::run(include_str!("programs/for_once.ggb"));
+    assert_eq!(&[7, 7, 7, 7, 7, 7, 7, 7, 7, 0], &memory.static_[..10])
+}
@@ -0,0 +1,10 @@
+mod utils;
+
+#[test]
+fn match_() {
+    let memory = utils::run(include_str!("programs/match.ggb"));
+    assert_eq!(
+        &[1, 2, 3, 3, 4, 0xff, 0, 0xff, 0xff, 5, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1],
+        &memory.static_[..17]
+    )
+}
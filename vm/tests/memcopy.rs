@@ -12,3 +12,11 @@ fn memcopy() {
         &memory.static_[..54]
     )
 }
+
+#[test]
+fn memcopy_aggregate() {
+    let memory = utils::run(include_str!("programs/memcopy_aggregate.ggb"));
+    assert_eq!(&[1, 2, 3, 4, 1, 2, 3, 4], &memory.static_[..8]);
+    assert_eq!(&memory.static_[8..12], &memory.static_[12..16]);
+    assert_eq!(&[1, 2, 3, 4], &memory.static_[16..20]);
+}
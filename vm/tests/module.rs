@@ -0,0 +1,30 @@
+use ir::{byteorder::NativeEndian, parser::ContextBuilder, Ir};
+use vm::{Machine, Opts};
+
+mod utils;
+
+#[test]
+fn module() {
+    let memory = utils::run(include_str!("programs/module.ggb"));
+    assert_eq!(&[3, 4, 1, 2], &memory.static_[..4]);
+}
+
+#[test]
+fn import() {
+    let lib = r#"
+        pub static X:u8
+        const OFFSET:u8 = 1
+        pub fn set(x:u8) { (= X (+ x OFFSET)) }
+        "#;
+    let mut context = ContextBuilder::default()
+        .resolver(|path| match path {
+            "lib.ggb" => Some(lib),
+            _ => None,
+        })
+        .build();
+    let input = "import \"lib.ggb\"\n(lib::set 3)";
+    let ast = ir::parser::parse_with_context(input, &mut context).unwrap();
+    let ir: Ir<NativeEndian> = Ir::new(&ast);
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!(4, memory.static_[0]);
+}
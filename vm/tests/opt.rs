@@ -0,0 +1,219 @@
+use ir::{
+    byteorder::NativeEndian,
+    opt::{
+        allocate_registers, const_fold, copy_propagation, cse, inline, licm, peephole::Peephole,
+        simplify_branches, strength_reduction, OptLevel, Pipeline,
+    },
+    Ir,
+};
+use vm::{Machine, Opts};
+
+// the program runs the same before and after optimizing it.
+fn assert_opt(pass: fn(&mut Ir<NativeEndian>), input: &str) {
+    let ast = ir::parser::parse(input).unwrap();
+    let mut ir: Ir<NativeEndian> = Ir::new(&ast);
+    let expected = Machine::new(&ir, Opts::default()).run();
+    pass(&mut ir);
+    ir.optimize();
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!(expected.static_[..], memory.static_[..]);
+}
+
+fn programs(pass: fn(&mut Ir<NativeEndian>)) {
+    assert_opt(pass, include_str!("programs/array_assign.ggb"));
+    assert_opt(pass, include_str!("programs/assign.ggb"));
+    assert_opt(pass, include_str!("programs/bool.ggb"));
+    assert_opt(pass, include_str!("programs/break.ggb"));
+    assert_opt(pass, include_str!("programs/compare.ggb"));
+    assert_opt(pass, include_str!("programs/const.ggb"));
+    assert_opt(pass, include_str!("programs/defer.ggb"));
+    assert_opt(pass, include_str!("programs/fibonacci.ggb"));
+    assert_opt(pass, include_str!("programs/fixed.ggb"));
+    assert_opt(pass, include_str!("programs/for.ggb"));
+    assert_opt(pass, include_str!("programs/loop.ggb"));
+    assert_opt(pass, include_str!("programs/match.ggb"));
+    assert_opt(pass, include_str!("programs/memcopy.ggb"));
+    assert_opt(pass, include_str!("programs/mul.ggb"));
+    assert_opt(pass, include_str!("programs/overflow.ggb"));
+    assert_opt(pass, include_str!("programs/sort.ggb"));
+    assert_opt(pass, include_str!("programs/string.ggb"));
+    assert_opt(pass, include_str!("programs/struct.ggb"));
+    assert_opt(pass, include_str!("programs/union.ggb"));
+}
+
+// multiplications by literals, in a loop.
+const MUL_LOOP: &str = "
+static K:u8
+static W:u16
+static S:u8
+(= K 3)
+let s:u8 = 0
+for i:u8 in 0..8 {
+    (= s (+ s (* K 10)))
+    (= s (+ s (* i 6)))
+    (= W (+ (* W 5) 300))
+}
+(= S s)
+";
+
+// nested if/else statements.
+const IF_ELSE: &str = "
+static R:[u8 4]
+for i:u8 in 0..4 {
+    if (< i 2) {
+        if (== i 0) {
+            (= ([i]R) 10)
+        } else {
+            (= ([i]R) 11)
+        }
+    } else {
+        if (== i 2) {
+            (= ([i]R) 12)
+        } else {
+            loop {
+                (= ([i]R) 13)
+                break
+            }
+        }
+    }
+}
+";
+
+#[test]
+fn fold() {
+    programs(|ir| {
+        const_fold(ir);
+    });
+}
+
+#[test]
+fn common_subexpressions() {
+    programs(|ir| {
+        cse(ir);
+    });
+}
+
+#[test]
+fn copies() {
+    programs(|ir| {
+        copy_propagation(ir);
+    });
+}
+
+#[test]
+fn inlining() {
+    programs(|ir| {
+        inline(ir, 64);
+    });
+}
+
+#[test]
+fn loop_invariants() {
+    programs(|ir| {
+        licm(ir);
+    });
+    assert_opt(
+        |ir| {
+            strength_reduction(ir);
+            licm(ir);
+        },
+        MUL_LOOP,
+    );
+}
+
+#[test]
+fn strength() {
+    programs(|ir| {
+        strength_reduction(ir);
+    });
+    assert_opt(
+        |ir| {
+            assert!(strength_reduction(ir));
+        },
+        MUL_LOOP,
+    );
+}
+
+#[test]
+fn branches() {
+    programs(|ir| {
+        simplify_branches(ir);
+    });
+    assert_opt(
+        |ir| {
+            assert!(simplify_branches(ir));
+        },
+        IF_ELSE,
+    );
+}
+
+#[test]
+fn registers() {
+    programs(|ir| {
+        allocate_registers(ir, 16);
+    });
+    // spills most of the registers
+    programs(|ir| {
+        allocate_registers(ir, 2);
+    });
+}
+
+#[test]
+fn registers_spilled() {
+    // (+ (+ x x) (+ (+ x x) ... x)) keeps every (+ x x) in a register until the
+    // innermost expression is computed. The compiler recurses for each level,
+    // so it runs with a larger stack.
+    let compile = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let mut expression = String::from("x");
+            for _ in 0..80 {
+                expression = format!("(+ (+ x x) {})", expression);
+            }
+            let input = format!("static R:u8\nlet x:u8 = 1\n(= R {})", expression);
+            let ast = ir::parser::parse(&input).unwrap();
+            Ir::<NativeEndian>::new(&ast)
+        });
+    let mut ir = compile.unwrap().join().unwrap();
+    assert!(allocate_registers(&mut ir, 16));
+    let memory = Machine::new(&ir, Opts::default()).run();
+    assert_eq!(161, memory.static_[0]);
+}
+
+#[test]
+fn peephole() {
+    programs(|ir| {
+        Peephole::standard().run(ir);
+    });
+}
+
+#[test]
+fn all() {
+    programs(|ir| {
+        inline(ir, 64);
+        cse(ir);
+        const_fold(ir);
+        strength_reduction(ir);
+        licm(ir);
+        copy_propagation(ir);
+        simplify_branches(ir);
+        Peephole::standard().run(ir);
+        allocate_registers(ir, 16);
+    });
+}
+
+#[test]
+fn levels() {
+    programs(|ir| {
+        Pipeline::level(OptLevel::O1).run(ir);
+    });
+    programs(|ir| {
+        Pipeline::level(OptLevel::O2).run(ir);
+    });
+    assert_opt(
+        |ir| {
+            Pipeline::level(OptLevel::O2).run(ir);
+        },
+        MUL_LOOP,
+    );
+}
@@ -0,0 +1,45 @@
+use ir::{byteorder::NativeEndian, Ir, Options, Overflow};
+use vm::{Machine, Opts};
+
+fn machine(ir: &Ir<NativeEndian>) -> Machine<'_, NativeEndian> {
+    Machine::new(ir, Opts::default())
+}
+
+fn compile(overflow: Overflow) -> Ir<NativeEndian> {
+    let ast = ir::parser::parse(include_str!("programs/overflow.ggb")).unwrap();
+    let options = Options {
+        overflow,
+        ..Default::default()
+    };
+    Ir::with_options(&ast, options)
+}
+
+#[test]
+fn wrapping() {
+    let ir = compile(Overflow::Wrapping);
+    let memory = machine(&ir).run();
+    assert_eq!(&[44, 66, 144], &memory.static_[..3]);
+    assert_eq!(&0x0010_u16.to_ne_bytes(), &memory.static_[3..5]);
+}
+
+#[test]
+fn saturating() {
+    let ir = compile(Overflow::Saturating);
+    let memory = machine(&ir).run();
+    assert_eq!(&[255, 0, 255], &memory.static_[..3]);
+    assert_eq!(&0xffff_u16.to_ne_bytes(), &memory.static_[3..5]);
+}
+
+#[test]
+fn trapping() {
+    let ir = compile(Overflow::Trapping);
+    let mut machine = machine(&ir);
+    while machine.is_running() {
+        machine.step();
+    }
+    assert!(machine.is_error());
+    assert_eq!(&[0, 0, 0], &machine.memory().static_[..3]);
+    // (= A (+ x 100))
+    let span = machine.span().unwrap();
+    assert_eq!(([8, 0], [8, 15]), (span.min, span.max));
+}
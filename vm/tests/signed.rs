@@ -0,0 +1,15 @@
+mod utils;
+
+#[test]
+fn signed() {
+    let memory = utils::run(include_str!("programs/signed.ggb"));
+    let word =
+        |offset: usize| u16::from_ne_bytes([memory.static_[offset], memory.static_[offset + 1]]);
+    assert_eq!(&[1, 0, 1, 1, 0], &memory.static_[..5]);
+    // -6 / 4, -6 >> 1
+    assert_eq!(-1, memory.static_[5] as i8);
+    assert_eq!(-3, memory.static_[6] as i8);
+    // -1000 / 7, -1000 >> 3
+    assert_eq!(-142, word(7) as i16);
+    assert_eq!(-125, word(9) as i16);
+}
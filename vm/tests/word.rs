@@ -0,0 +1,26 @@
+mod utils;
+
+#[test]
+fn word() {
+    let memory = utils::run(include_str!("programs/word.ggb"));
+    let word =
+        |offset: usize| u16::from_ne_bytes([memory.static_[offset], memory.static_[offset + 1]]);
+    assert_eq!(&[1, 1, 0, 1, 1, 0, 1, 1, 1], &memory.static_[..9]);
+    assert_eq!(900, word(9));
+    assert_eq!(142, word(11));
+    // 1200 >> 4
+    assert_eq!(75, word(13));
+}
+
+#[test]
+fn word_array() {
+    let memory = utils::run(include_str!("programs/word_array.ggb"));
+    let word =
+        |offset: usize| u16::from_ne_bytes([memory.static_[offset], memory.static_[offset + 1]]);
+    assert_eq!(1000, word(0));
+    assert_eq!(300, word(2));
+    assert_eq!(1300, word(4));
+    assert_eq!(0xabcd, word(6));
+    assert_eq!(1300, word(8));
+    assert_eq!(0xabcd, word(10));
+}